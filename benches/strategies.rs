@@ -0,0 +1,248 @@
+//! Statistical benchmarks of each strategy's phases: `cargo bench --bench strategies [filter]`.
+//!
+//! Every phase is warmed up, then sampled repeatedly with its setup (creating or removing
+//! the files) kept out of the timing. Samples are summarised with the median and a
+//! bootstrapped 95% confidence interval of it, the mean and standard deviation, outliers
+//! are classified with Tukey's fences, and each median is compared with the previous
+//! run's, kept in `target/io-bench/last.json`: a change counts only when the two
+//! intervals don't overlap. An HTML report is written to `target/io-bench/report.html`.
+//!
+//! This is criterion's method without criterion: a phase here is one batch of file
+//! operations taking milliseconds, sampled whole with its setup excluded, which is what
+//! `iter_batched` would do, and criterion would bring dozens of crates (plotting,
+//! serialization, its CLI) into a workspace that otherwise builds from rayon, memmap2,
+//! libc and, optionally, tokio alone, also offline.
+//!
+//! Options: `--samples <n>` (default 20), `--files <n>` (default 1000), `--warm-up-ms <n>`
+//! (default 500); any other argument filters benchmarks by substring.
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use ::io::bench::{self, Options, Phase, STRATEGIES, Strategy, Workload};
+use ::io::json::{self, Value};
+
+/// Resamples drawn to bootstrap each median's confidence interval.
+const RESAMPLES: usize = 1000;
+
+struct Config {
+    samples: usize,
+    files: usize,
+    warm_up: Duration,
+    filter: Vec<String>,
+}
+
+fn parse_args() -> Config {
+    let mut config = Config { samples: 20, files: 1000, warm_up: Duration::from_millis(500), filter: Vec::new() };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().and_then(|v| v.parse::<u64>().ok()).unwrap_or_else(|| panic!("{} expects a number", arg));
+        match arg.as_str() {
+            "--bench" => {}
+            "--samples" => config.samples = value().max(2) as usize,
+            "--files" => config.files = value().max(1) as usize,
+            "--warm-up-ms" => config.warm_up = Duration::from_millis(value()),
+            _ => config.filter.push(arg),
+        }
+    }
+    config
+}
+
+struct Summary {
+    id: String,
+    samples: Vec<Duration>,
+    median: f64,
+    /// 95% confidence interval of the median.
+    low: f64,
+    high: f64,
+    mean: f64,
+    std_dev: f64,
+    mild_outliers: usize,
+    severe_outliers: usize,
+}
+
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+/// The 2.5th and 97.5th percentiles of the medians of [`RESAMPLES`] resamples of `sorted`,
+/// drawn with a fixed seed so reruns over the same samples agree.
+fn bootstrap_median(sorted: &[f64]) -> (f64, f64) {
+    let mut state: u64 = 0x5eed;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut medians: Vec<f64> = (0..RESAMPLES)
+        .map(|_| {
+            let mut resample: Vec<f64> = (0..sorted.len()).map(|_| sorted[(next() % sorted.len() as u64) as usize]).collect();
+            resample.sort_by(f64::total_cmp);
+            quantile(&resample, 0.5)
+        })
+        .collect();
+    medians.sort_by(f64::total_cmp);
+    (quantile(&medians, 0.025), quantile(&medians, 0.975))
+}
+
+fn summarise(id: String, samples: Vec<Duration>) -> Summary {
+    let mut ms: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+    ms.sort_by(f64::total_cmp);
+    let (low, high) = bootstrap_median(&ms);
+    let mean = ms.iter().sum::<f64>() / ms.len() as f64;
+    let variance = ms.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (ms.len() - 1) as f64;
+    let (q1, q3) = (quantile(&ms, 0.25), quantile(&ms, 0.75));
+    let iqr = q3 - q1;
+    let outside = |k: f64| ms.iter().filter(|&&x| x < q1 - k * iqr || x > q3 + k * iqr).count();
+    let severe = outside(3.0);
+    Summary {
+        id,
+        samples,
+        median: quantile(&ms, 0.5),
+        low,
+        high,
+        mean,
+        std_dev: variance.sqrt(),
+        mild_outliers: outside(1.5) - severe,
+        severe_outliers: severe,
+    }
+}
+
+/// How a median moved since the previous run, e.g. `-12.3% (improved)`; a change inside
+/// either run's confidence interval is noise.
+fn change(summary: &Summary, last: Option<&Value>) -> String {
+    let previous = |key: &str| last.and_then(|last| last.get(&summary.id)?.get(key)?.as_f64());
+    let (Some(median), Some(low), Some(high)) = (previous("median_ms"), previous("low_ms"), previous("high_ms")) else {
+        return "new".to_string();
+    };
+    let verdict = if summary.low > high {
+        "regressed"
+    } else if summary.high < low {
+        "improved"
+    } else {
+        "no change"
+    };
+    format!("{:+.1}% ({})", (summary.median / median - 1.0) * 100.0, verdict)
+}
+
+/// Each benchmark's median and interval, as kept in `last.json`.
+fn saved(summaries: &[Summary]) -> Vec<(String, Value)> {
+    summaries.iter().map(|s| (s.id.clone(), Value::object().with("median_ms", s.median).with("low_ms", s.low).with("high_ms", s.high))).collect()
+}
+
+/// Puts `strategy`'s files in the state `phase` expects: absent for create, present otherwise.
+fn prepare(strategy: &Strategy, phase: Phase, paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let exists = strategy.stored_paths(paths).first().is_some_and(|path| path.exists());
+    match (phase, exists) {
+        (Phase::Create, true) => strategy.run_phase(Phase::Delete, paths, options),
+        (Phase::Read | Phase::Update | Phase::Delete, false) => strategy.run_phase(Phase::Create, paths, options),
+        _ => Ok(()),
+    }
+}
+
+fn bench_phase(strategy: &Strategy, phase: Phase, paths: &[PathBuf], options: &Options, config: &Config) -> io::Result<Vec<Duration>> {
+    let sample = || -> io::Result<Duration> {
+        prepare(strategy, phase, paths, options)?;
+        let start = Instant::now();
+        strategy.run_phase(phase, paths, options)?;
+        Ok(start.elapsed())
+    };
+    let warm_up = Instant::now();
+    while warm_up.elapsed() < config.warm_up {
+        sample()?;
+    }
+    (0..config.samples).map(|_| sample()).collect()
+}
+
+fn write_report(path: &Path, summaries: &[Summary], changes: &[String], config: &Config) -> io::Result<()> {
+    let slowest = summaries.iter().map(|s| s.median).fold(0.0, f64::max);
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>io strategy benchmarks</title>\n<style>\
+         body{{font-family:sans-serif}}td,th{{padding:2px 8px;text-align:right}}td:first-child{{text-align:left}}\
+         .bar{{background:#4a7ebb;height:12px}}</style></head><body>\n\
+         <h1>io strategy benchmarks</h1>\n<p>{} files of {} bytes, {} samples per benchmark.</p>\n\
+         <table><tr><th>Benchmark</th><th>Median (ms)</th><th>95% CI (ms)</th><th>Change</th><th>Mean (ms)</th><th>Std dev (ms)</th><th>Outliers</th><th>Samples (ms)</th><th></th></tr>\n",
+        config.files,
+        Workload::default().size(),
+        config.samples
+    );
+    for (s, change) in summaries.iter().zip(changes) {
+        let samples: Vec<String> = s.samples.iter().map(|d| format!("{:.2}", d.as_secs_f64() * 1000.0)).collect();
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{:.3}</td><td>{:.3}–{:.3}</td><td>{}</td><td>{:.3}</td><td>{:.3}</td><td>{} mild, {} severe</td><td title=\"{}\">{}</td>\
+             <td style=\"width:300px\"><div class=\"bar\" style=\"width:{:.1}%\"></div></td></tr>",
+            s.id,
+            s.median,
+            s.low,
+            s.high,
+            change,
+            s.mean,
+            s.std_dev,
+            s.mild_outliers,
+            s.severe_outliers,
+            samples.join(" "),
+            samples.len(),
+            if slowest > 0.0 { s.median / slowest * 100.0 } else { 0.0 }
+        );
+    }
+    html.push_str("</table></body></html>\n");
+    fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+    fs::write(path, html)
+}
+
+fn main() -> io::Result<()> {
+    let config = parse_args();
+    let options = Options { workload: Workload::new(config.files, Workload::default().size()), ..Options::default() };
+    let dir_path = bench::get_dir();
+    fs::create_dir_all(&dir_path)?;
+    let paths = bench::file_paths(&dir_path, config.files);
+
+    let target = env::var_os("CARGO_TARGET_DIR").map_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("target"), PathBuf::from);
+    let last_path = target.join("io-bench").join("last.json");
+    let last = fs::read_to_string(&last_path).ok().and_then(|text| json::parse(&text).ok());
+    let mut summaries = Vec::new();
+    let mut changes = Vec::new();
+    for strategy in STRATEGIES {
+        for phase in Phase::ALL {
+            let name = strategy.name.split(' ').next().unwrap_or(strategy.name);
+            let id = format!("{}/{}", name, phase.name());
+            if !config.filter.is_empty() && !config.filter.iter().any(|f| id.contains(f.as_str())) {
+                continue;
+            }
+            let samples = bench_phase(strategy, phase, &paths, &options, &config)?;
+            let s = summarise(id, samples);
+            let change = change(&s, last.as_ref());
+            println!(
+                "{:<24} median {:>9.3} ms [{:.3} {:.3}]  {}  mean {:>9.3} ms ± {:.3}  outliers: {} mild, {} severe",
+                s.id, s.median, s.low, s.high, change, s.mean, s.std_dev, s.mild_outliers, s.severe_outliers
+            );
+            summaries.push(s);
+            changes.push(change);
+        }
+    }
+    fs::remove_dir_all(&dir_path)?;
+
+    let report = target.join("io-bench").join("report.html");
+    write_report(&report, &summaries, &changes, &config)?;
+    // Keep earlier results for benchmarks this run filtered out.
+    let mut kept = match last {
+        Some(Value::Object(pairs)) => pairs,
+        _ => Vec::new(),
+    };
+    let now = saved(&summaries);
+    kept.retain(|(id, _)| !now.iter().any(|(new, _)| new == id));
+    kept.extend(now);
+    fs::write(&last_path, Value::Object(kept).to_string())?;
+    println!("\nWrote {}", report.display());
+    Ok(())
+}
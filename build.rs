@@ -0,0 +1,25 @@
+//! Records the commit the crate is built from as `IO_GIT_COMMIT`, for the metadata `io
+//! bench` saves with its results. Builds outside a git checkout, such as from crates.io,
+//! leave it unset.
+
+use std::fs;
+use std::process::Command;
+
+fn main() {
+    // HEAD names the branch; the branch's ref moves with each commit. Without a checkout,
+    // once is enough.
+    println!("cargo:rerun-if-changed=build.rs");
+    if fs::exists(".git/HEAD").unwrap_or(false) {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+    }
+    if let Some(branch) = fs::read_to_string(".git/HEAD").ok().and_then(|head| head.strip_prefix("ref: ").map(|r| format!(".git/{}", r.trim())))
+        && fs::exists(&branch).unwrap_or(false)
+    {
+        println!("cargo:rerun-if-changed={}", branch);
+    }
+    if let Ok(output) = Command::new("git").args(["rev-parse", "HEAD"]).output()
+        && output.status.success()
+    {
+        println!("cargo:rustc-env=IO_GIT_COMMIT={}", String::from_utf8_lossy(&output.stdout).trim());
+    }
+}
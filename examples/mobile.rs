@@ -0,0 +1,51 @@
+//! The strategies on a phone's app storage: `mobile [DIR] [FILES]` runs every strategy's
+//! phases on `FILES` files (default 1000) in `DIR`, by default the app's cache directory
+//! where there is one (see `io::mobile`) and the temp directory otherwise, and prints each
+//! phase's time.
+//!
+//! On Android, build it with the NDK's linker configured for the target, push it and run it
+//! as the app whose storage is being measured:
+//!
+//! ```text
+//! cargo build --release --example mobile --target aarch64-linux-android
+//! adb push target/aarch64-linux-android/release/examples/mobile /data/local/tmp/
+//! adb shell run-as com.example.app /data/local/tmp/mobile
+//! ```
+//!
+//! iOS runs no command-line programs, so there an app links the crate and runs the loop in
+//! `run` below from its own Rust code, with `io::mobile::cache_dir()` as the directory.
+
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use ::io::bench::{self, Options, STRATEGIES, Workload};
+use ::io::human::{self, Table};
+use ::io::mobile;
+use ::io::rundir::{self, RunDir};
+
+/// Runs every strategy in a run directory inside `parent` and tabulates the phases.
+fn run(parent: &Path, files: usize) -> io::Result<String> {
+    let options = Options { workload: Workload::new(files, Workload::default().size()), ..Options::default() };
+    let dir = RunDir::create(rundir::run_dir(parent))?;
+    let mut table = Table::new(["Strategy", "create ms", "read ms", "update ms", "delete ms"]);
+    for strategy in STRATEGIES {
+        let times = bench::run_strategy(strategy, &dir, &options)?.times;
+        let ms = |phase: Duration| human::decimal(phase.as_secs_f64() * 1000.0, 2);
+        table.row([strategy.label.to_string(), ms(times.create), ms(times.read), ms(times.update), ms(times.delete)]);
+    }
+    Ok(table.render())
+}
+
+fn main() -> io::Result<()> {
+    let mut args = env::args().skip(1);
+    let parent = args.next().map(PathBuf::from).or_else(mobile::cache_dir).unwrap_or_else(mobile::temp_dir);
+    let files = match args.next() {
+        Some(files) => files.parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid file count {}: {}", files, e)))?,
+        None => 1000,
+    };
+    println!("{} files of {} in {}", human::thousands(files as u64), human::bytes(Workload::default().size() as u64), parent.display());
+    print!("{}", run(&parent, files)?);
+    Ok(())
+}
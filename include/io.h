@@ -0,0 +1,47 @@
+/*
+ * C interface to the io crate's bulk file operations, built with the `capi` feature:
+ *
+ *     cargo rustc --lib --release --no-default-features --features capi,rayon --crate-type cdylib
+ *
+ * Every function returning int returns 0 on success and -1 on failure, when
+ * io_last_error() describes the failure on the calling thread. Paths are NUL-terminated;
+ * on Unix they may hold any bytes, elsewhere they must be UTF-8. Each file is retried on
+ * transient errors, and a batch stops at the first file that still fails.
+ */
+#ifndef IO_H
+#define IO_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* File contents handed out by io_read_many; free them with io_free_buffers. */
+typedef struct IoBuffer {
+    uint8_t *data;
+    size_t len;
+} IoBuffer;
+
+/* Writes count files, paths[i] getting the lens[i] bytes at contents[i], creating or
+ * truncating each. */
+int io_write_many(const char *const *paths, const uint8_t *const *contents, const size_t *lens, size_t count);
+
+/* Reads count files into out[0..count]. On failure out is left untouched. */
+int io_read_many(const char *const *paths, size_t count, IoBuffer *out);
+
+/* Frees count buffers filled by io_read_many, setting each to empty. */
+void io_free_buffers(IoBuffer *buffers, size_t count);
+
+/* Deletes count files; if one fails, others may already be gone. */
+int io_delete_many(const char *const *paths, size_t count);
+
+/* The last failure on this thread, or NULL if none. Valid until the next failure. */
+const char *io_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
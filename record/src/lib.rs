@@ -0,0 +1,227 @@
+//! The `LD_PRELOAD` shim behind `io record`: it wraps libc's file calls in the recorded
+//! program and everything it starts, and appends every call that succeeded to the existing
+//! file named by `IO_RECORD_LOG` as a line of `strace -f -ttt` output, so `io::oplog` reads the
+//! log like any other trace. Without the variable every call passes straight through.
+//!
+//! Programs that make system calls without libc (static musl or Go binaries) aren't seen,
+//! nor are the files libc opens for itself, as `fopen` does, or data moved in the kernel by
+//! `copy_file_range` or `sendfile`. `open` and its relatives are
+//! variadic in C; they are declared here with the mode as a plain argument, which the
+//! x86-64 and AArch64 Linux calling conventions pass the same way.
+
+// Every export stands in for the libc function of the same name, under its contract.
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::CStr;
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+use libc::{c_char, c_int, c_uint, c_void, iovec, mode_t, off_t, size_t, ssize_t};
+
+/// Names the log file.
+const LOG_VARIABLE: &CStr = c"IO_RECORD_LOG";
+/// [`LOG`] before the process looked for its log; -1 once it found there is none.
+const UNOPENED: c_int = -2;
+/// The most one line of the log holds; longer calls aren't recorded.
+const LINE: usize = 4096;
+
+static LOG: AtomicI32 = AtomicI32::new(UNOPENED);
+
+/// The next definition of a libc function after this library's, looked up once; used
+/// inside `unsafe`.
+macro_rules! real {
+    ($name:ident: $ty:ty) => {{
+        static ADDRESS: AtomicUsize = AtomicUsize::new(0);
+        let mut address = ADDRESS.load(Ordering::Relaxed);
+        if address == 0 {
+            address = libc::dlsym(libc::RTLD_NEXT, concat!(stringify!($name), "\0").as_ptr().cast()) as usize;
+            ADDRESS.store(address, Ordering::Relaxed);
+        }
+        // A program only calls what its libc defines, so the lookup finds it.
+        std::mem::transmute::<usize, $ty>(address)
+    }};
+}
+
+type Open = unsafe extern "C" fn(*const c_char, c_int, mode_t) -> c_int;
+type Close = unsafe extern "C" fn(c_int) -> c_int;
+type WriteFn = unsafe extern "C" fn(c_int, *const c_void, size_t) -> ssize_t;
+
+/// The log's descriptor, opened on first use in each process. The descriptor is inherited
+/// over `fork` and reopened after `exec`; every line is appended with one `write`, so
+/// processes sharing the file don't split each other's lines.
+fn log() -> Option<c_int> {
+    let fd = LOG.load(Ordering::Acquire);
+    if fd != UNOPENED {
+        return (fd >= 0).then_some(fd);
+    }
+    let path = unsafe { libc::getenv(LOG_VARIABLE.as_ptr()) };
+    let fd = match path.is_null() {
+        true => -1,
+        // `io record` made the log; never create one, or follow a link in its place.
+        false => unsafe { real!(open: Open)(path, libc::O_WRONLY | libc::O_APPEND | libc::O_NOFOLLOW | libc::O_CLOEXEC, 0) },
+    };
+    match LOG.compare_exchange(UNOPENED, fd, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => (fd >= 0).then_some(fd),
+        Err(opened) => {
+            if fd >= 0 {
+                unsafe { real!(close: Close)(fd) };
+            }
+            (opened >= 0).then_some(opened)
+        }
+    }
+}
+
+fn now() -> libc::timespec {
+    let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut time) };
+    time
+}
+
+/// One line of the log, built without allocating.
+struct Line {
+    bytes: [u8; LINE],
+    len: usize,
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > LINE {
+            return Err(fmt::Error);
+        }
+        self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Appends a call that started at `start` and returned `result`, unless it failed.
+fn record(start: libc::timespec, result: i64, call: fmt::Arguments) {
+    if result < 0 {
+        return;
+    }
+    let Some(log) = log() else {
+        return;
+    };
+    let mut line = Line { bytes: [0; LINE], len: 0 };
+    let pid = unsafe { libc::getpid() };
+    if writeln!(line, "{} {}.{:06} {} = {}", pid, start.tv_sec, start.tv_nsec / 1000, call, result).is_ok() {
+        unsafe { real!(write: WriteFn)(log, line.bytes.as_ptr().cast(), line.len) };
+    }
+}
+
+/// A path argument, quoted and escaped as strace prints it.
+struct Quoted(*const c_char);
+
+impl fmt::Display for Quoted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_null() {
+            return f.write_str("NULL");
+        }
+        // The caller passed libc a string, so it is one.
+        let path = unsafe { CStr::from_ptr(self.0) };
+        f.write_char('"')?;
+        for &b in path.to_bytes() {
+            match b {
+                b'"' | b'\\' => write!(f, "\\{}", b as char)?,
+                0x20..=0x7e => f.write_char(b as char)?,
+                _ => write!(f, "\\x{:02x}", b)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+/// A directory descriptor argument.
+struct Dir(c_int);
+
+impl fmt::Display for Dir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            libc::AT_FDCWD => f.write_str("AT_FDCWD"),
+            fd => write!(f, "{}", fd),
+        }
+    }
+}
+
+/// `open` flags, as far as a trace cares about them.
+struct Flags(c_int);
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.0 & libc::O_ACCMODE {
+            libc::O_WRONLY => "O_WRONLY",
+            libc::O_RDWR => "O_RDWR",
+            _ => "O_RDONLY",
+        })?;
+        for (flag, name) in [(libc::O_CREAT, "O_CREAT"), (libc::O_TRUNC, "O_TRUNC"), (libc::O_APPEND, "O_APPEND"), (libc::O_DIRECTORY, "O_DIRECTORY")] {
+            if self.0 & flag != 0 {
+                write!(f, "|{}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Exports `$name`, which calls libc's and records the call as `format_args!($format)`.
+macro_rules! wrap {
+    ($name:ident($($arg:ident: $ty:ty),*) -> $ret:ty, $($format:tt)*) => {
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn $name($($arg: $ty),*) -> $ret {
+            let start = now();
+            let result = unsafe { real!($name: unsafe extern "C" fn($($ty),*) -> $ret)($($arg),*) };
+            record(start, result as i64, format_args!($($format)*));
+            result
+        }
+    };
+}
+
+wrap!(open(path: *const c_char, flags: c_int, mode: mode_t) -> c_int, "open({}, {})", Quoted(path), Flags(flags));
+wrap!(open64(path: *const c_char, flags: c_int, mode: mode_t) -> c_int, "open({}, {})", Quoted(path), Flags(flags));
+wrap!(openat(dir: c_int, path: *const c_char, flags: c_int, mode: mode_t) -> c_int, "openat({}, {}, {})", Dir(dir), Quoted(path), Flags(flags));
+wrap!(openat64(dir: c_int, path: *const c_char, flags: c_int, mode: mode_t) -> c_int, "openat({}, {}, {})", Dir(dir), Quoted(path), Flags(flags));
+wrap!(creat(path: *const c_char, mode: mode_t) -> c_int, "creat({}, {:o})", Quoted(path), mode);
+wrap!(creat64(path: *const c_char, mode: mode_t) -> c_int, "creat({}, {:o})", Quoted(path), mode);
+
+wrap!(read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t, "read({})", fd);
+wrap!(pread(fd: c_int, buf: *mut c_void, count: size_t, offset: off_t) -> ssize_t, "pread64({})", fd);
+wrap!(pread64(fd: c_int, buf: *mut c_void, count: size_t, offset: off_t) -> ssize_t, "pread64({})", fd);
+wrap!(readv(fd: c_int, iov: *const iovec, count: c_int) -> ssize_t, "readv({})", fd);
+wrap!(write(fd: c_int, buf: *const c_void, count: size_t) -> ssize_t, "write({})", fd);
+wrap!(pwrite(fd: c_int, buf: *const c_void, count: size_t, offset: off_t) -> ssize_t, "pwrite64({})", fd);
+wrap!(pwrite64(fd: c_int, buf: *const c_void, count: size_t, offset: off_t) -> ssize_t, "pwrite64({})", fd);
+wrap!(writev(fd: c_int, iov: *const iovec, count: c_int) -> ssize_t, "writev({})", fd);
+
+/// Closes `fd`, except the log's: programs that close every descriptor they didn't open
+/// would otherwise end the recording.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn close(fd: c_int) -> c_int {
+    if fd >= 0 && LOG.load(Ordering::Acquire) == fd {
+        return 0;
+    }
+    let start = now();
+    let result = unsafe { real!(close: Close)(fd) };
+    record(start, result as i64, format_args!("close({})", fd));
+    result
+}
+
+wrap!(dup(fd: c_int) -> c_int, "dup({})", fd);
+wrap!(dup2(fd: c_int, to: c_int) -> c_int, "dup2({}, {})", fd, to);
+wrap!(dup3(fd: c_int, to: c_int, flags: c_int) -> c_int, "dup3({}, {}, {})", fd, to, flags);
+
+wrap!(rename(from: *const c_char, to: *const c_char) -> c_int, "rename({}, {})", Quoted(from), Quoted(to));
+wrap!(renameat(from_dir: c_int, from: *const c_char, to_dir: c_int, to: *const c_char) -> c_int, "renameat({}, {}, {}, {})", Dir(from_dir), Quoted(from), Dir(to_dir), Quoted(to));
+wrap!(renameat2(from_dir: c_int, from: *const c_char, to_dir: c_int, to: *const c_char, flags: c_uint) -> c_int, "renameat2({}, {}, {}, {}, {})", Dir(from_dir), Quoted(from), Dir(to_dir), Quoted(to), flags);
+wrap!(unlink(path: *const c_char) -> c_int, "unlink({})", Quoted(path));
+wrap!(unlinkat(dir: c_int, path: *const c_char, flags: c_int) -> c_int, "unlinkat({}, {}, {})", Dir(dir), Quoted(path), if flags & libc::AT_REMOVEDIR != 0 { "AT_REMOVEDIR" } else { "0" });
+wrap!(mkdir(path: *const c_char, mode: mode_t) -> c_int, "mkdir({}, {:o})", Quoted(path), mode);
+wrap!(mkdirat(dir: c_int, path: *const c_char, mode: mode_t) -> c_int, "mkdirat({}, {}, {:o})", Dir(dir), Quoted(path), mode);
+wrap!(rmdir(path: *const c_char) -> c_int, "rmdir({})", Quoted(path));
+
+wrap!(stat(path: *const c_char, buf: *mut c_void) -> c_int, "stat({})", Quoted(path));
+wrap!(stat64(path: *const c_char, buf: *mut c_void) -> c_int, "stat({})", Quoted(path));
+wrap!(lstat(path: *const c_char, buf: *mut c_void) -> c_int, "lstat({})", Quoted(path));
+wrap!(lstat64(path: *const c_char, buf: *mut c_void) -> c_int, "lstat({})", Quoted(path));
+wrap!(fstatat(dir: c_int, path: *const c_char, buf: *mut c_void, flags: c_int) -> c_int, "newfstatat({}, {})", Dir(dir), Quoted(path));
+wrap!(fstatat64(dir: c_int, path: *const c_char, buf: *mut c_void, flags: c_int) -> c_int, "newfstatat({}, {})", Dir(dir), Quoted(path));
+wrap!(statx(dir: c_int, path: *const c_char, flags: c_int, mask: c_uint, buf: *mut c_void) -> c_int, "statx({}, {})", Dir(dir), Quoted(path));
@@ -0,0 +1,168 @@
+//! Spotting what an average hides: a phase whose mean looks ordinary can be one iteration
+//! ten times slower than the rest, or every file fast except a cluster stuck behind
+//! writeback. [`examine`] looks at a phase's samples, per-file times or one time per
+//! iteration, and reports such [`Finding`]s, so the report can say so next to the number.
+//!
+//! Two groups are found by splitting the sorted logarithms of the samples where the groups
+//! are furthest apart for their spread (Otsu's method). A single lognormal cloud of times
+//! always splits somewhere, but the split explains under two thirds of its variance;
+//! [`SEPARATION`] asks for far more before calling the samples bimodal.
+
+use std::fmt;
+
+use crate::human;
+use crate::json::Value;
+use crate::sketch::Running;
+
+/// The share of the variance of the log samples a split into two groups must explain for
+/// them to count as bimodal.
+pub const SEPARATION: f64 = 0.8;
+
+/// What counts as anomalous for one kind of sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Criteria {
+    /// Samples at least this many times the median are outliers.
+    pub outlier_ratio: f64,
+    /// Two groups whose medians are at least this many times apart make the samples bimodal.
+    pub mode_ratio: f64,
+    /// The smallest share of the samples, and never fewer than two, the smaller group of a
+    /// bimodal split needs; fewer slow samples are outliers instead.
+    pub min_mode_share: f64,
+    /// Standard deviation over mean above which the samples are too spread out to average;
+    /// `None` where a wide spread is normal.
+    pub max_spread: Option<f64>,
+    /// Fewer samples than this say nothing either way.
+    pub min_samples: usize,
+}
+
+impl Criteria {
+    /// The files of one phase, whose times spread widely anyway; a second cluster or a
+    /// handful far out is what stands out.
+    pub const FILES: Criteria = Criteria { outlier_ratio: 50.0, mode_ratio: 4.0, min_mode_share: 0.02, max_spread: None, min_samples: 50 };
+    /// One phase's time in each of several iterations, which should agree closely.
+    pub const ITERATIONS: Criteria = Criteria { outlier_ratio: 2.0, mode_ratio: 1.5, min_mode_share: 0.2, max_spread: Some(0.25), min_samples: 3 };
+}
+
+/// Something about a phase's samples that its average doesn't show.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    /// `count` of `of` samples are at least [`Criteria::outlier_ratio`] times the median,
+    /// the slowest `worst` times it.
+    Outliers { count: usize, of: usize, worst: f64 },
+    /// The samples form a fast and a slow group, `slow_share` of them in the slow one,
+    /// whose median is `ratio` times the fast one's.
+    Bimodal { slow_share: f64, ratio: f64 },
+    /// The standard deviation is `cv` times the mean.
+    Spread { cv: f64 },
+}
+
+impl Finding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Finding::Outliers { .. } => "outliers",
+            Finding::Bimodal { .. } => "bimodal",
+            Finding::Spread { .. } => "spread",
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let value = Value::object().with("kind", self.name());
+        match *self {
+            Finding::Outliers { count, of, worst } => value.with("count", count).with("of", of).with("worst_ratio", worst),
+            Finding::Bimodal { slow_share, ratio } => value.with("slow_share", slow_share).with("ratio", ratio),
+            Finding::Spread { cv } => value.with("cv", cv),
+        }
+    }
+
+    pub fn from_json(value: &Value) -> Option<Finding> {
+        let number = |key: &str| value.get(key)?.as_f64();
+        match value.get("kind")?.as_str()? {
+            "outliers" => Some(Finding::Outliers { count: value.get("count")?.as_u64()? as usize, of: value.get("of")?.as_u64()? as usize, worst: number("worst_ratio")? }),
+            "bimodal" => Some(Finding::Bimodal { slow_share: number("slow_share")?, ratio: number("ratio")? }),
+            "spread" => Some(Finding::Spread { cv: number("cv")? }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Finding::Outliers { count, of, worst } => {
+                write!(f, "{} of {} far above the median, the slowest {}x it", human::thousands(count as u64), human::thousands(of as u64), human::decimal(worst, 1))
+            }
+            Finding::Bimodal { slow_share, ratio } => write!(f, "bimodal, {}% in a cluster {}x slower than the rest", human::decimal(slow_share * 100.0, 1), human::decimal(ratio, 1)),
+            Finding::Spread { cv } => write!(f, "unstable, standard deviation {}% of the mean", human::decimal(cv * 100.0, 0)),
+        }
+    }
+}
+
+/// The median of sorted `values`.
+fn median(sorted: &[f64]) -> f64 {
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2.0 } else { sorted[mid] }
+}
+
+/// The bimodal split of sorted positive `sorted`, if there is one.
+fn bimodal(sorted: &[f64], criteria: &Criteria) -> Option<Finding> {
+    let logs: Vec<f64> = sorted.iter().filter(|&&value| value > 0.0).map(|value| value.ln()).collect();
+    let n = logs.len();
+    let smallest = ((criteria.min_mode_share * n as f64).ceil() as usize).max(2);
+    if n < 2 * smallest {
+        return None;
+    }
+    let mean = logs.iter().sum::<f64>() / n as f64;
+    let variance = logs.iter().map(|log| (log - mean).powi(2)).sum::<f64>() / n as f64;
+    if variance <= 0.0 {
+        return None;
+    }
+    // Between-group variance of splitting before each index, from a running prefix sum.
+    let mut below = 0.0;
+    let mut best: Option<(usize, f64)> = None;
+    for (split, log) in logs.iter().enumerate().skip(1) {
+        below += logs[split - 1];
+        if split < smallest || n - split < smallest {
+            continue;
+        }
+        let (low, high) = (below / split as f64, (mean * n as f64 - below) / (n - split) as f64);
+        let between = split as f64 * (n - split) as f64 / (n * n) as f64 * (high - low).powi(2);
+        // Ties on one value can't be split between the groups.
+        if *log > logs[split - 1] && best.is_none_or(|(_, most)| between > most) {
+            best = Some((split, between));
+        }
+    }
+    let (split, between) = best?;
+    let ratio = (median(&logs[split..]) - median(&logs[..split])).exp();
+    (between / variance >= SEPARATION && ratio >= criteria.mode_ratio).then(|| Finding::Bimodal { slow_share: (n - split) as f64 / n as f64, ratio })
+}
+
+/// What stands out in `samples` by `criteria`: a bimodal split, or else outliers above the
+/// median, and, where [`Criteria::max_spread`] is set and nothing else was found, too wide
+/// a spread. Non-finite samples are ignored; too few samples find nothing.
+pub fn examine(samples: &[f64], criteria: &Criteria) -> Vec<Finding> {
+    let mut sorted: Vec<f64> = samples.iter().copied().filter(|value| value.is_finite()).collect();
+    if sorted.len() < criteria.min_samples.max(1) {
+        return Vec::new();
+    }
+    sorted.sort_by(f64::total_cmp);
+    let mut findings = Vec::new();
+    let median = median(&sorted);
+    if let Some(finding) = bimodal(&sorted, criteria) {
+        findings.push(finding);
+    } else if median > 0.0 {
+        let count = sorted.iter().filter(|&&value| value >= median * criteria.outlier_ratio).count();
+        if count > 0 {
+            findings.push(Finding::Outliers { count, of: sorted.len(), worst: sorted[sorted.len() - 1] / median });
+        }
+    }
+    if let Some(max) = criteria.max_spread
+        && findings.is_empty()
+    {
+        let mut running = Running::default();
+        sorted.iter().for_each(|&value| running.add(value));
+        if running.mean() > 0.0 && running.stddev() / running.mean() > max {
+            findings.push(Finding::Spread { cv: running.stddev() / running.mean() });
+        }
+    }
+    findings
+}
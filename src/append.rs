@@ -0,0 +1,217 @@
+//! An append-heavy workload, the way log writers use files: many threads appending small
+//! records to a few files, where the other benchmarks write each file whole.
+//!
+//! Three ways to share the logs are compared: every thread writing each record straight to
+//! a file opened with `O_APPEND`, which the kernel keeps whole; a mutex around one buffered
+//! writer per log; and a file per thread per log, buffered and never shared. Afterwards
+//! every log is read back and its records checked for tears (a record with bytes from
+//! another), losses, and records from one thread landing out of order.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::bench::Options;
+
+/// A record's thread and sequence number, and the newline that ends it.
+pub const MIN_RECORD: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendMode {
+    /// One unbuffered `write` per record to a file shared with `O_APPEND`.
+    OAppend,
+    /// A buffered writer per log, shared behind a mutex.
+    Locked,
+    /// A buffered file per thread per log.
+    PerThread,
+}
+
+impl AppendMode {
+    pub const ALL: [AppendMode; 3] = [AppendMode::OAppend, AppendMode::Locked, AppendMode::PerThread];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AppendMode::OAppend => "o_append",
+            AppendMode::Locked => "locked",
+            AppendMode::PerThread => "per-thread",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AppendConfig {
+    /// The number of log files.
+    pub logs: usize,
+    /// Records written in all, spread over the threads.
+    pub records: usize,
+    /// Bytes per record, at least [`MIN_RECORD`].
+    pub record_size: usize,
+}
+
+/// What reading the logs back found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Interleaving {
+    /// Records intact.
+    pub records: usize,
+    /// Records whose bytes don't belong together.
+    pub torn: usize,
+    /// Records written but not found intact.
+    pub missing: usize,
+    /// Records found before one their thread wrote earlier to the same file.
+    pub out_of_order: usize,
+}
+
+impl Interleaving {
+    pub fn is_clean(&self) -> bool {
+        self.torn == 0 && self.missing == 0 && self.out_of_order == 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModeResult {
+    pub mode: AppendMode,
+    pub elapsed: Duration,
+    pub check: Interleaving,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AppendResult {
+    /// The threads appending.
+    pub threads: usize,
+    pub modes: Vec<ModeResult>,
+}
+
+/// The filler byte at `index` of record `sequence` from `thread`.
+fn filler(thread: u32, sequence: u64, index: usize) -> u8 {
+    (thread as u64 * 131 + sequence * 31 + index as u64) as u8 | 0x80
+}
+
+/// Record `sequence` of `thread`, `size` bytes: both numbers, filler that depends on them,
+/// and a newline.
+pub fn encode_record(thread: u32, sequence: u64, size: usize, buf: &mut Vec<u8>) {
+    buf.clear();
+    buf.extend_from_slice(&thread.to_le_bytes());
+    buf.extend_from_slice(&sequence.to_le_bytes());
+    buf.extend((12..size - 1).map(|index| filler(thread, sequence, index)));
+    buf.push(b'\n');
+}
+
+/// The thread and sequence number of `record`, unless it is torn.
+pub fn decode_record(record: &[u8]) -> Option<(u32, u64)> {
+    if record.len() < MIN_RECORD {
+        return None;
+    }
+    let thread = u32::from_le_bytes(record[..4].try_into().unwrap());
+    let sequence = u64::from_le_bytes(record[4..12].try_into().unwrap());
+    let filled = record[12..record.len() - 1].iter().enumerate().all(|(i, &byte)| byte == filler(thread, sequence, 12 + i));
+    (filled && record.last() == Some(&b'\n')).then_some((thread, sequence))
+}
+
+/// Checks the records in `files`, of `record_size` bytes each, against the `expected` that
+/// were written. Each thread's records must appear in each file in the order it wrote them.
+pub fn check(files: &[PathBuf], record_size: usize, expected: usize) -> io::Result<Interleaving> {
+    let mut check = Interleaving::default();
+    for path in files {
+        let bytes = fs::read(path)?;
+        let mut last: Vec<Option<u64>> = Vec::new();
+        for record in bytes.chunks(record_size) {
+            let Some((thread, sequence)) = decode_record(record).filter(|_| record.len() == record_size) else {
+                check.torn += 1;
+                continue;
+            };
+            let thread = thread as usize;
+            if last.len() <= thread {
+                last.resize(thread + 1, None);
+            }
+            if last[thread].is_some_and(|previous| previous >= sequence) {
+                check.out_of_order += 1;
+            }
+            last[thread] = Some(sequence);
+            check.records += 1;
+        }
+    }
+    check.missing = expected.saturating_sub(check.records);
+    Ok(check)
+}
+
+/// The records `thread` of `threads` writes out of `records`.
+fn share(records: usize, threads: usize, thread: usize) -> usize {
+    records / threads + usize::from(thread < records % threads)
+}
+
+/// Appends a record to the log numbered by the first argument.
+type AppendTo<'a> = dyn FnMut(usize, &[u8]) -> io::Result<()> + 'a;
+
+fn open_log(path: &Path, append: bool) -> io::Result<File> {
+    OpenOptions::new().create(true).truncate(!append).write(!append).append(append).open(path)
+}
+
+/// Runs `writer(thread)` on every thread of the current rayon pool at once.
+fn on_every_thread(threads: usize, writer: impl Fn(usize) -> io::Result<()> + Sync + Send) -> io::Result<()> {
+    (0..threads).into_par_iter().try_for_each(writer)
+}
+
+/// Times appending `config.records` records to `config.logs` logs in `dir_path` from every
+/// thread of the current rayon pool, in each of `modes`, and checks the logs each leaves.
+/// Thread `t` sends its `n`th record to log `(t + n) % logs`, so every log sees every
+/// thread. Each mode's logs are removed before the next.
+pub fn run(dir_path: &Path, config: AppendConfig, modes: &[AppendMode], options: &Options) -> io::Result<AppendResult> {
+    if config.record_size < MIN_RECORD || config.logs == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("appending needs at least one log and records of at least {} bytes", MIN_RECORD)));
+    }
+    let threads = rayon::current_num_threads();
+    let mut result = AppendResult { threads, ..AppendResult::default() };
+    let log_path = |dir: &Path, log: usize| dir.join(format!("log_{}", log));
+    for &mode in modes {
+        let dir = dir_path.join(mode.name());
+        fs::create_dir_all(&dir)?;
+        options.progress.set_stage(format!("Append {}", mode.name()));
+        options.op_scope.set(format!("append/{}", mode.name()));
+        options.progress.begin(threads);
+        let records = |thread: usize, write: &mut AppendTo| -> io::Result<()> {
+            let mut buf = Vec::with_capacity(config.record_size);
+            for sequence in 0..share(config.records, threads, thread) {
+                encode_record(thread as u32, sequence as u64, config.record_size, &mut buf);
+                write((thread + sequence) % config.logs, &buf)?;
+            }
+            options.progress.advance();
+            Ok(())
+        };
+
+        let start = Instant::now();
+        let files: Vec<PathBuf> = match mode {
+            AppendMode::OAppend => {
+                let logs = (0..config.logs).map(|log| open_log(&log_path(&dir, log), true)).collect::<io::Result<Vec<_>>>()?;
+                on_every_thread(threads, |thread| records(thread, &mut |log, record| (&logs[log]).write_all(record)))?;
+                (0..config.logs).map(|log| log_path(&dir, log)).collect()
+            }
+            AppendMode::Locked => {
+                let logs = (0..config.logs).map(|log| Ok(Mutex::new(BufWriter::new(open_log(&log_path(&dir, log), false)?)))).collect::<io::Result<Vec<_>>>()?;
+                on_every_thread(threads, |thread| records(thread, &mut |log, record| logs[log].lock().unwrap_or_else(|e| e.into_inner()).write_all(record)))?;
+                for log in logs {
+                    log.into_inner().unwrap_or_else(|e| e.into_inner()).flush()?;
+                }
+                (0..config.logs).map(|log| log_path(&dir, log)).collect()
+            }
+            AppendMode::PerThread => {
+                let thread_path = |log: usize, thread: usize| dir.join(format!("log_{}.{}", log, thread));
+                on_every_thread(threads, |thread| {
+                    let mut logs = (0..config.logs).map(|log| Ok(BufWriter::new(open_log(&thread_path(log, thread), false)?))).collect::<io::Result<Vec<_>>>()?;
+                    records(thread, &mut |log, record| logs[log].write_all(record))?;
+                    logs.iter_mut().try_for_each(|log| log.flush())
+                })?;
+                (0..config.logs).flat_map(|log| (0..threads).map(move |thread| (log, thread))).map(|(log, thread)| thread_path(log, thread)).collect()
+            }
+        };
+        let elapsed = start.elapsed();
+
+        let check = check(&files, config.record_size, config.records)?;
+        result.modes.push(ModeResult { mode, elapsed, check });
+        fs::remove_dir_all(&dir)?;
+    }
+    Ok(result)
+}
@@ -0,0 +1,473 @@
+//! Archives of many small files: a tree packed into one tar or zip, optionally compressed,
+//! and unpacked again in parallel. Shipping a dependency tree as one archive trades
+//! thousands of file creations at the far end for one sequential read and an unpack, and
+//! whether that wins depends on how fast the unpack is.
+//!
+//! [`pack`] writes the files in the order given; [`unpack`] first indexes the archive (the
+//! tar headers, or the zip central directory) and then extracts every member at its offset
+//! at once, in parallel with the `rayon` feature. A gzipped tar has no index and can only
+//! be decompressed from the start, so [`Format::TarGz`] decompresses the whole archive into
+//! memory first; zip compresses each member on its own, so its members decompress in
+//! parallel too. Compression is [`crate::deflate`]'s. Zip archives are limited to 65,535
+//! members of under 4 GiB each (no zip64), and unpacking creates regular files and
+//! directories and skips everything else. Tar member names longer than ustar's 100 bytes
+//! are written with a pax header, and pax and GNU long names are read.
+
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+use crate::buffers;
+use crate::bundle::{TAR_BLOCK, tar_headers};
+use crate::deflate::{self, GzipWriter};
+use crate::pack::read_at;
+
+const ZIP_LOCAL: u32 = 0x0403_4b50;
+const ZIP_CENTRAL: u32 = 0x0201_4b50;
+const ZIP_END: u32 = 0x0605_4b50;
+const ZIP_LOCAL_LEN: usize = 30;
+const ZIP_CENTRAL_LEN: usize = 46;
+const ZIP_END_LEN: usize = 22;
+/// Version 2.0, the first with deflate and directories.
+const ZIP_VERSION: u16 = 20;
+/// Names are UTF-8.
+const ZIP_UTF8: u16 = 1 << 11;
+const ZIP_ENCRYPTED: u16 = 1;
+const ZIP_STORED: u16 = 0;
+const ZIP_DEFLATED: u16 = 8;
+/// 1980-01-01 00:00, the earliest time zip can record.
+const ZIP_DATE: u16 = 1 << 5 | 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Tar,
+    /// A tar compressed as a whole with gzip.
+    TarGz,
+    /// Members stored uncompressed.
+    Zip,
+    /// Members each compressed with deflate, or stored where that doesn't shrink them.
+    ZipDeflate,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Format::Tar, Format::TarGz, Format::Zip, Format::ZipDeflate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Tar => "tar",
+            Format::TarGz => "tar.gz",
+            Format::Zip => "zip",
+            Format::ZipDeflate => "zip-deflate",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Tar => "tar",
+            Format::TarGz => "tar.gz",
+            Format::Zip | Format::ZipDeflate => "zip",
+        }
+    }
+}
+
+/// One member of an archive, as its index found it.
+#[derive(Debug, Clone)]
+struct Member {
+    name: String,
+    /// The tar data, or the zip local header.
+    offset: u64,
+    size: u64,
+    /// Zip only: the bytes stored, how and their CRC.
+    stored: u64,
+    method: u16,
+    crc: u32,
+    dir: bool,
+}
+
+/// Packs the files of `root` named by `names` (relative paths with `/` between components)
+/// into a new archive at `archive`, and returns its size.
+pub fn pack(root: &Path, names: &[String], archive: &Path, format: Format) -> io::Result<u64> {
+    let out = BufWriter::with_capacity(1 << 20, File::create(archive)?);
+    let mut out = match format {
+        Format::Tar => write_tar(root, names, out)?,
+        Format::TarGz => write_tar(root, names, GzipWriter::new(out)?)?.finish()?,
+        Format::Zip | Format::ZipDeflate => write_zip(root, names, out, format == Format::ZipDeflate)?,
+    };
+    out.flush()?;
+    Ok(out.get_ref().metadata()?.len())
+}
+
+fn write_tar<W: Write>(root: &Path, names: &[String], mut out: W) -> io::Result<W> {
+    let mut data = Vec::new();
+    for name in names {
+        let mut file = File::open(root.join(name))?;
+        let mtime = file.metadata()?.modified()?.duration_since(std::time::UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        data.clear();
+        file.read_to_end(&mut data)?;
+        out.write_all(&tar_headers(name, data.len() as u64, mtime))?;
+        out.write_all(&data)?;
+        out.write_all(&[0; TAR_BLOCK][..data.len().next_multiple_of(TAR_BLOCK) - data.len()])?;
+    }
+    out.write_all(&[0; 2 * TAR_BLOCK])?;
+    Ok(out)
+}
+
+fn write_zip<W: Write>(root: &Path, names: &[String], mut out: W, compress: bool) -> io::Result<W> {
+    let too_big = || io::Error::new(io::ErrorKind::InvalidInput, "archive needs zip64, which isn't written");
+    let count = u16::try_from(names.len()).map_err(|_| too_big())?;
+    let mut central = Vec::with_capacity(names.len() * (ZIP_CENTRAL_LEN + 32));
+    let mut offset = 0u64;
+    for name in names {
+        let data = fs::read(root.join(name))?;
+        let crc = deflate::crc32(&data);
+        let (method, stored) = match compress {
+            true => match deflate::deflate(&data) {
+                compressed if compressed.len() < data.len() => (ZIP_DEFLATED, Cow::Owned(compressed)),
+                _ => (ZIP_STORED, Cow::Borrowed(&data[..])),
+            },
+            false => (ZIP_STORED, Cow::Borrowed(&data[..])),
+        };
+        let (at, stored_len, size) = (u32::try_from(offset), u32::try_from(stored.len()), u32::try_from(data.len()));
+        let (Ok(at), Ok(stored_len), Ok(size), Ok(name_len)) = (at, stored_len, size, u16::try_from(name.len())) else {
+            return Err(too_big());
+        };
+        // The fields local and central headers share, from the version needed on.
+        let mut common = Vec::with_capacity(26);
+        for field in [ZIP_VERSION, ZIP_UTF8, method, 0, ZIP_DATE] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [crc, stored_len, size] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        common.extend_from_slice(&name_len.to_le_bytes());
+        // No extra field.
+        common.extend_from_slice(&[0, 0]);
+
+        out.write_all(&ZIP_LOCAL.to_le_bytes())?;
+        out.write_all(&common)?;
+        out.write_all(name.as_bytes())?;
+        out.write_all(&stored)?;
+        offset += (ZIP_LOCAL_LEN + name.len() + stored.len()) as u64;
+
+        central.extend_from_slice(&ZIP_CENTRAL.to_le_bytes());
+        // Made by Unix, so the external attributes are a mode.
+        central.extend_from_slice(&(3 << 8 | ZIP_VERSION).to_le_bytes());
+        central.extend_from_slice(&common);
+        // No comment, disk 0, no internal attributes, then the mode and the local header.
+        central.extend_from_slice(&[0; 6]);
+        central.extend_from_slice(&(0o100644u32 << 16).to_le_bytes());
+        central.extend_from_slice(&at.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+    let (Ok(central_at), Ok(central_len)) = (u32::try_from(offset), u32::try_from(central.len())) else {
+        return Err(too_big());
+    };
+    out.write_all(&central)?;
+    out.write_all(&ZIP_END.to_le_bytes())?;
+    out.write_all(&[0; 4])?;
+    out.write_all(&count.to_le_bytes())?;
+    out.write_all(&count.to_le_bytes())?;
+    out.write_all(&central_len.to_le_bytes())?;
+    out.write_all(&central_at.to_le_bytes())?;
+    out.write_all(&[0, 0])?;
+    Ok(out)
+}
+
+/// Unpacks `archive` into `dest`, creating it if needed, and returns how many files it
+/// held. Fails without writing anything if a member's name would land outside `dest`.
+pub fn unpack(archive: &Path, format: Format, dest: &Path) -> io::Result<usize> {
+    let context = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", archive.display(), e));
+    match format {
+        Format::Tar => {
+            let file = File::open(archive)?;
+            let len = file.metadata()?.len();
+            let members = tar_members(len, |at, header| read_at(&file, header, at)).map_err(context)?;
+            extract(&members, dest, |member, buf| {
+                buf.resize(member.size as usize, 0);
+                read_at(&file, buf, member.offset)
+            })
+        }
+        Format::TarGz => {
+            let tar = deflate::gunzip(&fs::read(archive)?).map_err(context)?;
+            let members = tar_members(tar.len() as u64, |at, header| {
+                let bytes = tar.get(at as usize..at as usize + header.len()).ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "tar ends early"))?;
+                header.copy_from_slice(bytes);
+                Ok(())
+            })
+            .map_err(context)?;
+            extract(&members, dest, |member, buf| {
+                let data = tar.get(member.offset as usize..(member.offset + member.size) as usize).ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "tar ends early"))?;
+                buf.clear();
+                buf.extend_from_slice(data);
+                Ok(())
+            })
+        }
+        Format::Zip | Format::ZipDeflate => {
+            let file = File::open(archive)?;
+            let members = zip_members(&file).map_err(context)?;
+            extract(&members, dest, |member, buf| read_zip_member(&file, member, buf).map_err(|e| io::Error::new(e.kind(), format!("{}: {}: {}", archive.display(), member.name, e))))
+        }
+    }
+}
+
+/// Indexes a tar of `len` bytes whose 512-byte block at an offset `header_at` reads.
+fn tar_members(len: u64, mut header_at: impl FnMut(u64, &mut [u8]) -> io::Result<()>) -> io::Result<Vec<Member>> {
+    let mut members = Vec::new();
+    let mut header = [0u8; TAR_BLOCK];
+    let mut at = 0;
+    // The name a pax or GNU long-name header gave the next member.
+    let mut long_name = None;
+    while at + TAR_BLOCK as u64 <= len {
+        header_at(at, &mut header)?;
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let checksum: u32 = header[..148].iter().chain(&[b' '; 8]).chain(&header[156..]).map(|&b| b as u32).sum();
+        if octal(&header[148..156]) != Some(checksum as u64) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad tar header checksum at {}", at)));
+        }
+        let size = octal(&header[124..136]).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("bad tar size at {}", at)))?;
+        let mut name = field(&header[..100]);
+        // ustar splits long names, keeping the directories in the prefix field.
+        if &header[257..262] == b"ustar" && header[345] != 0 {
+            name = format!("{}/{}", field(&header[345..500]), name);
+        }
+        let offset = at + TAR_BLOCK as u64;
+        if matches!(header[156], b'x' | b'L') {
+            let mut data = vec![0u8; size.next_multiple_of(TAR_BLOCK as u64) as usize];
+            for (i, block) in data.chunks_mut(TAR_BLOCK).enumerate() {
+                header_at(offset + (i * TAR_BLOCK) as u64, block)?;
+            }
+            data.truncate(size as usize);
+            long_name = if header[156] == b'L' { Some(field(&data)) } else { pax_path(&data).or(long_name) };
+        }
+        let name = match header[156] {
+            b'0' | 0 | b'5' => long_name.take().unwrap_or(name),
+            _ => name,
+        };
+        match header[156] {
+            b'0' | 0 => members.push(Member { name, offset, size, stored: size, method: ZIP_STORED, crc: 0, dir: false }),
+            b'5' => members.push(Member { name, offset, size: 0, stored: 0, method: ZIP_STORED, crc: 0, dir: true }),
+            // Links, devices, and pax and GNU extension headers.
+            _ => {}
+        }
+        at = offset + size.next_multiple_of(TAR_BLOCK as u64);
+        if at > len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "tar ends inside a member"));
+        }
+    }
+    Ok(members)
+}
+
+/// The `path` of a pax extended header's records, `<length> <key>=<value>\n` each.
+fn pax_path(mut records: &[u8]) -> Option<String> {
+    while !records.is_empty() {
+        let space = records.iter().position(|&b| b == b' ')?;
+        let len: usize = std::str::from_utf8(&records[..space]).ok()?.parse().ok()?;
+        let record = records.get(space + 1..len)?.strip_suffix(b"\n")?;
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(String::from_utf8_lossy(path).into_owned());
+        }
+        records = &records[len..];
+    }
+    None
+}
+
+/// A NUL-terminated header field.
+fn field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// A tar number: octal digits, padded with spaces or NULs.
+fn octal(bytes: &[u8]) -> Option<u64> {
+    let digits = String::from_utf8_lossy(bytes);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// Indexes a zip through its central directory.
+fn zip_members(file: &File) -> io::Result<Vec<Member>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let len = file.metadata()?.len();
+    // The end record is the last thing in the file, followed by at most a 64 KiB comment.
+    let tail_len = len.min((ZIP_END_LEN + u16::MAX as usize) as u64);
+    let mut tail = vec![0; tail_len as usize];
+    read_at(file, &mut tail, len - tail_len)?;
+    let end = (0..tail.len().saturating_sub(ZIP_END_LEN - 1)).rev().find(|&at| tail[at..at + 4] == ZIP_END.to_le_bytes()).ok_or_else(|| invalid("no zip end record"))?;
+    let end = &tail[end..];
+    let u16_at = |bytes: &[u8], at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+    let u32_at = |bytes: &[u8], at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    let count = u16_at(end, 10) as usize;
+    let (central_len, central_at) = (u32_at(end, 12) as u64, u32_at(end, 16) as u64);
+    if central_at + central_len > len {
+        return Err(invalid("zip central directory runs past the end"));
+    }
+    let mut central = vec![0; central_len as usize];
+    read_at(file, &mut central, central_at)?;
+
+    let mut members = Vec::with_capacity(count);
+    let mut at = 0;
+    for _ in 0..count {
+        let header = central.get(at..at + ZIP_CENTRAL_LEN).filter(|header| u32_at(header, 0) == ZIP_CENTRAL).ok_or_else(|| invalid("bad zip central directory"))?;
+        let (flags, method, crc) = (u16_at(header, 8), u16_at(header, 10), u32_at(header, 16));
+        let (stored, size, offset) = (u32_at(header, 20), u32_at(header, 24), u32_at(header, 42));
+        let (name_len, extra_len, comment_len) = (u16_at(header, 28) as usize, u16_at(header, 30) as usize, u16_at(header, 32) as usize);
+        let name = central.get(at + ZIP_CENTRAL_LEN..at + ZIP_CENTRAL_LEN + name_len).ok_or_else(|| invalid("bad zip central directory"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        at += ZIP_CENTRAL_LEN + name_len + extra_len + comment_len;
+        if flags & ZIP_ENCRYPTED != 0 {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} is encrypted", name)));
+        }
+        if method != ZIP_STORED && method != ZIP_DEFLATED {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} uses compression method {}", name, method)));
+        }
+        if [stored, size, offset].contains(&u32::MAX) {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "zip64 archives aren't read"));
+        }
+        let dir = name.ends_with('/');
+        members.push(Member { name, offset: offset as u64, size: size as u64, stored: stored as u64, method, crc, dir });
+    }
+    Ok(members)
+}
+
+/// Reads a zip member's data into `buf`, decompressed and checked against its CRC.
+fn read_zip_member(file: &File, member: &Member, buf: &mut Vec<u8>) -> io::Result<()> {
+    let mut local = [0; ZIP_LOCAL_LEN];
+    read_at(file, &mut local, member.offset)?;
+    if u32::from_le_bytes(local[..4].try_into().unwrap()) != ZIP_LOCAL {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "bad local header"));
+    }
+    // The local header's name and extra field may differ in length from the central one's.
+    let skip = u16::from_le_bytes([local[26], local[27]]) as u64 + u16::from_le_bytes([local[28], local[29]]) as u64;
+    buf.resize(member.stored as usize, 0);
+    read_at(file, buf, member.offset + ZIP_LOCAL_LEN as u64 + skip)?;
+    if member.method == ZIP_DEFLATED {
+        *buf = deflate::inflate(buf)?;
+    }
+    if buf.len() as u64 != member.size || deflate::crc32(buf) != member.crc {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "contents don't match the recorded size and CRC"));
+    }
+    Ok(())
+}
+
+/// Where member `name` goes under `dest`, refusing names that would escape it. `./`
+/// components, which `tar -C dir .` writes, are dropped.
+fn member_path(dest: &Path, name: &str) -> io::Result<PathBuf> {
+    let mut path = dest.to_path_buf();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("archive member {:?} would land outside the destination", name))),
+        }
+    }
+    Ok(path)
+}
+
+/// Creates the directories of `members`, then writes every file with what `read` puts in
+/// the buffer it's given.
+fn extract(members: &[Member], dest: &Path, read: impl Fn(&Member, &mut Vec<u8>) -> io::Result<()> + Sync) -> io::Result<usize> {
+    let paths = members.iter().map(|member| member_path(dest, member.name.trim_end_matches('/'))).collect::<io::Result<Vec<_>>>()?;
+    let mut dirs: Vec<&Path> = members.iter().zip(&paths).filter_map(|(member, path)| if member.dir { Some(path.as_path()) } else { path.parent() }).collect();
+    dirs.push(dest);
+    dirs.sort();
+    dirs.dedup();
+    for dir in dirs {
+        fs::create_dir_all(dir)?;
+    }
+    let files: Vec<(&Member, &PathBuf)> = members.iter().zip(&paths).filter(|(member, _)| !member.dir).collect();
+    let one = |&(member, path): &(&Member, &PathBuf)| {
+        buffers::with_buffer(|buf| {
+            read(member, buf)?;
+            fs::write(path, &buf[..])
+        })
+    };
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        files.par_iter().try_for_each(one)?;
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        files.iter().try_for_each(one)?;
+    }
+    Ok(files.len())
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct FormatResult {
+    pub format: Format,
+    pub pack: Duration,
+    pub unpack: Duration,
+    /// The archive's size.
+    pub bytes: u64,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveResult {
+    /// Copying the loose files, the baseline an unpack competes with.
+    pub copy: Duration,
+    /// The workload's bytes, before any archive overhead or compression.
+    pub source_bytes: u64,
+    pub formats: Vec<FormatResult>,
+    pub failures: Vec<Failure>,
+}
+
+/// Writes the configured workload as a source tree of `dir_path`, times copying it as loose
+/// files, then for each format times packing it into an archive and unpacking that into a
+/// fresh tree. With `options.verify` every unpacked file is checked, untimed. Each copy,
+/// archive and unpacked tree is removed before the next.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<ArchiveResult> {
+    let source = dir_path.join("source");
+    fs::create_dir_all(&source)?;
+    let paths = options.workload.paths(&source);
+    for (index, path) in paths.iter().enumerate() {
+        bench::with_content(options, index, false, |bytes| fs::write(path, bytes))?;
+    }
+    let names: Vec<String> = paths.iter().filter_map(|path| Some(path.file_name()?.to_str()?.to_string())).collect();
+    let mut result = ArchiveResult { source_bytes: paths.len() as u64 * options.workload.size() as u64, ..ArchiveResult::default() };
+    options.failures.take("");
+
+    let copy = dir_path.join("copy");
+    fs::create_dir_all(&copy)?;
+    let copies = bench::file_paths(&copy, paths.len());
+    let start = Instant::now();
+    bench::phase(options, "Archive copy", "archive/copy", "copy", &mut result.failures, || bench::each_indexed(&copies, options, |index, to| fs::copy(&paths[index], to).map(drop)))?;
+    result.copy = start.elapsed();
+    fs::remove_dir_all(&copy)?;
+
+    for format in Format::ALL {
+        let archive = dir_path.join(format!("workload.{}", format.extension()));
+        let unpacked = dir_path.join("unpacked");
+        options.progress.set_stage(format!("Archive {} pack", format.name()));
+        options.op_scope.set(format!("archive/{}/pack", format.name()));
+        let start = Instant::now();
+        let bytes = pack(&source, &names, &archive, format)?;
+        let pack_time = start.elapsed();
+        options.progress.set_stage(format!("Archive {} unpack", format.name()));
+        options.op_scope.set(format!("archive/{}/unpack", format.name()));
+        let start = Instant::now();
+        unpack(&archive, format, &unpacked)?;
+        let unpack_time = start.elapsed();
+        if options.verify {
+            for (index, name) in names.iter().enumerate() {
+                let path = unpacked.join(name);
+                bench::read_done(options, index, &path, &fs::read(&path)?)?;
+            }
+        }
+        result.formats.push(FormatResult { format, pack: pack_time, unpack: unpack_time, bytes });
+        fs::remove_dir_all(&unpacked)?;
+        fs::remove_file(&archive)?;
+    }
+    fs::remove_dir_all(&source)?;
+    Ok(result)
+}
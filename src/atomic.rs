@@ -0,0 +1,463 @@
+//! Replacing a file's contents so that readers, and the file after a crash, only ever see
+//! the old contents or the new ones.
+//!
+//! [`write_file_atomic`] writes the bytes to a temporary file in the same directory, syncs
+//! it, renames it over the target and syncs the directory, which is the sequence most
+//! hand-rolled versions get subtly wrong: skipping the first sync can leave an empty file
+//! after a power cut on ext4 and XFS, and skipping the last can lose the rename itself. On
+//! macOS the syncs use `F_FULLFSYNC` (with the `libc` feature), since plain `fsync` there
+//! doesn't reach the disk; on Windows an existing target is swapped with `ReplaceFileW`,
+//! which keeps its attributes and ACLs, and a new one is moved into place.
+//!
+//! Unix permissions of an existing target carry over to the new file. Ownership, extended
+//! attributes and hard links don't: the target becomes a new inode.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+use crate::names;
+
+/// How far [`write_file_atomic`] goes to survive a crash. Every level is atomic for
+/// readers; they differ in what a power cut or kernel panic can leave behind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// No syncs. After a crash the file may hold the old contents, the new ones, or
+    /// (depending on the filesystem) be empty.
+    None,
+    /// Sync the new contents before renaming. After a crash the file holds the old
+    /// contents or the new ones, but the call may have returned before the rename was on
+    /// disk.
+    Data,
+    /// Also sync the directory after renaming: once the call returns, the new contents
+    /// survive a crash.
+    #[default]
+    Full,
+}
+
+impl std::str::FromStr for Durability {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Durability> {
+        match s {
+            "none" => Ok(Durability::None),
+            "data" => Ok(Durability::Data),
+            "full" => Ok(Durability::Full),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown durability '{}', expected none|data|full", s))),
+        }
+    }
+}
+
+/// Replaces the contents of `path` with `bytes`, creating it if needed. On error the
+/// target is untouched and the temporary file removed.
+pub fn write_file_atomic(path: impl AsRef<Path>, bytes: impl AsRef<[u8]>, durability: Durability) -> io::Result<()> {
+    let path = path.as_ref();
+    let temp = write_temp(path, bytes.as_ref(), durability)?;
+    replace(&temp, path)?;
+    if durability == Durability::Full {
+        sync_dir(parent(path))?;
+    }
+    Ok(())
+}
+
+/// [`write_file_atomic`] for many files: each file is replaced atomically, and with
+/// [`Durability::Full`] each directory is synced once after all its renames rather than
+/// once per file. The batch as a whole isn't atomic; on error, files before the failing one
+/// have been replaced and the rest are untouched.
+pub fn write_files_atomic<P: AsRef<Path>, B: AsRef<[u8]>>(files: &[(P, B)], durability: Durability) -> io::Result<()> {
+    let files: Vec<(&Path, &[u8])> = files.iter().map(|(path, bytes)| (path.as_ref(), bytes.as_ref())).collect();
+    let temps = write_temps(&files, durability)?;
+    let dirs = rename_temps(&files, &temps)?;
+    if durability == Durability::Full {
+        for dir in &dirs {
+            sync_dir(dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// Files on one filesystem from which [`commit_batch`] flushes the whole filesystem with
+/// `syncfs` instead of syncing each file. Below it, the other dirty data `syncfs` would
+/// also write back is likely to cost more than the syncs it saves. This is a guess rather
+/// than a measured crossover, which depends on the device and on what else is dirty; `io
+/// bench commit` times both ways, and [`commit_batch_with`] takes another threshold.
+pub const SYNCFS_MIN_FILES: usize = 32;
+
+/// The first Linux release whose `syncfs` reports writeback errors. Before it, a page that
+/// failed to write back still left `syncfs` returning success.
+#[cfg(all(target_os = "linux", feature = "libc"))]
+const SYNCFS_ERRORS_SINCE: (u32, u32) = (5, 8);
+
+/// Whether `syncfs` on this kernel fails when writeback did, so a batch flushed with it is
+/// known to be durable. Only Linux 5.8 and later promise that.
+pub fn syncfs_reports_errors() -> bool {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        static REPORTS: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+        *REPORTS.get_or_init(|| {
+            let release = fs::read_to_string("/proc/sys/kernel/osrelease").unwrap_or_default();
+            let mut numbers = release.trim().split(['.', '-']).map(|part| part.parse::<u32>().ok());
+            matches!((numbers.next().flatten(), numbers.next().flatten()), (Some(major), Some(minor)) if (major, minor) >= SYNCFS_ERRORS_SINCE)
+        })
+    }
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    {
+        false
+    }
+}
+
+/// What [`commit_batch`] did to make a batch durable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommitReport {
+    pub files: usize,
+    pub bytes: u64,
+    /// `fsync`s of single files.
+    pub file_syncs: usize,
+    /// `fsync`s of directories.
+    pub dir_syncs: usize,
+    /// `syncfs` calls, each flushing a whole filesystem.
+    pub filesystem_syncs: usize,
+}
+
+impl CommitReport {
+    /// Every sync call made.
+    pub fn syncs(&self) -> usize {
+        self.file_syncs + self.dir_syncs + self.filesystem_syncs
+    }
+}
+
+/// Replaces every file in `files` atomically and durably, as [`write_files_atomic`] with
+/// [`Durability::Full`] does, with as few sync calls as that takes: files are grouped by
+/// filesystem, and each group either syncs every file and then each directory once, or,
+/// with [`SYNCFS_MIN_FILES`] files or more where [`syncfs_reports_errors`], flushes its
+/// filesystem once before the renames and once after, whatever the number of files and
+/// directories. Files before a failing group are committed; the failing group's files are
+/// replaced or untouched as with [`write_files_atomic`].
+pub fn commit_batch<P: AsRef<Path>, B: AsRef<[u8]>>(files: &[(P, B)]) -> io::Result<CommitReport> {
+    commit_batch_with(files, SYNCFS_MIN_FILES)
+}
+
+/// [`commit_batch`], flushing whole filesystems from `syncfs_min_files` files on one;
+/// `usize::MAX` always syncs file by file.
+pub fn commit_batch_with<P: AsRef<Path>, B: AsRef<[u8]>>(files: &[(P, B)], syncfs_min_files: usize) -> io::Result<CommitReport> {
+    let files: Vec<(&Path, &[u8])> = files.iter().map(|(path, bytes)| (path.as_ref(), bytes.as_ref())).collect();
+    let mut report = CommitReport { files: files.len(), bytes: files.iter().map(|(_, bytes)| bytes.len() as u64).sum(), ..CommitReport::default() };
+    let mut groups: BTreeMap<u64, Vec<(&Path, &[u8])>> = BTreeMap::new();
+    for &(path, bytes) in &files {
+        groups.entry(filesystem_id(parent(path))?).or_default().push((path, bytes));
+    }
+    for group in groups.values() {
+        let whole = group.len() >= syncfs_min_files && syncfs_reports_errors();
+        let temps = write_temps(group, if whole { Durability::None } else { Durability::Data })?;
+        if whole {
+            if let Err(e) = sync_filesystem(parent(group[0].0)) {
+                remove_all(&temps);
+                return Err(e);
+            }
+            report.filesystem_syncs += 1;
+        } else {
+            report.file_syncs += group.len();
+        }
+        let dirs = rename_temps(group, &temps)?;
+        if whole {
+            sync_filesystem(parent(group[0].0))?;
+            report.filesystem_syncs += 1;
+        } else {
+            for dir in &dirs {
+                sync_dir(dir)?;
+            }
+            report.dir_syncs += dirs.len();
+        }
+    }
+    Ok(report)
+}
+
+/// [`write_file_atomic`] on tokio's blocking pool.
+#[cfg(feature = "tokio")]
+pub async fn write_file_atomic_async(path: impl Into<PathBuf>, bytes: impl Into<Vec<u8>>, durability: Durability) -> io::Result<()> {
+    let (path, bytes) = (path.into(), bytes.into());
+    tokio::task::spawn_blocking(move || write_file_atomic(path, bytes, durability)).await.map_err(io::Error::other)?
+}
+
+/// [`write_files_atomic`] on tokio's blocking pool.
+#[cfg(feature = "tokio")]
+pub async fn write_files_atomic_async(files: Vec<(PathBuf, Vec<u8>)>, durability: Durability) -> io::Result<()> {
+    tokio::task::spawn_blocking(move || write_files_atomic(&files, durability)).await.map_err(io::Error::other)?
+}
+
+/// [`commit_batch`] on tokio's blocking pool.
+#[cfg(feature = "tokio")]
+pub async fn commit_batch_async(files: Vec<(PathBuf, Vec<u8>)>) -> io::Result<CommitReport> {
+    tokio::task::spawn_blocking(move || commit_batch(&files)).await.map_err(io::Error::other)?
+}
+
+/// The directory `path` is in; the current one for bare file names.
+pub(crate) fn parent(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// A name next to `path` that no other writer, in this process or another, picks.
+pub(crate) fn temp_path(path: &Path) -> io::Result<PathBuf> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    if path.file_name().is_none() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} doesn't name a file", path.display())));
+    }
+    Ok(names::sibling(path, ".", &format!(".{}.{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed))))
+}
+
+/// Writes `bytes` to a new temporary file beside `path`, synced as `durability` asks.
+pub(crate) fn write_temp(path: &Path, bytes: &[u8], durability: Durability) -> io::Result<PathBuf> {
+    let temp = temp_path(path)?;
+    let result = OpenOptions::new().write(true).create_new(true).open(&temp).and_then(|mut file| {
+        file.write_all(bytes)?;
+        copy_permissions(path, &file)?;
+        if durability != Durability::None {
+            sync_file(&file)?;
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => Ok(temp),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+/// A temporary file for each of `files`, or none if any failed.
+fn write_temps(files: &[(&Path, &[u8])], durability: Durability) -> io::Result<Vec<PathBuf>> {
+    let mut temps = Vec::with_capacity(files.len());
+    for &(path, bytes) in files {
+        match write_temp(path, bytes, durability) {
+            Ok(temp) => temps.push(temp),
+            Err(e) => {
+                remove_all(&temps);
+                return Err(e);
+            }
+        }
+    }
+    Ok(temps)
+}
+
+/// Moves each of `temps` over its file, returning the directories that changed. On error
+/// the temporary files not yet moved are removed.
+fn rename_temps(files: &[(&Path, &[u8])], temps: &[PathBuf]) -> io::Result<BTreeSet<PathBuf>> {
+    let mut dirs = BTreeSet::new();
+    for (i, ((path, _), temp)) in files.iter().zip(temps).enumerate() {
+        if let Err(e) = replace(temp, path) {
+            remove_all(&temps[i + 1..]);
+            return Err(e);
+        }
+        dirs.insert(parent(path).to_path_buf());
+    }
+    Ok(dirs)
+}
+
+fn remove_all(temps: &[PathBuf]) {
+    for temp in temps {
+        let _ = fs::remove_file(temp);
+    }
+}
+
+/// Gives the new file the target's permission bits, if the target exists.
+fn copy_permissions(path: &Path, file: &File) -> io::Result<()> {
+    #[cfg(unix)]
+    match fs::metadata(path) {
+        Ok(metadata) => file.set_permissions(metadata.permissions())?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    #[cfg(not(unix))]
+    let _ = (path, file);
+    Ok(())
+}
+
+/// Moves `temp` over `path`, removing `temp` if that fails.
+fn replace(temp: &Path, path: &Path) -> io::Result<()> {
+    let result = rename_over(temp, path);
+    if result.is_err() {
+        let _ = fs::remove_file(temp);
+    }
+    result
+}
+
+#[cfg(not(windows))]
+fn rename_over(temp: &Path, path: &Path) -> io::Result<()> {
+    fs::rename(temp, path)
+}
+
+#[cfg(windows)]
+fn rename_over(temp: &Path, path: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn ReplaceFileW(replaced: *const u16, replacement: *const u16, backup: *const u16, flags: u32, exclude: *mut std::ffi::c_void, reserved: *mut std::ffi::c_void) -> i32;
+    }
+    // ReplaceFileW only replaces; a new file is moved into place.
+    if !path.exists() {
+        return fs::rename(temp, path);
+    }
+    let wide = |path: &Path| path.as_os_str().encode_wide().chain([0]).collect::<Vec<u16>>();
+    let (replaced, replacement) = (wide(path), wide(temp));
+    if unsafe { ReplaceFileW(replaced.as_ptr(), replacement.as_ptr(), std::ptr::null(), 0, std::ptr::null_mut(), std::ptr::null_mut()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Flushes `file` to the device; on macOS and iOS through the drive's own cache as well.
+fn sync_file(file: &File) -> io::Result<()> {
+    #[cfg(all(target_vendor = "apple", feature = "libc"))]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_FULLFSYNC) } == 0 {
+            return Ok(());
+        }
+        // Some filesystems (network ones, FAT) don't support it.
+    }
+    file.sync_all()
+}
+
+/// Identifies the filesystem `dir` is on; every path is on one filesystem where that
+/// can't be told.
+fn filesystem_id(dir: &Path) -> io::Result<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        Ok(fs::metadata(dir)?.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        Ok(0)
+    }
+}
+
+/// Writes back everything dirty on the filesystem holding `dir`, metadata included.
+fn sync_filesystem(dir: &Path) -> io::Result<()> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        // Since Linux 5.8 this also reports writeback errors of the filesystem, which
+        // `commit_batch` checks for before relying on it.
+        if unsafe { libc::syncfs(File::open(dir)?.as_raw_fd()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    {
+        let _ = dir;
+        Err(io::Error::new(io::ErrorKind::Unsupported, "syncfs is only available on Linux"))
+    }
+}
+
+/// Makes the directory entries of `dir` durable. Windows has no directory handles to sync;
+/// `ReplaceFileW` and `MoveFileExW` update the directory as part of the move.
+pub(crate) fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        sync_file(&File::open(dir)?)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        Ok(())
+    }
+}
+
+/// How the commit benchmark makes a batch durable.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Commit {
+    /// [`write_file_atomic`] with [`Durability::Full`] per file: a file and a directory
+    /// sync each.
+    PerFile,
+    /// [`write_files_atomic`]: a sync per file, then one per directory.
+    Batch,
+    /// [`commit_batch`].
+    Minimal,
+}
+
+#[cfg(feature = "bench")]
+impl Commit {
+    pub const ALL: [Commit; 3] = [Commit::PerFile, Commit::Batch, Commit::Minimal];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Commit::PerFile => "per-file",
+            Commit::Batch => "batch",
+            Commit::Minimal => "commit_batch",
+        }
+    }
+
+    fn apply(self, files: &[(PathBuf, &[u8])]) -> io::Result<CommitReport> {
+        let dirs = files.iter().map(|(path, _)| parent(path)).collect::<BTreeSet<_>>().len();
+        let report = CommitReport { files: files.len(), bytes: files.iter().map(|(_, bytes)| bytes.len() as u64).sum(), ..CommitReport::default() };
+        match self {
+            Commit::PerFile => {
+                for (path, bytes) in files {
+                    write_file_atomic(path, bytes, Durability::Full)?;
+                }
+                Ok(CommitReport { file_syncs: files.len(), dir_syncs: files.len(), ..report })
+            }
+            Commit::Batch => {
+                write_files_atomic(files, Durability::Full)?;
+                Ok(CommitReport { file_syncs: files.len(), dir_syncs: dirs, ..report })
+            }
+            Commit::Minimal => commit_batch(files),
+        }
+    }
+}
+
+/// Directories the commit benchmark spreads its files over, so directory syncs can be
+/// shared.
+#[cfg(feature = "bench")]
+pub const COMMIT_DIRS: usize = 16;
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct CommitResult {
+    pub times: Vec<(Commit, Duration, CommitReport)>,
+    pub failures: Vec<Failure>,
+}
+
+/// Creates the configured workload spread over [`COMMIT_DIRS`] directories of `dir_path`,
+/// then times durably replacing every file with its updated contents by each method in
+/// turn.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<CommitResult> {
+    for dir in 0..COMMIT_DIRS {
+        fs::create_dir_all(dir_path.join(format!("dir_{}", dir)))?;
+    }
+    let paths: Vec<PathBuf> = (0..options.workload.files).map(|i| dir_path.join(format!("dir_{}", i % COMMIT_DIRS)).join(format!("file_{}.txt", i))).collect();
+    let mut result = CommitResult::default();
+    options.failures.take("");
+
+    bench::phase(options, "Commit create", "commit/create", "create", &mut result.failures, || bench::create_files(&paths, options))?;
+
+    let files: Vec<(PathBuf, &[u8])> = paths.iter().map(|path| (path.clone(), options.workload.update_content())).collect();
+    for method in Commit::ALL {
+        options.progress.set_stage(format!("Commit {}", method.name()));
+        let start = Instant::now();
+        let report = method.apply(&files)?;
+        result.times.push((method, start.elapsed(), report));
+    }
+
+    bench::phase(options, "Commit delete", "commit/delete", "delete", &mut result.failures, || bench::delete_files(&paths, options))?;
+    Ok(result)
+}
@@ -0,0 +1,283 @@
+//! Saving `io bench` results as a baseline and checking later runs against it.
+//!
+//! Each file says what it is: a schema version, the machine, kernel and CPU the run was
+//! on, the build that ran it and the options that shaped its workload, so files collected
+//! across machines and months can be told apart, and `io bench compare` can refuse to
+//! compare runs of different workloads.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use ::io::anomaly::Finding;
+use ::io::bench::PhaseTimes;
+use ::io::cgroup::Limits;
+use ::io::filesystem::Filesystem;
+use ::io::human::{self, Align, Table};
+use ::io::json::{self, Value};
+use ::io::noise::Readings;
+use ::io::platform::Host;
+
+/// The schema this writes. Version 1 files, from before [`Metadata`], still load.
+const SCHEMA_VERSION: u64 = 2;
+pub const PHASES: [&str; 4] = ["create", "read", "update", "delete"];
+
+/// Phase times in milliseconds, in [`PHASES`] order.
+pub fn phase_ms(times: &PhaseTimes) -> [f64; 4] {
+    [times.create, times.read, times.update, times.delete].map(|d| d.as_secs_f64() * 1000.0)
+}
+
+#[derive(Debug)]
+pub struct Baseline {
+    pub threads: usize,
+    pub files: usize,
+    pub size: usize,
+    /// What the run's directory was stored on, when it could be found; the first
+    /// target's with several.
+    pub filesystem: Option<Filesystem>,
+    /// Each `--target` and what it was stored on, when the run had more than one.
+    pub targets: Vec<(String, Option<Filesystem>)>,
+    /// The cgroup limits the run was under, when in one.
+    pub cgroup: Option<Limits>,
+    /// Strategy label and its phase times.
+    pub results: Vec<(String, [f64; 4])>,
+    /// Where and how the run happened; `None` in version 1 files.
+    pub metadata: Option<Metadata>,
+    /// What the per-file times of each phase showed that its total doesn't, when they
+    /// were kept.
+    pub anomalies: Vec<Anomaly>,
+    /// The machine's load, memory, CPU frequency and device temperature around the run.
+    pub conditions: Option<Conditions>,
+}
+
+/// The machine's state just before the strategies ran and just after.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conditions {
+    pub before: Readings,
+    pub after: Readings,
+}
+
+/// Something that stood out in one strategy's phase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub strategy: String,
+    pub phase: String,
+    pub finding: Finding,
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.strategy, self.phase, self.finding)
+    }
+}
+
+/// What a run's results need besides the times to be comparable with another's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub host: Host,
+    pub io_version: String,
+    /// The commit the binary was built from, when built in a git checkout.
+    pub git_commit: Option<String>,
+    /// The options shaping the workload besides its threads, files and size, by name,
+    /// with their values as given on the command line.
+    pub config: Vec<(String, String)>,
+}
+
+impl Metadata {
+    /// This machine and build, running a workload configured as `config`.
+    pub fn current(config: Vec<(String, String)>) -> Metadata {
+        Metadata { host: Host::current(), io_version: env!("CARGO_PKG_VERSION").to_string(), git_commit: option_env!("IO_GIT_COMMIT").map(str::to_string), config }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let host = &self.host;
+        Value::object()
+            .with("hostname", host.hostname.as_deref())
+            .with("os", host.os.as_str())
+            .with("arch", host.arch.as_str())
+            .with("kernel", host.kernel.as_deref())
+            .with("cpu_model", host.cpu_model.as_deref())
+            .with("io_version", self.io_version.as_str())
+            .with("git_commit", self.git_commit.as_deref())
+            .with("config", Value::Object(self.config.iter().map(|(key, value)| (key.clone(), Value::from(value.as_str()))).collect()))
+    }
+
+    fn from_json(value: &Value) -> Option<Metadata> {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        let config = match value.get("config")? {
+            Value::Object(pairs) => pairs.iter().map(|(key, value)| Some((key.clone(), value.as_str()?.to_string()))).collect::<Option<_>>()?,
+            _ => return None,
+        };
+        Some(Metadata {
+            host: Host { hostname: text("hostname"), os: text("os")?, arch: text("arch")?, kernel: text("kernel"), cpu_model: text("cpu_model") },
+            io_version: text("io_version")?,
+            git_commit: text("git_commit"),
+            config,
+        })
+    }
+
+    /// The options set differently here than in `other`, as `name: this vs other`.
+    pub fn config_differences(&self, other: &Metadata) -> Vec<String> {
+        let value = |config: &[(String, String)], key: &str| config.iter().find(|(k, _)| k == key).map_or("(unset)".to_string(), |(_, value)| value.clone());
+        let mut keys: Vec<&String> = self.config.iter().chain(&other.config).map(|(key, _)| key).collect();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .map(|key| (key, value(&self.config, key), value(&other.config, key)))
+            .filter(|(_, here, there)| here != there)
+            .map(|(key, here, there)| format!("{}: {} vs {}", key, here, there))
+            .collect()
+    }
+
+    /// How the machine and build differ from `other`'s, as `name: this vs other`; times
+    /// from different ones can be compared, but with care.
+    pub fn host_differences(&self, other: &Metadata) -> Vec<String> {
+        let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_string());
+        [
+            ("host", show(&self.host.hostname), show(&other.host.hostname)),
+            ("cpu", show(&self.host.cpu_model), show(&other.host.cpu_model)),
+            ("kernel", show(&self.host.kernel), show(&other.host.kernel)),
+            ("io", self.io_version.clone(), other.io_version.clone()),
+            ("commit", show(&self.git_commit), show(&other.git_commit)),
+        ]
+        .into_iter()
+        .filter(|(_, here, there)| here != there)
+        .map(|(key, here, there)| format!("{}: {} vs {}", key, here, there))
+        .collect()
+    }
+}
+
+impl Baseline {
+    /// [`Baseline::targets`] as a JSON array of `{"target", "filesystem"}` objects.
+    pub fn targets_json(&self) -> Value {
+        let targets = self.targets.iter().map(|(target, filesystem)| Value::object().with("target", target.as_str()).with("filesystem", filesystem.as_ref().map(Filesystem::to_json)));
+        Value::from(targets.collect::<Vec<_>>())
+    }
+
+    pub fn to_json(&self) -> Value {
+        let results = self
+            .results
+            .iter()
+            .map(|(label, ms)| PHASES.iter().zip(ms).fold(Value::object().with("strategy", label.as_str()), |v, (phase, ms)| v.with(&format!("{}_ms", phase), *ms)))
+            .collect::<Vec<_>>();
+        Value::object()
+            .with("schema_version", SCHEMA_VERSION)
+            .with("threads", self.threads)
+            .with("files", self.files)
+            .with("size_bytes", self.size)
+            .with("filesystem", self.filesystem.as_ref().map(Filesystem::to_json))
+            .with("targets", self.targets_json())
+            .with("cgroup", self.cgroup.as_ref().map(Limits::to_json))
+            .with("results", results)
+            .with("metadata", self.metadata.as_ref().map(Metadata::to_json))
+            .with(
+                "anomalies",
+                self.anomalies
+                    .iter()
+                    .map(|anomaly| Value::object().with("strategy", anomaly.strategy.as_str()).with("phase", anomaly.phase.as_str()).with("finding", anomaly.finding.to_json()))
+                    .collect::<Vec<_>>(),
+            )
+            .with("conditions", self.conditions.as_ref().map(|conditions| Value::object().with("before", conditions.before.to_json()).with("after", conditions.after.to_json())))
+    }
+
+    fn from_json(value: &Value) -> Option<Baseline> {
+        let field = |key: &str| value.get(key)?.as_u64().map(|n| n as usize);
+        let results = value.get("results")?.as_array()?.iter().map(|result| {
+            let label = result.get("strategy")?.as_str()?.to_string();
+            let mut ms = [0.0; 4];
+            for (slot, phase) in ms.iter_mut().zip(PHASES) {
+                *slot = result.get(&format!("{}_ms", phase))?.as_f64()?;
+            }
+            Some((label, ms))
+        });
+        Some(Baseline {
+            threads: field("threads")?,
+            files: field("files")?,
+            size: field("size_bytes")?,
+            filesystem: value.get("filesystem").and_then(Filesystem::from_json),
+            targets: value
+                .get("targets")
+                .and_then(Value::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|target| Some((target.get("target")?.as_str()?.to_string(), target.get("filesystem").and_then(Filesystem::from_json))))
+                .collect(),
+            cgroup: value.get("cgroup").and_then(Limits::from_json),
+            results: results.collect::<Option<_>>()?,
+            metadata: value.get("metadata").and_then(Metadata::from_json),
+            anomalies: value.get("anomalies").and_then(Value::as_array).unwrap_or_default().iter().filter_map(|anomaly| {
+                Some(Anomaly { strategy: anomaly.get("strategy")?.as_str()?.to_string(), phase: anomaly.get("phase")?.as_str()?.to_string(), finding: Finding::from_json(anomaly.get("finding")?)? })
+            }).collect(),
+            conditions: value.get("conditions").and_then(|conditions| {
+                Some(Conditions { before: Readings::from_json(conditions.get("before")?)?, after: Readings::from_json(conditions.get("after")?)? })
+            }),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, format!("{:#}\n", self.to_json()))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Baseline> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+        let value = json::parse(&fs::read_to_string(path)?).map_err(invalid)?;
+        // Version 1 called its schema version just `version`.
+        let version = value.get("schema_version").or_else(|| value.get("version")).and_then(Value::as_u64);
+        if !matches!(version, Some(1..=SCHEMA_VERSION)) {
+            return Err(invalid(format!("not a baseline of schema version {} or earlier", SCHEMA_VERSION)));
+        }
+        Baseline::from_json(&value).ok_or_else(|| invalid("missing or malformed fields".to_string()))
+    }
+}
+
+/// A regression threshold such as `10%`.
+#[derive(Debug, Clone, Copy)]
+pub struct Percent(pub f64);
+
+impl FromStr for Percent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let value: f64 = s.trim_end_matches('%').parse().map_err(|_| format!("'{}' is not a percentage", s))?;
+        if value < 0.0 {
+            return Err("percentage must not be negative".to_string());
+        }
+        Ok(Percent(value))
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+/// Prints each phase against the baseline and returns how many regressed by more than `threshold`.
+pub fn compare(baseline: &Baseline, current: &[(String, [f64; 4])], threshold: Percent) -> usize {
+    let mut regressions = 0;
+    let mut table = Table::new(["Strategy", "Phase", "Baseline ms", "Current ms", "Change", ""]).align(1, Align::Left).align(5, Align::Left);
+    for (label, ms) in current {
+        let Some((_, base)) = baseline.results.iter().find(|(l, _)| l == label) else {
+            table.row([label.as_str(), "not in baseline"]);
+            continue;
+        };
+        for ((phase, &now), &before) in PHASES.iter().zip(ms).zip(base) {
+            let change = if before > 0.0 { (now / before - 1.0) * 100.0 } else { 0.0 };
+            let regressed = change > threshold.0;
+            regressions += regressed as usize;
+            table.row([
+                label.clone(),
+                phase.to_string(),
+                human::decimal(before, 2),
+                human::decimal(now, 2),
+                human::change(change, 1),
+                if regressed { "REGRESSED" } else { "" }.to_string(),
+            ]);
+        }
+    }
+    println!();
+    print!("{}", table.render());
+    regressions
+}
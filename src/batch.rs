@@ -0,0 +1,288 @@
+//! All-or-nothing changes to a set of files, for build tools whose outputs are only
+//! consistent together. A [`Batch`] records creates, updates and deletes, and
+//! [`Batch::commit`] applies them all or, if any fails, none: every file is back as it
+//! was when the call returns the error.
+//!
+//! Committing first writes every new content to a temporary file beside its target, so
+//! a full disk or a bad path fails before anything changes. It then writes a journal
+//! naming each target, its temporary file and a backup name, and applies the changes in
+//! order: an existing target is renamed to its backup before its new contents are
+//! renamed into place, and a deleted file only goes to its backup. Once all are applied
+//! and their directories synced, removing the journal commits the batch, and only then
+//! are the backups removed. A process that dies before that leaves the journal behind,
+//! with every backup still in place, and [`recover`] rolls its batch back from it.
+//!
+//! Readers see each file change atomically, but not the batch: between the first rename
+//! and the last some files are new and others old. Paths must be valid UTF-8 to go in the
+//! journal.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::atomic::{self, Durability};
+use crate::json::{self, Value};
+
+/// Journal files are named `<prefix><pid>.<n>.json`.
+const JOURNAL_PREFIX: &str = ".io-batch.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Intent {
+    /// Fails if the file exists.
+    Create(Vec<u8>),
+    /// Fails if the file doesn't exist.
+    Update(Vec<u8>),
+    Delete,
+}
+
+/// Changes to apply together; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Batch {
+    journal_dir: PathBuf,
+    durability: Durability,
+    intents: Vec<(PathBuf, Intent)>,
+}
+
+/// One change as committing applies it, and as the journal records it.
+#[derive(Debug, Clone)]
+struct Entry {
+    target: PathBuf,
+    /// The new contents, waiting to be renamed into place.
+    temp: Option<PathBuf>,
+    /// Where the old file goes while the batch is applied.
+    backup: Option<PathBuf>,
+}
+
+/// What a committed batch did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchReport {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub bytes: u64,
+}
+
+impl Batch {
+    /// An empty batch keeping its journal in `journal_dir`, which must be on a disk that
+    /// survives what the batch should survive, and is where [`recover`] looks.
+    pub fn new(journal_dir: impl Into<PathBuf>) -> Batch {
+        Batch { journal_dir: journal_dir.into(), durability: Durability::default(), intents: Vec::new() }
+    }
+
+    /// How far to sync; with [`Durability::Full`] (the default) a committed batch survives
+    /// a crash, and an interrupted one can always be rolled back.
+    pub fn durability(&mut self, durability: Durability) -> &mut Batch {
+        self.durability = durability;
+        self
+    }
+
+    /// Creates `path` with `contents`; the batch fails if it exists.
+    pub fn create(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) -> &mut Batch {
+        self.intents.push((path.into(), Intent::Create(contents.into())));
+        self
+    }
+
+    /// Replaces the contents of `path`; the batch fails if it doesn't exist.
+    pub fn update(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) -> &mut Batch {
+        self.intents.push((path.into(), Intent::Update(contents.into())));
+        self
+    }
+
+    /// Deletes `path`; the batch fails if it doesn't exist.
+    pub fn delete(&mut self, path: impl Into<PathBuf>) -> &mut Batch {
+        self.intents.push((path.into(), Intent::Delete));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.intents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intents.is_empty()
+    }
+
+    /// Applies every change, or none. On error every file is as it was, unless rolling
+    /// back failed too, in which case the journal is kept for [`recover`] and the error
+    /// says so.
+    pub fn commit(&self) -> io::Result<BatchReport> {
+        let mut report = BatchReport::default();
+        let mut entries = Vec::with_capacity(self.intents.len());
+        let staged = self.stage(&mut entries, &mut report);
+        if let Err(e) = staged {
+            remove_temps(&entries);
+            return Err(e);
+        }
+        let journal = match write_journal(&self.journal_dir, &entries, self.durability) {
+            Ok(journal) => journal,
+            Err(e) => {
+                remove_temps(&entries);
+                return Err(e);
+            }
+        };
+        // Rolls back the first `applied` entries and drops the rest, then the journal.
+        let abort = |e: io::Error, applied: usize| match roll_back(&entries[..applied]).and_then(|()| {
+            remove_temps(&entries[applied..]);
+            fs::remove_file(&journal)
+        }) {
+            Ok(()) => e,
+            Err(undo) => io::Error::new(e.kind(), format!("{}; rolling back failed too ({}), {} has what's left to undo", e, undo, journal.display())),
+        };
+        for (applied, entry) in entries.iter().enumerate() {
+            if let Err(e) = apply(entry) {
+                return Err(abort(io::Error::new(e.kind(), format!("{}: {}", entry.target.display(), e)), applied + 1));
+            }
+        }
+        // Removing the journal is what commits the batch; until it's gone a crash rolls the
+        // batch back, which needs every backup.
+        let synced = if self.durability == Durability::Full { sync_dirs(&entries) } else { Ok(()) };
+        if let Err(e) = synced.and_then(|()| fs::remove_file(&journal)) {
+            return Err(abort(e, entries.len()));
+        }
+        if self.durability == Durability::Full {
+            atomic::sync_dir(&self.journal_dir)?;
+        }
+        for entry in &entries {
+            if let Some(backup) = &entry.backup {
+                let _ = fs::remove_file(backup);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Checks every intent and writes the new contents to temporary files, adding an
+    /// entry for each as it goes so a failure can remove what was written.
+    fn stage(&self, entries: &mut Vec<Entry>, report: &mut BatchReport) -> io::Result<()> {
+        let sync = if self.durability == Durability::None { Durability::None } else { Durability::Data };
+        for (target, intent) in &self.intents {
+            if target.to_str().is_none() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} isn't valid UTF-8", target.display())));
+            }
+            if entries.iter().any(|entry| entry.target == *target) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is in the batch twice", target.display())));
+            }
+            let exists = fs::symlink_metadata(target).is_ok();
+            let contents = match intent {
+                Intent::Create(_) if exists => return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} exists", target.display()))),
+                Intent::Update(_) | Intent::Delete if !exists => return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} doesn't exist", target.display()))),
+                Intent::Create(contents) => {
+                    report.created += 1;
+                    Some(contents)
+                }
+                Intent::Update(contents) => {
+                    report.updated += 1;
+                    Some(contents)
+                }
+                Intent::Delete => {
+                    report.deleted += 1;
+                    None
+                }
+            };
+            let backup = if exists { Some(atomic::temp_path(target)?.with_extension("bak")) } else { None };
+            entries.push(Entry { target: target.clone(), temp: None, backup });
+            if let Some(contents) = contents {
+                report.bytes += contents.len() as u64;
+                let temp = atomic::write_temp(target, contents, sync).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", target.display(), e)))?;
+                entries.last_mut().expect("just pushed").temp = Some(temp);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn apply(entry: &Entry) -> io::Result<()> {
+    if let Some(backup) = &entry.backup {
+        fs::rename(&entry.target, backup)?;
+    }
+    if let Some(temp) = &entry.temp {
+        fs::rename(temp, &entry.target)?;
+    }
+    Ok(())
+}
+
+/// Undoes `entries` in reverse, whether or not each was applied, tolerating the state any
+/// point of [`apply`] leaves: a backup present means the old file is restored over
+/// whatever is at the target, and a target whose temporary file is gone and that had no
+/// old file was created by the batch and is removed.
+fn roll_back(entries: &[Entry]) -> io::Result<()> {
+    for entry in entries.iter().rev() {
+        let temp_left = entry.temp.as_ref().is_some_and(|temp| temp.exists());
+        match &entry.backup {
+            Some(backup) if backup.exists() => fs::rename(backup, &entry.target)?,
+            Some(_) => {}
+            None if entry.temp.is_some() && !temp_left && entry.target.exists() => fs::remove_file(&entry.target)?,
+            None => {}
+        }
+        if let Some(temp) = entry.temp.as_ref().filter(|_| temp_left) {
+            fs::remove_file(temp)?;
+        }
+    }
+    Ok(())
+}
+
+/// Syncs each directory the batch changed, once.
+fn sync_dirs(entries: &[Entry]) -> io::Result<()> {
+    let mut dirs: Vec<&Path> = entries.iter().map(|entry| atomic::parent(&entry.target)).collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs.into_iter().try_for_each(atomic::sync_dir)
+}
+
+fn remove_temps(entries: &[Entry]) {
+    for temp in entries.iter().filter_map(|entry| entry.temp.as_ref()) {
+        let _ = fs::remove_file(temp);
+    }
+}
+
+fn write_journal(dir: &Path, entries: &[Entry], durability: Durability) -> io::Result<PathBuf> {
+    static JOURNALS: AtomicU64 = AtomicU64::new(0);
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}{}.{}.json", JOURNAL_PREFIX, std::process::id(), JOURNALS.fetch_add(1, Ordering::Relaxed)));
+    let optional = |path: &Option<PathBuf>| path.as_ref().map_or(Value::Null, |path| Value::from(path.to_string_lossy().into_owned()));
+    let entries = entries
+        .iter()
+        .map(|entry| Value::object().with("target", entry.target.to_string_lossy().into_owned()).with("temp", optional(&entry.temp)).with("backup", optional(&entry.backup)))
+        .collect();
+    let mut file = OpenOptions::new().write(true).create_new(true).open(&path)?;
+    file.write_all(format!("{:#}\n", Value::object().with("entries", Value::Array(entries))).as_bytes())?;
+    if durability == Durability::Full {
+        file.sync_all()?;
+        atomic::sync_dir(dir)?;
+    }
+    Ok(path)
+}
+
+/// Rolls back every batch whose journal is in `journal_dir`, left by a process that died
+/// while committing, and returns how many there were. Don't call it while a batch using
+/// that directory is being committed.
+pub fn recover(journal_dir: &Path) -> io::Result<usize> {
+    let mut recovered = 0;
+    let read = match fs::read_dir(journal_dir) {
+        Ok(read) => read,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    for dir_entry in read {
+        let path = dir_entry?.path();
+        if !path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with(JOURNAL_PREFIX) && name.ends_with(".json")) {
+            continue;
+        }
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+        let journal = json::parse(&fs::read_to_string(&path)?).map_err(invalid)?;
+        let entries = journal
+            .get("entries")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("no entries".to_string()))?
+            .iter()
+            .map(|entry| {
+                let path = |key: &str| entry.get(key).and_then(Value::as_str).map(PathBuf::from);
+                Ok(Entry { target: path("target").ok_or_else(|| invalid("an entry has no target".to_string()))?, temp: path("temp"), backup: path("backup") })
+            })
+            .collect::<io::Result<Vec<Entry>>>()?;
+        roll_back(&entries)?;
+        fs::remove_file(&path)?;
+        recovered += 1;
+    }
+    Ok(recovered)
+}
@@ -0,0 +1,1924 @@
+//! The create/read/update/delete workload and the strategies compared on it.
+
+use std::cell::Cell;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IoSlice, Read, Write};
+use std::os::unix::fs::FileExt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock, mpsc};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::buffers;
+use crate::cache::{self, Hint, Prefetch};
+use crate::cancel;
+use crate::contents;
+use crate::crossover::Thresholds;
+use crate::dirs::{self, Access, Dirs, Resolve};
+use crate::dirty::Backpressure;
+use crate::fdlimit::{self, OpenFileLimiter};
+use crate::golden::{self, Recorder};
+use crate::human;
+use crate::log::{self, Level};
+#[cfg(all(unix, feature = "lmdb"))]
+use crate::lmdb::{self, Lmdb};
+use crate::memory::{self, Memory, PeakSampler};
+use crate::mmap::{self, MmapFile};
+use crate::mobile;
+use crate::names::{self, Names};
+use crate::netfs::{RpcCounter, RpcSample, SimulatedNetwork};
+use crate::pack::Pack;
+use crate::pace::{Pacer, Rate};
+use crate::parquet::{Column, Values};
+use crate::pattern::Generator;
+use crate::pinning;
+use crate::population;
+use crate::platform;
+use crate::progress::Progress;
+use crate::profile::Profiler;
+use crate::queues::{QueueCounter, QueueSample};
+use crate::rundir;
+use crate::rusage::Usage;
+use crate::schedule::PauseGate;
+use crate::space;
+#[cfg(feature = "sqlite")]
+use crate::sqlite::{self, BlobStore};
+use crate::sketch::{Running, TDigest};
+use crate::throttle::Throttle;
+use crate::trace::{OpId, OpScope};
+use crate::watchdog::{OpWatch, Watchdog};
+
+pub const NUM_FILES: usize = 10000;
+const CONTENT: &[u8] = b"initial content padded to simulate dx-check workload....................100 bytes..";
+const UPDATE_CONTENT: &[u8] = b"updated content padded to simulate dx-check workload....................100 bytes..";
+const VECTORED_CHUNKS: usize = 4;
+pub const DEFAULT_RESIDENCY_SAMPLE: usize = 256;
+/// Operations each io_uring ring or tokio batch keeps in flight unless told otherwise.
+pub const DEFAULT_QUEUE_DEPTH: u32 = 64;
+/// Deepest queue `--queue-depth` accepts; io_uring rings hold up to 32768 entries, but each
+/// slot here also holds a buffer.
+pub const MAX_QUEUE_DEPTH: u32 = 4096;
+/// Files the read phase prefetches ahead of each one it reads unless told otherwise.
+pub const DEFAULT_PREFETCH_WINDOW: usize = 64;
+
+/// How many files the phases touch and what gets written to them.
+#[derive(Debug, Clone)]
+pub struct Workload {
+    pub files: usize,
+    /// What the files are called.
+    pub names: Names,
+    content: Vec<u8>,
+    update_content: Vec<u8>,
+}
+
+impl Workload {
+    /// `files` files of `size` bytes each, filled by repeating the default payloads.
+    pub fn new(files: usize, size: usize) -> Workload {
+        let fill = |pattern: &[u8]| pattern.iter().cycle().take(size).copied().collect();
+        Workload {
+            files,
+            names: Names::Ascii,
+            content: fill(CONTENT),
+            update_content: fill(UPDATE_CONTENT),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.content.len()
+    }
+
+    /// The workload with its files named by `names`.
+    pub fn named(mut self, names: Names) -> Workload {
+        self.names = names;
+        self
+    }
+
+    /// The paths of the workload's files in `dir`.
+    pub fn paths(&self, dir: &Path) -> Vec<PathBuf> {
+        names::paths(&[dir.to_path_buf()], self.files, self.names)
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn update_content(&self) -> &[u8] {
+        &self.update_content
+    }
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Workload::new(NUM_FILES, CONTENT.len())
+    }
+}
+
+/// A file whose work panicked, or failed in a phase that tolerates errors; the rest of its
+/// phase still ran.
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub op: OpId,
+    pub phase: &'static str,
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// Collects per-file failures from the worker threads of a phase.
+#[derive(Debug, Default)]
+pub struct FailureLog(Mutex<Vec<Failure>>);
+
+impl FailureLog {
+    pub(crate) fn record(&self, op: OpId, path: &Path, message: String) {
+        let failure = Failure { op, phase: "", path: path.to_path_buf(), message };
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(failure);
+    }
+
+    /// Drains the failures recorded so far, labelling them with `phase`.
+    pub fn take(&self, phase: &'static str) -> Vec<Failure> {
+        let mut failures = std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()));
+        failures.iter_mut().for_each(|failure| failure.phase = phase);
+        failures
+    }
+}
+
+/// How long the operation on one file took.
+#[derive(Debug, Clone)]
+pub struct FileTime {
+    pub op: OpId,
+    pub path: PathBuf,
+    /// The [`OpScope`] it ran in, such as `Smart/update`.
+    pub scope: Arc<str>,
+    /// The rayon worker that ran it.
+    pub worker: usize,
+    pub elapsed: Duration,
+}
+
+/// Per-file wall times, collected when [`Options::file_times`] is set.
+#[derive(Debug, Default)]
+pub struct FileTimes(Mutex<Vec<FileTime>>);
+
+impl FileTimes {
+    fn record(&self, op: OpId, path: &Path, scope: &Arc<str>, elapsed: Duration) {
+        let time = FileTime { op, path: path.to_path_buf(), scope: scope.clone(), worker: rayon::current_thread_index().unwrap_or(0), elapsed };
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(time);
+    }
+
+    /// Drains the times recorded so far.
+    pub fn take(&self) -> Vec<FileTime> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn extend(&self, times: Vec<FileTime>) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).extend(times);
+    }
+}
+
+/// One worker's share of one phase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkerShare {
+    /// The [`OpScope`] of the phase, such as `Smart/update`.
+    pub scope: Arc<str>,
+    /// The rayon worker.
+    pub worker: usize,
+    /// The CPU it last ran a file on.
+    pub cpu: Option<usize>,
+    pub files: u64,
+    /// Time spent on its files, summed.
+    pub busy: Duration,
+}
+
+/// How many files each worker handled in each phase and how long they took it, collected
+/// when [`Options::worker_times`] is set: an imbalance the phase's total time hides, such
+/// as one slow core or a worker far from its memory.
+#[derive(Debug, Default)]
+pub struct WorkerTimes(OnceLock<Vec<Mutex<Vec<WorkerShare>>>>);
+
+impl WorkerTimes {
+    fn record(&self, scope: &Arc<str>, elapsed: Duration) {
+        // A slot per worker of the pool the first file runs in, so workers don't contend.
+        let slots = self.0.get_or_init(|| (0..rayon::current_num_threads()).map(|_| Mutex::default()).collect());
+        let worker = rayon::current_thread_index().unwrap_or(0);
+        let mut shares = slots[worker % slots.len()].lock().unwrap_or_else(|e| e.into_inner());
+        let cpu = pinning::current_cpu();
+        match shares.iter_mut().rev().find(|share| share.worker == worker && share.scope == *scope) {
+            Some(share) => {
+                share.files += 1;
+                share.busy += elapsed;
+                share.cpu = cpu.or(share.cpu);
+            }
+            None => shares.push(WorkerShare { scope: scope.clone(), worker, cpu, files: 1, busy: elapsed }),
+        }
+    }
+
+    /// Drains the shares recorded so far, by worker and then in the order the phases ran.
+    pub fn take(&self) -> Vec<WorkerShare> {
+        let Some(slots) = self.0.get() else { return Vec::new() };
+        let mut shares: Vec<WorkerShare> = slots.iter().flat_map(|slot| std::mem::take(&mut *slot.lock().unwrap_or_else(|e| e.into_inner()))).collect();
+        shares.sort_by_key(|share| share.worker);
+        shares
+    }
+}
+
+/// `times` as Parquet columns, one row per file: `op` and `path_hash` (FNV-1a of the
+/// path), `strategy` and `phase` split from the scope, `worker`, the file's `size` as given
+/// by `size`, and `latency_ns`.
+pub fn file_time_columns(times: &[FileTime], size: impl Fn(&FileTime) -> u64) -> Vec<Column> {
+    let split = |time: &FileTime| {
+        let (strategy, phase) = time.scope.rsplit_once('/').unwrap_or(("", &time.scope));
+        (strategy.to_string(), phase.to_string())
+    };
+    let (strategies, phases) = times.iter().map(split).unzip();
+    vec![
+        Column::new("op", Values::UInt64(times.iter().map(|time| time.op.0).collect())),
+        Column::new("path_hash", Values::UInt64(times.iter().map(|time| population::hash_bytes(time.path.as_os_str().as_encoded_bytes())).collect())),
+        Column::new("strategy", Values::Utf8(strategies)),
+        Column::new("phase", Values::Utf8(phases)),
+        Column::new("worker", Values::Int32(times.iter().map(|time| time.worker as i32).collect())),
+        Column::new("size", Values::Int64(times.iter().map(|time| size(time) as i64).collect())),
+        Column::new("latency_ns", Values::Int64(times.iter().map(|time| time.elapsed.as_nanos() as i64).collect())),
+    ]
+}
+
+/// Per-file latencies of one phase in seconds, summarised in bounded memory.
+#[derive(Debug, Clone, Default)]
+pub struct Latency {
+    pub running: Running,
+    pub digest: TDigest,
+    /// Files that started over an interval behind the [`Options::rate`] schedule.
+    pub late: u64,
+}
+
+/// Streams per-file times into a [`Latency`], collected when [`Options::latency`] is set.
+/// Unlike [`FileTimes`] it stays the same size however many files a phase touches.
+#[derive(Debug, Default)]
+pub struct LatencySketch(Mutex<Latency>);
+
+impl LatencySketch {
+    fn record(&self, elapsed: Duration) {
+        let mut latency = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        latency.running.add(elapsed.as_secs_f64());
+        latency.digest.add(elapsed.as_secs_f64());
+    }
+
+    fn add_late(&self, late: u64) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).late += late;
+    }
+
+    /// The latencies recorded so far, starting afresh.
+    pub fn take(&self) -> Latency {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// How written files are made durable before they count as done.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Leave writeback to the kernel.
+    #[default]
+    None,
+    /// `fdatasync` after writing: data and the metadata needed to read it back.
+    Data,
+    /// `fsync` after writing: data and all metadata.
+    Full,
+}
+
+impl SyncMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            SyncMode::None => "none",
+            SyncMode::Data => "data",
+            SyncMode::Full => "full",
+        }
+    }
+
+    /// Makes what was written to `file` as durable as this mode asks.
+    pub fn sync(self, file: &File) -> io::Result<()> {
+        match self {
+            SyncMode::None => Ok(()),
+            SyncMode::Data => file.sync_data(),
+            SyncMode::Full => file.sync_all(),
+        }
+    }
+}
+
+impl FromStr for SyncMode {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<SyncMode> {
+        match s {
+            "none" => Ok(SyncMode::None),
+            "data" | "fdatasync" => Ok(SyncMode::Data),
+            "full" | "fsync" => Ok(SyncMode::Full),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown sync mode '{}', expected none|data|full", s),
+            )),
+        }
+    }
+}
+
+/// When the update phase writes a mapping's dirty pages back with `msync`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MmapFlush {
+    /// Leave the pages to the kernel's writeback, unless [`Options::sync`] asks for more, so
+    /// the phase's time leaves out writing them.
+    #[default]
+    None,
+    /// `msync(MS_ASYNC)` after each file: writeback is started, not waited for.
+    Async,
+    /// `msync(MS_SYNC)` after each file, waiting for its pages to reach the device.
+    Sync,
+    /// Nothing per file, then `fdatasync` on every file once the phase has mapped them all.
+    EndOfPhase,
+}
+
+impl MmapFlush {
+    pub fn name(self) -> &'static str {
+        match self {
+            MmapFlush::None => "none",
+            MmapFlush::Async => "async",
+            MmapFlush::Sync => "sync",
+            MmapFlush::EndOfPhase => "end",
+        }
+    }
+}
+
+impl FromStr for MmapFlush {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<MmapFlush> {
+        match s {
+            "none" => Ok(MmapFlush::None),
+            "async" => Ok(MmapFlush::Async),
+            "sync" => Ok(MmapFlush::Sync),
+            "end" | "end-of-phase" => Ok(MmapFlush::EndOfPhase),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown mmap flush policy '{}', expected none|async|sync|end", s))),
+        }
+    }
+}
+
+/// Time the workers spent waiting on writeback (`msync`, `fsync` and `fdatasync`) during
+/// the update phase, summed over them.
+#[derive(Debug, Default)]
+pub struct WritebackTime(AtomicU64);
+
+impl WritebackTime {
+    /// Runs `f`, counting its time as writeback.
+    pub(crate) fn time<R>(&self, f: impl FnOnce() -> io::Result<R>) -> io::Result<R> {
+        let start = Instant::now();
+        let result = f();
+        self.0.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        result
+    }
+
+    /// The time counted so far, resetting it.
+    pub fn take(&self) -> Duration {
+        Duration::from_nanos(self.0.swap(0, Ordering::Relaxed))
+    }
+}
+
+/// What a panic in one file's work does to its phase.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnPanic {
+    /// Record it in [`Options::failures`] for that file and let the other workers carry on.
+    #[default]
+    Collect,
+    /// Stop the phase with the panic, naming the file, as its error.
+    Abort,
+}
+
+impl FromStr for OnPanic {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<OnPanic> {
+        match s {
+            "collect" => Ok(OnPanic::Collect),
+            "abort" => Ok(OnPanic::Abort),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown panic action '{}', expected collect|abort", s))),
+        }
+    }
+}
+
+/// How a phase's files are divided among the workers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scheduling {
+    /// rayon's work stealing: idle workers take files from busy ones, in
+    /// [`Options::chunk`]-file pieces at the smallest.
+    #[default]
+    Stealing,
+    /// Each worker gets a fixed share of the path list up front and nothing moves, so
+    /// files next to each other in the list, often adjacent inodes, stay on one worker.
+    /// The shares are equal contiguous runs, or with [`Options::chunk`], runs of that many
+    /// files dealt out in turn.
+    Static,
+}
+
+impl Scheduling {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scheduling::Stealing => "stealing",
+            Scheduling::Static => "static",
+        }
+    }
+}
+
+impl FromStr for Scheduling {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Scheduling> {
+        match s {
+            "stealing" | "dynamic" => Ok(Scheduling::Stealing),
+            "static" => Ok(Scheduling::Static),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown scheduling '{}', expected stealing|static", s))),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub workload: Workload,
+    pub residency_sample: Option<usize>,
+    pub preallocate: bool,
+    pub cold_read: bool,
+    pub madvise: Option<Hint>,
+    pub fadvise: Option<Hint>,
+    pub huge_pages: bool,
+    /// Applied after every file the create and update phases write.
+    pub sync: SyncMode,
+    /// When the update phase flushes the files it maps.
+    pub mmap_flush: MmapFlush,
+    /// Counts the update phase's time spent on writeback.
+    pub writeback: WritebackTime,
+    pub thresholds: Thresholds,
+    pub open_files: Arc<OpenFileLimiter>,
+    pub failures: FailureLog,
+    /// Allocate a new buffer for every read instead of reusing one per thread.
+    pub fresh_buffers: bool,
+    /// Time the plain read path with and without buffer reuse after the read phase.
+    pub compare_buffers: bool,
+    /// Poll the process's memory from a thread while each strategy phase runs, for its
+    /// peak RSS and mapping count.
+    pub sample_memory: bool,
+    /// Checked before each file; pausing it holds workers between files.
+    pub pause: Arc<PauseGate>,
+    /// Advanced after each file.
+    pub progress: Arc<Progress>,
+    /// Time every file individually, for breakdowns by directory or size.
+    pub file_times: Option<Arc<FileTimes>>,
+    /// Summarise per-file times in fixed-size sketches, for runs that can't keep them all.
+    pub latency: Option<Arc<LatencySketch>>,
+    /// Write per-file contents from a seed instead of repeating the default payloads; the
+    /// update phase writes the generator's next version.
+    pub generator: Option<Generator>,
+    /// Check during the read phase that every file holds what the create phase wrote.
+    pub verify: bool,
+    /// Scopes the [`OpId`]s of failures and file times.
+    pub op_scope: OpScope,
+    /// Counts completions per hardware queue of the device under test around each phase.
+    pub queues: Option<QueueCounter>,
+    /// Open files relative to their directory's descriptor instead of by full path.
+    pub open_at: Option<Resolve>,
+    /// Record every file's backend decision and outcome for a golden run.
+    pub golden: Option<Arc<Recorder>>,
+    /// Watches every file operation and phase for hangs.
+    pub watchdog: Option<Arc<Watchdog>>,
+    /// Start files at this fixed rate instead of as fast as the workers go, measuring
+    /// [`Options::latency`] from each file's intended start.
+    pub rate: Option<Rate>,
+    /// Bandwidth and IOPS limits shared by every worker.
+    pub throttle: Option<Arc<Throttle>>,
+    /// A round trip to charge before every metadata operation, as on a network filesystem.
+    pub network: Option<Arc<SimulatedNetwork>>,
+    /// Counts the RPCs of the NFS mount under test around each phase.
+    pub rpcs: Option<RpcCounter>,
+    /// Watches dirty pages during the write phases and, above its ceiling, paces the
+    /// workers or starts writeback early.
+    pub backpressure: Option<Arc<Backpressure>>,
+    /// Operations the async backends keep in flight per ring or runtime, and opened files
+    /// the pipelined create queues for its writers, instead of [`DEFAULT_QUEUE_DEPTH`].
+    pub queue_depth: Option<u32>,
+    /// How each phase divides its files among the workers.
+    pub scheduling: Scheduling,
+    /// Files per piece of work: the smallest piece stolen, or the run dealt out statically.
+    pub chunk: Option<usize>,
+    /// Have a thread start reading the next files into the page cache while the read
+    /// phase works on the current ones.
+    pub prefetch: Option<Prefetch>,
+    /// How far ahead of each file being read to prefetch, instead of
+    /// [`DEFAULT_PREFETCH_WINDOW`].
+    pub prefetch_window: Option<usize>,
+    /// Record a flamegraph of every phase each strategy runs.
+    pub profiler: Option<Arc<Profiler>>,
+    /// Whether a file whose work panics is a failure to collect or the end of its phase.
+    pub on_panic: OnPanic,
+    /// Count each worker's files and time per phase.
+    pub worker_times: Option<Arc<WorkerTimes>>,
+    /// Stops this run's phases; see [`cancel`].
+    pub cancel: cancel::Token,
+}
+
+impl Options {
+    /// The same settings for a different workload, sharing the open-file budget and pause
+    /// gate but with failure and file-time logs of its own, for phases that run side by side.
+    pub fn with_workload(&self, workload: Workload) -> Options {
+        Options {
+            workload,
+            residency_sample: self.residency_sample,
+            preallocate: self.preallocate,
+            cold_read: self.cold_read,
+            madvise: self.madvise,
+            fadvise: self.fadvise,
+            huge_pages: self.huge_pages,
+            sync: self.sync,
+            mmap_flush: self.mmap_flush,
+            writeback: WritebackTime::default(),
+            thresholds: self.thresholds,
+            open_files: self.open_files.clone(),
+            failures: FailureLog::default(),
+            fresh_buffers: self.fresh_buffers,
+            compare_buffers: self.compare_buffers,
+            sample_memory: self.sample_memory,
+            pause: self.pause.clone(),
+            progress: self.progress.clone(),
+            file_times: self.file_times.as_ref().map(|_| Arc::default()),
+            latency: self.latency.as_ref().map(|_| Arc::default()),
+            generator: self.generator,
+            // Phases of a workload may read files after another phase rewrote them.
+            verify: false,
+            op_scope: OpScope::default(),
+            queues: self.queues.clone(),
+            open_at: self.open_at,
+            golden: self.golden.clone(),
+            watchdog: self.watchdog.clone(),
+            rate: self.rate,
+            throttle: self.throttle.clone(),
+            network: self.network.clone(),
+            rpcs: self.rpcs.clone(),
+            backpressure: self.backpressure.clone(),
+            queue_depth: self.queue_depth,
+            scheduling: self.scheduling,
+            chunk: self.chunk,
+            prefetch: self.prefetch,
+            prefetch_window: self.prefetch_window,
+            profiler: self.profiler.clone(),
+            on_panic: self.on_panic,
+            worker_times: self.worker_times.clone(),
+            cancel: self.cancel.clone(),
+        }
+    }
+
+    pub fn queue_depth(&self) -> u32 {
+        self.queue_depth.unwrap_or(DEFAULT_QUEUE_DEPTH)
+    }
+
+    pub fn prefetch_window(&self) -> usize {
+        self.prefetch_window.unwrap_or(DEFAULT_PREFETCH_WINDOW)
+    }
+}
+
+/// This run's benchmark directory in the temp directory, or an Android app's cache
+/// directory (see [`crate::mobile::temp_dir`]), unique to the process.
+pub fn get_dir() -> PathBuf {
+    rundir::run_dir(&mobile::temp_dir())
+}
+
+thread_local! {
+    /// The backend a strategy picked for the file this thread is working on.
+    static DECISION: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// Notes that the current file is handled by `backend`, for [`Options::golden`].
+fn decide(backend: &'static str) {
+    DECISION.set(Some(backend));
+}
+
+/// Runs `f` on every path in parallel while holding an open-file slot, so no phase can
+/// exceed the descriptor budget. A panic in `f` is caught rather than tearing down the
+/// whole phase, and recorded in `options.failures` for that path or returned as the
+/// phase's error as `options.on_panic` says.
+pub(crate) fn each_file<F>(paths: &[PathBuf], options: &Options, f: F) -> io::Result<()>
+where
+    F: Fn(&PathBuf) -> io::Result<()> + Sync + Send,
+{
+    each_indexed(paths, options, |_, path| f(path))
+}
+
+/// [`each_file`], also passing each path's index in `paths`.
+pub(crate) fn each_indexed<F>(paths: &[PathBuf], options: &Options, f: F) -> io::Result<()>
+where
+    F: Fn(usize, &PathBuf) -> io::Result<()> + Sync + Send,
+{
+    options.progress.begin(paths.len());
+    let scope: Arc<str> = options.op_scope.get().into();
+    let finished = AtomicUsize::new(0);
+    let _phase = options.watchdog.as_ref().map(|watchdog| watchdog.phase(&scope));
+    let pacer = options.rate.map(Pacer::new);
+    let traced = log::enabled(Level::Trace);
+    let decisions = options.golden.is_some() || traced;
+    let each = |(index, path): (usize, &PathBuf)| {
+        options.pause.wait();
+        if options.cancel.is_cancelled() {
+            return Err(cancel::cancelled());
+        }
+        let _permit = options.open_files.acquire();
+        let intended = pacer.as_ref().map(Pacer::next);
+        if let Some(throttle) = &options.throttle {
+            throttle.op();
+        }
+        if let Some(backpressure) = &options.backpressure {
+            backpressure.before(&scope);
+        }
+        let watch = options.watchdog.as_ref().map(|watchdog| watchdog.op(&scope, path));
+        let start = (options.file_times.is_some() || options.latency.is_some() || options.worker_times.is_some() || traced).then(Instant::now);
+        if decisions {
+            DECISION.set(None);
+        }
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| f(index, path)));
+        if let (Some(backpressure), Ok(Ok(()))) = (&options.backpressure, &outcome) {
+            backpressure.after(path);
+        }
+        if let Some(elapsed) = watch.and_then(OpWatch::finish) {
+            options.failures.record(OpId::new(&scope, path), path, format!("timed out: took {}", human::duration(elapsed)));
+        }
+        let decision = if decisions { DECISION.take() } else { None };
+        if let Some(golden) = &options.golden {
+            golden.record(&scope, index, decision, golden::outcome(outcome.as_ref().ok()));
+        }
+        if traced {
+            let mut fields = vec![("scope", scope.to_string()), ("path", path.display().to_string())];
+            fields.extend(decision.map(|backend| ("backend", backend.to_string())));
+            fields.extend(start.map(|start| ("elapsed", human::duration(start.elapsed()))));
+            log::event(Level::Trace, "bench", "file done", &fields);
+        }
+        if let Some(start) = start {
+            let elapsed = start.elapsed();
+            if let Some(times) = &options.file_times {
+                times.record(OpId::new(&scope, path), path, &scope, elapsed);
+            }
+            if let Some(latency) = &options.latency {
+                latency.record(intended.map_or(elapsed, |intended| intended.elapsed()));
+            }
+            if let Some(workers) = &options.worker_times {
+                workers.record(&scope, elapsed);
+            }
+        }
+        let result = match outcome {
+            Ok(Err(e)) if space::is_no_space(&e) => {
+                // What the file holds is partial; freeing it gives the rest of the machine
+                // back some room. The phase stops here.
+                let _ = fs::remove_file(path);
+                let done = finished.load(Ordering::Relaxed);
+                Err(io::Error::new(e.kind(), format!("{} writing {} after {} of {} files of {}; removed the partial file", e, path.display(), done, paths.len(), scope)))
+            }
+            Ok(result) => result.map_err(|e| fdlimit::explain(e, &options.open_files)),
+            Err(payload) => match options.on_panic {
+                OnPanic::Collect => {
+                    options.failures.record(OpId::new(&scope, path), path, contents::panic_message(&*payload));
+                    Ok(())
+                }
+                OnPanic::Abort => Err(io::Error::other(format!("{} panicked: {}", path.display(), contents::panic_message(&*payload)))),
+            },
+        };
+        finished.fetch_add(1, Ordering::Relaxed);
+        options.progress.advance();
+        result
+    };
+    let result = match options.scheduling {
+        Scheduling::Stealing => paths.par_iter().enumerate().with_min_len(options.chunk.unwrap_or(1)).try_for_each(each),
+        Scheduling::Static => each_static(paths, options.chunk, each),
+    };
+    if let (Some(pacer), Some(latency)) = (&pacer, &options.latency) {
+        latency.add_late(pacer.late());
+    }
+    // Files skipped after an error still count as done, so the next phase starts from zero.
+    options.progress.skip(paths.len() - finished.into_inner());
+    result
+}
+
+/// Runs `f` on every path with [`Scheduling::Static`]: worker `k` of the current pool takes
+/// runs `k`, `k + workers`, and so on, each of `chunk` files or an equal share, and works
+/// through them in order. Every worker stops at the first error any of them hits.
+fn each_static<F>(paths: &[PathBuf], chunk: Option<usize>, f: F) -> io::Result<()>
+where
+    F: Fn((usize, &PathBuf)) -> io::Result<()> + Sync,
+{
+    let workers = rayon::current_num_threads();
+    let chunk = chunk.unwrap_or(paths.len().div_ceil(workers)).max(1);
+    let failed = AtomicBool::new(false);
+    let results = rayon::broadcast(|ctx| {
+        for run in (ctx.index() * chunk..paths.len()).step_by(workers * chunk) {
+            for (index, path) in paths.iter().enumerate().take(run + chunk).skip(run) {
+                if failed.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+                f((index, path)).inspect_err(|_| failed.store(true, Ordering::Relaxed))?;
+            }
+        }
+        Ok(())
+    });
+    results.into_iter().collect()
+}
+
+/// Calls `f` with what file `index` holds after the create phase, or after the update
+/// phase when `update` is set, once [`Options::throttle`] lets that much be written.
+pub(crate) fn with_content<R>(options: &Options, index: usize, update: bool, f: impl FnOnce(&[u8]) -> R) -> R {
+    if let Some(throttle) = &options.throttle {
+        throttle.bytes(options.workload.size() as u64);
+    }
+    let Some(generator) = options.generator else {
+        return f(if update { options.workload.update_content() } else { options.workload.content() });
+    };
+    let generator = if update { generator.next_version() } else { generator };
+    buffers::with_buffer(|buf| {
+        buf.resize(options.workload.size(), 0);
+        generator.fill(index as u64, buf);
+        f(buf)
+    })
+}
+
+/// Counts `bytes`, just read from file `index`, against [`Options::throttle`] and checks
+/// them with [`verify_content`].
+pub(crate) fn read_done(options: &Options, index: usize, path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(throttle) = &options.throttle {
+        throttle.bytes(bytes.len() as u64);
+    }
+    verify_content(options, index, path, bytes)
+}
+
+/// With `options.verify`, fails unless `bytes` are what the create phase wrote to file `index`.
+pub(crate) fn verify_content(options: &Options, index: usize, path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if !options.verify {
+        return Ok(());
+    }
+    let matches = match options.generator {
+        Some(generator) => bytes.len() == options.workload.size() && generator.matches(index as u64, bytes),
+        None => bytes == options.workload.content(),
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} does not hold the contents written to it", path.display())))
+    }
+}
+
+/// Reserves `len` bytes of disk space up front so the following write doesn't have to allocate extents.
+fn preallocate(file: &File, len: usize) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    if len > 0 {
+        // fallocate refuses an empty range, and an empty file needs no extents.
+        use std::os::unix::io::AsRawFd;
+
+        let result = unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, len as libc::off_t) };
+        if result != 0 {
+            let err = io::Error::last_os_error();
+            // Filesystems without fallocate support simply allocate on write.
+            if err.raw_os_error() != Some(libc::EOPNOTSUPP) {
+                return Err(err);
+            }
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = (file, len);
+    Ok(())
+}
+
+/// Charges one round trip of [`Options::network`], if it is set.
+fn round_trip(options: &Options) {
+    if let Some(network) = &options.network {
+        network.round_trip();
+    }
+}
+
+/// Opens `path` as [`dirs::open_file`] does, after a round trip of [`Options::network`].
+fn open_file(options: &Options, dirs: Option<&Dirs>, path: &Path, access: Access) -> io::Result<File> {
+    round_trip(options);
+    dirs::open_file(dirs, path, access)
+}
+
+/// The directories of `paths` opened once, when the run opens files relative to them.
+pub(crate) fn open_dirs(paths: &[PathBuf], options: &Options) -> io::Result<Option<Dirs>> {
+    options.open_at.map(|resolve| Dirs::open(paths).map(|dirs| dirs.resolve(resolve))).transpose()
+}
+
+fn create_files_with(paths: &[PathBuf], options: &Options, preallocate_space: bool) -> io::Result<()> {
+    let dirs = open_dirs(paths, options)?;
+    each_indexed(paths, options, |index, path| {
+        let file = open_file(options, dirs.as_ref(), path, Access::Create)?;
+        if preallocate_space {
+            preallocate(&file, options.workload.size())?;
+        }
+        let mut writer = BufWriter::new(file);
+        with_content(options, index, false, |content| writer.write_all(content))?;
+        writer.flush()?;
+        options.sync.sync(writer.get_ref())
+    })
+}
+
+pub(crate) fn create_files(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    create_files_with(paths, options, options.preallocate)
+}
+
+fn create_files_preallocated(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    create_files_with(paths, options, true)
+}
+
+/// Calls `f` with a pooled buffer, or a freshly allocated one when pooling is disabled.
+fn with_read_buffer<R>(fresh: bool, f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    if fresh { f(&mut Vec::new()) } else { buffers::with_buffer(f) }
+}
+
+/// Tells the prefetching thread, if any, which file a worker is starting.
+struct Ahead<'a> {
+    started: Option<(&'a [AtomicBool], mpsc::Sender<usize>)>,
+}
+
+impl Ahead<'_> {
+    fn start(&self, index: usize) {
+        if let Some((claimed, sender)) = &self.started {
+            claimed[index].store(true, Ordering::Relaxed);
+            let _ = sender.send(index);
+        }
+    }
+}
+
+/// Runs the read phase `read` with [`Options::prefetch`]: as each worker starts a file, a
+/// thread prefetches the [`Options::prefetch_window`] files after it that no one has
+/// started or prefetched yet. Workers take runs of consecutive files, so those are the ones
+/// they read next. Prefetch errors are left for the reads to report.
+fn prefetching(paths: &[PathBuf], options: &Options, read: impl FnOnce(&Ahead) -> io::Result<()>) -> io::Result<()> {
+    let Some(how) = options.prefetch else {
+        return read(&Ahead { started: None });
+    };
+    let window = options.prefetch_window();
+    let claimed: Vec<AtomicBool> = paths.iter().map(|_| AtomicBool::new(false)).collect();
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel::<usize>();
+        let claimed = &claimed;
+        scope.spawn(move || {
+            for index in receiver {
+                for next in index + 1..paths.len().min(index + 1 + window) {
+                    if !claimed[next].swap(true, Ordering::Relaxed)
+                        && let Ok(file) = File::open(&paths[next])
+                    {
+                        let _ = cache::prefetch(&file, how);
+                    }
+                }
+            }
+        });
+        read(&Ahead { started: Some((claimed, sender)) })
+    })
+}
+
+fn read_files_with(paths: &[PathBuf], options: &Options, fresh_buffers: bool) -> io::Result<()> {
+    prefetching(paths, options, |ahead| read_files_ahead(paths, options, fresh_buffers, ahead))
+}
+
+fn read_files_ahead(paths: &[PathBuf], options: &Options, fresh_buffers: bool, ahead: &Ahead) -> io::Result<()> {
+    let dirs = open_dirs(paths, options)?;
+    each_indexed(paths, options, |index, path| {
+        ahead.start(index);
+        let mut file = open_file(options, dirs.as_ref(), path, Access::Read)?;
+        if let Some(hint) = options.fadvise {
+            cache::fadvise(&file, hint)?;
+        }
+        with_read_buffer(fresh_buffers, |buf| {
+            file.read_to_end(buf)?;
+            read_done(options, index, path, buf)
+        })
+    })
+}
+
+fn read_files(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    read_files_with(paths, options, options.fresh_buffers)
+}
+
+/// How the read phase gets at a file's bytes, the counterpart of [`UpdateMethod`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadMethod {
+    /// `read(2)` into a buffer.
+    Read,
+    /// Map the file and copy the mapping into a buffer, as callers that need the bytes
+    /// owned do.
+    Mmap,
+    /// Map the file and touch one byte per page, faulting every page in without copying.
+    MmapTouch,
+}
+
+impl ReadMethod {
+    pub const ALL: [ReadMethod; 3] = [ReadMethod::Read, ReadMethod::Mmap, ReadMethod::MmapTouch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ReadMethod::Read => "read",
+            ReadMethod::Mmap => "mmap",
+            ReadMethod::MmapTouch => "mmap+touch",
+        }
+    }
+}
+
+impl fmt::Display for ReadMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ReadMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        ReadMethod::ALL.into_iter().find(|method| method.name() == s).ok_or_else(|| "expected read, mmap or mmap+touch".to_string())
+    }
+}
+
+/// Reads every file of `paths` using `method`, prefetching as [`Options::prefetch`] says.
+/// Mappings get [`Options::madvise`] before they are read, so `willneed` starts readahead
+/// for the whole file up front.
+pub fn read_files_by(paths: &[PathBuf], options: &Options, method: ReadMethod) -> io::Result<()> {
+    if method == ReadMethod::Read {
+        return read_files(paths, options);
+    }
+    let dirs = open_dirs(paths, options)?;
+    let page = cache::page_size();
+    prefetching(paths, options, |ahead| each_indexed(paths, options, |index, path| {
+        ahead.start(index);
+        let file = open_file(options, dirs.as_ref(), path, Access::Read)?;
+        let map = unsafe { Mmap::map(&file)? };
+        if let Some(hint) = options.madvise {
+            cache::madvise(&map, hint)?;
+        }
+        if method == ReadMethod::MmapTouch {
+            std::hint::black_box(map.iter().step_by(page).fold(0u8, |sum, &byte| sum.wrapping_add(byte)));
+            return read_done(options, index, path, &map);
+        }
+        with_read_buffer(options.fresh_buffers, |buf| {
+            buf.extend_from_slice(&map);
+            read_done(options, index, path, buf)
+        })
+    }))
+}
+
+fn read_files_adaptively(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let dirs = open_dirs(paths, options)?;
+    each_indexed(paths, options, |index, path| {
+        let mut file = open_file(options, dirs.as_ref(), path, Access::Read)?;
+        if options.thresholds.use_mmap_read(file.metadata()?.len()) {
+            decide("mmap");
+            let map = unsafe { Mmap::map(&file)? };
+            with_read_buffer(options.fresh_buffers, |buf| {
+                buf.extend_from_slice(&map);
+                read_done(options, index, path, buf)
+            })
+        } else {
+            decide("read");
+            with_read_buffer(options.fresh_buffers, |buf| {
+                file.read_to_end(buf)?;
+                read_done(options, index, path, buf)
+            })
+        }
+    })
+}
+
+/// How the update phase overwrites a file that already exists. They differ as much in what
+/// a crash part-way through leaves behind as in speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateMethod {
+    /// Truncate, then write the new content from the start, buffered.
+    Truncate,
+    /// Write the new content over the old at offset 0 with positioned writes, trimming only
+    /// a longer tail.
+    Pwrite,
+    /// Map the file and copy the new content into the mapping, flushed as
+    /// [`Options::mmap_flush`] says.
+    Mmap,
+    /// As [`UpdateMethod::Mmap`], always with `msync` before the file counts as done.
+    MmapFlush,
+}
+
+impl UpdateMethod {
+    pub const ALL: [UpdateMethod; 4] = [UpdateMethod::Truncate, UpdateMethod::Pwrite, UpdateMethod::Mmap, UpdateMethod::MmapFlush];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            UpdateMethod::Truncate => "truncate",
+            UpdateMethod::Pwrite => "pwrite",
+            UpdateMethod::Mmap => "mmap",
+            UpdateMethod::MmapFlush => "mmap+flush",
+        }
+    }
+
+    /// What a crash before the update is synced can leave in the file.
+    pub fn crash_leaves(self) -> &'static str {
+        match self {
+            UpdateMethod::Truncate => "empty or short file",
+            UpdateMethod::Pwrite => "old and new blocks mixed, full length",
+            UpdateMethod::Mmap => "any pages old or new, until the kernel writes back",
+            UpdateMethod::MmapFlush => "old and new pages mixed only if mid-msync",
+        }
+    }
+}
+
+impl fmt::Display for UpdateMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for UpdateMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        UpdateMethod::ALL.into_iter().find(|method| method.name() == s).ok_or_else(|| "expected truncate, pwrite, mmap or mmap+flush".to_string())
+    }
+}
+
+/// Overwrites every file of `paths` with its updated content using `method`, counting
+/// the time spent syncing and flushing in [`Options::writeback`].
+pub fn update_files(paths: &[PathBuf], options: &Options, method: UpdateMethod) -> io::Result<()> {
+    let dirs = open_dirs(paths, options)?;
+    each_indexed(paths, options, |index, path| match method {
+        UpdateMethod::Truncate => {
+            let file = open_file(options, dirs.as_ref(), path, Access::Truncate)?;
+            let mut writer = BufWriter::new(file);
+            with_content(options, index, true, |content| writer.write_all(content))?;
+            writer.flush()?;
+            options.writeback.time(|| options.sync.sync(writer.get_ref()))
+        }
+        UpdateMethod::Pwrite => {
+            let file = open_file(options, dirs.as_ref(), path, Access::ReadWrite)?;
+            let len = with_content(options, index, true, |content| file.write_all_at(content, 0).map(|()| content.len() as u64))?;
+            if file.metadata()?.len() > len {
+                file.set_len(len)?;
+            }
+            options.writeback.time(|| options.sync.sync(&file))
+        }
+        UpdateMethod::Mmap | UpdateMethod::MmapFlush => {
+            let mut mapped = MmapFile::new(open_file(options, dirs.as_ref(), path, Access::ReadWrite)?)?;
+            with_content(options, index, true, |content| mapped.replace(content))?;
+            let flush = if method == UpdateMethod::MmapFlush { MmapFlush::Sync } else { options.mmap_flush };
+            flush_mapped(&mapped, flush, options)
+        }
+    })?;
+    if method == UpdateMethod::Mmap {
+        flush_mapped_phase(paths, options)?;
+    }
+    Ok(())
+}
+
+fn update_files_traditionally(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    update_files(paths, options, UpdateMethod::Truncate)
+}
+
+#[derive(Default)]
+struct HugePageStats {
+    small: AtomicUsize,
+    refused: AtomicUsize,
+    advised: AtomicUsize,
+    backed: AtomicUsize,
+}
+
+impl HugePageStats {
+    fn report(&self) {
+        println!(
+            "Huge pages: {} mappings advised, {} backed by huge pages, {} refused, {} smaller than {} KiB",
+            self.advised.load(Ordering::Relaxed),
+            self.backed.load(Ordering::Relaxed),
+            self.refused.load(Ordering::Relaxed),
+            self.small.load(Ordering::Relaxed),
+            mmap::huge_page_size() / 1024
+        );
+    }
+}
+
+fn update_files_smartly(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let len = options.workload.size();
+    let huge_pages = HugePageStats::default();
+    let dirs = open_dirs(paths, options)?;
+    each_indexed(paths, options, |index, path| {
+        let mut mapped = MmapFile::new(open_file(options, dirs.as_ref(), path, Access::ReadWrite)?)?;
+        mapped.set_len(len as u64)?;
+        if let Some(hint) = options.madvise {
+            cache::madvise(mapped.as_slice(), hint)?;
+        }
+        let advised = options.huge_pages && mmap::advise_huge_pages(mapped.as_slice());
+        if options.huge_pages {
+            let counter = match (advised, len < mmap::huge_page_size()) {
+                (true, _) => &huge_pages.advised,
+                (false, true) => &huge_pages.small,
+                (false, false) => &huge_pages.refused,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+        with_content(options, index, true, |content| mapped.replace(content))?;
+        if advised && mmap::huge_page_bytes(mapped.as_slice())? > 0 {
+            huge_pages.backed.fetch_add(1, Ordering::Relaxed);
+        }
+        flush_mapped(&mapped, options.mmap_flush, options)
+    })?;
+    flush_mapped_phase(paths, options)?;
+    if options.huge_pages {
+        huge_pages.report();
+    }
+    Ok(())
+}
+
+/// Writes a dirty mapping back as `flush` says, and with `msync` before syncing the file
+/// whenever [`Options::sync`] asks for it, counting the time in [`Options::writeback`].
+fn flush_mapped(mapped: &MmapFile, flush: MmapFlush, options: &Options) -> io::Result<()> {
+    options.writeback.time(|| {
+        match (flush, options.sync) {
+            (MmapFlush::Sync, _) | (_, SyncMode::Data | SyncMode::Full) => mapped.flush()?,
+            (MmapFlush::Async, SyncMode::None) => mapped.flush_async()?,
+            (MmapFlush::None | MmapFlush::EndOfPhase, SyncMode::None) => {}
+        }
+        options.sync.sync(mapped.file())
+    })
+}
+
+/// With [`MmapFlush::EndOfPhase`], writes back every file of `paths` the phase mapped, in
+/// parallel, once all are written.
+fn flush_mapped_phase(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    if options.mmap_flush != MmapFlush::EndOfPhase {
+        return Ok(());
+    }
+    options.writeback.time(|| paths.par_iter().try_for_each(|path| File::open(path)?.sync_data()))
+}
+
+fn update_files_adaptively(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let len = options.workload.size() as u64;
+    let dirs = open_dirs(paths, options)?;
+    each_indexed(paths, options, |index, path| {
+        if options.thresholds.use_mmap_update(len) {
+            decide("mmap");
+            let mut mapped = MmapFile::new(open_file(options, dirs.as_ref(), path, Access::ReadWrite)?)?;
+            with_content(options, index, true, |content| mapped.replace(content))?;
+            flush_mapped(&mapped, options.mmap_flush, options)
+        } else {
+            decide("write");
+            let mut file = open_file(options, dirs.as_ref(), path, Access::Truncate)?;
+            with_content(options, index, true, |content| file.write_all(content))?;
+            options.writeback.time(|| options.sync.sync(&file))
+        }
+    })?;
+    if options.thresholds.use_mmap_update(len) {
+        flush_mapped_phase(paths, options)?;
+    }
+    Ok(())
+}
+
+fn write_all_vectored(file: &mut File, content: &[u8]) -> io::Result<()> {
+    // Whole cache lines per chunk, so no line of an aligned buffer is split between two.
+    // At least one line, so an empty file isn't cut into chunks of nothing.
+    let chunk_len = platform::align_to_cache_line(content.len().div_ceil(VECTORED_CHUNKS).max(1));
+    let mut slices: Vec<IoSlice<'_>> = content.chunks(chunk_len).map(IoSlice::new).collect();
+    let mut bufs = &mut slices[..];
+    while !bufs.is_empty() {
+        match file.write_vectored(bufs)? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            n => IoSlice::advance_slices(&mut bufs, n),
+        }
+    }
+    Ok(())
+}
+
+fn create_files_vectored(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let dirs = open_dirs(paths, options)?;
+    each_indexed(paths, options, |index, path| {
+        let mut file = open_file(options, dirs.as_ref(), path, Access::Create)?;
+        if options.preallocate {
+            preallocate(&file, options.workload.size())?;
+        }
+        with_content(options, index, false, |content| write_all_vectored(&mut file, content))?;
+        options.sync.sync(&file)
+    })
+}
+
+fn update_files_vectored(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let dirs = open_dirs(paths, options)?;
+    each_indexed(paths, options, |index, path| {
+        let mut file = open_file(options, dirs.as_ref(), path, Access::Truncate)?;
+        with_content(options, index, true, |content| write_all_vectored(&mut file, content))?;
+        options.writeback.time(|| options.sync.sync(&file))
+    })
+}
+
+/// Unlinks every file relative to its open parent directory, sparing a full path lookup
+/// per file.
+pub(crate) fn delete_files(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let dirs = Dirs::open(paths)?;
+    each_file(paths, options, |path| {
+        round_trip(options);
+        dirs.unlink(path)
+    })
+}
+
+/// The pack file the pack strategy keeps the files of a run in, named after the first.
+fn pack_path(first: &Path) -> PathBuf {
+    container_path(first, "pack")
+}
+
+/// The file beside `first` a container strategy stores a run in: its name with `extension`
+/// for its own, kept within [`names::MAX_NAME`] with room for the `-journal` SQLite keeps
+/// beside a database.
+fn container_path(first: &Path, extension: &str) -> PathBuf {
+    const JOURNAL: &str = "-journal";
+    let journal = names::sibling(&first.with_extension(""), "", &format!(".{}{}", extension, JOURNAL));
+    let name = journal.file_name().unwrap_or_default().to_string_lossy();
+    journal.with_file_name(name.strip_suffix(JOURNAL).unwrap_or(&name))
+}
+
+/// The name a file is stored under in a pack or database.
+fn pack_name(path: &Path) -> io::Result<&str> {
+    path.file_name().and_then(|name| name.to_str()).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no name to pack it by", path.display())))
+}
+
+fn create_packed(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    round_trip(options);
+    let pack = Pack::create(pack_path(first))?;
+    each_indexed(paths, options, |index, path| {
+        with_content(options, index, false, |content| pack.put(pack_name(path)?, content))?;
+        options.sync.sync(pack.file())
+    })
+}
+
+fn read_packed(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    round_trip(options);
+    let pack = Pack::open_read_only(pack_path(first))?;
+    each_indexed(paths, options, |index, path| {
+        with_read_buffer(options.fresh_buffers, |buf| {
+            if !pack.get_into(pack_name(path)?, buf)? {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not in {}", path.display(), pack.path().display())));
+            }
+            read_done(options, index, path, buf)
+        })
+    })
+}
+
+fn update_packed(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    round_trip(options);
+    let pack = Pack::open(pack_path(first))?;
+    each_indexed(paths, options, |index, path| {
+        with_content(options, index, true, |content| pack.put(pack_name(path)?, content))?;
+        options.sync.sync(pack.file())
+    })
+}
+
+/// Deletes every file with a tombstone, then the pack itself once it holds none.
+fn delete_packed(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    round_trip(options);
+    let pack = Pack::open(pack_path(first))?;
+    each_file(paths, options, |path| match pack.delete(pack_name(path)?)? {
+        true => Ok(()),
+        false => Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not in {}", path.display(), pack.path().display()))),
+    })?;
+    if pack.is_empty() {
+        fs::remove_file(pack.path())?;
+    }
+    Ok(())
+}
+
+/// Rows written per transaction by the batched SQLite strategy.
+#[cfg(feature = "sqlite")]
+pub const SQLITE_BATCH: usize = 1000;
+
+/// The database the SQLite strategies keep the files of a run in, named after the first.
+#[cfg(feature = "sqlite")]
+fn sqlite_path(first: &Path) -> PathBuf {
+    container_path(first, "sqlite")
+}
+
+/// Applies `write` to file `index` of `paths` in turn through one connection, since SQLite
+/// has one writer at a time anyway, committing every `batch` files or, for `batch` 1,
+/// every file on its own.
+#[cfg(feature = "sqlite")]
+fn write_rows(store: BlobStore, paths: &[PathBuf], options: &Options, batch: usize, write: impl Fn(&mut BlobStore, usize, &Path) -> io::Result<()> + Sync) -> io::Result<()> {
+    let writer = Mutex::new((store, 0));
+    let mut store = writer.lock().unwrap_or_else(|e| e.into_inner());
+    store.0.set_durable(options.sync != SyncMode::None)?;
+    drop(store);
+    each_indexed(paths, options, |index, path| {
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+        let (store, pending) = &mut *writer;
+        if batch > 1 && *pending == 0 {
+            store.begin()?;
+        }
+        write(store, index, path)?;
+        *pending += 1;
+        if batch > 1 && *pending == batch {
+            store.commit()?;
+            *pending = 0;
+        }
+        Ok(())
+    })?;
+    let (mut store, pending) = writer.into_inner().unwrap_or_else(|e| e.into_inner());
+    if batch > 1 && pending > 0 {
+        store.commit()?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn create_rows(paths: &[PathBuf], options: &Options, batch: usize) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    sqlite::remove(&sqlite_path(first))?;
+    round_trip(options);
+    let store = BlobStore::open(sqlite_path(first))?;
+    write_rows(store, paths, options, batch, |store, index, path| with_content(options, index, false, |content| store.put(pack_name(path)?, content)))
+}
+
+/// Reads every row through a connection per worker, so reads run in parallel.
+#[cfg(feature = "sqlite")]
+fn read_rows(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    let path = sqlite_path(first);
+    round_trip(options);
+    let idle: Mutex<Vec<BlobStore>> = Mutex::new(vec![BlobStore::open(&path)?]);
+    each_indexed(paths, options, |index, file| {
+        let connection = idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let mut store = match connection {
+            Some(store) => store,
+            None => {
+                round_trip(options);
+                BlobStore::open(&path)?
+            }
+        };
+        let result = with_read_buffer(options.fresh_buffers, |buf| {
+            if !store.get_into(pack_name(file)?, buf)? {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not in {}", file.display(), path.display())));
+            }
+            read_done(options, index, file, buf)
+        });
+        idle.lock().unwrap_or_else(|e| e.into_inner()).push(store);
+        result
+    })
+}
+
+#[cfg(feature = "sqlite")]
+fn update_rows(paths: &[PathBuf], options: &Options, batch: usize) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    round_trip(options);
+    let store = BlobStore::open(sqlite_path(first))?;
+    write_rows(store, paths, options, batch, |store, index, path| with_content(options, index, true, |content| store.put(pack_name(path)?, content)))
+}
+
+/// Deletes every row, then the database once it holds none.
+#[cfg(feature = "sqlite")]
+fn delete_rows(paths: &[PathBuf], options: &Options, batch: usize) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    let path = sqlite_path(first);
+    round_trip(options);
+    write_rows(BlobStore::open(&path)?, paths, options, batch, |store, _, file| match store.delete(pack_name(file)?)? {
+        true => Ok(()),
+        false => Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not in {}", file.display(), path.display()))),
+    })?;
+    if BlobStore::open(&path)?.is_empty()? {
+        sqlite::remove(&path)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn create_rows_singly(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    create_rows(paths, options, 1)
+}
+
+#[cfg(feature = "sqlite")]
+fn update_rows_singly(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    update_rows(paths, options, 1)
+}
+
+#[cfg(feature = "sqlite")]
+fn delete_rows_singly(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    delete_rows(paths, options, 1)
+}
+
+#[cfg(feature = "sqlite")]
+fn create_rows_batched(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    create_rows(paths, options, SQLITE_BATCH)
+}
+
+#[cfg(feature = "sqlite")]
+fn update_rows_batched(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    update_rows(paths, options, SQLITE_BATCH)
+}
+
+#[cfg(feature = "sqlite")]
+fn delete_rows_batched(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    delete_rows(paths, options, SQLITE_BATCH)
+}
+
+/// The environment the LMDB strategy keeps the files of a run in, named after the first.
+#[cfg(all(unix, feature = "lmdb"))]
+fn lmdb_path(first: &Path) -> PathBuf {
+    container_path(first, "lmdb")
+}
+
+/// Opens the run's environment with room for every file four times over, so updates that
+/// copy pages on write never fill the map.
+#[cfg(all(unix, feature = "lmdb"))]
+fn open_lmdb(first: &Path, options: &Options) -> io::Result<Lmdb> {
+    let map_size = (options.workload.files * (options.workload.size() + 64) * 4).max(1 << 30);
+    round_trip(options);
+    let lmdb = Lmdb::open(lmdb_path(first), map_size)?;
+    lmdb.set_durable(options.sync != SyncMode::None)?;
+    Ok(lmdb)
+}
+
+#[cfg(all(unix, feature = "lmdb"))]
+fn create_keys(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    lmdb::remove(&lmdb_path(first))?;
+    let lmdb = open_lmdb(first, options)?;
+    each_indexed(paths, options, |index, path| with_content(options, index, false, |content| lmdb.put(pack_name(path)?, content)))
+}
+
+#[cfg(all(unix, feature = "lmdb"))]
+fn read_keys(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    let lmdb = open_lmdb(first, options)?;
+    each_indexed(paths, options, |index, path| {
+        with_read_buffer(options.fresh_buffers, |buf| {
+            if !lmdb.get_into(pack_name(path)?, buf)? {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not in {}", path.display(), lmdb.path().display())));
+            }
+            read_done(options, index, path, buf)
+        })
+    })
+}
+
+#[cfg(all(unix, feature = "lmdb"))]
+fn update_keys(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    let lmdb = open_lmdb(first, options)?;
+    each_indexed(paths, options, |index, path| with_content(options, index, true, |content| lmdb.put(pack_name(path)?, content)))
+}
+
+/// Deletes every key, then the environment once it holds none.
+#[cfg(all(unix, feature = "lmdb"))]
+fn delete_keys(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let Some(first) = paths.first() else { return Ok(()) };
+    let lmdb = open_lmdb(first, options)?;
+    each_file(paths, options, |path| match lmdb.delete(pack_name(path)?)? {
+        true => Ok(()),
+        false => Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not in {}", path.display(), lmdb.path().display()))),
+    })?;
+    if lmdb.is_empty()? {
+        drop(lmdb);
+        lmdb::remove(&lmdb_path(first))?;
+    }
+    Ok(())
+}
+
+fn report_residency(phase: &str, paths: &[PathBuf], sample: usize) {
+    match cache::sample_residency(paths, sample) {
+        Ok(r) => println!(
+            "{} residency: {:.1}% cached ({}/{} pages over {} sampled files)",
+            phase,
+            r.percent(),
+            r.resident_pages,
+            r.total_pages,
+            r.files
+        ),
+        Err(e) => log::warn("bench", format_args!("failed to sample {} residency: {}", phase.to_lowercase(), e)),
+    }
+}
+
+type PhaseFn = fn(&[PathBuf], &Options) -> io::Result<()>;
+
+#[derive(Debug)]
+pub struct Strategy {
+    pub name: &'static str,
+    pub label: &'static str,
+    create: PhaseFn,
+    read: PhaseFn,
+    update: PhaseFn,
+    delete: PhaseFn,
+    /// The extension of the one file a strategy keeps every file of the run in, named
+    /// after the first (see [`Strategy::stored_paths`]); `None` when files are kept as named.
+    container: Option<&'static str>,
+}
+
+/// One timed phase of a strategy run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Create,
+    Read,
+    Update,
+    Delete,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [Phase::Create, Phase::Read, Phase::Update, Phase::Delete];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Create => "create",
+            Phase::Read => "read",
+            Phase::Update => "update",
+            Phase::Delete => "delete",
+        }
+    }
+}
+
+impl Strategy {
+    /// Runs a single phase over `paths`, for harnesses that time phases on their own. Read,
+    /// update and delete expect the files to exist; create expects them not to.
+    pub fn run_phase(&self, phase: Phase, paths: &[PathBuf], options: &Options) -> io::Result<()> {
+        match phase {
+            Phase::Create => (self.create)(paths, options),
+            Phase::Read => (self.read)(paths, options),
+            Phase::Update => (self.update)(paths, options),
+            Phase::Delete => (self.delete)(paths, options),
+        }
+    }
+
+    /// The files on disk that hold the logical files `paths` between create and delete.
+    pub fn stored_paths(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        match self.container {
+            Some(extension) => paths.first().map(|first| vec![container_path(first, extension)]).unwrap_or_default(),
+            None => paths.to_vec(),
+        }
+    }
+}
+
+/// The paths a run over `files` files in `dir_path` uses.
+pub fn file_paths(dir_path: &Path, files: usize) -> Vec<PathBuf> {
+    (0..files).map(|i| dir_path.join(format!("file_{}.txt", i))).collect()
+}
+
+/// Slice `index` of `count` contiguous slices of `items` that differ in length by at most
+/// one, as `io bench --processes` hands its workers their files.
+pub fn slice<T>(items: &[T], index: usize, count: usize) -> &[T] {
+    &items[items.len() * index / count..items.len() * (index + 1) / count]
+}
+
+/// The paths of `files` files striped round-robin over `dirs`: file `i` goes to directory
+/// `i % dirs.len()`, so every phase keeps all of them busy at once.
+pub fn striped_paths(dirs: &[PathBuf], files: usize) -> Vec<PathBuf> {
+    names::paths(dirs, files, Names::Ascii)
+}
+
+pub const STRATEGIES: &[Strategy] = &[
+    Strategy {
+        name: "traditional_io",
+        label: "Traditional",
+        create: create_files,
+        read: read_files,
+        update: update_files_traditionally,
+        delete: delete_files,
+        container: None,
+    },
+    Strategy {
+        name: "smart_io (mmap Update Only)",
+        label: "Smart",
+        create: create_files,
+        read: read_files,
+        update: update_files_smartly,
+        delete: delete_files,
+        container: None,
+    },
+    Strategy {
+        name: "vectored_io (writev Create/Update)",
+        label: "Vectored",
+        create: create_files_vectored,
+        read: read_files,
+        update: update_files_vectored,
+        delete: delete_files,
+        container: None,
+    },
+    Strategy {
+        name: "preallocated_io (fallocate Create)",
+        label: "Preallocated",
+        create: create_files_preallocated,
+        read: read_files,
+        update: update_files_traditionally,
+        delete: delete_files,
+        container: None,
+    },
+    Strategy {
+        name: "adaptive_io (mmap by measured size thresholds)",
+        label: "Adaptive",
+        create: create_files,
+        read: read_files_adaptively,
+        update: update_files_adaptively,
+        delete: delete_files,
+        container: None,
+    },
+    Strategy {
+        name: "pack_io (one append-only pack file)",
+        label: "Pack",
+        create: create_packed,
+        read: read_packed,
+        update: update_packed,
+        delete: delete_packed,
+        container: Some("pack"),
+    },
+    #[cfg(feature = "sqlite")]
+    Strategy {
+        name: "sqlite_io (one row per file, a transaction each)",
+        label: "SQLite",
+        create: create_rows_singly,
+        read: read_rows,
+        update: update_rows_singly,
+        delete: delete_rows_singly,
+        container: Some("sqlite"),
+    },
+    #[cfg(feature = "sqlite")]
+    Strategy {
+        name: "sqlite_batched_io (rows committed 1000 at a time)",
+        label: "SQLiteBatched",
+        create: create_rows_batched,
+        read: read_rows,
+        update: update_rows_batched,
+        delete: delete_rows_batched,
+        container: Some("sqlite"),
+    },
+    #[cfg(all(unix, feature = "lmdb"))]
+    Strategy {
+        name: "lmdb_io (one key per file, a transaction each)",
+        label: "LMDB",
+        create: create_keys,
+        read: read_keys,
+        update: update_keys,
+        delete: delete_keys,
+        container: Some("lmdb"),
+    },
+];
+
+/// Read-phase time with per-thread buffer reuse versus a fresh allocation per file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BufferComparison {
+    pub pooled: Duration,
+    pub fresh: Duration,
+}
+
+fn compare_buffers(paths: &[PathBuf], options: &Options) -> io::Result<BufferComparison> {
+    let start = Instant::now();
+    read_files_with(paths, options, false)?;
+    let pooled = start.elapsed();
+    let start = Instant::now();
+    read_files_with(paths, options, true)?;
+    let fresh = start.elapsed();
+    Ok(BufferComparison { pooled, fresh })
+}
+
+/// Timings, resource usage and contained failures of one strategy run.
+#[derive(Debug, Clone, Default)]
+pub struct RunResult {
+    pub times: PhaseTimes,
+    pub usage: PhaseUsage,
+    pub memory: PhaseMemory,
+    pub failures: Vec<Failure>,
+    /// Per-file latency of each phase, when [`Options::latency`] is set.
+    pub latency: Vec<(&'static str, Latency)>,
+    /// Completions per hardware queue in each phase, when [`Options::queues`] is set.
+    pub queues: Vec<(&'static str, QueueSample)>,
+    /// NFS RPCs per operation in each phase, when [`Options::rpcs`] is set.
+    pub rpcs: Vec<(&'static str, RpcSample)>,
+    /// The phase the run was cancelled in, if it was; the phases before it finished and
+    /// those from it on have no times.
+    pub interrupted: Option<&'static str>,
+}
+
+/// Wall-clock time of each phase of one strategy run.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhaseTimes {
+    pub create: Duration,
+    pub read: Duration,
+    pub update: Duration,
+    pub delete: Duration,
+    /// Only measured when `Options::compare_buffers` is set; not part of the total.
+    pub buffer_comparison: Option<BufferComparison>,
+    /// Of the update phase, the time workers spent waiting on writeback, summed over them.
+    pub writeback: Duration,
+}
+
+/// `getrusage` differences per phase, alongside [`PhaseTimes`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PhaseUsage {
+    pub create: Usage,
+    pub read: Usage,
+    pub update: Usage,
+    pub delete: Usage,
+}
+
+impl PhaseUsage {
+    pub fn total(&self) -> Usage {
+        self.create + self.read + self.update + self.delete
+    }
+}
+
+/// Peak memory of each phase, sampled while it runs; `None` where it can't be read.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PhaseMemory {
+    pub create: Option<Memory>,
+    pub read: Option<Memory>,
+    pub update: Option<Memory>,
+    pub delete: Option<Memory>,
+}
+
+impl PhaseMemory {
+    /// The highest of any phase.
+    pub fn peak(&self) -> Option<Memory> {
+        [self.create, self.read, self.update, self.delete].into_iter().flatten().reduce(Memory::max)
+    }
+}
+
+/// Runs `phase`, returning its wall time, resource usage and, with
+/// [`Options::sample_memory`], peak memory.
+fn measure(phase: impl FnOnce() -> io::Result<()>, options: &Options) -> io::Result<(Duration, Usage, Option<Memory>)> {
+    let before = Usage::current()?;
+    let sampler = options.sample_memory.then(|| PeakSampler::start(memory::SAMPLE_INTERVAL));
+    let start = Instant::now();
+    let result = phase();
+    let elapsed = start.elapsed();
+    let peak = sampler.and_then(PeakSampler::finish);
+    result?;
+    Ok((elapsed, Usage::current()?.since(&before), peak))
+}
+
+impl PhaseTimes {
+    pub fn total(&self) -> Duration {
+        self.create + self.read + self.update + self.delete
+    }
+
+    pub fn phase(&self, phase: Phase) -> Duration {
+        match phase {
+            Phase::Create => self.create,
+            Phase::Read => self.read,
+            Phase::Update => self.update,
+            Phase::Delete => self.delete,
+        }
+    }
+
+    pub fn phase_mut(&mut self, phase: Phase) -> &mut Duration {
+        match phase {
+            Phase::Create => &mut self.create,
+            Phase::Read => &mut self.read,
+            Phase::Update => &mut self.update,
+            Phase::Delete => &mut self.delete,
+        }
+    }
+}
+
+fn begin_phase(options: &Options, strategy: &Strategy, phase: &str) {
+    options.progress.set_stage(format!("{} {}", strategy.label, phase));
+    options.op_scope.set(format!("{}/{}", strategy.label, phase));
+}
+
+/// Runs one step of a benchmark other than a strategy's: shows `stage` in the progress
+/// display, logs its operations under `scope`, and moves the per-file failures it recorded
+/// into `failures` labelled `name`, whether or not it succeeds.
+pub fn phase<T>(options: &Options, stage: impl Into<String>, scope: impl Into<String>, name: &'static str, failures: &mut Vec<Failure>, f: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    options.progress.set_stage(stage);
+    options.op_scope.set(scope);
+    let result = f();
+    failures.extend(options.failures.take(name));
+    result
+}
+
+/// Runs every phase of `strategy` on the configured workload inside `dir_path`, on the
+/// current rayon pool.
+pub fn run_strategy(strategy: &Strategy, dir_path: &Path, options: &Options) -> io::Result<RunResult> {
+    run_strategy_on(strategy, &options.workload.paths(dir_path), options)
+}
+
+/// Runs every phase of `strategy` on `file_paths`, which may span several directories (see
+/// [`striped_paths`]), on the current rayon pool.
+pub fn run_strategy_on(strategy: &Strategy, file_paths: &[PathBuf], options: &Options) -> io::Result<RunResult> {
+    let _span = log::span("strategy", strategy.label);
+    let mut times = PhaseTimes::default();
+    let mut usage = PhaseUsage::default();
+    let mut memory = PhaseMemory::default();
+    // Drop anything left over from a run that bailed out with an error.
+    options.failures.take("");
+    options.writeback.take();
+    if let Some(latency) = &options.latency {
+        latency.take();
+    }
+    let mut failures = Vec::new();
+    let mut latency = Vec::new();
+    let mut queues = Vec::new();
+    let mut rpcs = Vec::new();
+    let queue_sample = || options.queues.as_ref().map(QueueCounter::sample);
+    let rpc_sample = || options.rpcs.as_ref().map(RpcCounter::sample);
+    let mut finish_phase = |phase: &'static str, queues_before: Option<QueueSample>, rpcs_before: Option<RpcSample>| {
+        failures.extend(options.failures.take(phase));
+        if let Some(sketch) = &options.latency {
+            latency.push((phase, sketch.take()));
+        }
+        if let (Some(before), Some(after)) = (queues_before, queue_sample()) {
+            queues.push((phase, after.since(&before)));
+        }
+        if let (Some(before), Some(after)) = (rpcs_before, rpc_sample()) {
+            rpcs.push((phase, after.since(&before)));
+        }
+    };
+
+    // Once cancelled, the phase in flight stops issuing files and the ones after it don't
+    // run; what finished is still returned.
+    let interrupted = Cell::new(None);
+    let mut phase = |name: &'static str, run: &dyn Fn() -> io::Result<()>| -> io::Result<Option<(Duration, Usage, Option<Memory>)>> {
+        if interrupted.get().is_some() {
+            return Ok(None);
+        }
+        begin_phase(options, strategy, name);
+        if let Some(backpressure) = &options.backpressure {
+            backpressure.set_writing(matches!(name, "create" | "update"));
+        }
+        let _span = log::span("phase", name);
+        let (queues_before, rpcs_before) = (queue_sample(), rpc_sample());
+        let recording = options.profiler.as_ref().map(|profiler| profiler.start(strategy.label, name)).transpose()?;
+        let measured = match measure(run, options) {
+            Ok(measured) => Some(measured),
+            Err(_) if options.cancel.is_cancelled() => {
+                interrupted.set(Some(name));
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        if let Some(recording) = recording {
+            recording.finish()?;
+        }
+        finish_phase(name, queues_before, rpcs_before);
+        Ok(measured)
+    };
+    let running = || interrupted.get().is_none();
+    let stored = strategy.stored_paths(file_paths);
+
+    if let Some(measured) = phase("create", &|| (strategy.create)(file_paths, options))? {
+        (times.create, usage.create, memory.create) = measured;
+    }
+    if let (Some(sample), true) = (options.residency_sample, running()) {
+        report_residency("Create", &stored, sample);
+    }
+
+    if options.cold_read && running() {
+        let remaining = cache::evict_from_cache(&stored)?;
+        if remaining.resident_pages > 0 {
+            log::warn("bench", format_args!("{} pages stayed cached after eviction", remaining.resident_pages));
+        }
+    }
+
+    if let Some(measured) = phase("read", &|| (strategy.read)(file_paths, options))? {
+        (times.read, usage.read, memory.read) = measured;
+    }
+    if let (Some(sample), true) = (options.residency_sample, running()) {
+        report_residency("Read", &stored, sample);
+    }
+
+    // The comparison reads the files one by one, which a strategy with a container doesn't keep.
+    if options.compare_buffers && strategy.container.is_none() && running() {
+        times.buffer_comparison = Some(compare_buffers(file_paths, options)?);
+    }
+
+    if let Some(measured) = phase("update", &|| (strategy.update)(file_paths, options))? {
+        (times.update, usage.update, memory.update) = measured;
+    }
+    times.writeback = options.writeback.take();
+    if let Some(measured) = phase("delete", &|| (strategy.delete)(file_paths, options))? {
+        (times.delete, usage.delete, memory.delete) = measured;
+    }
+
+    let interrupted = interrupted.get();
+    Ok(RunResult { times, usage, memory, failures, latency, queues, rpcs, interrupted })
+}
+
+/// Runs `strategy` in each of `dirs` at the same time, one thread per directory sharing
+/// `pool`'s workers, or the global pool's without one, so several devices are loaded
+/// together and each reports its own times, and counts its files on a
+/// [`Progress::target`] of its own. Resource usage and peak memory are the whole
+/// process's, not one directory's.
+pub fn run_strategy_across(strategy: &Strategy, dirs: &[PathBuf], options: &Options, pool: Option<&rayon::ThreadPool>) -> io::Result<Vec<RunResult>> {
+    let targets: Vec<Options> = dirs
+        .iter()
+        .map(|dir| Options {
+            verify: options.verify,
+            progress: Progress::target(&options.progress, dir.parent().unwrap_or(dir).display().to_string()),
+            ..options.with_workload(options.workload.clone())
+        })
+        .collect();
+    let results: Vec<io::Result<RunResult>> = thread::scope(|scope| {
+        let runs: Vec<_> = dirs.iter().zip(&targets).map(|(dir, target)| scope.spawn(move || match pool {
+                Some(pool) => pool.install(|| run_strategy(strategy, dir, target)),
+                None => run_strategy(strategy, dir, target),
+            })).collect();
+        runs.into_iter().map(|run| run.join().unwrap_or_else(|_| Err(io::Error::other("target run panicked")))).collect()
+    });
+    if let Some(all) = &options.file_times {
+        for times in targets.iter().filter_map(|target| target.file_times.as_ref()) {
+            all.extend(times.take());
+        }
+    }
+    results.into_iter().zip(dirs).map(|(result, dir)| result.map_err(|e| io::Error::new(e.kind(), format!("{}: {}", dir.display(), e)))).collect()
+}
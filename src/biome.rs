@@ -5,12 +5,14 @@ use std::time::Instant;
 
 fn main() -> std::io::Result<()> {
     let num_files = 10_000;
-    let dir = "temp_files";
-    fs::create_dir_all(dir)?;
+    // Unique to the process, so two copies running at once don't share files; `io clean`
+    // removes it if the run dies.
+    let dir = std::env::temp_dir().join(format!("bench_files-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
 
     // Generate file paths
-    let file_paths: Vec<String> = (0..num_files)
-        .map(|i| format!("{}/file_{}.txt", dir, i))
+    let file_paths: Vec<std::path::PathBuf> = (0..num_files)
+        .map(|i| dir.join(format!("file_{}.txt", i)))
         .collect();
 
     let start = Instant::now();
@@ -46,7 +48,7 @@ fn main() -> std::io::Result<()> {
     })?;
 
     // Clean up directory
-    fs::remove_dir(dir)?;
+    fs::remove_dir(&dir)?;
 
     let duration = start.elapsed();
     println!("Total time taken: {:.3} seconds", duration.as_secs_f64());
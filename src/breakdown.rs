@@ -0,0 +1,65 @@
+//! Files of a tree sliced by extension and by directory depth, with their bytes and the
+//! time spent on them, for the questions monorepos and asset trees raise: which file types
+//! hold the space, and how deep the expensive part of the tree sits.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Totals of one extension or depth.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Slice {
+    pub files: u64,
+    pub bytes: u64,
+    pub time: Duration,
+}
+
+impl Slice {
+    fn add(&mut self, bytes: u64, time: Duration) {
+        self.files += 1;
+        self.bytes += bytes;
+        self.time += time;
+    }
+}
+
+/// Files grouped both ways.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Breakdown {
+    /// Keyed by [`extension`].
+    pub by_extension: BTreeMap<String, Slice>,
+    /// Keyed by [`depth`].
+    pub by_depth: BTreeMap<usize, Slice>,
+}
+
+/// The lowercased extension of `path`, or `(none)`. Dotfiles like `.gitignore` have none.
+pub fn extension(path: &Path) -> String {
+    path.extension().map_or_else(|| "(none)".to_string(), |extension| extension.to_string_lossy().to_lowercase())
+}
+
+/// Directories between `root` and `path`: 0 for files directly in `root`.
+pub fn depth(root: &Path, path: &Path) -> usize {
+    path.strip_prefix(root).map_or(0, |relative| relative.components().count().saturating_sub(1))
+}
+
+impl Breakdown {
+    /// Counts the file at `path` under `root`, of `bytes` bytes that took `time`.
+    pub fn add(&mut self, root: &Path, path: &Path, bytes: u64, time: Duration) {
+        self.by_extension.entry(extension(path)).or_default().add(bytes, time);
+        self.by_depth.entry(depth(root, path)).or_default().add(bytes, time);
+    }
+
+    /// The `count` extensions holding the most bytes, largest first, and everything else
+    /// summed into one slice.
+    pub fn top_extensions(&self, count: usize) -> (Vec<(&str, Slice)>, Slice) {
+        let mut extensions: Vec<(&str, Slice)> = self.by_extension.iter().map(|(extension, slice)| (extension.as_str(), *slice)).collect();
+        extensions.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then(a.0.cmp(b.0)));
+        let mut rest = Slice::default();
+        for (_, slice) in extensions.iter().skip(count) {
+            rest.files += slice.files;
+            rest.bytes += slice.bytes;
+            rest.time += slice.time;
+        }
+        extensions.truncate(count);
+        (extensions, rest)
+    }
+}
@@ -0,0 +1,26 @@
+//! Per-thread reusable I/O buffers, so bulk reads don't allocate once per file.
+
+use std::cell::RefCell;
+
+/// Buffers that grew beyond this are released after use instead of being kept per thread.
+const MAX_RETAINED: usize = 16 * 1024 * 1024;
+
+thread_local! {
+    static BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Calls `f` with this thread's buffer, emptied but keeping its capacity from earlier uses.
+/// Nested calls on the same thread get a fresh allocation.
+pub fn with_buffer<R>(f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    BUFFER.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut buf) => {
+            buf.clear();
+            let result = f(&mut buf);
+            if buf.capacity() > MAX_RETAINED {
+                *buf = Vec::new();
+            }
+            result
+        }
+        Err(_) => f(&mut Vec::new()),
+    })
+}
@@ -0,0 +1,152 @@
+//! Single-file artifacts: a [`Bundle`] gathers named files in memory and writes them as one
+//! `.tar.zst`, which `tar --zstd -xf` unpacks, so a whole run can be attached to a bug
+//! report.
+//!
+//! The archive is POSIX ustar, with a pax header ahead of any path too long for ustar's
+//! name field, and the zstd frame holds it in raw blocks: valid zstd that
+//! every decoder reads, but stored rather than compressed, written by hand like
+//! [`crate::parquet`] so bundling needs no dependencies. Run artifacts are small text, so
+//! what that costs in size rarely matters.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) const TAR_BLOCK: usize = 512;
+/// Longest name a ustar header holds without the prefix field.
+pub(crate) const MAX_NAME: usize = 100;
+const ZSTD_MAGIC: u32 = 0xfd2f_b528;
+/// Largest block zstd allows.
+const ZSTD_MAX_BLOCK: usize = 128 * 1024;
+
+/// Files to archive, each under the bundle's top-level directory.
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    root: String,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl Bundle {
+    /// A bundle whose files unpack into the directory `root`.
+    pub fn new(root: impl Into<String>) -> Bundle {
+        Bundle { root: root.into(), files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, contents: impl Into<Vec<u8>>) -> &mut Bundle {
+        self.files.push((name.into(), contents.into()));
+        self
+    }
+
+    /// Adds the file at `path` under `name`, or nothing if it doesn't exist.
+    pub fn add_file(&mut self, name: impl Into<String>, path: &Path) -> io::Result<&mut Bundle> {
+        match fs::read(path) {
+            Ok(contents) => Ok(self.add(name, contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(self),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Names of the files added so far.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// The bundle as a tar archive, before the zstd frame.
+    pub fn to_tar(&self) -> io::Result<Vec<u8>> {
+        let mtime = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        let mut tar = Vec::new();
+        for (name, contents) in &self.files {
+            let path = format!("{}/{}", self.root, name);
+            tar.extend_from_slice(&tar_headers(&path, contents.len() as u64, mtime));
+            tar.extend_from_slice(contents);
+            tar.resize(tar.len().next_multiple_of(TAR_BLOCK), 0);
+        }
+        // Two empty blocks end the archive.
+        tar.resize(tar.len() + 2 * TAR_BLOCK, 0);
+        Ok(tar)
+    }
+
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        self.to_tar().map(|tar| zstd_stored(&tar))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_bytes()?)
+    }
+}
+
+/// A ustar header for a regular file, readable by everyone, owned by root.
+pub(crate) fn tar_header(path: &str, size: u64, mtime: u64) -> [u8; TAR_BLOCK] {
+    header(path.as_bytes(), size, mtime, b'0')
+}
+
+/// The headers of a regular file at `path`: its ustar header, preceded by a pax extended
+/// header holding the whole path when it's longer than the name field. The name field
+/// then holds as much of the path as fits, for readers that don't know pax.
+pub(crate) fn tar_headers(path: &str, size: u64, mtime: u64) -> Vec<u8> {
+    if path.len() <= MAX_NAME {
+        return tar_header(path, size, mtime).to_vec();
+    }
+    let record = pax_record("path", path);
+    let mut headers = header(b"././@PaxHeader", record.len() as u64, mtime, b'x').to_vec();
+    headers.extend_from_slice(&record);
+    headers.resize(headers.len().next_multiple_of(TAR_BLOCK), 0);
+    let cut = (0..=MAX_NAME).rev().find(|&at| path.is_char_boundary(at)).unwrap_or(0);
+    headers.extend_from_slice(&tar_header(&path[..cut], size, mtime));
+    headers
+}
+
+/// A pax record, `<length> <key>=<value>\n`, its length counting its own digits.
+fn pax_record(key: &str, value: &str) -> Vec<u8> {
+    let body = key.len() + value.len() + 3;
+    let mut len = body + 1;
+    while len != body + len.to_string().len() {
+        len = body + len.to_string().len();
+    }
+    format!("{} {}={}\n", len, key, value).into_bytes()
+}
+
+fn header(path: &[u8], size: u64, mtime: u64, kind: u8) -> [u8; TAR_BLOCK] {
+    let mut header = [0u8; TAR_BLOCK];
+    let mut field = |offset: usize, len: usize, value: &[u8]| header[offset..offset + value.len().min(len)].copy_from_slice(&value[..value.len().min(len)]);
+    let octal = |value: u64, len: usize| format!("{:0width$o}", value, width = len - 1).into_bytes();
+    field(0, 100, path);
+    field(100, 8, &octal(0o644, 8));
+    field(108, 8, &octal(0, 8));
+    field(116, 8, &octal(0, 8));
+    field(124, 12, &octal(size, 12));
+    field(136, 12, &octal(mtime, 12));
+    // The checksum is summed with its own field as spaces.
+    field(148, 8, b"        ");
+    field(156, 1, &[kind]);
+    field(257, 6, b"ustar\0");
+    field(263, 2, b"00");
+    field(265, 32, b"root");
+    field(297, 32, b"root");
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    header
+}
+
+/// `data` as one zstd frame of raw blocks.
+fn zstd_stored(data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(data.len() + data.len() / ZSTD_MAX_BLOCK * 3 + 16);
+    frame.extend_from_slice(&ZSTD_MAGIC.to_le_bytes());
+    // Single segment with an 8-byte content size, no checksum or dictionary.
+    frame.push(0b1110_0000);
+    frame.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    let mut blocks = data.chunks(ZSTD_MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        // Even an empty frame has a last block.
+        frame.extend_from_slice(&[1, 0, 0]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none() as u32;
+        // Block type 0, raw, in bits 1-2.
+        let header = (block.len() as u32) << 3 | last;
+        frame.extend_from_slice(&header.to_le_bytes()[..3]);
+        frame.extend_from_slice(block);
+    }
+    frame
+}
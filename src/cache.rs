@@ -0,0 +1,230 @@
+//! Page-cache inspection and control.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+/// How much of a set of files is resident in the page cache.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Residency {
+    pub files: usize,
+    pub resident_pages: usize,
+    pub total_pages: usize,
+}
+
+impl Residency {
+    pub fn percent(&self) -> f64 {
+        if self.total_pages == 0 {
+            return 0.0;
+        }
+        self.resident_pages as f64 * 100.0 / self.total_pages as f64
+    }
+
+    fn merge(self, other: Residency) -> Residency {
+        Residency {
+            files: self.files + other.files,
+            resident_pages: self.resident_pages + other.resident_pages,
+            total_pages: self.total_pages + other.total_pages,
+        }
+    }
+}
+
+pub fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+fn file_residency(file: &File) -> io::Result<Residency> {
+    if file.metadata()?.len() == 0 {
+        return Ok(Residency { files: 1, ..Residency::default() });
+    }
+    // Mapping does not fault pages in; mincore only inspects the page tables of the cache.
+    let mmap = unsafe { Mmap::map(file)? };
+    let pages = mmap.len().div_ceil(page_size());
+    let mut vec = vec![0; pages];
+    let result = unsafe { libc::mincore(mmap.as_ptr() as *mut libc::c_void, mmap.len(), vec.as_mut_ptr()) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Residency {
+        files: 1,
+        resident_pages: vec.iter().filter(|&&page| page & 1 != 0).count(),
+        total_pages: pages,
+    })
+}
+
+/// Counts how many pages of `path` are currently in the page cache.
+pub fn residency(path: &Path) -> io::Result<Residency> {
+    file_residency(&File::open(path)?)
+}
+
+/// Samples up to `sample` evenly spaced files out of `paths`.
+pub fn sample_residency<P: AsRef<Path>>(paths: &[P], sample: usize) -> io::Result<Residency> {
+    let step = (paths.len() / sample.max(1)).max(1);
+    let mut total = Residency::default();
+    for path in paths.iter().step_by(step).take(sample) {
+        total = total.merge(residency(path.as_ref())?);
+    }
+    Ok(total)
+}
+
+/// Drops `file`'s pages from the page cache. Dirty pages can't be evicted, so the
+/// file is synced first.
+fn evict_file(file: &File) -> io::Result<()> {
+    file.sync_data()?;
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let result = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+        if result != 0 {
+            return Err(io::Error::from_raw_os_error(result));
+        }
+    }
+    Ok(())
+}
+
+/// Evicts every file in `paths` from the page cache and returns the residency measured
+/// afterwards, so callers can check that the cold-cache scenario actually holds.
+///
+/// The kernel may keep pages that are mapped or locked by another process; those show up
+/// as `resident_pages` in the returned report.
+pub fn evict_from_cache<P: AsRef<Path> + Sync>(paths: &[P]) -> io::Result<Residency> {
+    paths
+        .par_iter()
+        .map(|path| {
+            let file = File::open(path.as_ref())?;
+            evict_file(&file)?;
+            file_residency(&file)
+        })
+        .try_reduce(Residency::default, |a, b| Ok(a.merge(b)))
+}
+
+/// Access-pattern hint, applied with `madvise` to mappings and `posix_fadvise` to open files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hint {
+    Sequential,
+    Random,
+    WillNeed,
+    DontNeed,
+}
+
+impl FromStr for Hint {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Hint> {
+        match s {
+            "sequential" => Ok(Hint::Sequential),
+            "random" => Ok(Hint::Random),
+            "willneed" => Ok(Hint::WillNeed),
+            "dontneed" => Ok(Hint::DontNeed),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown hint '{}', expected sequential|random|willneed|dontneed", s),
+            )),
+        }
+    }
+}
+
+/// How to ask the kernel to start reading a file into the page cache ahead of use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefetch {
+    /// `readahead(2)`, or `POSIX_FADV_WILLNEED` where there is none.
+    Readahead,
+    /// Map the file and `madvise(MADV_WILLNEED)` the mapping.
+    WillNeed,
+}
+
+impl Prefetch {
+    pub fn name(self) -> &'static str {
+        match self {
+            Prefetch::Readahead => "readahead",
+            Prefetch::WillNeed => "willneed",
+        }
+    }
+}
+
+impl FromStr for Prefetch {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Prefetch> {
+        match s {
+            "readahead" => Ok(Prefetch::Readahead),
+            "willneed" => Ok(Prefetch::WillNeed),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown prefetch '{}', expected readahead|willneed", s))),
+        }
+    }
+}
+
+/// Starts reading all of `file` into the page cache as `how` says. The pages arrive in the
+/// background, so it returns about as soon as the reads are queued.
+pub fn prefetch(file: &File, how: Prefetch) -> io::Result<()> {
+    match how {
+        Prefetch::Readahead => {
+            #[cfg(target_os = "linux")]
+            {
+                use std::os::unix::io::AsRawFd;
+
+                if unsafe { libc::readahead(file.as_raw_fd(), 0, file.metadata()?.len() as usize) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            }
+            #[cfg(not(target_os = "linux"))]
+            fadvise(file, Hint::WillNeed)
+        }
+        Prefetch::WillNeed => {
+            if file.metadata()?.len() == 0 {
+                return Ok(());
+            }
+            // The pages stay cached after the mapping is gone.
+            madvise(&unsafe { Mmap::map(file)? }, Hint::WillNeed)
+        }
+    }
+}
+
+/// Applies `hint` to the whole of `file` via `posix_fadvise`. A no-op where the call isn't available.
+pub fn fadvise(file: &File, hint: Hint) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let advice = match hint {
+            Hint::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+            Hint::Random => libc::POSIX_FADV_RANDOM,
+            Hint::WillNeed => libc::POSIX_FADV_WILLNEED,
+            Hint::DontNeed => libc::POSIX_FADV_DONTNEED,
+        };
+        let result = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice) };
+        if result != 0 {
+            return Err(io::Error::from_raw_os_error(result));
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = (file, hint);
+    Ok(())
+}
+
+/// Applies `hint` to a shared file mapping via `madvise`.
+///
+/// `region` must be the full slice of a mapping, which is always page aligned. `DontNeed`
+/// only drops the page-table entries; for shared mappings the data stays in the page cache.
+pub fn madvise(region: &[u8], hint: Hint) -> io::Result<()> {
+    if region.is_empty() {
+        return Ok(());
+    }
+    let advice = match hint {
+        Hint::Sequential => libc::MADV_SEQUENTIAL,
+        Hint::Random => libc::MADV_RANDOM,
+        Hint::WillNeed => libc::MADV_WILLNEED,
+        Hint::DontNeed => libc::MADV_DONTNEED,
+    };
+    let result = unsafe { libc::madvise(region.as_ptr() as *mut libc::c_void, region.len(), advice) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+//! The fastest strategy per phase as `io calibrate` measured it, cached per storage so
+//! tools can pick their I/O path at start-up without benchmarking every time.
+//!
+//! The cache lives in `$XDG_CACHE_HOME/io/calibration.json` (`~/.cache/io` without it) and
+//! holds one calibration per block device and filesystem type, each with the workload
+//! sizes it ran. Strategies are named by their labels in [`crate::bench`]'s strategy list
+//! (`Traditional`, `Smart`, `Pack`, ...). [`crate::calibrated_backend`] looks up the
+//! current directory's storage.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::atomic::{self, Durability};
+use crate::filesystem::Filesystem;
+use crate::json::{self, Value};
+
+const VERSION: u64 = 1;
+
+/// The phases a calibration picks a strategy for.
+pub const PHASES: [&str; 4] = ["create", "read", "update", "delete"];
+
+/// The fastest strategies for one workload size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeChoice {
+    pub size: usize,
+    pub files: usize,
+    /// The fastest strategy's label per phase, in [`PHASES`] order.
+    pub strategies: [String; 4],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Calibration {
+    /// The storage it ran on, as [`storage_key`] names it.
+    pub key: String,
+    pub threads: usize,
+    /// Seconds since the Unix epoch.
+    pub calibrated_at: u64,
+    /// By size, smallest first.
+    pub sizes: Vec<SizeChoice>,
+}
+
+impl Calibration {
+    /// An empty calibration of `key`, stamped now.
+    pub fn new(key: String, threads: usize) -> Calibration {
+        let calibrated_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        Calibration { key, threads, calibrated_at, sizes: Vec::new() }
+    }
+
+    pub fn add(&mut self, choice: SizeChoice) {
+        self.sizes.retain(|known| known.size != choice.size);
+        self.sizes.push(choice);
+        self.sizes.sort_by_key(|choice| choice.size);
+    }
+
+    /// The fastest strategy for `phase` with files of `size` bytes, from the calibrated
+    /// size nearest to it by ratio.
+    pub fn strategy(&self, phase: &str, size: usize) -> Option<&str> {
+        let index = PHASES.iter().position(|known| *known == phase)?;
+        let distance = |choice: &&SizeChoice| ((choice.size.max(1) as f64).ln() - (size.max(1) as f64).ln()).abs();
+        let nearest = self.sizes.iter().min_by(|a, b| distance(a).total_cmp(&distance(b)))?;
+        Some(&nearest.strategies[index])
+    }
+
+    fn to_json(&self) -> Value {
+        let sizes = self
+            .sizes
+            .iter()
+            .map(|choice| PHASES.iter().zip(&choice.strategies).fold(Value::object().with("size_bytes", choice.size).with("files", choice.files), |v, (phase, strategy)| v.with(phase, strategy.as_str())))
+            .collect::<Vec<_>>();
+        Value::object().with("key", self.key.as_str()).with("threads", self.threads).with("calibrated_at", self.calibrated_at).with("sizes", sizes)
+    }
+
+    fn from_json(value: &Value) -> Option<Calibration> {
+        let sizes = value.get("sizes")?.as_array()?.iter().map(|choice| {
+            let strategy = |phase: &str| choice.get(phase)?.as_str().map(str::to_string);
+            Some(SizeChoice {
+                size: choice.get("size_bytes")?.as_u64()? as usize,
+                files: choice.get("files")?.as_u64()? as usize,
+                strategies: [strategy("create")?, strategy("read")?, strategy("update")?, strategy("delete")?],
+            })
+        });
+        Some(Calibration {
+            key: value.get("key")?.as_str()?.to_string(),
+            threads: value.get("threads")?.as_u64()? as usize,
+            calibrated_at: value.get("calibrated_at")?.as_u64()?,
+            sizes: sizes.collect::<Option<_>>()?,
+        })
+    }
+
+    /// Stores this calibration in the cache file `cache`, replacing any of the same
+    /// storage and keeping the others.
+    pub fn store_in(&self, cache: &Path) -> io::Result<()> {
+        let mut calibrations: Vec<Calibration> = read(cache)?.into_iter().filter(|known| known.key != self.key).collect();
+        calibrations.push(self.clone());
+        if let Some(dir) = cache.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let value = Value::object().with("version", VERSION).with("calibrations", calibrations.iter().map(Calibration::to_json).collect::<Vec<_>>());
+        atomic::write_file_atomic(cache, format!("{:#}\n", value), Durability::Data)
+    }
+
+    /// Stores this calibration in the user's cache, returning the file.
+    pub fn store(&self) -> io::Result<PathBuf> {
+        let cache = cache_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "neither XDG_CACHE_HOME nor HOME is set"))?;
+        self.store_in(&cache)?;
+        Ok(cache)
+    }
+}
+
+/// The user's calibration cache file, if a cache directory can be found.
+pub fn cache_path() -> Option<PathBuf> {
+    let cache = env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from).or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache.join("io").join("calibration.json"))
+}
+
+/// What a calibration is keyed by: the whole block device and the filesystem type, as in
+/// `nvme0n1:ext4`, or the mount source for filesystems without a device (`tmpfs:tmpfs`).
+pub fn storage_key(filesystem: &Filesystem) -> String {
+    let device = filesystem.device.as_ref().map_or(filesystem.source.as_str(), |device| device.name.as_str());
+    format!("{}:{}", device, filesystem.fs_type)
+}
+
+/// Every calibration in the cache file `cache`; none if it doesn't exist.
+pub fn read(cache: &Path) -> io::Result<Vec<Calibration>> {
+    let text = match fs::read_to_string(cache) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", cache.display(), message));
+    let value = json::parse(&text).map_err(invalid)?;
+    if value.get("version").and_then(Value::as_u64) != Some(VERSION) {
+        return Err(invalid(format!("not a version {} calibration cache", VERSION)));
+    }
+    let calibrations = value.get("calibrations").and_then(Value::as_array).ok_or_else(|| invalid("missing calibrations".to_string()))?;
+    calibrations.iter().map(|calibration| Calibration::from_json(calibration).ok_or_else(|| invalid("malformed calibration".to_string()))).collect()
+}
+
+/// The calibration in `cache` for the storage `dir` is on.
+pub fn load_from(cache: &Path, dir: &Path) -> Option<Calibration> {
+    let key = storage_key(&Filesystem::of(dir)?);
+    read(cache).ok()?.into_iter().find(|calibration| calibration.key == key)
+}
+
+/// The calibration in the user's cache for the storage `dir` is on.
+pub fn load(dir: &Path) -> Option<Calibration> {
+    load_from(&cache_path()?, dir)
+}
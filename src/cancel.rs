@@ -0,0 +1,47 @@
+//! Stopping a run part way, as Ctrl-C does: once a run's [`Token`] is cancelled, every
+//! phase stops handing out files, lets the operations already in flight finish, and
+//! returns [`cancelled`]; runners report the phases that finished before it.
+//!
+//! Each run has a token of its own in [`crate::bench::Options::cancel`], so the watchdog
+//! aborting one run leaves the others in the process alone. Ctrl-C, through [`interrupt`],
+//! stops the whole process: every token reports it, and the flag is never cleared, since
+//! an interrupted process is on its way out.
+
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Cancels every run in the process, as Ctrl-C does.
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::Release);
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Acquire)
+}
+
+/// One run's cancellation, shared by its clones.
+#[derive(Debug, Clone, Default)]
+pub struct Token(Arc<AtomicBool>);
+
+impl Token {
+    pub fn new() -> Token {
+        Token::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Whether this run was cancelled or the process [`interrupt`]ed.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire) || is_interrupted()
+    }
+}
+
+/// The error work stopped by a cancelled [`Token`] returns.
+pub fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
@@ -0,0 +1,165 @@
+//! A C interface to the bulk file operations of [`crate::contents`], for build tools in
+//! Node, Python or anything else with a C FFI that want the many-small-files path without
+//! a Rust toolchain. `include/io.h` declares it; build the shared library with
+//!
+//! ```text
+//! cargo rustc --lib --release --no-default-features --features capi,rayon --crate-type cdylib
+//! ```
+//!
+//! Every function returns 0 on success and -1 on failure, when [`io_last_error`] says what
+//! went wrong on the calling thread. Paths are NUL-terminated byte strings: any bytes on
+//! Unix, UTF-8 elsewhere. Each file is retried on transient errors as
+//! [`RetryPolicy::default`] says, and a batch stops at the first file that still fails, as
+//! the Rust functions do. A panic inside the library is reported as an error rather than
+//! unwinding into the caller.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+use std::slice;
+
+use crate::contents;
+use crate::retry::RetryPolicy;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// File contents handed out by [`io_read_many`]; free them with [`io_free_buffers`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct IoBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// Runs `f`, turning an error or a panic into -1 and the thread's last error.
+fn call(f: impl FnOnce() -> io::Result<()>) -> c_int {
+    let message = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return 0,
+        Ok(Err(e)) => e.to_string(),
+        Err(payload) => format!("panicked: {}", contents::panic_message(&*payload)),
+    };
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with_borrow_mut(|last| *last = Some(message));
+    -1
+}
+
+/// The `count` paths at `paths`, which may be null only when `count` is 0.
+///
+/// # Safety
+///
+/// `paths` must point to `count` NUL-terminated strings.
+unsafe fn paths_of(paths: *const *const c_char, count: usize) -> io::Result<Vec<PathBuf>> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    if paths.is_null() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "null path array"));
+    }
+    unsafe { slice::from_raw_parts(paths, count) }
+        .iter()
+        .map(|&path| {
+            if path.is_null() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "null path"));
+            }
+            let bytes = unsafe { CStr::from_ptr(path) }.to_bytes();
+            #[cfg(unix)]
+            {
+                use std::os::unix::ffi::OsStrExt;
+                Ok(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)))
+            }
+            #[cfg(not(unix))]
+            {
+                std::str::from_utf8(bytes).map(PathBuf::from).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+            }
+        })
+        .collect()
+}
+
+/// Writes `count` files, `paths[i]` getting the `lens[i]` bytes at `contents[i]`, creating
+/// or truncating each.
+///
+/// # Safety
+///
+/// `paths`, `contents` and `lens` must each point to `count` elements, every path being a
+/// NUL-terminated string and every `contents[i]` pointing to `lens[i]` readable bytes (or
+/// being null when `lens[i]` is 0).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn io_write_many(paths: *const *const c_char, contents: *const *const u8, lens: *const usize, count: usize) -> c_int {
+    call(|| {
+        let paths = unsafe { paths_of(paths, count) }?;
+        if count > 0 && (contents.is_null() || lens.is_null()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "null contents or lengths"));
+        }
+        let files: Vec<(PathBuf, &[u8])> = (0..count)
+            .map(|i| {
+                let (data, len) = unsafe { (*contents.add(i), *lens.add(i)) };
+                let bytes: &[u8] = if len == 0 { &[] } else { unsafe { slice::from_raw_parts(data, len) } };
+                (paths[i].clone(), bytes)
+            })
+            .collect();
+        contents::write_many(&files, &RetryPolicy::default())
+    })
+}
+
+/// Reads `count` files, filling `out[i]` with the contents of `paths[i]`. On failure
+/// `out` is left untouched.
+///
+/// # Safety
+///
+/// `paths` must point to `count` NUL-terminated strings and `out` to room for `count`
+/// buffers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn io_read_many(paths: *const *const c_char, count: usize, out: *mut IoBuffer) -> c_int {
+    call(|| {
+        let paths = unsafe { paths_of(paths, count) }?;
+        if count > 0 && out.is_null() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "null output array"));
+        }
+        for (i, contents) in contents::read_many(&paths, &RetryPolicy::default())?.into_iter().enumerate() {
+            // A boxed slice's allocation is exactly its length, which is all the C side keeps.
+            let contents = Box::into_raw(contents.into_boxed_slice());
+            unsafe { out.add(i).write(IoBuffer { data: contents.cast(), len: contents.len() }) };
+        }
+        Ok(())
+    })
+}
+
+/// Frees `count` buffers filled by [`io_read_many`], setting each to empty.
+///
+/// # Safety
+///
+/// Each buffer must have come from [`io_read_many`] and not been freed since, or be empty.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn io_free_buffers(buffers: *mut IoBuffer, count: usize) {
+    if buffers.is_null() {
+        return;
+    }
+    for buffer in unsafe { slice::from_raw_parts_mut(buffers, count) } {
+        if !buffer.data.is_null() {
+            drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)) });
+        }
+        *buffer = IoBuffer { data: ptr::null_mut(), len: 0 };
+    }
+}
+
+/// Deletes `count` files, by which time others may be gone if one fails.
+///
+/// # Safety
+///
+/// `paths` must point to `count` NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn io_delete_many(paths: *const *const c_char, count: usize) -> c_int {
+    call(|| contents::delete_many(&unsafe { paths_of(paths, count) }?, &RetryPolicy::default()))
+}
+
+/// What the last failed call on this thread went wrong with, or null if none has. The
+/// string stays valid until the next failure on the thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn io_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|last| last.as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
@@ -0,0 +1,256 @@
+//! A content-addressed store: files kept under the hash of their contents, each distinct
+//! content once, and trees materialized out of it by hard link, reflink or copy. This is
+//! the workload of pnpm's store and of cargo-style caches: many small files ingested, most
+//! of them already present, then checked out into many trees.
+//!
+//! A [`Store`] keeps content `h` at `<root>/<first two hex digits>/<other fourteen>`, so no
+//! directory holds more than a 256th of the store. [`Store::insert`] writes new content to
+//! a temporary file and links it into place, so concurrent inserts of the same content
+//! are safe and a reader never sees a partial file. The hash is the crate's 64-bit FNV-1a
+//! ([`hash_bytes`]), which is fast and good enough to benchmark with but not
+//! collision-resistant: don't store untrusted content by it.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "bench")]
+use std::sync::atomic::AtomicUsize;
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+use crate::population::hash_bytes;
+
+/// Numbers temporary files so concurrent inserts never share one.
+static TEMPORARY: AtomicU64 = AtomicU64::new(0);
+
+/// How a file is materialized from the store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checkout {
+    /// Shares the stored inode: instant, but writing to the checkout changes the store.
+    HardLink,
+    /// Shares the stored extents copy-on-write (`FICLONE`, on Btrfs, XFS and bcachefs).
+    Reflink,
+    /// A full copy.
+    Copy,
+}
+
+impl Checkout {
+    pub const ALL: [Checkout; 3] = [Checkout::HardLink, Checkout::Reflink, Checkout::Copy];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Checkout::HardLink => "hardlink",
+            Checkout::Reflink => "reflink",
+            Checkout::Copy => "copy",
+        }
+    }
+}
+
+/// What inserting one file's contents did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ingested {
+    pub hash: u64,
+    pub size: u64,
+    /// Whether the contents were new to the store, rather than deduplicated.
+    pub new: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Store {
+    root: PathBuf,
+}
+
+impl Store {
+    /// Opens the store at `root`, creating it and its 256 fan-out directories if needed.
+    pub fn open(root: impl Into<PathBuf>) -> io::Result<Store> {
+        let root = root.into();
+        for shard in 0..256 {
+            fs::create_dir_all(root.join(format!("{:02x}", shard)))?;
+        }
+        Ok(Store { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Where content with `hash` is kept.
+    pub fn path_of(&self, hash: u64) -> PathBuf {
+        let hex = format!("{:016x}", hash);
+        self.root.join(&hex[..2]).join(&hex[2..])
+    }
+
+    pub fn contains(&self, hash: u64) -> bool {
+        self.path_of(hash).is_file()
+    }
+
+    /// Stores `bytes` unless the store already has them.
+    pub fn insert(&self, bytes: &[u8]) -> io::Result<Ingested> {
+        let hash = hash_bytes(bytes);
+        let (path, size) = (self.path_of(hash), bytes.len() as u64);
+        if path.is_file() {
+            return Ok(Ingested { hash, size, new: false });
+        }
+        let temporary = path.with_extension(format!("tmp-{}-{}", std::process::id(), TEMPORARY.fetch_add(1, Ordering::Relaxed)));
+        // Linking rather than renaming into place tells a racing insert of the same
+        // content that it lost.
+        let written = File::create(&temporary).and_then(|mut file| file.write_all(bytes)).and_then(|()| fs::hard_link(&temporary, &path));
+        let _ = fs::remove_file(&temporary);
+        match written {
+            Ok(()) => Ok(Ingested { hash, size, new: true }),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(Ingested { hash, size, new: false }),
+            Err(e) => Err(io::Error::new(e.kind(), format!("{}: {}", path.display(), e))),
+        }
+    }
+
+    /// Stores the contents of the file at `path`.
+    pub fn ingest(&self, path: &Path) -> io::Result<Ingested> {
+        self.insert(&fs::read(path)?)
+    }
+
+    /// Materializes content `hash` at `dest`, which must not exist yet.
+    pub fn checkout(&self, hash: u64, dest: &Path, method: Checkout) -> io::Result<()> {
+        let stored = self.path_of(hash);
+        let result = match method {
+            Checkout::HardLink => fs::hard_link(&stored, dest),
+            Checkout::Reflink => reflink(&stored, dest),
+            Checkout::Copy => fs::copy(&stored, dest).map(drop),
+        };
+        result.map_err(|e| io::Error::new(e.kind(), format!("{}: {}", dest.display(), e)))
+    }
+
+    /// Materializes a tree: every `(relative path, hash)` of `manifest` under `root`,
+    /// creating the directories on the way. In parallel with the `rayon` feature.
+    pub fn checkout_tree(&self, manifest: &[(PathBuf, u64)], root: &Path, method: Checkout) -> io::Result<()> {
+        let mut dirs: Vec<PathBuf> = manifest.iter().filter_map(|(path, _)| path.parent().map(|dir| root.join(dir))).collect();
+        dirs.sort();
+        dirs.dedup();
+        for dir in dirs {
+            fs::create_dir_all(dir)?;
+        }
+        let one = |(path, hash): &(PathBuf, u64)| self.checkout(*hash, &root.join(path), method);
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            manifest.par_iter().try_for_each(one)
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            manifest.iter().try_for_each(one)
+        }
+    }
+}
+
+/// Creates `dest` sharing the extents of `source` copy-on-write. Fails with
+/// [`io::ErrorKind::Unsupported`] where the filesystem or platform can't.
+pub fn reflink(source: &Path, dest: &Path) -> io::Result<()> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        use std::os::fd::AsRawFd;
+
+        let from = File::open(source)?;
+        let to = File::create_new(dest)?;
+        if unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) } == 0 {
+            return Ok(());
+        }
+        let error = io::Error::last_os_error();
+        drop(to);
+        let _ = fs::remove_file(dest);
+        // Filesystems without reflinks, and sources on another filesystem, refuse the same way.
+        match error.raw_os_error() {
+            Some(libc::EOPNOTSUPP | libc::ENOTTY | libc::EINVAL | libc::EXDEV) => Err(io::Error::new(io::ErrorKind::Unsupported, format!("no reflinks here: {}", error))),
+            _ => Err(error),
+        }
+    }
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    {
+        let _ = (source, dest);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "reflinks need Linux and the libc feature"))
+    }
+}
+
+/// How many source files share each distinct content in the benchmark.
+#[cfg(feature = "bench")]
+pub const COPIES: usize = 4;
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct CasResult {
+    pub ingest: Duration,
+    /// Distinct contents the store ended up with, and their bytes.
+    pub stored: usize,
+    pub stored_bytes: u64,
+    /// `None` for methods the filesystem doesn't support.
+    pub checkouts: Vec<(Checkout, Option<Duration>)>,
+    pub failures: Vec<Failure>,
+}
+
+/// Writes the configured workload as a source tree of `dir_path` in which every content
+/// appears in [`COPIES`] files, then times ingesting it into a store in `dir_path/store`
+/// and checking the tree out of the store by each method in turn. Each checkout is
+/// removed untimed before the next; a method the filesystem refuses is skipped.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<CasResult> {
+    let (files, size) = (options.workload.files, options.workload.size());
+    let source = dir_path.join("source");
+    fs::create_dir_all(&source)?;
+    let paths = bench::file_paths(&source, files);
+    // Content `i` is `i` repeated; files `i`, `i + distinct`, ... share it.
+    let distinct = files.div_ceil(COPIES).max(1);
+    let content = |index: usize| -> Vec<u8> { ((index % distinct) as u64).to_le_bytes().iter().copied().cycle().take(size).collect() };
+    for (index, path) in paths.iter().enumerate() {
+        fs::write(path, content(index))?;
+    }
+    let store = Store::open(dir_path.join("store"))?;
+    let mut result = CasResult::default();
+    options.failures.take("");
+
+    let hashes: Vec<AtomicU64> = paths.iter().map(|_| AtomicU64::new(0)).collect();
+    let (stored, stored_bytes) = (AtomicUsize::new(0), AtomicU64::new(0));
+    let start = Instant::now();
+    bench::phase(options, "CAS ingest", "cas/ingest", "ingest", &mut result.failures, || {
+        bench::each_indexed(&paths, options, |index, path| {
+            let ingested = store.ingest(path)?;
+            hashes[index].store(ingested.hash, Ordering::Relaxed);
+            if ingested.new {
+                stored.fetch_add(1, Ordering::Relaxed);
+                stored_bytes.fetch_add(ingested.size, Ordering::Relaxed);
+            }
+            Ok(())
+        })
+    })?;
+    result.ingest = start.elapsed();
+    (result.stored, result.stored_bytes) = (stored.into_inner(), stored_bytes.into_inner());
+
+    let manifest: Vec<(PathBuf, u64)> = paths.iter().zip(&hashes).filter_map(|(path, hash)| Some((path.strip_prefix(&source).ok()?.to_path_buf(), hash.load(Ordering::Relaxed)))).collect();
+    for method in Checkout::ALL {
+        let tree = dir_path.join(format!("checkout-{}", method.name()));
+        fs::create_dir_all(&tree)?;
+        let supported = match manifest.first() {
+            Some((path, hash)) if method == Checkout::Reflink => match store.checkout(*hash, &tree.join(path), method) {
+                Err(e) if e.kind() == io::ErrorKind::Unsupported => false,
+                other => other.map(|()| true)?,
+            },
+            _ => true,
+        };
+        if supported {
+            let _ = fs::remove_dir_all(&tree);
+            fs::create_dir_all(&tree)?;
+            let links: Vec<PathBuf> = manifest.iter().map(|(path, _)| tree.join(path)).collect();
+            let start = Instant::now();
+            bench::phase(options, format!("CAS {}", method.name()), format!("cas/{}", method.name()), method.name(), &mut result.failures, || {
+                bench::each_indexed(&links, options, |index, link| store.checkout(manifest[index].1, link, method))
+            })?;
+            result.checkouts.push((method, Some(start.elapsed())));
+        } else {
+            result.checkouts.push((method, None));
+        }
+        fs::remove_dir_all(&tree)?;
+    }
+    fs::remove_dir_all(store.root())?;
+    fs::remove_dir_all(&source)?;
+    Ok(result)
+}
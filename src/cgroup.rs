@@ -0,0 +1,260 @@
+//! The CPU and I/O limits the process's cgroup imposes, v1 or v2. In a container the CPU
+//! count is the host's while the quota may allow a fraction of it, so a pool sized to the
+//! CPUs oversubscribes the quota and measures throttling; a `blkio` or `io.max` limit caps
+//! throughput below what the device does, and the results say nothing about it.
+//!
+//! Limits are read from the cgroup the process is in and every ancestor up to the mount,
+//! the tightest winning as the kernel applies them. Nothing is found off Linux, and
+//! whatever can't be read is treated as unlimited.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::health::Device;
+use crate::human;
+use crate::json::Value;
+
+/// Where the cgroup hierarchies are mounted.
+const ROOT: &str = "/sys/fs/cgroup";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+/// Limits on one block device, per second; `None` is unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IoLimit {
+    /// `major:minor`, as the cgroup files name it.
+    pub device: String,
+    pub read_bps: Option<u64>,
+    pub write_bps: Option<u64>,
+    pub read_iops: Option<u64>,
+    pub write_iops: Option<u64>,
+}
+
+impl IoLimit {
+    /// Keeps the tighter of each limit.
+    fn tighten(&mut self, other: &IoLimit) {
+        let min = |a: Option<u64>, b: Option<u64>| a.into_iter().chain(b).min();
+        self.read_bps = min(self.read_bps, other.read_bps);
+        self.write_bps = min(self.write_bps, other.write_bps);
+        self.read_iops = min(self.read_iops, other.read_iops);
+        self.write_iops = min(self.write_iops, other.write_iops);
+    }
+
+    fn is_limited(&self) -> bool {
+        self.read_bps.is_some() || self.write_bps.is_some() || self.read_iops.is_some() || self.write_iops.is_some()
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::object()
+            .with("device", self.device.as_str())
+            .with("read_bps", self.read_bps)
+            .with("write_bps", self.write_bps)
+            .with("read_iops", self.read_iops)
+            .with("write_iops", self.write_iops)
+    }
+
+    pub fn from_json(value: &Value) -> Option<IoLimit> {
+        let limit = |key: &str| value.get(key).and_then(Value::as_u64);
+        Some(IoLimit {
+            device: value.get("device")?.as_str()?.to_string(),
+            read_bps: limit("read_bps"),
+            write_bps: limit("write_bps"),
+            read_iops: limit("read_iops"),
+            write_iops: limit("write_iops"),
+        })
+    }
+}
+
+impl fmt::Display for IoLimit {
+    /// `8:0 read 10.0 MiB/s, write 1,000 IOPS`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let limits: Vec<String> = [("read", self.read_bps, self.read_iops), ("write", self.write_bps, self.write_iops)]
+            .into_iter()
+            .flat_map(|(direction, bps, iops)| {
+                let bps = bps.map(|bps| format!("{} {}/s", direction, human::bytes(bps)));
+                let iops = iops.map(|iops| format!("{} {} IOPS", direction, human::decimal(iops as f64, 0)));
+                bps.into_iter().chain(iops)
+            })
+            .collect();
+        write!(f, "{} {}", self.device, limits.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+    pub version: Version,
+    /// The CFS quota and period in microseconds, `None` without a quota.
+    pub cpu_quota: Option<(u64, u64)>,
+    /// Devices with at least one limit.
+    pub io: Vec<IoLimit>,
+}
+
+impl Limits {
+    /// The limits on this process, or `None` off Linux or without a cgroup mount.
+    pub fn detect() -> Option<Limits> {
+        Limits::read(Path::new(ROOT), &fs::read_to_string("/proc/self/cgroup").ok()?)
+    }
+
+    /// The limits under the cgroup mount `root` for a process whose `/proc/self/cgroup`
+    /// reads `membership`.
+    pub fn read(root: &Path, membership: &str) -> Option<Limits> {
+        // `controller list:path` per hierarchy; v2 has one line with no controllers.
+        let groups: Vec<(&str, &str)> = membership.lines().filter_map(|line| line.split_once(':')?.1.split_once(':')).collect();
+        if root.join("cgroup.controllers").exists() {
+            let dirs = ancestors(root, groups.iter().find(|(controllers, _)| controllers.is_empty())?.1);
+            let cpu_quota = dirs.iter().filter_map(|dir| cpu_max(&fs::read_to_string(dir.join("cpu.max")).ok()?)).min_by(by_share);
+            let io = dirs.iter().filter_map(|dir| fs::read_to_string(dir.join("io.max")).ok()).flat_map(|io_max| io_max.lines().filter_map(io_max_line).collect::<Vec<_>>());
+            return Some(Limits { version: Version::V2, cpu_quota, io: merge(io) });
+        }
+        let hierarchy = |controller: &str| -> Vec<PathBuf> {
+            let Some((controllers, path)) = groups.iter().find(|(controllers, _)| controllers.split(',').any(|name| name == controller)) else {
+                return Vec::new();
+            };
+            // Mounted under the joined controller names, usually with a link per controller.
+            [root.join(controllers), root.join(controller)].into_iter().find(|mount| mount.is_dir()).map_or_else(Vec::new, |mount| ancestors(&mount, path))
+        };
+        let (cpu, blkio) = (hierarchy("cpu"), hierarchy("blkio"));
+        if cpu.is_empty() && blkio.is_empty() {
+            return None;
+        }
+        let cpu_quota = cpu.iter().filter_map(|dir| cfs_quota(dir)).min_by(by_share);
+        let io = blkio.iter().flat_map(|dir| {
+            ["read_bps", "write_bps", "read_iops", "write_iops"].into_iter().flat_map(move |kind| {
+                let throttle = fs::read_to_string(dir.join(format!("blkio.throttle.{}_device", kind))).unwrap_or_default();
+                throttle.lines().filter_map(|line| throttle_line(line, kind)).collect::<Vec<_>>()
+            })
+        });
+        Some(Limits { version: Version::V1, cpu_quota, io: merge(io) })
+    }
+
+    /// The CPUs the quota allows, as a fraction.
+    pub fn cpus(&self) -> Option<f64> {
+        self.cpu_quota.map(|(quota, period)| quota as f64 / period as f64)
+    }
+
+    /// Workers to run given `available` CPUs: no more than the quota rounds up to.
+    pub fn threads(&self, available: usize) -> usize {
+        match self.cpus() {
+            Some(cpus) => available.min(cpus.ceil() as usize).max(1),
+            None => available.max(1),
+        }
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.cpu_quota.is_some() || !self.io.is_empty()
+    }
+
+    /// The I/O limits on the whole device `device`, if any.
+    pub fn io_limit(&self, device: &Device) -> Option<&IoLimit> {
+        let number = fs::read_to_string(device.sysfs.join("dev")).ok()?;
+        self.io.iter().find(|limit| limit.device == number.trim())
+    }
+
+    /// One line per limit found, for reports.
+    pub fn notes(&self) -> Vec<String> {
+        let version = match self.version {
+            Version::V1 => "cgroup v1",
+            Version::V2 => "cgroup v2",
+        };
+        let cpus = self.cpus().map(|cpus| format!("{} CPU quota of {} CPUs", version, human::decimal(cpus, 2)));
+        cpus.into_iter().chain(self.io.iter().map(|limit| format!("{} I/O limit on {}", version, limit))).collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::object()
+            .with("version", if self.version == Version::V1 { 1u64 } else { 2 })
+            .with("cpu_quota_us", self.cpu_quota.map(|(quota, _)| quota))
+            .with("cpu_period_us", self.cpu_quota.map(|(_, period)| period))
+            .with("cpus", self.cpus())
+            .with("io", self.io.iter().map(IoLimit::to_json).collect::<Vec<_>>())
+    }
+
+    pub fn from_json(value: &Value) -> Option<Limits> {
+        let version = match value.get("version")?.as_u64()? {
+            1 => Version::V1,
+            _ => Version::V2,
+        };
+        let field = |key: &str| value.get(key).and_then(Value::as_u64);
+        let io = value.get("io").and_then(Value::as_array).map_or_else(Vec::new, |io| io.iter().filter_map(IoLimit::from_json).collect());
+        Some(Limits { version, cpu_quota: field("cpu_quota_us").zip(field("cpu_period_us")), io })
+    }
+}
+
+/// Compares quotas by the CPUs they allow.
+fn by_share(a: &(u64, u64), b: &(u64, u64)) -> std::cmp::Ordering {
+    (a.0 as u128 * b.1 as u128).cmp(&(b.0 as u128 * a.1 as u128))
+}
+
+/// The cgroup directory for `path` under `mount` and each parent up to the mount. A path
+/// from outside a cgroup namespace doesn't exist under the mount, which is then the
+/// process's own cgroup.
+fn ancestors(mount: &Path, path: &str) -> Vec<PathBuf> {
+    let own = mount.join(path.trim_start_matches('/'));
+    let own = if own.is_dir() { own } else { mount.to_path_buf() };
+    own.ancestors().take_while(|dir| dir.starts_with(mount)).map(Path::to_path_buf).collect()
+}
+
+/// `cpu.max`, which reads `max 100000` or `150000 100000`.
+fn cpu_max(cpu_max: &str) -> Option<(u64, u64)> {
+    let mut fields = cpu_max.split_whitespace();
+    let quota = fields.next()?.parse().ok()?;
+    let period = fields.next().map_or(Some(100_000), |period| period.parse().ok())?;
+    (period > 0).then_some((quota, period))
+}
+
+/// `cpu.cfs_quota_us`, -1 without a quota, over `cpu.cfs_period_us`.
+fn cfs_quota(dir: &Path) -> Option<(u64, u64)> {
+    let quota: i64 = fs::read_to_string(dir.join("cpu.cfs_quota_us")).ok()?.trim().parse().ok()?;
+    let period: u64 = fs::read_to_string(dir.join("cpu.cfs_period_us")).ok()?.trim().parse().ok()?;
+    (quota > 0 && period > 0).then_some((quota as u64, period))
+}
+
+/// An `io.max` line: `8:0 rbps=1048576 wbps=max riops=max wiops=max`.
+fn io_max_line(line: &str) -> Option<IoLimit> {
+    let mut fields = line.split_whitespace();
+    let mut limit = IoLimit { device: fields.next()?.to_string(), ..IoLimit::default() };
+    for field in fields {
+        let (key, value) = field.split_once('=')?;
+        let value = value.parse().ok();
+        match key {
+            "rbps" => limit.read_bps = value,
+            "wbps" => limit.write_bps = value,
+            "riops" => limit.read_iops = value,
+            "wiops" => limit.write_iops = value,
+            _ => {}
+        }
+    }
+    Some(limit)
+}
+
+/// A `blkio.throttle.<kind>_device` line: `8:0 1048576`.
+fn throttle_line(line: &str, kind: &str) -> Option<IoLimit> {
+    let (device, value) = line.split_once(' ')?;
+    let value = Some(value.trim().parse().ok()?);
+    let mut limit = IoLimit { device: device.to_string(), ..IoLimit::default() };
+    match kind {
+        "read_bps" => limit.read_bps = value,
+        "write_bps" => limit.write_bps = value,
+        "read_iops" => limit.read_iops = value,
+        _ => limit.write_iops = value,
+    }
+    Some(limit)
+}
+
+/// One limit per device, the tightest of those given, in device order.
+fn merge(limits: impl Iterator<Item = IoLimit>) -> Vec<IoLimit> {
+    let mut merged: Vec<IoLimit> = Vec::new();
+    for limit in limits.filter(IoLimit::is_limited) {
+        match merged.iter_mut().find(|known| known.device == limit.device) {
+            Some(known) => known.tighten(&limit),
+            None => merged.push(limit),
+        }
+    }
+    merged.sort_by(|a, b| a.device.cmp(&b.device));
+    merged
+}
@@ -0,0 +1,155 @@
+//! Checkpoints of long benchmark sessions, so a sweep or soak run of hours that a crash or
+//! a reboot stops can pick up where it was instead of starting over. A [`Checkpoint`]
+//! holds the arguments the session was started with and every cell of it finished so far
+//! (one strategy on one workload, or one strategy's soak iteration), rewritten atomically
+//! after each cell. Resuming runs the same arguments and skips the cells already there;
+//! the cell that was running when the session stopped runs again from the start, creating
+//! its files afresh.
+//!
+//! Checkpoints live in `$XDG_STATE_HOME/io/checkpoints` (`~/.local/state/io` without it),
+//! named by run ID, rather than in the temp directory a reboot may clear. A session that
+//! finishes removes its checkpoint.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::atomic::{self, Durability};
+use crate::json::{self, Value};
+
+const VERSION: u64 = 1;
+
+/// The user's checkpoint directory, if a state directory can be found.
+pub fn state_dir() -> Option<PathBuf> {
+    let state = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state")))?;
+    Some(state.join("io").join("checkpoints"))
+}
+
+/// The run IDs with a checkpoint in `dir`, sorted.
+pub fn ids(dir: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut ids = Vec::new();
+    for entry in entries {
+        if let Some(id) = entry?.file_name().to_str().and_then(|name| name.strip_suffix(".json")) {
+            ids.push(id.to_string());
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+/// A session's progress, kept in a file of its own.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    path: PathBuf,
+    run_id: String,
+    command: String,
+    args: Vec<String>,
+    /// Seconds since the Unix epoch.
+    started_at: u64,
+    cells: Vec<Value>,
+}
+
+impl Checkpoint {
+    /// Starts the checkpoint of run `run_id` in `dir`, a session of `command` given `args`,
+    /// and writes it with no cells yet.
+    pub fn create(dir: &Path, run_id: &str, command: &str, args: Vec<String>) -> io::Result<Checkpoint> {
+        if run_id.is_empty() || run_id.contains(['/', '\\']) || run_id.starts_with('.') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' can't name a checkpoint", run_id)));
+        }
+        fs::create_dir_all(dir)?;
+        let started_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        let checkpoint = Checkpoint { path: dir.join(format!("{}.json", run_id)), run_id: run_id.to_string(), command: command.to_string(), args, started_at, cells: Vec::new() };
+        checkpoint.save()?;
+        Ok(checkpoint)
+    }
+
+    /// The checkpoint of run `run_id` in `dir`.
+    pub fn load(dir: &Path, run_id: &str) -> io::Result<Checkpoint> {
+        let path = dir.join(format!("{}.json", run_id));
+        let text = fs::read_to_string(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                let known = ids(dir).unwrap_or_default();
+                let known = if known.is_empty() { "there are none".to_string() } else { format!("there are {}", known.join(", ")) };
+                io::Error::new(io::ErrorKind::NotFound, format!("no checkpoint of run {} in {}; {}", run_id, dir.display(), known))
+            }
+            _ => e,
+        })?;
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+        let value = json::parse(&text).map_err(|e| invalid(&e))?;
+        if value.get("version").and_then(Value::as_u64) != Some(VERSION) {
+            return Err(invalid(&format!("not a version {} checkpoint", VERSION)));
+        }
+        let strings = |key: &str| -> Option<Vec<String>> { value.get(key)?.as_array()?.iter().map(|arg| arg.as_str().map(str::to_string)).collect() };
+        Ok(Checkpoint {
+            run_id: value.get("run_id").and_then(Value::as_str).ok_or_else(|| invalid("missing run_id"))?.to_string(),
+            command: value.get("command").and_then(Value::as_str).ok_or_else(|| invalid("missing command"))?.to_string(),
+            args: strings("args").ok_or_else(|| invalid("missing args"))?,
+            started_at: value.get("started_at").and_then(Value::as_u64).ok_or_else(|| invalid("missing started_at"))?,
+            cells: value.get("cells").and_then(Value::as_array).ok_or_else(|| invalid("missing cells"))?.to_vec(),
+            path,
+        })
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let value = Value::object()
+            .with("version", VERSION)
+            .with("run_id", self.run_id.as_str())
+            .with("command", self.command.as_str())
+            .with("args", self.args.clone())
+            .with("started_at", self.started_at)
+            .with("cells", self.cells.clone());
+        atomic::write_file_atomic(&self.path, format!("{}\n", value), Durability::Data)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// What the session runs, such as `sweep` or `soak`.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// The arguments the session was started with, after its command.
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// When the session first started, in seconds since the Unix epoch.
+    pub fn started_at(&self) -> u64 {
+        self.started_at
+    }
+
+    /// The finished cells, in the order they finished.
+    pub fn cells(&self) -> &[Value] {
+        &self.cells
+    }
+
+    /// Adds a finished cell and writes the checkpoint before returning.
+    pub fn record(&mut self, cell: Value) -> io::Result<()> {
+        self.cells.push(cell);
+        self.save()
+    }
+
+    /// Removes the checkpoint of a session that finished.
+    pub fn finish(self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,835 @@
+//! Encryption at rest: content sealed with an authenticated cipher before it is written and
+//! opened after it is read, to show what a requirement to encrypt caches costs on top of
+//! the I/O itself.
+//!
+//! Both ciphers are written by hand, so this needs no dependencies: AES-256-GCM, which
+//! uses AES-NI and carry-less multiplication where the CPU has them and portable tables
+//! where it doesn't, and XChaCha20-Poly1305, which is fast everywhere and whose 192-bit
+//! nonces are safe to pick at random. A [`Sealer`] holds a key and stores each file as its
+//! nonce, the ciphertext and the 16-byte tag.
+//!
+//! They are here to measure what encryption costs, not to protect data: the portable AES
+//! looks up tables by secret bytes, so its timing leaks the key, and nothing here has been
+//! reviewed as cryptography. The module is built only with the benchmark harness; anything
+//! that needs its files kept secret wants a vetted implementation.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::bench::{self, Failure, Options};
+use crate::cache;
+
+pub const KEY_LEN: usize = 32;
+pub const TAG_LEN: usize = 16;
+
+fn tag_mismatch() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "authentication tag mismatch")
+}
+
+/// Fails if `data` is longer than `max` bytes, past which the block counter would wrap
+/// and reuse keystream.
+fn check_len(data: &[u8], max: u64, cipher: &str) -> io::Result<()> {
+    match data.len() as u64 > max {
+        true => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} bytes is more than one {} message can hold ({})", data.len(), cipher, max))),
+        false => Ok(()),
+    }
+}
+
+/// Compares tags without stopping at the first difference.
+fn tags_match(a: &[u8; TAG_LEN], b: &[u8; TAG_LEN]) -> bool {
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Fills `buf` from the operating system's random source.
+pub fn random_bytes(buf: &mut [u8]) -> io::Result<()> {
+    File::open("/dev/urandom")?.read_exact(buf)
+}
+
+/// A fresh random key.
+pub fn random_key() -> io::Result<[u8; KEY_LEN]> {
+    let mut key = [0; KEY_LEN];
+    random_bytes(&mut key)?;
+    Ok(key)
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+const CHACHA_CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+#[inline(always)]
+fn quarter_round(mut a: u32, mut b: u32, mut c: u32, mut d: u32) -> (u32, u32, u32, u32) {
+    a = a.wrapping_add(b);
+    d = (d ^ a).rotate_left(16);
+    c = c.wrapping_add(d);
+    b = (b ^ c).rotate_left(12);
+    a = a.wrapping_add(b);
+    d = (d ^ a).rotate_left(8);
+    c = c.wrapping_add(d);
+    b = (b ^ c).rotate_left(7);
+    (a, b, c, d)
+}
+
+/// The twenty ChaCha rounds over `state`, without the final addition.
+#[inline(always)]
+fn chacha_rounds(state: &mut [u32; 16]) {
+    let [mut x0, mut x1, mut x2, mut x3, mut x4, mut x5, mut x6, mut x7, mut x8, mut x9, mut x10, mut x11, mut x12, mut x13, mut x14, mut x15] = *state;
+    for _ in 0..10 {
+        (x0, x4, x8, x12) = quarter_round(x0, x4, x8, x12);
+        (x1, x5, x9, x13) = quarter_round(x1, x5, x9, x13);
+        (x2, x6, x10, x14) = quarter_round(x2, x6, x10, x14);
+        (x3, x7, x11, x15) = quarter_round(x3, x7, x11, x15);
+        (x0, x5, x10, x15) = quarter_round(x0, x5, x10, x15);
+        (x1, x6, x11, x12) = quarter_round(x1, x6, x11, x12);
+        (x2, x7, x8, x13) = quarter_round(x2, x7, x8, x13);
+        (x3, x4, x9, x14) = quarter_round(x3, x4, x9, x14);
+    }
+    *state = [x0, x1, x2, x3, x4, x5, x6, x7, x8, x9, x10, x11, x12, x13, x14, x15];
+}
+
+fn chacha_state(key: &[u32; 8], tail: [u32; 4]) -> [u32; 16] {
+    let mut state = [0; 16];
+    state[..4].copy_from_slice(&CHACHA_CONSTANTS);
+    state[4..12].copy_from_slice(key);
+    state[12..].copy_from_slice(&tail);
+    state
+}
+
+fn key_words(key: &[u8; KEY_LEN]) -> [u32; 8] {
+    std::array::from_fn(|i| u32_le(&key[i * 4..]))
+}
+
+/// HChaCha20: a subkey from `key` and the first 16 bytes of an XChaCha20 nonce.
+pub fn hchacha20(key: &[u8; KEY_LEN], nonce: &[u8; 16]) -> [u8; KEY_LEN] {
+    let mut state = chacha_state(&key_words(key), std::array::from_fn(|i| u32_le(&nonce[i * 4..])));
+    chacha_rounds(&mut state);
+    let mut subkey = [0; KEY_LEN];
+    for (out, word) in subkey.chunks_exact_mut(4).zip(state[..4].iter().chain(&state[12..])) {
+        out.copy_from_slice(&word.to_le_bytes());
+    }
+    subkey
+}
+
+/// XORs `data` with the ChaCha20 (IETF, 96-bit nonce) keystream from block `counter` on.
+fn chacha20_xor(key: &[u32; 8], nonce: &[u8; 12], mut counter: u32, mut data: &mut [u8]) {
+    let tail = |counter: u32| [counter, u32_le(nonce), u32_le(&nonce[4..]), u32_le(&nonce[8..])];
+    #[cfg(target_arch = "x86_64")]
+    {
+        let mut quads = data.chunks_exact_mut(256);
+        for quad in &mut quads {
+            // SSE2 is part of x86-64.
+            unsafe { x86::chacha20_xor4(&chacha_state(key, tail(counter)), quad) };
+            counter = counter.wrapping_add(4);
+        }
+        data = quads.into_remainder();
+    }
+    for (block, chunk) in data.chunks_mut(64).enumerate() {
+        let input = chacha_state(key, tail(counter.wrapping_add(block as u32)));
+        let mut state = input;
+        chacha_rounds(&mut state);
+        if chunk.len() == 64 {
+            for ((bytes, word), add) in chunk.chunks_exact_mut(4).zip(state).zip(input) {
+                let xored = u32_le(bytes) ^ word.wrapping_add(add);
+                bytes.copy_from_slice(&xored.to_le_bytes());
+            }
+        } else {
+            let mut keystream = [0; 64];
+            for ((out, word), add) in keystream.chunks_exact_mut(4).zip(state).zip(input) {
+                out.copy_from_slice(&word.wrapping_add(add).to_le_bytes());
+            }
+            for (byte, key) in chunk.iter_mut().zip(keystream) {
+                *byte ^= key;
+            }
+        }
+    }
+}
+
+fn u64_le(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+const LIMB44: u64 = (1 << 44) - 1;
+const LIMB42: u64 = (1 << 42) - 1;
+
+/// Poly1305 in 44-, 44- and 42-bit limbs, fed whole 16-byte blocks as the AEAD construction
+/// pads them.
+struct Poly1305 {
+    r: [u64; 3],
+    h: [u64; 3],
+    pad: [u64; 2],
+}
+
+impl Poly1305 {
+    fn new(key: &[u8; 32]) -> Poly1305 {
+        let (t0, t1) = (u64_le(key), u64_le(&key[8..]));
+        let r = [t0 & 0xffc_0fff_ffff, (t0 >> 44 | t1 << 20) & 0xfff_ffc0_ffff, (t1 >> 24) & 0x00f_ffff_fc0f];
+        Poly1305 { r, h: [0; 3], pad: [u64_le(&key[16..]), u64_le(&key[24..])] }
+    }
+
+    fn block(&mut self, block: &[u8]) {
+        let [r0, r1, r2] = self.r.map(u128::from);
+        // 2^130 is 5, and the limbs put the wrapped products 2^2 higher.
+        let (s1, s2) = (r1 * 20, r2 * 20);
+        let (t0, t1) = (u64_le(block), u64_le(&block[8..]));
+        let h = &mut self.h;
+        h[0] += t0 & LIMB44;
+        h[1] += (t0 >> 44 | t1 << 20) & LIMB44;
+        h[2] += (t1 >> 24) & LIMB42 | 1 << 40;
+        let [h0, h1, h2] = h.map(u128::from);
+        let d0 = h0 * r0 + h1 * s2 + h2 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0;
+        d1 += d0 >> 44;
+        d2 += d1 >> 44;
+        *h = [d0 as u64 & LIMB44, d1 as u64 & LIMB44, d2 as u64 & LIMB42];
+        h[0] += (d2 >> 42) as u64 * 5;
+        h[1] += h[0] >> 44;
+        h[0] &= LIMB44;
+    }
+
+    /// Feeds `data` zero-padded to a whole number of blocks.
+    fn padded(&mut self, data: &[u8]) {
+        let mut blocks = data.chunks_exact(16);
+        for block in &mut blocks {
+            self.block(block);
+        }
+        let rest = blocks.remainder();
+        if !rest.is_empty() {
+            let mut block = [0; 16];
+            block[..rest.len()].copy_from_slice(rest);
+            self.block(&block);
+        }
+    }
+
+    fn finish(mut self) -> [u8; TAG_LEN] {
+        let h = &mut self.h;
+        for _ in 0..2 {
+            h[2] += h[1] >> 44;
+            h[1] &= LIMB44;
+            h[0] += (h[2] >> 42) * 5;
+            h[2] &= LIMB42;
+            h[1] += h[0] >> 44;
+            h[0] &= LIMB44;
+        }
+        // h - p, kept if it doesn't go negative.
+        let g0 = h[0] + 5;
+        let g1 = h[1] + (g0 >> 44);
+        let g2 = (h[2] + (g1 >> 44)).wrapping_sub(1 << 42);
+        let keep_g = (g2 >> 63).wrapping_sub(1);
+        let g = [g0 & LIMB44, g1 & LIMB44, g2];
+        for i in 0..3 {
+            h[i] = (h[i] & !keep_g) | (g[i] & keep_g);
+        }
+        let [pad0, pad1] = self.pad;
+        h[0] += pad0 & LIMB44;
+        h[1] += ((pad0 >> 44 | pad1 << 20) & LIMB44) + (h[0] >> 44);
+        h[2] += ((pad1 >> 24) & LIMB42) + (h[1] >> 44);
+        let low = (h[0] & LIMB44) | h[1] << 44;
+        let high = (h[1] & LIMB44) >> 20 | h[2] << 24;
+        let mut tag = [0; TAG_LEN];
+        tag[..8].copy_from_slice(&low.to_le_bytes());
+        tag[8..].copy_from_slice(&high.to_le_bytes());
+        tag
+    }
+}
+
+/// XChaCha20-Poly1305 (draft-irtf-cfrg-xchacha), without associated data.
+#[derive(Clone)]
+pub struct XChaCha20Poly1305 {
+    key: [u8; KEY_LEN],
+}
+
+impl XChaCha20Poly1305 {
+    pub const NONCE_LEN: usize = 24;
+    /// The longest message: the 32-bit block counter starts at 1 for the data.
+    pub const MAX_LEN: u64 = (u32::MAX as u64) * 64;
+
+    pub fn new(key: &[u8; KEY_LEN]) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305 { key: *key }
+    }
+
+    /// The subkey's words, the inner nonce and the Poly1305 state for `nonce`.
+    fn start(&self, nonce: &[u8; 24]) -> ([u32; 8], [u8; 12], Poly1305) {
+        let subkey = key_words(&hchacha20(&self.key, nonce[..16].try_into().unwrap()));
+        let mut inner = [0; 12];
+        inner[4..].copy_from_slice(&nonce[16..]);
+        let mut poly_key = [0; 32];
+        chacha20_xor(&subkey, &inner, 0, &mut poly_key);
+        (subkey, inner, Poly1305::new(&poly_key))
+    }
+
+    fn tag(mut poly: Poly1305, ciphertext: &[u8]) -> [u8; TAG_LEN] {
+        poly.padded(ciphertext);
+        let mut lengths = [0; 16];
+        lengths[8..].copy_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+        poly.block(&lengths);
+        poly.finish()
+    }
+
+    /// Encrypts `data` in place, returning its tag, unless it is longer than
+    /// [`XChaCha20Poly1305::MAX_LEN`].
+    pub fn seal(&self, nonce: &[u8; 24], data: &mut [u8]) -> io::Result<[u8; TAG_LEN]> {
+        check_len(data, Self::MAX_LEN, "XChaCha20-Poly1305")?;
+        let (subkey, inner, poly) = self.start(nonce);
+        chacha20_xor(&subkey, &inner, 1, data);
+        Ok(Self::tag(poly, data))
+    }
+
+    /// Checks `tag` and decrypts `data` in place, leaving it untouched if the tag is wrong.
+    pub fn open(&self, nonce: &[u8; 24], data: &mut [u8], tag: &[u8; TAG_LEN]) -> io::Result<()> {
+        check_len(data, Self::MAX_LEN, "XChaCha20-Poly1305")?;
+        let (subkey, inner, poly) = self.start(nonce);
+        if !tags_match(&Self::tag(poly, data), tag) {
+            return Err(tag_mismatch());
+        }
+        chacha20_xor(&subkey, &inner, 1, data);
+        Ok(())
+    }
+}
+
+const fn gf256_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = (a << 1) ^ if a & 0x80 != 0 { 0x1b } else { 0 };
+        b >>= 1;
+    }
+    product
+}
+
+/// The AES S-box, worked out from its definition: the inverse in GF(2^8), then the affine
+/// map.
+const SBOX: [u8; 256] = {
+    let mut sbox = [0; 256];
+    let mut x = 0;
+    while x < 256 {
+        // x^254 is x's inverse, and 0 for 0.
+        let mut inverse = 1;
+        let mut i = 0;
+        while i < 254 {
+            inverse = gf256_mul(inverse, x as u8);
+            i += 1;
+        }
+        let b = inverse;
+        sbox[x] = b ^ b.rotate_left(1) ^ b.rotate_left(2) ^ b.rotate_left(3) ^ b.rotate_left(4) ^ 0x63;
+        x += 1;
+    }
+    sbox
+};
+
+/// SubBytes, ShiftRows' byte and MixColumns folded into one lookup per byte, for a column
+/// as a big-endian word; the other three tables are its rotations.
+const TE0: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut x = 0;
+    while x < 256 {
+        let s = SBOX[x];
+        table[x] = u32::from_be_bytes([gf256_mul(s, 2), s, s, gf256_mul(s, 3)]);
+        x += 1;
+    }
+    table
+};
+
+/// AES-256's fifteen round keys, from `key`.
+fn expand_key(key: &[u8; KEY_LEN]) -> [u32; 60] {
+    let sub_word = |word: u32| u32::from_be_bytes(word.to_be_bytes().map(|b| SBOX[b as usize]));
+    let mut words = [0; 60];
+    for (i, bytes) in key.chunks_exact(4).enumerate() {
+        words[i] = u32::from_be_bytes(bytes.try_into().unwrap());
+    }
+    let mut rcon = 1u8;
+    for i in 8..60 {
+        let mut temp = words[i - 1];
+        if i % 8 == 0 {
+            temp = sub_word(temp.rotate_left(8)) ^ u32::from(rcon) << 24;
+            rcon = gf256_mul(rcon, 2);
+        } else if i % 8 == 4 {
+            temp = sub_word(temp);
+        }
+        words[i] = words[i - 8] ^ temp;
+    }
+    words
+}
+
+fn aes256_encrypt(keys: &[u32; 60], block: &[u8; 16]) -> [u8; 16] {
+    let column = |i: usize| u32::from_be_bytes(block[i * 4..i * 4 + 4].try_into().unwrap());
+    let mut s = [column(0) ^ keys[0], column(1) ^ keys[1], column(2) ^ keys[2], column(3) ^ keys[3]];
+    let byte = |word: u32, shift: u32| (word >> shift & 0xff) as usize;
+    for round in 1..14 {
+        s = std::array::from_fn(|c| {
+            TE0[byte(s[c], 24)]
+                ^ TE0[byte(s[(c + 1) % 4], 16)].rotate_right(8)
+                ^ TE0[byte(s[(c + 2) % 4], 8)].rotate_right(16)
+                ^ TE0[byte(s[(c + 3) % 4], 0)].rotate_right(24)
+                ^ keys[round * 4 + c]
+        });
+    }
+    let mut out = [0; 16];
+    for c in 0..4 {
+        let bytes = [SBOX[byte(s[c], 24)], SBOX[byte(s[(c + 1) % 4], 16)], SBOX[byte(s[(c + 2) % 4], 8)], SBOX[byte(s[(c + 3) % 4], 0)]];
+        out[c * 4..c * 4 + 4].copy_from_slice(&(u32::from_be_bytes(bytes) ^ keys[56 + c]).to_be_bytes());
+    }
+    out
+}
+
+/// Multiplication by x in GCM's bit-reflected field.
+const fn gf128_mul_x(v: u128) -> u128 {
+    (v >> 1) ^ if v & 1 != 0 { 0xe1 << 120 } else { 0 }
+}
+
+/// What the four low bits shifted out of a value fold back in as, for the nibble-wise
+/// multiplication.
+const GF128_REDUCE: [u128; 16] = {
+    let mut table = [0; 16];
+    let mut n = 0;
+    while n < 16 {
+        table[n] = gf128_mul_x(gf128_mul_x(gf128_mul_x(gf128_mul_x(n as u128))));
+        n += 1;
+    }
+    table
+};
+
+/// AES-256-GCM with 96-bit nonces, without associated data.
+#[derive(Clone)]
+pub struct Aes256Gcm {
+    keys: [u32; 60],
+    /// The hash key, E(0), as a big-endian number.
+    h: u128,
+    /// H times each nibble, for hashing without carry-less multiplication.
+    table: [u128; 16],
+    hardware: bool,
+}
+
+impl Aes256Gcm {
+    pub const NONCE_LEN: usize = 12;
+    /// The longest message: the 32-bit block counter starts at 2 for the data.
+    pub const MAX_LEN: u64 = (u32::MAX as u64 - 1) * 16;
+
+    pub fn new(key: &[u8; KEY_LEN]) -> Aes256Gcm {
+        let mut cipher = Aes256Gcm::portable(key);
+        #[cfg(target_arch = "x86_64")]
+        {
+            cipher.hardware = std::arch::is_x86_feature_detected!("aes") && std::arch::is_x86_feature_detected!("pclmulqdq");
+        }
+        cipher
+    }
+
+    /// AES-256-GCM on the portable tables even where the CPU has AES-NI, as it runs on
+    /// CPUs without.
+    pub fn portable(key: &[u8; KEY_LEN]) -> Aes256Gcm {
+        let keys = expand_key(key);
+        let h = u128::from_be_bytes(aes256_encrypt(&keys, &[0; 16]));
+        // Nibble bit 3 is the lowest power of x.
+        let mut table = [0; 16];
+        table[8] = h;
+        table[4] = gf128_mul_x(table[8]);
+        table[2] = gf128_mul_x(table[4]);
+        table[1] = gf128_mul_x(table[2]);
+        for n in 1..16usize {
+            let low = n & n.wrapping_neg();
+            table[n] = table[low] ^ table[n ^ low];
+        }
+        Aes256Gcm { keys, h, table, hardware: false }
+    }
+
+    /// Whether this CPU runs AES and GHASH in hardware.
+    pub fn is_hardware(&self) -> bool {
+        self.hardware
+    }
+
+    fn mul_h(&self, x: u128) -> u128 {
+        let mut z = 0u128;
+        for byte in x.to_le_bytes() {
+            for nibble in [byte & 15, byte >> 4] {
+                z = (z >> 4) ^ GF128_REDUCE[(z & 15) as usize] ^ self.table[nibble as usize];
+            }
+        }
+        z
+    }
+
+    /// GHASH of `ciphertext` and its length.
+    fn ghash(&self, ciphertext: &[u8]) -> u128 {
+        let mut lengths = [0; 16];
+        lengths[8..].copy_from_slice(&(ciphertext.len() as u64 * 8).to_be_bytes());
+        let mut blocks = ciphertext.chunks_exact(16);
+        let mut rest = [0; 16];
+        rest[..blocks.remainder().len()].copy_from_slice(blocks.remainder());
+        let tail: &[[u8; 16]] = if blocks.remainder().is_empty() { &[lengths] } else { &[rest, lengths] };
+        #[cfg(target_arch = "x86_64")]
+        if self.hardware {
+            // The feature was detected when the key was set up.
+            let y = unsafe { x86::ghash(self.h, 0, &ciphertext[..ciphertext.len() / 16 * 16]) };
+            return unsafe { x86::ghash(self.h, y, tail.as_flattened()) };
+        }
+        let mut y = 0;
+        for block in (&mut blocks).chain(tail.iter().map(|block| &block[..])) {
+            y = self.mul_h(y ^ u128::from_be_bytes(block.try_into().unwrap()));
+        }
+        y
+    }
+
+    /// XORs `data` with the keystream of counter blocks from `counter` on.
+    fn ctr(&self, nonce: &[u8; 12], counter: u32, data: &mut [u8]) {
+        #[cfg(target_arch = "x86_64")]
+        if self.hardware {
+            return unsafe { x86::ctr(&self.keys, nonce, counter, data) };
+        }
+        let mut block = [0; 16];
+        block[..12].copy_from_slice(nonce);
+        for (i, chunk) in data.chunks_mut(16).enumerate() {
+            block[12..].copy_from_slice(&counter.wrapping_add(i as u32).to_be_bytes());
+            for (byte, key) in chunk.iter_mut().zip(aes256_encrypt(&self.keys, &block)) {
+                *byte ^= key;
+            }
+        }
+    }
+
+    fn tag(&self, nonce: &[u8; 12], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+        let mut tag = self.ghash(ciphertext).to_be_bytes();
+        self.ctr(nonce, 1, &mut tag);
+        tag
+    }
+
+    /// Encrypts `data` in place, returning its tag, unless it is longer than
+    /// [`Aes256Gcm::MAX_LEN`].
+    pub fn seal(&self, nonce: &[u8; 12], data: &mut [u8]) -> io::Result<[u8; TAG_LEN]> {
+        check_len(data, Self::MAX_LEN, "AES-GCM")?;
+        self.ctr(nonce, 2, data);
+        Ok(self.tag(nonce, data))
+    }
+
+    /// Checks `tag` and decrypts `data` in place, leaving it untouched if the tag is wrong.
+    pub fn open(&self, nonce: &[u8; 12], data: &mut [u8], tag: &[u8; TAG_LEN]) -> io::Result<()> {
+        check_len(data, Self::MAX_LEN, "AES-GCM")?;
+        if !tags_match(&self.tag(nonce, data), tag) {
+            return Err(tag_mismatch());
+        }
+        self.ctr(nonce, 2, data);
+        Ok(())
+    }
+}
+
+/// AES-NI and PCLMULQDQ, for CPUs that have them, and ChaCha20 on the SSE2 every x86-64
+/// CPU has.
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "sse2")]
+    fn rotate<const LEFT: i32, const RIGHT: i32>(v: __m128i) -> __m128i {
+        _mm_or_si128(_mm_slli_epi32::<LEFT>(v), _mm_srli_epi32::<RIGHT>(v))
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn quarter_round(x: &mut [__m128i; 16], a: usize, b: usize, c: usize, d: usize) {
+        x[a] = _mm_add_epi32(x[a], x[b]);
+        x[d] = rotate::<16, 16>(_mm_xor_si128(x[d], x[a]));
+        x[c] = _mm_add_epi32(x[c], x[d]);
+        x[b] = rotate::<12, 20>(_mm_xor_si128(x[b], x[c]));
+        x[a] = _mm_add_epi32(x[a], x[b]);
+        x[d] = rotate::<8, 24>(_mm_xor_si128(x[d], x[a]));
+        x[c] = _mm_add_epi32(x[c], x[d]);
+        x[b] = rotate::<7, 25>(_mm_xor_si128(x[b], x[c]));
+    }
+
+    /// XORs 256 bytes of `data` with four ChaCha20 blocks from the one `input` starts,
+    /// a word of each block per lane.
+    #[target_feature(enable = "sse2")]
+    pub(super) fn chacha20_xor4(input: &[u32; 16], data: &mut [u8]) {
+        let mut start: [__m128i; 16] = std::array::from_fn(|i| _mm_set1_epi32(input[i] as i32));
+        start[12] = _mm_add_epi32(start[12], _mm_set_epi32(3, 2, 1, 0));
+        let mut x = start;
+        for _ in 0..10 {
+            quarter_round(&mut x, 0, 4, 8, 12);
+            quarter_round(&mut x, 1, 5, 9, 13);
+            quarter_round(&mut x, 2, 6, 10, 14);
+            quarter_round(&mut x, 3, 7, 11, 15);
+            quarter_round(&mut x, 0, 5, 10, 15);
+            quarter_round(&mut x, 1, 6, 11, 12);
+            quarter_round(&mut x, 2, 7, 8, 13);
+            quarter_round(&mut x, 3, 4, 9, 14);
+        }
+        for (word, add) in x.iter_mut().zip(start) {
+            *word = _mm_add_epi32(*word, add);
+        }
+        // Transpose each group of four words into a row of each block.
+        for group in 0..4 {
+            let [a, b, c, d] = [x[group * 4], x[group * 4 + 1], x[group * 4 + 2], x[group * 4 + 3]];
+            let (ab_low, cd_low) = (_mm_unpacklo_epi32(a, b), _mm_unpacklo_epi32(c, d));
+            let (ab_high, cd_high) = (_mm_unpackhi_epi32(a, b), _mm_unpackhi_epi32(c, d));
+            let rows = [_mm_unpacklo_epi64(ab_low, cd_low), _mm_unpackhi_epi64(ab_low, cd_low), _mm_unpacklo_epi64(ab_high, cd_high), _mm_unpackhi_epi64(ab_high, cd_high)];
+            for (block, row) in rows.into_iter().enumerate() {
+                let at = &mut data[block * 64 + group * 16..block * 64 + group * 16 + 16];
+                unsafe { _mm_storeu_si128(at.as_mut_ptr().cast(), _mm_xor_si128(row, load(at))) };
+            }
+        }
+    }
+
+    fn load(bytes: &[u8]) -> __m128i {
+        assert!(bytes.len() >= 16);
+        unsafe { _mm_loadu_si128(bytes.as_ptr().cast()) }
+    }
+
+    fn from_u128(v: u128) -> __m128i {
+        load(&v.to_le_bytes())
+    }
+
+    fn to_u128(v: __m128i) -> u128 {
+        let mut bytes = [0; 16];
+        unsafe { _mm_storeu_si128(bytes.as_mut_ptr().cast(), v) };
+        u128::from_le_bytes(bytes)
+    }
+
+    /// XORs `data` with AES-256-CTR keystream; `keys` are the round keys as big-endian words.
+    #[target_feature(enable = "aes,sse2")]
+    pub(super) fn ctr(keys: &[u32; 60], nonce: &[u8; 12], counter: u32, data: &mut [u8]) {
+        let round_keys: [__m128i; 15] = std::array::from_fn(|round| {
+            let mut bytes = [0; 16];
+            for (out, word) in bytes.chunks_exact_mut(4).zip(&keys[round * 4..round * 4 + 4]) {
+                out.copy_from_slice(&word.to_be_bytes());
+            }
+            load(&bytes)
+        });
+        let mut block = [0; 16];
+        block[..12].copy_from_slice(nonce);
+        for (i, chunk) in data.chunks_mut(16).enumerate() {
+            block[12..].copy_from_slice(&counter.wrapping_add(i as u32).to_be_bytes());
+            let mut x = _mm_xor_si128(load(&block), round_keys[0]);
+            for key in &round_keys[1..14] {
+                x = _mm_aesenc_si128(x, *key);
+            }
+            x = _mm_aesenclast_si128(x, round_keys[14]);
+            if chunk.len() == 16 {
+                unsafe { _mm_storeu_si128(chunk.as_mut_ptr().cast(), _mm_xor_si128(x, load(chunk))) };
+            } else {
+                let keystream = to_u128(x).to_le_bytes();
+                for (byte, key) in chunk.iter_mut().zip(keystream) {
+                    *byte ^= key;
+                }
+            }
+        }
+    }
+
+    /// Multiplies in GCM's field, on values whose bytes are reversed so bit order is
+    /// numeric (Intel's carry-less multiplication white paper, algorithms 1 and 5).
+    #[target_feature(enable = "pclmulqdq,sse2")]
+    fn gf_mul(a: __m128i, b: __m128i) -> __m128i {
+        let mut low = _mm_clmulepi64_si128(a, b, 0x00);
+        let mut middle = _mm_xor_si128(_mm_clmulepi64_si128(a, b, 0x10), _mm_clmulepi64_si128(a, b, 0x01));
+        let mut high = _mm_clmulepi64_si128(a, b, 0x11);
+        low = _mm_xor_si128(low, _mm_slli_si128(middle, 8));
+        middle = _mm_srli_si128(middle, 8);
+        high = _mm_xor_si128(high, middle);
+        // Shift the 256-bit product left by one for the reflected bit order.
+        let low_carry = _mm_srli_epi32(low, 31);
+        let high_carry = _mm_srli_epi32(high, 31);
+        low = _mm_or_si128(_mm_slli_epi32(low, 1), _mm_slli_si128(low_carry, 4));
+        high = _mm_or_si128(_mm_or_si128(_mm_slli_epi32(high, 1), _mm_slli_si128(high_carry, 4)), _mm_srli_si128(low_carry, 12));
+        // Reduce modulo x^128 + x^7 + x^2 + x + 1.
+        let folded = _mm_xor_si128(_mm_xor_si128(_mm_slli_epi32(low, 31), _mm_slli_epi32(low, 30)), _mm_slli_epi32(low, 25));
+        low = _mm_xor_si128(low, _mm_slli_si128(folded, 12));
+        let shifted = _mm_xor_si128(_mm_xor_si128(_mm_srli_epi32(low, 1), _mm_srli_epi32(low, 2)), _mm_srli_epi32(low, 7));
+        let shifted = _mm_xor_si128(shifted, _mm_srli_si128(folded, 4));
+        _mm_xor_si128(high, _mm_xor_si128(low, shifted))
+    }
+
+    /// Folds whole 16-byte `blocks` into the GHASH value `y` under key `h`.
+    #[target_feature(enable = "pclmulqdq,sse2")]
+    pub(super) fn ghash(h: u128, y: u128, blocks: &[u8]) -> u128 {
+        let h = from_u128(h);
+        let mut y = from_u128(y);
+        for block in blocks.chunks_exact(16) {
+            y = gf_mul(_mm_xor_si128(y, from_u128(u128::from_be_bytes(block.try_into().unwrap()))), h);
+        }
+        to_u128(y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    /// Written as is, the baseline.
+    Plaintext,
+    Aes256Gcm,
+    XChaCha20Poly1305,
+}
+
+impl Cipher {
+    pub const ALL: [Cipher; 3] = [Cipher::Plaintext, Cipher::Aes256Gcm, Cipher::XChaCha20Poly1305];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Cipher::Plaintext => "none",
+            Cipher::Aes256Gcm => "aes-gcm",
+            Cipher::XChaCha20Poly1305 => "xchacha20",
+        }
+    }
+
+    pub fn nonce_len(self) -> usize {
+        match self {
+            Cipher::Plaintext => 0,
+            Cipher::Aes256Gcm => Aes256Gcm::NONCE_LEN,
+            Cipher::XChaCha20Poly1305 => XChaCha20Poly1305::NONCE_LEN,
+        }
+    }
+
+    /// What sealing adds to each file.
+    pub fn overhead(self) -> usize {
+        match self {
+            Cipher::Plaintext => 0,
+            _ => self.nonce_len() + TAG_LEN,
+        }
+    }
+}
+
+impl fmt::Display for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Cipher {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Cipher::ALL.into_iter().find(|cipher| cipher.name() == s).ok_or_else(|| "expected none, aes-gcm or xchacha20".to_string())
+    }
+}
+
+enum Keyed {
+    Plaintext,
+    Aes256Gcm(Box<Aes256Gcm>),
+    XChaCha20Poly1305(XChaCha20Poly1305),
+}
+
+/// A cipher and key that seal and open whole files, each as its nonce, the ciphertext and
+/// the tag. Every message gets a nonce drawn at random in full, so sealers sharing a key,
+/// in this process or another, only repeat one by chance: with AES-GCM's 96-bit nonces
+/// that stays negligible for up to [`Sealer::MAX_AES_GCM_MESSAGES`] messages per key (NIST
+/// SP 800-38D, 8.3), a limit on the key rather than the `Sealer`, which can only count its
+/// own. XChaCha20's 192-bit nonces have no such limit in practice.
+pub struct Sealer {
+    keyed: Keyed,
+    nonce_len: usize,
+    random: File,
+    sealed: AtomicU64,
+}
+
+impl Sealer {
+    pub const MAX_AES_GCM_MESSAGES: u64 = 1 << 32;
+
+    pub fn new(cipher: Cipher, key: &[u8; KEY_LEN]) -> io::Result<Sealer> {
+        let keyed = match cipher {
+            Cipher::Plaintext => Keyed::Plaintext,
+            Cipher::Aes256Gcm => Keyed::Aes256Gcm(Box::new(Aes256Gcm::new(key))),
+            Cipher::XChaCha20Poly1305 => Keyed::XChaCha20Poly1305(XChaCha20Poly1305::new(key)),
+        };
+        Ok(Sealer { keyed, nonce_len: cipher.nonce_len(), random: File::open("/dev/urandom")?, sealed: AtomicU64::new(0) })
+    }
+
+    /// `data` encrypted under a fresh random nonce. Fails if `data` is too long for one
+    /// message, or once this `Sealer` has sealed as many AES-GCM messages as a key may.
+    pub fn seal(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        if let Keyed::Aes256Gcm(_) = self.keyed
+            && self.sealed.fetch_add(1, Ordering::Relaxed) >= Self::MAX_AES_GCM_MESSAGES
+        {
+            return Err(io::Error::other(format!("this key has sealed {} AES-GCM messages, as many as random nonces allow", Self::MAX_AES_GCM_MESSAGES)));
+        }
+        let mut sealed = vec![0; self.nonce_len];
+        (&self.random).read_exact(&mut sealed)?;
+        sealed.reserve(data.len() + TAG_LEN);
+        sealed.extend_from_slice(data);
+        let (nonce, body) = sealed.split_at_mut(self.nonce_len);
+        let tag = match &self.keyed {
+            Keyed::Plaintext => return Ok(sealed),
+            Keyed::Aes256Gcm(cipher) => cipher.seal((&*nonce).try_into().unwrap(), body)?,
+            Keyed::XChaCha20Poly1305(cipher) => cipher.seal((&*nonce).try_into().unwrap(), body)?,
+        };
+        sealed.extend_from_slice(&tag);
+        Ok(sealed)
+    }
+
+    /// The contents [`Sealer::seal`] sealed into `sealed`, failing with
+    /// [`io::ErrorKind::InvalidData`] if they were altered or sealed under another key.
+    pub fn open(&self, sealed: &[u8]) -> io::Result<Vec<u8>> {
+        if let Keyed::Plaintext = self.keyed {
+            return Ok(sealed.to_vec());
+        }
+        if sealed.len() < self.nonce_len + TAG_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "sealed file too short"));
+        }
+        let (nonce, rest) = sealed.split_at(self.nonce_len);
+        let (body, tag) = rest.split_at(rest.len() - TAG_LEN);
+        let tag = tag.try_into().unwrap();
+        let mut data = body.to_vec();
+        match &self.keyed {
+            Keyed::Plaintext => unreachable!(),
+            Keyed::Aes256Gcm(cipher) => cipher.open(nonce.try_into().unwrap(), &mut data, tag)?,
+            Keyed::XChaCha20Poly1305(cipher) => cipher.open(nonce.try_into().unwrap(), &mut data, tag)?,
+        }
+        Ok(data)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CipherResult {
+    pub cipher: Cipher,
+    /// Sealing and writing every file.
+    pub create: Duration,
+    /// Reading and opening every file.
+    pub read: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EncryptResult {
+    /// The workload's bytes, in plaintext.
+    pub source_bytes: u64,
+    /// Whether AES-GCM ran on AES-NI rather than tables.
+    pub aes_hardware: bool,
+    pub ciphers: Vec<CipherResult>,
+    pub failures: Vec<Failure>,
+}
+
+/// For each of `ciphers`, under a fresh random key, times creating the configured workload
+/// in `dir_path` with every file sealed, then reading it back and opening it, evicting the
+/// files from the page cache in between when `options.cold_read` is set. Each cipher's
+/// files are removed before the next.
+pub fn run(dir_path: &Path, ciphers: &[Cipher], options: &Options) -> io::Result<EncryptResult> {
+    let files = options.workload.files;
+    let mut result = EncryptResult { source_bytes: files as u64 * options.workload.size() as u64, ..EncryptResult::default() };
+    result.aes_hardware = Aes256Gcm::new(&[0; KEY_LEN]).is_hardware();
+    options.failures.take("");
+    for &cipher in ciphers {
+        let sealer = Sealer::new(cipher, &random_key()?)?;
+        let dir = dir_path.join(cipher.name());
+        fs::create_dir_all(&dir)?;
+        let paths = bench::file_paths(&dir, files);
+
+        let start = Instant::now();
+        bench::phase(options, format!("Encrypt {} create", cipher), format!("encrypt/{}/create", cipher), "create", &mut result.failures, || {
+            bench::each_indexed(&paths, options, |index, path| fs::write(path, bench::with_content(options, index, false, |content| sealer.seal(content))?))
+        })?;
+        let create = start.elapsed();
+
+        if options.cold_read {
+            cache::evict_from_cache(&paths)?;
+        }
+        let start = Instant::now();
+        bench::phase(options, format!("Encrypt {} read", cipher), format!("encrypt/{}/read", cipher), "read", &mut result.failures, || {
+            bench::each_indexed(&paths, options, |index, path| bench::read_done(options, index, path, &sealer.open(&fs::read(path)?)?))
+        })?;
+        let read = start.elapsed();
+
+        result.ciphers.push(CipherResult { cipher, create, read });
+        fs::remove_dir_all(&dir)?;
+    }
+    Ok(result)
+}
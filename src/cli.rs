@@ -0,0 +1,4558 @@
+//! Argument parsing and the `io bench` commands.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::baseline::{self, Anomaly, Baseline, Conditions, Metadata, Percent};
+use crate::job;
+use crate::processes;
+use crate::recommend::Recommendation;
+use crate::report::{self, ReportFormat, Run};
+use crate::tui::Dashboard;
+use ::io::append::{self, AppendConfig, AppendMode};
+use ::io::anomaly::{self, Criteria};
+use ::io::archive;
+use ::io::atomic;
+use ::io::bench::{self, Failure, FileTime, Latency, MmapFlush, Options, PhaseMemory, PhaseTimes, PhaseUsage, ReadMethod, RunResult, STRATEGIES, Scheduling, SyncMode, UpdateMethod, WorkerShare, Workload};
+use ::io::breakdown::{Breakdown, Slice};
+use ::io::bundle::Bundle;
+use ::io::cache::Hint;
+use ::io::calibration::{self, Calibration, SizeChoice};
+use ::io::cancel;
+use ::io::cas;
+use ::io::cgroup::Limits;
+use ::io::checkpoint::{self, Checkpoint};
+use ::io::cipher::{self, Cipher};
+use ::io::dedupe;
+use ::io::coalesce;
+use ::io::codec::{self, Codec};
+use ::io::copy;
+use ::io::crash::{self, CrashConfig, CrashMode, CrashStrategy};
+use ::io::crossover::{self, Thresholds};
+use ::io::digest::{self, Algorithm};
+use ::io::dirs::{self, OpenPath};
+use ::io::dirty::{Backpressure, Relief};
+use ::io::engine::Engine;
+use ::io::environment::{Capability, Environment};
+use ::io::fdlimit::{self, OpenFileLimiter};
+use ::io::filesystem::Filesystem;
+use ::io::golden::{Recorder, Recording};
+use ::io::handles;
+use ::io::human::{self, Align, Table};
+use ::io::health::{Device, Snapshot};
+use ::io::idle::{IdleDetector, IdleThresholds};
+use ::io::json::Value;
+#[cfg(target_os = "linux")]
+use ::io::linked;
+use ::io::links;
+use ::io::lock::{self, LockConfig, LockKind};
+use ::io::log::{self, Level};
+use ::io::memory::Memory;
+use ::io::names::{self, Names};
+use ::io::netfs::{self, RpcCounter, RpcSample, SimulatedNetwork};
+use ::io::noise::{self, Quiet, Readings};
+use ::io::oplog::{self, Timing, Trace};
+use ::io::order::{self, OrderResult, ReadOrder};
+use ::io::pace::Rate;
+use ::io::metadata::{self, MetaPhase, META_STRATEGIES};
+use ::io::mirror;
+use ::io::mkdir;
+use ::io::mobile;
+use ::io::parquet;
+use ::io::pattern::{self, Generator, Pattern};
+use ::io::permissions;
+use ::io::pinning::{self, NumaNode, NumaPlacement};
+use ::io::pipeline;
+use ::io::population::{Aging, Source, Store};
+use ::io::platform::{self, Clock};
+use ::io::precondition::Precondition;
+use ::io::priority::Priority;
+use ::io::probe::Probe;
+use ::io::profile::{ProfileKind, Profiler};
+use ::io::progress::Progress;
+use ::io::queues::{self, QueueCounter, QueueSample};
+use ::io::ramdisk::Ramdisk;
+use ::io::ranged;
+use ::io::read;
+use ::io::readonly::{self, DataSet};
+#[cfg(target_os = "linux")]
+use ::io::registered::{self, Tier};
+use ::io::remove;
+use ::io::rename;
+use ::io::rundir::{self, RunDir};
+#[cfg(feature = "s3")]
+use ::io::s3;
+use ::io::sample::{self, Estimate, Sampler};
+use ::io::scan;
+use ::io::schedule::{self, Scheduler, Window};
+use ::io::select::{Glob, Selector};
+use ::io::selftest::{self, Check, Outcome};
+use ::io::snapshot::{self, Instability};
+use ::io::soak::{self, Iteration, State};
+use ::io::space::{self, Reservation};
+use ::io::sparse;
+use ::io::stash;
+use ::io::throttle::Throttle;
+use ::io::treemap::{self, Tree};
+use ::io::update;
+use ::io::watch::{self, WatchBackend, WatchConfig};
+use ::io::watchdog::{OnTimeout, Timeouts, Watchdog};
+use ::io::wear::{self, Flash};
+use ::io::workload;
+use ::io::xattr;
+
+pub fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+pub fn flag_value<T>(args: &mut impl Iterator<Item = String>, flag: &str) -> io::Result<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = args.next().ok_or_else(|| invalid_input(format!("missing value for {}", flag)))?;
+    value
+        .parse()
+        .map_err(|e| invalid_input(format!("invalid value for {}: {}", flag, e)))
+}
+
+/// Applies `--log-level` and `--log-format`, which every command takes anywhere on its
+/// command line, and returns the other arguments.
+pub fn log_flags(args: impl Iterator<Item = String>) -> io::Result<Vec<String>> {
+    let mut args = args;
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--log-level" => log::set_level(flag_value(&mut args, &arg)?),
+            "--log-format" => log::set_format(flag_value(&mut args, &arg)?),
+            _ => rest.push(arg),
+        }
+    }
+    Ok(rest)
+}
+
+/// A comma-separated list such as `1,2,4,8`.
+struct List<T>(Vec<T>);
+
+impl<T: FromStr> FromStr for List<T>
+where
+    T::Err: fmt::Display,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        s.split(',')
+            .map(|item| item.trim().parse().map_err(|e| format!("'{}': {}", item, e)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(List)
+    }
+}
+
+/// A byte count with an optional binary suffix: `100`, `100B`, `4K`, `10M`, `1G`.
+pub struct ByteSize(pub usize);
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let digits = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+        let (number, suffix) = s.split_at(digits);
+        let multiplier: f64 = match suffix.trim().to_ascii_uppercase().as_str() {
+            "" | "B" => 1.0,
+            "K" | "KB" | "KIB" => 1024.0,
+            "M" | "MB" | "MIB" => 1024.0 * 1024.0,
+            "G" | "GB" | "GIB" => 1024.0 * 1024.0 * 1024.0,
+            other => return Err(format!("unknown size suffix '{}'", other)),
+        };
+        let number: f64 = number.parse().map_err(|_| format!("'{}' is not a size", s))?;
+        Ok(ByteSize((number * multiplier) as usize))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [(&str, usize); 3] = [("G", 1 << 30), ("M", 1 << 20), ("K", 1 << 10)];
+        match UNITS.iter().find(|(_, unit)| self.0 >= *unit && self.0.is_multiple_of(*unit)) {
+            Some((suffix, unit)) => write!(f, "{}{}", self.0 / unit, suffix),
+            None => write!(f, "{}B", self.0),
+        }
+    }
+}
+
+/// A ratio of at least 1 such as `3` or `3:1`.
+pub struct Ratio(pub f64);
+
+impl FromStr for Ratio {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        pattern::parse_ratio(s).map(Ratio).ok_or_else(|| format!("'{}' is not a ratio of at least 1", s))
+    }
+}
+
+/// `--output human` or `--output human:<version>`: asks for the stable human layout and
+/// prints its version first, refusing versions this build doesn't produce.
+pub enum Output {
+    Human,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let version = match s.split_once(':') {
+            None if s == "human" => human::LAYOUT_VERSION,
+            Some(("human", version)) => version.strip_prefix('v').unwrap_or(version).parse().map_err(|_| format!("'{}' is not a layout version", version))?,
+            _ => return Err(format!("unknown output '{}', expected human or human:<version>", s)),
+        };
+        if version != human::LAYOUT_VERSION {
+            return Err(format!("human output v{} is not available, this build writes v{}", version, human::LAYOUT_VERSION));
+        }
+        Ok(Output::Human)
+    }
+}
+
+/// `--preset <name>`: a set of defaults for a kind of storage, which later flags override.
+pub enum Preset {
+    /// SD cards and eMMC: erase-block-sized files written whole into preallocated extents,
+    /// so the device never rewrites a block it only partly received, and a wear estimate.
+    Flash,
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "flash" => Ok(Preset::Flash),
+            _ => Err(format!("unknown preset '{}', expected flash", s)),
+        }
+    }
+}
+
+/// Files the flash preset writes, 256 MiB in all: enough to cycle through several erase
+/// blocks without wearing a card out for a benchmark.
+const FLASH_FILES: usize = 64;
+
+/// A duration such as `500ms`, `30s`, `5m` or `1h`; a bare number is seconds.
+pub struct DurationArg(pub Duration);
+
+impl FromStr for DurationArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let digits = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+        let (number, unit) = s.split_at(digits);
+        let number: f64 = number.parse().map_err(|_| format!("'{}' is not a duration", s))?;
+        let seconds = match unit {
+            "ms" => number / 1000.0,
+            "" | "s" => number,
+            "m" => number * 60.0,
+            "h" => number * 3600.0,
+            other => return Err(format!("unknown duration unit '{}'", other)),
+        };
+        Duration::try_from_secs_f64(seconds).map(DurationArg).map_err(|e| e.to_string())
+    }
+}
+
+/// Options shared by the `io bench` commands and `io serve`.
+pub struct BenchArgs {
+    pub options: Options,
+    pub crossover: bool,
+    /// Probe the benchmark directory's filesystem first and keep `adaptive_io` off what it
+    /// gets wrong.
+    pub probe: bool,
+    pub threads: Vec<usize>,
+    pub files: Vec<usize>,
+    pub sizes: Vec<usize>,
+    pub csv: Option<PathBuf>,
+    pub raise_nofile: bool,
+    pub max_open: Option<usize>,
+    pub window: Option<Window>,
+    pub idle: Option<IdleThresholds>,
+    /// Warn before the strategies run about what could make them noisy.
+    pub preflight: bool,
+    /// Wait up to this long for the machine to quiet down before the strategies run.
+    pub quiesce: Option<Duration>,
+    pub save_baseline: Option<PathBuf>,
+    /// Write the fastest strategy per phase to this JSON file.
+    pub recommend: Option<PathBuf>,
+    /// Render the phase times as a Markdown or HTML comparison report.
+    pub report: Option<ReportFormat>,
+    /// Where the report goes instead of standard output.
+    pub report_file: Option<PathBuf>,
+    /// Allocate the workload's projected size up front and hold it until the phases start.
+    pub reserve_space: bool,
+    /// Run even when the filesystem has less free space than the workload needs.
+    pub skip_space_check: bool,
+    /// What the workload's files are called.
+    pub names: Names,
+    /// An existing tree `io bench read` reads in place instead of the generated workload.
+    pub root: Option<PathBuf>,
+    /// Which files of an existing tree `io bench read`, `readonly` and `order` use.
+    pub selector: Selector,
+    /// Age the filesystems under test before the benchmark.
+    pub precondition: Option<Precondition>,
+    /// How long `io bench soak` keeps rerunning the workload.
+    pub soak_duration: Duration,
+    /// Where `io bench soak` appends each iteration, as CSV or JSON Lines by extension.
+    pub soak_log: PathBuf,
+    pub baseline: Option<PathBuf>,
+    pub threshold: Percent,
+    pub populations: Store,
+    pub tui: bool,
+    pub html: Option<PathBuf>,
+    /// Write every file's operation time to this Parquet file.
+    pub parquet: Option<PathBuf>,
+    /// Keep every file's time and flag phases whose times are bimodal or have outliers.
+    pub anomalies: bool,
+    pub job: Option<PathBuf>,
+    pub output: Option<Output>,
+    /// Single worker and bounded-memory statistics, for small boards and containers.
+    pub embedded: bool,
+    /// Where `io bench rename` moves files across filesystems.
+    pub move_to: Option<PathBuf>,
+    /// Estimate each strategy's wear on flash with this geometry.
+    pub wear: Option<Flash>,
+    /// Read device temperature and SMART attributes before and after the strategies run.
+    pub health: bool,
+    /// Directories `io bench open` nests its files in.
+    pub depth: usize,
+    /// Random walks `io bench readonly` estimates from instead of listing the whole tree.
+    pub sample: Option<usize>,
+    /// Order `io bench readonly` reads the tree in, instead of path order.
+    pub read_order: Option<ReadOrder>,
+    /// `--read-order auto`: measure every order first and read in the fastest.
+    pub pick_read_order: bool,
+    /// Directories, one per device, `io bench` runs in instead of the temp directory.
+    pub targets: Vec<PathBuf>,
+    /// Spread one run's files over the targets instead of running each target separately.
+    pub stripe: bool,
+    /// Size of the tmpfs `--target ramdisk` mounts, instead of one sized to the workload.
+    pub ramdisk_size: Option<u64>,
+    /// Write every file's backend decision and outcome to this golden file.
+    pub record: Option<PathBuf>,
+    /// Package everything about the run into this `.tar.zst`.
+    pub bundle: Option<PathBuf>,
+    /// Each strategy runs once per scheduling, `--schedule both` comparing them.
+    pub schedules: Vec<Scheduling>,
+    /// Keep workers and their buffers on one NUMA node, or interleave them over all nodes.
+    pub numa: Option<NumaPlacement>,
+    /// Nice level and I/O class for the workers.
+    pub priority: Priority,
+    /// Run each strategy in this many worker processes instead of one.
+    pub processes: Option<usize>,
+    /// The endpoint and bucket `io bench s3` runs against.
+    #[cfg(feature = "s3")]
+    pub s3: s3::Config,
+    /// Create the bucket before `io bench s3` uses it.
+    #[cfg(feature = "s3")]
+    pub create_bucket: bool,
+    /// The codecs `io bench compress` compares.
+    pub codecs: Vec<Codec>,
+    /// The hashes `io bench hash` compares.
+    pub algorithms: Vec<Algorithm>,
+    /// The ciphers `io bench encrypt` compares.
+    pub ciphers: Vec<Cipher>,
+    /// The update methods `io bench update` compares.
+    pub update_methods: Vec<UpdateMethod>,
+    /// The read methods `io bench read` compares.
+    pub read_methods: Vec<ReadMethod>,
+    /// Records `io bench append` writes across its threads.
+    pub records: usize,
+    /// The write strategies `io bench crash` crashes.
+    pub crash_strategies: Vec<CrashStrategy>,
+    /// Whether `io bench crash` kills the worker or cuts its disk's power.
+    pub crash_mode: CrashMode,
+    /// Crashes per strategy.
+    pub trials: usize,
+    /// The longest `io bench crash` lets a worker write before killing it.
+    pub crash_after: Duration,
+    /// The backend `io bench watch` watches with.
+    pub watch_backend: WatchBackend,
+    /// Leaf directories `io bench watch` spreads the files over.
+    pub watch_dirs: usize,
+    /// How long `io bench watch` waits for late events after the update.
+    pub settle: Duration,
+    /// The lock kinds `io bench lock` compares.
+    pub lock_kinds: Vec<LockKind>,
+    /// Lock sets `io bench lock` takes across its threads.
+    pub acquisitions: usize,
+    /// Lock files in each set.
+    pub locks_per_acquisition: usize,
+    /// How long `io bench lock` waits for a set before giving up on it.
+    pub lock_timeout: Duration,
+    /// Bytes of metadata `io bench xattr` stores per file.
+    pub xattr_size: usize,
+    /// Files `io bench handles` keeps open; by default what the open-file budget leaves
+    /// the workers.
+    pub handle_cache: Option<usize>,
+    /// Threads `io bench pipeline` opens files in.
+    pub open_threads: usize,
+    /// Files per submission `io bench linked` tries.
+    pub batches: Vec<u32>,
+}
+
+/// The block device the benchmark directory is on, looked up from its parent before the
+/// directory exists.
+fn bench_device() -> Option<Device> {
+    let dir = bench::get_dir();
+    Device::of(&dir).or_else(|| dir.parent().and_then(Device::of))
+}
+
+/// One line on what `dir` is stored on, so results can't be read without it.
+fn report_filesystem(label: &str, dir: &Path) {
+    let Some(filesystem) = Filesystem::of(dir) else {
+        return;
+    };
+    let mut line = format!("{}: {} at {} from {} ({})", label, filesystem.fs_type, filesystem.mount_point.display(), filesystem.source, filesystem.options);
+    if let Some(device) = &filesystem.device {
+        let kind = match filesystem.rotational {
+            Some(true) => ", rotational",
+            Some(false) => ", non-rotational",
+            None => "",
+        };
+        line.push_str(&format!(", device {}{}", device.name, kind));
+    }
+    if let (Some(available), Some(total)) = (filesystem.available, filesystem.total) {
+        line.push_str(&format!(", {} free of {}", human::bytes(available), human::bytes(total)));
+    }
+    if let Some(block_size) = filesystem.block_size {
+        line.push_str(&format!(", {} blocks", human::bytes(block_size)));
+    }
+    println!("{}", line);
+}
+
+/// Creates this run's benchmark directory and reports the filesystem it is on. The
+/// directory is removed when the returned guard drops, even if the run fails on the way.
+pub fn bench_dir() -> io::Result<RunDir> {
+    let dir_path = RunDir::create(bench::get_dir())?;
+    report_filesystem("Filesystem", &dir_path);
+    Ok(dir_path)
+}
+
+/// `--target ramdisk`: a tmpfs set up for the run rather than a directory.
+const RAMDISK: &str = "ramdisk";
+
+/// A benchmark directory and the label its rows get.
+type Target = (String, RunDir);
+
+/// `--precondition`: ages the filesystem of each of `dirs` with filler files in a run
+/// directory next to it, returned so the filler stays for the benchmark and goes with it.
+fn precondition(args: &BenchArgs, dirs: &[&Path]) -> io::Result<Vec<RunDir>> {
+    let Some(precondition) = &args.precondition else {
+        return Ok(Vec::new());
+    };
+    let mut fillers = Vec::new();
+    for dir in dirs {
+        let name = dir.file_name().map_or_else(|| rundir::run_id().into(), |name| name.to_string_lossy());
+        let filler = RunDir::create(dir.with_file_name(format!("{}-filler", name)))?;
+        println!(
+            "Preconditioning {}: filling to {}% used, then deleting {}% of the filler",
+            dir.display(),
+            human::decimal(precondition.utilization * 100.0, 0),
+            human::decimal(precondition.fragmentation * 100.0, 0)
+        );
+        let report = precondition.run(&filler)?;
+        println!(
+            "Preconditioned: wrote {} in {} files and deleted {} in {}; {}% used, was {}%",
+            human::bytes(report.bytes_written),
+            human::thousands(report.files_written as u64),
+            human::bytes(report.bytes_deleted),
+            human::thousands(report.files_deleted as u64),
+            human::decimal(report.utilization_after * 100.0, 1),
+            human::decimal(report.utilization_before * 100.0, 1)
+        );
+        fillers.push(filler);
+    }
+    Ok(fillers)
+}
+
+/// Checks that each of `dirs` has room for `needed` bytes, and with `--reserve-space` claims
+/// it, so a run that can't fit fails before its first phase rather than in the middle of
+/// one. The reservations are returned to be released when the phases start.
+fn claim_space(args: &BenchArgs, dirs: &[&Path], needed: u64) -> io::Result<Vec<Reservation>> {
+    if !args.skip_space_check {
+        for dir in dirs {
+            space::check(dir, needed).map_err(|e| io::Error::new(e.kind(), format!("{}; free some space or pass --no-space-check", e)))?;
+        }
+    }
+    if !args.reserve_space {
+        return Ok(Vec::new());
+    }
+    let reservations = dirs.iter().map(|dir| Reservation::new(dir, needed)).collect::<io::Result<Vec<_>>>()?;
+    println!("Reserved {} in {} for the run", human::bytes(needed), if dirs.len() == 1 { "its directory".to_string() } else { format!("each of {} directories", dirs.len()) });
+    Ok(reservations)
+}
+
+/// Room a ramdisk gets without `--ramdisk-size`: every file at once, each rounded up to
+/// whole pages, twice over for the update phases that write a copy first.
+fn ramdisk_room(files: usize, size: usize) -> u64 {
+    space::projected(files, size).max(64 << 20)
+}
+
+/// The directories `io bench` runs in, labelled for its tables: a run directory inside
+/// each `--target` (never the target itself, since it is removed afterwards), or
+/// the default benchmark directory. `--target ramdisk` sets up a tmpfs with `room` bytes,
+/// returned so it lives as long as the run; given alone, the default directory runs too,
+/// as the real disk it bounds.
+fn target_dirs(targets: &[PathBuf], room: u64) -> io::Result<(Vec<Target>, Option<Ramdisk>)> {
+    let mut dirs = Vec::new();
+    if targets.iter().all(|target| target == Path::new(RAMDISK)) {
+        dirs.push((mobile::temp_dir().display().to_string(), bench_dir()?));
+    }
+    let mut ramdisk = None;
+    for target in targets {
+        let dir_path = if target == Path::new(RAMDISK) {
+            if ramdisk.is_some() {
+                return Err(invalid_input("--target ramdisk given twice".to_string()));
+            }
+            let disk = Ramdisk::setup(room)?;
+            let how = if disk.mounted() { format!("tmpfs of {} mounted for this run", human::bytes(room)) } else { "directory on an existing tmpfs".to_string() };
+            println!("Ramdisk: {} ({})", disk.dir().display(), how);
+            let dir_path = rundir::run_dir(disk.dir());
+            ramdisk = Some(disk);
+            dir_path
+        } else {
+            rundir::run_dir(target)
+        };
+        let dir_path = RunDir::create(dir_path)?;
+        report_filesystem(&format!("Target {}", target.display()), &dir_path);
+        dirs.push((target.display().to_string(), dir_path));
+    }
+    Ok((dirs, ramdisk))
+}
+
+/// Prints each real target's total next to the ramdisk's: how much of a strategy's time
+/// went to storage rather than the kernel and the benchmark itself.
+fn print_storage_share(targets: &[Target], totals: &[(String, Vec<Duration>)]) {
+    let Some(ramdisk) = targets.iter().position(|(label, _)| label == RAMDISK) else {
+        return;
+    };
+    println!("\nNo-storage upper bound from the ramdisk:");
+    let mut table = Table::new(["Strategy", "Target", "Total ms", "Ramdisk ms", "Storage share"]);
+    for (strategy, times) in totals {
+        for (i, (label, _)) in targets.iter().enumerate().filter(|&(i, _)| i != ramdisk) {
+            let (disk, ram) = (times[i].as_secs_f64(), times[ramdisk].as_secs_f64());
+            let share = if disk > 0.0 { ((disk - ram) / disk * 100.0).max(0.0) } else { 0.0 };
+            table.row([strategy.clone(), label.clone(), human::millis(times[i]), human::millis(times[ramdisk]), format!("{}%", human::decimal(share, 1))]);
+        }
+    }
+    print!("{}", table.render());
+}
+
+fn positive_list<T: FromStr>(args: &mut impl Iterator<Item = String>, flag: &str, value: impl Fn(T) -> usize) -> io::Result<Vec<usize>>
+where
+    T::Err: fmt::Display,
+{
+    let List(items) = flag_value::<List<T>>(args, flag)?;
+    let items: Vec<usize> = items.into_iter().map(value).collect();
+    if items.is_empty() || items.contains(&0) {
+        return Err(invalid_input(format!("{} expects positive values", flag)));
+    }
+    Ok(items)
+}
+
+fn positive(args: &mut impl Iterator<Item = String>, flag: &str) -> io::Result<usize> {
+    match flag_value(args, flag)? {
+        0 => Err(invalid_input(format!("{} expects a positive value", flag))),
+        n => Ok(n),
+    }
+}
+
+/// A worker per CPU, but no more than the cgroup's CPU quota allows.
+fn default_threads() -> usize {
+    let available = rayon::current_num_threads();
+    Limits::detect().map_or(available, |limits| limits.threads(available))
+}
+
+pub fn parse_bench_args(args: impl Iterator<Item = String>) -> io::Result<BenchArgs> {
+    let mut parsed = BenchArgs {
+        options: Options::default(),
+        crossover: false,
+        probe: false,
+        threads: vec![default_threads()],
+        files: vec![bench::NUM_FILES],
+        sizes: vec![Workload::default().size()],
+        csv: None,
+        raise_nofile: false,
+        max_open: None,
+        window: None,
+        idle: None,
+        preflight: false,
+        quiesce: None,
+        save_baseline: None,
+        recommend: None,
+        report: None,
+        report_file: None,
+        reserve_space: false,
+        skip_space_check: false,
+        names: Names::Ascii,
+        root: None,
+        selector: Selector::new(),
+        precondition: None,
+        soak_duration: Duration::from_secs(3600),
+        soak_log: PathBuf::from("soak.jsonl"),
+        baseline: None,
+        threshold: Percent(10.0),
+        populations: Store::default(),
+        tui: false,
+        html: None,
+        parquet: None,
+        anomalies: false,
+        job: None,
+        output: None,
+        embedded: false,
+        move_to: None,
+        wear: None,
+        health: false,
+        depth: dirs::DEFAULT_DEPTH,
+        sample: None,
+        read_order: None,
+        pick_read_order: false,
+        targets: Vec::new(),
+        stripe: false,
+        ramdisk_size: None,
+        record: None,
+        bundle: None,
+        schedules: vec![Scheduling::Stealing],
+        numa: None,
+        priority: Priority::default(),
+        processes: None,
+        #[cfg(feature = "s3")]
+        s3: s3::Config::default(),
+        #[cfg(feature = "s3")]
+        create_bucket: false,
+        codecs: Codec::ALL.to_vec(),
+        algorithms: Algorithm::ALL.to_vec(),
+        ciphers: Cipher::ALL.to_vec(),
+        update_methods: UpdateMethod::ALL.to_vec(),
+        read_methods: ReadMethod::ALL.to_vec(),
+        records: 100_000,
+        crash_strategies: CrashStrategy::ALL.to_vec(),
+        crash_mode: CrashMode::Kill,
+        trials: 5,
+        crash_after: Duration::from_millis(200),
+        watch_backend: WatchBackend::native(),
+        watch_dirs: 64,
+        settle: Duration::from_secs(1),
+        lock_kinds: LockKind::ALL.to_vec(),
+        acquisitions: 10_000,
+        locks_per_acquisition: 2,
+        lock_timeout: Duration::from_secs(1),
+        xattr_size: 64,
+        handle_cache: None,
+        open_threads: pipeline::DEFAULT_OPEN_THREADS,
+        #[cfg(target_os = "linux")]
+        batches: linked::DEFAULT_BATCHES.to_vec(),
+        #[cfg(not(target_os = "linux"))]
+        batches: Vec::new(),
+    };
+    let options = &mut parsed.options;
+    let mut args = args;
+    let (mut pattern, mut seed, mut dedupe, mut block) = (None, None, None, None);
+    let (mut queues, mut match_queues) = (false, false);
+    let mut timeouts = Timeouts::default();
+    let (mut bandwidth, mut iops) = (None, None);
+    let (mut relief, mut dirty_ceiling) = (None, None);
+    let mut net_latency: Option<netfs::Latency> = None;
+    let (mut profile, mut profile_dir) = (None::<ProfileKind>, PathBuf::from("profiles"));
+    let (mut utilization, mut fragmentation) = (None::<Percent>, None::<Percent>);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--residency" => {
+                options.residency_sample.get_or_insert(bench::DEFAULT_RESIDENCY_SAMPLE);
+            }
+            "--preallocate" => options.preallocate = true,
+            "--cold" => options.cold_read = true,
+            "--fresh-buffers" => options.fresh_buffers = true,
+            "--compare-buffers" => options.compare_buffers = true,
+            "--memory" => options.sample_memory = true,
+            "--huge-pages" => options.huge_pages = true,
+            "--crossover" => parsed.crossover = true,
+            "--probe" => parsed.probe = true,
+            "--residency-sample" => options.residency_sample = Some(flag_value::<usize>(&mut args, &arg)?.max(1)),
+            "--madvise" => options.madvise = Some(flag_value(&mut args, &arg)?),
+            "--fadvise" => options.fadvise = Some(flag_value(&mut args, &arg)?),
+            "--sync" => options.sync = flag_value(&mut args, &arg)?,
+            "--mmap-flush" => options.mmap_flush = flag_value(&mut args, &arg)?,
+            "--pattern" => pattern = Some(flag_value(&mut args, &arg)?),
+            "--seed" => seed = Some(flag_value(&mut args, &arg)?),
+            "--dedupe" => dedupe = Some(flag_value::<Ratio>(&mut args, &arg)?.0),
+            "--block" => block = Some(flag_value::<ByteSize>(&mut args, &arg)?.0.max(1)),
+            "--verify" => options.verify = true,
+            "--threads" => parsed.threads = positive_list(&mut args, &arg, |n: usize| n)?,
+            "--files" => parsed.files = positive_list(&mut args, &arg, |n: usize| n)?,
+            "--size" | "--sizes" => parsed.sizes = positive_list(&mut args, &arg, |ByteSize(n)| n)?,
+            "--csv" => parsed.csv = Some(flag_value(&mut args, &arg)?),
+            "--raise-nofile" => parsed.raise_nofile = true,
+            "--max-open" => parsed.max_open = Some(flag_value::<usize>(&mut args, &arg)?.max(1)),
+            "--tui" => parsed.tui = true,
+            "--health" => parsed.health = true,
+            "--open-at" => options.open_at = Some(flag_value(&mut args, &arg)?),
+            "--depth" => parsed.depth = flag_value(&mut args, &arg)?,
+            "--sample" => {
+                parsed.sample.get_or_insert(sample::DEFAULT_PROBES);
+            }
+            "--probes" => parsed.sample = Some(flag_value::<usize>(&mut args, &arg)?.max(1)),
+            "--read-order" => match flag_value::<String>(&mut args, &arg)?.as_str() {
+                "auto" => parsed.pick_read_order = true,
+                order => parsed.read_order = Some(order.parse()?),
+            },
+            "--queues" => queues = true,
+            "--match-queues" => match_queues = true,
+            "--embedded" => parsed.embedded = true,
+            "--move-to" => parsed.move_to = Some(flag_value(&mut args, &arg)?),
+            #[cfg(feature = "s3")]
+            "--endpoint" => parsed.s3.endpoint = flag_value(&mut args, &arg)?,
+            #[cfg(feature = "s3")]
+            "--bucket" => parsed.s3.bucket = flag_value(&mut args, &arg)?,
+            #[cfg(feature = "s3")]
+            "--region" => parsed.s3.region = flag_value(&mut args, &arg)?,
+            #[cfg(feature = "s3")]
+            "--part-size" => parsed.s3.part_size = flag_value::<ByteSize>(&mut args, &arg)?.0,
+            #[cfg(feature = "s3")]
+            "--create-bucket" => parsed.create_bucket = true,
+            "--codecs" => parsed.codecs = flag_value::<List<Codec>>(&mut args, &arg)?.0,
+            "--algorithms" => parsed.algorithms = flag_value::<List<Algorithm>>(&mut args, &arg)?.0,
+            "--ciphers" => parsed.ciphers = flag_value::<List<Cipher>>(&mut args, &arg)?.0,
+            "--update-methods" => parsed.update_methods = flag_value::<List<UpdateMethod>>(&mut args, &arg)?.0,
+            "--read-methods" => parsed.read_methods = flag_value::<List<ReadMethod>>(&mut args, &arg)?.0,
+            "--records" => parsed.records = flag_value(&mut args, &arg)?,
+            "--crash-strategies" => parsed.crash_strategies = flag_value::<List<CrashStrategy>>(&mut args, &arg)?.0,
+            "--crash-mode" => parsed.crash_mode = flag_value(&mut args, &arg)?,
+            "--trials" => parsed.trials = flag_value::<usize>(&mut args, &arg)?.max(1),
+            "--crash-after" => parsed.crash_after = flag_value::<DurationArg>(&mut args, &arg)?.0,
+            "--watch-backend" => parsed.watch_backend = flag_value(&mut args, &arg)?,
+            "--watch-dirs" => parsed.watch_dirs = flag_value::<usize>(&mut args, &arg)?.max(1),
+            "--settle" => parsed.settle = flag_value::<DurationArg>(&mut args, &arg)?.0,
+            "--lock-kinds" => parsed.lock_kinds = flag_value::<List<LockKind>>(&mut args, &arg)?.0,
+            "--acquisitions" => parsed.acquisitions = positive(&mut args, &arg)?,
+            "--locks-per-acquisition" => parsed.locks_per_acquisition = flag_value::<usize>(&mut args, &arg)?.max(1),
+            "--lock-timeout" => parsed.lock_timeout = flag_value::<DurationArg>(&mut args, &arg)?.0,
+            "--xattr-size" => parsed.xattr_size = flag_value::<ByteSize>(&mut args, &arg)?.0,
+            "--batches" => parsed.batches = flag_value::<List<u32>>(&mut args, &arg)?.0.into_iter().map(|batch| batch.clamp(1, 4096)).collect(),
+            "--open-threads" => parsed.open_threads = flag_value::<usize>(&mut args, &arg)?.max(1),
+            "--handle-cache" => parsed.handle_cache = Some(flag_value::<usize>(&mut args, &arg)?.max(1)),
+            "--target" => parsed.targets.push(flag_value(&mut args, &arg)?),
+            "--stripe" => parsed.stripe = true,
+            "--ramdisk-size" => parsed.ramdisk_size = Some(flag_value::<ByteSize>(&mut args, &arg)?.0.max(1) as u64),
+            "--preset" => match flag_value(&mut args, &arg)? {
+                Preset::Flash => {
+                    parsed.files = vec![FLASH_FILES];
+                    parsed.sizes = vec![wear::DEFAULT_ERASE_BLOCK];
+                    options.preallocate = true;
+                    parsed.wear.get_or_insert_with(Flash::default);
+                }
+            },
+            "--erase-block" => parsed.wear = Some(Flash { erase_block: flag_value::<ByteSize>(&mut args, &arg)?.0.max(1) }),
+            "--html" => parsed.html = Some(flag_value(&mut args, &arg)?),
+            "--parquet" => parsed.parquet = Some(flag_value(&mut args, &arg)?),
+            "--per-worker" => options.worker_times = Some(Arc::default()),
+            "--anomalies" => parsed.anomalies = true,
+            "--record" => parsed.record = Some(flag_value(&mut args, &arg)?),
+            "--rate" => options.rate = Some(flag_value(&mut args, &arg)?),
+            "--throttle" => {
+                let value: String = flag_value(&mut args, &arg)?;
+                let ByteSize(bytes) = value.strip_suffix("/s").unwrap_or(&value).parse().map_err(|e| invalid_input(format!("invalid value for {}: {}", arg, e)))?;
+                bandwidth = Some(bytes.max(1) as f64);
+            }
+            "--iops-limit" => iops = Some(flag_value::<Rate>(&mut args, &arg)?.0),
+            "--net-latency" => net_latency.get_or_insert_default().mean = flag_value::<DurationArg>(&mut args, &arg)?.0,
+            "--net-jitter" => net_latency.get_or_insert_default().jitter = flag_value::<DurationArg>(&mut args, &arg)?.0,
+            "--backpressure" => relief = Some(flag_value::<Relief>(&mut args, &arg)?),
+            "--dirty-ceiling" => dirty_ceiling = Some(flag_value::<ByteSize>(&mut args, &arg)?.0 as u64),
+            "--queue-depth" => match flag_value::<u32>(&mut args, &arg)? {
+                depth @ 1..=bench::MAX_QUEUE_DEPTH => options.queue_depth = Some(depth),
+                depth => return Err(invalid_input(format!("--queue-depth must be between 1 and {}, not {}", bench::MAX_QUEUE_DEPTH, depth))),
+            },
+            "--schedule" => {
+                parsed.schedules = match flag_value::<String>(&mut args, &arg)?.as_str() {
+                    "both" => vec![Scheduling::Stealing, Scheduling::Static],
+                    value => vec![value.parse().map_err(|e| invalid_input(format!("invalid value for {}: {}", arg, e)))?],
+                };
+                options.scheduling = parsed.schedules[0];
+            }
+            "--processes" => parsed.processes = Some(flag_value::<usize>(&mut args, &arg)?.max(1)),
+            "--numa" => parsed.numa = Some(flag_value(&mut args, &arg)?),
+            "--nice" => parsed.priority.nice = Some(flag_value(&mut args, &arg)?),
+            "--ionice" => parsed.priority.io = Some(flag_value(&mut args, &arg)?),
+            "--chunk" => options.chunk = Some(flag_value::<usize>(&mut args, &arg)?.max(1)),
+            "--prefetch" => options.prefetch = Some(flag_value(&mut args, &arg)?),
+            "--prefetch-window" => options.prefetch_window = Some(flag_value::<usize>(&mut args, &arg)?.max(1)),
+            "--profile" => profile = Some(flag_value(&mut args, &arg)?),
+            "--profile-dir" => profile_dir = flag_value(&mut args, &arg)?,
+            "--bundle" => parsed.bundle = Some(flag_value(&mut args, &arg)?),
+            "--op-timeout" => timeouts.op = Some(flag_value::<DurationArg>(&mut args, &arg)?.0),
+            "--phase-timeout" => timeouts.phase = Some(flag_value::<DurationArg>(&mut args, &arg)?.0),
+            "--on-timeout" => timeouts.on_timeout = flag_value(&mut args, &arg)?,
+            "--on-panic" => options.on_panic = flag_value(&mut args, &arg)?,
+            "--job" => parsed.job = Some(flag_value(&mut args, &arg)?),
+            "--output" => parsed.output = Some(flag_value(&mut args, &arg)?),
+            "--progress" => options.progress = progress_bar(),
+            "--window" => parsed.window = Some(flag_value(&mut args, &arg)?),
+            "--idle" => {
+                parsed.idle.get_or_insert_with(IdleThresholds::default);
+            }
+            "--save-baseline" => parsed.save_baseline = Some(flag_value(&mut args, &arg)?),
+            "--recommend" => parsed.recommend = Some(flag_value(&mut args, &arg)?),
+            "--report" => parsed.report = Some(flag_value(&mut args, &arg)?),
+            "--report-file" => parsed.report_file = Some(flag_value(&mut args, &arg)?),
+            "--reserve-space" => parsed.reserve_space = true,
+            "--no-space-check" => parsed.skip_space_check = true,
+            "--names" => parsed.names = flag_value(&mut args, &arg)?,
+            "--root" => parsed.root = Some(flag_value(&mut args, &arg)?),
+            "--glob" => parsed.selector.include.push(flag_value::<Glob>(&mut args, &arg)?),
+            "--exclude" => parsed.selector.exclude.push(flag_value::<Glob>(&mut args, &arg)?),
+            "--precondition" => utilization = Some(flag_value(&mut args, &arg)?),
+            "--fragmentation" => fragmentation = Some(flag_value(&mut args, &arg)?),
+            "--duration" => parsed.soak_duration = flag_value::<DurationArg>(&mut args, &arg)?.0,
+            "--soak-log" => parsed.soak_log = flag_value(&mut args, &arg)?,
+            "--baseline" => parsed.baseline = Some(flag_value(&mut args, &arg)?),
+            "--threshold" => parsed.threshold = flag_value(&mut args, &arg)?,
+            "--populations-dir" => parsed.populations = Store::new(flag_value::<PathBuf>(&mut args, &arg)?),
+            "--idle-cpu" => parsed.idle.get_or_insert_with(IdleThresholds::default).cpu_percent = flag_value(&mut args, &arg)?,
+            "--preflight" => parsed.preflight = true,
+            "--quiesce" => {
+                parsed.quiesce.get_or_insert(QUIESCE_TIMEOUT);
+            }
+            "--quiesce-timeout" => parsed.quiesce = Some(flag_value::<DurationArg>(&mut args, &arg)?.0),
+            _ => return Err(invalid_input(format!("unknown argument: {}", arg))),
+        }
+    }
+    if pattern.is_some() || seed.is_some() || dedupe.is_some() || block.is_some() {
+        let mut generator = Generator::new(seed.unwrap_or(0), pattern.unwrap_or(Pattern::Random));
+        generator.dedupe = dedupe.unwrap_or(generator.dedupe);
+        generator.block = block.unwrap_or(generator.block);
+        parsed.options.generator = Some(generator);
+    }
+    if queues || match_queues {
+        let device = bench_device().ok_or_else(|| invalid_input(format!("--queues: no block device found for {}", bench::get_dir().display())))?;
+        let hardware = queues::hardware_queues(&device);
+        if hardware.is_empty() {
+            return Err(invalid_input(format!("--queues: {} has no blk-mq hardware queues", device.name)));
+        }
+        if match_queues {
+            parsed.threads = vec![hardware.len()];
+        }
+        if queues {
+            parsed.options.queues = Some(QueueCounter::new(&device).ok_or_else(|| invalid_input(format!("--queues: per-queue counts of {} aren't readable", device.name)))?);
+        }
+    }
+    if bandwidth.is_some() || iops.is_some() {
+        parsed.options.throttle = Some(Arc::new(Throttle::new(bandwidth, iops)));
+    }
+    if let Some(latency) = net_latency {
+        if latency.mean.is_zero() {
+            return Err(invalid_input("--net-jitter needs --net-latency".to_string()));
+        }
+        if latency.jitter > latency.mean {
+            return Err(invalid_input("--net-jitter can't exceed --net-latency".to_string()));
+        }
+        parsed.options.network = Some(Arc::new(SimulatedNetwork::new(latency)));
+    }
+    parsed.options.rpcs = RpcCounter::new(&bench::get_dir());
+    if relief.is_some() || dirty_ceiling.is_some() {
+        let relief = relief.unwrap_or(Relief::Pace);
+        parsed.options.backpressure = Some(Arc::new(Backpressure::new(relief, dirty_ceiling).map_err(|e| io::Error::new(e.kind(), format!("--backpressure {}: {}", relief, e)))?));
+    }
+    if timeouts.op.is_some() || timeouts.phase.is_some() {
+        parsed.options.watchdog = Some(Watchdog::start(timeouts, parsed.options.cancel.clone(), || {
+            // The run's threads are stuck in the kernel and can't return.
+            rundir::remove_live();
+            std::process::exit(crate::EXIT_TIMED_OUT);
+        })?);
+    } else if timeouts.on_timeout == OnTimeout::Abort {
+        return Err(invalid_input("--on-timeout abort needs --op-timeout".to_string()));
+    }
+    match (utilization, fragmentation) {
+        (Some(utilization), fragmentation) => {
+            let mut precondition = Precondition::new(utilization.0 / 100.0);
+            precondition.fragmentation = fragmentation.map_or(precondition.fragmentation, |fragmentation| fragmentation.0 / 100.0);
+            parsed.precondition = Some(precondition);
+        }
+        (None, Some(_)) => return Err(invalid_input("--fragmentation sets how --precondition ages the filesystem; give both".to_string())),
+        (None, None) => {}
+    }
+    if profile == Some(ProfileKind::Flamegraph) {
+        parsed.options.profiler = Some(Arc::new(Profiler::new(&profile_dir)?));
+    }
+    if parsed.stripe && parsed.targets.is_empty() {
+        return Err(invalid_input("--stripe spreads files over --target directories; give at least one".to_string()));
+    }
+    if parsed.stripe && parsed.targets.iter().any(|target| target == Path::new(RAMDISK)) {
+        return Err(invalid_input("--target ramdisk is a bound to compare against and can't be striped".to_string()));
+    }
+    if parsed.options.rate.is_some() {
+        parsed.options.latency.get_or_insert_with(Arc::default);
+    }
+    if parsed.tui {
+        // The dashboard shows progress itself; a bar would draw over it.
+        parsed.options.progress = Arc::default();
+    }
+    if parsed.embedded {
+        if parsed.html.is_some() || parsed.parquet.is_some() || parsed.anomalies || parsed.tui || parsed.options.compare_buffers {
+            return Err(invalid_input("--embedded keeps memory bounded and can't be combined with --html, --parquet, --anomalies, --tui or --compare-buffers".to_string()));
+        }
+        parsed.threads = vec![1];
+        parsed.options.latency = Some(Arc::default());
+    }
+    if parsed.processes.is_some() {
+        processes::check_args(&parsed)?;
+    }
+    Ok(parsed)
+}
+
+/// Sizes the open-file budget from `RLIMIT_NOFILE`, raising the soft limit first if asked,
+/// and warns when the budget is smaller than the number of workers.
+pub fn limit_open_files(args: &mut BenchArgs) -> io::Result<()> {
+    let limit = if args.raise_nofile { fdlimit::raise_nofile_limit()? } else { fdlimit::nofile_limit()? };
+    let budget = fdlimit::open_file_budget(limit);
+    let max_open = args.max_open.map_or(budget, |max| max.min(budget));
+    println!(
+        "Open files: RLIMIT_NOFILE soft {} hard {}, at most {} open at once",
+        limit.soft, limit.hard, max_open
+    );
+    let threads = args.threads.iter().copied().max().unwrap_or(1);
+    if max_open < threads {
+        log::warn("fdlimit", format_args!("only {} files can be open at once but up to {} threads will run; workers will wait for descriptors (try --raise-nofile)", max_open, threads));
+    }
+    args.options.open_files = Arc::new(OpenFileLimiter::new(max_open));
+    Ok(())
+}
+
+const PROGRESS_WIDTH: usize = 40;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A one-line progress bar on stderr, redrawn at most every 100 ms and cleared when a
+/// batch of files completes: the stage, files done, rate and time left, as indicatif would
+/// draw it. Drawn here over [`Progress`]'s callback rather than with indicatif, which the
+/// library leaves to its callers so it adds no dependency. Draws nothing when stderr is not
+/// a terminal.
+fn progress_bar() -> Arc<Progress> {
+    let enabled = io::stderr().is_terminal();
+    // When the bar last drew, and when the batch it is drawing started.
+    let state = Mutex::new((None::<Instant>, None::<Instant>));
+    Arc::new_cyclic(|progress: &Weak<Progress>| {
+        let progress = progress.clone();
+        Progress::on_progress(move |done, total| {
+            if !enabled {
+                return;
+            }
+            let finished = done >= total;
+            let mut state = if finished {
+                state.lock().unwrap_or_else(|e| e.into_inner())
+            } else {
+                match state.try_lock() {
+                    Ok(guard) if guard.0.is_none_or(|t| t.elapsed() >= PROGRESS_INTERVAL) => guard,
+                    _ => return,
+                }
+            };
+            let now = Instant::now();
+            let started = *state.1.get_or_insert(now);
+            *state = (Some(now), if finished { None } else { Some(started) });
+            let mut stderr = io::stderr().lock();
+            let _ = if finished {
+                write!(stderr, "\r\x1b[K")
+            } else {
+                let filled = done * PROGRESS_WIDTH / total.max(1);
+                let rate = done as f64 / started.elapsed().as_secs_f64().max(1e-3);
+                let left = Duration::from_secs_f64((total - done) as f64 / rate.max(1e-3));
+                let stage = progress.upgrade().map(|progress| progress.stage()).filter(|stage| !stage.is_empty());
+                write!(
+                    stderr,
+                    "\r\x1b[K{}[{}{}] {}/{} files ({:.0}%), {}/s, {} left",
+                    stage.map_or_else(String::new, |stage| format!("{} ", stage)),
+                    "#".repeat(filled),
+                    "-".repeat(PROGRESS_WIDTH - filled),
+                    done,
+                    total,
+                    done as f64 * 100.0 / total.max(1) as f64,
+                    human::thousands(rate as u64),
+                    human::duration(left)
+                )
+            };
+            let _ = stderr.flush();
+        })
+    })
+}
+
+fn start_dashboard(args: &BenchArgs) -> io::Result<Option<Dashboard>> {
+    if !args.tui {
+        return Ok(None);
+    }
+    Dashboard::start(args.options.progress.clone())
+}
+
+fn close_dashboard(dashboard: Option<Dashboard>) -> io::Result<()> {
+    dashboard.map_or(Ok(()), Dashboard::close)
+}
+
+/// Holds workers between files whenever the local time is outside `--window` or, with
+/// `--idle`, while the machine is in use.
+pub fn start_scheduler(args: &BenchArgs) -> io::Result<Option<Scheduler>> {
+    if args.window.is_none() && args.idle.is_none() {
+        return Ok(None);
+    }
+    let window = args.window;
+    let mut idle = args.idle.map(IdleDetector::new);
+    let interval = Duration::from_secs(if idle.is_some() { 2 } else { 15 });
+    let hold = move || {
+        if let Some(window) = window.filter(|w| !w.contains(schedule::local_minute_of_day())) {
+            return Some(format!("outside maintenance window {}", window));
+        }
+        idle.as_mut().and_then(IdleDetector::busy)
+    };
+    let scheduler = Scheduler::spawn(args.options.pause.clone(), interval, hold, |reason| match reason {
+        Some(reason) => println!("Pausing: {}", reason),
+        None => println!("Resuming"),
+    })?;
+    Ok(Some(scheduler))
+}
+
+fn report_paused(args: &BenchArgs) {
+    let paused = args.options.pause.paused_total();
+    if !paused.is_zero() {
+        println!("Paused for {} in total (included in phase times)", human::duration(paused));
+    }
+}
+
+fn format_threshold(threshold: Option<u64>) -> String {
+    threshold.map_or_else(|| "never".to_string(), |size| format!(">= {} bytes", size))
+}
+
+fn find_crossover(dir_path: &Path) -> io::Result<Thresholds> {
+    let (timings, thresholds) = crossover::find(dir_path, &crossover::Config::default())?;
+    let mut table = Table::new(["Size", "Files", "read ms", "mmap read ms", "write ms", "mmap update ms"]).align(0, Align::Right);
+    for t in &timings {
+        table.row([human::bytes(t.size as u64), human::thousands(t.files as u64)].into_iter().chain([t.read, t.mmap_read, t.write, t.mmap_update].map(human::millis)));
+    }
+    print!("{}", table.render());
+    println!(
+        "Thresholds: mmap read {}, mmap update {}\n",
+        format_threshold(thresholds.mmap_read),
+        format_threshold(thresholds.mmap_update)
+    );
+    Ok(thresholds)
+}
+
+/// `0-3,6`: CPU numbers with runs collapsed into ranges.
+fn cpu_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == cpu => *end = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    let ranges: Vec<String> = ranges.iter().map(|&(start, end)| if start == end { start.to_string() } else { format!("{}-{}", start, end) }).collect();
+    ranges.join(",")
+}
+
+fn report_platform() {
+    let clock = Clock::measure();
+    println!(
+        "Platform: {}, {}-byte cache lines, allowed CPUs {}, clock resolution {}, {} per reading{}",
+        env::consts::ARCH,
+        platform::cache_line_size(),
+        cpu_list(&platform::allowed_cpus()),
+        human::duration(clock.resolution),
+        human::duration(clock.overhead),
+        if clock.is_slow() { " (slow: per-file times are mostly clock overhead)" } else { "" }
+    );
+    let nodes = pinning::numa_nodes();
+    if nodes.len() > 1 {
+        let nodes: Vec<String> = nodes.iter().map(numa_node).collect();
+        println!("NUMA: {}", nodes.join("; "));
+    }
+}
+
+/// `node0: CPUs 0-7, 31.2 GiB`.
+fn numa_node(node: &NumaNode) -> String {
+    let memory = node.memory.map_or_else(String::new, |memory| format!(", {}", human::bytes(memory)));
+    format!("node{}: CPUs {}{}", node.id, cpu_list(&node.cpus), memory)
+}
+
+pub fn warm_engine(threads: usize, numa: Option<NumaPlacement>, priority: Priority) -> io::Result<Engine> {
+    report_platform();
+    let mut engine = Engine::new(threads).numa(numa).priority(priority);
+    let warm_up = engine.warm_up()?;
+    let placement = match numa {
+        Some(NumaPlacement::Node(id)) => format!(", {} on node {}", warm_up.memory_bound, id),
+        Some(NumaPlacement::Interleave) => format!(", {} interleaved over NUMA nodes", warm_up.memory_bound),
+        None => String::new(),
+    };
+    let priority = if priority.is_set() { format!(", {}", priority) } else { String::new() };
+    println!(
+        "Warm-up: rayon pool {} ({} of {} threads pinned{}{})\n",
+        human::duration(warm_up.rayon),
+        warm_up.pinned_threads,
+        engine.threads(),
+        placement,
+        priority
+    );
+    Ok(engine)
+}
+
+const MAX_FAILURES_SHOWN: usize = 10;
+
+pub fn report_failures(failures: &[Failure]) {
+    if failures.is_empty() {
+        return;
+    }
+    log::error("bench", format_args!("{} files failed", failures.len()));
+    for failure in failures.iter().take(MAX_FAILURES_SHOWN) {
+        let fields = [("phase", failure.phase.to_string()), ("op", failure.op.to_string()), ("path", failure.path.display().to_string())];
+        log::event(Level::Error, "bench", &failure.message, &fields);
+    }
+    if failures.len() > MAX_FAILURES_SHOWN {
+        log::error("bench", format_args!("... and {} more", failures.len() - MAX_FAILURES_SHOWN));
+    }
+}
+
+/// A phase's sampled peak RSS and mapping count as table cells.
+fn memory_cells(memory: Option<Memory>) -> [String; 2] {
+    memory.map_or_else(|| ["-".to_string(), "-".to_string()], |memory| [human::bytes(memory.rss), human::thousands(memory.regions as u64)])
+}
+
+fn print_usage(usage: &PhaseUsage, memory: &PhaseMemory) {
+    let mut table = Table::new(["Phase", "user s", "sys s", "maxRSS KiB", "peak RSS", "maps", "minflt", "majflt", "vcsw", "ivcsw", "blk in", "blk out"]);
+    let phases = [("create", usage.create), ("read", usage.read), ("update", usage.update), ("delete", usage.delete), ("total", usage.total())];
+    let peaks = [memory.create, memory.read, memory.update, memory.delete, memory.peak()];
+    for ((phase, u), peak) in phases.into_iter().zip(peaks) {
+        let [rss, regions] = memory_cells(peak);
+        table.row([
+            phase.to_string(),
+            human::decimal(u.user.as_secs_f64(), 3),
+            human::decimal(u.system.as_secs_f64(), 3),
+            human::thousands(u.max_rss_kib),
+            rss,
+            regions,
+            human::thousands(u.minor_faults),
+            human::thousands(u.major_faults),
+            human::thousands(u.voluntary_switches),
+            human::thousands(u.involuntary_switches),
+            human::thousands(u.blocks_in),
+            human::thousands(u.blocks_out),
+        ]);
+    }
+    print!("{}", table.render());
+    if phases.iter().all(|(_, u)| u.io.is_none()) {
+        return;
+    }
+    let mut table = Table::new(["Phase", "read calls", "write calls", "rchar", "wchar", "read bytes", "write bytes", "cancelled"]);
+    for (phase, u) in phases {
+        let Some(io) = u.io else {
+            continue;
+        };
+        table.row([
+            phase.to_string(),
+            human::thousands(io.read_calls),
+            human::thousands(io.write_calls),
+            human::bytes(io.chars_read),
+            human::bytes(io.chars_written),
+            human::bytes(io.bytes_read),
+            human::bytes(io.bytes_written),
+            human::bytes(io.cancelled_write_bytes),
+        ]);
+    }
+    print!("{}", table.render());
+}
+
+/// How each phase's completions spread over the device's hardware queues.
+fn print_queues(queues: &[(&str, QueueSample)]) {
+    let Some(queue_count) = queues.first().map(|(_, sample)| sample.0.len()) else {
+        return;
+    };
+    println!("Hardware queues ({}):", queue_count);
+    let mut table = Table::new(["Phase", "Completions", "Active", "Busiest", "Imbalance"]);
+    for (phase, sample) in queues {
+        let busiest = sample.0.iter().enumerate().max_by_key(|&(_, count)| *count).filter(|&(_, count)| *count > 0);
+        table.row([
+            phase.to_string(),
+            human::thousands(sample.total()),
+            format!("{}/{}", sample.active(), queue_count),
+            busiest.map_or_else(|| "-".to_string(), |(index, count)| format!("q{} ({}%)", index, human::decimal(*count as f64 * 100.0 / sample.total() as f64, 1))),
+            format!("{}x", human::decimal(sample.imbalance(), 2)),
+        ]);
+    }
+    print!("{}", table.render());
+}
+
+/// Each phase's NFS RPCs, per file and by the operations sending the most.
+fn print_rpcs(rpcs: &[(&str, RpcSample)], files: usize) {
+    if rpcs.is_empty() {
+        return;
+    }
+    println!("NFS RPCs:");
+    let mut table = Table::new(["Phase", "RPCs", "Per file", "Mean RTT", "Most"]);
+    for (phase, sample) in rpcs {
+        let most: Vec<String> = sample.top(3).iter().map(|rpc| format!("{} {}", rpc.op, human::thousands(rpc.count))).collect();
+        table.row([
+            phase.to_string(),
+            human::thousands(sample.total()),
+            human::decimal(sample.total() as f64 / files.max(1) as f64, 2),
+            sample.mean_rtt().map_or_else(|| "-".to_string(), human::duration),
+            if most.is_empty() { "-".to_string() } else { most.join(", ") },
+        ]);
+    }
+    print!("{}", table.render());
+}
+
+/// Each worker's files and busy time per phase, with the CPU and NUMA node it ran on, and
+/// per phase how much longer the busiest worker was busy than the average one.
+fn print_workers(shares: &[WorkerShare]) {
+    if shares.is_empty() {
+        return;
+    }
+    let mut scopes: Vec<&str> = Vec::new();
+    let mut workers: Vec<(usize, Option<usize>)> = Vec::new();
+    for share in shares {
+        if !scopes.contains(&&*share.scope) {
+            scopes.push(&share.scope);
+        }
+        match workers.iter_mut().find(|(worker, _)| *worker == share.worker) {
+            Some((_, cpu)) => *cpu = share.cpu.or(*cpu),
+            None => workers.push((share.worker, share.cpu)),
+        }
+    }
+    let nodes = pinning::numa_nodes();
+    let phase = |scope: &str| scope.rsplit_once('/').map_or(scope.to_string(), |(_, phase)| phase.to_string());
+    let titles = ["Worker".to_string(), "CPU".to_string(), "Node".to_string()].into_iter().chain(scopes.iter().flat_map(|scope| [format!("{} files", phase(scope)), format!("{} ms", phase(scope))]));
+    let mut table = Table::new(titles);
+    let share = |worker: usize, scope: &str| shares.iter().find(|share| share.worker == worker && &*share.scope == scope);
+    for &(worker, cpu) in &workers {
+        let node = cpu.and_then(|cpu| nodes.iter().find(|node| node.cpus.contains(&cpu))).map_or_else(|| "-".to_string(), |node| node.id.to_string());
+        let cells = scopes.iter().flat_map(|scope| match share(worker, scope) {
+            Some(share) => [human::thousands(share.files), human::decimal(share.busy.as_secs_f64() * 1000.0, 2)],
+            None => ["0".to_string(), "-".to_string()],
+        });
+        table.row([worker.to_string(), cpu.map_or_else(|| "-".to_string(), |cpu| cpu.to_string()), node].into_iter().chain(cells));
+    }
+    // The busiest worker's time over the mean of all workers', idle ones included.
+    let imbalance = scopes.iter().flat_map(|scope| {
+        let busy: Vec<f64> = workers.iter().map(|&(worker, _)| share(worker, scope).map_or(0.0, |share| share.busy.as_secs_f64())).collect();
+        let mean = busy.iter().sum::<f64>() / busy.len() as f64;
+        let max = busy.iter().copied().fold(0.0, f64::max);
+        [String::new(), if mean > 0.0 { format!("{}x", human::decimal(max / mean, 2)) } else { "-".to_string() }]
+    });
+    table.row(["Imbalance".to_string(), String::new(), String::new()].into_iter().chain(imbalance));
+    println!("Workers:");
+    print!("{}", table.render());
+}
+
+/// The per-file latency of each phase, from the bounded sketches of `--embedded` or `--rate`.
+/// At a fixed rate latency counts from each file's intended start, and the files that
+/// started behind schedule are shown.
+fn print_latency(latency: &[(&str, Latency)], rate: Option<Rate>) {
+    if latency.is_empty() {
+        return;
+    }
+    match rate {
+        Some(Rate(rate)) => println!("Per-file latency from intended starts at {} files/s:", human::thousands(rate as u64)),
+        None => println!("Per-file latency:"),
+    }
+    let late = rate.is_some().then_some("Late");
+    let mut table = Table::new(["Phase", "Files", "Mean", "p50", "p90", "p99", "Max"].into_iter().chain(late));
+    let time = |seconds: f64| human::duration(Duration::from_secs_f64(seconds.max(0.0)));
+    for (phase, latency) in latency {
+        let (running, digest) = (&latency.running, &latency.digest);
+        table.row([
+            phase.to_string(),
+            human::thousands(running.count()),
+            time(running.mean()),
+            time(digest.quantile(0.5)),
+            time(digest.quantile(0.9)),
+            time(digest.quantile(0.99)),
+            time(running.max()),
+        ].into_iter().chain(rate.map(|_| human::thousands(latency.late))));
+    }
+    print!("{}", table.render());
+}
+
+/// Phase times as table cells in milliseconds: create, read, update, delete, total.
+pub fn phase_cells(times: &PhaseTimes) -> [String; 5] {
+    [times.create, times.read, times.update, times.delete, times.total()].map(human::millis)
+}
+
+/// [`phase_cells`] of a run cancelled during `interrupted`, with `-` for that phase, the
+/// ones after it and the total.
+fn partial_phase_cells(times: &PhaseTimes, interrupted: &str) -> [String; 5] {
+    let finished = baseline::PHASES.iter().position(|&phase| phase == interrupted).unwrap_or(0);
+    let mut cells = phase_cells(times);
+    for cell in &mut cells[finished..] {
+        *cell = "-".to_string();
+    }
+    cells
+}
+
+/// `create 1.20 ms, read ..., total ...` for one line of output.
+pub fn phase_summary(times: &PhaseTimes) -> String {
+    format!(
+        "create {}, read {}, update {}, delete {}, total {}",
+        human::duration(times.create),
+        human::duration(times.read),
+        human::duration(times.update),
+        human::duration(times.delete),
+        human::duration(times.total())
+    )
+}
+
+pub const PHASE_COLUMNS: [&str; 5] = ["Create ms", "Read ms", "Update ms", "Delete ms", "Total ms"];
+
+fn ms(times: &PhaseTimes) -> [u128; 5] {
+    [
+        times.create.as_millis(),
+        times.read.as_millis(),
+        times.update.as_millis(),
+        times.delete.as_millis(),
+        times.create.as_millis() + times.read.as_millis() + times.update.as_millis() + times.delete.as_millis(),
+    ]
+}
+
+/// A SMART or sensor reading: whole numbers with separators, temperatures to a tenth.
+fn reading(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 { human::thousands(value.abs() as u64) } else { human::decimal(value.abs(), 1) }
+}
+
+/// Prints how the device's readings moved between two snapshots, and whether it spent
+/// the run throttling.
+fn report_health(device: &Device, before: &Snapshot, after: &Snapshot) {
+    if before.is_empty() && after.is_empty() {
+        println!("\nNo temperature sensors or SMART attributes readable for {} (smartctl usually needs root)", device.node().display());
+        return;
+    }
+    println!("\nDevice health of {}:", device.node().display());
+    let mut table = Table::new(["Reading", "Before", "After", "Change"]);
+    for change in before.changes(after) {
+        let signed = |value: f64| format!("{}{}", if value < 0.0 { "-" } else { "" }, reading(value));
+        table.row([
+            change.name.clone(),
+            change.before.map_or_else(|| "-".to_string(), signed),
+            change.after.map_or_else(|| "-".to_string(), signed),
+            change.delta().map_or_else(|| "-".to_string(), |delta| format!("{}{}", if delta < 0.0 { "-" } else { "+" }, reading(delta))),
+        ]);
+    }
+    print!("{}", table.render());
+    if let (Some(before), Some(after)) = (before.throttled_minutes(), after.throttled_minutes())
+        && after > before
+    {
+        println!("Time above the controller's warning temperature grew by {} min; it likely throttled during the run", reading(after - before));
+    }
+}
+
+/// The options in `args` that change what the strategies do to each file, for
+/// [`Metadata::config`]; threads, files and size are recorded beside it.
+pub fn workload_config(args: &BenchArgs) -> Vec<(String, String)> {
+    let options = &args.options;
+    let or_off = |value: Option<String>| value.unwrap_or_else(|| "off".to_string());
+    let hint = |hint: Option<Hint>| or_off(hint.map(|hint| format!("{:?}", hint).to_lowercase()));
+    [
+        ("names", args.names.to_string()),
+        ("contents", or_off(options.generator.as_ref().map(Generator::to_string))),
+        ("sync", options.sync.name().to_string()),
+        ("mmap_flush", options.mmap_flush.name().to_string()),
+        ("preallocate", options.preallocate.to_string()),
+        ("cold", options.cold_read.to_string()),
+        ("madvise", hint(options.madvise)),
+        ("fadvise", hint(options.fadvise)),
+        ("huge_pages", options.huge_pages.to_string()),
+        ("fresh_buffers", options.fresh_buffers.to_string()),
+        ("verify", options.verify.to_string()),
+        ("open_at", or_off(options.open_at.map(|resolve| format!("{:?}", resolve).to_lowercase()))),
+        ("schedule", options.scheduling.name().to_string()),
+        ("chunk", or_off(options.chunk.map(|chunk| chunk.to_string()))),
+        ("queue_depth", options.queue_depth().to_string()),
+        ("prefetch", or_off(options.prefetch.map(|prefetch| prefetch.name().to_string()))),
+        ("rate", or_off(options.rate.map(|rate| rate.0.to_string()))),
+        ("net_latency", or_off(options.network.as_ref().map(|network| format!("{:?}±{:?}", network.latency().mean, network.latency().jitter)))),
+        ("backpressure", or_off(options.backpressure.as_ref().map(|backpressure| format!("{} {}", backpressure.relief(), backpressure.ceiling())))),
+        ("crossover", args.crossover.to_string()),
+        ("probe", args.probe.to_string()),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value))
+    .collect()
+}
+
+/// Runs every strategy once on the single workload in `args`, printing its phase times.
+fn run_strategies(args: &mut BenchArgs) -> io::Result<Baseline> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("use `io bench sweep` to run several thread counts, file counts or sizes".to_string()));
+    };
+    let metadata = Some(Metadata::current(workload_config(args)));
+    let mut results = Baseline { threads, files, size, filesystem: None, targets: Vec::new(), cgroup: None, results: Vec::new(), metadata, anomalies: Vec::new(), conditions: None };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    if args.parquet.is_some() || args.anomalies {
+        args.options.file_times = Some(Arc::default());
+    }
+    if args.record.is_some() || args.bundle.is_some() {
+        args.options.golden.get_or_insert_with(Arc::default);
+    }
+    limit_open_files(args)?;
+    let scheduler = start_scheduler(args)?;
+    let dashboard = start_dashboard(args)?;
+    let (targets, ramdisk) = target_dirs(&args.targets, args.ramdisk_size.unwrap_or_else(|| ramdisk_room(files, size)))?;
+    let dirs: Vec<PathBuf> = targets.iter().map(|(_, dir)| dir.to_path_buf()).collect();
+    let dir_path = dirs[0].clone();
+    let aged: Vec<&Path> = targets.iter().filter(|(label, _)| label != RAMDISK).map(|(_, dir)| dir.as_ref()).collect();
+    let fillers = precondition(args, &aged)?;
+    let reservations = claim_space(args, &targets.iter().map(|(_, dir)| dir.as_ref()).collect::<Vec<_>>(), space::projected(files, size))?;
+    results.filesystem = Filesystem::of(&dir_path);
+    if targets.len() > 1 {
+        results.targets = targets.iter().map(|(target, dir)| (target.clone(), Filesystem::of(dir))).collect();
+    }
+    // Found out up front, so a sandbox turns options off rather than failing mid-run.
+    let environment = Environment::detect(&dir_path);
+    let constraints: Vec<String> = environment.notes().into_iter().chain(environment.degrade(&mut args.options)).collect();
+    results.cgroup = environment.cgroup.clone();
+    for constraint in &constraints {
+        println!("Environment: {}", constraint);
+    }
+
+    if let Some(generator) = &args.options.generator {
+        println!("Writing {}", generator);
+    }
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    if args.crossover {
+        println!("Finding read/mmap crossover...");
+        args.options.thresholds = engine.install(|| find_crossover(&dir_path))?;
+    }
+    if args.probe {
+        let probe = Probe::run(&dir_path)?;
+        print!("{}", probe_table(&probe).render());
+        args.options.thresholds = probe.adjust(args.options.thresholds);
+        println!();
+    }
+
+    let health = args.health.then(|| Device::of(&dir_path)).map(|device| {
+        if device.is_none() {
+            println!("No block device found for {}; skipping device health", dir_path.display());
+        }
+        device.map(|device| {
+            let before = device.snapshot();
+            (device, before)
+        })
+    });
+
+    let before = quiet_down(args, &dir_path);
+    let mut summary = Table::new(["Strategy"].into_iter().chain(PHASE_COLUMNS).chain(["Peak RSS", "Maps"]));
+    let mut wear_table = Table::new(["Strategy", "Written", "Amplification", "Device writes"]);
+    let mut logical_writes = false;
+    let mut file_times = Vec::new();
+    let mut totals = Vec::new();
+    let mut failures = Vec::new();
+    drop(reservations);
+    for (i, strategy) in STRATEGIES.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("Running {}...", strategy.name);
+        let mut runs: Vec<(String, RunResult)> = Vec::new();
+        for scheduling in args.schedules.clone() {
+            args.options.scheduling = scheduling;
+            let label = if args.schedules.len() > 1 { format!("{} ({})", strategy.label, scheduling.name()) } else { strategy.label.to_string() };
+            let first = runs.len();
+            if args.stripe || dirs.len() == 1 {
+                let paths = names::paths(&dirs, files, args.names);
+                runs.push((label.clone(), engine.install(|| bench::run_strategy_on(strategy, &paths, &args.options))?));
+            } else if ramdisk.is_some() {
+                // One target after another, so the ramdisk's bound doesn't share workers with a disk.
+                for (target, dir) in &targets {
+                    runs.push((format!("{} on {}", label, target), engine.install(|| bench::run_strategy(strategy, dir, &args.options))?));
+                }
+            } else {
+                let results = bench::run_strategy_across(strategy, &dirs, &args.options, engine.pool()?)?;
+                runs.extend(targets.iter().zip(results).map(|((target, _), result)| (format!("{} on {}", label, target), result)));
+            }
+            totals.push((label, runs[first..].iter().map(|(_, result)| result.times.total()).collect()));
+        }
+        if let Some(times) = &args.options.file_times {
+            file_times.extend(times.take());
+        }
+        let workers = args.options.worker_times.as_ref().map_or_else(Vec::new, |workers| workers.take());
+        for (label, result) in runs {
+            let times = &result.times;
+            if let Some(phase) = result.interrupted {
+                println!("{} interrupted during {}", label, phase);
+                report_failures(&result.failures);
+                failures.extend(result.failures);
+                summary.row([label].into_iter().chain(partial_phase_cells(times, phase)).chain(memory_cells(result.memory.peak())));
+                continue;
+            }
+            println!("{} times: {}", label, phase_summary(times));
+            if !times.writeback.is_zero() && (args.options.sync != SyncMode::None || args.options.mmap_flush != MmapFlush::None) {
+                println!("Update writeback: {} waiting on msync and fsync, summed over workers", human::duration(times.writeback));
+            }
+            if let Some(buffers) = times.buffer_comparison {
+                let (pooled, fresh) = (buffers.pooled.as_secs_f64(), buffers.fresh.as_secs_f64());
+                println!(
+                    "Read buffers: reused {}, fresh per file {} ({}% from reuse)",
+                    human::duration(buffers.pooled),
+                    human::duration(buffers.fresh),
+                    human::decimal(if fresh > 0.0 { (pooled / fresh - 1.0) * 100.0 } else { 0.0 }, 1)
+                );
+            }
+            print_usage(&result.usage, &result.memory);
+            print_latency(&result.latency, args.options.rate);
+            print_queues(&result.queues);
+            print_rpcs(&result.rpcs, files);
+            report_failures(&result.failures);
+            failures.extend(result.failures.iter().cloned());
+            summary.row([label.clone()].into_iter().chain(phase_cells(times)).chain(memory_cells(result.memory.peak())));
+            results.results.push((label.clone(), baseline::phase_ms(times)));
+            if let Some(flash) = args.wear {
+                // tmpfs and some containers don't account block writes; the create and update
+                // phases each write every file once.
+                let mut written = result.usage.total().blocks_out * 512;
+                if written == 0 {
+                    written = 2 * (files * size) as u64;
+                    logical_writes = true;
+                }
+                let wear = flash.estimate(written, size as u64);
+                wear_table.row([
+                    label,
+                    human::bytes(wear.written),
+                    format!("{}x", human::decimal(wear.amplification, 2)),
+                    human::bytes(wear.programmed() as u64),
+                ]);
+            }
+        }
+        print_workers(&workers);
+        if args.options.cancel.is_cancelled() {
+            break;
+        }
+    }
+    println!();
+    if args.options.cancel.is_cancelled() {
+        println!("Cancelled; results of what finished:");
+    }
+    print!("{}", summary.render());
+    if !constraints.is_empty() {
+        println!("Environment: {}", constraints.join("; "));
+    }
+    results.anomalies = file_anomalies(&file_times);
+    for anomaly in &results.anomalies {
+        println!("Anomaly: {}", anomaly);
+    }
+    print_storage_share(&targets, &totals);
+    if let Some(throttle) = &args.options.throttle {
+        report_throttle(throttle);
+    }
+    if let Some(backpressure) = &args.options.backpressure {
+        report_backpressure(backpressure);
+    }
+    if let Some(network) = &args.options.network {
+        report_network(network);
+    }
+    if let Some(profiler) = &args.options.profiler {
+        println!("Wrote {} flamegraphs to {}", profiler.written().len(), profiler.dir().display());
+    }
+    if let Some(flash) = args.wear {
+        println!("\nEstimated flash wear ({} erase blocks{}):", ByteSize(flash.erase_block), if logical_writes { ", bytes the phases wrote" } else { "" });
+        print!("{}", wear_table.render());
+    }
+    if let Some(Some((device, before))) = &health {
+        report_health(device, before, &device.snapshot());
+    }
+    let after = Readings::sample(&dir_path);
+    if args.preflight || args.quiesce.is_some() {
+        let already = before.concerns(&Quiet::default());
+        for concern in after.concerns(&Quiet::default()).into_iter().filter(|concern| !already.contains(concern)) {
+            log::warn("cli", format!("after the run: {}", concern));
+        }
+    }
+    results.conditions = Some(Conditions { before, after });
+
+    if let Some(path) = &args.parquet {
+        write_parquet(path, &file_times, |_| size as u64)?;
+    }
+    if let (Some(path), Some(golden)) = (&args.record, &args.options.golden) {
+        let recording = golden.recording(&args.options);
+        recording.write(path)?;
+        println!("Recorded {} operations to {}", human::thousands(recording.operations() as u64), path.display());
+    }
+    if let Some(path) = &args.bundle {
+        write_bundle(path, args, &results, &summary, &failures, &environment)?;
+    }
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    report_paused(args);
+    drop(fillers);
+    drop(targets);
+    drop(ramdisk);
+    if args.options.cancel.is_cancelled() {
+        // Partial results are no baseline and no comparison.
+        return Err(cancel::cancelled());
+    }
+    Ok(results)
+}
+
+/// What stands out in each strategy's phases among `times`, in the order they ran.
+fn file_anomalies(times: &[FileTime]) -> Vec<Anomaly> {
+    let mut scopes: Vec<(&str, Vec<f64>)> = Vec::new();
+    for time in times {
+        match scopes.iter_mut().find(|(scope, _)| *scope == &*time.scope) {
+            Some((_, samples)) => samples.push(time.elapsed.as_secs_f64()),
+            None => scopes.push((&time.scope, vec![time.elapsed.as_secs_f64()])),
+        }
+    }
+    scopes
+        .into_iter()
+        .flat_map(|(scope, samples)| {
+            let (strategy, phase) = scope.rsplit_once('/').unwrap_or(("", scope));
+            anomaly::examine(&samples, &Criteria::FILES).into_iter().map(|finding| Anomaly { strategy: strategy.to_string(), phase: phase.to_string(), finding })
+        })
+        .collect()
+}
+
+/// How long `--quiesce` waits at most, unless `--quiesce-timeout` says otherwise.
+const QUIESCE_TIMEOUT: Duration = Duration::from_secs(600);
+/// How long `--quiesce` waits between readings.
+const QUIESCE_POLL: Duration = Duration::from_secs(5);
+
+/// The machine's state before the strategies run, after waiting for it to quiet down with
+/// `--quiesce`, warning about what could make the run noisy with `--preflight` or
+/// `--quiesce`.
+fn quiet_down(args: &BenchArgs, dir: &Path) -> Readings {
+    let quiet = Quiet::default();
+    let readings = match args.quiesce {
+        Some(timeout) => {
+            let (readings, waited) = noise::quiesce(dir, &quiet, timeout, QUIESCE_POLL);
+            if !waited.is_zero() {
+                println!("Waited {} for the machine to quiet down", human::duration(waited));
+            }
+            readings
+        }
+        None => Readings::sample(dir),
+    };
+    if args.preflight || args.quiesce.is_some() {
+        for concern in readings.concerns(&quiet) {
+            log::warn("cli", format!("noisy run: {}", concern));
+        }
+    }
+    readings
+}
+
+fn report_throttle(throttle: &Throttle) {
+    let limits: Vec<String> = [
+        throttle.bytes_per_second().map(|rate| format!("{}/s", human::bytes(rate as u64))),
+        throttle.ops_per_second().map(|rate| format!("{} files/s", human::thousands(rate as u64))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    println!("Throttled to {}; workers waited {} in all", limits.join(" and "), human::duration(throttle.waited()));
+}
+
+fn report_network(network: &SimulatedNetwork) {
+    let latency = network.latency();
+    let jitter = if latency.jitter.is_zero() { String::new() } else { format!(" ± {}", human::duration(latency.jitter)) };
+    println!(
+        "Simulated network: {}{} per metadata operation; {} round trips, workers waited {} in all",
+        human::duration(latency.mean),
+        jitter,
+        human::thousands(network.round_trips()),
+        human::duration(network.waited())
+    );
+}
+
+fn report_backpressure(backpressure: &Backpressure) {
+    let relieved = match backpressure.relief() {
+        Relief::Watch => String::new(),
+        Relief::Pace => format!("; workers waited {} in all", human::duration(backpressure.waited())),
+        Relief::Flush => format!("; started writeback of {} files early", human::thousands(backpressure.flushed() as u64)),
+    };
+    println!("Dirty pages: peaked at {} against a ceiling of {}{}", human::bytes(backpressure.peak()), human::bytes(backpressure.ceiling()), relieved);
+    let throttled = backpressure.throttled();
+    if let (Some(thresholds), false) = (backpressure.thresholds(), throttled.is_empty()) {
+        log::warn("cli", format!("dirty pages passed the kernel's limit of {} during {}; writers were likely throttled", human::bytes(thresholds.free_run()), throttled.join(", ")));
+    }
+}
+
+/// Writes everything about a run to one `.tar.zst` for bug reports: how `io` was invoked,
+/// the results, the machine and filesystem, what this build and kernel support, every
+/// file's decision and outcome, and the failures.
+fn write_bundle(path: &Path, args: &BenchArgs, results: &Baseline, summary: &Table, failures: &[Failure], found: &Environment) -> io::Result<()> {
+    let mut bundle = Bundle::new(format!("io-bundle-{}", rundir::run_id()));
+    bundle.add("command.txt", format!("{}\n", env::args().collect::<Vec<_>>().join(" ")));
+    bundle.add("results.json", format!("{:#}\n", results.to_json()));
+    bundle.add("summary.txt", summary.render());
+    bundle.add("environment.json", format!("{:#}\n", environment(results)));
+    bundle.add("capabilities.json", format!("{:#}\n", capabilities(found)));
+    if let Some(golden) = &args.options.golden {
+        bundle.add("journal.txt", golden.recording(&args.options).to_text());
+    }
+    let log: String = failures.iter().map(|failure| format!("[{} op {}] {}: {}\n", failure.phase, failure.op, failure.path.display(), failure.message)).collect();
+    bundle.add("failures.txt", log);
+    if let Some(parquet) = &args.parquet {
+        bundle.add_file("files.parquet", parquet)?;
+    }
+    bundle.write(path)?;
+    println!("Bundled {} into {}", bundle.names().collect::<Vec<_>>().join(", "), path.display());
+    Ok(())
+}
+
+/// The machine, OS and filesystem a run saw.
+fn environment(results: &Baseline) -> Value {
+    let clock = Clock::measure();
+    let limit = fdlimit::nofile_limit().ok();
+    Value::object()
+        .with("metadata", results.metadata.as_ref().map(Metadata::to_json))
+        .with("allowed_cpus", cpu_list(&platform::allowed_cpus()))
+        .with("numa_nodes", pinning::numa_nodes().iter().map(|node| Value::object().with("id", node.id).with("cpus", cpu_list(&node.cpus)).with("memory_bytes", node.memory)).collect::<Vec<_>>())
+        .with("cache_line_bytes", platform::cache_line_size())
+        .with("clock_resolution_ns", clock.resolution.as_nanos() as u64)
+        .with("clock_overhead_ns", clock.overhead.as_nanos() as u64)
+        .with("nofile_soft", limit.map(|limit| limit.soft))
+        .with("nofile_hard", limit.map(|limit| limit.hard))
+        .with("filesystem", results.filesystem.as_ref().map(Filesystem::to_json))
+        .with("targets", results.targets_json())
+        .with("cgroup", results.cgroup.as_ref().map(Limits::to_json))
+}
+
+/// The features this build has and the kernel interfaces that work here.
+fn capabilities(found: &Environment) -> Value {
+    Value::object()
+        .with("bench", cfg!(feature = "bench"))
+        .with("rayon", cfg!(feature = "rayon"))
+        .with("mmap", cfg!(feature = "mmap"))
+        .with("libc", cfg!(feature = "libc"))
+        .with("tokio", cfg!(feature = "tokio"))
+        .with("sqlite", cfg!(feature = "sqlite"))
+        .with("lmdb", cfg!(feature = "lmdb"))
+        .with("s3", cfg!(feature = "s3"))
+        .with("zstd", cfg!(feature = "zstd"))
+        .with("io_uring", found.has(Capability::IoUring))
+        .with("open_at", cfg!(all(unix, feature = "libc")))
+        .with("shared_tmpfs", Filesystem::of(Path::new("/dev/shm")).is_some_and(|filesystem| filesystem.fs_type == "tmpfs"))
+        .with("container", found.container.clone())
+        .with("seccomp", found.seccomp)
+        .with("unavailable", found.unavailable.iter().fold(Value::object(), |unavailable, (capability, why)| unavailable.with(capability.name(), why.as_str())))
+}
+
+/// Writes one row per timed file operation to `path`.
+fn write_parquet(path: &Path, times: &[FileTime], size: impl Fn(&FileTime) -> u64) -> io::Result<()> {
+    parquet::write(path, &bench::file_time_columns(times, size))?;
+    println!("Wrote {} file records to {}", human::thousands(times.len() as u64), path.display());
+    Ok(())
+}
+
+/// `io bench metadata`: creates the workload once per metadata strategy and times its stat,
+/// chmod, utimes and touch phases.
+fn metadata(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench metadata runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+
+    let mut summary = Table::new(["Strategy", "Stat ms", "Chmod ms", "Utimes ms", "Touch ms"]);
+    for (i, strategy) in META_STRATEGIES.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("Running {}...", strategy.name);
+        let result = match engine.install(|| metadata::run_strategy(strategy, &dir_path, &args.options)) {
+            Ok(result) => result,
+            // io_uring may be missing or blocked; the other strategies still compare.
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                println!("{} skipped: {}", strategy.label, e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let times: Vec<String> = MetaPhase::ALL
+            .iter()
+            .filter_map(|&phase| result.times.get(phase).map(|time| format!("{} {}", phase.name(), human::duration(time))))
+            .collect();
+        println!("{} times: {}", strategy.label, times.join(", "));
+        report_failures(&result.failures);
+        summary.row([strategy.label.to_string()].into_iter().chain(MetaPhase::ALL.map(|phase| result.times.get(phase).map_or_else(|| "-".to_string(), human::millis))));
+    }
+    println!();
+    print!("{}", summary.render());
+
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    report_paused(&args);
+    fs::remove_dir_all(&dir_path)?;
+    Ok(())
+}
+
+/// `io bench links`: times materializing the workload from a store by copying, hard
+/// linking and symlinking.
+fn links(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench links runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| links::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let mut table = Table::new(["Method", "Time", "Per file", "Files/s"]);
+    for (method, elapsed) in &result.times {
+        table.row([
+            method.name().to_string(),
+            human::duration(*elapsed),
+            human::duration(*elapsed / files.max(1) as u32),
+            human::rate(files as f64, *elapsed),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench cas`: times ingesting the workload into a content-addressed store, most of it
+/// duplicates, and checking it out by each method.
+fn cas(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench cas runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| cas::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    println!(
+        "Stored {} distinct files ({}) for {} ({} copies of each)",
+        human::thousands(result.stored as u64),
+        human::bytes(result.stored_bytes),
+        human::thousands(files as u64),
+        cas::COPIES
+    );
+    let mut table = Table::new(["Phase", "Time", "Per file", "Files/s"]);
+    let phases = [("ingest", Some(result.ingest))].into_iter().chain(result.checkouts.iter().map(|(method, elapsed)| (method.name(), *elapsed)));
+    for (phase, elapsed) in phases {
+        match elapsed {
+            Some(elapsed) => table.row([phase.to_string(), human::duration(elapsed), human::duration(elapsed / files.max(1) as u32), human::rate(files as f64, elapsed)]),
+            None => table.row([phase.to_string(), "unsupported".to_string(), "-".to_string(), "-".to_string()]),
+        }
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench archive`: times packing the workload into each archive format and unpacking
+/// it again, against copying the loose files.
+fn archive(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench archive runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| archive::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let mut table = Table::new(["Method", "Pack", "Unpack", "Files/s", "Size", "Ratio"]);
+    table.row([
+        "loose copy".to_string(),
+        "-".to_string(),
+        human::duration(result.copy),
+        human::rate(files as f64, result.copy),
+        human::bytes(result.source_bytes),
+        "-".to_string(),
+    ]);
+    for format in &result.formats {
+        table.row([
+            format.format.name().to_string(),
+            human::duration(format.pack),
+            human::duration(format.unpack),
+            human::rate(files as f64, format.unpack),
+            human::bytes(format.bytes),
+            format!("{:.2}x", result.source_bytes as f64 / format.bytes.max(1) as f64),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench compress`: times creating and reading the workload with each codec on the
+/// write path.
+fn compress(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench compress runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| codec::run(&dir_path, &args.codecs, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let per_second = |elapsed: Duration| format!("{}/s", human::bytes(if elapsed.is_zero() { 0 } else { (result.source_bytes as f64 / elapsed.as_secs_f64()) as u64 }));
+    let mut table = Table::new(["Codec", "Create", "Read", "Create rate", "Read rate", "On disk", "Ratio"]);
+    for codec in &result.codecs {
+        table.row([
+            codec.codec.name().to_string(),
+            human::duration(codec.create),
+            human::duration(codec.read),
+            per_second(codec.create),
+            per_second(codec.read),
+            human::bytes(codec.bytes),
+            format!("{:.2}x", result.source_bytes as f64 / codec.bytes.max(1) as f64),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench update`: times overwriting the workload with each update method, which the
+/// strategies otherwise each pick one of.
+fn update(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench update runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| update::run(&dir_path, &args.update_methods, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let per_second = |elapsed: Duration| format!("{}/s", human::bytes(if elapsed.is_zero() { 0 } else { (result.updated_bytes as f64 / elapsed.as_secs_f64()) as u64 }));
+    let mut table = Table::new(["Method", "Update", "Rate", "Writeback", "A crash before sync leaves"]);
+    for method in &result.methods {
+        table.row([
+            method.method.name().to_string(),
+            human::duration(method.update),
+            per_second(method.update),
+            human::duration(method.writeback),
+            method.method.crash_leaves().to_string(),
+        ]);
+    }
+    print!("{}", table.render());
+    println!("Sync: {}, mmap flush: {}; writeback summed over workers", args.options.sync.name(), args.options.mmap_flush.name());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench watch`: times the update phase with and without every directory of the tree
+/// watched, and how late and how reliably the watcher hears about each file.
+fn watch(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench watch runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    let config = WatchConfig { backend: args.watch_backend, dirs: args.watch_dirs, settle: args.settle };
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| watch::run(&dir_path, config, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let mut table = Table::new(["Backend", "Watches", "Setup", "Update unwatched", "Update watched", "Events", "Delivered", "Dropped", "Overflows"]);
+    table.row([
+        result.backend.name().to_string(),
+        human::thousands(result.watches as u64),
+        human::duration(result.setup),
+        human::duration(result.unwatched),
+        human::duration(result.watched),
+        human::thousands(result.events),
+        format!("{} of {}", human::thousands(result.delivered as u64), human::thousands(result.files as u64)),
+        format!("{}%", human::decimal(result.drop_rate() * 100.0, 2)),
+        human::thousands(result.overflows),
+    ]);
+    print!("{}", table.render());
+    print_latency(&[("delivery", result.latency)], None);
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench lock`: threads contending for exclusive locks on a few lock files, a couple at
+/// a time, with each lock kind.
+fn lock(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files]) = (&args.threads[..], &args.files[..]) else {
+        return Err(invalid_input("io bench lock runs a single thread count and file count".to_string()));
+    };
+    let config = LockConfig { lock_files: files, per_acquisition: args.locks_per_acquisition, acquisitions: args.acquisitions, timeout: args.lock_timeout };
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| lock::run(&dir_path, config, &args.lock_kinds, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let mut table = Table::new(["Kind", "Time", "Acquired/s", "Acquired", "Timed out", "Lost updates"]);
+    for kind in &result.kinds {
+        let seconds = kind.elapsed.as_secs_f64();
+        table.row([
+            kind.kind.name().to_string(),
+            human::duration(kind.elapsed),
+            human::thousands(if seconds == 0.0 { 0 } else { (kind.acquired as f64 / seconds) as u64 }),
+            human::thousands(kind.acquired),
+            human::thousands(kind.timed_out),
+            human::thousands(kind.lost_updates),
+        ]);
+    }
+    print!("{}", table.render());
+    println!(
+        "{} threads taking {} of {} lock files at a time, giving up after {}",
+        result.threads,
+        config.per_acquisition.min(files),
+        human::thousands(files as u64),
+        human::duration(config.timeout),
+    );
+    let mut table = Table::new(["Kind", "Sets", "Mean wait", "p50", "p90", "p99", "Max"]);
+    let time = |seconds: f64| human::duration(Duration::from_secs_f64(seconds.max(0.0)));
+    for kind in &result.kinds {
+        let (running, digest) = (&kind.wait.running, &kind.wait.digest);
+        table.row([
+            kind.kind.name().to_string(),
+            human::thousands(running.count()),
+            time(running.mean()),
+            time(digest.quantile(0.5)),
+            time(digest.quantile(0.9)),
+            time(digest.quantile(0.99)),
+            time(running.max()),
+        ]);
+    }
+    print!("{}", table.render());
+    report_paused(&args);
+    match result.kinds.iter().filter(|kind| kind.lost_updates > 0).map(|kind| kind.kind.name()).collect::<Vec<_>>() {
+        broken if broken.is_empty() => Ok(()),
+        broken => Err(io::Error::other(format!("{} locks let holders overlap", broken.join(", ")))),
+    }
+}
+
+/// `io bench xattr`: per-file metadata kept in an extended attribute against a sidecar file.
+fn xattr(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench xattr runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| xattr::run(&dir_path, args.xattr_size, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let per_file = |elapsed: Duration| human::duration(elapsed / files.max(1) as u32);
+    let mut table = Table::new(["Store", "Write", "Per file", "Read", "Per file"]);
+    for store in &result.stores {
+        table.row([store.store.name().to_string(), human::duration(store.write), per_file(store.write), human::duration(store.read), per_file(store.read)]);
+    }
+    print!("{}", table.render());
+    println!("{} of metadata for each of {} files", human::bytes(result.value_size as u64), human::thousands(files as u64));
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// The globs of `selector`, for messages.
+fn selection(selector: &Selector) -> String {
+    let include = if selector.include.is_empty() { "every file".to_string() } else { selector.include.iter().map(Glob::to_string).collect::<Vec<_>>().join(" or ") };
+    match selector.exclude.is_empty() {
+        true => include,
+        false => format!("{} except {}", include, selector.exclude.iter().map(Glob::to_string).collect::<Vec<_>>().join(" or ")),
+    }
+}
+
+/// `io bench permissions`: setting every file's mode and owner by path against through
+/// descriptors already open.
+fn permissions(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench permissions runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| permissions::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let per_file = |elapsed: Duration| human::duration(elapsed / files.max(1) as u32);
+    let mut table = Table::new(["Method", "chmod", "Per file", "chown", "Per file"]);
+    for method in &result.methods {
+        table.row([method.method.name().to_string(), human::duration(method.chmod), per_file(method.chmod), human::duration(method.chown), per_file(method.chown)]);
+    }
+    print!("{}", table.render());
+    if let [by_path, by_descriptor] = &result.methods[..] {
+        let (path, descriptor) = (by_path.chmod + by_path.chown, by_descriptor.chmod + by_descriptor.chown);
+        if !descriptor.is_zero() {
+            println!("Open descriptors were {}x as fast as paths for {} files", human::decimal(path.as_secs_f64() / descriptor.as_secs_f64(), 2), human::thousands(files as u64));
+        }
+    }
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench stash`: times saving the workload tree aside and restoring it, as CI caches
+/// do, by each way of making the files.
+fn stash(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench stash runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| stash::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let per_file = |elapsed: Duration| human::duration(elapsed / files.max(1) as u32);
+    let mut table = Table::new(["Method", "Snapshot", "Per file", "Restore", "Per file"]);
+    for method in &result.methods {
+        match (method.snapshot, method.restore) {
+            (Some(snapshot), Some(restore)) => table.row([method.method.name().to_string(), human::duration(snapshot), per_file(snapshot), human::duration(restore), per_file(restore)]),
+            _ => table.row([method.method.name().to_string(), "unsupported".to_string(), "-".to_string(), "-".to_string(), "-".to_string()]),
+        }
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench mirror`: times syncing the workload into a fresh tree, again with nothing
+/// changed, and after a tenth of it changed, deciding changes by each comparison.
+fn mirror(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench mirror runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| mirror::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let changes = result.changes;
+    println!(
+        "Between syncs {} files were rewritten, {} deleted and {} added",
+        human::thousands(changes.updated as u64),
+        human::thousands(changes.deleted as u64),
+        human::thousands(changes.created as u64)
+    );
+    let mut table = Table::new(["Compare", "Full", "Unchanged", "Incremental", "Copied"]);
+    for compare in &result.compares {
+        table.row([
+            compare.compare.name().to_string(),
+            human::duration(compare.full),
+            human::duration(compare.unchanged),
+            human::duration(compare.incremental),
+            human::bytes(compare.report.bytes),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench copy`: times copying the workload tree with `copy_tree`, with and without
+/// preserving metadata, against a naive recursive copy and `cp -r`.
+fn copy(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench copy runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| copy::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    if let Some(checkout) = result.checkout {
+        println!("copy_tree made the files by {}", checkout.name());
+    }
+    let mut table = Table::new(["Method", "Time", "Per file", "Files/s"]);
+    for method in &result.methods {
+        match method.elapsed {
+            Some(elapsed) => table.row([method.method.name().to_string(), human::duration(elapsed), human::duration(elapsed / files.max(1) as u32), human::rate(files as f64, elapsed)]),
+            None => table.row([method.method.name().to_string(), "unsupported".to_string(), "-".to_string(), "-".to_string()]),
+        }
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench mkdir`: times making a deep tree of as many leaf directories as the workload
+/// has files, `--depth` levels down, by `create_dir_all` per leaf and by
+/// `create_dirs_many`.
+fn mkdir(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench mkdir runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| mkdir::run(&dir_path, args.depth, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    println!("Making {} leaf directories {} levels down, {} directories in all", human::thousands(files as u64), args.depth, human::thousands(result.dirs as u64));
+    let mut table = Table::new(["Method", "Time", "Per leaf", "Dirs/s"]);
+    for (method, elapsed) in &result.times {
+        table.row([method.name().to_string(), human::duration(*elapsed), human::duration(*elapsed / files.max(1) as u32), human::rate(result.dirs as f64, *elapsed)]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench pipeline`: the create phase with each worker opening and writing its files,
+/// against open threads handing descriptors to the workers.
+fn pipeline(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench pipeline runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| pipeline::run(&dir_path, args.open_threads, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let per_second = |elapsed: Duration| human::thousands(if elapsed.is_zero() { 0 } else { (files as f64 / elapsed.as_secs_f64()) as u64 });
+    let mut table = Table::new(["Create", "Time", "Files/s", "Speedup"]);
+    let speedup = if result.pipelined.is_zero() { 0.0 } else { result.per_task.as_secs_f64() / result.pipelined.as_secs_f64() };
+    table.row(["per-task".to_string(), human::duration(result.per_task), per_second(result.per_task), String::new()]);
+    table.row([format!("pipelined ({} open threads)", result.open_threads), human::duration(result.pipelined), per_second(result.pipelined), format!("{}x", human::decimal(speedup, 2))]);
+    print!("{}", table.render());
+    println!(
+        "{} writers waited {} for opened files in all, {} at most queued",
+        threads,
+        human::duration(result.starved),
+        args.options.queue_depth(),
+    );
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench handles`: reading, updating and deleting the workload with every phase opening
+/// each file again, then through an open-handle cache.
+fn handles(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench handles runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let capacity = args.handle_cache.unwrap_or_else(|| args.options.open_files.max().saturating_sub(threads).max(1));
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| handles::run(&dir_path, capacity, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let mut table = Table::new(["Handles", "Read", "Update", "Delete", "Total"]);
+    for (name, times) in [("reopened", &result.reopened), ("cached", &result.cached)] {
+        table.row([name.to_string(), human::duration(times.read), human::duration(times.update), human::duration(times.delete), human::duration(times.read + times.update + times.delete)]);
+    }
+    print!("{}", table.render());
+    let stats = result.stats;
+    println!(
+        "Cache of {} handles: {} hits, {} misses, {} evictions",
+        human::thousands(result.capacity as u64),
+        human::thousands(stats.hits),
+        human::thousands(stats.misses),
+        human::thousands(stats.evictions),
+    );
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench read`: the read phase once per read method, on the same files.
+fn read(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench read runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    let set = match &args.root {
+        Some(root) => {
+            let set = DataSet::select(root, &args.selector)?;
+            if set.is_empty() {
+                return Err(invalid_input(format!("no files under {} match {}", root.display(), selection(&args.selector))));
+            }
+            println!("Reading {} files, {}, of {} in place", human::thousands(set.len() as u64), human::bytes(set.bytes()), root.display());
+            Some(set)
+        }
+        None if !args.selector.is_all() => return Err(invalid_input("--glob and --exclude pick files under --root".to_string())),
+        None => None,
+    };
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = if set.is_none() { Some(bench_dir()?) } else { None };
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| match (&set, &dir_path) {
+        (Some(set), _) => read::run_set(set, &args.read_methods, &args.options),
+        (None, Some(dir_path)) => read::run(dir_path, &args.read_methods, &args.options),
+        (None, None) => unreachable!("the workload always gets a benchmark directory"),
+    });
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    if let Some(dir_path) = &dir_path {
+        fs::remove_dir_all(dir_path)?;
+    }
+    let result = result?;
+
+    let per_second = |elapsed: Duration| format!("{}/s", human::bytes(if elapsed.is_zero() { 0 } else { (result.read_bytes as f64 / elapsed.as_secs_f64()) as u64 }));
+    let prefetch = args.options.prefetch.map(|how| format!("With {}", how.name()));
+    let mut table = Table::new(["Method", "Read", "Rate"].into_iter().map(str::to_string).chain(prefetch.into_iter().flat_map(|with| [with, "Rate".to_string(), "Speedup".to_string()])));
+    for method in &result.methods {
+        let prefetched = method.prefetched.map(|prefetched| {
+            let speedup = if prefetched.is_zero() { 0.0 } else { method.read.as_secs_f64() / prefetched.as_secs_f64() };
+            [human::duration(prefetched), per_second(prefetched), format!("{}x", human::decimal(speedup, 2))]
+        });
+        table.row([method.method.name().to_string(), human::duration(method.read), per_second(method.read)].into_iter().chain(prefetched.into_iter().flatten()));
+    }
+    print!("{}", table.render());
+    println!(
+        "{}, madvise: {}{}",
+        if args.options.cold_read { "Evicted before each read" } else { "Warm page cache" },
+        args.options.madvise.map_or("none".to_string(), |hint| format!("{:?}", hint).to_lowercase()),
+        match args.options.prefetch {
+            Some(_) => format!(", prefetching {} files ahead", args.options.prefetch_window()),
+            None => String::new(),
+        },
+    );
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench crash`: crashes a worker process rewriting the workload with each write
+/// strategy and checks which files survived whole and up to date.
+fn crash(args: BenchArgs) -> io::Result<()> {
+    let (&[files], &[size]) = (&args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench crash runs a single file count and size".to_string()));
+    };
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |elapsed| elapsed.as_nanos() as u64);
+    let config = CrashConfig { mode: args.crash_mode, files, size: size.max(crash::MIN_SIZE), trials: args.trials, max_delay: args.crash_after, seed };
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let exe = env::current_exe()?;
+    let worker = |dir: &Path, strategy: CrashStrategy| {
+        let mut command = Command::new(&exe);
+        command.args(["bench", "crash-worker", "--strategy", strategy.name(), "--files", &files.to_string(), "--size", &config.size.to_string(), "--dir"]).arg(dir);
+        command
+    };
+    let result = crash::run(&dir_path, &args.crash_strategies, config, worker, &args.options);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let mut table = Table::new(["Strategy", "Claims", "Acked writes", "Current", "Stale", "Torn", "Missing", "Broken trials"]);
+    for crashes in &result.strategies {
+        let claims = match (crashes.strategy.atomic(), crashes.strategy.durable()) {
+            (true, true) => "atomic, durable",
+            (true, false) => "atomic",
+            (false, true) => "durable",
+            (false, false) => "-",
+        };
+        let survival = &crashes.survival;
+        table.row([
+            crashes.strategy.name().to_string(),
+            claims.to_string(),
+            human::thousands(crashes.acknowledged),
+            human::thousands(survival.current as u64),
+            human::thousands(survival.stale as u64),
+            human::thousands(survival.torn as u64),
+            human::thousands(survival.missing as u64),
+            format!("{} of {}", crashes.broken, config.trials),
+        ]);
+    }
+    print!("{}", table.render());
+    println!(
+        "{} crashes of {} files of {}, each up to {} in; durability only counts for power cuts",
+        config.mode.name(),
+        human::thousands(files as u64),
+        human::bytes(config.size as u64),
+        human::duration(config.max_delay),
+    );
+    match result.strategies.iter().filter(|crashes| crashes.broken > 0).map(|crashes| crashes.strategy.name()).collect::<Vec<_>>() {
+        broken if broken.is_empty() => Ok(()),
+        broken => Err(io::Error::other(format!("crashes broke the claims of {}", broken.join(", ")))),
+    }
+}
+
+/// `io bench crash-worker`: the process `io bench crash` kills, rewriting its files until then.
+fn crash_worker(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let (mut dir, mut strategy, mut files, mut size) = (None, None, 0, crash::MIN_SIZE);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => dir = Some(flag_value::<PathBuf>(&mut args, &arg)?),
+            "--strategy" => strategy = Some(flag_value::<CrashStrategy>(&mut args, &arg)?),
+            "--files" => files = flag_value(&mut args, &arg)?,
+            "--size" => size = flag_value::<ByteSize>(&mut args, &arg)?.0,
+            other => return Err(invalid_input(format!("unknown io bench crash-worker flag '{}'", other))),
+        }
+    }
+    let (Some(dir), Some(strategy)) = (dir, strategy) else {
+        return Err(invalid_input("io bench crash-worker needs --dir and --strategy".to_string()));
+    };
+    crash::serve_worker(&dir, strategy, files, size, io::stdout().lock())
+}
+
+/// `io bench append`: times many threads appending small records to a few logs through
+/// `O_APPEND`, a locked writer and per-thread files, and checks the logs left behind.
+fn append(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench append runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    let config = AppendConfig { logs: files, records: args.records, record_size: size.max(append::MIN_RECORD) };
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| append::run(&dir_path, config, &AppendMode::ALL, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let mut table = Table::new(["Mode", "Time", "Records/s", "Rate", "Torn", "Missing", "Out of order"]);
+    for mode in &result.modes {
+        let seconds = mode.elapsed.as_secs_f64();
+        let per_second = |count: u64| if seconds == 0.0 { 0 } else { (count as f64 / seconds) as u64 };
+        table.row([
+            mode.mode.name().to_string(),
+            human::duration(mode.elapsed),
+            human::thousands(per_second(config.records as u64)),
+            format!("{}/s", human::bytes(per_second((config.records * config.record_size) as u64))),
+            human::thousands(mode.check.torn as u64),
+            human::thousands(mode.check.missing as u64),
+            human::thousands(mode.check.out_of_order as u64),
+        ]);
+    }
+    print!("{}", table.render());
+    println!(
+        "{} records of {} from {} threads over {} logs",
+        human::thousands(config.records as u64),
+        human::bytes(config.record_size as u64),
+        result.threads,
+        config.logs,
+    );
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench sparse`: times creating the workload as sparse files against writing it whole,
+/// punching holes into whole files, and reading sparse files whole or data only.
+fn sparse(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench sparse runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| sparse::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let per_second = |elapsed: Duration| format!("{}/s", human::bytes(if elapsed.is_zero() { 0 } else { (result.logical_bytes as f64 / elapsed.as_secs_f64()) as u64 }));
+    let mut table = Table::new(["Phase", "Time", "Logical rate", "Allocated"]);
+    for (phase, elapsed, allocated) in [
+        ("create whole", result.create_dense, Some(result.dense_allocated)),
+        ("create sparse (seek + write)", result.create_sparse, Some(result.sparse_allocated)),
+        ("punch holes", result.punch, Some(result.punched_allocated)),
+        ("read whole", result.read_whole, None),
+        ("read data only (SEEK_DATA)", result.read_data, None),
+    ] {
+        table.row([phase.to_string(), human::duration(elapsed), per_second(elapsed), allocated.map_or("-".to_string(), human::bytes)]);
+    }
+    print!("{}", table.render());
+    println!(
+        "{} logical, {} data (one in {} extents of {}); holes {}",
+        human::bytes(result.logical_bytes),
+        human::bytes(result.data_bytes),
+        sparse::DATA_EVERY,
+        human::bytes(sparse::EXTENT),
+        if result.holes_detected { "found where written" } else { "not reported by this filesystem" },
+    );
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench encrypt`: times creating and reading the workload with each cipher sealing
+/// the files at rest.
+fn encrypt(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench encrypt runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| cipher::run(&dir_path, &args.ciphers, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    println!("AES: {}", if result.aes_hardware { "AES-NI and PCLMULQDQ" } else { "portable tables" });
+    let per_second = |elapsed: Duration| format!("{}/s", human::bytes(if elapsed.is_zero() { 0 } else { (result.source_bytes as f64 / elapsed.as_secs_f64()) as u64 }));
+    let baseline = result.ciphers.iter().find(|cipher| cipher.cipher == Cipher::Plaintext);
+    let overhead = |elapsed: Duration, base: Option<Duration>| match base {
+        Some(base) if !base.is_zero() => format!("{:+.0}%", (elapsed.as_secs_f64() / base.as_secs_f64() - 1.0) * 100.0),
+        _ => "-".to_string(),
+    };
+    let mut table = Table::new(["Cipher", "Create", "Read", "Create rate", "Read rate", "Create cost", "Read cost"]);
+    for cipher in &result.ciphers {
+        table.row([
+            cipher.cipher.name().to_string(),
+            human::duration(cipher.create),
+            human::duration(cipher.read),
+            per_second(cipher.create),
+            per_second(cipher.read),
+            overhead(cipher.create, baseline.map(|base| base.create)),
+            overhead(cipher.read, baseline.map(|base| base.read)),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench hash`: times hashing every file of the workload with each algorithm, reading
+/// it buffered, mapped or streamed in chunks.
+fn hash(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench hash runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| digest::run(&dir_path, &args.algorithms, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let per_second = |elapsed: Duration| format!("{}/s", human::bytes(if elapsed.is_zero() { 0 } else { (result.source_bytes as f64 / elapsed.as_secs_f64()) as u64 }));
+    let mut table = Table::new(["Hash", "Method", "Time", "Rate", "vs read"]);
+    table.row(["-".to_string(), "read only".to_string(), human::duration(result.read), per_second(result.read), "1.00x".to_string()]);
+    for method in &result.methods {
+        table.row([
+            method.algorithm.name().to_string(),
+            method.method.name().to_string(),
+            human::duration(method.elapsed),
+            per_second(method.elapsed),
+            format!("{:.2}x", method.elapsed.as_secs_f64() / result.read.as_secs_f64().max(f64::EPSILON)),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench s3`: runs the workload's phases against an S3-compatible bucket, one object
+/// per file, then the traditional strategy on the local filesystem, and compares the two.
+#[cfg(feature = "s3")]
+fn s3(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench s3 runs a single thread count, file count and size".to_string()));
+    };
+    if args.s3.endpoint.is_empty() || args.s3.bucket.is_empty() {
+        return Err(invalid_input("io bench s3 needs --endpoint http://host:port and --bucket <name>".to_string()));
+    }
+    args.options.workload = Workload::new(files, size).named(args.names);
+    let client = s3::Client::new(args.s3.clone())?;
+    if args.create_bucket {
+        client.create_bucket()?;
+    }
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let prefix = format!("io-bench-{}", std::process::id());
+    println!("Running against s3://{}/{}/ at {}...", client.bucket(), prefix, args.s3.endpoint);
+    let remote = engine.install(|| s3::run(&client, &prefix, &args.options));
+    let local = match &remote {
+        Ok(_) => Some(engine.install(|| bench::run_strategy(&STRATEGIES[0], &dir_path, &args.options))),
+        Err(_) => None,
+    };
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let remote = remote?;
+    let local = local.transpose()?.unwrap_or_default();
+
+    let mut table = Table::new(["Backend"].into_iter().chain(PHASE_COLUMNS));
+    table.row(["Local (traditional)".to_string()].into_iter().chain(phase_cells(&local.times)));
+    table.row([format!("S3 ({})", args.s3.endpoint)].into_iter().chain(phase_cells(&remote.times)));
+    print!("{}", table.render());
+    report_failures(&local.failures);
+    report_failures(&remote.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench commit`: times making the workload durable with a sync per file, per batch,
+/// and with the fewest syncs `commit_batch` can manage.
+fn commit(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench commit runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| atomic::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    println!("Replacing {} files in {} directories durably:", human::thousands(files as u64), atomic::COMMIT_DIRS.min(files));
+    let mut table = Table::new(["Method", "Time", "Per file", "File syncs", "Dir syncs", "syncfs", "vs per-file"]);
+    let per_file = result.times.first().map_or(Duration::ZERO, |(_, elapsed, _)| *elapsed);
+    for (method, elapsed, report) in &result.times {
+        table.row([
+            method.name().to_string(),
+            human::duration(*elapsed),
+            human::duration(*elapsed / files.max(1) as u32),
+            human::thousands(report.file_syncs as u64),
+            human::thousands(report.dir_syncs as u64),
+            human::thousands(report.filesystem_syncs as u64),
+            human::change((elapsed.as_secs_f64() / per_file.as_secs_f64().max(f64::MIN_POSITIVE) - 1.0) * 100.0, 1),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench scan`: times enumerating the workload laid out as a nested tree with each
+/// walker.
+fn scan(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench scan runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| scan::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    for (walker, e) in &result.skipped {
+        println!("{} skipped: {}", walker.name(), e);
+    }
+    let mut table = Table::new(["Walker", "Entries", "Time", "Entries/s"]);
+    for walk in &result.walks {
+        table.row([walk.walker.name().to_string(), human::thousands(walk.entries as u64), human::duration(walk.elapsed), human::rate(walk.entries as f64, walk.elapsed)]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench remove`: times deleting the workload laid out as a nested tree per file and
+/// as a whole tree, with and without directory descriptors.
+fn remove(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench remove runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| remove::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let mut table = Table::new(["Method", "Time", "Per file", "Files/s"]);
+    for (removal, elapsed) in &result.times {
+        table.row([
+            removal.name().to_string(),
+            human::duration(*elapsed),
+            human::duration(*elapsed / files.max(1) as u32),
+            human::rate(files as f64, *elapsed),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench open`: times opening the workload's files by full path and relative to their
+/// directory, `--depth` directories down.
+fn open(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench open runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| dirs::run(&dir_path, args.depth, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    for (open_path, e) in &result.skipped {
+        println!("{} skipped: {}", open_path.name(), e);
+    }
+    println!("Opening {} files {} directories below {}", human::thousands(files as u64), args.depth, dir_path.display());
+    let absolute = result.times.iter().find(|(open_path, _)| *open_path == OpenPath::Absolute).map(|&(_, elapsed)| elapsed);
+    let mut table = Table::new(["Open", "Time", "Per file", "Files/s", "vs absolute"]);
+    for (open_path, elapsed) in &result.times {
+        table.row([
+            open_path.name().to_string(),
+            human::duration(*elapsed),
+            human::duration(*elapsed / files.max(1) as u32),
+            human::rate(files as f64, *elapsed),
+            absolute.map_or_else(|| "-".to_string(), |absolute| human::change((elapsed.as_secs_f64() / absolute.as_secs_f64() - 1.0) * 100.0, 1)),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench coalesce`: times creating and overwriting the workload's files, spread over
+/// directories and shuffled, in that order and grouped by directory.
+fn coalesce(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench coalesce runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| coalesce::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    println!("Writing {} files over {} directories in shuffled order", human::thousands(files as u64), coalesce::DIRECTORIES.min(files.max(1)));
+    let arbitrary = result.runs.iter().find(|run| run.method == coalesce::Method::Arbitrary).copied();
+    let versus = |elapsed: Duration, arbitrary: Duration| human::change((elapsed.as_secs_f64() / arbitrary.as_secs_f64().max(f64::MIN_POSITIVE) - 1.0) * 100.0, 1);
+    let mut table = Table::new(["Method", "Create", "Files/s", "vs arbitrary", "Overwrite", "Files/s", "vs arbitrary"]);
+    for run in &result.runs {
+        table.row([
+            run.method.name().to_string(),
+            human::duration(run.create),
+            human::rate(files as f64, run.create),
+            arbitrary.map_or_else(|| "-".to_string(), |arbitrary| versus(run.create, arbitrary.create)),
+            human::duration(run.update),
+            human::rate(files as f64, run.update),
+            arbitrary.map_or_else(|| "-".to_string(), |arbitrary| versus(run.update, arbitrary.update)),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench ranged`: times writing one large file sequentially against several writers
+/// each writing a range of it, for every `--threads` count.
+fn ranged_writes(args: BenchArgs) -> io::Result<()> {
+    let &[size] = &args.sizes[..] else {
+        return Err(invalid_input("io bench ranged writes a single file size".to_string()));
+    };
+    // The default size is a small file's; the file here is meant to be large.
+    let len = if size == Workload::default().size() { ranged::DEFAULT_LEN } else { size as u64 };
+    let scheduler = start_scheduler(&args)?;
+    let dir_path = bench_dir()?;
+    let result = ranged::run(&dir_path, len, &args.threads, &args.options);
+    drop(scheduler);
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    println!("Writing one {} file in segments of {}{}", human::bytes(len), human::bytes(ranged::SEGMENT as u64), if args.options.sync == SyncMode::None { "" } else { ", synced" });
+    let sequential = result.runs.iter().find(|run| !run.method.is_parallel()).map(|run| run.elapsed);
+    let versus = |elapsed: Duration| match sequential {
+        Some(sequential) => human::change((elapsed.as_secs_f64() / sequential.as_secs_f64().max(f64::MIN_POSITIVE) - 1.0) * 100.0, 1),
+        None => "-".to_string(),
+    };
+    let mut table = Table::new(["Method", "Writers", "Time", "Rate", "vs sequential"]);
+    for run in &result.runs {
+        table.row([
+            run.method.name().to_string(),
+            run.writers.to_string(),
+            human::duration(run.elapsed),
+            format!("{}/s", human::bytes(if run.elapsed.is_zero() { 0 } else { (len as f64 / run.elapsed.as_secs_f64()) as u64 })),
+            versus(run.elapsed),
+        ]);
+    }
+    print!("{}", table.render());
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench readonly <dir>`: times stat, read and hash phases over an existing tree,
+/// which only read-only handles can reach.
+fn readonly(root: PathBuf, mut args: BenchArgs) -> io::Result<()> {
+    let &[threads] = &args.threads[..] else {
+        return Err(invalid_input("io bench readonly runs a single thread count".to_string()));
+    };
+    if let Some(probes) = args.sample {
+        if !args.selector.is_all() {
+            return Err(invalid_input("--sample walks the whole tree; it can't be combined with --glob or --exclude".to_string()));
+        }
+        return readonly_sampled(&root, probes, threads, args);
+    }
+    let mut set = DataSet::select(&root, &args.selector)?;
+    if args.parquet.is_some() {
+        args.options.file_times = Some(Arc::default());
+    }
+    println!("Read-only over {}: {} files, {}", set.root().display(), human::thousands(set.len() as u64), human::bytes(set.bytes()));
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| {
+        arrange_read_order(&mut set, &args)?;
+        readonly::run(&set, &args.options)
+    });
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    let result = result?;
+
+    let mut table = Table::new(["Phase", "Time", "Files/s", "Read", "Throughput"]);
+    for (phase, elapsed, bytes) in &result.times {
+        let per_second = if elapsed.is_zero() { 0 } else { (*bytes as f64 / elapsed.as_secs_f64()) as u64 };
+        table.row([
+            phase.name().to_string(),
+            human::duration(*elapsed),
+            human::rate(set.len() as f64, *elapsed),
+            human::bytes(*bytes),
+            format!("{}/s", human::bytes(per_second)),
+        ]);
+    }
+    print!("{}", table.render());
+    if let (Some(path), Some(times)) = (&args.parquet, &args.options.file_times) {
+        let sizes: HashMap<&Path, u64> = set.paths().iter().map(PathBuf::as_path).zip(set.sizes().iter().copied()).collect();
+        write_parquet(path, &times.take(), |time| sizes.get(time.path.as_path()).copied().unwrap_or(0))?;
+    }
+    print_breakdown(&result.breakdown, set.bytes());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// Puts `set` in the `--read-order` given, or with `--read-order auto` in whichever order
+/// reads it fastest.
+fn arrange_read_order(set: &mut DataSet, args: &BenchArgs) -> io::Result<()> {
+    let mut read_order = args.read_order;
+    if args.pick_read_order {
+        let result = order::run(set, &args.options)?;
+        print_read_orders(&result, set.len());
+        report_failures(&result.failures);
+        read_order = result.best().map(|run| run.order);
+    }
+    if let Some(read_order) = read_order {
+        set.arrange(read_order);
+        println!("Reading in {} order", read_order.name());
+    }
+    Ok(())
+}
+
+/// Each read order's fastest time over `files` files, against directory order, and the
+/// fastest order.
+fn print_read_orders(result: &OrderResult, files: usize) {
+    let directory = result.runs.iter().find(|run| run.order == ReadOrder::Directory).map_or(Duration::ZERO, |run| run.elapsed);
+    let mut table = Table::new(["Order", "Time", "Files/s", "Throughput", "vs directory"]);
+    for run in &result.runs {
+        let per_second = if run.elapsed.is_zero() { 0 } else { (run.bytes as f64 / run.elapsed.as_secs_f64()) as u64 };
+        table.row([
+            run.order.name().to_string(),
+            human::duration(run.elapsed),
+            human::rate(files as f64, run.elapsed),
+            format!("{}/s", human::bytes(per_second)),
+            human::change((run.elapsed.as_secs_f64() / directory.as_secs_f64().max(f64::MIN_POSITIVE) - 1.0) * 100.0, 1),
+        ]);
+    }
+    print!("{}", table.render());
+    if let Some(cached) = result.runs.iter().map(|run| run.cached_pages).max().filter(|&pages| pages > 0) {
+        println!("Note: up to {} pages stayed cached after eviction, so orders partly read from memory", human::thousands(cached));
+    }
+    if let Some(best) = result.best() {
+        println!("Best read order: {}", best.order.name());
+    }
+}
+
+/// `io bench order [dir]`: reads an existing tree, or the workload's files, in each read
+/// order with a cold cache and reports the fastest.
+fn read_orders(root: Option<PathBuf>, mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench order runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let generated = match &root {
+        Some(root) => {
+            report_filesystem("Filesystem", root);
+            None
+        }
+        None => Some(bench_dir()?),
+    };
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| match (&root, &generated) {
+        (Some(root), _) => {
+            let set = DataSet::select(root, &args.selector)?;
+            println!("Reading {} files, {}, in each order {} times", human::thousands(set.len() as u64), human::bytes(set.bytes()), order::ROUNDS);
+            order::run(&set, &args.options).map(|result| (result, set.len()))
+        }
+        (None, Some(dir_path)) => order::run_workload(dir_path, &args.options).map(|result| (result, files)),
+        (None, None) => unreachable!("the workload always gets a benchmark directory"),
+    });
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    if let Some(dir_path) = &generated {
+        fs::remove_dir_all(dir_path)?;
+    }
+    let (result, files) = result?;
+
+    print_read_orders(&result, files);
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// Extensions shown by name in the breakdown; the rest are summed into one row.
+const TOP_EXTENSIONS: usize = 15;
+
+/// The tree's files by extension (largest first) and by depth, with each slice's share of
+/// the bytes and the time every phase spent on it.
+fn print_breakdown(breakdown: &Breakdown, total_bytes: u64) {
+    let share = |bytes: u64| format!("{}%", human::decimal(if total_bytes == 0 { 0.0 } else { bytes as f64 * 100.0 / total_bytes as f64 }, 1));
+    let cells = |slice: &Slice| [human::thousands(slice.files), human::bytes(slice.bytes), share(slice.bytes), human::duration(slice.time)];
+    let (top, rest) = breakdown.top_extensions(TOP_EXTENSIONS);
+    let mut table = Table::new(["Extension", "Files", "Bytes", "Share", "Time"]);
+    for (extension, slice) in &top {
+        table.row([extension.to_string()].into_iter().chain(cells(slice)));
+    }
+    if rest.files > 0 {
+        table.row(["(other)".to_string()].into_iter().chain(cells(&rest)));
+    }
+    print!("{}", table.render());
+    let mut table = Table::new(["Depth", "Files", "Bytes", "Share", "Time"]).align(0, Align::Right);
+    for (depth, slice) in &breakdown.by_depth {
+        table.row([depth.to_string()].into_iter().chain(cells(slice)));
+    }
+    print!("{}", table.render());
+}
+
+/// An estimate as `~value ±percent`, marked so it can't be mistaken for a measurement.
+fn estimated(estimate: &Estimate, format: impl Fn(f64) -> String) -> String {
+    format!("~{} ±{}%", format(estimate.mean), human::decimal(estimate.relative() * 100.0, 1))
+}
+
+fn interval(estimate: &Estimate, format: impl Fn(f64) -> String) -> String {
+    let (low, high) = estimate.interval();
+    format!("{} - {}", format(low), format(high))
+}
+
+fn seconds(seconds: f64) -> String {
+    human::duration(Duration::from_secs_f64(seconds.max(0.0)))
+}
+
+/// `io bench readonly <dir> --sample`: estimates the tree's totals and each phase's time
+/// from random walks, for trees too big to list.
+fn readonly_sampled(root: &Path, probes: usize, threads: usize, args: BenchArgs) -> io::Result<()> {
+    let sampler = Sampler::new(probes);
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let result = readonly::run_sampled(root, &sampler, &args.options);
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    let result = result?;
+
+    let tree = &result.tree;
+    println!(
+        "Sampled read-only over {}: {} probes (seed {}) listed {} directories",
+        root.display(),
+        human::thousands(probes as u64),
+        sampler.seed,
+        human::thousands(tree.listed as u64)
+    );
+    let count = |n: f64| human::thousands(n.round() as u64);
+    let size = |n: f64| human::bytes(n.round() as u64);
+    let mut totals = Table::new(["Total", "Estimate", "95% interval"]);
+    totals.row(["directories".to_string(), estimated(&tree.dirs(), count), interval(&tree.dirs(), count)]);
+    totals.row(["files".to_string(), estimated(&tree.files(), count), interval(&tree.files(), count)]);
+    totals.row(["bytes".to_string(), estimated(&tree.bytes(), size), interval(&tree.bytes(), size)]);
+    print!("{}", totals.render());
+
+    let mut table = Table::new(["Phase", "Work", "95% interval", &format!("Time on {} thread{}", threads, if threads == 1 { "" } else { "s" }), "Read"]);
+    for (phase, work, bytes) in &result.times {
+        table.row([
+            phase.name().to_string(),
+            estimated(work, seconds),
+            interval(work, seconds),
+            format!("~{}", seconds(work.mean / threads as f64)),
+            estimated(bytes, size),
+        ]);
+    }
+    print!("{}", table.render());
+    println!("Figures marked ~ are extrapolated from the sample; work is the summed time of every file on one thread.");
+    if tree.unreadable > 0 {
+        println!("{} directories couldn't be listed and were counted as empty", human::thousands(tree.unreadable as u64));
+    }
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench linked`: the create phase against linked io_uring open, write and close
+/// chains at each batch size.
+#[cfg(target_os = "linux")]
+fn linked(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench linked runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| linked::run(&dir_path, &args.batches, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    println!("Creating {} files of {}, sync: {}", human::thousands(files as u64), human::bytes(size as u64), args.options.sync.name());
+    let vs = |elapsed: Duration| human::change((elapsed.as_secs_f64() / result.per_file.as_secs_f64() - 1.0) * 100.0, 1);
+    let mut table = Table::new(["Create", "Time", "Files/s", "Enters", "Enters/file", "vs per-file"]);
+    table.row(["per-file syscalls".to_string(), human::duration(result.per_file), human::rate(files as f64, result.per_file), "-".to_string(), "-".to_string(), "-".to_string()]);
+    for batch in &result.batches {
+        table.row([
+            format!("linked, {} per enter", batch.batch),
+            human::duration(batch.elapsed),
+            human::rate(files as f64, batch.elapsed),
+            human::thousands(batch.enters),
+            human::decimal(batch.enters as f64 / files.max(1) as f64, 3),
+            vs(batch.elapsed),
+        ]);
+    }
+    print!("{}", table.render());
+    if let Some(e) = &result.skipped {
+        println!("linked chains skipped: {}", e);
+    }
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench uring`: times reading the workload with syscalls, plain io_uring and
+/// io_uring over registered files and buffers.
+#[cfg(target_os = "linux")]
+fn uring(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench uring runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| registered::run(&dir_path, &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    for (tier, e) in &result.skipped {
+        println!("{} skipped: {}", tier.name(), e);
+    }
+    if result.uring_unavailable.is_some() {
+        println!("Without io_uring, the posix-aio and nowait tiers are the async comparison");
+    }
+    println!("Reading {} files of {}", human::thousands(files as u64), human::bytes(size as u64));
+    let read = result.times.iter().find(|(tier, _, _)| *tier == Tier::Read).map(|&(_, elapsed, _)| elapsed);
+    let mut table = Table::new(["Tier", "Time", "Per file", "Files/s", "Read", "vs read", "QD"]);
+    for (tier, elapsed, bytes) in &result.times {
+        let concurrency = result.concurrency.iter().find(|(other, _, _)| other == tier);
+        table.row([
+            tier.name().to_string(),
+            human::duration(*elapsed),
+            human::duration(*elapsed / files.max(1) as u32),
+            human::rate(files as f64, *elapsed),
+            human::bytes(*bytes),
+            read.map_or_else(|| "-".to_string(), |read| human::change((elapsed.as_secs_f64() / read.as_secs_f64() - 1.0) * 100.0, 1)),
+            concurrency.map_or_else(|| "-".to_string(), |(_, requested, achieved)| format!("{:.1}/{}", achieved, requested)),
+        ]);
+    }
+    print!("{}", table.render());
+    if let Some((tier, requested, achieved)) = result.concurrency.iter().find(|(_, requested, achieved)| *achieved < *requested as f64 * 0.5) {
+        println!(
+            "{} kept {:.1} of {} operations in flight; the workers, not the queue depth, were the limit",
+            tier.name(),
+            achieved,
+            requested
+        );
+    }
+    if let Some((served, punted)) = result.nowait {
+        println!(
+            "nowait: {} files read without blocking, {} fell back to pread",
+            human::thousands(served),
+            human::thousands(punted)
+        );
+    }
+    if let (Some(first), Some(last)) = (result.shards.iter().min(), result.shards.iter().max()) {
+        println!(
+            "per-core: {} shards finished between {} and {}",
+            result.shards.len(),
+            human::duration(*first),
+            human::duration(*last)
+        );
+    }
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench rename`: times renaming the workload into a sibling directory and moving it
+/// to another filesystem.
+fn rename(mut args: BenchArgs) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench rename runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let cross_dir = args.move_to.clone().or_else(|| rename::default_cross_dir(&dir_path));
+    match &cross_dir {
+        Some(dir) if !rename::same_filesystem(&dir_path, dir)? => println!("Cross-filesystem moves go to {}", dir.display()),
+        Some(dir) => println!("{} is on the same filesystem as {}; its moves are renames too", dir.display(), dir_path.display()),
+        None => println!("No directory on another filesystem found; use --move-to to time cross-filesystem moves"),
+    }
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let result = engine.install(|| rename::run(&dir_path, cross_dir.as_deref(), &args.options));
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let result = result?;
+
+    let mut table = Table::new(["Phase", "Directory", "Renamed", "Copied", "Time", "Per file"]).align(1, Align::Left);
+    for phase in &result.phases {
+        table.row([
+            phase.name.to_string(),
+            phase.dir.display().to_string(),
+            human::thousands(phase.counts.renamed as u64),
+            human::thousands(phase.counts.copied as u64),
+            human::duration(phase.elapsed),
+            human::duration(phase.elapsed / files.max(1) as u32),
+        ]);
+    }
+    print!("{}", table.render());
+    report_failures(&result.failures);
+    report_paused(&args);
+    Ok(())
+}
+
+/// `io bench`: runs every strategy once, optionally saving the times as a baseline.
+fn run(mut args: BenchArgs) -> io::Result<()> {
+    if let Some(path) = args.job.clone() {
+        return run_jobs(&path, args);
+    }
+    let results = match args.processes {
+        Some(processes) => processes::run(&mut args, processes)?,
+        None => run_strategies(&mut args)?,
+    };
+    if let Some(recommendation) = Recommendation::from_results(&results) {
+        recommendation.print();
+        if let Some(path) = &args.recommend {
+            recommendation.save(path)?;
+            println!("\nSaved recommendation to {}", path.display());
+        }
+    }
+    if let Some(path) = &args.save_baseline {
+        results.save(path)?;
+        println!("\nSaved baseline to {}", path.display());
+    }
+    write_report(&args, &[Run { name: "this run".to_string(), results: &results }])
+}
+
+/// With `--report`, renders `runs` side by side to `--report-file` or standard output.
+fn write_report(args: &BenchArgs, runs: &[Run]) -> io::Result<()> {
+    let Some(format) = args.report else {
+        return Ok(());
+    };
+    let rendered = report::render(format, runs);
+    match &args.report_file {
+        Some(path) => {
+            fs::write(path, rendered)?;
+            println!("\nWrote {}", path.display());
+        }
+        None => print!("\n{}", rendered),
+    }
+    Ok(())
+}
+
+/// `io bench report <results.json>...`: a `--report` (Markdown by default) comparing saved
+/// results side by side, each named after its file.
+fn compare_files(paths: &[PathBuf], mut args: BenchArgs) -> io::Result<()> {
+    if paths.is_empty() {
+        return Err(invalid_input("io bench report needs at least one results file".to_string()));
+    }
+    let saved = paths.iter().map(|path| Baseline::load(path)).collect::<io::Result<Vec<_>>>()?;
+    let runs: Vec<Run> = paths
+        .iter()
+        .zip(&saved)
+        .map(|(path, results)| Run { name: path.file_stem().map_or_else(|| path.display().to_string(), |stem| stem.to_string_lossy().into_owned()), results })
+        .collect();
+    args.report.get_or_insert(ReportFormat::Markdown);
+    write_report(&args, &runs)
+}
+
+/// `io bench compare`: reruns the baseline's workload and fails if any phase got slower
+/// than the baseline by more than the threshold.
+fn compare(mut args: BenchArgs) -> io::Result<()> {
+    let Some(path) = args.baseline.clone() else {
+        return Err(invalid_input("io bench compare needs --baseline <file>".to_string()));
+    };
+    let saved = Baseline::load(&path)?;
+    println!("Comparing against {} ({} files of {} with {} threads)", path.display(), saved.files, ByteSize(saved.size), saved.threads);
+    args.threads = vec![saved.threads];
+    args.files = vec![saved.files];
+    args.sizes = vec![saved.size];
+    let here = Metadata::current(workload_config(&args));
+    match &saved.metadata {
+        Some(before) => {
+            let differences = before.config_differences(&here);
+            if !differences.is_empty() {
+                return Err(invalid_input(format!("the baseline's workload was configured differently ({}); rerun with its options or save a new baseline", differences.join(", "))));
+            }
+            let differences = before.host_differences(&here);
+            if !differences.is_empty() {
+                println!("Note: the baseline ran elsewhere ({})", differences.join(", "));
+            }
+        }
+        None => log::warn("cli", format!("{} predates recorded workload options; they can't be checked", path.display())),
+    }
+    let current = run_strategies(&mut args)?;
+    if let (Some(before), Some(now)) = (&saved.filesystem, &current.filesystem)
+        && before.fs_type != now.fs_type
+    {
+        println!("\nWarning: the baseline ran on {}, this run on {}; times aren't comparable", before.fs_type, now.fs_type);
+    }
+    let limits = |run: &Baseline| run.cgroup.iter().flat_map(Limits::notes).collect::<Vec<_>>().join("; ");
+    if limits(&saved) != limits(&current) {
+        let describe = |limits: String| if limits.is_empty() { "no cgroup limits".to_string() } else { limits };
+        println!("\nWarning: the baseline ran under {}, this run under {}; times aren't comparable", describe(limits(&saved)), describe(limits(&current)));
+    }
+    write_report(&args, &[Run { name: "baseline".to_string(), results: &saved }, Run { name: "current".to_string(), results: &current }])?;
+    let regressions = baseline::compare(&saved, &current.results, args.threshold);
+    if regressions > 0 {
+        return Err(io::Error::other(format!("{} phases regressed by more than {}", regressions, args.threshold)));
+    }
+    println!("\nNo phase regressed by more than {}", args.threshold);
+    Ok(())
+}
+
+const MAX_DIFFERENCES_SHOWN: usize = 20;
+
+/// `io bench replay <golden>`: reruns the workload a golden file recorded, with the
+/// thresholds it recorded, and fails if any file was decided or ended differently.
+fn replay(path: PathBuf, mut args: BenchArgs) -> io::Result<()> {
+    let recorded = Recording::read(&path)?;
+    println!("Replaying {} ({} operations over {} files of {})", path.display(), human::thousands(recorded.operations() as u64), human::thousands(recorded.files as u64), ByteSize(recorded.size));
+    args.files = vec![recorded.files];
+    args.sizes = vec![recorded.size];
+    args.crossover = false;
+    args.options.thresholds = recorded.thresholds;
+    let recorder = Arc::new(Recorder::default());
+    args.options.golden = Some(recorder.clone());
+    run_strategies(&mut args)?;
+    let differences = recorded.diff(&recorder.recording(&args.options));
+    if differences.is_empty() {
+        println!("\nEvery decision and outcome matches {}", path.display());
+        return Ok(());
+    }
+    println!("\nOperations that went differently:");
+    let mut table = Table::new(["Scope", "File", "Recorded", "Replayed"]).align(1, Align::Right);
+    for difference in differences.iter().take(MAX_DIFFERENCES_SHOWN) {
+        let missing = || "(not run)".to_string();
+        table.row([difference.scope.clone(), difference.index.to_string(), difference.recorded.clone().unwrap_or_else(missing), difference.replayed.clone().unwrap_or_else(missing)]);
+    }
+    print!("{}", table.render());
+    if differences.len() > MAX_DIFFERENCES_SHOWN {
+        println!("... and {} more", differences.len() - MAX_DIFFERENCES_SHOWN);
+    }
+    Err(io::Error::other(format!("{} operations differ from {}", differences.len(), path.display())))
+}
+
+/// Starts the checkpoint of a `command` session given `args`, named by this run's ID, or
+/// warns and goes without when there's nowhere to keep it.
+fn start_checkpoint(command: &str, args: Vec<String>) -> Option<Checkpoint> {
+    let started = checkpoint::state_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state directory; set XDG_STATE_HOME or HOME"))
+        .and_then(|dir| Checkpoint::create(&dir, rundir::run_id(), command, args));
+    match started {
+        Ok(checkpoint) => {
+            println!("Checkpointing to {}; if stopped, continue with `io bench --resume {}`", checkpoint.path().display(), checkpoint.run_id());
+            Some(checkpoint)
+        }
+        Err(e) => {
+            log::warn("checkpoint", format!("running without a checkpoint: {}", e));
+            None
+        }
+    }
+}
+
+/// Records a finished cell of the session, if it has a checkpoint.
+fn checkpoint_cell(checkpoint: &mut Option<Checkpoint>, cell: Value) -> io::Result<()> {
+    checkpoint.as_mut().map_or(Ok(()), |checkpoint| checkpoint.record(cell))
+}
+
+/// Removes the checkpoint of a session that ran to the end, or says how to continue one
+/// that was cancelled.
+fn end_checkpoint(checkpoint: Option<Checkpoint>, cancel: &cancel::Token) -> io::Result<()> {
+    let Some(checkpoint) = checkpoint else {
+        return Ok(());
+    };
+    if cancel.is_cancelled() {
+        println!("Continue with `io bench --resume {}`", checkpoint.run_id());
+        return Ok(());
+    }
+    checkpoint.finish()
+}
+
+/// `io bench --resume <run-id>`: continues the sweep or soak session whose checkpoint has
+/// that run ID, with the arguments it was started with, after removing the files the
+/// stopped run left behind.
+fn resume(run_id: &str) -> io::Result<()> {
+    let dir = checkpoint::state_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state directory; set XDG_STATE_HOME or HOME"))?;
+    let checkpoint = Checkpoint::load(&dir, run_id)?;
+    let stale = bench::get_dir().join(format!("{}{}", rundir::PREFIX, checkpoint.run_id()));
+    if stale.is_dir() {
+        fs::remove_dir_all(&stale)?;
+    }
+    println!("Resuming {} run {} after {} finished cells", checkpoint.command(), checkpoint.run_id(), checkpoint.cells().len());
+    let args = parse_run_args(checkpoint.args().iter().cloned())?;
+    match checkpoint.command() {
+        "sweep" => sweep(args, Some(checkpoint)),
+        "soak" => run_soak(args, Some(checkpoint)),
+        command => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: io bench {} can't be resumed", checkpoint.path().display(), command))),
+    }
+}
+
+/// The strategy a checkpoint cell names.
+fn cell_strategy(cell: &Value) -> Option<&'static str> {
+    let label = cell.get("strategy")?.as_str()?;
+    STRATEGIES.iter().map(|strategy| strategy.label).find(|&known| known == label)
+}
+
+/// A checkpoint cell's phase times, in milliseconds.
+fn cell_ms(cell: &Value) -> Option<[f64; 4]> {
+    let ms: Vec<f64> = cell.get("ms")?.as_array()?.iter().map(Value::as_f64).collect::<Option<_>>()?;
+    ms.try_into().ok()
+}
+
+struct SweepRow {
+    label: &'static str,
+    threads: usize,
+    files: usize,
+    size: usize,
+    times: PhaseTimes,
+}
+
+/// Prints, per thread count, how much faster (negative) or slower the mmap update of
+/// `smart_io` is than the buffered rewrite of `traditional_io` for every size and file count.
+fn print_update_matrix(rows: &[SweepRow], threads: usize, files: &[usize], sizes: &[usize]) {
+    let update = |label: &str, f: usize, size: usize| {
+        rows.iter()
+            .find(|r| r.label == label && r.threads == threads && r.files == f && r.size == size)
+            .map(|r| r.times.update.as_secs_f64())
+    };
+    println!("\nmmap vs buffered update with {} threads (% change, negative = mmap faster)", threads);
+    let mut table = Table::new(["Size".to_string()].into_iter().chain(files.iter().map(|f| format!("{} files", human::thousands(*f as u64))))).align(0, Align::Right);
+    for &size in sizes {
+        let cells = files.iter().map(|&f| match (update("Smart", f, size), update("Traditional", f, size)) {
+            (Some(mmap), Some(buffered)) if buffered > 0.0 => human::change((mmap / buffered - 1.0) * 100.0, 0),
+            _ => "-".to_string(),
+        });
+        table.row([human::bytes(size as u64)].into_iter().chain(cells));
+    }
+    print!("{}", table.render());
+}
+
+/// `io bench sweep`: reruns every strategy for each combination of thread count, file count
+/// and file size, printing a scaling table that can also be written as CSV.
+fn sweep(mut args: BenchArgs, mut checkpoint: Option<Checkpoint>) -> io::Result<()> {
+    // Cells finished before a resume.
+    let mut rows: Vec<SweepRow> = checkpoint
+        .iter()
+        .flat_map(Checkpoint::cells)
+        .filter_map(|cell| {
+            let [create, read, update, delete] = cell_ms(cell)?.map(|ms| Duration::from_secs_f64(ms / 1000.0));
+            Some(SweepRow {
+                label: cell_strategy(cell)?,
+                threads: cell.get("threads")?.as_u64()? as usize,
+                files: cell.get("files")?.as_u64()? as usize,
+                size: cell.get("size_bytes")?.as_u64()? as usize,
+                times: PhaseTimes { create, read, update, delete, ..PhaseTimes::default() },
+            })
+        })
+        .collect();
+    let done = |rows: &[SweepRow], label: &str, threads: usize, files: usize, size: usize| rows.iter().any(|r| r.label == label && r.threads == threads && r.files == files && r.size == size);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let _fillers = precondition(&args, &[&dir_path])?;
+    let largest = args.files.iter().flat_map(|&files| args.sizes.iter().map(move |&size| space::projected(files, size))).max().unwrap_or(0);
+    let mut reservations = claim_space(&args, &[&dir_path], largest)?;
+
+    'sweep: for &threads in &args.threads {
+        let finished = args.files.iter().all(|&files| args.sizes.iter().all(|&size| STRATEGIES.iter().all(|strategy| done(&rows, strategy.label, threads, files, size))));
+        if finished {
+            continue;
+        }
+        let mut engine = warm_engine(threads, args.numa, args.priority)?;
+        if args.crossover {
+            args.options.thresholds = engine.install(|| find_crossover(&dir_path))?;
+        }
+        reservations.clear();
+        for &files in &args.files {
+            for &size in &args.sizes {
+                if STRATEGIES.iter().all(|strategy| done(&rows, strategy.label, threads, files, size)) {
+                    continue;
+                }
+                println!("Sweeping {} files of {} with {} threads...", files, ByteSize(size), threads);
+                args.options.workload = Workload::new(files, size).named(args.names);
+                for strategy in STRATEGIES {
+                    if done(&rows, strategy.label, threads, files, size) {
+                        continue;
+                    }
+                    let result = engine.install(|| bench::run_strategy(strategy, &dir_path, &args.options))?;
+                    report_failures(&result.failures);
+                    if result.interrupted.is_some() {
+                        break 'sweep;
+                    }
+                    let cell = Value::object()
+                        .with("strategy", strategy.label)
+                        .with("threads", threads)
+                        .with("files", files)
+                        .with("size_bytes", size)
+                        .with("ms", baseline::phase_ms(&result.times).to_vec());
+                    checkpoint_cell(&mut checkpoint, cell)?;
+                    rows.push(SweepRow { label: strategy.label, threads, files, size, times: result.times });
+                }
+            }
+        }
+    }
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    report_paused(&args);
+    fs::remove_dir_all(&dir_path)?;
+
+    // Cells restored from a checkpoint go where the sweep would have run them.
+    let position = |items: &[usize], item: usize| items.iter().position(|&other| other == item);
+    rows.sort_by_key(|row| {
+        (
+            position(&args.threads, row.threads),
+            position(&args.files, row.files),
+            position(&args.sizes, row.size),
+            STRATEGIES.iter().position(|strategy| strategy.label == row.label),
+        )
+    });
+    let mut table = Table::new(["Strategy", "Threads", "Files", "Size"].into_iter().chain(PHASE_COLUMNS));
+    for row in &rows {
+        let shape = [row.label.to_string(), row.threads.to_string(), human::thousands(row.files as u64), human::bytes(row.size as u64)];
+        table.row(shape.into_iter().chain(phase_cells(&row.times)));
+    }
+    println!();
+    print!("{}", table.render());
+    if args.files.len() > 1 || args.sizes.len() > 1 {
+        for &threads in &args.threads {
+            print_update_matrix(&rows, threads, &args.files, &args.sizes);
+        }
+    }
+
+    if let Some(path) = &args.csv {
+        let mut csv = BufWriter::new(File::create(path)?);
+        writeln!(csv, "strategy,threads,files,size_bytes,create_ms,read_ms,update_ms,delete_ms,total_ms")?;
+        for row in &rows {
+            let [create, read, update, delete, total] = ms(&row.times);
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{}",
+                row.label, row.threads, row.files, row.size, create, read, update, delete, total
+            )?;
+        }
+        csv.flush()?;
+        println!("\nWrote {}", path.display());
+    }
+    end_checkpoint(checkpoint, &args.options.cancel)
+}
+
+/// `io bench soak`: runs every strategy on the workload again and again for `--duration`,
+/// appending each strategy's phase times per iteration and the state of the machine to
+/// `--soak-log`, then compares the last iteration with the first.
+fn run_soak(mut args: BenchArgs, mut checkpoint: Option<Checkpoint>) -> io::Result<()> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench soak runs one workload; give one thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size).named(args.names);
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+    let dir_path = bench_dir()?;
+    let _fillers = precondition(&args, &[&dir_path])?;
+    let reservations = claim_space(&args, &[&dir_path], space::projected(files, size))?;
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let mut log = soak::Log::open(&args.soak_log)?;
+    drop(reservations);
+    println!(
+        "Soaking {} files of {} with {} threads for {}, appending to {}",
+        human::thousands(files as u64),
+        ByteSize(size),
+        threads,
+        human::duration(args.soak_duration),
+        args.soak_log.display()
+    );
+
+    let mut first: Vec<(&str, f64)> = Vec::new();
+    let mut last = Vec::new();
+    let mut iterations = 0;
+    // Every iteration's phase times per strategy, for what stands out among them.
+    let mut history: Vec<(&str, Vec<[f64; 4]>)> = Vec::new();
+    // Time soaked before a resume, and the strategies of an iteration it cut short.
+    let mut before = Duration::ZERO;
+    let mut unfinished: Vec<(&str, f64)> = Vec::new();
+    for cell in checkpoint.iter().flat_map(Checkpoint::cells) {
+        let (Some(iteration), Some(label), Some(ms), Some(elapsed)) = (cell.get("iteration").and_then(Value::as_u64), cell_strategy(cell), cell_ms(cell), cell.get("elapsed_ms").and_then(Value::as_f64)) else {
+            continue;
+        };
+        if iteration as usize != iterations {
+            if iterations == 1 {
+                first = unfinished.clone();
+            }
+            last = std::mem::take(&mut unfinished);
+            iterations = iteration as usize;
+        }
+        unfinished.push((label, ms.iter().sum()));
+        before = before.max(Duration::from_secs_f64(elapsed / 1000.0));
+        match history.iter_mut().find(|(other, _)| *other == label) {
+            Some((_, runs)) => runs.push(ms),
+            None => history.push((label, vec![ms])),
+        }
+    }
+    if unfinished.len() == STRATEGIES.len() {
+        if iterations == 1 {
+            first = unfinished.clone();
+        }
+        last = std::mem::take(&mut unfinished);
+    } else if !unfinished.is_empty() {
+        iterations -= 1;
+    }
+    let start = Instant::now();
+    while before + start.elapsed() < args.soak_duration && !args.options.cancel.is_cancelled() {
+        iterations += 1;
+        let mut totals = std::mem::take(&mut unfinished);
+        for strategy in STRATEGIES {
+            if totals.iter().any(|(label, _)| *label == strategy.label) {
+                continue;
+            }
+            let result = engine.install(|| bench::run_strategy(strategy, &dir_path, &args.options))?;
+            report_failures(&result.failures);
+            if result.interrupted.is_some() {
+                break;
+            }
+            let iteration = Iteration {
+                iteration: iterations,
+                timestamp: SystemTime::now(),
+                elapsed: before + start.elapsed(),
+                strategy: strategy.label.to_string(),
+                ms: baseline::phase_ms(&result.times),
+                state: State::sample(&dir_path),
+            };
+            log.append(&iteration)?;
+            let cell = Value::object()
+                .with("iteration", iterations)
+                .with("strategy", strategy.label)
+                .with("elapsed_ms", iteration.elapsed.as_secs_f64() * 1000.0)
+                .with("ms", iteration.ms.to_vec());
+            checkpoint_cell(&mut checkpoint, cell)?;
+            totals.push((strategy.label, iteration.total_ms()));
+            match history.iter_mut().find(|(label, _)| *label == strategy.label) {
+                Some((_, runs)) => runs.push(iteration.ms),
+                None => history.push((strategy.label, vec![iteration.ms])),
+            }
+        }
+        let cells: Vec<String> = totals.iter().map(|(label, ms)| format!("{} {} ms", label, human::decimal(*ms, 1))).collect();
+        println!("Iteration {} at {}: {}", iterations, human::duration(before + start.elapsed()), cells.join(", "));
+        if first.is_empty() {
+            first = totals.clone();
+        }
+        last = totals;
+    }
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    report_paused(&args);
+    fs::remove_dir_all(&dir_path)?;
+
+    if args.options.cancel.is_cancelled() {
+        println!("Cancelled after {} iterations", iterations);
+    }
+    if iterations > 1 {
+        println!("\nTotal time, last iteration against the first");
+        let mut table = Table::new(["Strategy", "First ms", "Last ms", "Change"]);
+        for (label, before) in &first {
+            let Some(&(_, after)) = last.iter().find(|(other, _)| other == label) else { continue };
+            let change = if *before > 0.0 { human::change((after / before - 1.0) * 100.0, 1) } else { "-".to_string() };
+            table.row([label.to_string(), human::decimal(*before, 2), human::decimal(after, 2), change]);
+        }
+        print!("{}", table.render());
+    }
+    for (label, runs) in &history {
+        for (i, phase) in baseline::PHASES.iter().enumerate() {
+            for finding in anomaly::examine(&runs.iter().map(|ms| ms[i]).collect::<Vec<_>>(), &Criteria::ITERATIONS) {
+                println!("Anomaly: {}", Anomaly { strategy: label.to_string(), phase: phase.to_string(), finding });
+            }
+        }
+    }
+    println!("Wrote {} iterations to {}", iterations, args.soak_log.display());
+    end_checkpoint(checkpoint, &args.options.cancel)
+}
+
+struct JobRow {
+    job: usize,
+    label: &'static str,
+    times: PhaseTimes,
+}
+
+/// `io bench --job <file>`: runs the workloads of a job file in sequence, then reports them
+/// all in one table (and CSV, with `--csv`).
+fn run_jobs(path: &Path, mut args: BenchArgs) -> io::Result<()> {
+    let mut jobs = job::load(path, &args)?;
+    if args.embedded {
+        jobs.iter_mut().for_each(|job| job.threads = 1);
+    }
+    args.threads = jobs.iter().map(|job| job.threads).collect();
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dashboard = start_dashboard(&args)?;
+
+    let mut rows = Vec::new();
+    for (index, job) in jobs.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        println!(
+            "Job {}: {} files of {} with {} threads, sync {}{}",
+            job.name,
+            job.files,
+            ByteSize(job.size),
+            job.threads,
+            job.sync.name(),
+            job.generator.map_or_else(String::new, |g| format!(", {}", g))
+        );
+        let options = &mut args.options;
+        options.workload = Workload::new(job.files, job.size).named(args.names);
+        options.sync = job.sync;
+        options.preallocate = job.preallocate;
+        options.cold_read = job.cold;
+        options.fresh_buffers = job.fresh_buffers;
+        options.huge_pages = job.huge_pages;
+        options.generator = job.generator;
+        options.verify = job.verify;
+        let dir_path = RunDir::create(job.dir())?;
+        let mut engine = warm_engine(job.threads, args.numa, args.priority)?;
+        if args.crossover {
+            args.options.thresholds = engine.install(|| find_crossover(&dir_path))?;
+        }
+        for strategy in &job.strategies {
+            let result = engine.install(|| bench::run_strategy(strategy, &dir_path, &args.options))?;
+            println!("{} times: {}", strategy.label, phase_summary(&result.times));
+            print_latency(&result.latency, args.options.rate);
+            report_failures(&result.failures);
+            rows.push(JobRow { job: index, label: strategy.label, times: result.times });
+        }
+        fs::remove_dir_all(&dir_path)?;
+    }
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    report_paused(&args);
+
+    let mut table = Table::new(["Job", "Strategy", "Threads", "Files", "Size", "Sync"].into_iter().chain(PHASE_COLUMNS)).align(1, Align::Left).align(5, Align::Left);
+    for row in &rows {
+        let job = &jobs[row.job];
+        let shape = [job.name.clone(), row.label.to_string(), job.threads.to_string(), human::thousands(job.files as u64), human::bytes(job.size as u64), job.sync.name().to_string()];
+        table.row(shape.into_iter().chain(phase_cells(&row.times)));
+    }
+    println!();
+    print!("{}", table.render());
+    if let Some(csv_path) = &args.csv {
+        let mut csv = BufWriter::new(File::create(csv_path)?);
+        writeln!(csv, "job,strategy,threads,files,size_bytes,sync,create_ms,read_ms,update_ms,delete_ms,total_ms")?;
+        for row in &rows {
+            let job = &jobs[row.job];
+            let [create, read, update, delete, total] = ms(&row.times);
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                job.name, row.label, job.threads, job.files, job.size, job.sync.name(), create, read, update, delete, total
+            )?;
+        }
+        csv.flush()?;
+        println!("\nWrote {}", csv_path.display());
+    }
+    Ok(())
+}
+
+/// `io bench workload <file>`: runs the phase DAG of a workload file and reports when each
+/// phase ran and which chain of phases bounded the total.
+fn run_workload(path: &Path, mut args: BenchArgs) -> io::Result<()> {
+    let &[threads] = &args.threads[..] else {
+        return Err(invalid_input("io bench workload takes a single --threads value".to_string()));
+    };
+    let config = workload::Config::load(path)?;
+    if args.html.is_some() {
+        args.options.file_times = Some(Arc::default());
+    }
+    limit_open_files(&mut args)?;
+    let scheduler = start_scheduler(&args)?;
+    let dir_path = bench_dir()?;
+
+    let mut engine = warm_engine(threads, args.numa, args.priority)?;
+    let dashboard = start_dashboard(&args)?;
+    let report = workload::execute(&config, &dir_path, &args.populations, &args.options, engine.pool()?);
+    drop(scheduler);
+    close_dashboard(dashboard)?;
+    fs::remove_dir_all(&dir_path)?;
+    let report = report?;
+
+    let mut table = Table::new(["Phase", "Op", "Population", "Strategy", "Start ms", "Time ms"]).align(1, Align::Left).align(2, Align::Left).align(3, Align::Left);
+    for (node, timing) in config.nodes.iter().zip(&report.timings) {
+        table.row([
+            node.name.clone(),
+            node.op.name().to_string(),
+            config.populations[node.population].name.clone(),
+            node.strategy.label.to_string(),
+            human::millis(timing.start),
+            if timing.reused { "reused".to_string() } else { human::millis(timing.elapsed) },
+        ]);
+    }
+    print!("{}", table.render());
+    for timing in &report.timings {
+        report_failures(&timing.failures);
+    }
+    let critical: Vec<&str> = report.critical_path.iter().map(|&i| config.nodes[i].name.as_str()).collect();
+    let critical_time: Duration = report.critical_path.iter().map(|&i| report.timings[i].elapsed).sum();
+    println!("\nTotal: {}", human::duration(report.total));
+    println!("Critical path: {} ({})", critical.join(" -> "), human::duration(critical_time));
+    report_paused(&args);
+    if let Some(html) = &args.html {
+        write_workload_html(html, &config, &report)?;
+        println!("Wrote {}", html.display());
+    }
+    Ok(())
+}
+
+const TREEMAP_WIDTH: f64 = 900.0;
+const TREEMAP_HEIGHT: f64 = 360.0;
+const SLOWEST_FILES_SHOWN: usize = 10;
+
+/// An HTML report of a workload run: the phase table, then for every phase treemaps of
+/// its time by directory and by file size.
+fn write_workload_html(path: &Path, config: &workload::Config, report: &workload::Report) -> io::Result<()> {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>io workload report</title>\n<style>\
+         body{{font-family:sans-serif}}td,th{{padding:2px 8px;text-align:right}}td:first-child{{text-align:left}}{}</style></head><body>\n\
+         <h1>io workload report</h1>\n<p>Total {:.1} ms.</p>\n<table><tr><th>Phase</th><th>Op</th><th>Population</th><th>Start ms</th><th>Time ms</th></tr>\n",
+        treemap::STYLE,
+        report.total.as_secs_f64() * 1000.0
+    );
+    for (node, timing) in config.nodes.iter().zip(&report.timings) {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td><td>{:.1}</td></tr>\n",
+            treemap::escape(&node.name),
+            node.op.name(),
+            treemap::escape(&config.populations[node.population].name),
+            timing.start.as_secs_f64() * 1000.0,
+            timing.elapsed.as_secs_f64() * 1000.0
+        ));
+    }
+    html.push_str("</table>\n");
+    for (node, timing) in config.nodes.iter().zip(&report.timings) {
+        if timing.files.is_empty() {
+            continue;
+        }
+        let root = &report.population_dirs[node.population];
+        let ms = |elapsed: &Duration| elapsed.as_secs_f64() * 1000.0;
+        let by_dir = Tree::by_directory(root, timing.files.iter().map(|file| (file.path.as_path(), ms(&file.elapsed))));
+        let by_size = Tree::by_size(&node.name, timing.files.iter().map(|file| (file.size, ms(&file.elapsed))));
+        html.push_str(&format!(
+            "<h2>{} ({}, {} files)</h2>\n<h3>Time by directory</h3>\n{}\n<h3>Time by file size</h3>\n{}\n",
+            treemap::escape(&node.name),
+            node.op.name(),
+            timing.files.len(),
+            treemap::render(&by_dir, TREEMAP_WIDTH, TREEMAP_HEIGHT, "ms"),
+            treemap::render(&by_size, TREEMAP_WIDTH, TREEMAP_HEIGHT / 2.0, "ms")
+        ));
+        let mut slowest: Vec<_> = timing.files.iter().collect();
+        slowest.sort_by_key(|file| Reverse(file.elapsed));
+        html.push_str("<h3>Slowest files</h3>\n<table><tr><th>Op</th><th>File</th><th>Bytes</th><th>Time ms</th></tr>\n");
+        for file in slowest.iter().take(SLOWEST_FILES_SHOWN) {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.3}</td></tr>\n",
+                file.op,
+                treemap::escape(&file.path.strip_prefix(root).unwrap_or(&file.path).display().to_string()),
+                file.size,
+                ms(&file.elapsed)
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    let failures: Vec<&Failure> = report.timings.iter().flat_map(|timing| &timing.failures).collect();
+    if !failures.is_empty() {
+        html.push_str("<h2>Failures</h2>\n<table><tr><th>Op</th><th>Phase</th><th>File</th><th>Message</th></tr>\n");
+        for failure in failures {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                failure.op,
+                failure.phase,
+                treemap::escape(&failure.path.display().to_string()),
+                treemap::escape(&failure.message)
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body></html>\n");
+    fs::write(path, html)
+}
+
+/// `io bench populations [remove <name>... | verify <name>... | age <name>...]`: lists,
+/// forgets, checks or ages persisted populations.
+fn populations(mut args: impl Iterator<Item = String>) -> io::Result<()> {
+    let mut store = Store::default();
+    let mut action = None;
+    let mut names = Vec::new();
+    let mut sample = usize::MAX;
+    let mut aging = Aging::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--populations-dir" => store = Store::new(flag_value::<PathBuf>(&mut args, &arg)?),
+            "--sample" => sample = flag_value::<usize>(&mut args, &arg)?.max(1),
+            "--rounds" => aging.rounds = flag_value(&mut args, &arg)?,
+            "--append" => aging.append = flag_value::<ByteSize>(&mut args, &arg)?.0,
+            "--append-fraction" => aging.append_fraction = flag_value::<Percent>(&mut args, &arg)?.0 / 100.0,
+            "--delete-fraction" => aging.delete_fraction = flag_value::<Percent>(&mut args, &arg)?.0 / 100.0,
+            "--seed" => aging.seed = flag_value(&mut args, &arg)?,
+            "remove" | "verify" | "age" if action.is_none() => action = Some(arg),
+            _ if action.is_some() => names.push(arg),
+            _ => return Err(invalid_input(format!("unknown argument: {}", arg))),
+        }
+    }
+    let load = |name: &str| store.load(name)?.ok_or_else(|| invalid_input(format!("no population named {}", name)));
+    match action.as_deref() {
+        Some("remove") => {
+            for name in names {
+                store.remove(&name)?;
+                println!("Removed {}", name);
+            }
+        }
+        Some("verify") => {
+            let mut bad = 0;
+            for name in names {
+                let mismatches = load(&name)?.verify(sample);
+                for mismatch in &mismatches {
+                    log::event(Level::Error, "population", &mismatch.problem.to_string(), &[("population", name.clone()), ("path", mismatch.path.display().to_string())]);
+                }
+                println!("{}: {}", name, if mismatches.is_empty() { "ok".to_string() } else { format!("{} files differ", mismatches.len()) });
+                bad += mismatches.len();
+            }
+            if bad > 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} files differ from their manifests", bad)));
+            }
+        }
+        Some(_) => {
+            for name in names {
+                let mut manifest = load(&name)?;
+                let report = manifest.age(&aging)?;
+                store.save(&manifest)?;
+                println!(
+                    "{}: appended to {} files, replaced {} ({} rounds so far, {} bytes)",
+                    name,
+                    report.appended,
+                    report.replaced,
+                    manifest.rounds,
+                    manifest.total_bytes()
+                );
+            }
+        }
+        None => {
+            let mut table = Table::new(["Population", "Source", "Files", "Bytes", "Aged", "Directory"]).align(1, Align::Left).align(5, Align::Left);
+            for manifest in store.list()? {
+                let source = match manifest.source {
+                    Source::Generated { .. } => "generated",
+                    Source::Discovered(_) => "discovered",
+                };
+                table.row([
+                    manifest.name.clone(),
+                    source.to_string(),
+                    human::thousands(manifest.entries.len() as u64),
+                    human::thousands(manifest.total_bytes()),
+                    manifest.rounds.to_string(),
+                    manifest.dir.display().to_string(),
+                ]);
+            }
+            print!("{}", table.render());
+        }
+    }
+    Ok(())
+}
+
+/// `io bench snapshot <dir>`: reads a tree that may be changing underneath, retrying files
+/// that change mid-read, and reports which files never settled.
+fn snapshot(mut args: impl Iterator<Item = String>) -> io::Result<()> {
+    let mut config = snapshot::Config::default();
+    let mut root = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--retries" => config.retries = flag_value(&mut args, &arg)?,
+            "--hash" => config.hash = true,
+            _ if root.is_none() && !arg.starts_with("--") => root = Some(PathBuf::from(arg)),
+            _ => return Err(invalid_input(format!("unknown argument: {}", arg))),
+        }
+    }
+    let root = root.ok_or_else(|| invalid_input("io bench snapshot needs a directory".to_string()))?;
+    let start = Instant::now();
+    let snapshot = snapshot::read(&root, &config)?;
+    let elapsed = start.elapsed();
+    println!(
+        "Snapshot of {}: {} files, {} in {} ({} needed retries)",
+        root.display(),
+        human::thousands(snapshot.files.len() as u64),
+        human::bytes(snapshot.bytes()),
+        human::duration(elapsed),
+        snapshot.retried()
+    );
+    for file in snapshot.files.iter().filter(|file| file.attempts > 1).take(MAX_FAILURES_SHOWN) {
+        println!("  [op {}] {}: stable after {} reads", file.op, file.path.display(), file.attempts);
+    }
+    if !snapshot.unstable.is_empty() {
+        println!("{} unstable files:", snapshot.unstable.len());
+        for unstable in &snapshot.unstable {
+            let reason = match unstable.reason {
+                Instability::Changed { attempts } => format!("changed during all {} reads", attempts),
+                Instability::Vanished => "removed before it was read".to_string(),
+                Instability::Appeared => "created after the listing".to_string(),
+            };
+            println!("  [op {}] {}: {}", unstable.op, unstable.path.display(), reason);
+        }
+    }
+    Ok(())
+}
+
+/// `io bench trace <file> [--timing compressed|original|<n>x]`: replays a recorded trace in
+/// a run directory.
+fn trace(mut args: impl Iterator<Item = String>) -> io::Result<()> {
+    let (mut path, mut timing) = (None, Timing::default());
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--timing" => timing = flag_value(&mut args, &arg)?,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(PathBuf::from(arg)),
+            _ => return Err(invalid_input(format!("unknown argument: {}", arg))),
+        }
+    }
+    let path = path.ok_or_else(|| invalid_input("io bench trace needs a trace file".to_string()))?;
+    let trace = Trace::load(&path)?;
+    println!(
+        "Trace {}: {} operations over {} ({} lines skipped)",
+        path.display(),
+        human::thousands(trace.events.len() as u64),
+        human::duration(trace.duration()),
+        human::thousands(trace.skipped as u64)
+    );
+    let dir = bench_dir()?;
+    let prepared = oplog::prepare(&trace, &dir)?;
+    println!("Prepared {} files and directories the trace found in place", human::thousands(prepared as u64));
+    let report = oplog::replay(&trace, &dir, timing)?;
+
+    let mut table = Table::new(["Op", "Count", "Bytes", "Time ms", "Mean us", "Max ms"]).align(0, Align::Left);
+    for stats in &report.stats {
+        table.row([
+            stats.kind.name().to_string(),
+            human::thousands(stats.count),
+            human::bytes(stats.bytes),
+            human::millis(stats.time),
+            human::decimal(stats.time.as_secs_f64() * 1e6 / stats.count as f64, 1),
+            human::millis(stats.max),
+        ]);
+    }
+    print!("{}", table.render());
+    println!("Replayed in {} (recorded {})", human::duration(report.elapsed), human::duration(trace.duration()));
+    if timing != Timing::Compressed {
+        println!("At most {} behind the recorded pace", human::duration(report.behind));
+    }
+    if !report.failures.is_empty() {
+        log::error("replay", format_args!("{} operations failed", report.failures.len()));
+        for failure in report.failures.iter().take(MAX_FAILURES_SHOWN) {
+            let fields = [("event", (failure.event + 1).to_string()), ("kind", failure.kind.name().to_string()), ("path", failure.path.display().to_string())];
+            log::event(Level::Error, "replay", &failure.error.to_string(), &fields);
+        }
+        if report.failures.len() > MAX_FAILURES_SHOWN {
+            log::error("replay", format_args!("... and {} more", report.failures.len() - MAX_FAILURES_SHOWN));
+        }
+    }
+    Ok(())
+}
+
+/// `io clean [--dry-run] [--force] [DIR...]`: removes the run directories that runs which
+/// are no longer running left in the temp directory and in each `DIR` (the `--target`s
+/// they ran on). The fixed-name directories older versions used carry nothing to tell
+/// them from the user's own, so they go only with `--force`.
+pub fn clean(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let (mut dry_run, mut force) = (false, false);
+    let mut parents = vec![mobile::temp_dir()];
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--force" => force = true,
+            flag if flag.starts_with("--") => return Err(invalid_input(format!("unknown io clean flag: {}", flag))),
+            dir => parents.push(PathBuf::from(dir)),
+        }
+    }
+    let mut removed = 0;
+    for parent in &parents {
+        let mut stale = rundir::stale(parent)?;
+        for dir in rundir::legacy(parent)? {
+            match force {
+                true => stale.push(dir),
+                false => println!("Kept {}, named as older versions named run directories (--force removes it)", dir.display()),
+            }
+        }
+        for dir in stale {
+            if dry_run {
+                println!("Would remove {}", dir.display());
+            } else {
+                match fs::remove_dir_all(&dir) {
+                    Ok(()) => println!("Removed {}", dir.display()),
+                    Err(e) => {
+                        log::event(Level::Error, "clean", &format!("failed to remove: {}", e), &[("path", dir.display().to_string())]);
+                        continue;
+                    }
+                }
+            }
+            removed += 1;
+        }
+    }
+    let what = if dry_run { "to remove" } else { "removed" };
+    println!("{} stale run director{} {}", removed, if removed == 1 { "y" } else { "ies" }, what);
+    Ok(())
+}
+
+fn probe_table(probe: &Probe) -> Table {
+    let known = |value: Option<usize>, unit: &str| value.map_or_else(|| "no".to_string(), |value| format!("{} {}", value, unit));
+    let mut table = Table::new(["Property", "Holds"]);
+    table.row(["atomic rename over".to_string(), probe.atomic_rename.to_string()]);
+    table.row(["sparse files".to_string(), probe.sparse_files.to_string()]);
+    table.row(["reflink".to_string(), probe.reflink.to_string()]);
+    table.row(["O_DIRECT alignment".to_string(), known(probe.direct_io_alignment, "bytes")]);
+    table.row(["mmap coherent with write(2)".to_string(), probe.mmap_coherent.to_string()]);
+    table.row(["max file name".to_string(), known(probe.max_name_len, "bytes")]);
+    table
+}
+
+/// `io probe [DIR]`: checks what the filesystem `DIR` (by default the temp directory) is on
+/// actually does, in a scratch directory removed afterwards.
+pub fn probe(mut args: impl Iterator<Item = String>) -> io::Result<()> {
+    let dir = args.next().map_or_else(mobile::temp_dir, PathBuf::from);
+    if let Some(arg) = args.next() {
+        return Err(invalid_input(format!("unexpected io probe argument: {}", arg)));
+    }
+    report_filesystem("Probing", &dir);
+    let probe = Probe::run(&dir)?;
+    print!("{}", probe_table(&probe).render());
+    if probe.adjust(Thresholds::default()) != Thresholds::default() {
+        println!("adaptive_io would not use mmap here");
+    }
+    Ok(())
+}
+
+/// `io selftest [dir]`: checks every backend in the build for conformance on the filesystem
+/// of `dir`, the benchmark directory by default, and prints a matrix of backends against
+/// checks. Fails if any backend failed a check.
+pub fn selftest(mut args: impl Iterator<Item = String>) -> io::Result<()> {
+    let dir = args.next().map_or_else(bench::get_dir, |parent| rundir::run_dir(Path::new(&parent)));
+    if let Some(arg) = args.next() {
+        return Err(invalid_input(format!("unexpected io selftest argument: {}", arg)));
+    }
+    let dir = RunDir::create(dir)?;
+    report_filesystem("Filesystem", &dir);
+    let backends = selftest::Backend::all();
+    println!("Checking {} backends on {} files of each of {}", backends.len(), selftest::FILES, selftest::SIZES.map(|size| human::bytes(size as u64)).join(", "));
+    let results = selftest::run(&dir, &backends)?;
+
+    let mut table = Table::new(["Backend"].into_iter().chain(Check::ALL.map(Check::name)));
+    for conformance in &results {
+        let cells = Check::ALL.map(|check| match conformance.outcome(check) {
+            Outcome::Pass => "ok",
+            Outcome::Fail(_) => "FAIL",
+            Outcome::Skipped(_) => "skip",
+            Outcome::NotApplicable => "-",
+        });
+        table.row([conformance.backend.name()].into_iter().chain(cells.map(str::to_string)));
+    }
+    println!();
+    print!("{}", table.render());
+
+    let mut skipped: Vec<&str> = Vec::new();
+    for conformance in &results {
+        for check in Check::ALL {
+            match conformance.outcome(check) {
+                Outcome::Fail(why) => println!("{} {}: {}", conformance.backend.name(), check.name(), why),
+                Outcome::Skipped(why) if !skipped.contains(&why.as_str()) => skipped.push(why),
+                _ => {}
+            }
+        }
+    }
+    for why in skipped {
+        println!("Skipped: {}", why);
+    }
+    let failed = results.iter().filter(|conformance| conformance.failed()).count();
+    if failed > 0 {
+        return Err(io::Error::other(format!("{} of {} backends failed the self-test", failed, results.len())));
+    }
+    println!("All {} backends passed", results.len());
+    Ok(())
+}
+
+/// `io dedupe --report DIR`: lists the groups of files under `DIR` with the same contents
+/// and how much keeping one of each would free. It only reports; nothing is changed.
+pub fn dedupe(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let mut report = false;
+    let mut root = None;
+    for arg in args {
+        match arg.as_str() {
+            "--report" => report = true,
+            flag if flag.starts_with("--") => return Err(invalid_input(format!("unknown io dedupe flag: {}", flag))),
+            dir if root.is_none() => root = Some(PathBuf::from(dir)),
+            dir => return Err(invalid_input(format!("unexpected io dedupe argument: {}", dir))),
+        }
+    }
+    let (true, Some(root)) = (report, root) else {
+        return Err(invalid_input("usage: io dedupe --report DIR".to_string()));
+    };
+    let start = Instant::now();
+    let groups = dedupe::find_duplicates(&root)?;
+    for group in &groups {
+        println!("{} x {} ({} reclaimable)", human::bytes(group.size), group.copies.len(), human::bytes(group.wasted()));
+        for copy in &group.copies {
+            println!("  {}", copy.paths[0].display());
+            for link in &copy.paths[1..] {
+                println!("    = {} (hard link)", link.display());
+            }
+        }
+    }
+    let files: usize = groups.iter().map(|group| group.copies.len()).sum();
+    println!(
+        "{} groups of duplicates, {} files, {} reclaimable, found in {}",
+        human::thousands(groups.len() as u64),
+        human::thousands(files as u64),
+        human::bytes(groups.iter().map(dedupe::Group::wasted).sum()),
+        human::duration(start.elapsed())
+    );
+    Ok(())
+}
+
+/// Workload sizes `io calibrate` runs, and with `--quick`.
+const CALIBRATION_SIZES: [usize; 4] = [100, 4 * 1024, 64 * 1024, 1024 * 1024];
+const QUICK_CALIBRATION_SIZES: [usize; 3] = [100, 4 * 1024, 64 * 1024];
+
+/// Files per size `io calibrate` runs, and with `--quick`: this many, fewer for large sizes
+/// so no run writes more than the byte budget.
+const CALIBRATION_FILES: (usize, u64) = (10_000, 256 * 1024 * 1024);
+const QUICK_CALIBRATION_FILES: (usize, u64) = (1_000, 16 * 1024 * 1024);
+
+/// `io calibrate [--quick]`: runs every strategy at a few sizes and caches the fastest per
+/// phase for the storage the benchmark directory is on, for `io::calibrated_backend`.
+/// The other `io bench` options apply, except the workload shape.
+pub fn calibrate(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let (quick, rest): (Vec<String>, Vec<String>) = args.partition(|arg| arg == "--quick");
+    let mut args = parse_bench_args(rest.into_iter())?;
+    let (sizes, (max_files, budget)) = if !quick.is_empty() { (&QUICK_CALIBRATION_SIZES[..], QUICK_CALIBRATION_FILES) } else { (&CALIBRATION_SIZES[..], CALIBRATION_FILES) };
+    let mut calibration: Option<Calibration> = None;
+    for &size in sizes {
+        let files = ((budget / size as u64) as usize).clamp(1, max_files);
+        (args.files, args.sizes) = (vec![files], vec![size]);
+        let results = run_strategies(&mut args)?;
+        let recommendation = Recommendation::from_results(&results).ok_or_else(|| io::Error::other("calibrating needs at least two strategies"))?;
+        println!("\n{}\n", recommendation.headline());
+        let filesystem = results.filesystem.as_ref().ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "can't tell what storage the benchmark directory is on"))?;
+        calibration
+            .get_or_insert_with(|| Calibration::new(calibration::storage_key(filesystem), results.threads))
+            .add(SizeChoice { size, files, strategies: recommendation.strategies() });
+    }
+    let Some(calibration) = calibration else {
+        return Ok(());
+    };
+    let path = calibration.store()?;
+    println!("Saved the calibration for {} to {}", calibration.key, path.display());
+    Ok(())
+}
+
+/// Parses the arguments of a benchmarking command and, with `--output`, starts the output
+/// with the layout header.
+fn parse_run_args(args: impl Iterator<Item = String>) -> io::Result<BenchArgs> {
+    let args = parse_bench_args(args)?;
+    if args.output.is_some() {
+        println!("{}", human::header());
+    }
+    Ok(args)
+}
+
+pub fn bench(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let mut args = args.peekable();
+    match args.peek().map(String::as_str) {
+        Some("sweep") => {
+            args.next();
+            let args: Vec<String> = args.collect();
+            let parsed = parse_run_args(args.iter().cloned())?;
+            sweep(parsed, start_checkpoint("sweep", args))
+        }
+        Some("compare") => {
+            args.next();
+            compare(parse_run_args(args)?)
+        }
+        Some("soak") => {
+            args.next();
+            let args: Vec<String> = args.collect();
+            let parsed = parse_run_args(args.iter().cloned())?;
+            run_soak(parsed, start_checkpoint("soak", args))
+        }
+        Some("--resume") => {
+            args.next();
+            let run_id = args.next().ok_or_else(|| invalid_input("--resume needs a run ID".to_string()))?;
+            if let Some(arg) = args.next() {
+                return Err(invalid_input(format!("--resume takes the arguments the run started with, not {}", arg)));
+            }
+            resume(&run_id)
+        }
+        Some("report") => {
+            args.next();
+            let mut paths = Vec::new();
+            while let Some(path) = args.next_if(|arg| !arg.starts_with("--")) {
+                paths.push(PathBuf::from(path));
+            }
+            compare_files(&paths, parse_bench_args(args)?)
+        }
+        Some("replay") => {
+            args.next();
+            let path = args.next().filter(|arg| !arg.starts_with("--")).ok_or_else(|| invalid_input("io bench replay needs a golden file".to_string()))?;
+            replay(PathBuf::from(path), parse_run_args(args)?)
+        }
+        Some("worker") => {
+            args.next();
+            processes::worker(args)
+        }
+        Some("populations") => {
+            args.next();
+            populations(args)
+        }
+        Some("metadata") => {
+            args.next();
+            metadata(parse_run_args(args)?)
+        }
+        Some("links") => {
+            args.next();
+            links(parse_run_args(args)?)
+        }
+        Some("cas") => {
+            args.next();
+            cas(parse_run_args(args)?)
+        }
+        Some("archive") => {
+            args.next();
+            archive(parse_run_args(args)?)
+        }
+        Some("compress") => {
+            args.next();
+            compress(parse_run_args(args)?)
+        }
+        Some("hash") => {
+            args.next();
+            hash(parse_run_args(args)?)
+        }
+        Some("encrypt") => {
+            args.next();
+            encrypt(parse_run_args(args)?)
+        }
+        Some("sparse") => {
+            args.next();
+            sparse(parse_run_args(args)?)
+        }
+        Some("append") => {
+            args.next();
+            append(parse_run_args(args)?)
+        }
+        Some("pipeline") => {
+            args.next();
+            pipeline(parse_run_args(args)?)
+        }
+        Some("handles") => {
+            args.next();
+            handles(parse_run_args(args)?)
+        }
+        Some("read") => {
+            args.next();
+            read(parse_run_args(args)?)
+        }
+        Some("update") => {
+            args.next();
+            update(parse_run_args(args)?)
+        }
+        Some("crash") => {
+            args.next();
+            crash(parse_run_args(args)?)
+        }
+        Some("crash-worker") => {
+            args.next();
+            crash_worker(args)
+        }
+        Some("watch") => {
+            args.next();
+            watch(parse_run_args(args)?)
+        }
+        Some("lock") => {
+            args.next();
+            lock(parse_run_args(args)?)
+        }
+        Some("xattr") => {
+            args.next();
+            xattr(parse_run_args(args)?)
+        }
+        Some("permissions") => {
+            args.next();
+            permissions(parse_run_args(args)?)
+        }
+        Some("stash") => {
+            args.next();
+            stash(parse_run_args(args)?)
+        }
+        Some("mirror") => {
+            args.next();
+            mirror(parse_run_args(args)?)
+        }
+        Some("copy") => {
+            args.next();
+            copy(parse_run_args(args)?)
+        }
+        Some("mkdir") => {
+            args.next();
+            mkdir(parse_run_args(args)?)
+        }
+        Some("commit") => {
+            args.next();
+            commit(parse_run_args(args)?)
+        }
+        #[cfg(feature = "s3")]
+        Some("s3") => {
+            args.next();
+            s3(parse_run_args(args)?)
+        }
+        Some("scan") => {
+            args.next();
+            scan(parse_run_args(args)?)
+        }
+        Some("remove") => {
+            args.next();
+            remove(parse_run_args(args)?)
+        }
+        Some("open") => {
+            args.next();
+            open(parse_run_args(args)?)
+        }
+        Some("coalesce") => {
+            args.next();
+            coalesce(parse_run_args(args)?)
+        }
+        Some("ranged") => {
+            args.next();
+            ranged_writes(parse_run_args(args)?)
+        }
+        Some("order") => {
+            args.next();
+            let root = args.next_if(|arg| !arg.starts_with("--")).map(PathBuf::from);
+            read_orders(root, parse_run_args(args)?)
+        }
+        Some("readonly") => {
+            args.next();
+            let root = args.next().filter(|arg| !arg.starts_with("--")).ok_or_else(|| invalid_input("io bench readonly needs a directory".to_string()))?;
+            readonly(PathBuf::from(root), parse_run_args(args)?)
+        }
+        #[cfg(target_os = "linux")]
+        Some("linked") => {
+            args.next();
+            linked(parse_run_args(args)?)
+        }
+        #[cfg(target_os = "linux")]
+        Some("uring") => {
+            args.next();
+            uring(parse_run_args(args)?)
+        }
+        Some("rename") => {
+            args.next();
+            rename(parse_run_args(args)?)
+        }
+        Some("snapshot") => {
+            args.next();
+            snapshot(args)
+        }
+        Some("trace") => {
+            args.next();
+            trace(args)
+        }
+        Some("workload") => {
+            args.next();
+            let path = args.next().ok_or_else(|| invalid_input("io bench workload needs a workload file".to_string()))?;
+            run_workload(Path::new(&path), parse_run_args(args)?)
+        }
+        _ => run(parse_run_args(args)?),
+    }
+}
@@ -0,0 +1,268 @@
+//! Writing many small files grouped by directory. A batch in arbitrary order, as a build
+//! tool's outputs arrive, has every worker hopping between directories: each open walks
+//! the path again, takes a different directory's lock, and new inodes are allocated
+//! wherever each directory's last one happened to go. [`write_grouped`] instead opens
+//! each directory once, puts each directory's files together, existing ones by inode
+//! number and new ones after them by name, and hands the workers runs of [`RUN`] files of
+//! one directory at a time. On disks, and on network filesystems where every lookup is a
+//! round trip, that locality can outweigh the lost freedom to balance file by file; on
+//! SSDs it often doesn't, which `io bench coalesce` measures.
+//!
+//! [`Coalescer`] queues writes as they come and issues them this way once enough are
+//! pending.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::mem::MaybeUninit;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options, Scheduling};
+use crate::dirs::{Access, Dirs};
+#[cfg(feature = "bench")]
+use crate::names;
+#[cfg(feature = "bench")]
+use crate::order;
+#[cfg(feature = "bench")]
+use crate::population::Rng;
+use crate::retry::RetryPolicy;
+
+/// Files of one directory a worker writes in a row before taking the next run.
+pub const RUN: usize = 64;
+
+/// The inode of `path`, looked up from its directory in `dirs`, or `None` if it doesn't exist.
+fn inode(dirs: &Dirs, path: &Path) -> io::Result<Option<u64>> {
+    let (dir, name) = dirs.entry(path)?;
+    let mut stat = MaybeUninit::<libc::stat>::uninit();
+    if unsafe { libc::fstatat(dir, name.as_ptr(), stat.as_mut_ptr(), libc::AT_SYMLINK_NOFOLLOW) } == 0 {
+        return Ok(Some(unsafe { stat.assume_init() }.st_ino));
+    }
+    match io::Error::last_os_error() {
+        e if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        e => Err(e),
+    }
+}
+
+/// The indices of `paths` in the order [`write_grouped`] writes them: directories in the
+/// order `paths` first reaches them, and within each, files that exist by inode number,
+/// then new ones by name. `dirs` must hold every path's directory.
+pub fn arrange(dirs: &Dirs, paths: &[PathBuf]) -> io::Result<Vec<usize>> {
+    let mut numbers: HashMap<&Path, usize> = HashMap::new();
+    let mut keys = Vec::with_capacity(paths.len());
+    for path in paths {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let next = numbers.len();
+        let number = *numbers.entry(dir).or_insert(next);
+        // Existing files first, by inode; new ones have none and go after them by name.
+        let inode = inode(dirs, path)?;
+        keys.push((number, inode.is_none(), inode, path.file_name()));
+    }
+    let mut indices: Vec<usize> = (0..paths.len()).collect();
+    indices.sort_by(|&a, &b| keys[a].cmp(&keys[b]));
+    Ok(indices)
+}
+
+/// Runs of at most [`RUN`] indices of `arranged`, none spanning two directories.
+fn runs<'a>(arranged: &'a [usize], paths: &[PathBuf]) -> Vec<&'a [usize]> {
+    arranged.chunk_by(|&a, &b| paths[a].parent() == paths[b].parent()).flat_map(|group| group.chunks(RUN)).collect()
+}
+
+/// Writes each `(path, contents)` pair, creating or truncating the file, grouped by
+/// directory as the module describes, retrying each file on transient errors as `policy`
+/// says. Every directory must exist. Fails on the first file that still can't be written.
+pub fn write_grouped<P, B>(files: &[(P, B)], policy: &RetryPolicy) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+    B: AsRef<[u8]> + Sync,
+{
+    let paths: Vec<PathBuf> = files.iter().map(|(path, _)| path.as_ref().to_path_buf()).collect();
+    let dirs = Dirs::open(&paths)?;
+    let arranged = arrange(&dirs, &paths)?;
+    let write_run = |run: &&[usize]| {
+        run.iter().try_for_each(|&i| {
+            policy
+                .run(|| dirs.open_file(&paths[i], Access::Create)?.write_all(files[i].1.as_ref()))
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", paths[i].display(), e)))
+        })
+    };
+    let runs = runs(&arranged, &paths);
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        runs.par_iter().try_for_each(write_run)
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        runs.iter().try_for_each(write_run)
+    }
+}
+
+/// Small writes held back until enough are pending to write them together with
+/// [`write_grouped`]. Writes are only on disk once [`Coalescer::flush`] returns, whether
+/// called or triggered by a write; dropping a coalescer with writes pending loses them.
+#[derive(Debug)]
+pub struct Coalescer {
+    pending: Vec<(PathBuf, Vec<u8>)>,
+    /// Where each pending path is in `pending`, so a later write replaces an earlier one.
+    positions: HashMap<PathBuf, usize>,
+    bytes: usize,
+    limit: usize,
+    policy: RetryPolicy,
+}
+
+impl Coalescer {
+    /// A coalescer that flushes once `limit` bytes are pending.
+    pub fn new(limit: usize, policy: RetryPolicy) -> Coalescer {
+        Coalescer { pending: Vec::new(), positions: HashMap::new(), bytes: 0, limit, policy }
+    }
+
+    /// Queues `contents` for `path`, replacing what was queued for it before, and flushes
+    /// if that brings the pending bytes to the limit.
+    pub fn write(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) -> io::Result<()> {
+        let (path, contents) = (path.into(), contents.into());
+        self.bytes += contents.len();
+        match self.positions.get(&path) {
+            Some(&at) => self.bytes -= std::mem::replace(&mut self.pending[at].1, contents).len(),
+            None => {
+                self.positions.insert(path.clone(), self.pending.len());
+                self.pending.push((path, contents));
+            }
+        }
+        if self.bytes >= self.limit { self.flush() } else { Ok(()) }
+    }
+
+    /// Files queued and not yet written.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Writes everything pending. On error nothing stays queued: some files may have been
+    /// written and the rest not.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        self.positions.clear();
+        self.bytes = 0;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        write_grouped(&pending, &self.policy)
+    }
+}
+
+/// Directories the benchmark spreads the workload's files over.
+#[cfg(feature = "bench")]
+pub const DIRECTORIES: usize = 64;
+
+/// How the benchmark issues a batch's writes.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// In the batch's own order, by full path, balanced file by file as rayon steals.
+    Arbitrary,
+    /// As [`write_grouped`] does: arranged, opened by name from each directory's
+    /// descriptor, and dealt to the workers in runs of [`RUN`].
+    Grouped,
+}
+
+#[cfg(feature = "bench")]
+impl Method {
+    pub const ALL: [Method; 2] = [Method::Arbitrary, Method::Grouped];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Arbitrary => "arbitrary",
+            Method::Grouped => "grouped",
+        }
+    }
+}
+
+/// One method's create and overwrite times.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy)]
+pub struct MethodRun {
+    pub method: Method,
+    pub create: Duration,
+    pub update: Duration,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Default)]
+pub struct CoalesceResult {
+    /// In [`Method::ALL`] order.
+    pub runs: Vec<MethodRun>,
+    pub failures: Vec<Failure>,
+}
+
+/// Spreads the workload's files over [`DIRECTORIES`] directories of `dir_path` and
+/// shuffles them, as a batch arrives, then with each [`Method`] creates them, overwrites
+/// them and deletes them, [`order::ROUNDS`] times with the methods taking turns; each
+/// keeps its fastest create and overwrite. Arranging the grouped batch counts toward its
+/// times.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<CoalesceResult> {
+    let dirs: Vec<PathBuf> = (0..DIRECTORIES.min(options.workload.files.max(1))).map(|i| dir_path.join(format!("dir{}", i))).collect();
+    for dir in &dirs {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut paths = names::paths(&dirs, options.workload.files, options.workload.names);
+    let mut rng = Rng(order::SEED);
+    for i in (1..paths.len()).rev() {
+        paths.swap(i, rng.below(i + 1));
+    }
+    // The content follows the file, not its place in the arranged order.
+    let indices: HashMap<&Path, usize> = paths.iter().enumerate().map(|(i, path)| (path.as_path(), i)).collect();
+    let grouped = Options { scheduling: Scheduling::Static, chunk: Some(RUN), ..options.with_workload(options.workload.clone()) };
+    let mut result = CoalesceResult::default();
+    options.failures.take("");
+
+    for round in 0..order::ROUNDS {
+        for method in Method::ALL {
+            let mut times = [Duration::ZERO; 2];
+            for (phase, time) in ["create", "update"].into_iter().zip(&mut times) {
+                options.progress.set_stage(format!("Coalesce {} {} ({}/{})", method.name(), phase, round + 1, order::ROUNDS));
+                let update = phase == "update";
+                let start = Instant::now();
+                match method {
+                    Method::Arbitrary => {
+                        options.op_scope.set(format!("coalesce/{}/{}", method.name(), phase));
+                        bench::each_indexed(&paths, options, |index, path| {
+                            bench::with_content(options, index, update, |content| crate::dirs::open_file(None, path, Access::Create)?.write_all(content))
+                        })?;
+                    }
+                    Method::Grouped => {
+                        grouped.op_scope.set(format!("coalesce/{}/{}", method.name(), phase));
+                        let opened = Dirs::open(&paths)?;
+                        let arranged: Vec<PathBuf> = arrange(&opened, &paths)?.into_iter().map(|i| paths[i].clone()).collect();
+                        bench::each_indexed(&arranged, &grouped, |_, path| {
+                            bench::with_content(options, indices[path.as_path()], update, |content| opened.open_file(path, Access::Create)?.write_all(content))
+                        })?;
+                    }
+                }
+                *time = start.elapsed();
+                // Every round fails on the same files; the first round's failures say it all.
+                let failures = [options.failures.take(phase), grouped.failures.take(phase)];
+                if round == 0 {
+                    result.failures.extend(failures.into_iter().flatten());
+                }
+            }
+            match result.runs.iter_mut().find(|fastest| fastest.method == method) {
+                Some(fastest) => {
+                    fastest.create = fastest.create.min(times[0]);
+                    fastest.update = fastest.update.min(times[1]);
+                }
+                None => result.runs.push(MethodRun { method, create: times[0], update: times[1] }),
+            }
+            let mut failures = Vec::new();
+            bench::phase(options, format!("Coalesce {} delete", method.name()), format!("coalesce/{}/delete", method.name()), "delete", &mut failures, || bench::delete_files(&paths, options))?;
+            if round == 0 {
+                result.failures.extend(failures);
+            }
+        }
+    }
+    for dir in &dirs {
+        std::fs::remove_dir(dir)?;
+    }
+    Ok(result)
+}
@@ -0,0 +1,155 @@
+//! Compression on the write path: content compressed before it is written and
+//! decompressed after it is read, to see when spending CPU to write fewer bytes pays off on
+//! a given device. A slow disk or a network filesystem rewards it; a fast NVMe drive
+//! usually doesn't, least of all with gzip.
+//!
+//! Every [`Codec`] writes a self-describing stream that its command-line tool reads: gzip
+//! through [`crate::deflate`], LZ4 frames through [`crate::lz4`], and zstd frames through
+//! the system `libzstd` with the `zstd` feature.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+#[cfg(feature = "bench")]
+use std::fs;
+#[cfg(feature = "bench")]
+use std::path::Path;
+#[cfg(feature = "bench")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+#[cfg(feature = "bench")]
+use crate::cache;
+use crate::{deflate, lz4};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// Written as is, the baseline.
+    Uncompressed,
+    Lz4,
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Codec {
+    /// Every codec this build has, fastest first.
+    pub const ALL: &[Codec] = &[
+        Codec::Uncompressed,
+        Codec::Lz4,
+        #[cfg(feature = "zstd")]
+        Codec::Zstd,
+        Codec::Gzip,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Uncompressed => "none",
+            Codec::Lz4 => "lz4",
+            Codec::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => "zstd",
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(match self {
+            Codec::Uncompressed => data.to_vec(),
+            Codec::Lz4 => lz4::compress(data),
+            Codec::Gzip => deflate::gzip(data),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => crate::zstd::compress(data, crate::zstd::DEFAULT_LEVEL)?,
+        })
+    }
+
+    pub fn decompress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::Uncompressed => Ok(data.to_vec()),
+            Codec::Lz4 => lz4::decompress(data),
+            Codec::Gzip => deflate::gunzip(data),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => crate::zstd::decompress(data),
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "zstd" if !cfg!(feature = "zstd") => Err("zstd needs the zstd feature".to_string()),
+            _ => Codec::ALL.iter().copied().find(|codec| codec.name() == s).ok_or_else(|| format!("expected one of {}", Codec::ALL.iter().map(|codec| codec.name()).collect::<Vec<_>>().join(", "))),
+        }
+    }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct CodecResult {
+    pub codec: Codec,
+    /// Compressing and writing every file.
+    pub create: Duration,
+    /// Reading and decompressing every file.
+    pub read: Duration,
+    /// What the files hold on disk, compressed.
+    pub bytes: u64,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct CompressResult {
+    /// The workload's bytes, uncompressed.
+    pub source_bytes: u64,
+    pub codecs: Vec<CodecResult>,
+    pub failures: Vec<Failure>,
+}
+
+/// For each of `codecs`, times creating the configured workload in `dir_path` with every
+/// file compressed, then reading it back and decompressing it, evicting the files from the
+/// page cache in between when `options.cold_read` is set. Each codec's files are removed
+/// before the next.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, codecs: &[Codec], options: &Options) -> io::Result<CompressResult> {
+    let files = options.workload.files;
+    let mut result = CompressResult { source_bytes: files as u64 * options.workload.size() as u64, ..CompressResult::default() };
+    options.failures.take("");
+    for &codec in codecs {
+        let dir = dir_path.join(codec.name());
+        fs::create_dir_all(&dir)?;
+        let paths = bench::file_paths(&dir, files);
+
+        let bytes = AtomicU64::new(0);
+        let start = Instant::now();
+        bench::phase(options, format!("Compress {} create", codec), format!("compress/{}/create", codec), "create", &mut result.failures, || {
+            bench::each_indexed(&paths, options, |index, path| {
+                let compressed = bench::with_content(options, index, false, |content| codec.compress(content))?;
+                bytes.fetch_add(compressed.len() as u64, Ordering::Relaxed);
+                fs::write(path, compressed)
+            })
+        })?;
+        let create = start.elapsed();
+
+        if options.cold_read {
+            cache::evict_from_cache(&paths)?;
+        }
+        let start = Instant::now();
+        bench::phase(options, format!("Compress {} read", codec), format!("compress/{}/read", codec), "read", &mut result.failures, || {
+            bench::each_indexed(&paths, options, |index, path| bench::read_done(options, index, path, &codec.decompress(&fs::read(path)?)?))
+        })?;
+        let read = start.elapsed();
+
+        result.codecs.push(CodecResult { codec, create, read, bytes: bytes.into_inner() });
+        fs::remove_dir_all(&dir)?;
+    }
+    Ok(result)
+}
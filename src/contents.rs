@@ -0,0 +1,303 @@
+//! Reading, writing and deleting whole files in bulk, reading without copying into heap
+//! buffers where the files can be mapped. [`crate::plan`] previews writes and deletes.
+//! [`read_many_stream`] reads a list too long to hold in memory at once.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+
+use crate::retry::RetryPolicy;
+
+/// The contents of one file: a read-only mapping where possible, otherwise an owned copy.
+/// Borrow the bytes through `Deref`/`AsRef`; they live as long as this value.
+///
+/// A mapping reflects later writes to the file, and truncating the file while it is mapped
+/// makes accesses past the new end fault, so only map files nothing else is rewriting.
+#[derive(Debug)]
+pub enum FileBytes {
+    #[cfg(feature = "mmap")]
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl FileBytes {
+    pub fn is_mapped(&self) -> bool {
+        match self {
+            #[cfg(feature = "mmap")]
+            FileBytes::Mapped(_) => true,
+            FileBytes::Owned(_) => false,
+        }
+    }
+}
+
+impl Deref for FileBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "mmap")]
+            FileBytes::Mapped(map) => map,
+            FileBytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+impl AsRef<[u8]> for FileBytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+fn read_owned(mut file: File) -> io::Result<FileBytes> {
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    Ok(FileBytes::Owned(bytes))
+}
+
+/// Maps `path` read-only, copying it instead when it is empty, not a regular file (pipes,
+/// procfs entries report no size), fails to map, the `mmap` feature is disabled, or on WASI.
+pub fn map_file(path: &Path) -> io::Result<FileBytes> {
+    let file = File::open(path)?;
+    // WASI has no mappings; memmap2 would only fail there.
+    #[cfg(all(feature = "mmap", not(target_os = "wasi")))]
+    {
+        let metadata = file.metadata()?;
+        if metadata.is_file()
+            && metadata.len() > 0
+            && let Ok(map) = unsafe { Mmap::map(&file) }
+        {
+            return Ok(FileBytes::Mapped(map));
+        }
+    }
+    read_owned(file)
+}
+
+/// Maps every file in `paths`, in parallel with the `rayon` feature, returning the contents
+/// in the same order. Fails on the first file that cannot be opened or read.
+pub fn read_many_mmap<P>(paths: &[P]) -> io::Result<Vec<FileBytes>>
+where
+    P: AsRef<Path> + Sync,
+{
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        paths.par_iter().map(|path| map_file(path.as_ref())).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        paths.iter().map(|path| map_file(path.as_ref())).collect()
+    }
+}
+
+/// What a caught panic said, if it said it with a string.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string())
+}
+
+/// Runs `f` on every item, in parallel with the `rayon` feature, retrying each as `policy`
+/// says. Errors name the path they happened on. A panic in `f` is that item's error, not
+/// retried, rather than unwinding out of the thread pool.
+pub(crate) fn each_retried<T, R, F>(items: &[T], policy: &RetryPolicy, path: impl Fn(&T) -> &Path + Sync, f: F) -> io::Result<Vec<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> io::Result<R> + Sync,
+{
+    let run = |item: &T| {
+        panic::catch_unwind(AssertUnwindSafe(|| policy.run(|| f(item))))
+            .unwrap_or_else(|payload| Err(io::Error::other(format!("panicked: {}", panic_message(&*payload)))))
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path(item).display(), e)))
+    };
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        items.par_iter().map(run).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        items.iter().map(run).collect()
+    }
+}
+
+/// Reads every file in `paths` into memory, returning the contents in the same order. Each
+/// file is retried on transient errors as `policy` says; the batch fails on the first file
+/// that still can't be read.
+pub fn read_many<P>(paths: &[P], policy: &RetryPolicy) -> io::Result<Vec<Vec<u8>>>
+where
+    P: AsRef<Path> + Sync,
+{
+    each_retried(paths, policy, |path| path.as_ref(), |path| fs::read(path))
+}
+
+/// Writes each `(path, contents)` pair, creating or truncating the file, retrying each on
+/// transient errors as `policy` says. Fails on the first file that still can't be written.
+pub fn write_many<P, B>(files: &[(P, B)], policy: &RetryPolicy) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+    B: AsRef<[u8]> + Sync,
+{
+    each_retried(files, policy, |(path, _)| path.as_ref(), |(path, contents)| fs::write(path, contents)).map(drop)
+}
+
+/// Deletes every file in `paths`, retrying each on transient errors as `policy` says. Fails
+/// on the first file that still can't be deleted, by which time others may be gone;
+/// [`crate::plan::plan_delete`] checks the list first, and [`crate::trash::Trash`] deletes
+/// so it can be undone.
+pub fn delete_many<P>(paths: &[P], policy: &RetryPolicy) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+{
+    each_retried(paths, policy, |path| path.as_ref(), |path| fs::remove_file(path)).map(drop)
+}
+
+/// The order a bulk read hands back its files in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    /// The order of the paths, a file that finished early waiting for those before it.
+    #[default]
+    Input,
+    /// As each read finishes, which keeps the workers busiest.
+    Completion,
+}
+
+/// What the readers of a [`ReadStream`] share with it.
+#[derive(Debug, Default)]
+struct Window {
+    /// Files a reader has taken, and files the stream has handed out.
+    started: usize,
+    yielded: usize,
+    stopped: bool,
+}
+
+/// Files read by [`read_many_stream`], as an iterator of each path and its contents.
+#[derive(Debug)]
+pub struct ReadStream {
+    paths: Arc<Vec<PathBuf>>,
+    order: Order,
+    window: Arc<(Mutex<Window>, Condvar)>,
+    results: Receiver<(usize, io::Result<Vec<u8>>)>,
+    /// Files read ahead of their turn, in input order.
+    early: HashMap<usize, io::Result<Vec<u8>>>,
+    yielded: usize,
+    readers: Vec<JoinHandle<()>>,
+    /// Where no thread could be started, as on WASI, the consumer reads each file itself
+    /// as it advances, with this policy.
+    inline: Option<RetryPolicy>,
+}
+
+/// Reads every file in `paths` on background threads and hands each back, with its path,
+/// in `order` as the iterator is advanced. At most `in_flight` files (at least one) are
+/// being read or held unyielded at once, however long the list, so memory stays bounded
+/// by the largest `in_flight` files; readers wait while the consumer catches up. Each file
+/// is retried on transient errors as `policy` says, and a file that still fails is handed
+/// back as its error without stopping the others. Dropping the stream stops the readers
+/// after the files they are on. Where threads can't be started, as on WASI, each file is
+/// read as the iterator reaches it instead. For async code,
+/// `crate::stream::read_many_stream` is the same as a `Stream`.
+pub fn read_many_stream<P>(paths: &[P], in_flight: usize, order: Order, policy: &RetryPolicy) -> ReadStream
+where
+    P: AsRef<Path>,
+{
+    let in_flight = in_flight.max(1);
+    let paths: Arc<Vec<PathBuf>> = Arc::new(paths.iter().map(|path| path.as_ref().to_path_buf()).collect());
+    let window = Arc::new((Mutex::new(Window::default()), Condvar::new()));
+    let (sender, results) = mpsc::channel();
+    let readers = thread::available_parallelism().map_or(1, |n| n.get()).min(in_flight).min(paths.len());
+    let readers: Vec<JoinHandle<()>> = (0..readers)
+        .map_while(|_| {
+            let (paths, window, sender, policy) = (paths.clone(), window.clone(), sender.clone(), policy.clone());
+            thread::Builder::new().spawn(move || {
+                loop {
+                    let index = {
+                        let (lock, ready) = &*window;
+                        let mut state = lock.lock().unwrap_or_else(|e| e.into_inner());
+                        while !state.stopped && state.started < paths.len() && state.started - state.yielded >= in_flight {
+                            state = ready.wait(state).unwrap_or_else(|e| e.into_inner());
+                        }
+                        if state.stopped || state.started == paths.len() {
+                            return;
+                        }
+                        state.started += 1;
+                        state.started - 1
+                    };
+                    let read = policy.run(|| fs::read(&paths[index]));
+                    if sender.send((index, read)).is_err() {
+                        return;
+                    }
+                }
+            })
+            .ok()
+        })
+        .collect();
+    let inline = readers.is_empty().then(|| policy.clone());
+    ReadStream { paths, order, window, results, early: HashMap::new(), yielded: 0, readers, inline }
+}
+
+impl ReadStream {
+    /// Files not handed out yet.
+    pub fn remaining(&self) -> usize {
+        self.paths.len() - self.yielded
+    }
+}
+
+impl Iterator for ReadStream {
+    type Item = (PathBuf, io::Result<Vec<u8>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.yielded == self.paths.len() {
+            return None;
+        }
+        if let Some(policy) = &self.inline {
+            let path = self.paths[self.yielded].clone();
+            self.yielded += 1;
+            let read = policy.run(|| fs::read(&path));
+            return Some((path, read));
+        }
+        let (index, read) = match self.order {
+            Order::Completion => self.results.recv().ok()?,
+            Order::Input => loop {
+                if let Some(read) = self.early.remove(&self.yielded) {
+                    break (self.yielded, read);
+                }
+                let (index, read) = self.results.recv().ok()?;
+                self.early.insert(index, read);
+            },
+        };
+        self.yielded += 1;
+        let (lock, ready) = &*self.window;
+        lock.lock().unwrap_or_else(|e| e.into_inner()).yielded += 1;
+        ready.notify_one();
+        Some((self.paths[index].clone(), read))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining(), Some(self.remaining()))
+    }
+}
+
+impl ExactSizeIterator for ReadStream {}
+
+impl Drop for ReadStream {
+    fn drop(&mut self) {
+        let (lock, ready) = &*self.window;
+        lock.lock().unwrap_or_else(|e| e.into_inner()).stopped = true;
+        ready.notify_all();
+        for reader in self.readers.drain(..) {
+            let _ = reader.join();
+        }
+    }
+}
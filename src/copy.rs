@@ -0,0 +1,248 @@
+//! Copying a directory tree in parallel, as `cp -r` (or with [`Preserve::ALL`], `cp -a`)
+//! would but without its one-file-at-a-time loop. [`copy_tree`] creates the directories a
+//! level at a time, each level in parallel, then copies the files in parallel with the
+//! `rayon` feature and recreates symlinks as symlinks.
+//!
+//! Each file is cloned by reflink where the filesystem supports it, which shares its
+//! extents instead of copying bytes; otherwise its bytes are copied by `std::io::copy`,
+//! which on Linux moves them inside the kernel with `copy_file_range` (itself a reflink or
+//! server-side copy on filesystems that offer one). The first file tells which applies.
+
+use std::fs::{self, File, FileTimes, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "bench")]
+use std::process::Command;
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+use crate::cas::{self, Checkout};
+use crate::contents;
+use crate::links;
+use crate::retry::RetryPolicy;
+use crate::stash::{self, Listing, Report};
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+#[cfg(feature = "bench")]
+use crate::names;
+
+/// What [`copy_tree`] carries over besides contents. Without any, new files and
+/// directories get the modes and times anything newly created gets, as with `cp -r`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Preserve {
+    /// Permission bits.
+    pub mode: bool,
+    /// Access and modification times.
+    pub times: bool,
+    /// Extended attributes; on Linux only those in the `user.` namespace, the others
+    /// needing privileges or belonging to the filesystem. They need Unix and the `libc`
+    /// feature.
+    pub xattrs: bool,
+}
+
+impl Preserve {
+    pub const NONE: Preserve = Preserve { mode: false, times: false, xattrs: false };
+    pub const ALL: Preserve = Preserve { mode: true, times: true, xattrs: true };
+}
+
+/// Copies the tree at `src` to `dst`, which must not exist or be an empty directory,
+/// carrying over what `preserve` says. Fails on the first entry that still can't be
+/// copied after retrying, leaving what was copied. The report's method is
+/// [`Checkout::Reflink`] or [`Checkout::Copy`].
+pub fn copy_tree(src: &Path, dst: &Path, preserve: Preserve) -> io::Result<Report> {
+    #[cfg(not(all(unix, feature = "libc")))]
+    if preserve.xattrs {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "copying extended attributes needs Unix and the libc feature"));
+    }
+    let listing = Listing::of(src)?;
+    stash::empty_dir(dst)?;
+    let policy = RetryPolicy::default();
+
+    // A level's parents all exist once the level above is done.
+    let mut levels: Vec<Vec<&PathBuf>> = Vec::new();
+    for dir in &listing.dirs {
+        let depth = dir.components().count();
+        levels.resize_with(levels.len().max(depth), Vec::new);
+        levels[depth - 1].push(dir);
+    }
+    for level in &levels {
+        contents::each_retried(level, &policy, |dir| dir.as_path(), |dir| fs::create_dir(dst.join(dir)))?;
+    }
+
+    let copy = |method: Checkout, (path, _): &(PathBuf, u64)| copy_file(&src.join(path), &dst.join(path), method, preserve);
+    // Cloning the first file tells whether reflinks work here.
+    let (method, rest) = match listing.files.split_first() {
+        None => (Checkout::Copy, &listing.files[..]),
+        Some(((path, _), rest)) => match cas::reflink(&src.join(path), &dst.join(path)) {
+            Ok(()) => {
+                preserve_metadata(&src.join(path), &dst.join(path), &fs::metadata(src.join(path))?, preserve)?;
+                (Checkout::Reflink, rest)
+            }
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => (Checkout::Copy, &listing.files[..]),
+            Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {}", src.join(path).display(), e))),
+        },
+    };
+    contents::each_retried(rest, &policy, |(path, _)| path, |file| copy(method, file))?;
+    for (path, target) in &listing.symlinks {
+        links::symlink(target, &dst.join(path))?;
+    }
+    // Deepest first, since creating entries in a directory changes its times and a
+    // read-only mode would stop them being created.
+    for dir in listing.dirs.iter().rev().map(|dir| dir.as_path()).chain([Path::new("")]) {
+        preserve_metadata(&src.join(dir), &dst.join(dir), &fs::metadata(src.join(dir))?, preserve)?;
+    }
+    Ok(Report { method, files: listing.files.len(), bytes: listing.files.iter().map(|(_, size)| size).sum(), dirs: listing.dirs.len(), symlinks: listing.symlinks.len() })
+}
+
+fn copy_file(src: &Path, dst: &Path, method: Checkout, preserve: Preserve) -> io::Result<()> {
+    match method {
+        Checkout::Reflink => cas::reflink(src, dst)?,
+        _ => {
+            let mut from = File::open(src)?;
+            io::copy(&mut from, &mut File::create_new(dst)?)?;
+        }
+    }
+    preserve_metadata(src, dst, &fs::metadata(src)?, preserve)
+}
+
+/// Gives `dst` what `preserve` asks for of `src`'s metadata: attributes, then times, then
+/// the mode last in case it takes away the write permission the others need.
+fn preserve_metadata(src: &Path, dst: &Path, metadata: &Metadata, preserve: Preserve) -> io::Result<()> {
+    #[cfg(all(unix, feature = "libc"))]
+    if preserve.xattrs {
+        use crate::xattr;
+
+        for name in xattr::list_xattrs(src)? {
+            if cfg!(any(target_os = "linux", target_os = "android")) && !name.starts_with("user.") {
+                continue;
+            }
+            if let Some(value) = xattr::get_xattr(src, &name)? {
+                xattr::set_xattr(dst, &name, &value)?;
+            }
+        }
+    }
+    #[cfg(not(all(unix, feature = "libc")))]
+    let _ = src;
+    if preserve.times {
+        // The owner may set times through a read-only descriptor, which directories need.
+        File::open(dst)?.set_times(FileTimes::new().set_accessed(metadata.accessed()?).set_modified(metadata.modified()?))?;
+    }
+    if preserve.mode {
+        fs::set_permissions(dst, metadata.permissions())?;
+    }
+    Ok(())
+}
+
+/// Directories the benchmark spreads the workload over.
+#[cfg(feature = "bench")]
+pub const BENCH_DIRS: usize = 16;
+
+/// The ways the benchmark copies its tree.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// [`copy_tree`] carrying over nothing.
+    Parallel,
+    /// [`copy_tree`] with [`Preserve::ALL`].
+    Preserving,
+    /// A recursive walk copying one file after another with `fs::copy`.
+    Naive,
+    /// The system's `cp -r`.
+    Cp,
+}
+
+#[cfg(feature = "bench")]
+impl Method {
+    pub const ALL: [Method; 4] = [Method::Parallel, Method::Preserving, Method::Naive, Method::Cp];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Parallel => "copy_tree",
+            Method::Preserving => "copy_tree preserving",
+            Method::Naive => "naive",
+            Method::Cp => "cp -r",
+        }
+    }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct MethodResult {
+    pub method: Method,
+    /// `None` where the method can't run here: no `cp`, or no extended attributes.
+    pub elapsed: Option<Duration>,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct CopyResult {
+    /// How [`copy_tree`] made the files.
+    pub checkout: Option<Checkout>,
+    pub methods: Vec<MethodResult>,
+    pub failures: Vec<Failure>,
+}
+
+#[cfg(feature = "bench")]
+fn naive_copy(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let (from, to) = (entry.path(), dst.join(entry.file_name()));
+        if entry.file_type()?.is_dir() {
+            naive_copy(&from, &to)?;
+        } else {
+            fs::copy(&from, &to)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the configured workload as a tree of [`BENCH_DIRS`] directories under
+/// `dir_path/src`, then times copying it to `dir_path/dst` by each [`Method`], checking
+/// every copy has all the files and removing it untimed before the next.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<CopyResult> {
+    let (src, dst) = (dir_path.join("src"), dir_path.join("dst"));
+    let dirs: Vec<PathBuf> = (0..BENCH_DIRS).map(|i| src.join(format!("d{:02}", i))).collect();
+    for dir in &dirs {
+        fs::create_dir_all(dir)?;
+    }
+    let paths = names::paths(&dirs, options.workload.files, options.workload.names);
+    let mut result = CopyResult::default();
+    options.failures.take("");
+    bench::phase(options, "Copy create", "copy/create", "create", &mut result.failures, || {
+        bench::each_indexed(&paths, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+    })?;
+
+    for method in Method::ALL {
+        options.progress.set_stage(format!("Copy {}", method.name()));
+        options.op_scope.set(format!("copy/{}", method.name()));
+        let start = Instant::now();
+        let copied = match method {
+            Method::Parallel | Method::Preserving => {
+                let preserve = if method == Method::Parallel { Preserve::NONE } else { Preserve::ALL };
+                copy_tree(&src, &dst, preserve).map(|report| result.checkout = Some(report.method))
+            }
+            Method::Naive => naive_copy(&src, &dst),
+            Method::Cp => Command::new("cp").arg("-r").arg(&src).arg(&dst).status().and_then(|status| match status.success() {
+                true => Ok(()),
+                false => Err(io::Error::other(format!("cp -r exited with {}", status))),
+            }),
+        };
+        let elapsed = match copied {
+            Ok(()) => Some(start.elapsed()),
+            Err(e) if (method, e.kind()) == (Method::Preserving, io::ErrorKind::Unsupported) || (method, e.kind()) == (Method::Cp, io::ErrorKind::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+        if elapsed.is_some() {
+            let files = Listing::of(&dst)?.files.len();
+            if files != paths.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} copied {} of {} files", method.name(), files, paths.len())));
+            }
+        }
+        let _ = fs::remove_dir_all(&dst);
+        result.methods.push(MethodResult { method, elapsed });
+    }
+    fs::remove_dir_all(&src)?;
+    Ok(result)
+}
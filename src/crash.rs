@@ -0,0 +1,426 @@
+//! Crash-consistency trials for the ways of writing a file: a worker process rewrites a set
+//! of files over and over with one [`CrashStrategy`], is killed at a random moment, and
+//! what it left is checked against what it had acknowledged. This tests the durability
+//! claims of mmap, fsync'd writes and atomic renames instead of assuming them.
+//!
+//! Every version of every file names itself (file index and generation in a header, the
+//! rest derived from both), so a file can be told apart as the version being written or
+//! the last one acknowledged (current), an older one (stale: an acknowledged write lost),
+//! or none at all (torn: empty, short or mixed).
+//!
+//! [`CrashMode::Kill`] only kills the process, so the page cache survives and only
+//! half-done writes show. [`CrashMode::PowerCut`] runs the worker on an ext4 filesystem on
+//! a loop device and, right after the kill, copies the image as the device holds it and
+//! mounts the copy: whatever was still only in the page cache is gone, as after a power
+//! cut. It needs root, `mkfs.ext4` and loop devices, and is Linux only. The copy is taken
+//! while the kernel may still be writing back, so a trial can come out slightly kinder
+//! than a real power cut, never harsher.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use crate::atomic::{self, Durability};
+use crate::bench::Options;
+use crate::mmap::MmapFile;
+use crate::population::Rng;
+
+/// A version's file index and generation.
+pub const MIN_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrashStrategy {
+    /// Truncate and write the new contents.
+    Truncate,
+    /// Truncate, write and `fdatasync`.
+    TruncateFsync,
+    /// Copy the new contents into a mapping of the file.
+    Mmap,
+    /// Copy into a mapping and `msync` it.
+    MmapMsync,
+    /// Write a temporary file and rename it over the old one, without syncing.
+    Rename,
+    /// [`atomic::write_file_atomic`]: sync the temporary file, rename, sync the directory.
+    RenameFsync,
+}
+
+impl CrashStrategy {
+    pub const ALL: [CrashStrategy; 6] = [
+        CrashStrategy::Truncate,
+        CrashStrategy::TruncateFsync,
+        CrashStrategy::Mmap,
+        CrashStrategy::MmapMsync,
+        CrashStrategy::Rename,
+        CrashStrategy::RenameFsync,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CrashStrategy::Truncate => "truncate",
+            CrashStrategy::TruncateFsync => "truncate+fsync",
+            CrashStrategy::Mmap => "mmap",
+            CrashStrategy::MmapMsync => "mmap+msync",
+            CrashStrategy::Rename => "rename",
+            CrashStrategy::RenameFsync => "rename+fsync",
+        }
+    }
+
+    /// Whether a write, once it returned, is claimed to survive a crash of the machine.
+    pub fn durable(self) -> bool {
+        matches!(self, CrashStrategy::TruncateFsync | CrashStrategy::MmapMsync | CrashStrategy::RenameFsync)
+    }
+
+    /// Whether a crash is claimed to leave every file whole, old or new.
+    pub fn atomic(self) -> bool {
+        matches!(self, CrashStrategy::Rename | CrashStrategy::RenameFsync)
+    }
+
+    /// Replaces the contents of `path` with `content` this way.
+    pub fn write(self, path: &Path, content: &[u8]) -> io::Result<()> {
+        match self {
+            CrashStrategy::Truncate | CrashStrategy::TruncateFsync => {
+                let mut file = File::create(path)?;
+                file.write_all(content)?;
+                if self == CrashStrategy::TruncateFsync {
+                    file.sync_data()?;
+                }
+                Ok(())
+            }
+            CrashStrategy::Mmap | CrashStrategy::MmapMsync => {
+                let mut mapped = MmapFile::open(path)?;
+                mapped.replace(content)?;
+                if self == CrashStrategy::MmapMsync {
+                    mapped.flush()?;
+                }
+                Ok(())
+            }
+            CrashStrategy::Rename => atomic::write_file_atomic(path, content, Durability::None),
+            CrashStrategy::RenameFsync => atomic::write_file_atomic(path, content, Durability::Full),
+        }
+    }
+}
+
+impl FromStr for CrashStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        CrashStrategy::ALL
+            .into_iter()
+            .find(|strategy| strategy.name() == s)
+            .ok_or_else(|| "expected truncate, truncate+fsync, mmap, mmap+msync, rename or rename+fsync".to_string())
+    }
+}
+
+/// What is lost with the worker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CrashMode {
+    /// The process: the page cache survives.
+    #[default]
+    Kill,
+    /// The machine, simulated on a loop device: the page cache is lost too.
+    PowerCut,
+}
+
+impl CrashMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            CrashMode::Kill => "kill",
+            CrashMode::PowerCut => "power-cut",
+        }
+    }
+}
+
+impl FromStr for CrashMode {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<CrashMode> {
+        match s {
+            "kill" => Ok(CrashMode::Kill),
+            "power-cut" | "power" => Ok(CrashMode::PowerCut),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown crash mode '{}', expected kill|power-cut", s))),
+        }
+    }
+}
+
+fn filler(index: usize, generation: u64, at: usize) -> u8 {
+    (index as u64 * 61 + generation * 29 + at as u64) as u8
+}
+
+/// Generation `generation` of file `index`, `size` bytes (at least [`MIN_SIZE`]).
+pub fn version(index: usize, generation: u64, size: usize) -> Vec<u8> {
+    let size = size.max(MIN_SIZE);
+    let mut content = Vec::with_capacity(size);
+    content.extend_from_slice(&(index as u32).to_le_bytes());
+    content.extend_from_slice(&generation.to_le_bytes());
+    content.extend((12..size).map(|at| filler(index, generation, at)));
+    content
+}
+
+/// The generation `content` holds whole, if it is a version of file `index` of `size` bytes.
+pub fn generation_of(content: &[u8], index: usize, size: usize) -> Option<u64> {
+    if content.len() != size.max(MIN_SIZE) || content[..4] != (index as u32).to_le_bytes() {
+        return None;
+    }
+    let generation = u64::from_le_bytes(content[4..12].try_into().unwrap());
+    content[12..].iter().enumerate().all(|(at, &byte)| byte == filler(index, generation, 12 + at)).then_some(generation)
+}
+
+/// The path of file `index` in `dir`.
+pub fn file_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("file_{}", index))
+}
+
+/// Writes generation 0 of `files` files into `dir` and makes it durable, then rewrites
+/// them with `strategy` one after another, generation after generation, calling
+/// `acknowledge(index, generation)` as each write returns (and `acknowledge(usize::MAX, 0)`
+/// once generation 0 is durable). Stops after `generations` rounds, or runs until killed.
+pub fn write_generations(dir: &Path, strategy: CrashStrategy, files: usize, size: usize, generations: Option<u64>, mut acknowledge: impl FnMut(usize, u64) -> io::Result<()>) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for index in 0..files {
+        atomic::write_file_atomic(file_path(dir, index), version(index, 0, size), Durability::Full)?;
+    }
+    File::open(dir)?.sync_all()?;
+    acknowledge(usize::MAX, 0)?;
+    let mut generation = 1;
+    while generations.is_none_or(|generations| generation <= generations) {
+        for index in 0..files {
+            strategy.write(&file_path(dir, index), &version(index, generation, size))?;
+            acknowledge(index, generation)?;
+        }
+        generation += 1;
+    }
+    Ok(())
+}
+
+/// The worker side of [`run`]: [`write_generations`] until killed, answering `ready` on
+/// `out` once generation 0 is durable and `ack <index> <generation>` after every write.
+pub fn serve_worker(dir: &Path, strategy: CrashStrategy, files: usize, size: usize, mut out: impl Write) -> io::Result<()> {
+    write_generations(dir, strategy, files, size, None, |index, generation| {
+        match index {
+            usize::MAX => writeln!(out, "ready")?,
+            index => writeln!(out, "ack {} {}", index, generation)?,
+        }
+        out.flush()
+    })
+}
+
+/// How the files came through a crash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Survival {
+    /// Whole, at the last acknowledged generation or the one being written.
+    pub current: usize,
+    /// Whole, but older than acknowledged: an acknowledged write was lost.
+    pub stale: usize,
+    /// Empty, short, or holding parts of different versions.
+    pub torn: usize,
+    pub missing: usize,
+}
+
+impl Survival {
+    fn add(&mut self, other: Survival) {
+        self.current += other.current;
+        self.stale += other.stale;
+        self.torn += other.torn;
+        self.missing += other.missing;
+    }
+
+    /// Whether the crash broke what `strategy` claims in `mode`: durability only counts
+    /// when the machine went down.
+    pub fn breaks(&self, strategy: CrashStrategy, mode: CrashMode) -> bool {
+        let lost = strategy.durable() && mode == CrashMode::PowerCut && self.stale > 0;
+        let torn = strategy.atomic() && self.torn + self.missing > 0;
+        lost || torn
+    }
+}
+
+/// Checks the files in `dir` against `acknowledged`, the last generation acknowledged for
+/// each file.
+pub fn check(dir: &Path, size: usize, acknowledged: &[u64]) -> io::Result<Survival> {
+    let mut survival = Survival::default();
+    for (index, &acked) in acknowledged.iter().enumerate() {
+        let content = match fs::read(file_path(dir, index)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                survival.missing += 1;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        match generation_of(&content, index, size) {
+            Some(generation) if generation >= acked && generation <= acked + 1 => survival.current += 1,
+            Some(generation) if generation < acked => survival.stale += 1,
+            // Newer than anything started can't happen; count it with the torn ones.
+            _ => survival.torn += 1,
+        }
+    }
+    Ok(survival)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CrashConfig {
+    pub mode: CrashMode,
+    pub files: usize,
+    pub size: usize,
+    /// Crashes per strategy.
+    pub trials: usize,
+    /// The worker is killed a random time up to this long after generation 0 is durable.
+    pub max_delay: Duration,
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct StrategyCrashes {
+    pub strategy: CrashStrategy,
+    /// Writes acknowledged before the crashes, over every trial.
+    pub acknowledged: u64,
+    pub survival: Survival,
+    /// Trials whose crash broke the strategy's claim.
+    pub broken: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CrashResult {
+    pub strategies: Vec<StrategyCrashes>,
+}
+
+/// Runs `config.trials` crashes of each of `strategies`. For each, `worker(dir, strategy)`
+/// must build a command running [`serve_worker`] in `dir` (with the configured files and
+/// size) and printing to its stdout; it is spawned, killed with `SIGKILL` a random time
+/// after it answers `ready`, and its files checked, on a fresh loop device for
+/// [`CrashMode::PowerCut`].
+pub fn run(dir_path: &Path, strategies: &[CrashStrategy], config: CrashConfig, worker: impl Fn(&Path, CrashStrategy) -> Command, options: &Options) -> io::Result<CrashResult> {
+    let mut rng = Rng(config.seed | 1);
+    let mut result = CrashResult::default();
+    for &strategy in strategies {
+        let mut crashes = StrategyCrashes { strategy, acknowledged: 0, survival: Survival::default(), broken: 0 };
+        options.progress.set_stage(format!("Crash {}", strategy.name()));
+        options.op_scope.set(format!("crash/{}", strategy.name()));
+        options.progress.begin(config.trials);
+        for trial in 0..config.trials {
+            let delay = config.max_delay.mul_f64(rng.below(1001) as f64 / 1000.0);
+            let trial_dir = dir_path.join(format!("{}-{}", strategy.name().replace('+', "-"), trial));
+            let survival = match config.mode {
+                CrashMode::Kill => {
+                    let acknowledged = crash_worker(worker(&trial_dir, strategy), config.files, delay)?;
+                    crashes.acknowledged += acknowledged.iter().sum::<u64>();
+                    let survival = check(&trial_dir, config.size, &acknowledged)?;
+                    fs::remove_dir_all(&trial_dir)?;
+                    survival
+                }
+                CrashMode::PowerCut => {
+                    let image_size = (config.files * config.size.max(MIN_SIZE) * 3) as u64 + (64 << 20);
+                    let mut disk = LoopDisk::setup(&trial_dir, image_size)?;
+                    let acknowledged = crash_worker(worker(&disk.dir().join("files"), strategy), config.files, delay)?;
+                    crashes.acknowledged += acknowledged.iter().sum::<u64>();
+                    let recovered = disk.cut()?;
+                    check(&recovered.join("files"), config.size, &acknowledged)?
+                }
+            };
+            crashes.broken += usize::from(survival.breaks(strategy, config.mode));
+            crashes.survival.add(survival);
+            options.progress.advance();
+        }
+        result.strategies.push(crashes);
+    }
+    Ok(result)
+}
+
+/// Spawns `command`, waits for `ready`, kills it `delay` later and returns the last
+/// generation it acknowledged for each of `files` files.
+fn crash_worker(mut command: Command, files: usize, delay: Duration) -> io::Result<Vec<u64>> {
+    let mut child = command.stdout(Stdio::piped()).spawn()?;
+    let mut output = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let mut line = String::new();
+    output.read_line(&mut line)?;
+    if line.trim_end() != "ready" {
+        let _ = child.kill();
+        let status = child.wait()?;
+        return Err(io::Error::other(format!("crash worker did not start ({}): {}", status, line.trim_end())));
+    }
+    // Every ack read, also those still in the pipe after the kill, is for a write that returned.
+    let reader = thread::spawn(move || -> io::Result<Vec<u64>> {
+        let mut acknowledged = vec![0; files];
+        for line in output.lines() {
+            let line = line?;
+            let mut fields = line.strip_prefix("ack ").unwrap_or_default().split(' ').map(str::parse::<u64>);
+            if let (Some(Ok(index)), Some(Ok(generation))) = (fields.next(), fields.next())
+                && let Some(acked) = acknowledged.get_mut(index as usize)
+            {
+                *acked = generation;
+            }
+        }
+        Ok(acknowledged)
+    });
+    thread::sleep(delay);
+    child.kill()?;
+    child.wait()?;
+    reader.join().unwrap_or_else(|_| Err(io::Error::other("reading the crash worker's answers panicked")))
+}
+
+/// An ext4 filesystem on a loop device over an image file, for simulated power cuts: see
+/// [`CrashMode::PowerCut`]. Dropping it unmounts everything and removes the image.
+#[derive(Debug)]
+pub struct LoopDisk {
+    root: PathBuf,
+    mounted: Vec<PathBuf>,
+}
+
+impl LoopDisk {
+    /// Creates and mounts a filesystem of `size` bytes in `root`, which it owns.
+    pub fn setup(root: &Path, size: u64) -> io::Result<LoopDisk> {
+        fs::create_dir_all(root.join("disk"))?;
+        let mut disk = LoopDisk { root: root.to_path_buf(), mounted: Vec::new() };
+        let image = root.join("disk.img");
+        OpenOptions::new().write(true).create(true).truncate(true).open(&image)?.set_len(size)?;
+        command("mkfs.ext4", &["-q".as_ref(), "-F".as_ref(), image.as_os_str()])?;
+        disk.mount(&image, &root.join("disk"))?;
+        Ok(disk)
+    }
+
+    /// Where the filesystem is mounted.
+    pub fn dir(&self) -> PathBuf {
+        self.root.join("disk")
+    }
+
+    fn mount(&mut self, image: &Path, dir: &Path) -> io::Result<()> {
+        command("mount", &["-o".as_ref(), "loop".as_ref(), image.as_os_str(), dir.as_os_str()])?;
+        self.mounted.push(dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Cuts the power: copies the image as the device holds it, with nothing the page cache
+    /// hasn't written back, and mounts the copy, replaying its journal. Returns where.
+    pub fn cut(&mut self) -> io::Result<PathBuf> {
+        let image = self.root.join("cut.img");
+        fs::copy(self.root.join("disk.img"), &image)?;
+        let recovered = self.root.join("recovered");
+        fs::create_dir_all(&recovered)?;
+        self.mount(&image, &recovered)?;
+        Ok(recovered)
+    }
+}
+
+impl Drop for LoopDisk {
+    fn drop(&mut self) {
+        for dir in self.mounted.drain(..).rev() {
+            let _ = command("umount", &[dir.as_os_str()]);
+        }
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Runs `program`, failing with what it printed unless it succeeds.
+fn command(program: &str, args: &[&std::ffi::OsStr]) -> io::Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {} (power-cut crashes need root, mkfs.ext4 and loop devices)", program, e)))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("{} failed ({}): {}", program, output.status, String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(())
+}
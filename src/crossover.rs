@@ -0,0 +1,126 @@
+//! Measures the file size at which memory-mapped I/O starts beating plain read()/write()
+//! on a given filesystem.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use memmap2::{Mmap, MmapMut};
+use rayon::prelude::*;
+
+/// Smallest file sizes (in bytes) at which the mmap path wins. `None` means mmap never won.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thresholds {
+    pub mmap_read: Option<u64>,
+    pub mmap_update: Option<u64>,
+}
+
+impl Default for Thresholds {
+    /// The historical heuristic: always mmap for updates, never for reads.
+    fn default() -> Self {
+        Thresholds { mmap_read: None, mmap_update: Some(0) }
+    }
+}
+
+impl Thresholds {
+    pub fn use_mmap_read(&self, size: u64) -> bool {
+        self.mmap_read.is_some_and(|min| size >= min)
+    }
+
+    pub fn use_mmap_update(&self, size: u64) -> bool {
+        self.mmap_update.is_some_and(|min| size >= min)
+    }
+}
+
+pub struct Config {
+    pub sizes: Vec<usize>,
+    /// Upper bound on bytes written per size; the file count is derived from it.
+    pub bytes_per_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            sizes: (0..7).map(|i| 4096 << (2 * i)).collect(),
+            bytes_per_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SizeTiming {
+    pub size: usize,
+    pub files: usize,
+    pub read: Duration,
+    pub mmap_read: Duration,
+    pub write: Duration,
+    pub mmap_update: Duration,
+}
+
+fn time<F: FnOnce() -> io::Result<()>>(f: F) -> io::Result<Duration> {
+    let start = Instant::now();
+    f()?;
+    Ok(start.elapsed())
+}
+
+fn measure(dir: &Path, size: usize, files: usize) -> io::Result<SizeTiming> {
+    let paths: Vec<PathBuf> = (0..files).map(|i| dir.join(format!("crossover_{}_{}.bin", size, i))).collect();
+    let content = vec![0xa5u8; size];
+    paths.par_iter().try_for_each(|path| File::create(path)?.write_all(&content))?;
+
+    let read = time(|| {
+        paths.par_iter().try_for_each(|path| {
+            let mut buf = vec![0; size];
+            File::open(path)?.read_exact(&mut buf)
+        })
+    })?;
+    let mmap_read = time(|| {
+        paths.par_iter().try_for_each(|path| {
+            let mut buf = vec![0; size];
+            let map = unsafe { Mmap::map(&File::open(path)?)? };
+            buf.copy_from_slice(&map);
+            Ok(())
+        })
+    })?;
+    let write = time(|| {
+        paths.par_iter().try_for_each(|path| OpenOptions::new().write(true).open(path)?.write_all(&content))
+    })?;
+    let mmap_update = time(|| {
+        paths.par_iter().try_for_each(|path| {
+            let file = OpenOptions::new().read(true).write(true).open(path)?;
+            let mut map = unsafe { MmapMut::map_mut(&file)? };
+            map.copy_from_slice(&content);
+            Ok(())
+        })
+    })?;
+
+    paths.par_iter().try_for_each(fs::remove_file)?;
+    Ok(SizeTiming { size, files, read, mmap_read, write, mmap_update })
+}
+
+/// The smallest size from which mmap wins at every larger measured size as well, so a
+/// single noisy small size can't drag the threshold down.
+fn crossover(timings: &[SizeTiming], mmap_wins: impl Fn(&SizeTiming) -> bool) -> Option<u64> {
+    timings
+        .iter()
+        .rev()
+        .take_while(|t| mmap_wins(t))
+        .last()
+        .map(|t| t.size as u64)
+}
+
+/// Runs the experiment in `dir`, which must exist, and returns the per-size timings along
+/// with the thresholds derived from them.
+pub fn find(dir: &Path, config: &Config) -> io::Result<(Vec<SizeTiming>, Thresholds)> {
+    let timings = config
+        .sizes
+        .iter()
+        .map(|&size| measure(dir, size, (config.bytes_per_size / size.max(1)).clamp(8, 256)))
+        .collect::<io::Result<Vec<_>>>()?;
+    let thresholds = Thresholds {
+        mmap_read: crossover(&timings, |t| t.mmap_read < t.read),
+        mmap_update: crossover(&timings, |t| t.mmap_update < t.write),
+    };
+    Ok((timings, thresholds))
+}
@@ -0,0 +1,151 @@
+//! Finding files with the same contents across a tree, the way `fdupes` and `jdupes` do,
+//! so a cache or a build output can be checked for copies worth hard-linking or deleting.
+//!
+//! [`find_duplicates`] narrows the candidates in three passes, each cheaper than the next
+//! would be on what the last one dropped: files are grouped by size, which costs nothing
+//! beyond the walk; files sharing a size are hashed on their first [`PARTIAL_BYTES`]; and
+//! only files still sharing a partial hash are hashed whole. Both hashing passes read in
+//! parallel with the `rayon` feature. The hash is [BLAKE3](crate::digest::blake3), so two
+//! files in a group differ only if the hash is broken.
+//!
+//! Hard links to one file are found by device and inode before anything is hashed: they
+//! are one [`Inode`] with several names, read once, and free nothing if removed.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::contents;
+use crate::digest::Blake3;
+use crate::retry::RetryPolicy;
+use crate::stash::Listing;
+
+/// Bytes the partial hash reads from the start of each file.
+pub const PARTIAL_BYTES: u64 = 4 * 1024;
+
+/// One file in a [`Group`], under every name it is hard-linked as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inode {
+    /// Sorted; at least one.
+    pub paths: Vec<PathBuf>,
+}
+
+/// Distinct files with the same contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub size: u64,
+    /// The BLAKE3 digest of the contents.
+    pub hash: [u8; 32],
+    /// Sorted by first path; at least two.
+    pub copies: Vec<Inode>,
+}
+
+impl Group {
+    /// Bytes that keeping one copy would free. Extra hard links to a copy free nothing.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.copies.len() as u64 - 1)
+    }
+}
+
+/// The file's device and inode, which its hard links share, or `None` where there are none.
+fn identity(path: &Path) -> io::Result<Option<(u64, u64)>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = fs::symlink_metadata(path)?;
+        Ok(Some((metadata.dev(), metadata.ino())))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(None)
+    }
+}
+
+/// The BLAKE3 digest of everything `reader` yields.
+fn hash_reader(mut reader: impl Read) -> io::Result<[u8; 32]> {
+    let mut hasher = Blake3::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(hasher.finish()),
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Every group of distinct files under `root` with the same contents, the most wasteful
+/// first. Empty files and symlinks are left out, as are files whose only other names are
+/// their own hard links.
+pub fn find_duplicates(root: &Path) -> io::Result<Vec<Group>> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (path, size) in Listing::of(root)?.files {
+        if size > 0 {
+            by_size.entry(size).or_default().push(root.join(path));
+        }
+    }
+    let policy = RetryPolicy::default();
+
+    // Hard links share an inode, so each is gathered under the first of its names seen and
+    // only that one is read.
+    let mut copies: Vec<(u64, Inode)> = Vec::new();
+    for (size, paths) in by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        let identities = contents::each_retried(&paths, &policy, |path| path.as_path(), |path| identity(path))?;
+        let mut by_inode: HashMap<(u64, u64), usize> = HashMap::new();
+        let first = copies.len();
+        for (path, identity) in paths.into_iter().zip(identities) {
+            match identity.and_then(|inode| by_inode.get(&inode).copied()) {
+                Some(copy) => copies[copy].1.paths.push(path),
+                None => {
+                    if let Some(inode) = identity {
+                        by_inode.insert(inode, copies.len());
+                    }
+                    copies.push((size, Inode { paths: vec![path] }));
+                }
+            }
+        }
+        // Files of this size that are all one inode can't be copies of each other.
+        if copies.len() - first < 2 {
+            copies.truncate(first);
+        }
+    }
+    for (_, copy) in &mut copies {
+        copy.paths.sort();
+    }
+
+    let partial_hashes = contents::each_retried(&copies, &policy, |(_, copy)| copy.paths[0].as_path(), |(_, copy)| hash_reader(File::open(&copy.paths[0])?.take(PARTIAL_BYTES)))?;
+    let mut by_partial: HashMap<(u64, [u8; 32]), Vec<Inode>> = HashMap::new();
+    for ((size, copy), hash) in copies.into_iter().zip(partial_hashes) {
+        by_partial.entry((size, hash)).or_default().push(copy);
+    }
+
+    // A file no longer than the partial read was hashed whole already.
+    let mut by_hash: HashMap<(u64, [u8; 32]), Vec<Inode>> = HashMap::new();
+    let mut full: Vec<(u64, Inode)> = Vec::new();
+    for ((size, hash), copies) in by_partial.into_iter().filter(|(_, copies)| copies.len() > 1) {
+        if size <= PARTIAL_BYTES {
+            by_hash.insert((size, hash), copies);
+        } else {
+            full.extend(copies.into_iter().map(|copy| (size, copy)));
+        }
+    }
+    let full_hashes = contents::each_retried(&full, &policy, |(_, copy)| copy.paths[0].as_path(), |(_, copy)| hash_reader(File::open(&copy.paths[0])?))?;
+    for ((size, copy), hash) in full.into_iter().zip(full_hashes) {
+        by_hash.entry((size, hash)).or_default().push(copy);
+    }
+
+    let mut groups: Vec<Group> = by_hash
+        .into_iter()
+        .filter(|(_, copies)| copies.len() > 1)
+        .map(|((size, hash), mut copies)| {
+            copies.sort_by(|a, b| a.paths.cmp(&b.paths));
+            Group { size, hash, copies }
+        })
+        .collect();
+    groups.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.copies[0].paths.cmp(&b.copies[0].paths)));
+    Ok(groups)
+}
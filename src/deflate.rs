@@ -0,0 +1,565 @@
+//! Deflate (RFC 1951), its gzip wrapping (RFC 1952) and CRC-32, written by hand like
+//! [`crate::bundle`]'s zstd frames so archives can be compressed without dependencies.
+//!
+//! The compressor finds matches through a hash of the next three bytes, following a short
+//! chain of earlier positions, and codes them with the fixed Huffman codes; input it can't
+//! shrink goes out in stored blocks. That is faster than zlib's default level and
+//! compresses less, somewhere near `gzip -1` on text. It works through the input in
+//! [`CHUNK`]-byte blocks with no matches across them, so [`GzipWriter`] streams with
+//! bounded memory. The decompressor reads every kind of block any deflate encoder writes.
+
+use std::io::{self, Write};
+
+/// Input compressed as one block, and the most a [`GzipWriter`] buffers.
+pub const CHUNK: usize = 1 << 20;
+const WINDOW: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Earlier positions tried per match.
+const MAX_CHAIN: usize = 16;
+const HASH_BITS: u32 = 15;
+const STORED_MAX: usize = 65_535;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// The order code length code lengths are sent in.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Slicing-by-8 tables: `CRC_TABLES[k][b]` is the CRC of byte `b` followed by `k` zeros.
+const CRC_TABLES: [[u32; 256]; 8] = {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut k = 1;
+    while k < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let previous = tables[k - 1][i];
+            tables[k][i] = tables[0][(previous & 0xff) as usize] ^ (previous >> 8);
+            i += 1;
+        }
+        k += 1;
+    }
+    tables
+};
+
+/// Continues the CRC-32 (as zip and gzip use it) `crc` of earlier bytes over `data`; start
+/// from 0.
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let t = &CRC_TABLES;
+    let mut crc = !crc;
+    let mut words = data.chunks_exact(8);
+    for word in &mut words {
+        let low = crc ^ u32::from_le_bytes(word[..4].try_into().unwrap());
+        crc = t[7][(low & 0xff) as usize]
+            ^ t[6][(low >> 8 & 0xff) as usize]
+            ^ t[5][(low >> 16 & 0xff) as usize]
+            ^ t[4][(low >> 24) as usize]
+            ^ t[3][word[4] as usize]
+            ^ t[2][word[5] as usize]
+            ^ t[1][word[6] as usize]
+            ^ t[0][word[7] as usize];
+    }
+    !words.remainder().iter().fold(crc, |crc, &b| t[0][((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Bits packed least significant first, as deflate wants them.
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which goes most significant bit first.
+    fn code(&mut self, code: u32, len: u32) {
+        self.put(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.put(0, 8 - self.count);
+        }
+    }
+
+    /// Takes the whole bytes written so far, keeping a partial one.
+    fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.out)
+    }
+}
+
+/// The fixed literal/length code, bit-reversed ready to write, and its length.
+const FIXED_LITERALS: [(u16, u8); 288] = {
+    let mut table = [(0, 0); 288];
+    let mut symbol: u32 = 0;
+    while symbol < 288 {
+        let (code, len): (u32, u32) = match symbol {
+            0..=143 => (0x30 + symbol, 8),
+            144..=255 => (0x190 + symbol - 144, 9),
+            256..=279 => (symbol - 256, 7),
+            _ => (0xc0 + symbol - 280, 8),
+        };
+        table[symbol as usize] = ((code.reverse_bits() >> (32 - len)) as u16, len as u8);
+        symbol += 1;
+    }
+    table
+};
+
+/// Writes literal/length `symbol` in the fixed code.
+fn fixed_literal(writer: &mut BitWriter, symbol: u32) {
+    let (code, len) = FIXED_LITERALS[symbol as usize];
+    writer.put(code as u32, len as u32);
+}
+
+fn fixed_match(writer: &mut BitWriter, len: usize, dist: usize) {
+    let code = LENGTH_BASE.partition_point(|&base| base as usize <= len) - 1;
+    fixed_literal(writer, 257 + code as u32);
+    writer.put((len - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+    let code = DIST_BASE.partition_point(|&base| base as usize <= dist) - 1;
+    writer.code(code as u32, 5);
+    writer.put((dist - DIST_BASE[code] as usize) as u32, DIST_EXTRA[code] as u32);
+}
+
+fn hash(bytes: &[u8], bits: u32) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+    (value.wrapping_mul(0x9e37_79b1) >> (32 - bits)) as usize
+}
+
+/// Compresses `data` as one block, fixed-Huffman or, if that wouldn't be smaller, stored.
+fn block(writer: &mut BitWriter, data: &[u8], last: bool) {
+    let mut compressed = BitWriter { out: Vec::with_capacity(data.len() / 2), bits: writer.bits, count: writer.count };
+    compressed.put(last as u32, 1);
+    compressed.put(1, 2);
+    // Tables no bigger than the input, so small files don't pay for a whole window.
+    let size = data.len().max(256).next_power_of_two().min(WINDOW);
+    let bits = size.trailing_zeros().min(HASH_BITS);
+    let mut head = vec![u32::MAX; 1 << bits];
+    // The previous position with the same hash, for each position in the window.
+    let mut prev = vec![u32::MAX; size];
+    let insert = |pos: usize, head: &mut [u32], prev: &mut [u32]| {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(&data[pos..], bits);
+            prev[pos & (size - 1)] = head[h];
+            head[h] = pos as u32;
+        }
+    };
+    let (mut pos, mut literals) = (0, 0);
+    while pos < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+        // The longer a run of literals, the less likely the data compresses at all, so
+        // search shorter chains and, past a few hundred bytes, only every fourth position.
+        let chain = MAX_CHAIN >> (literals / 64).min(4);
+        let searching = literals < 256 || literals % 4 == 0;
+        if pos + MIN_MATCH <= data.len() && searching {
+            let mut candidate = head[hash(&data[pos..], bits)];
+            let max = MAX_MATCH.min(data.len() - pos);
+            for _ in 0..chain {
+                // A slot the window has wrapped over can point anywhere; stop there.
+                if candidate == u32::MAX || candidate as usize >= pos || pos - candidate as usize > WINDOW {
+                    break;
+                }
+                let start = candidate as usize;
+                let len = data[start..start + max].iter().zip(&data[pos..pos + max]).take_while(|(a, b)| a == b).count();
+                if len > best_len {
+                    (best_len, best_dist) = (len, pos - start);
+                    if len == max {
+                        break;
+                    }
+                }
+                candidate = prev[start & (size - 1)];
+            }
+        }
+        if best_len >= MIN_MATCH {
+            fixed_match(&mut compressed, best_len, best_dist);
+            for at in pos..pos + best_len {
+                insert(at, &mut head, &mut prev);
+            }
+            pos += best_len;
+            literals = 0;
+        } else {
+            fixed_literal(&mut compressed, data[pos] as u32);
+            if searching {
+                insert(pos, &mut head, &mut prev);
+            }
+            pos += 1;
+            literals += 1;
+        }
+    }
+    fixed_literal(&mut compressed, 256);
+
+    // A stored block costs its bytes plus five per 64 KiB.
+    if compressed.out.len() <= data.len() + data.len() / STORED_MAX * 5 + 5 {
+        writer.out.append(&mut compressed.out);
+        (writer.bits, writer.count) = (compressed.bits, compressed.count);
+        return;
+    }
+    let mut pieces = data.chunks(STORED_MAX).peekable();
+    while let Some(piece) = pieces.next() {
+        writer.put((last && pieces.peek().is_none()) as u32, 1);
+        writer.put(0, 2);
+        writer.align();
+        let len = piece.len() as u16;
+        writer.out.extend_from_slice(&len.to_le_bytes());
+        writer.out.extend_from_slice(&(!len).to_le_bytes());
+        writer.out.extend_from_slice(piece);
+    }
+}
+
+/// `data` as a raw deflate stream.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { out: Vec::with_capacity(data.len() / 2 + 16), bits: 0, count: 0 };
+    let mut chunks = data.chunks(CHUNK).peekable();
+    if chunks.peek().is_none() {
+        block(&mut writer, &[], true);
+    }
+    while let Some(chunk) = chunks.next() {
+        block(&mut writer, chunk, chunks.peek().is_none());
+    }
+    writer.align();
+    writer.out
+}
+
+/// Huffman decoding table: for every `max`-bit string, the symbol whose code it starts with
+/// and that code's length, as `symbol << 4 | length`.
+struct Table {
+    entries: Vec<u16>,
+    max: u32,
+}
+
+impl Table {
+    fn new(lengths: &[u8]) -> io::Result<Table> {
+        let max = lengths.iter().copied().max().unwrap_or(0) as u32;
+        let mut count = [0u32; 16];
+        for &len in lengths {
+            count[len as usize] += 1;
+        }
+        count[0] = 0;
+        let mut next = [0u32; 16];
+        let mut code = 0;
+        for len in 1..16 {
+            code = (code + count[len - 1]) << 1;
+            next[len] = code;
+        }
+        let mut entries = vec![0u16; 1 << max.max(1)];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+            let len = len as u32;
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            if code >= 1 << len {
+                return Err(invalid("over-subscribed Huffman code"));
+            }
+            let reversed = code.reverse_bits() >> (32 - len);
+            for fill in (reversed as usize..entries.len()).step_by(1 << len) {
+                entries[fill] = (symbol as u16) << 4 | len as u16;
+            }
+        }
+        Ok(Table { entries, max })
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bits: u64,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn refill(&mut self) {
+        // Eight bytes at once where there are that many: the bytes beyond the ones counted
+        // land where the next refill puts them again.
+        if let Some(word) = self.data.get(self.pos..self.pos + 8) {
+            self.bits |= u64::from_le_bytes(word.try_into().unwrap()) << self.count;
+            let taken = (63 - self.count) / 8;
+            self.pos += taken as usize;
+            self.count += taken * 8;
+            return;
+        }
+        while self.count <= 56 && self.pos < self.data.len() {
+            self.bits |= (self.data[self.pos] as u64) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+    }
+
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        if self.count < count {
+            self.refill();
+            if self.count < count {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "deflate stream ends early"));
+            }
+        }
+        let value = (self.bits & ((1u64 << count) - 1)) as u32;
+        self.bits >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    fn decode(&mut self, table: &Table) -> io::Result<u16> {
+        if self.count < table.max {
+            self.refill();
+        }
+        let entry = table.entries[(self.bits & ((1u64 << table.max) - 1)) as usize];
+        let len = (entry & 15) as u32;
+        if len == 0 {
+            return Err(invalid("invalid Huffman code"));
+        }
+        if len > self.count {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "deflate stream ends early"));
+        }
+        self.bits >>= len;
+        self.count -= len;
+        Ok(entry >> 4)
+    }
+
+    fn align(&mut self) {
+        let drop = self.count % 8;
+        self.bits >>= drop;
+        self.count -= drop;
+    }
+
+    /// Bytes consumed, not counting whole bytes read ahead.
+    fn consumed(&self) -> usize {
+        self.pos - (self.count / 8) as usize
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Decompresses a raw deflate stream, returning it and the bytes of `data` it took.
+fn inflate_prefix(data: &[u8], size_hint: usize) -> io::Result<(Vec<u8>, usize)> {
+    let mut reader = BitReader { data, pos: 0, bits: 0, count: 0 };
+    let mut out = Vec::with_capacity(size_hint);
+    let fixed = {
+        let mut lengths = [8u8; 288];
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        (Table::new(&lengths)?, Table::new(&[5; 30])?)
+    };
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let len = reader.bits(16)?;
+                if reader.bits(16)? != !len & 0xffff {
+                    return Err(invalid("stored block length doesn't match its complement"));
+                }
+                let mut remaining = len as usize;
+                while remaining > 0 && reader.count >= 8 {
+                    out.push(reader.bits(8)? as u8);
+                    remaining -= 1;
+                }
+                if remaining > 0 {
+                    // Whatever was read ahead is stale once the copy moves past it.
+                    reader.bits = 0;
+                    let bytes = reader.data.get(reader.pos..reader.pos + remaining).ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "deflate stream ends early"))?;
+                    out.extend_from_slice(bytes);
+                    reader.pos += remaining;
+                }
+            }
+            1 => codes(&mut reader, &mut out, &fixed.0, &fixed.1)?,
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut reader)?;
+                codes(&mut reader, &mut out, &literals, &distances)?;
+            }
+            _ => return Err(invalid("reserved block type")),
+        }
+        if last {
+            return Ok((out, reader.consumed()));
+        }
+    }
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> io::Result<(Table, Table)> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[symbol] = reader.bits(3)? as u8;
+    }
+    let table = Table::new(&lengths)?;
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match reader.decode(&table)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or_else(|| invalid("repeat with no length before it"))?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literals + distances {
+        return Err(invalid("code lengths run past the end"));
+    }
+    Ok((Table::new(&lengths[..literals])?, Table::new(&lengths[literals..])?))
+}
+
+fn codes(reader: &mut BitReader, out: &mut Vec<u8>, literals: &Table, distances: &Table) -> io::Result<()> {
+    loop {
+        let symbol = reader.decode(literals)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let code = symbol - 257;
+                if code >= LENGTH_BASE.len() {
+                    return Err(invalid("invalid length code"));
+                }
+                let len = LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code] as u32)? as usize;
+                let code = reader.decode(distances)? as usize;
+                if code >= DIST_BASE.len() {
+                    return Err(invalid("invalid distance code"));
+                }
+                let dist = DIST_BASE[code] as usize + reader.bits(DIST_EXTRA[code] as u32)? as usize;
+                if dist > out.len() {
+                    return Err(invalid("distance reaches before the start"));
+                }
+                let start = out.len() - dist;
+                if dist >= len {
+                    out.extend_from_within(start..start + len);
+                } else {
+                    for i in 0..len {
+                        out.push(out[start + i]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Decompresses a raw deflate stream.
+pub fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    inflate_prefix(data, data.len() * 3).map(|(out, _)| out)
+}
+
+const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = GZIP_HEADER.to_vec();
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Decompresses every member of a gzip file, checking each one's CRC and length.
+pub fn gunzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        if rest.len() < 18 || rest[..3] != GZIP_HEADER[..3] {
+            return Err(invalid("not a gzip member"));
+        }
+        let flags = rest[3];
+        let mut at = 10;
+        if flags & 4 != 0 {
+            let extra = u16::from_le_bytes([rest[at], rest[at + 1]]) as usize;
+            at += 2 + extra;
+        }
+        // File name and comment, each zero-terminated.
+        for flag in [8, 16] {
+            if flags & flag != 0 {
+                at += rest.get(at..).and_then(|tail| tail.iter().position(|&b| b == 0)).ok_or_else(|| invalid("gzip header ends early"))? + 1;
+            }
+        }
+        if flags & 2 != 0 {
+            at += 2;
+        }
+        let body = rest.get(at..).ok_or_else(|| invalid("gzip header ends early"))?;
+        let start = out.len();
+        let (member, used) = inflate_prefix(body, body.len() * 3)?;
+        out.extend_from_slice(&member);
+        let trailer = body.get(used..used + 8).ok_or_else(|| invalid("gzip trailer missing"))?;
+        if u32::from_le_bytes(trailer[..4].try_into().unwrap()) != crc32(&out[start..]) {
+            return Err(invalid("gzip CRC mismatch"));
+        }
+        if u32::from_le_bytes(trailer[4..].try_into().unwrap()) != (out.len() - start) as u32 {
+            return Err(invalid("gzip length mismatch"));
+        }
+        rest = &body[used + 8..];
+    }
+    Ok(out)
+}
+
+/// Gzips what is written to it into `inner`, a block at a time; [`GzipWriter::finish`]
+/// writes the last block and the trailer.
+pub struct GzipWriter<W: Write> {
+    inner: W,
+    pending: Vec<u8>,
+    bits: BitWriter,
+    crc: u32,
+    len: u64,
+}
+
+impl<W: Write> GzipWriter<W> {
+    pub fn new(mut inner: W) -> io::Result<GzipWriter<W>> {
+        inner.write_all(&GZIP_HEADER)?;
+        Ok(GzipWriter { inner, pending: Vec::with_capacity(CHUNK), bits: BitWriter { out: Vec::new(), bits: 0, count: 0 }, crc: 0, len: 0 })
+    }
+
+    fn flush_block(&mut self, last: bool) -> io::Result<()> {
+        self.crc = crc32_update(self.crc, &self.pending);
+        self.len += self.pending.len() as u64;
+        block(&mut self.bits, &self.pending, last);
+        self.pending.clear();
+        self.inner.write_all(&self.bits.take())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_block(true)?;
+        self.bits.align();
+        self.inner.write_all(&self.bits.take())?;
+        self.inner.write_all(&self.crc.to_le_bytes())?;
+        self.inner.write_all(&(self.len as u32).to_le_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for GzipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(CHUNK - self.pending.len());
+        self.pending.extend_from_slice(&buf[..n]);
+        if self.pending.len() == CHUNK {
+            self.flush_block(false)?;
+        }
+        Ok(n)
+    }
+
+    /// Writes out the blocks finished so far; the pending one waits for more input.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
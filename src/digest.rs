@@ -0,0 +1,645 @@
+//! Content hashes for cache keys and integrity checks, written by hand so hashing a tree
+//! needs no dependencies: XXH64 (fast, not cryptographic), BLAKE3 and SHA-256. Each has a
+//! one-shot function and a streaming state, and [`Algorithm`] picks one at run time.
+//!
+//! These are portable implementations, without the SIMD of the reference crates: BLAKE3
+//! here hashes one chunk at a time rather than eight at once, so it is slower than `b3sum`
+//! but still faster than SHA-256 without the SHA extensions.
+
+use std::fmt;
+use std::str::FromStr;
+#[cfg(feature = "bench")]
+use std::fs::{self, File};
+#[cfg(feature = "bench")]
+use std::io::{self, Read};
+#[cfg(feature = "bench")]
+use std::path::Path;
+#[cfg(feature = "bench")]
+use std::sync::OnceLock;
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+#[cfg(feature = "bench")]
+use crate::{buffers, cache, contents};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Xxh64,
+    Blake3,
+    Sha256,
+}
+
+impl Algorithm {
+    /// Fastest first.
+    pub const ALL: [Algorithm; 3] = [Algorithm::Xxh64, Algorithm::Blake3, Algorithm::Sha256];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Xxh64 => "xxh64",
+            Algorithm::Blake3 => "blake3",
+            Algorithm::Sha256 => "sha256",
+        }
+    }
+
+    pub fn hasher(self) -> Hasher {
+        match self {
+            Algorithm::Xxh64 => Hasher::Xxh64(Xxh64::new(0)),
+            Algorithm::Blake3 => Hasher::Blake3(Box::default()),
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    /// The digest of `data`: 8 bytes, big-endian, for XXH64 and 32 for the others.
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Algorithm::Xxh64 => xxh64(data, 0).to_be_bytes().to_vec(),
+            Algorithm::Blake3 => blake3(data).to_vec(),
+            Algorithm::Sha256 => sha256(data).to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Algorithm::ALL.into_iter().find(|algorithm| algorithm.name() == s).ok_or_else(|| "expected xxh64, blake3 or sha256".to_string())
+    }
+}
+
+/// A streaming hash of any [`Algorithm`].
+#[derive(Clone)]
+pub enum Hasher {
+    Xxh64(Xxh64),
+    Blake3(Box<Blake3>),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Xxh64(state) => state.update(data),
+            Hasher::Blake3(state) => state.update(data),
+            Hasher::Sha256(state) => state.update(data),
+        }
+    }
+
+    /// The digest, as [`Algorithm::digest`] gives it.
+    pub fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Xxh64(state) => state.finish().to_be_bytes().to_vec(),
+            Hasher::Blake3(state) => state.finish().to_vec(),
+            Hasher::Sha256(state) => state.finish().to_vec(),
+        }
+    }
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+fn u64_le(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+const XXH32_PRIMES: [u32; 5] = [2_654_435_761, 2_246_822_519, 3_266_489_917, 668_265_263, 374_761_393];
+
+/// XXH32, which LZ4 frames use for their checksums.
+pub fn xxh32(data: &[u8], seed: u32) -> u32 {
+    let [p1, p2, p3, p4, p5] = XXH32_PRIMES;
+    let round = |acc: u32, lane: u32| acc.wrapping_add(lane.wrapping_mul(p2)).rotate_left(13).wrapping_mul(p1);
+    let mut stripes = data.chunks_exact(16);
+    let mut hash = if data.len() >= 16 {
+        let mut lanes = [seed.wrapping_add(p1).wrapping_add(p2), seed.wrapping_add(p2), seed, seed.wrapping_sub(p1)];
+        for stripe in &mut stripes {
+            for (lane, word) in lanes.iter_mut().zip(stripe.chunks_exact(4)) {
+                *lane = round(*lane, u32_le(word));
+            }
+        }
+        lanes[0].rotate_left(1).wrapping_add(lanes[1].rotate_left(7)).wrapping_add(lanes[2].rotate_left(12)).wrapping_add(lanes[3].rotate_left(18))
+    } else {
+        seed.wrapping_add(p5)
+    };
+    hash = hash.wrapping_add(data.len() as u32);
+    let mut words = stripes.remainder().chunks_exact(4);
+    for word in &mut words {
+        hash = hash.wrapping_add(u32_le(word).wrapping_mul(p3)).rotate_left(17).wrapping_mul(p4);
+    }
+    for &byte in words.remainder() {
+        hash = hash.wrapping_add((byte as u32).wrapping_mul(p5)).rotate_left(11).wrapping_mul(p1);
+    }
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(p2);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(p3);
+    hash ^ hash >> 16
+}
+
+const XXH64_PRIMES: [u64; 5] = [0x9e37_79b1_85eb_ca87, 0xc2b2_ae3d_27d4_eb4f, 0x1656_67b1_9e37_79f9, 0x85eb_ca77_c2b2_ae63, 0x27d4_eb2f_1656_67c5];
+
+fn xxh64_round(acc: u64, lane: u64) -> u64 {
+    let [p1, p2, ..] = XXH64_PRIMES;
+    acc.wrapping_add(lane.wrapping_mul(p2)).rotate_left(31).wrapping_mul(p1)
+}
+
+/// XXH64 as a stream: 32-byte stripes into four lanes, the rest buffered.
+#[derive(Debug, Clone)]
+pub struct Xxh64 {
+    seed: u64,
+    lanes: [u64; 4],
+    buffer: [u8; 32],
+    buffered: usize,
+    len: u64,
+}
+
+impl Xxh64 {
+    pub fn new(seed: u64) -> Xxh64 {
+        let [p1, p2, ..] = XXH64_PRIMES;
+        let lanes = [seed.wrapping_add(p1).wrapping_add(p2), seed.wrapping_add(p2), seed, seed.wrapping_sub(p1)];
+        Xxh64 { seed, lanes, buffer: [0; 32], buffered: 0, len: 0 }
+    }
+
+    fn stripe(&mut self, stripe: &[u8]) {
+        for (lane, word) in self.lanes.iter_mut().zip(stripe.chunks_exact(8)) {
+            *lane = xxh64_round(*lane, u64_le(word));
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        if self.buffered > 0 {
+            let take = data.len().min(32 - self.buffered);
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+            if self.buffered < 32 {
+                return;
+            }
+            let buffer = self.buffer;
+            self.stripe(&buffer);
+            self.buffered = 0;
+        }
+        let mut stripes = data.chunks_exact(32);
+        for stripe in &mut stripes {
+            self.stripe(stripe);
+        }
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    pub fn finish(&self) -> u64 {
+        let [p1, p2, p3, p4, p5] = XXH64_PRIMES;
+        let mut hash = if self.len >= 32 {
+            let [v1, v2, v3, v4] = self.lanes;
+            let mut hash = v1.rotate_left(1).wrapping_add(v2.rotate_left(7)).wrapping_add(v3.rotate_left(12)).wrapping_add(v4.rotate_left(18));
+            for lane in self.lanes {
+                hash = (hash ^ xxh64_round(0, lane)).wrapping_mul(p1).wrapping_add(p4);
+            }
+            hash
+        } else {
+            self.seed.wrapping_add(p5)
+        };
+        hash = hash.wrapping_add(self.len);
+        let mut words = self.buffer[..self.buffered].chunks_exact(8);
+        for word in &mut words {
+            hash = (hash ^ xxh64_round(0, u64_le(word))).rotate_left(27).wrapping_mul(p1).wrapping_add(p4);
+        }
+        let mut rest = words.remainder();
+        if rest.len() >= 4 {
+            hash = (hash ^ (u32_le(rest) as u64).wrapping_mul(p1)).rotate_left(23).wrapping_mul(p2).wrapping_add(p3);
+            rest = &rest[4..];
+        }
+        for &byte in rest {
+            hash = (hash ^ (byte as u64).wrapping_mul(p5)).rotate_left(11).wrapping_mul(p1);
+        }
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(p2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(p3);
+        hash ^ hash >> 32
+    }
+}
+
+pub fn xxh64(data: &[u8], seed: u64) -> u64 {
+    let mut state = Xxh64::new(seed);
+    state.update(data);
+    state.finish()
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74,
+    0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d,
+    0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e,
+    0x92722c85, 0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5,
+    0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 (FIPS 180-4) as a stream of 64-byte blocks.
+#[derive(Debug, Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; 64],
+    buffered: usize,
+    len: u64,
+}
+
+impl Default for Sha256 {
+    fn default() -> Sha256 {
+        Sha256::new()
+    }
+}
+
+impl Sha256 {
+    pub fn new() -> Sha256 {
+        let state = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+        Sha256 { state, buffer: [0; 64], buffered: 0, len: 0 }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        if self.buffered > 0 {
+            let take = data.len().min(64 - self.buffered);
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+            if self.buffered < 64 {
+                return;
+            }
+            sha256_block(&mut self.state, &self.buffer);
+            self.buffered = 0;
+        }
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            sha256_block(&mut self.state, block);
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    pub fn finish(mut self) -> [u8; 32] {
+        let bits = self.len * 8;
+        let mut tail = [0; 128];
+        tail[..self.buffered].copy_from_slice(&self.buffer[..self.buffered]);
+        tail[self.buffered] = 0x80;
+        let tail_len = if self.buffered < 56 { 64 } else { 128 };
+        tail[tail_len - 8..tail_len].copy_from_slice(&bits.to_be_bytes());
+        for block in tail[..tail_len].chunks_exact(64) {
+            sha256_block(&mut self.state, block);
+        }
+        let mut digest = [0; 32];
+        for (out, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            out.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+fn sha256_block(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes(bytes.try_into().unwrap());
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        (h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+    }
+    for (word, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(add);
+    }
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state = Sha256::new();
+    state.update(data);
+    state.finish()
+}
+
+const BLAKE3_IV: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+const BLAKE3_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+/// The message word each round reads in each position: the permutation applied round after
+/// round, worked out once instead of shuffling the block.
+const BLAKE3_SCHEDULE: [[usize; 16]; 7] = {
+    let mut schedule = [[0; 16]; 7];
+    let mut i = 0;
+    while i < 16 {
+        schedule[0][i] = i;
+        i += 1;
+    }
+    let mut round = 1;
+    while round < 7 {
+        let mut i = 0;
+        while i < 16 {
+            schedule[round][i] = schedule[round - 1][BLAKE3_PERMUTATION[i]];
+            i += 1;
+        }
+        round += 1;
+    }
+    schedule
+};
+const BLAKE3_BLOCK: usize = 64;
+const BLAKE3_CHUNK: usize = 1024;
+const CHUNK_START: u32 = 1;
+const CHUNK_END: u32 = 2;
+const PARENT: u32 = 4;
+const ROOT: u32 = 8;
+
+#[inline(always)]
+fn blake3_g(state: &mut [u32; 16], [a, b, c, d]: [usize; 4], x: u32, y: u32) {
+    state[a] = state[a].wrapping_add(state[b]).wrapping_add(x);
+    state[d] = (state[d] ^ state[a]).rotate_right(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_right(12);
+    state[a] = state[a].wrapping_add(state[b]).wrapping_add(y);
+    state[d] = (state[d] ^ state[a]).rotate_right(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_right(7);
+}
+
+/// The compression function: seven rounds over a block, keyed by a chaining value.
+#[inline(always)]
+fn blake3_compress(cv: &[u32; 8], block: &[u32; 16], counter: u64, len: u32, flags: u32) -> [u32; 16] {
+    let mut state = [
+        cv[0], cv[1], cv[2], cv[3], cv[4], cv[5], cv[6], cv[7],
+        BLAKE3_IV[0], BLAKE3_IV[1], BLAKE3_IV[2], BLAKE3_IV[3],
+        counter as u32, (counter >> 32) as u32, len, flags,
+    ];
+    for round in &BLAKE3_SCHEDULE {
+        let m = |i: usize| block[round[i]];
+        blake3_g(&mut state, [0, 4, 8, 12], m(0), m(1));
+        blake3_g(&mut state, [1, 5, 9, 13], m(2), m(3));
+        blake3_g(&mut state, [2, 6, 10, 14], m(4), m(5));
+        blake3_g(&mut state, [3, 7, 11, 15], m(6), m(7));
+        blake3_g(&mut state, [0, 5, 10, 15], m(8), m(9));
+        blake3_g(&mut state, [1, 6, 11, 12], m(10), m(11));
+        blake3_g(&mut state, [2, 7, 8, 13], m(12), m(13));
+        blake3_g(&mut state, [3, 4, 9, 14], m(14), m(15));
+    }
+    for i in 0..8 {
+        state[i] ^= state[i + 8];
+        state[i + 8] ^= cv[i];
+    }
+    state
+}
+
+fn blake3_words(bytes: &[u8; BLAKE3_BLOCK]) -> [u32; 16] {
+    std::array::from_fn(|i| u32_le(&bytes[i * 4..]))
+}
+
+fn first_eight(words: [u32; 16]) -> [u32; 8] {
+    std::array::from_fn(|i| words[i])
+}
+
+/// A node's last compression, kept unrun until it is known whether the node is the root.
+struct Blake3Output {
+    cv: [u32; 8],
+    block: [u32; 16],
+    counter: u64,
+    len: u32,
+    flags: u32,
+}
+
+impl Blake3Output {
+    fn chaining_value(&self) -> [u32; 8] {
+        first_eight(blake3_compress(&self.cv, &self.block, self.counter, self.len, self.flags))
+    }
+
+    fn root(&self) -> [u8; 32] {
+        let words = blake3_compress(&self.cv, &self.block, 0, self.len, self.flags | ROOT);
+        let mut digest = [0; 32];
+        for (out, word) in digest.chunks_exact_mut(4).zip(words) {
+            out.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+}
+
+fn blake3_parent(left: [u32; 8], right: [u32; 8]) -> Blake3Output {
+    let block = std::array::from_fn(|i| if i < 8 { left[i] } else { right[i - 8] });
+    Blake3Output { cv: BLAKE3_IV, block, counter: 0, len: BLAKE3_BLOCK as u32, flags: PARENT }
+}
+
+/// BLAKE3, unkeyed, as a stream: 1 KiB chunks hashed in turn and merged up a tree whose
+/// pending left edges wait on a stack.
+#[derive(Clone)]
+pub struct Blake3 {
+    /// The current chunk: its chaining value so far, index, pending block and blocks done.
+    cv: [u32; 8],
+    chunk: u64,
+    block: [u8; BLAKE3_BLOCK],
+    block_len: usize,
+    blocks: usize,
+    /// Chaining values of complete subtrees, one per set bit of the chunks done.
+    stack: Vec<[u32; 8]>,
+}
+
+impl Default for Blake3 {
+    fn default() -> Blake3 {
+        Blake3::new()
+    }
+}
+
+impl Blake3 {
+    pub fn new() -> Blake3 {
+        Blake3 { cv: BLAKE3_IV, chunk: 0, block: [0; BLAKE3_BLOCK], block_len: 0, blocks: 0, stack: Vec::with_capacity(54) }
+    }
+
+    fn chunk_len(&self) -> usize {
+        self.blocks * BLAKE3_BLOCK + self.block_len
+    }
+
+    fn start_flag(&self) -> u32 {
+        if self.blocks == 0 { CHUNK_START } else { 0 }
+    }
+
+    fn chunk_output(&self) -> Blake3Output {
+        let mut block = [0; BLAKE3_BLOCK];
+        block[..self.block_len].copy_from_slice(&self.block[..self.block_len]);
+        Blake3Output { cv: self.cv, block: blake3_words(&block), counter: self.chunk, len: self.block_len as u32, flags: self.start_flag() | CHUNK_END }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.chunk_len() == BLAKE3_CHUNK {
+                let mut cv = self.chunk_output().chaining_value();
+                self.chunk += 1;
+                // Each trailing zero of the chunk count closes a subtree.
+                let mut done = self.chunk;
+                while done & 1 == 0 {
+                    cv = blake3_parent(self.stack.pop().expect("a left sibling per trailing zero"), cv).chaining_value();
+                    done >>= 1;
+                }
+                self.stack.push(cv);
+                (self.cv, self.block_len, self.blocks) = (BLAKE3_IV, 0, 0);
+            }
+            // A full block is only compressed once more input shows it isn't the chunk's last.
+            if self.block_len == BLAKE3_BLOCK {
+                let words = blake3_words(&self.block);
+                self.cv = first_eight(blake3_compress(&self.cv, &words, self.chunk, BLAKE3_BLOCK as u32, self.start_flag()));
+                self.blocks += 1;
+                self.block_len = 0;
+            }
+            let take = data.len().min(BLAKE3_BLOCK - self.block_len);
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&data[..take]);
+            self.block_len += take;
+            data = &data[take..];
+        }
+    }
+
+    pub fn finish(&self) -> [u8; 32] {
+        let mut output = self.chunk_output();
+        for &left in self.stack.iter().rev() {
+            output = blake3_parent(left, output.chaining_value());
+        }
+        output.root()
+    }
+}
+
+pub fn blake3(data: &[u8]) -> [u8; 32] {
+    let mut state = Blake3::new();
+    state.update(data);
+    state.finish()
+}
+
+/// How a file's bytes reach the hash.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Read whole into a buffer, then hashed in one call.
+    Buffered,
+    /// Mapped and hashed in place, without a copy.
+    Mmap,
+    /// Read and hashed [`STREAM_CHUNK`] bytes at a time, so memory stays flat however large
+    /// the file.
+    Streaming,
+}
+
+#[cfg(feature = "bench")]
+impl Method {
+    pub const ALL: [Method; 3] = [Method::Buffered, Method::Mmap, Method::Streaming];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Buffered => "buffered",
+            Method::Mmap => "mmap",
+            Method::Streaming => "streaming",
+        }
+    }
+
+    /// The digest of the file at `path` with `algorithm`.
+    pub fn hash_file(self, algorithm: Algorithm, path: &Path) -> io::Result<Vec<u8>> {
+        match self {
+            Method::Buffered => buffers::with_buffer(|buf| {
+                File::open(path)?.read_to_end(buf)?;
+                Ok(algorithm.digest(buf))
+            }),
+            Method::Mmap => Ok(algorithm.digest(&contents::map_file(path)?)),
+            Method::Streaming => buffers::with_buffer(|buf| {
+                let mut file = File::open(path)?;
+                buf.resize(STREAM_CHUNK, 0);
+                let mut hasher = algorithm.hasher();
+                loop {
+                    match file.read(buf) {
+                        Ok(0) => return Ok(hasher.finish()),
+                        Ok(read) => hasher.update(&buf[..read]),
+                        Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+            }),
+        }
+    }
+}
+
+/// The read size of [`Method::Streaming`].
+#[cfg(feature = "bench")]
+pub const STREAM_CHUNK: usize = 64 << 10;
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct MethodResult {
+    pub algorithm: Algorithm,
+    pub method: Method,
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct HashResult {
+    /// The workload's bytes, what every pass reads.
+    pub source_bytes: u64,
+    /// Reading every file without hashing it, the floor the others are measured against.
+    pub read: Duration,
+    pub methods: Vec<MethodResult>,
+    pub failures: Vec<Failure>,
+}
+
+/// Creates the configured workload in `dir_path`, times reading it once without hashing,
+/// then times hashing every file with each of `algorithms` by each [`Method`], evicting the
+/// files from the page cache before every pass when `options.cold_read` is set. Fails if
+/// two methods disagree on a file's digest.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, algorithms: &[Algorithm], options: &Options) -> io::Result<HashResult> {
+    let files = options.workload.files;
+    let mut result = HashResult { source_bytes: files as u64 * options.workload.size() as u64, ..HashResult::default() };
+    options.failures.take("");
+    fs::create_dir_all(dir_path)?;
+    let paths = bench::file_paths(dir_path, files);
+    bench::phase(options, "Hash create", "hash/create", "create", &mut result.failures, || {
+        bench::each_indexed(&paths, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+    })?;
+
+    if options.cold_read {
+        cache::evict_from_cache(&paths)?;
+    }
+    let start = Instant::now();
+    bench::phase(options, "Hash read", "hash/read", "read", &mut result.failures, || {
+        bench::each_indexed(&paths, options, |index, path| buffers::with_buffer(|buf| {
+            File::open(path)?.read_to_end(buf)?;
+            bench::read_done(options, index, path, buf)
+        }))
+    })?;
+    result.read = start.elapsed();
+
+    for &algorithm in algorithms {
+        // The first method to hash a file records its digest for the others to match.
+        let digests: Vec<OnceLock<Vec<u8>>> = paths.iter().map(|_| OnceLock::new()).collect();
+        for method in Method::ALL {
+            if options.cold_read {
+                cache::evict_from_cache(&paths)?;
+            }
+            let start = Instant::now();
+            bench::phase(options, format!("Hash {} {}", algorithm, method.name()), format!("hash/{}/{}", algorithm, method.name()), "hash", &mut result.failures, || {
+                bench::each_indexed(&paths, options, |index, path| {
+                    let digest = method.hash_file(algorithm, path)?;
+                    if *digests[index].get_or_init(|| digest.clone()) != digest {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} {} digest of {} differs from the other methods", algorithm, method.name(), path.display())));
+                    }
+                    Ok(())
+                })
+            })?;
+            result.methods.push(MethodResult { algorithm, method, elapsed: start.elapsed() });
+        }
+    }
+    Ok(result)
+}
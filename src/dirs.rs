@@ -0,0 +1,241 @@
+//! Reaching files relative to open directory descriptors instead of by full path.
+//!
+//! Every `open("/a/b/c/file")` walks each component's dentry again; with the parent held
+//! open, `openat(dirfd, "file")` looks up one name. [`Dirs`] opens the directories of a set
+//! of paths once and then opens, stats or unlinks their files by name. With
+//! [`Resolve::Beneath`] opens go through `openat2` and `RESOLVE_BENEATH`, which also
+//! refuses names that would escape the directory, at the cost of Linux 5.6 or later.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+use crate::atomic;
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+
+pub(crate) fn c_string(bytes: &[u8]) -> io::Result<CString> {
+    CString::new(bytes).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+}
+
+pub(crate) fn check(result: libc::c_int) -> io::Result<()> {
+    if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+/// How a name is resolved from its directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Resolve {
+    /// `openat`.
+    #[default]
+    Openat,
+    /// `openat2` with `RESOLVE_BENEATH`. Linux only.
+    Beneath,
+}
+
+impl std::str::FromStr for Resolve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "openat" => Ok(Resolve::Openat),
+            "openat2" | "beneath" => Ok(Resolve::Beneath),
+            _ => Err(format!("unknown resolve mode '{}', expected openat or openat2", s)),
+        }
+    }
+}
+
+/// What a file is opened for, matching the `std::fs` calls it replaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// `File::open`.
+    Read,
+    /// `OpenOptions::new().read(true).write(true)`.
+    ReadWrite,
+    /// `File::create`: created if missing, truncated if not.
+    Create,
+    /// `OpenOptions::new().write(true).truncate(true)`.
+    Truncate,
+}
+
+impl Access {
+    fn flags(self) -> libc::c_int {
+        let flags = match self {
+            Access::Read => libc::O_RDONLY,
+            Access::ReadWrite => libc::O_RDWR,
+            Access::Create => libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+            Access::Truncate => libc::O_WRONLY | libc::O_TRUNC,
+        };
+        flags | libc::O_CLOEXEC
+    }
+}
+
+/// Mode of created files before the umask, as `File::create` uses.
+const CREATE_MODE: libc::c_uint = 0o666;
+
+/// The directories holding a set of paths, each opened once so files can be reached by name.
+pub struct Dirs {
+    dirs: HashMap<PathBuf, OwnedFd>,
+    resolve: Resolve,
+}
+
+impl Dirs {
+    pub fn open(paths: &[PathBuf]) -> io::Result<Dirs> {
+        let mut dirs = HashMap::new();
+        for path in paths {
+            let dir = atomic::parent(path);
+            if !dirs.contains_key(dir) {
+                dirs.insert(dir.to_path_buf(), OwnedFd::from(File::open(dir)?));
+            }
+        }
+        Ok(Dirs { dirs, resolve: Resolve::default() })
+    }
+
+    /// Resolves names for [`Dirs::open_file`] as `resolve` asks.
+    pub fn resolve(mut self, resolve: Resolve) -> Dirs {
+        self.resolve = resolve;
+        self
+    }
+
+    /// The directory descriptor and file name to reach `path` by. `path` must be in one of
+    /// the directories opened, or it is an error.
+    pub fn entry(&self, path: &Path) -> io::Result<(RawFd, CString)> {
+        let dir = self
+            .dirs
+            .get(atomic::parent(path))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{}'s directory wasn't opened", path.display())))?
+            .as_raw_fd();
+        let name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no file name", path.display())))?;
+        Ok((dir, c_string(name.as_bytes())?))
+    }
+
+    /// Opens the file at `path` relative to its directory.
+    pub fn open_file(&self, path: &Path, access: Access) -> io::Result<File> {
+        let (dir, name) = self.entry(path)?;
+        let fd = match self.resolve {
+            Resolve::Openat => unsafe { libc::openat(dir, name.as_ptr(), access.flags(), CREATE_MODE) },
+            Resolve::Beneath => openat2_beneath(dir, &name, access)?,
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(File::from(unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+
+    /// Removes the file at `path` with `unlinkat`.
+    pub fn unlink(&self, path: &Path) -> io::Result<()> {
+        let (dir, name) = self.entry(path)?;
+        check(unsafe { libc::unlinkat(dir, name.as_ptr(), 0) })
+    }
+}
+
+/// `openat2(dir, name, RESOLVE_BENEATH)`, returning the raw result.
+fn openat2_beneath(dir: RawFd, name: &CString, access: Access) -> io::Result<libc::c_int> {
+    #[cfg(target_os = "linux")]
+    {
+        // `open_how` is non-exhaustive; zeroed is its documented default.
+        let mut how: libc::open_how = unsafe { std::mem::zeroed() };
+        how.flags = access.flags() as u64;
+        how.mode = if access == Access::Create { CREATE_MODE as u64 } else { 0 };
+        how.resolve = libc::RESOLVE_BENEATH;
+        let fd = unsafe { libc::syscall(libc::SYS_openat2, dir, name.as_ptr(), &how as *const libc::open_how, size_of::<libc::open_how>()) };
+        Ok(fd as libc::c_int)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (dir, name, access);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "openat2 is only available on Linux"))
+    }
+}
+
+/// Opens `path` through `dirs` when given, else by its full path.
+pub fn open_file(dirs: Option<&Dirs>, path: &Path, access: Access) -> io::Result<File> {
+    if let Some(dirs) = dirs {
+        return dirs.open_file(path, access);
+    }
+    let mut options = std::fs::OpenOptions::new();
+    match access {
+        Access::Read => options.read(true),
+        Access::ReadWrite => options.read(true).write(true),
+        Access::Create => options.write(true).create(true).truncate(true),
+        Access::Truncate => options.write(true).truncate(true),
+    };
+    options.open(path)
+}
+
+/// Directories the benchmark tree nests its files in when none is given.
+#[cfg(feature = "bench")]
+pub const DEFAULT_DEPTH: usize = 16;
+
+/// How the open phase reached each file.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenPath {
+    /// The full path, walked from the root every time.
+    Absolute,
+    At(Resolve),
+}
+
+#[cfg(feature = "bench")]
+impl OpenPath {
+    pub const ALL: [OpenPath; 3] = [OpenPath::Absolute, OpenPath::At(Resolve::Openat), OpenPath::At(Resolve::Beneath)];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OpenPath::Absolute => "absolute",
+            OpenPath::At(Resolve::Openat) => "openat",
+            OpenPath::At(Resolve::Beneath) => "openat2",
+        }
+    }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Default)]
+pub struct OpenResult {
+    pub times: Vec<(OpenPath, Duration)>,
+    /// Ways of opening that the kernel refused, with why.
+    pub skipped: Vec<(OpenPath, io::Error)>,
+    pub failures: Vec<Failure>,
+}
+
+/// Creates the configured workload `depth` directories below `dir_path`, then times
+/// opening and closing every file by each [`OpenPath`], and deletes it. Only the opens are
+/// timed; for relative opens that includes opening the directory once.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, depth: usize, options: &Options) -> io::Result<OpenResult> {
+    let leaf = (0..depth).fold(dir_path.join("deep"), |dir, level| dir.join(format!("d{}", level)));
+    std::fs::create_dir_all(&leaf)?;
+    let paths = options.workload.paths(&leaf);
+    let mut result = OpenResult::default();
+    options.failures.take("");
+
+    bench::phase(options, "Open create", "open/create", "create", &mut result.failures, || bench::create_files(&paths, options))?;
+
+    for open_path in OpenPath::ALL {
+        let start = Instant::now();
+        let opened = bench::phase(options, format!("Open {}", open_path.name()), format!("open/{}", open_path.name()), open_path.name(), &mut result.failures, || match open_path {
+            OpenPath::Absolute => bench::each_file(&paths, options, |path| open_file(None, path, Access::Read).map(drop)),
+            OpenPath::At(resolve) => Dirs::open(&paths).and_then(|dirs| {
+                let dirs = dirs.resolve(resolve);
+                bench::each_file(&paths, options, |path| dirs.open_file(path, Access::Read).map(drop))
+            }),
+        });
+        match opened {
+            Ok(()) => result.times.push((open_path, start.elapsed())),
+            // openat2 needs Linux 5.6, and seccomp profiles may not know it.
+            Err(e) if e.kind() == io::ErrorKind::Unsupported || e.raw_os_error() == Some(libc::ENOSYS) || e.raw_os_error() == Some(libc::EPERM) => {
+                result.skipped.push((open_path, e));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    bench::phase(options, "Open delete", "open/delete", "delete", &mut result.failures, || bench::delete_files(&paths, options))?;
+    std::fs::remove_dir_all(dir_path.join("deep"))?;
+    Ok(result)
+}
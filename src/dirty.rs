@@ -0,0 +1,269 @@
+//! Dirty-page pressure during write phases. Linux lets written pages sit dirty in the page
+//! cache until their share of memory passes `dirty_background_ratio`, then writes them
+//! back in the background; once it passes halfway between that and `dirty_ratio` (the
+//! free-run limit), the kernel makes every writer sleep in `balance_dirty_pages` until
+//! writeback catches up. Writing ten thousand files fast enough hits that limit partway
+//! through a phase, and the phase's time then depends on when it did.
+//!
+//! A [`Backpressure`] watches `Dirty` and `Writeback` in `/proc/meminfo` before each file
+//! and, above its ceiling, either holds the worker until writeback brings them down
+//! ([`Relief::Pace`]) or starts writeback of each file as soon as it is written
+//! ([`Relief::Flush`]), so the kernel never has to step in. Either way it notes the phases
+//! in which the free-run limit was passed anyway.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::soak::meminfo_bytes;
+
+/// How often `/proc/meminfo` is read, at most, however many files start meanwhile.
+const SAMPLE: Duration = Duration::from_millis(5);
+/// How long a paced worker sleeps between readings.
+const POLL: Duration = Duration::from_millis(10);
+/// The longest one file waits. Below the background threshold the kernel leaves dirty
+/// pages until they expire, half a minute by default, so a low ceiling can't be waited out.
+pub const MAX_WAIT: Duration = Duration::from_secs(1);
+
+/// The kernel's dirty-page thresholds, in bytes, as it computed them from the memory it
+/// considers dirtyable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thresholds {
+    /// Background writeback starts above this.
+    pub background: u64,
+    /// `dirty_ratio` or `dirty_bytes`: writers are stopped outright above this.
+    pub limit: u64,
+}
+
+impl Thresholds {
+    /// The thresholds now, from `/proc/vmstat`.
+    pub fn current() -> Option<Thresholds> {
+        Thresholds::parse(&fs::read_to_string("/proc/vmstat").ok()?, page_size())
+    }
+
+    /// Reads `nr_dirty_background_threshold` and `nr_dirty_threshold`, in pages of
+    /// `page_size` bytes, from the contents of `/proc/vmstat`.
+    pub fn parse(vmstat: &str, page_size: u64) -> Option<Thresholds> {
+        let pages = |field: &str| vmstat.lines().find_map(|line| line.strip_prefix(field)?.strip_prefix(' ')?.trim().parse::<u64>().ok());
+        Some(Thresholds { background: pages("nr_dirty_background_threshold")? * page_size, limit: pages("nr_dirty_threshold")? * page_size })
+    }
+
+    /// Halfway between the two: above it the kernel starts throttling writers.
+    pub fn free_run(&self) -> u64 {
+        (self.background + self.limit) / 2
+    }
+
+    /// Halfway between the background threshold and [`Thresholds::free_run`]: high enough
+    /// that background writeback is running, low enough that writers aren't throttled.
+    pub fn default_ceiling(&self) -> u64 {
+        (self.background + self.free_run()) / 2
+    }
+}
+
+fn page_size() -> u64 {
+    #[cfg(all(unix, feature = "libc"))]
+    {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as u64 }
+    }
+    #[cfg(not(all(unix, feature = "libc")))]
+    {
+        4096
+    }
+}
+
+/// Pages dirty or under writeback, in bytes, from the contents of `/proc/meminfo`.
+pub fn dirty_bytes(meminfo: &str) -> Option<u64> {
+    Some(meminfo_bytes(meminfo, "Dirty")? + meminfo_bytes(meminfo, "Writeback")?)
+}
+
+/// What a [`Backpressure`] does above its ceiling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relief {
+    /// Nothing; only watch and report.
+    Watch,
+    /// Hold each worker before its next file until dirty pages are back under the ceiling,
+    /// for at most [`MAX_WAIT`].
+    Pace,
+    /// Start writeback of each file right after it is written (`sync_file_range` without
+    /// waiting), so dirty pages drain as fast as they are made.
+    Flush,
+}
+
+impl Relief {
+    pub fn name(self) -> &'static str {
+        match self {
+            Relief::Watch => "watch",
+            Relief::Pace => "pace",
+            Relief::Flush => "flush",
+        }
+    }
+}
+
+impl FromStr for Relief {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Relief> {
+        match s {
+            "watch" => Ok(Relief::Watch),
+            "pace" => Ok(Relief::Pace),
+            "flush" => Ok(Relief::Flush),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown backpressure '{}', expected watch|pace|flush", s))),
+        }
+    }
+}
+
+impl fmt::Display for Relief {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Dirty-page pressure shared by every worker of a run.
+#[derive(Debug)]
+pub struct Backpressure {
+    relief: Relief,
+    ceiling: u64,
+    thresholds: Option<Thresholds>,
+    /// Whether the current phase writes; reads and deletes are never held.
+    writing: AtomicBool,
+    dirty: AtomicU64,
+    sampled: Mutex<Instant>,
+    peak: AtomicU64,
+    waited_nanos: AtomicU64,
+    flushed: AtomicUsize,
+    /// The scopes of the phases in which dirty pages passed the free-run limit.
+    throttled: Mutex<Vec<String>>,
+}
+
+impl Backpressure {
+    /// Watches dirty pages against `ceiling`, or [`Thresholds::default_ceiling`] without
+    /// one. Fails where `/proc/meminfo` doesn't say how many there are, or without a
+    /// ceiling where `/proc/vmstat` doesn't give the kernel's thresholds.
+    pub fn new(relief: Relief, ceiling: Option<u64>) -> io::Result<Backpressure> {
+        let unsupported = |what: &str| io::Error::new(io::ErrorKind::Unsupported, format!("dirty-page backpressure needs {}, which Linux provides", what));
+        let dirty = fs::read_to_string("/proc/meminfo").ok().as_deref().and_then(dirty_bytes).ok_or_else(|| unsupported("Dirty and Writeback in /proc/meminfo"))?;
+        if relief == Relief::Flush && !cfg!(all(target_os = "linux", feature = "libc")) {
+            return Err(unsupported("sync_file_range"));
+        }
+        let thresholds = Thresholds::current();
+        let ceiling = match ceiling {
+            Some(ceiling) => ceiling,
+            None => thresholds.ok_or_else(|| unsupported("the dirty thresholds in /proc/vmstat"))?.default_ceiling(),
+        };
+        Ok(Backpressure {
+            relief,
+            ceiling,
+            thresholds,
+            writing: AtomicBool::new(true),
+            dirty: AtomicU64::new(dirty),
+            sampled: Mutex::new(Instant::now()),
+            peak: AtomicU64::new(dirty),
+            waited_nanos: AtomicU64::new(0),
+            flushed: AtomicUsize::new(0),
+            throttled: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn relief(&self) -> Relief {
+        self.relief
+    }
+
+    pub fn ceiling(&self) -> u64 {
+        self.ceiling
+    }
+
+    /// The kernel's thresholds when the run started.
+    pub fn thresholds(&self) -> Option<Thresholds> {
+        self.thresholds
+    }
+
+    /// Marks whether the phase about to run writes; only writing phases are held or flushed.
+    pub fn set_writing(&self, writing: bool) {
+        self.writing.store(writing, Ordering::Relaxed);
+    }
+
+    /// Dirty and writeback bytes, read afresh unless another worker just did. Readings
+    /// over the free-run limit mark `scope` as throttled.
+    fn dirty(&self, scope: &str) -> u64 {
+        if let Ok(mut sampled) = self.sampled.try_lock()
+            && sampled.elapsed() >= SAMPLE
+        {
+            *sampled = Instant::now();
+            if let Some(dirty) = fs::read_to_string("/proc/meminfo").ok().as_deref().and_then(dirty_bytes) {
+                self.dirty.store(dirty, Ordering::Relaxed);
+                self.peak.fetch_max(dirty, Ordering::Relaxed);
+                if let Some(thresholds) = self.thresholds
+                    && dirty > thresholds.free_run()
+                {
+                    let mut throttled = self.throttled.lock().unwrap_or_else(|e| e.into_inner());
+                    if !throttled.iter().any(|seen| seen == scope) {
+                        throttled.push(scope.to_string());
+                    }
+                }
+            }
+        }
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    /// Called before each file of phase `scope`: samples the pressure and, pacing, waits
+    /// while it is above the ceiling.
+    pub fn before(&self, scope: &str) {
+        if !self.writing.load(Ordering::Relaxed) {
+            return;
+        }
+        if self.dirty(scope) <= self.ceiling || self.relief != Relief::Pace {
+            return;
+        }
+        let start = Instant::now();
+        while self.dirty(scope) > self.ceiling && start.elapsed() < MAX_WAIT && !cancel::is_interrupted() {
+            thread::sleep(POLL);
+        }
+        self.waited_nanos.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Called after each file of a writing phase: flushing, starts writeback of `path`
+    /// when dirty pages are above the ceiling. Files that can't be opened by their path,
+    /// such as those inside a container, are left alone.
+    pub fn after(&self, path: &Path) {
+        if self.relief != Relief::Flush || !self.writing.load(Ordering::Relaxed) || self.dirty.load(Ordering::Relaxed) <= self.ceiling {
+            return;
+        }
+        #[cfg(all(target_os = "linux", feature = "libc"))]
+        if let Ok(file) = fs::File::open(path) {
+            use std::os::fd::AsRawFd;
+            if unsafe { libc::sync_file_range(file.as_raw_fd(), 0, 0, libc::SYNC_FILE_RANGE_WRITE) } == 0 {
+                self.flushed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        #[cfg(not(all(target_os = "linux", feature = "libc")))]
+        let _ = path;
+    }
+
+    /// The most dirty and writeback bytes seen at once.
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Time workers spent held, added up over all of them.
+    pub fn waited(&self) -> Duration {
+        Duration::from_nanos(self.waited_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Files whose writeback was started early.
+    pub fn flushed(&self) -> usize {
+        self.flushed.load(Ordering::Relaxed)
+    }
+
+    /// The phases, as `strategy/phase`, in which dirty pages passed the kernel's free-run
+    /// limit, so writers were likely throttled.
+    pub fn throttled(&self) -> Vec<String> {
+        self.throttled.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
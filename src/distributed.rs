@@ -0,0 +1,306 @@
+//! `io agent` and `io orchestrate hosts.toml`: the same workload on several machines at
+//! once, for benchmarking file servers (NFS, SMB, Lustre) under load from many clients.
+//!
+//! An agent listens for an orchestrator and runs what it asks in a fresh directory under
+//! its `--dir`, which may be a local disk or a mount of the shared server. The orchestrator
+//! drives every agent through each strategy's phases in lockstep, with the protocol of
+//! `io bench --processes` (see [`crate::processes`]) carried over TCP, and reports each
+//! host's times next to the phases' wall times over all of them.
+//!
+//! The hosts file is TOML. An optional `global` table sets what every host runs; every
+//! other table is a host, which may override the workload:
+//!
+//! ```toml
+//! [global]
+//! files = 10000
+//! size = "4K"
+//! threads = 8
+//! strategies = ["Smart", "Adaptive"]
+//! args = ["--sync", "data"]
+//!
+//! [client-a]
+//! address = "10.0.0.11:9200"
+//! token = "correct horse battery staple"
+//!
+//! [client-b]
+//! address = "10.0.0.12:9200"
+//! threads = 16
+//! ```
+//!
+//! Keys: `address` (required, `host:port`), `token`, `files`, `size`, `threads`, `args`
+//! (more `io bench` flags), and in `global` only, `strategies`. A host without a `token`
+//! gets `$IO_AGENT_TOKEN`.
+//!
+//! Agents create and delete whatever workload an orchestrator sends them, so they listen
+//! on loopback unless told otherwise, and refuse any other address without a shared
+//! `--token` that every request must carry. The token travels in plain text: it keeps out
+//! strangers on a network you trust, not eavesdroppers on one you don't.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use ::io::bench::{STRATEGIES, Strategy};
+use ::io::cancel;
+use ::io::human::{self, Table};
+use ::io::json::Value;
+use ::io::log::{self, Level};
+use ::io::rundir::{self, RunDir};
+
+use crate::cli::{self, ByteSize, flag_value, invalid_input};
+use crate::job;
+use crate::processes::{self, Worker};
+
+/// Where agents listen unless told otherwise.
+const DEFAULT_ADDRESS: &str = "127.0.0.1:9200";
+
+/// Where agents and orchestrators find the shared token when not given one.
+const TOKEN_VAR: &str = "IO_AGENT_TOKEN";
+
+/// How long an agent waits for a connection's request before dropping it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an agent waits for the orchestrator between phases by default, which must
+/// cover the slowest other host's phase.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How often an idle agent checks for Ctrl-C between connections.
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+
+/// One host of a hosts file, with every setting resolved.
+#[derive(Debug, Clone)]
+struct Host {
+    name: String,
+    address: String,
+    files: usize,
+    size: usize,
+    threads: usize,
+    args: Vec<String>,
+    token: String,
+}
+
+impl Host {
+    fn apply(&mut self, settings: &[(String, Value)], global: bool) -> Result<(), String> {
+        for (key, value) in settings {
+            let count = || match value.as_u64() {
+                Some(n) if n > 0 => Ok(n as usize),
+                _ => Err(format!("{} must be a positive integer", key)),
+            };
+            match key.as_str() {
+                "address" if !global => self.address = value.as_str().ok_or("address must be a string")?.to_string(),
+                "token" => self.token = check_token(value.as_str().ok_or("token must be a string")?)?.to_string(),
+                "files" => self.files = count()?,
+                "threads" => self.threads = count()?,
+                "size" => {
+                    self.size = match value {
+                        Value::String(s) => s.parse::<ByteSize>()?.0,
+                        _ => count()?,
+                    };
+                }
+                "args" => {
+                    let args = value.as_array().ok_or("args must be a list")?;
+                    self.args = args.iter().map(|arg| arg.as_str().map(str::to_string).ok_or("args must be strings")).collect::<Result<_, _>>()?;
+                }
+                "strategies" if global => {}
+                _ => return Err(format!("unknown key '{}'", key)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `token`, unless it can't travel in a request line.
+fn check_token(token: &str) -> Result<&str, String> {
+    match token.contains(['\t', '\r', '\n']) {
+        true => Err("a token can't hold tabs or line breaks".to_string()),
+        false => Ok(token),
+    }
+}
+
+/// Compares tokens without stopping at the first difference.
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The hosts of the file at `path` and the strategies they all run, filling unset keys
+/// from `args`.
+fn load(path: &Path, args: &cli::BenchArgs) -> io::Result<(Vec<Host>, Vec<&'static Strategy>)> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+    let Value::Object(tables) = job::parse_toml(&fs::read_to_string(path)?).map_err(invalid)? else {
+        return Err(invalid("expected a table of hosts".to_string()));
+    };
+    let settings = |name: &str, value: &Value| match value {
+        Value::Object(settings) => Ok(settings.clone()),
+        _ => Err(invalid(format!("'{}' must be a table", name))),
+    };
+    let global = match tables.iter().find(|(name, _)| name == "global") {
+        Some((name, value)) => settings(name, value)?,
+        None => Vec::new(),
+    };
+    let strategies = match global.iter().find(|(key, _)| key == "strategies") {
+        Some((_, names)) => names
+            .as_array()
+            .ok_or_else(|| invalid("strategies must be a list".to_string()))?
+            .iter()
+            .map(|name| name.as_str().ok_or_else(|| "strategies must be strings".to_string()).and_then(job::strategy))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid)?,
+        None => STRATEGIES.iter().collect(),
+    };
+    let token = std::env::var(TOKEN_VAR).unwrap_or_default();
+    check_token(&token).map_err(|e| invalid(format!("${}: {}", TOKEN_VAR, e)))?;
+    let mut hosts = Vec::new();
+    for (name, value) in tables.iter().filter(|(name, _)| name != "global") {
+        let mut host = Host { name: name.clone(), address: String::new(), files: args.files[0], size: args.sizes[0], threads: args.threads[0], args: Vec::new(), token: token.clone() };
+        let own = settings(name, value)?;
+        host.apply(&global, true)
+            .and_then(|()| host.apply(&own, false))
+            .map_err(|e| invalid(format!("[{}] {}", name, e)))?;
+        if host.address.is_empty() {
+            return Err(invalid(format!("[{}] has no address", name)));
+        }
+        hosts.push(host);
+    }
+    if hosts.is_empty() {
+        return Err(invalid("no hosts".to_string()));
+    }
+    Ok((hosts, strategies))
+}
+
+/// Connects to `host`'s agent and asks it to run `strategy`, with the `io bench` flags in
+/// `common` before the host's own.
+fn connect(host: &Host, common: &[String], strategy: &Strategy) -> io::Result<Worker> {
+    let stream = TcpStream::connect(&host.address).map_err(|e| io::Error::new(e.kind(), format!("{} ({}): {}", host.name, host.address, e)))?;
+    stream.set_nodelay(true)?;
+    let mut args: Vec<String> = common.iter().chain(&host.args).cloned().collect();
+    args.extend(["--files", &host.files.to_string(), "--size", &host.size.to_string(), "--threads", &host.threads.to_string(), "--slice", "0/1", "--strategy", strategy.label].map(String::from));
+    let mut worker = Worker::remote(host.name.clone(), Box::new(stream.try_clone()?), Box::new(stream));
+    worker.send(&["run", &host.name, &host.token].into_iter().chain(args.iter().map(String::as_str)).collect::<Vec<_>>().join("\t"))?;
+    Ok(worker)
+}
+
+/// `io orchestrate <hosts.toml> [bench options]`: runs the hosts file's strategies on all
+/// its agents at once, passing the bench options on to every agent.
+pub fn orchestrate(mut args: impl Iterator<Item = String>) -> io::Result<()> {
+    let path = args.next().filter(|arg| !arg.starts_with("--")).ok_or_else(|| invalid_input("io orchestrate needs a hosts file".to_string()))?;
+    let common: Vec<String> = args.collect();
+    let defaults = cli::parse_bench_args(common.iter().cloned())?;
+    let (hosts, strategies) = load(Path::new(&path), &defaults)?;
+    for host in &hosts {
+        println!(
+            "{} at {}: {} files of {} on {} threads",
+            host.name,
+            host.address,
+            human::thousands(host.files as u64),
+            human::bytes(host.size as u64),
+            host.threads
+        );
+    }
+
+    let mut summary = Table::new(["Strategy"].into_iter().chain(cli::PHASE_COLUMNS).chain(["Spread"]));
+    let mut failed = 0;
+    for strategy in strategies {
+        println!("\nRunning {} on {} hosts...", strategy.name, hosts.len());
+        let workers = hosts.iter().map(|host| connect(host, &common, strategy)).collect::<io::Result<Vec<_>>>()?;
+        let result = processes::lockstep(workers)?;
+        failed += result.failures;
+        for (host, times) in hosts.iter().zip(&result.workers) {
+            println!("{} on {}: {}", strategy.label, host.name, cli::phase_summary(times));
+            summary.row([format!("{} on {}", strategy.label, host.name)].into_iter().chain(cli::phase_cells(times)).chain([String::new()]));
+        }
+        summary.row(
+            [format!("{}, all hosts", strategy.label)]
+                .into_iter()
+                .chain(cli::phase_cells(&result.times))
+                .chain([format!("{}x", human::decimal(result.spread(), 2))]),
+        );
+    }
+    println!();
+    print!("{}", summary.render());
+    println!("All-hosts times run from starting a phase everywhere to the last host finishing it; spread is the slowest host's phase time over the fastest's.");
+    if failed > 0 {
+        log::error("distributed", format_args!("{} files failed; the agents logged them", failed));
+    }
+    Ok(())
+}
+
+/// Runs one orchestrator's request on `stream`, in a directory of its own under `base`,
+/// if it carries `token`. The orchestrator is given up on once it is silent for `idle`.
+fn session(stream: TcpStream, base: &Path, session: usize, token: &str, idle: Duration) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(idle))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut fields = request.trim_end_matches(['\r', '\n']).split('\t');
+    let (Some("run"), Some(name), Some(given)) = (fields.next(), fields.next(), fields.next()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected request"));
+    };
+    if !tokens_match(given, token) {
+        let _ = writeln!(writer, "error wrong token");
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "request with the wrong token"));
+    }
+    reader.get_ref().set_read_timeout(Some(idle))?;
+    // Hosts sharing a server share `base`; the name keeps their directories apart.
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+    let dir = RunDir::create(PathBuf::from(format!("{}-{}-{}", rundir::run_dir(base).display(), session, name)))?;
+    let args = fields.map(str::to_string).collect();
+    let mut answer = |line: &str| -> io::Result<()> {
+        writeln!(writer, "{}", line)?;
+        writer.flush()
+    };
+    let result = processes::serve_phases(args, &dir, reader, &mut answer);
+    if let Err(e) = &result {
+        let _ = answer(&format!("error {}", e.to_string().replace('\n', " ")));
+    }
+    result
+}
+
+/// `io agent [--listen <address>] [--dir <dir>] [--token <token>] [--idle-timeout <secs>]`:
+/// serves orchestrators one at a time until interrupted. Listening anywhere but loopback
+/// needs a token, from `--token` or `$IO_AGENT_TOKEN`.
+pub fn agent(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let (mut address, mut base) = (DEFAULT_ADDRESS.to_string(), std::env::temp_dir());
+    let (mut token, mut idle) = (std::env::var(TOKEN_VAR).unwrap_or_default(), IDLE_TIMEOUT);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => address = flag_value(&mut args, &arg)?,
+            "--dir" => base = flag_value(&mut args, &arg)?,
+            "--token" => token = flag_value(&mut args, &arg)?,
+            "--idle-timeout" => idle = Duration::from_secs(flag_value::<u64>(&mut args, &arg)?.max(1)),
+            _ => return Err(invalid_input(format!("unknown argument: {}", arg))),
+        }
+    }
+    check_token(&token).map_err(invalid_input)?;
+    let listener = TcpListener::bind(&address)?;
+    let local = listener.local_addr()?;
+    if token.is_empty() && !local.ip().is_loopback() {
+        return Err(invalid_input(format!("refusing to listen on {} without a token; give --token or set ${}", local, TOKEN_VAR)));
+    }
+    println!("Agent listening on {}, running in {}", local, base.display());
+    // Polled, so Ctrl-C stops an agent that no orchestrator is talking to.
+    listener.set_nonblocking(true)?;
+    let mut runs = 0;
+    while !cancel::is_interrupted() {
+        let (stream, peer) = match listener.accept() {
+            Ok(accepted) => accepted,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        stream.set_nonblocking(false)?;
+        runs += 1;
+        println!("Run {} for {}", runs, peer);
+        if let Err(e) = session(stream, &base, runs - 1, &token, idle) {
+            log::event(Level::Error, "distributed", &format!("run {} failed: {}", runs, e), &[("peer", peer.to_string())]);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,229 @@
+//! Owns the execution backends used by the benchmarks and lets callers pay their
+//! start-up cost before any timed work.
+//!
+//! By default an engine builds a rayon pool of its own. An application that already has
+//! one hands it over with [`Workers::Shared`], or runs on rayon's global pool with
+//! [`Workers::Global`], so embedding the crate doesn't add a second set of threads; the
+//! engine then leaves those threads as it found them, unpinned and at their priority.
+
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::pinning::{self, NumaPlacement};
+use crate::platform;
+use crate::priority::Priority;
+
+/// Time spent bringing each backend up, reported separately from the measured phases.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WarmUp {
+    pub rayon: Duration,
+    pub pinned_threads: usize,
+    /// Workers whose memory policy follows [`Engine::numa`].
+    pub memory_bound: usize,
+    pub tokio: Option<Duration>,
+}
+
+/// Where an [`Engine`]'s rayon workers come from.
+#[derive(Debug, Clone, Default)]
+pub enum Workers {
+    /// A pool of the engine's own, with its `threads` workers pinned, placed and
+    /// prioritized as configured.
+    #[default]
+    Dedicated,
+    /// rayon's global pool, as the application configured it.
+    Global,
+    /// A pool the application built, shared with the rest of it.
+    Shared(Arc<rayon::ThreadPool>),
+}
+
+pub struct Engine {
+    threads: usize,
+    pin: bool,
+    numa: Option<NumaPlacement>,
+    priority: Priority,
+    tokio: bool,
+    workers: Workers,
+    pool: Option<Arc<rayon::ThreadPool>>,
+    #[cfg(feature = "tokio")]
+    runtime: Option<tokio::runtime::Runtime>,
+    warm_up: Option<WarmUp>,
+}
+
+impl Engine {
+    /// A rayon-backed engine with `threads` workers, each pinned to its own core among
+    /// those the process may use.
+    pub fn new(threads: usize) -> Engine {
+        Engine {
+            threads: threads.max(1),
+            pin: true,
+            numa: None,
+            priority: Priority::default(),
+            tokio: false,
+            workers: Workers::Dedicated,
+            pool: None,
+            #[cfg(feature = "tokio")]
+            runtime: None,
+            warm_up: None,
+        }
+    }
+
+    pub fn pinned(mut self, pin: bool) -> Engine {
+        self.pin = pin;
+        self
+    }
+
+    /// Keeps the workers on one NUMA node, or deals them over all nodes, and binds the
+    /// memory they allocate the same way; see [`crate::pinning`].
+    pub fn numa(mut self, placement: Option<NumaPlacement>) -> Engine {
+        self.numa = placement;
+        self
+    }
+
+    /// Runs every worker, the tokio runtime's included, at this nice level and I/O class;
+    /// see [`crate::priority`]. `warm_up` fails if the kernel refuses it.
+    pub fn priority(mut self, priority: Priority) -> Engine {
+        self.priority = priority;
+        self
+    }
+
+    /// Also brings up a tokio runtime with the same number of workers. Requires the
+    /// `tokio` feature; without it `warm_up` fails when this is enabled.
+    pub fn with_tokio(mut self, enabled: bool) -> Engine {
+        self.tokio = enabled;
+        self
+    }
+
+    /// Runs on a pool the application already has instead of building one; the engine's
+    /// thread count becomes that pool's. Pinning is skipped, and `warm_up` fails if a NUMA
+    /// placement or priority is set, since either would change threads the engine doesn't own.
+    pub fn workers(mut self, workers: Workers) -> Engine {
+        self.workers = workers;
+        self
+    }
+
+    /// The number of rayon workers; for [`Workers::Global`], the global pool's.
+    pub fn threads(&self) -> usize {
+        match &self.workers {
+            Workers::Dedicated => self.threads,
+            Workers::Global => rayon::current_num_threads(),
+            Workers::Shared(pool) => pool.current_num_threads(),
+        }
+    }
+
+    /// Spawns and pins every worker thread and builds the selected runtimes. Calling it
+    /// again is free and returns the first measurement.
+    pub fn warm_up(&mut self) -> io::Result<WarmUp> {
+        if let Some(warm_up) = self.warm_up {
+            return Ok(warm_up);
+        }
+
+        if !matches!(self.workers, Workers::Dedicated) {
+            if self.numa.is_some() || self.priority.is_set() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "NUMA placement and priority need a dedicated pool"));
+            }
+            self.pool = match &self.workers {
+                Workers::Shared(pool) => Some(Arc::clone(pool)),
+                _ => None,
+            };
+            let warm_up = WarmUp { tokio: self.start_tokio()?, ..WarmUp::default() };
+            self.warm_up = Some(warm_up);
+            return Ok(warm_up);
+        }
+
+        let start = Instant::now();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .map_err(io::Error::other)?;
+        let (pinned, memory_bound) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let nodes = pinning::numa_nodes();
+        let cpus = pinning::worker_cpus(self.numa, &nodes, &platform::allowed_cpus())?;
+        // broadcast runs exactly once on every worker, so each thread is started and pinned.
+        let prioritized = pool.broadcast(|ctx| {
+            if self.pin && pinning::pin_thread(cpus[ctx.index() % cpus.len()]).is_ok() {
+                pinned.fetch_add(1, Ordering::Relaxed);
+            }
+            if let Some(placement) = self.numa
+                && pinning::bind_memory(placement, &nodes).is_ok()
+            {
+                memory_bound.fetch_add(1, Ordering::Relaxed);
+            }
+            self.priority.apply()
+        });
+        prioritized.into_iter().try_for_each(|applied| applied)?;
+        let rayon = start.elapsed();
+        self.pool = Some(Arc::new(pool));
+        let warm_up = WarmUp {
+            rayon,
+            pinned_threads: pinned.into_inner(),
+            memory_bound: memory_bound.into_inner(),
+            tokio: self.start_tokio()?,
+        };
+        self.warm_up = Some(warm_up);
+        Ok(warm_up)
+    }
+
+    #[cfg(feature = "tokio")]
+    fn start_tokio(&mut self) -> io::Result<Option<Duration>> {
+        if !self.tokio {
+            return Ok(None);
+        }
+        let start = Instant::now();
+        let (priority, threads) = (self.priority, self.threads());
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(threads)
+            // The rayon workers already showed the kernel accepts it.
+            .on_thread_start(move || {
+                let _ = priority.apply();
+            })
+            .build()?;
+        // Blocking threads are spawned on demand; start one per worker up front.
+        runtime.block_on(async {
+            let tasks: Vec<_> = (0..threads).map(|_| tokio::task::spawn_blocking(|| ())).collect();
+            for task in tasks {
+                task.await.map_err(io::Error::other)?;
+            }
+            Ok::<(), io::Error>(())
+        })?;
+        self.runtime = Some(runtime);
+        Ok(Some(start.elapsed()))
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    fn start_tokio(&mut self) -> io::Result<Option<Duration>> {
+        if self.tokio {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "built without the tokio feature"));
+        }
+        Ok(None)
+    }
+
+    /// Runs `f` inside the rayon pool, warming the engine up first if that hasn't happened.
+    /// On [`Workers::Global`] `f` runs on the calling thread, and its parallel work goes
+    /// wherever the caller's would.
+    pub fn install<F, T>(&mut self, f: F) -> io::Result<T>
+    where
+        F: FnOnce() -> io::Result<T> + Send,
+        T: Send,
+    {
+        match self.pool()? {
+            Some(pool) => pool.install(f),
+            None => f(),
+        }
+    }
+
+    /// The warmed-up rayon pool, for callers that start work on it from several threads;
+    /// `None` on [`Workers::Global`], whose work needs no pool to be installed into.
+    pub fn pool(&mut self) -> io::Result<Option<&rayon::ThreadPool>> {
+        self.warm_up()?;
+        Ok(self.pool.as_deref())
+    }
+
+    /// The tokio runtime, if this engine was configured with one.
+    #[cfg(feature = "tokio")]
+    pub fn runtime(&mut self) -> io::Result<Option<&tokio::runtime::Runtime>> {
+        self.warm_up()?;
+        Ok(self.runtime.as_ref())
+    }
+}
@@ -0,0 +1,240 @@
+//! What this process may actually do where it runs. Containers and sandboxes refuse
+//! io_uring through seccomp, keep `/proc/sys` read only, pin nothing, and sit on overlay
+//! filesystems that ignore `POSIX_FADV_DONTNEED` or `O_DIRECT`; a run that finds out half
+//! way through fails or, worse, reports warm reads as cold ones.
+//!
+//! [`Environment::detect`] tries each interface once, in a scratch file in the benchmark
+//! directory where the filesystem matters, [`Environment::degrade`] turns off the options
+//! that can't work, and [`Environment::notes`] says what was found for the report.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::bench::Options;
+use crate::cgroup::Limits;
+use crate::dirs::{Access, Dirs, Resolve};
+use crate::{cache, pinning, platform};
+
+/// Bytes written to check that eviction empties the page cache.
+const EVICTION_PROBE_LEN: usize = 256 * 1024;
+
+/// An interface some option or subcommand relies on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// `io_uring_setup`, for `io bench uring`, `linked` and the batched `statx`.
+    IoUring,
+    /// `sched_setaffinity`, for pinning the workers.
+    Pinning,
+    /// `POSIX_FADV_DONTNEED` dropping a file's pages, for `--cold`.
+    Eviction,
+    /// Writing `/proc/sys/vm/drop_caches`, for dropping the whole page cache.
+    DropCaches,
+    /// `O_DIRECT` on the benchmark directory's filesystem.
+    DirectIo,
+    /// `openat2` with `RESOLVE_BENEATH`, for `--open-at beneath`.
+    Openat2,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 6] = [Capability::IoUring, Capability::Pinning, Capability::Eviction, Capability::DropCaches, Capability::DirectIo, Capability::Openat2];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::IoUring => "io_uring",
+            Capability::Pinning => "pinning",
+            Capability::Eviction => "eviction",
+            Capability::DropCaches => "drop_caches",
+            Capability::DirectIo => "O_DIRECT",
+            Capability::Openat2 => "openat2",
+        }
+    }
+
+    /// Tries the interface, `Err` saying why it isn't available.
+    fn check(self, dir: &Path) -> Result<(), String> {
+        match self {
+            Capability::IoUring => io_uring(),
+            Capability::Pinning => pinning(),
+            Capability::Eviction => eviction(dir).map_err(|e| e.to_string())?,
+            Capability::DropCaches => drop_caches(),
+            Capability::DirectIo => direct_io(dir).map_err(|e| e.to_string())?,
+            Capability::Openat2 => openat2(dir).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    /// The container runtime this process seems to run under.
+    pub container: Option<String>,
+    /// A seccomp filter is installed on this process.
+    pub seccomp: bool,
+    /// The cgroup's CPU quota and I/O limits.
+    pub cgroup: Option<Limits>,
+    /// What isn't available, with why.
+    pub unavailable: Vec<(Capability, String)>,
+}
+
+impl Environment {
+    /// Tries every [`Capability`], those that depend on the filesystem in `dir`.
+    pub fn detect(dir: &Path) -> Environment {
+        Environment {
+            container: container(),
+            seccomp: seccomp(),
+            cgroup: Limits::detect(),
+            unavailable: Capability::ALL.into_iter().filter_map(|capability| capability.check(dir).err().map(|why| (capability, why))).collect(),
+        }
+    }
+
+    pub fn has(&self, capability: Capability) -> bool {
+        !self.unavailable.iter().any(|(missing, _)| *missing == capability)
+    }
+
+    fn why(&self, capability: Capability) -> &str {
+        self.unavailable.iter().find(|(missing, _)| *missing == capability).map_or("", |(_, why)| why)
+    }
+
+    /// Turns off what `options` asks for that can't work here, returning a line for each
+    /// change: cold reads without eviction would read from the page cache, and
+    /// `RESOLVE_BENEATH` without `openat2` falls back to plain `openat`.
+    pub fn degrade(&self, options: &mut Options) -> Vec<String> {
+        let mut changes = Vec::new();
+        if options.cold_read && !self.has(Capability::Eviction) {
+            options.cold_read = false;
+            changes.push(format!("--cold turned off: {}", self.why(Capability::Eviction)));
+        }
+        if options.open_at == Some(Resolve::Beneath) && !self.has(Capability::Openat2) {
+            options.open_at = Some(Resolve::Openat);
+            changes.push(format!("--open-at beneath runs as openat: {}", self.why(Capability::Openat2)));
+        }
+        changes
+    }
+
+    /// One line for the container and seccomp, when either applies, one per cgroup limit
+    /// and one per missing capability.
+    pub fn notes(&self) -> Vec<String> {
+        let mut notes = Vec::new();
+        match (&self.container, self.seccomp) {
+            (Some(container), true) => notes.push(format!("running in a {} container with a seccomp filter", container)),
+            (Some(container), false) => notes.push(format!("running in a {} container", container)),
+            (None, true) => notes.push("running with a seccomp filter".to_string()),
+            (None, false) => {}
+        }
+        notes.extend(self.cgroup.iter().flat_map(Limits::notes));
+        notes.extend(self.unavailable.iter().map(|(capability, why)| format!("no {}: {}", capability, why)));
+        notes
+    }
+}
+
+/// The container runtime, from the markers runtimes leave behind.
+fn container() -> Option<String> {
+    if let Ok(name) = std::env::var("container")
+        && !name.is_empty()
+    {
+        return Some(name);
+    }
+    if std::env::var_os("KUBERNETES_SERVICE_HOST").is_some() {
+        return Some("kubernetes".to_string());
+    }
+    if Path::new("/.dockerenv").exists() {
+        return Some("docker".to_string());
+    }
+    if Path::new("/run/.containerenv").exists() {
+        return Some("podman".to_string());
+    }
+    let cgroup = fs::read_to_string("/proc/1/cgroup").unwrap_or_default();
+    ["kubepods", "docker", "containerd", "lxc"].into_iter().find(|runtime| cgroup.contains(runtime)).map(str::to_string)
+}
+
+/// Whether `/proc/self/status` reports seccomp filter mode.
+fn seccomp() -> bool {
+    let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+    status.lines().any(|line| line.strip_prefix("Seccomp:").is_some_and(|mode| mode.trim() == "2"))
+}
+
+fn io_uring() -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    return crate::uring::unavailable().map_or(Ok(()), |e| Err(e.to_string()));
+    #[cfg(not(target_os = "linux"))]
+    Err("io_uring is Linux only".to_string())
+}
+
+/// Pins a short-lived thread, so no thread of the caller's is left pinned.
+fn pinning() -> Result<(), String> {
+    if !cfg!(target_os = "linux") {
+        return Err("thread affinity is Linux only".to_string());
+    }
+    let cpu = platform::allowed_cpus().first().copied().unwrap_or(0);
+    let pinned = thread::spawn(move || pinning::pin_thread(cpu)).join().unwrap_or_else(|_| Err(io::Error::other("the pinning thread panicked")));
+    pinned.map_err(|e| format!("sched_setaffinity failed: {}", e))
+}
+
+/// A scratch file in `dir`, removed when dropped.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(dir: &Path, name: &str) -> Scratch {
+        Scratch(dir.join(format!(".io-environment-{}-{}", name, std::process::id())))
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Writes a file, syncs it and checks eviction leaves none of it cached.
+fn eviction(dir: &Path) -> io::Result<Result<(), String>> {
+    let scratch = Scratch::new(dir, "evict");
+    let mut file = File::create(&scratch.0)?;
+    file.write_all(&vec![1; EVICTION_PROBE_LEN])?;
+    file.sync_all()?;
+    let remaining = cache::evict_from_cache(&[&scratch.0])?;
+    Ok(match remaining.resident_pages {
+        0 => Ok(()),
+        pages => Err(format!("{} of {} pages stayed cached after POSIX_FADV_DONTNEED", pages, remaining.total_pages)),
+    })
+}
+
+/// Opens `drop_caches` for writing without writing to it.
+fn drop_caches() -> Result<(), String> {
+    if !cfg!(target_os = "linux") {
+        return Err("/proc/sys/vm/drop_caches is Linux only".to_string());
+    }
+    OpenOptions::new().write(true).open("/proc/sys/vm/drop_caches").map(drop).map_err(|e| format!("/proc/sys/vm/drop_caches isn't writable: {}", e))
+}
+
+fn direct_io(dir: &Path) -> io::Result<Result<(), String>> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let scratch = Scratch::new(dir, "direct");
+        match OpenOptions::new().write(true).create(true).truncate(true).custom_flags(libc::O_DIRECT).open(&scratch.0) {
+            Ok(_) => Ok(Ok(())),
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => Ok(Err("the filesystem refuses O_DIRECT opens".to_string())),
+            Err(e) => Err(e),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = dir;
+        Ok(Err("O_DIRECT is checked on Linux only".to_string()))
+    }
+}
+
+fn openat2(dir: &Path) -> io::Result<()> {
+    let scratch = Scratch::new(dir, "openat2");
+    File::create(&scratch.0)?;
+    let paths = [scratch.0.clone()];
+    Dirs::open(&paths)?.resolve(Resolve::Beneath).open_file(&scratch.0, Access::Read).map(drop)
+}
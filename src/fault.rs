@@ -0,0 +1,216 @@
+//! Fault injection for testing error handling: a [`FaultInjector`] wraps files, or any
+//! reader or writer, in [`Faulty`], which fails the way a sick disk does on a schedule
+//! fixed by [`Faults`]. Callers check that their retries, cleanup and error messages hold
+//! up without needing a full disk or a failing drive.
+//!
+//! Faults are drawn from a seeded generator, so one thread calling through one injector
+//! sees the same faults on every run. The written-bytes budget behind
+//! [`Faults::no_space_after`] is shared by everything the injector wraps, as a disk's free
+//! space is. Errors carry the same OS codes real syscalls would (`ENOSPC`, `EINTR`, `EIO`)
+//! where the `libc` feature provides them.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::population::Rng;
+
+/// Which faults to inject, and how often. The default injects none.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Faults {
+    /// Writes fail with `ENOSPC` once this many bytes have been written in all; the write
+    /// that crosses it is cut short.
+    pub no_space_after: Option<u64>,
+    /// Chance each read or write fails with `EINTR` before doing anything, which callers
+    /// should retry.
+    pub interrupt: f64,
+    /// Chance each read or write fails with `EIO`.
+    pub io_error: f64,
+    /// Added to every read and write.
+    pub delay: Option<Duration>,
+    pub seed: u64,
+}
+
+impl Default for Faults {
+    fn default() -> Faults {
+        Faults { no_space_after: None, interrupt: 0.0, io_error: 0.0, delay: None, seed: 0xfa17 }
+    }
+}
+
+/// Injected faults counted by kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Injected {
+    pub no_space: u64,
+    pub interrupts: u64,
+    pub io_errors: u64,
+}
+
+#[derive(Debug)]
+pub struct FaultInjector {
+    faults: Faults,
+    rng: Mutex<Rng>,
+    written: AtomicU64,
+    no_space: AtomicU64,
+    interrupts: AtomicU64,
+    io_errors: AtomicU64,
+}
+
+impl FaultInjector {
+    pub fn new(faults: Faults) -> FaultInjector {
+        // Xorshift never leaves zero.
+        let seed = if faults.seed == 0 { Faults::default().seed } else { faults.seed };
+        FaultInjector {
+            faults,
+            rng: Mutex::new(Rng(seed)),
+            written: AtomicU64::new(0),
+            no_space: AtomicU64::new(0),
+            interrupts: AtomicU64::new(0),
+            io_errors: AtomicU64::new(0),
+        }
+    }
+
+    pub fn faults(&self) -> Faults {
+        self.faults
+    }
+
+    pub fn wrap<T>(&self, inner: T) -> Faulty<'_, T> {
+        Faulty { injector: self, inner }
+    }
+
+    /// Bytes written through the injector so far.
+    pub fn written(&self) -> u64 {
+        self.written.load(Ordering::Relaxed)
+    }
+
+    pub fn injected(&self) -> Injected {
+        Injected {
+            no_space: self.no_space.load(Ordering::Relaxed),
+            interrupts: self.interrupts.load(Ordering::Relaxed),
+            io_errors: self.io_errors.load(Ordering::Relaxed),
+        }
+    }
+
+    fn chance(&self, probability: f64) -> bool {
+        if probability <= 0.0 {
+            return false;
+        }
+        let draw = self.rng.lock().unwrap_or_else(|e| e.into_inner()).next();
+        ((draw >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    /// The fault every operation may hit, checked before it runs; callers with operations
+    /// of their own, such as `open` or `rename`, can call it too.
+    pub fn before_op(&self) -> io::Result<()> {
+        if let Some(delay) = self.faults.delay {
+            thread::sleep(delay);
+        }
+        if self.chance(self.faults.interrupt) {
+            self.interrupts.fetch_add(1, Ordering::Relaxed);
+            return Err(Fault::Interrupt.error());
+        }
+        if self.chance(self.faults.io_error) {
+            self.io_errors.fetch_add(1, Ordering::Relaxed);
+            return Err(Fault::Io.error());
+        }
+        Ok(())
+    }
+
+    /// How much of a `len`-byte write fits in the budget, failing if none does.
+    fn reserve(&self, len: usize) -> io::Result<usize> {
+        let Some(limit) = self.faults.no_space_after else {
+            self.written.fetch_add(len as u64, Ordering::Relaxed);
+            return Ok(len);
+        };
+        let mut granted = 0;
+        let reserved = self.written.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |written| {
+            granted = limit.saturating_sub(written).min(len as u64);
+            (granted > 0).then_some(written + granted)
+        });
+        match reserved {
+            Ok(_) => Ok(granted as usize),
+            Err(_) if len == 0 => Ok(0),
+            Err(_) => {
+                self.no_space.fetch_add(1, Ordering::Relaxed);
+                Err(Fault::NoSpace.error())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Fault {
+    NoSpace,
+    Interrupt,
+    Io,
+}
+
+impl Fault {
+    fn error(self) -> io::Error {
+        #[cfg(all(unix, feature = "libc"))]
+        {
+            let code = match self {
+                Fault::NoSpace => libc::ENOSPC,
+                Fault::Interrupt => libc::EINTR,
+                Fault::Io => libc::EIO,
+            };
+            io::Error::from_raw_os_error(code)
+        }
+        #[cfg(not(all(unix, feature = "libc")))]
+        {
+            let (kind, name) = match self {
+                Fault::NoSpace => (io::ErrorKind::StorageFull, "ENOSPC"),
+                Fault::Interrupt => (io::ErrorKind::Interrupted, "EINTR"),
+                Fault::Io => (io::ErrorKind::Other, "EIO"),
+            };
+            io::Error::new(kind, format!("injected {}", name))
+        }
+    }
+}
+
+/// A file, reader or writer failing as its [`FaultInjector`] decides.
+#[derive(Debug)]
+pub struct Faulty<'a, T> {
+    injector: &'a FaultInjector,
+    inner: T,
+}
+
+impl<T> Faulty<'_, T> {
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Read> Read for Faulty<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.injector.before_op()?;
+        self.inner.read(buf)
+    }
+}
+
+impl<T: Write> Write for Faulty<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.injector.before_op()?;
+        let len = self.injector.reserve(buf.len())?;
+        let written = self.inner.write(&buf[..len]);
+        // Give back what the inner writer didn't take.
+        let unused = len - written.as_ref().map_or(0, |&n| n);
+        self.injector.written.fetch_sub(unused as u64, Ordering::Relaxed);
+        written
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: Seek> Seek for Faulty<'_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
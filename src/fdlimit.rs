@@ -0,0 +1,126 @@
+//! Keeps the number of simultaneously open file descriptors under the process limit.
+
+use std::fmt;
+use std::io;
+use std::sync::{Condvar, Mutex};
+
+/// A counting semaphore bounding how many files the bulk phases hold open at once.
+pub struct OpenFileLimiter {
+    max: usize,
+    open: Mutex<usize>,
+    released: Condvar,
+}
+
+/// Returned by [`OpenFileLimiter::acquire`]; frees the slot when dropped.
+pub struct OpenFilePermit<'a> {
+    limiter: &'a OpenFileLimiter,
+}
+
+impl OpenFileLimiter {
+    pub fn new(max: usize) -> OpenFileLimiter {
+        OpenFileLimiter {
+            max: max.max(1),
+            open: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn unlimited() -> OpenFileLimiter {
+        OpenFileLimiter::new(usize::MAX)
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Blocks until a descriptor slot is free.
+    pub fn acquire(&self) -> OpenFilePermit<'_> {
+        if self.max != usize::MAX {
+            let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+            while *open >= self.max {
+                open = self.released.wait(open).unwrap_or_else(|e| e.into_inner());
+            }
+            *open += 1;
+        }
+        OpenFilePermit { limiter: self }
+    }
+}
+
+impl Drop for OpenFilePermit<'_> {
+    fn drop(&mut self) {
+        if self.limiter.max != usize::MAX {
+            *self.limiter.open.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+            self.limiter.released.notify_one();
+        }
+    }
+}
+
+impl Default for OpenFileLimiter {
+    fn default() -> Self {
+        OpenFileLimiter::unlimited()
+    }
+}
+
+impl fmt::Debug for OpenFileLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpenFileLimiter").field("max", &self.max).finish()
+    }
+}
+
+/// Soft and hard `RLIMIT_NOFILE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoFileLimit {
+    pub soft: u64,
+    pub hard: u64,
+}
+
+#[cfg(all(unix, feature = "libc"))]
+#[allow(clippy::unnecessary_cast)] // rlim_t is 32-bit on some targets
+pub fn nofile_limit() -> io::Result<NoFileLimit> {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(NoFileLimit { soft: limit.rlim_cur as u64, hard: limit.rlim_max as u64 })
+}
+
+/// Raises the soft limit to the hard limit and returns the new limits.
+#[cfg(all(unix, feature = "libc"))]
+#[allow(clippy::unnecessary_cast)]
+pub fn raise_nofile_limit() -> io::Result<NoFileLimit> {
+    let current = nofile_limit()?;
+    if current.soft < current.hard {
+        let limit = libc::rlimit { rlim_cur: current.hard as libc::rlim_t, rlim_max: current.hard as libc::rlim_t };
+        if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    nofile_limit()
+}
+
+/// Descriptors kept back for stdio, the pool's own handles and whatever the host application holds.
+pub const RESERVED_FDS: u64 = 64;
+
+/// How many files the bulk phases may keep open given `limit`.
+pub fn open_file_budget(limit: NoFileLimit) -> usize {
+    limit.soft.saturating_sub(RESERVED_FDS).clamp(1, usize::MAX as u64) as usize
+}
+
+/// Rewrites `EMFILE`/`ENFILE` into an error that says which limit was hit and what to do.
+pub fn explain(err: io::Error, limiter: &OpenFileLimiter) -> io::Error {
+    #[cfg(all(unix, feature = "libc"))]
+    if matches!(err.raw_os_error(), Some(libc::EMFILE) | Some(libc::ENFILE)) {
+        let limit = nofile_limit().map_or_else(|_| "unknown".to_string(), |l| l.soft.to_string());
+        return io::Error::new(
+            err.kind(),
+            format!(
+                "{} (RLIMIT_NOFILE soft limit {}, at most {} files open at once); raise the limit with --raise-nofile or `ulimit -n`, or lower --threads",
+                err,
+                limit,
+                limiter.max()
+            ),
+        );
+    }
+    let _ = limiter;
+    err
+}
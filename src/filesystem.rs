@@ -0,0 +1,174 @@
+//! What a benchmark directory is stored on: filesystem type, mount options, block device
+//! and free space, since the same run means something different on tmpfs, ext4, btrfs or
+//! NFS.
+//!
+//! The mount comes from `/proc/self/mountinfo` (Linux), matched by the directory's device
+//! number so bind mounts and nested mounts resolve to the right entry; space comes from
+//! `statvfs`. Anything that can't be read is left out rather than guessed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::health::Device;
+use crate::json::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filesystem {
+    /// Such as `ext4`, `tmpfs` or `nfs4`.
+    pub fs_type: String,
+    pub mount_point: PathBuf,
+    /// What was mounted: a device node, a server export, or a name like `tmpfs`.
+    pub source: String,
+    /// Per-mount options followed by the filesystem's own, such as `rw,relatime,discard`.
+    pub options: String,
+    /// The whole block device, for filesystems that have one.
+    pub device: Option<Device>,
+    /// Whether the device spins; `None` without a device or when sysfs doesn't say.
+    pub rotational: Option<bool>,
+    /// Bytes available to unprivileged users, and the filesystem's size.
+    pub available: Option<u64>,
+    pub total: Option<u64>,
+    /// The block size the filesystem prefers for I/O.
+    pub block_size: Option<u64>,
+}
+
+impl Filesystem {
+    /// The filesystem holding `path`, or `None` off Linux or without `/proc`.
+    pub fn of(path: &Path) -> Option<Filesystem> {
+        let mount = find_mount(path)?;
+        let device = Device::of(path);
+        let rotational = device.as_ref().and_then(|device| fs::read_to_string(device.sysfs.join("queue/rotational")).ok()).map(|flag| flag.trim() == "1");
+        let (available, total, block_size) = match space(path) {
+            Some((available, total, block_size)) => (Some(available), Some(total), Some(block_size)),
+            None => (None, None, None),
+        };
+        Some(Filesystem { fs_type: mount.fs_type, mount_point: mount.mount_point, source: mount.source, options: mount.options, device, rotational, available, total, block_size })
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::object()
+            .with("type", self.fs_type.as_str())
+            .with("mount_point", self.mount_point.to_string_lossy().into_owned())
+            .with("source", self.source.as_str())
+            .with("options", self.options.as_str())
+            .with("device", self.device.as_ref().map(|device| device.name.as_str()))
+            .with("rotational", self.rotational)
+            .with("available_bytes", self.available)
+            .with("total_bytes", self.total)
+            .with("block_size", self.block_size)
+    }
+
+    /// The fields [`to_json`](Filesystem::to_json) wrote, or `None` if `value` isn't such an
+    /// object. The device is only known by name, so its sysfs directory is a guess.
+    pub fn from_json(value: &Value) -> Option<Filesystem> {
+        let string = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        let device = string("device").map(|name| Device { sysfs: Path::new("/sys/block").join(&name), name });
+        Some(Filesystem {
+            fs_type: string("type")?,
+            mount_point: PathBuf::from(string("mount_point")?),
+            source: string("source").unwrap_or_default(),
+            options: string("options").unwrap_or_default(),
+            device,
+            rotational: value.get("rotational").and_then(Value::as_bool),
+            available: value.get("available_bytes").and_then(Value::as_u64),
+            total: value.get("total_bytes").and_then(Value::as_u64),
+            block_size: value.get("block_size").and_then(Value::as_u64),
+        })
+    }
+}
+
+struct Mount {
+    fs_type: String,
+    mount_point: PathBuf,
+    source: String,
+    options: String,
+}
+
+/// The mountinfo entry for `path`: the one with its device number and the longest mount
+/// point above it, or by mount point alone where device numbers don't match (btrfs
+/// subvolumes report their own).
+fn find_mount(path: &Path) -> Option<Mount> {
+    let path = fs::canonicalize(path).ok()?;
+    let text = fs::read_to_string("/proc/self/mountinfo").ok()?;
+    let dev = device_number(&path);
+    let mounts: Vec<(String, Mount)> = text.lines().filter_map(parse_mountinfo).filter(|(_, mount)| path.starts_with(&mount.mount_point)).collect();
+    let longest = |mounts: Vec<(String, Mount)>| mounts.into_iter().max_by_key(|(_, mount)| mount.mount_point.as_os_str().len()).map(|(_, mount)| mount);
+    let (matching, others): (Vec<_>, Vec<_>) = mounts.into_iter().partition(|(number, _)| Some(number) == dev.as_ref());
+    longest(matching).or_else(|| longest(others))
+}
+
+/// `major:minor` of the device `path` is on, as mountinfo writes it.
+fn device_number(path: &Path) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let dev = fs::metadata(path).ok()?.dev();
+        Some(format!("{}:{}", ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff), (dev & 0xff) | ((dev >> 12) & !0xff)))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// One line of `/proc/self/mountinfo`: `id parent major:minor root mount_point options
+/// [optional fields...] - type source super_options`.
+fn parse_mountinfo(line: &str) -> Option<(String, Mount)> {
+    let (before, after) = line.split_once(" - ")?;
+    let fields: Vec<&str> = before.split(' ').collect();
+    let mut after = after.split(' ');
+    let (fs_type, source, super_options) = (after.next()?, after.next()?, after.next().unwrap_or(""));
+    // Both lists usually start with `rw`; keep each option once.
+    let mut options: Vec<&str> = fields.get(5)?.split(',').collect();
+    for option in super_options.split(',').filter(|option| !option.is_empty()) {
+        if !options.contains(&option) {
+            options.push(option);
+        }
+    }
+    let options = options.join(",");
+    Some((fields.get(2)?.to_string(), Mount { fs_type: fs_type.to_string(), mount_point: PathBuf::from(unescape(fields.get(4)?)), source: unescape(source), options }))
+}
+
+/// Undoes mountinfo's octal escapes (`\040` for a space).
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok());
+        match (bytes[i], octal) {
+            (b'\\', Some(byte)) => {
+                out.push(byte);
+                i += 4;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Available and total bytes of the filesystem holding `path`, and its block size.
+fn space(path: &Path) -> Option<(u64, u64, u64)> {
+    #[cfg(all(unix, feature = "libc"))]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+        let block = stat.f_frsize as u64;
+        Some((stat.f_bavail as u64 * block, stat.f_blocks as u64 * block, stat.f_bsize as u64))
+    }
+    #[cfg(not(all(unix, feature = "libc")))]
+    {
+        let _ = path;
+        None
+    }
+}
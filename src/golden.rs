@@ -0,0 +1,174 @@
+//! Golden runs: what every file operation of a run decided and how it ended, recorded so a
+//! later run of the same workload can be checked against it without looking at a single
+//! timing.
+//!
+//! With [`Options::golden`] set, every file a phase visits is recorded under the phase's
+//! scope (such as `Adaptive/read`) and its index in the phase, with the backend the
+//! strategy picked for it, if it picks one (`mmap`, `read`, `write`), and its outcome (`ok`,
+//! the error kind, or `panic`). A [`Recording`] stores them grouped by scope, decision and
+//! outcome as ranges of indices, so a run of ten thousand files that all went the same way
+//! is one line, and it keeps the workload and mmap thresholds the run used, since
+//! thresholds found by `--crossover` are measured and would differ on replay.
+//!
+//! Which worker handled a file, in what order, and anything a scheduler decided by the clock
+//! aren't recorded: they legitimately change from run to run.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::bench::Options;
+use crate::crossover::Thresholds;
+
+/// First line of every golden file.
+const HEADER: &str = "io golden 1";
+
+/// What happened to one file, as it appears in golden files; `None` if its work panicked.
+pub(crate) fn outcome(result: Option<&io::Result<()>>) -> String {
+    match result {
+        Some(Ok(())) => "ok".to_string(),
+        Some(Err(e)) => format!("{:?}", e.kind()),
+        None => "panic".to_string(),
+    }
+}
+
+/// `(scope, decision, outcome)`, the key files are grouped by.
+type Key = (String, String, String);
+
+/// Collects the decisions and outcomes of a run's files from every worker.
+#[derive(Debug, Default)]
+pub struct Recorder(Mutex<BTreeMap<Key, Vec<usize>>>);
+
+impl Recorder {
+    pub(crate) fn record(&self, scope: &str, index: usize, decision: Option<&'static str>, outcome: String) {
+        let key = (scope.to_string(), decision.unwrap_or("-").to_string(), outcome);
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).entry(key).or_default().push(index);
+    }
+
+    /// Everything recorded so far, with the workload and thresholds of `options`.
+    pub fn recording(&self, options: &Options) -> Recording {
+        let groups = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let groups = groups.iter().map(|(key, indices)| (key.clone(), ranges(indices))).collect();
+        Recording { files: options.workload.files, size: options.workload.size(), thresholds: options.thresholds, groups }
+    }
+}
+
+/// `indices` sorted and merged into inclusive ranges.
+fn ranges(indices: &[usize]) -> Vec<(usize, usize)> {
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for index in sorted {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == index => *end = index,
+            _ => ranges.push((index, index)),
+        }
+    }
+    ranges
+}
+
+/// One run's decisions and outcomes, and what it ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub files: usize,
+    pub size: usize,
+    pub thresholds: Thresholds,
+    groups: BTreeMap<Key, Vec<(usize, usize)>>,
+}
+
+/// One file that went differently in two recordings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub scope: String,
+    pub index: usize,
+    /// `decision outcome` in each recording, or `None` where the file wasn't visited.
+    pub recorded: Option<String>,
+    pub replayed: Option<String>,
+}
+
+impl Recording {
+    /// Files recorded, over all scopes.
+    pub fn operations(&self) -> usize {
+        self.groups.values().flatten().map(|(start, end)| end - start + 1).sum()
+    }
+
+    /// The golden file's text: a header, the workload, the thresholds, then one line per
+    /// scope, decision and outcome with its index ranges.
+    pub fn to_text(&self) -> String {
+        let threshold = |t: Option<u64>| t.map_or_else(|| "never".to_string(), |t| t.to_string());
+        let mut text = format!("{}\nworkload {} {}\nthresholds {} {}\n", HEADER, self.files, self.size, threshold(self.thresholds.mmap_read), threshold(self.thresholds.mmap_update));
+        for ((scope, decision, outcome), ranges) in &self.groups {
+            let ranges: Vec<String> = ranges.iter().map(|&(start, end)| if start == end { start.to_string() } else { format!("{}-{}", start, end) }).collect();
+            text.push_str(&format!("{}\t{}\t{}\t{}\n", scope, decision, outcome, ranges.join(",")));
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> io::Result<Recording> {
+        let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("malformed golden file line: {}", line));
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("not a golden file (expected '{}' first)", HEADER)));
+        }
+        let mut recording = Recording::default();
+        for line in lines.filter(|line| !line.is_empty()) {
+            // Scopes may hold spaces, so group lines are split on tabs only.
+            let fields: Vec<&str> = if line.contains('\t') { line.split('\t').collect() } else { line.split(' ').collect() };
+            let number = |field: &str| field.parse::<usize>().map_err(|_| invalid(line));
+            let threshold = |field: &str| if field == "never" { Ok(None) } else { field.parse::<u64>().map(Some).map_err(|_| invalid(line)) };
+            match fields[..] {
+                ["workload", files, size] => (recording.files, recording.size) = (number(files)?, number(size)?),
+                ["thresholds", read, update] => recording.thresholds = Thresholds { mmap_read: threshold(read)?, mmap_update: threshold(update)? },
+                [scope, decision, outcome, ranges] => {
+                    let ranges = ranges
+                        .split(',')
+                        .map(|range| match range.split_once('-') {
+                            Some((start, end)) => Ok((number(start)?, number(end)?)),
+                            None => number(range).map(|index| (index, index)),
+                        })
+                        .collect::<io::Result<_>>()?;
+                    recording.groups.insert((scope.to_string(), decision.to_string(), outcome.to_string()), ranges);
+                }
+                _ => return Err(invalid(line)),
+            }
+        }
+        Ok(recording)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Recording> {
+        Recording::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// `decision outcome` of every file, by scope and index.
+    fn by_file(&self) -> BTreeMap<(&str, usize), String> {
+        let mut files = BTreeMap::new();
+        for ((scope, decision, outcome), ranges) in &self.groups {
+            for &(start, end) in ranges {
+                for index in start..=end {
+                    files.insert((scope.as_str(), index), format!("{} {}", decision, outcome));
+                }
+            }
+        }
+        files
+    }
+
+    /// Every file that was decided or ended differently in `replayed`, or was visited in
+    /// only one of the two, in scope and index order.
+    pub fn diff(&self, replayed: &Recording) -> Vec<Difference> {
+        let (before, after) = (self.by_file(), replayed.by_file());
+        let mut keys: Vec<&(&str, usize)> = before.keys().chain(after.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .filter(|key| before.get(key) != after.get(key))
+            .map(|key| Difference { scope: key.0.to_string(), index: key.1, recorded: before.get(key).cloned(), replayed: after.get(key).cloned() })
+            .collect()
+    }
+}
@@ -0,0 +1,231 @@
+//! A cache of open files for workloads that come back to the same paths, such as reading
+//! every file and then updating it: the second pass finds its handles open instead of
+//! opening ten thousand files again.
+//!
+//! Handles are opened for reading and writing and shared, so use positioned I/O
+//! (`read_at`, `write_all_at`) rather than the file cursor. The least recently used handle
+//! is closed once the cache is full; keep the capacity under the open-file limit. A handle
+//! names an inode rather than a path, so deleting or renaming a cached path must go through
+//! [`HandleCache::remove_file`] and [`HandleCache::rename`] (or be followed by
+//! [`HandleCache::invalidate`]), or later lookups get the old file.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "bench")]
+use std::os::unix::fs::FileExt;
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+
+/// How often lookups found their handle open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandleStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Handles closed to make room.
+    pub evictions: u64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Each path's handle and when it was last used.
+    open: HashMap<PathBuf, (Arc<File>, u64)>,
+    /// Paths by last use, oldest first.
+    by_use: BTreeMap<u64, PathBuf>,
+    clock: u64,
+}
+
+impl Entries {
+    fn touch(&mut self, path: &Path) -> Option<Arc<File>> {
+        self.clock += 1;
+        let clock = self.clock;
+        let (file, used) = self.open.get_mut(path)?;
+        let path = self.by_use.remove(used).expect("every handle is in the use order");
+        *used = clock;
+        self.by_use.insert(clock, path);
+        Some(file.clone())
+    }
+
+    fn remove(&mut self, path: &Path) -> Option<Arc<File>> {
+        let (file, used) = self.open.remove(path)?;
+        self.by_use.remove(&used);
+        Some(file)
+    }
+}
+
+#[derive(Debug)]
+pub struct HandleCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl HandleCache {
+    /// A cache keeping at most `capacity` files open (at least one).
+    pub fn new(capacity: usize) -> HandleCache {
+        HandleCache {
+            capacity: capacity.max(1),
+            entries: Mutex::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The open handle for `path`, opening it for reading and writing if it isn't cached.
+    /// The file must exist. The open happens outside the lock, so two threads missing on
+    /// the same path may both open it; the later one's handle is kept.
+    pub fn open(&self, path: &Path) -> io::Result<Arc<File>> {
+        if let Some(file) = self.entries().touch(path) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(file);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let file = Arc::new(OpenOptions::new().read(true).write(true).open(path)?);
+        let mut entries = self.entries();
+        entries.remove(path);
+        while entries.open.len() >= self.capacity {
+            let Some((_, oldest)) = entries.by_use.pop_first() else { break };
+            entries.open.remove(&oldest);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+        entries.clock += 1;
+        let clock = entries.clock;
+        entries.by_use.insert(clock, path.to_path_buf());
+        entries.open.insert(path.to_path_buf(), (file.clone(), clock));
+        Ok(file)
+    }
+
+    /// Forgets the handle for `path`, if any, closing it once no caller holds it.
+    pub fn invalidate(&self, path: &Path) {
+        self.entries().remove(path);
+    }
+
+    /// Deletes `path`, forgetting its handle.
+    pub fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.invalidate(path);
+        fs::remove_file(path)
+    }
+
+    /// Renames `from` to `to`. The handle for `from` still names the same file, so it is
+    /// kept under `to`; a handle for the file `to` replaced is forgotten.
+    pub fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)?;
+        let mut entries = self.entries();
+        entries.remove(to);
+        if let Some(file) = entries.remove(from) {
+            entries.clock += 1;
+            let clock = entries.clock;
+            entries.by_use.insert(clock, to.to_path_buf());
+            entries.open.insert(to.to_path_buf(), (file, clock));
+        }
+        Ok(())
+    }
+
+    /// Closes every handle no caller holds.
+    pub fn clear(&self) {
+        *self.entries() = Entries::default();
+    }
+
+    /// The handles open now.
+    pub fn len(&self) -> usize {
+        self.entries().open.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> HandleStats {
+        HandleStats { hits: self.hits.load(Ordering::Relaxed), misses: self.misses.load(Ordering::Relaxed), evictions: self.evictions.load(Ordering::Relaxed) }
+    }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct PassTimes {
+    /// Reading every file whole.
+    pub read: Duration,
+    /// Rewriting every file with its update.
+    pub update: Duration,
+    /// Deleting every file.
+    pub delete: Duration,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct HandlesResult {
+    /// Opening every file in every phase.
+    pub reopened: PassTimes,
+    /// Going through a [`HandleCache`] of the configured capacity.
+    pub cached: PassTimes,
+    pub capacity: usize,
+    pub stats: HandleStats,
+    pub failures: Vec<Failure>,
+}
+
+/// Reads, updates and deletes the workload's files through `open`, a positioned read and
+/// write on whatever handle it returns, and `remove`.
+#[cfg(feature = "bench")]
+fn passes(paths: &[PathBuf], options: &Options, name: &str, open: impl Fn(&Path) -> io::Result<Arc<File>> + Sync, remove: impl Fn(&Path) -> io::Result<()> + Sync) -> io::Result<(PassTimes, Vec<Failure>)> {
+    let mut failures = Vec::new();
+    bench::phase(options, format!("Handles {} create", name), format!("handles/{}/create", name), "create", &mut failures, || {
+        bench::each_indexed(paths, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+    })?;
+
+    let start = Instant::now();
+    bench::phase(options, format!("Handles {} read", name), format!("handles/{}/read", name), "read", &mut failures, || {
+        bench::each_indexed(paths, options, |index, path| {
+            let file = open(path)?;
+            let mut bytes = vec![0; file.metadata()?.len() as usize];
+            file.read_exact_at(&mut bytes, 0)?;
+            bench::read_done(options, index, path, &bytes)
+        })
+    })?;
+    let read = start.elapsed();
+
+    let start = Instant::now();
+    bench::phase(options, format!("Handles {} update", name), format!("handles/{}/update", name), "update", &mut failures, || {
+        bench::each_indexed(paths, options, |index, path| {
+            let file = open(path)?;
+            let len = bench::with_content(options, index, true, |content| file.write_all_at(content, 0).map(|()| content.len() as u64))?;
+            if file.metadata()?.len() > len {
+                file.set_len(len)?;
+            }
+            options.sync.sync(&file)
+        })
+    })?;
+    let update = start.elapsed();
+
+    let start = Instant::now();
+    bench::phase(options, format!("Handles {} delete", name), format!("handles/{}/delete", name), "delete", &mut failures, || bench::each_indexed(paths, options, |_, path| remove(path)))?;
+    let delete = start.elapsed();
+    Ok((PassTimes { read, update, delete }, failures))
+}
+
+/// Creates the configured workload in `dir_path` untimed and times reading, updating and
+/// deleting it twice: opening every file in every phase, then through a [`HandleCache`]
+/// holding up to `capacity` files, which the update phase finds open when it is large
+/// enough for the workload.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, capacity: usize, options: &Options) -> io::Result<HandlesResult> {
+    let paths = options.workload.paths(dir_path);
+    options.failures.take("");
+    let (reopened, mut failures) = passes(&paths, options, "reopened", |path| Ok(Arc::new(OpenOptions::new().read(true).write(true).open(path)?)), |path| fs::remove_file(path))?;
+    let cache = HandleCache::new(capacity);
+    let (cached, cached_failures) = passes(&paths, options, "cached", |path| cache.open(path), |path| cache.remove_file(path))?;
+    failures.extend(cached_failures);
+    Ok(HandlesResult { reopened, cached, capacity: cache.capacity, stats: cache.stats(), failures })
+}
@@ -0,0 +1,195 @@
+//! Device temperature and SMART attributes, captured before and after a run so drops in
+//! sustained throughput can be matched to the device heating up and throttling.
+//!
+//! Temperatures come from the kernel's hwmon sensors for the block device (NVMe
+//! controllers, and SATA drives with `drivetemp`), which need no privileges. SMART
+//! attributes come from `smartctl --json` when it is installed and allowed to open the
+//! device, usually as root; either source may be missing, leaving only the other.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::json::{self, Value};
+
+/// The whole block device (not a partition) a path lives on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    /// The kernel name, such as `nvme0n1` or `sda`.
+    pub name: String,
+    /// The device's directory in sysfs.
+    pub sysfs: PathBuf,
+}
+
+impl Device {
+    /// The device holding `path`, or `None` off Linux and for filesystems without one
+    /// (tmpfs, overlay, network mounts).
+    pub fn of(path: &Path) -> Option<Device> {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let dev = fs::metadata(path).ok()?.dev();
+            let (major, minor) = (((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff), (dev & 0xff) | ((dev >> 12) & !0xff));
+            let mut sysfs = fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor)).ok()?;
+            if sysfs.join("partition").exists() {
+                sysfs.pop();
+            }
+            let name = sysfs.file_name()?.to_string_lossy().into_owned();
+            Some(Device { name, sysfs })
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = path;
+            None
+        }
+    }
+
+    pub fn node(&self) -> PathBuf {
+        Path::new("/dev").join(&self.name)
+    }
+
+    /// Reads the device's sensors and SMART attributes now.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        self.read_temperatures(&mut snapshot);
+        self.read_smart(&mut snapshot);
+        snapshot
+    }
+
+    /// Reads only the device's temperature sensors, which is quick enough to do often.
+    pub fn temperatures(&self) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        self.read_temperatures(&mut snapshot);
+        snapshot
+    }
+
+    /// `temp*_input` of every hwmon sensor of the device, in °C. NVMe controllers have
+    /// `device/hwmonN`, drivetemp has `device/hwmon/hwmonN`.
+    fn read_temperatures(&self, snapshot: &mut Snapshot) {
+        let device = self.sysfs.join("device");
+        let mut sensors: Vec<PathBuf> = [device.clone(), device.join("hwmon")]
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("hwmon") && name != "hwmon"))
+            .collect();
+        sensors.sort();
+        for sensor in sensors {
+            let Ok(entries) = fs::read_dir(&sensor) else {
+                continue;
+            };
+            let mut inputs: Vec<String> = entries
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| name.starts_with("temp") && name.ends_with("_input"))
+                .collect();
+            inputs.sort();
+            for input in inputs {
+                let Some(millidegrees) = fs::read_to_string(sensor.join(&input)).ok().and_then(|s| s.trim().parse::<f64>().ok()) else {
+                    continue;
+                };
+                let id = input.trim_end_matches("_input");
+                let label = fs::read_to_string(sensor.join(format!("{}_label", id))).map_or_else(|_| id.to_string(), |label| label.trim().to_string());
+                snapshot.push(format!("{} °C", label), millidegrees / 1000.0);
+            }
+        }
+    }
+
+    fn read_smart(&self, snapshot: &mut Snapshot) {
+        // smartctl's exit status is a bit mask that is non-zero for merely old or failing
+        // attributes, so the JSON is what tells whether it worked.
+        let Ok(output) = Command::new("smartctl").args(["--json", "-A"]).arg(self.node()).output() else {
+            return;
+        };
+        for (name, value) in Snapshot::from_smartctl(&String::from_utf8_lossy(&output.stdout)).readings {
+            snapshot.push(name, value);
+        }
+    }
+}
+
+/// The numeric attributes of `smartctl --json -A` output: the NVMe health log, or the ATA
+/// attribute table by raw value.
+fn smart_attributes(value: &Value, snapshot: &mut Snapshot) {
+    if let Some(Value::Object(fields)) = value.get("nvme_smart_health_information_log") {
+        for (name, field) in fields {
+            if let Some(n) = field.as_f64() {
+                snapshot.push(name.clone(), n);
+            }
+        }
+    }
+    let table = value.get("ata_smart_attributes").and_then(|attributes| attributes.get("table")).and_then(Value::as_array).unwrap_or_default();
+    for attribute in table {
+        let name = attribute.get("name").and_then(Value::as_str);
+        let raw = attribute.get("raw").and_then(|raw| raw.get("value")).and_then(Value::as_f64);
+        if let (Some(name), Some(raw)) = (name, raw) {
+            snapshot.push(name.to_string(), raw);
+        }
+    }
+}
+
+/// Named readings at one moment, in the order they were read.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    pub readings: Vec<(String, f64)>,
+}
+
+impl Snapshot {
+    /// The attributes in the output of `smartctl --json -A`; empty if it isn't JSON, as
+    /// when smartctl couldn't open the device.
+    pub fn from_smartctl(output: &str) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        if let Ok(value) = json::parse(output) {
+            smart_attributes(&value, &mut snapshot);
+        }
+        snapshot
+    }
+
+    fn push(&mut self, name: String, value: f64) {
+        if self.get(&name).is_none() {
+            self.readings.push((name, value));
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.readings.iter().find(|(n, _)| n == name).map(|&(_, value)| value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.readings.is_empty()
+    }
+
+    /// Every reading of either snapshot with its value in each, `self` being the earlier.
+    pub fn changes(&self, after: &Snapshot) -> Vec<Change> {
+        let mut changes: Vec<Change> = self.readings.iter().map(|(name, before)| Change { name: name.clone(), before: Some(*before), after: after.get(name) }).collect();
+        for (name, value) in &after.readings {
+            if self.get(name).is_none() {
+                changes.push(Change { name: name.clone(), before: None, after: Some(*value) });
+            }
+        }
+        changes
+    }
+
+    /// Minutes the NVMe controller reports having run above its warning or critical
+    /// composite temperature, where it throttles; `None` without an NVMe health log.
+    pub fn throttled_minutes(&self) -> Option<f64> {
+        let warning = self.get("warning_temp_time")?;
+        Some(warning + self.get("critical_comp_time").unwrap_or(0.0))
+    }
+}
+
+/// One reading before and after a run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub name: String,
+    pub before: Option<f64>,
+    pub after: Option<f64>,
+}
+
+impl Change {
+    pub fn delta(&self) -> Option<f64> {
+        Some(self.after? - self.before?)
+    }
+}
@@ -0,0 +1,161 @@
+//! Formatting for the human-readable output, kept independent of locale and stable across
+//! releases so scripts that parse it keep working.
+//!
+//! Numbers always use `.` for decimals and `,` between thousands, units are chosen by fixed
+//! thresholds, and tables pad columns with spaces. Any change to what these functions or
+//! the `io` commands print bumps [`LAYOUT_VERSION`], which `--output human` prints first.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Version of the human output layout.
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// The line that starts human output: `io output: human v1`.
+pub fn header() -> String {
+    format!("io output: human v{}", LAYOUT_VERSION)
+}
+
+/// `n` with `,` between groups of three digits: `1,234,567`.
+pub fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// `value` with `decimals` decimal places and thousands separators: `12,345.68`.
+pub fn decimal(value: f64, decimals: usize) -> String {
+    let formatted = format!("{:.*}", decimals, value.abs());
+    let (whole, fraction) = formatted.split_once('.').map_or((formatted.as_str(), None), |(w, f)| (w, Some(f)));
+    let sign = if value < 0.0 && formatted.chars().any(|c| c.is_ascii_digit() && c != '0') { "-" } else { "" };
+    let whole = whole.parse::<u64>().map_or_else(|_| whole.to_string(), thousands);
+    match fraction {
+        Some(fraction) => format!("{}{}.{}", sign, whole, fraction),
+        None => format!("{}{}", sign, whole),
+    }
+}
+
+/// A duration in milliseconds with two decimals, for table cells with a fixed unit.
+pub fn millis(d: Duration) -> String {
+    decimal(d.as_secs_f64() * 1000.0, 2)
+}
+
+/// A duration in the largest unit that keeps it at least 1: `850 ns`, `12.5 us`, `3.20 ms`,
+/// `1.234 s`, `2m 05.0s`. Only ASCII unit names are used.
+pub fn duration(d: Duration) -> String {
+    let nanos = d.as_nanos();
+    if nanos < 1_000 {
+        format!("{} ns", nanos)
+    } else if nanos < 1_000_000 {
+        format!("{:.1} us", nanos as f64 / 1e3)
+    } else if nanos < 1_000_000_000 {
+        format!("{:.2} ms", nanos as f64 / 1e6)
+    } else if d.as_secs() < 60 {
+        format!("{:.3} s", d.as_secs_f64())
+    } else {
+        let secs = d.as_secs_f64();
+        format!("{}m {:04.1}s", (secs / 60.0) as u64, secs % 60.0)
+    }
+}
+
+/// A byte count in binary units with one decimal: `512 B`, `4.0 KiB`, `1.5 GiB`.
+pub fn bytes(n: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if n < 1024 {
+        return format!("{} B", n);
+    }
+    let mut value = n as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{} {}", decimal(value, 1), UNITS[unit])
+}
+
+/// A relative change in percent with an explicit sign: `+12.5%`, `-3.0%`.
+pub fn change(percent: f64, decimals: usize) -> String {
+    let formatted = decimal(percent, decimals);
+    if formatted.starts_with('-') || formatted.trim_start_matches(['0', '.', ',']).is_empty() {
+        format!("{}%", formatted)
+    } else {
+        format!("+{}%", formatted)
+    }
+}
+
+/// A rate per second with one decimal and thousands separators: `12,345.6/s`.
+pub fn rate(count: f64, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    format!("{}/s", decimal(if secs > 0.0 { count / secs } else { 0.0 }, 1))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// A table whose columns are as wide as their widest cell, separated by two spaces.
+#[derive(Debug, Clone)]
+pub struct Table {
+    columns: Vec<(String, Align)>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// A table with these column titles; the first column is left-aligned, the rest right.
+    pub fn new<S: Into<String>>(titles: impl IntoIterator<Item = S>) -> Table {
+        let columns = titles
+            .into_iter()
+            .enumerate()
+            .map(|(i, title)| (title.into(), if i == 0 { Align::Left } else { Align::Right }))
+            .collect();
+        Table { columns, rows: Vec::new() }
+    }
+
+    pub fn align(mut self, column: usize, align: Align) -> Table {
+        self.columns[column].1 = align;
+        self
+    }
+
+    /// Adds a row; missing cells are left empty and extra cells dropped.
+    pub fn row<S: Into<String>>(&mut self, cells: impl IntoIterator<Item = S>) {
+        let mut row: Vec<String> = cells.into_iter().map(Into::into).take(self.columns.len()).collect();
+        row.resize(self.columns.len(), String::new());
+        self.rows.push(row);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The table as lines ending in `\n`, without trailing spaces.
+    pub fn render(&self) -> String {
+        let widths: Vec<usize> = (0..self.columns.len())
+            .map(|c| self.rows.iter().map(|row| row[c].chars().count()).chain([self.columns[c].0.chars().count()]).max().unwrap_or(0))
+            .collect();
+        let mut out = String::new();
+        let titles: Vec<&str> = self.columns.iter().map(|(title, _)| title.as_str()).collect();
+        for cells in [titles].into_iter().chain(self.rows.iter().map(|row| row.iter().map(String::as_str).collect())) {
+            let mut line = String::new();
+            for (c, cell) in cells.iter().enumerate() {
+                if c > 0 {
+                    line.push_str("  ");
+                }
+                let _ = match self.columns[c].1 {
+                    Align::Left => write!(line, "{:<width$}", cell, width = widths[c]),
+                    Align::Right => write!(line, "{:>width$}", cell, width = widths[c]),
+                };
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
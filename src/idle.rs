@@ -0,0 +1,96 @@
+//! Detecting whether the machine is idle, so soak workloads can yield to interactive use.
+//!
+//! Signals are read from Linux `/proc` and `/dev/pts`; any that are unavailable (other
+//! platforms, kernels without PSI) are treated as idle. Desktop input that never reaches a
+//! terminal is not visible here.
+
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Copy)]
+pub struct IdleThresholds {
+    /// CPU use by other processes, as a percentage of all CPUs.
+    pub cpu_percent: f64,
+    /// `some avg10` from `/proc/pressure/cpu`.
+    pub cpu_pressure: f64,
+    /// How long terminals must have gone without input.
+    pub input_idle: Duration,
+}
+
+impl Default for IdleThresholds {
+    fn default() -> Self {
+        IdleThresholds { cpu_percent: 15.0, cpu_pressure: 10.0, input_idle: Duration::from_secs(300) }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CpuSample {
+    total: u64,
+    idle: u64,
+    own: u64,
+}
+
+fn cpu_sample() -> Option<CpuSample> {
+    let stat = fs::read_to_string("/proc/stat").ok()?;
+    let ticks: Vec<u64> = stat.lines().next()?.split_whitespace().skip(1).filter_map(|t| t.parse().ok()).collect();
+    // user nice system idle iowait irq softirq steal ...
+    let total = ticks.iter().take(8).sum();
+    let idle = ticks.get(3)? + ticks.get(4).unwrap_or(&0);
+    // Our own workers would otherwise make the machine look busy.
+    let own_stat = fs::read_to_string("/proc/self/stat").ok()?;
+    let fields: Vec<&str> = own_stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let own = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+    Some(CpuSample { total, idle, own })
+}
+
+fn cpu_pressure() -> Option<f64> {
+    let pressure = fs::read_to_string("/proc/pressure/cpu").ok()?;
+    let some = pressure.lines().find(|l| l.starts_with("some "))?;
+    some.split_whitespace().find_map(|field| field.strip_prefix("avg10="))?.parse().ok()
+}
+
+/// Time since the most recent terminal input, judged like `w` by the access times of `/dev/pts/*`.
+fn input_idle() -> Option<Duration> {
+    let now = SystemTime::now();
+    fs::read_dir("/dev/pts")
+        .ok()?
+        .filter_map(|entry| entry.ok()?.metadata().ok()?.accessed().ok())
+        .map(|accessed| now.duration_since(accessed).unwrap_or(Duration::ZERO))
+        .min()
+}
+
+/// Compares successive samples against [`IdleThresholds`].
+#[derive(Debug)]
+pub struct IdleDetector {
+    thresholds: IdleThresholds,
+    last: Option<CpuSample>,
+}
+
+/// Fewer ticks than this between samples is too noisy to judge CPU use.
+const MIN_SAMPLE_TICKS: u64 = 10;
+
+impl IdleDetector {
+    pub fn new(thresholds: IdleThresholds) -> IdleDetector {
+        IdleDetector { thresholds, last: cpu_sample() }
+    }
+
+    /// Returns why the machine is busy, or `None` if it is idle.
+    pub fn busy(&mut self) -> Option<String> {
+        if let Some(idle) = input_idle().filter(|idle| *idle < self.thresholds.input_idle) {
+            return Some(format!("terminal input {} s ago", idle.as_secs()));
+        }
+        if let Some(pressure) = cpu_pressure().filter(|p| *p > self.thresholds.cpu_pressure) {
+            return Some(format!("CPU pressure {:.1}%", pressure));
+        }
+        let sample = cpu_sample()?;
+        let last = self.last.replace(sample)?;
+        let total = sample.total.saturating_sub(last.total);
+        if total < MIN_SAMPLE_TICKS {
+            self.last = Some(last);
+            return None;
+        }
+        let busy = total.saturating_sub(sample.idle.saturating_sub(last.idle)).saturating_sub(sample.own.saturating_sub(last.own));
+        let percent = busy as f64 * 100.0 / total as f64;
+        (percent > self.thresholds.cpu_percent).then(|| format!("CPU {:.0}% busy", percent))
+    }
+}
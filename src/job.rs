@@ -0,0 +1,433 @@
+//! fio-style job files for `io bench --job`: several named workloads, run one after another.
+//!
+//! A job file is TOML (`.toml`) or YAML (`.yaml`, `.yml`). An optional `global` table sets
+//! defaults; every other table is a workload, run in file order:
+//!
+//! ```toml
+//! [global]
+//! threads = 4
+//! sync = "none"
+//!
+//! [small-files]
+//! files = 10000
+//! size = "1K"
+//! strategies = ["traditional_io", "smart_io"]
+//!
+//! [durable-writes]
+//! files = 1000
+//! size = "64K"
+//! sync = "full"
+//! dir = "/mnt/nvme"
+//! ```
+//!
+//! Workload keys: `files`, `size` (bytes or `4K`/`1M`/...), `threads`, `strategies` (names
+//! such as `smart_io` or labels such as `Smart`; all when absent), `sync` (`none`, `data`,
+//! `full`), `dir` (runs in a fresh `bench_files-<run id>-<name>` directory inside it),
+//! `pattern`, `seed`, `dedupe` and `block` (generated contents, see `--pattern`), and the
+//! switches `preallocate`, `cold`, `fresh_buffers`, `huge_pages` and `verify`. Anything not set falls back to
+//! `global`, then to the command line. Only the parts of TOML and YAML these files need are
+//! understood: tables or mappings of strings, numbers, booleans and flat lists.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cli::{BenchArgs, ByteSize};
+use ::io::bench::{STRATEGIES, Strategy, SyncMode};
+use ::io::json::Value;
+use ::io::pattern::{self, Generator, Pattern};
+use ::io::workload;
+
+/// One workload of a job file, with every setting resolved.
+#[derive(Debug)]
+pub struct Job {
+    pub name: String,
+    pub files: usize,
+    pub size: usize,
+    pub threads: usize,
+    pub strategies: Vec<&'static Strategy>,
+    pub sync: SyncMode,
+    pub dir: Option<PathBuf>,
+    pub preallocate: bool,
+    pub cold: bool,
+    pub fresh_buffers: bool,
+    pub huge_pages: bool,
+    pub generator: Option<Generator>,
+    pub verify: bool,
+}
+
+impl Job {
+    /// The directory the job's files are created in, unique to the run.
+    pub fn dir(&self) -> PathBuf {
+        match &self.dir {
+            Some(dir) => {
+                let mut path = ::io::rundir::run_dir(dir).into_os_string();
+                path.push(format!("-{}", self.name));
+                PathBuf::from(path)
+            }
+            None => ::io::bench::get_dir(),
+        }
+    }
+
+    fn apply(&mut self, settings: &[(String, Value)]) -> Result<(), String> {
+        for (key, value) in settings {
+            let count = || match value.as_u64() {
+                Some(n) if n > 0 => Ok(n as usize),
+                _ => Err(format!("{} must be a positive integer", key)),
+            };
+            let flag = || match value {
+                Value::Bool(b) => Ok(*b),
+                _ => Err(format!("{} must be true or false", key)),
+            };
+            let text = || value.as_str().ok_or_else(|| format!("{} must be a string", key));
+            match key.as_str() {
+                "files" => self.files = count()?,
+                "threads" => self.threads = count()?,
+                "size" => {
+                    self.size = match value {
+                        Value::String(s) => s.parse::<ByteSize>()?.0,
+                        _ => count()?,
+                    };
+                    if self.size == 0 {
+                        return Err("size must be positive".to_string());
+                    }
+                }
+                "strategies" => {
+                    let names = value.as_array().ok_or_else(|| "strategies must be a list".to_string())?;
+                    self.strategies = names
+                        .iter()
+                        .map(|name| name.as_str().ok_or_else(|| "strategies must be strings".to_string()).and_then(strategy))
+                        .collect::<Result<_, _>>()?;
+                    if self.strategies.is_empty() {
+                        return Err("strategies must not be empty".to_string());
+                    }
+                }
+                "sync" => self.sync = text()?.parse().map_err(|e: io::Error| e.to_string())?,
+                "dir" => self.dir = Some(PathBuf::from(text()?)),
+                "preallocate" => self.preallocate = flag()?,
+                "cold" => self.cold = flag()?,
+                "fresh_buffers" => self.fresh_buffers = flag()?,
+                "huge_pages" => self.huge_pages = flag()?,
+                "verify" => self.verify = flag()?,
+                "pattern" => {
+                    let pattern = text()?.parse().map_err(|e: io::Error| e.to_string())?;
+                    self.generator.get_or_insert(Generator::new(0, Pattern::Random)).pattern = pattern;
+                }
+                "seed" => {
+                    let seed = value.as_u64().ok_or_else(|| "seed must be a non-negative integer".to_string())?;
+                    self.generator.get_or_insert(Generator::new(0, Pattern::Random)).seed = seed;
+                }
+                "dedupe" => {
+                    let ratio = match value {
+                        Value::String(s) => pattern::parse_ratio(s),
+                        _ => value.as_f64().filter(|ratio| *ratio >= 1.0),
+                    };
+                    let ratio = ratio.ok_or_else(|| "dedupe must be a ratio of at least 1".to_string())?;
+                    self.generator.get_or_insert(Generator::new(0, Pattern::Random)).dedupe = ratio;
+                }
+                "block" => {
+                    let block = match value {
+                        Value::String(s) => s.parse::<ByteSize>()?.0,
+                        _ => count()?,
+                    };
+                    self.generator.get_or_insert(Generator::new(0, Pattern::Random)).block = block.max(1);
+                }
+                _ => return Err(format!("unknown key '{}'", key)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A strategy by name (`smart_io`) or label (`Smart`), as [`workload::strategy`] finds it.
+pub fn strategy(name: &str) -> Result<&'static Strategy, String> {
+    workload::strategy(name).ok_or_else(|| format!("unknown strategy '{}'", name))
+}
+
+fn invalid(path: &Path, message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message))
+}
+
+/// Reads the workloads of the job file at `path`, filling unset keys from `args`.
+pub fn load(path: &Path, args: &BenchArgs) -> io::Result<Vec<Job>> {
+    let text = fs::read_to_string(path)?;
+    let document = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => parse_toml(&text),
+        Some("yaml" | "yml") => parse_yaml(&text),
+        _ => return Err(invalid(path, "job files must end in .toml, .yaml or .yml".to_string())),
+    }
+    .map_err(|e| invalid(path, e))?;
+    let Value::Object(tables) = document else {
+        return Err(invalid(path, "expected a table of workloads".to_string()));
+    };
+    let settings = |name: &str, value: &Value| match value {
+        Value::Object(settings) => Ok(settings.clone()),
+        _ => Err(invalid(path, format!("'{}' must be a table", name))),
+    };
+    let global = match tables.iter().find(|(name, _)| name == "global") {
+        Some((name, value)) => settings(name, value)?,
+        None => Vec::new(),
+    };
+    let mut jobs = Vec::new();
+    for (name, value) in tables.iter().filter(|(name, _)| name != "global") {
+        let mut job = Job {
+            name: name.clone(),
+            files: args.files[0],
+            size: args.sizes[0],
+            threads: args.threads[0],
+            strategies: STRATEGIES.iter().collect(),
+            sync: args.options.sync,
+            dir: None,
+            preallocate: args.options.preallocate,
+            cold: args.options.cold_read,
+            fresh_buffers: args.options.fresh_buffers,
+            huge_pages: args.options.huge_pages,
+            generator: args.options.generator,
+            verify: args.options.verify,
+        };
+        let own = settings(name, value)?;
+        job.apply(&global)
+            .and_then(|()| job.apply(&own))
+            .map_err(|e| invalid(path, format!("[{}] {}", name, e)))?;
+        jobs.push(job);
+    }
+    if jobs.is_empty() {
+        return Err(invalid(path, "no workloads".to_string()));
+    }
+    Ok(jobs)
+}
+
+/// Drops a `#` comment that isn't inside a quoted string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// A quoted string starting at the beginning of `s`, and the rest of `s` after it.
+fn quoted(s: &str) -> Result<(String, &str), String> {
+    let quote = s.chars().next().ok_or("expected a string")?;
+    let mut out = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((out, &s[i + 1..])),
+            '\\' if quote == '"' => match chars.next().map(|(_, c)| c) {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(c @ ('"' | '\\')) => out.push(c),
+                other => return Err(format!("unsupported escape '\\{}'", other.unwrap_or(' '))),
+            },
+            c => out.push(c),
+        }
+    }
+    Err(format!("unterminated string {}", s))
+}
+
+/// A bare scalar: boolean, number, or (for YAML) a plain string.
+fn scalar(s: &str, plain_strings: bool) -> Result<Value, String> {
+    match s {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        "~" | "null" if plain_strings => return Ok(Value::Null),
+        _ => {}
+    }
+    if s.starts_with('"') || s.starts_with('\'') {
+        return match quoted(s)? {
+            (text, rest) if rest.trim().is_empty() => Ok(Value::String(text)),
+            (_, rest) => Err(format!("unexpected '{}' after string", rest.trim())),
+        };
+    }
+    match s.replace('_', "").parse::<f64>() {
+        Ok(number) if s.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') => Ok(Value::Number(number)),
+        _ if plain_strings && !s.is_empty() => Ok(Value::String(s.to_string())),
+        _ => Err(format!("invalid value '{}'", s)),
+    }
+}
+
+/// A scalar or a flat `[a, b, c]` list.
+fn inline_value(s: &str, plain_strings: bool) -> Result<Value, String> {
+    let s = s.trim();
+    let Some(inner) = s.strip_prefix('[') else {
+        return scalar(s, plain_strings);
+    };
+    let inner = inner.strip_suffix(']').ok_or_else(|| format!("unterminated list {}", s))?;
+    let mut items = Vec::new();
+    let mut rest = inner.trim();
+    while !rest.is_empty() {
+        let (item, after) = if rest.starts_with('"') || rest.starts_with('\'') {
+            let (text, after) = quoted(rest)?;
+            (Value::String(text), after.trim_start())
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            (scalar(rest[..end].trim(), plain_strings)?, &rest[end..])
+        };
+        items.push(item);
+        rest = match after.strip_prefix(',') {
+            Some(after) => after.trim_start(),
+            None if after.is_empty() => after,
+            None => return Err(format!("expected ',' in list {}", s)),
+        };
+    }
+    Ok(Value::Array(items))
+}
+
+/// The table at `path` inside `root`, created if missing.
+fn table<'a>(root: &'a mut Vec<(String, Value)>, path: &[String]) -> Result<&'a mut Vec<(String, Value)>, String> {
+    let Some((first, rest)) = path.split_first() else {
+        return Ok(root);
+    };
+    let index = match root.iter().position(|(key, _)| key == first) {
+        Some(index) => index,
+        None => {
+            root.push((first.clone(), Value::Object(Vec::new())));
+            root.len() - 1
+        }
+    };
+    match &mut root[index].1 {
+        Value::Object(entries) => table(entries, rest),
+        _ => Err(format!("'{}' is not a table", first)),
+    }
+}
+
+/// A key or dotted table name, with quoted parts.
+fn key_path(s: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+    let mut rest = s.trim();
+    loop {
+        let (part, after) = if rest.starts_with('"') || rest.starts_with('\'') {
+            quoted(rest)?
+        } else {
+            let end = rest.find('.').unwrap_or(rest.len());
+            (rest[..end].trim().to_string(), &rest[end..])
+        };
+        if part.is_empty() {
+            return Err(format!("invalid key '{}'", s));
+        }
+        parts.push(part);
+        rest = match after.trim_start().strip_prefix('.') {
+            Some(after) => after.trim_start(),
+            None if after.trim().is_empty() => return Ok(parts),
+            None => return Err(format!("invalid key '{}'", s)),
+        };
+    }
+}
+
+/// The TOML subset job files use: `[table]` headers, `key = value` pairs, strings,
+/// numbers, booleans and flat lists, which may span lines.
+pub fn parse_toml(text: &str) -> Result<Value, String> {
+    let mut root = Vec::new();
+    let mut current = Vec::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let at = |e: String| format!("line {}: {}", number + 1, e);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.strip_suffix(']').ok_or_else(|| at(format!("invalid table header {}", line)))?;
+            current = key_path(header).map_err(at)?;
+            table(&mut root, &current).map_err(at)?;
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| at(format!("expected key = value, got {}", line)))?;
+        let mut value = value.trim().to_string();
+        while value.starts_with('[') && !value.ends_with(']') {
+            let (_, next) = lines.next().ok_or_else(|| at("unterminated list".to_string()))?;
+            value.push(' ');
+            value.push_str(strip_comment(next).trim());
+        }
+        let value = inline_value(&value.replace(", ]", "]").replace(",]", "]"), false).map_err(at)?;
+        let key = key_path(key).map_err(at)?;
+        let (name, parents) = key.split_last().expect("key_path returns at least one part");
+        let path: Vec<String> = current.iter().chain(parents).cloned().collect();
+        let entries = table(&mut root, &path).map_err(at)?;
+        if entries.iter().any(|(existing, _)| existing == name) {
+            return Err(at(format!("duplicate key '{}'", name)));
+        }
+        entries.push((name.clone(), value));
+    }
+    Ok(Value::Object(root))
+}
+
+/// The YAML subset job files use: nested block mappings by indentation, `- item` lists,
+/// `[a, b]` flow lists, and plain or quoted scalars.
+fn parse_yaml(text: &str) -> Result<Value, String> {
+    let lines: Vec<(usize, usize, &str)> = text
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, line.len() - line.trim_start().len(), strip_comment(line).trim_end()))
+        .filter(|(_, _, line)| !line.trim().is_empty() && line.trim() != "---")
+        .map(|(number, indent, line)| (number, indent, line.trim_start()))
+        .collect();
+    if let Some((number, _, _)) = lines.iter().find(|(_, _, line)| line.contains('\t')) {
+        return Err(format!("line {}: tabs are not allowed in YAML indentation", number));
+    }
+    let mut next = 0;
+    let value = yaml_block(&lines, &mut next, lines.first().map_or(0, |&(_, indent, _)| indent))?;
+    match lines.get(next) {
+        Some((number, _, line)) => Err(format!("line {}: unexpected '{}'", number, line)),
+        None => Ok(value),
+    }
+}
+
+fn yaml_block(lines: &[(usize, usize, &str)], next: &mut usize, indent: usize) -> Result<Value, String> {
+    let is_list = lines.get(*next).is_some_and(|(_, _, line)| *line == "-" || line.starts_with("- "));
+    let mut items = Vec::new();
+    let mut entries: Vec<(String, Value)> = Vec::new();
+    while let Some(&(number, line_indent, line)) = lines.get(*next) {
+        if line_indent < indent {
+            break;
+        }
+        let at = |e: String| format!("line {}: {}", number, e);
+        if line_indent > indent {
+            return Err(at("unexpected indentation".to_string()));
+        }
+        *next += 1;
+        if is_list {
+            let Some(item) = line.strip_prefix('-').filter(|_| line == "-" || line.starts_with("- ")) else {
+                // A list written at its key's indentation ends at the next key.
+                *next -= 1;
+                break;
+            };
+            items.push(yaml_value(lines, next, indent, item.trim()).map_err(at)?);
+            continue;
+        }
+        let (key, value) = match line.split_once(": ") {
+            Some((key, value)) => (key, value),
+            None => (line.strip_suffix(':').ok_or_else(|| at(format!("expected 'key: value', got {}", line)))?, ""),
+        };
+        let key = if key.starts_with('"') || key.starts_with('\'') { quoted(key).map_err(at)?.0 } else { key.trim().to_string() };
+        if entries.iter().any(|(existing, _)| *existing == key) {
+            return Err(at(format!("duplicate key '{}'", key)));
+        }
+        let value = yaml_value(lines, next, indent, value.trim()).map_err(at)?;
+        entries.push((key, value));
+    }
+    Ok(if is_list { Value::Array(items) } else { Value::Object(entries) })
+}
+
+/// The value after `key:` or `-`: inline, or a nested block on the following lines.
+fn yaml_value(lines: &[(usize, usize, &str)], next: &mut usize, indent: usize, inline: &str) -> Result<Value, String> {
+    if !inline.is_empty() {
+        return inline_value(inline, true);
+    }
+    match lines.get(*next) {
+        Some(&(_, child, line)) if child > indent || (child == indent && (line == "-" || line.starts_with("- "))) => yaml_block(lines, next, child),
+        _ => Ok(Value::Null),
+    }
+}
@@ -0,0 +1,170 @@
+//! Shared building blocks for the I/O benchmarks, usable by other crates that want
+//! to reproduce the same scenarios in their own harnesses.
+//!
+//! Everything beyond the standard library is behind a cargo feature: `rayon`, `mmap`,
+//! `libc`, `tokio`, `sqlite`, `lmdb`, `s3` and `zstd` enable the modules that need them,
+//! `capi` exports a C interface to the bulk operations, and the default `bench` feature
+//! pulls in the full benchmark harness used by the `io` binary.
+//!
+//! For `wasm32-wasip1`, build with `--no-default-features` and at most `rayon`, `mmap` and
+//! `capi`: [`contents`] then reads, writes and deletes in bulk with buffered I/O on the
+//! calling thread, since the sandbox has neither mappings nor threads to spread over, and
+//! the modules needing Unix or Linux are left out.
+
+pub mod anomaly;
+#[cfg(feature = "bench")]
+pub mod append;
+pub mod archive;
+pub mod atomic;
+pub mod batch;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod breakdown;
+pub mod buffers;
+pub mod bundle;
+pub mod contents;
+pub mod copy;
+#[cfg(all(feature = "libc", feature = "mmap", feature = "rayon"))]
+pub mod cache;
+pub mod calibration;
+pub mod cancel;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod cas;
+pub mod cgroup;
+pub mod checkpoint;
+#[cfg(feature = "bench")]
+pub mod cipher;
+#[cfg(all(unix, feature = "libc"))]
+pub mod coalesce;
+pub mod codec;
+#[cfg(all(unix, feature = "bench"))]
+pub mod crash;
+#[cfg(all(feature = "mmap", feature = "rayon"))]
+pub mod crossover;
+pub mod dedupe;
+pub mod deflate;
+pub mod digest;
+#[cfg(all(unix, feature = "libc"))]
+pub mod dirs;
+pub mod dirty;
+#[cfg(feature = "rayon")]
+pub mod engine;
+#[cfg(feature = "bench")]
+pub mod environment;
+pub mod fault;
+pub mod fdlimit;
+pub mod filesystem;
+#[cfg(feature = "bench")]
+pub mod golden;
+pub mod handles;
+pub mod health;
+pub mod human;
+pub mod idle;
+pub mod json;
+#[cfg(all(target_os = "linux", feature = "bench"))]
+pub mod linked;
+pub mod links;
+#[cfg(all(unix, feature = "libc"))]
+pub mod lock;
+pub mod log;
+pub mod lz4;
+#[cfg(all(unix, feature = "lmdb"))]
+pub mod lmdb;
+pub mod mapper;
+pub mod memory;
+#[cfg(feature = "bench")]
+pub mod metadata;
+pub mod mirror;
+pub mod mkdir;
+pub mod mobile;
+#[cfg(feature = "libc")]
+pub mod mmap;
+pub mod names;
+pub mod netfs;
+pub mod noise;
+pub mod oplog;
+pub mod order;
+pub mod pack;
+pub mod pace;
+pub mod parquet;
+pub mod pattern;
+#[cfg(unix)]
+pub mod permissions;
+#[cfg(feature = "bench")]
+pub mod pipeline;
+pub mod pinning;
+pub mod plan;
+pub mod platform;
+pub mod population;
+pub mod precondition;
+pub mod priority;
+#[cfg(all(unix, feature = "libc", feature = "mmap", feature = "rayon"))]
+pub mod probe;
+#[cfg(all(unix, feature = "libc"))]
+pub mod profile;
+pub mod progress;
+pub mod queues;
+#[cfg(unix)]
+pub mod ranged;
+#[cfg(all(unix, feature = "libc"))]
+pub mod ramdisk;
+#[cfg(feature = "bench")]
+pub mod read;
+pub mod readonly;
+#[cfg(all(target_os = "linux", feature = "bench"))]
+pub mod registered;
+#[cfg(all(unix, feature = "libc"))]
+pub mod remove;
+#[cfg(feature = "bench")]
+pub mod rename;
+pub mod retry;
+pub mod rundir;
+#[cfg(all(unix, feature = "libc"))]
+pub mod rusage;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod sample;
+#[cfg(feature = "bench")]
+pub mod scan;
+pub mod schedule;
+pub mod select;
+#[cfg(all(unix, feature = "bench"))]
+pub mod selftest;
+pub mod sketch;
+#[cfg(feature = "rayon")]
+pub mod snapshot;
+pub mod soak;
+pub mod space;
+#[cfg(all(unix, feature = "libc"))]
+pub mod sparse;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stash;
+#[cfg(feature = "tokio")]
+pub mod stream;
+pub mod throttle;
+pub mod trace;
+pub mod transform;
+pub mod trash;
+pub mod treemap;
+#[cfg(all(target_os = "linux", feature = "libc"))]
+pub mod uring;
+#[cfg(feature = "bench")]
+pub mod update;
+#[cfg(feature = "bench")]
+pub mod watch;
+pub mod watchdog;
+pub mod wear;
+#[cfg(feature = "bench")]
+pub mod workload;
+#[cfg(all(unix, feature = "libc"))]
+pub mod xattr;
+#[cfg(feature = "zstd")]
+pub mod zstd;
+
+/// The fastest strategies `io calibrate` found for the storage the current directory is
+/// on, or `None` if it hasn't been calibrated; see [`calibration`].
+pub fn calibrated_backend() -> Option<calibration::Calibration> {
+    calibration::load(&std::env::current_dir().ok()?)
+}
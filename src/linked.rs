@@ -0,0 +1,198 @@
+//! Creating small files with linked io_uring chains: each file is an `openat` into a
+//! registered table slot, a write through the slot, an optional `fsync` and a close, linked
+//! so each step runs only once the one before it succeeded. A whole chain, and a whole
+//! batch of chains, goes to the kernel in one `io_uring_enter`, which is as few syscalls as
+//! the create phase can get; the create phase proper makes three or four per file.
+//!
+//! Direct opens need Linux 5.15; older kernels, and sandboxes without io_uring, skip the
+//! comparison.
+
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::bench::{self, Failure, Options, SyncMode};
+use crate::uring::{Entry, Ring};
+
+/// Files per submission when none are asked for.
+pub const DEFAULT_BATCHES: [u32; 4] = [1, 8, 64, 256];
+
+/// The steps of a file's chain, in order; the last is always the close.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Open,
+    Write,
+    Sync,
+    Close,
+}
+
+impl Step {
+    const ALL: [Step; 4] = [Step::Open, Step::Write, Step::Sync, Step::Close];
+
+    fn of(user_data: u64) -> (usize, Step) {
+        ((user_data / 4) as usize, Step::ALL[(user_data % 4) as usize])
+    }
+
+    fn user_data(self, slot: usize) -> u64 {
+        slot as u64 * 4 + self as u64
+    }
+}
+
+/// A ring with one file table slot and one buffer per file of a batch. The ring is
+/// declared first so it's dropped before the buffers it may still write from are freed.
+struct Chains {
+    ring: Ring,
+    buffers: Vec<Vec<u8>>,
+    /// `io_uring_enter` calls made.
+    enters: u64,
+}
+
+impl Chains {
+    fn new(batch: u32) -> io::Result<Chains> {
+        let unavailable = |what: &str, e: io::Error| io::Error::new(io::ErrorKind::Unsupported, format!("{} failed: {}", what, e));
+        let mut ring = Ring::new(batch * Step::ALL.len() as u32).map_err(|e| unavailable("io_uring setup", e))?;
+        ring.register_files(&vec![-1; batch as usize]).map_err(|e| unavailable("registering files", e))?;
+        Ok(Chains { ring, buffers: vec![Vec::new(); batch as usize], enters: 0 })
+    }
+
+    /// Creates the files of `batch`, the first being file `first` of the workload.
+    fn create_batch(&mut self, batch: &[PathBuf], first: usize, options: &Options) -> io::Result<()> {
+        let names = batch.iter().map(|path| CString::new(path.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))).collect::<io::Result<Vec<_>>>()?;
+        let sync = options.sync;
+        let mut pending = 0;
+        for (slot, name) in names.iter().enumerate() {
+            let buffer = &mut self.buffers[slot];
+            bench::with_content(options, first + slot, false, |content| {
+                buffer.clear();
+                buffer.extend_from_slice(content);
+            });
+            let mut chain = vec![
+                Entry::openat(libc::AT_FDCWD, name, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC, 0o644).direct(slot as u32).user_data(Step::Open.user_data(slot)),
+                Entry::write(0, buffer.as_ptr(), buffer.len() as u32, 0).fixed_file(slot as u32).user_data(Step::Write.user_data(slot)),
+            ];
+            if sync != SyncMode::None {
+                chain.push(Entry::fsync(0, sync == SyncMode::Data).fixed_file(slot as u32).user_data(Step::Sync.user_data(slot)));
+            }
+            chain.push(Entry::close(0).direct(slot as u32).user_data(Step::Close.user_data(slot)));
+            let last = chain.len() - 1;
+            for (i, entry) in chain.iter().enumerate() {
+                let entry = if i < last { entry.link() } else { *entry };
+                // The ring holds every step of a batch, and the names and buffers outlive
+                // the completions reaped below.
+                assert!(unsafe { self.ring.push(&entry) }, "io_uring submission queue is full");
+            }
+            pending += chain.len();
+        }
+        let mut first_error = None;
+        while pending > 0 {
+            let Some(completion) = self.ring.pop() else {
+                self.enters += 1;
+                if let Err(e) = self.ring.submit(pending as u32) {
+                    // The kernel may still write from the buffers and open the names.
+                    std::mem::forget(std::mem::take(&mut self.buffers));
+                    std::mem::forget(names);
+                    return Err(e);
+                }
+                continue;
+            };
+            pending -= 1;
+            let (slot, step) = Step::of(completion.user_data);
+            let failed = match (step, completion.result()) {
+                (Step::Close, result) => {
+                    options.progress.advance();
+                    result.err().filter(|e| e.raw_os_error() != Some(libc::ECANCELED))
+                }
+                (Step::Open, Err(e)) if e.raw_os_error() == Some(libc::EINVAL) => {
+                    Some(io::Error::new(io::ErrorKind::Unsupported, format!("opening into a file table slot failed ({}); it needs Linux 5.15", e)))
+                }
+                (Step::Write, Ok(written)) if written as usize != self.buffers[slot].len() => Some(io::Error::new(io::ErrorKind::WriteZero, "short write")),
+                (_, result) => result.err().filter(|e| e.raw_os_error() != Some(libc::ECANCELED)),
+            };
+            if let Some(e) = failed {
+                first_error.get_or_insert_with(|| io::Error::new(e.kind(), format!("{}: {}", batch[slot].display(), e)));
+            }
+        }
+        match first_error {
+            Some(e) => {
+                // A chain that broke after its open cancelled the close, leaving the file in
+                // its slot.
+                self.ring.update_files(0, &vec![-1; batch.len()])?;
+                Err(e)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Creates and writes every file in `paths` like the create phase, through one ring per
+/// worker taking `batch` files' chains per `io_uring_enter`. Returns the enters made.
+pub fn create_files_linked(paths: &[PathBuf], options: &Options, batch: u32) -> io::Result<u64> {
+    let batch = batch.max(1);
+    let enters = AtomicU64::new(0);
+    options.progress.begin(paths.len());
+    paths.par_chunks(batch as usize).enumerate().try_for_each_init(
+        || Chains::new(batch),
+        |chains, (chunk, files)| {
+            let chains = chains.as_mut().map_err(|e| io::Error::new(e.kind(), e.to_string()))?;
+            options.pause.wait();
+            let before = chains.enters;
+            let created = chains.create_batch(files, chunk * batch as usize, options);
+            enters.fetch_add(chains.enters - before, Ordering::Relaxed);
+            created
+        },
+    )?;
+    Ok(enters.into_inner())
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    /// Files whose chains went in each submission.
+    pub batch: u32,
+    pub elapsed: Duration,
+    /// `io_uring_enter` calls made, across every worker.
+    pub enters: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct LinkedResult {
+    /// The create phase, opening, writing and closing each file with its own syscalls.
+    pub per_file: Duration,
+    pub batches: Vec<BatchResult>,
+    /// Why the linked runs were skipped.
+    pub skipped: Option<io::Error>,
+    pub failures: Vec<Failure>,
+}
+
+/// Times creating the configured workload in `dir_path` with the create phase, then with
+/// [`create_files_linked`] at each of `batches` files per submission, deleting the files
+/// untimed after each. Stops at the first batch size the kernel refuses.
+pub fn run(dir_path: &Path, batches: &[u32], options: &Options) -> io::Result<LinkedResult> {
+    let paths = options.workload.paths(dir_path);
+    let mut result = LinkedResult::default();
+    options.failures.take("");
+    let start = Instant::now();
+    bench::phase(options, "Linked per-file create", "linked/per-file", "create", &mut result.failures, || bench::create_files(&paths, options))?;
+    result.per_file = start.elapsed();
+
+    for &batch in batches {
+        bench::phase(options, "Linked delete", "linked/delete", "delete", &mut result.failures, || bench::delete_files(&paths, options))?;
+
+        options.progress.set_stage(format!("Linked create, {} per submission", batch));
+        options.op_scope.set(format!("linked/{}", batch));
+        let start = Instant::now();
+        match create_files_linked(&paths, options, batch) {
+            Ok(enters) => result.batches.push(BatchResult { batch: batch.max(1), elapsed: start.elapsed(), enters }),
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                result.skipped = Some(e);
+                break;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(result)
+}
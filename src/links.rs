@@ -0,0 +1,142 @@
+//! Creating many hard links or symlinks at once, for content-addressed stores (pnpm-style)
+//! that materialize trees by linking files out of a store instead of copying them.
+//!
+//! [`link_many`] and [`symlink_many`] take `(target, link)` pairs and run in parallel with
+//! the `rayon` feature. Like `std::fs`, they fail when a link path already exists; the
+//! error names the link that failed.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+#[cfg(feature = "bench")]
+use std::path::PathBuf;
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+
+fn with_link(link: &Path, result: io::Result<()>) -> io::Result<()> {
+    result.map_err(|e| io::Error::new(e.kind(), format!("{}: {}", link.display(), e)))
+}
+
+fn each_pair<P, Q>(pairs: &[(P, Q)], f: impl Fn(&Path, &Path) -> io::Result<()> + Sync) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+    Q: AsRef<Path> + Sync,
+{
+    let run = |(target, link): &(P, Q)| with_link(link.as_ref(), f(target.as_ref(), link.as_ref()));
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        pairs.par_iter().try_for_each(run)
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        pairs.iter().try_for_each(run)
+    }
+}
+
+/// Hard-links every `link` to its `target`. Targets and links must be on the same
+/// filesystem.
+pub fn link_many<P, Q>(pairs: &[(P, Q)]) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+    Q: AsRef<Path> + Sync,
+{
+    each_pair(pairs, |target, link| fs::hard_link(target, link))
+}
+
+/// Creates every `link` as a symlink to its `target`. Relative targets are resolved from
+/// the link's directory when the link is followed, as with `ln -s`.
+pub fn symlink_many<P, Q>(pairs: &[(P, Q)]) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+    Q: AsRef<Path> + Sync,
+{
+    each_pair(pairs, symlink)
+}
+
+/// A symlink to a file.
+pub fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (target, link);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "symlinks are not supported on this platform"))
+    }
+}
+
+/// How a tree is materialized from the store.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Materialize {
+    /// `fs::copy`, which may share extents on filesystems whose `copy_file_range` does.
+    Copy,
+    HardLink,
+    Symlink,
+}
+
+#[cfg(feature = "bench")]
+impl Materialize {
+    pub const ALL: [Materialize; 3] = [Materialize::Copy, Materialize::HardLink, Materialize::Symlink];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Materialize::Copy => "copy",
+            Materialize::HardLink => "hardlink",
+            Materialize::Symlink => "symlink",
+        }
+    }
+
+    fn apply(self, target: &Path, link: &Path) -> io::Result<()> {
+        match self {
+            Materialize::Copy => fs::copy(target, link).map(drop),
+            Materialize::HardLink => fs::hard_link(target, link),
+            Materialize::Symlink => symlink(target, link),
+        }
+    }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct LinkResult {
+    pub times: Vec<(Materialize, Duration)>,
+    pub failures: Vec<Failure>,
+}
+
+/// Creates the configured workload as a store in `dir_path/store`, then times
+/// materializing a tree of all its files by each method in turn, removing each tree
+/// untimed before the next.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<LinkResult> {
+    let store = dir_path.join("store");
+    fs::create_dir_all(&store)?;
+    let paths = options.workload.paths(&store);
+    let mut result = LinkResult::default();
+    options.failures.take("");
+
+    bench::phase(options, "Links create", "links/create", "create", &mut result.failures, || bench::create_files(&paths, options))?;
+
+    for method in Materialize::ALL {
+        let tree = dir_path.join(format!("tree-{}", method.name()));
+        fs::create_dir_all(&tree)?;
+        let links: Vec<PathBuf> = bench::file_paths(&tree, paths.len());
+        let start = Instant::now();
+        bench::phase(options, format!("Links {}", method.name()), format!("links/{}", method.name()), method.name(), &mut result.failures, || {
+            bench::each_indexed(&paths, options, |index, path| method.apply(path, &links[index]))
+        })?;
+        result.times.push((method, start.elapsed()));
+        fs::remove_dir_all(&tree)?;
+    }
+
+    bench::phase(options, "Links delete", "links/delete", "delete", &mut result.failures, || bench::delete_files(&paths, options))?;
+    Ok(result)
+}
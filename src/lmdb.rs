@@ -0,0 +1,266 @@
+//! Small files as keys of an LMDB database, the embedded key-value store to weigh against
+//! thousands of small files. An [`Lmdb`] is an environment kept in one memory-mapped file
+//! (with a `-lock` file next to it) holding one unnamed database; reads copy the value out
+//! of the map inside a read-only transaction, and writes go through [`WriteTxn`], of which
+//! LMDB lets one exist at a time.
+//!
+//! Like [`crate::sqlite`], this binds the system's `liblmdb` directly, so the `lmdb` feature
+//! needs the library but nothing from crates.io.
+
+use std::ffi::{CStr, CString, c_char, c_int, c_uint, c_void};
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+#[repr(C)]
+struct MdbEnv {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct MdbTxn {
+    _private: [u8; 0],
+}
+
+type MdbDbi = c_uint;
+
+#[repr(C)]
+struct MdbVal {
+    size: usize,
+    data: *mut c_void,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct MdbStat {
+    page_size: c_uint,
+    depth: c_uint,
+    branch_pages: usize,
+    leaf_pages: usize,
+    overflow_pages: usize,
+    entries: usize,
+}
+
+#[link(name = "lmdb")]
+unsafe extern "C" {
+    fn mdb_env_create(env: *mut *mut MdbEnv) -> c_int;
+    fn mdb_env_set_mapsize(env: *mut MdbEnv, size: usize) -> c_int;
+    fn mdb_env_open(env: *mut MdbEnv, path: *const c_char, flags: c_uint, mode: libc::mode_t) -> c_int;
+    fn mdb_env_set_flags(env: *mut MdbEnv, flags: c_uint, on: c_int) -> c_int;
+    fn mdb_env_sync(env: *mut MdbEnv, force: c_int) -> c_int;
+    fn mdb_env_close(env: *mut MdbEnv);
+    fn mdb_txn_begin(env: *mut MdbEnv, parent: *mut MdbTxn, flags: c_uint, txn: *mut *mut MdbTxn) -> c_int;
+    fn mdb_txn_commit(txn: *mut MdbTxn) -> c_int;
+    fn mdb_txn_abort(txn: *mut MdbTxn);
+    fn mdb_dbi_open(txn: *mut MdbTxn, name: *const c_char, flags: c_uint, dbi: *mut MdbDbi) -> c_int;
+    fn mdb_put(txn: *mut MdbTxn, dbi: MdbDbi, key: *mut MdbVal, data: *mut MdbVal, flags: c_uint) -> c_int;
+    fn mdb_get(txn: *mut MdbTxn, dbi: MdbDbi, key: *mut MdbVal, data: *mut MdbVal) -> c_int;
+    fn mdb_del(txn: *mut MdbTxn, dbi: MdbDbi, key: *mut MdbVal, data: *mut MdbVal) -> c_int;
+    fn mdb_stat(txn: *mut MdbTxn, dbi: MdbDbi, stat: *mut MdbStat) -> c_int;
+    fn mdb_strerror(err: c_int) -> *const c_char;
+}
+
+/// The environment is a file rather than a directory.
+const MDB_NOSUBDIR: c_uint = 0x4000;
+/// Commits don't flush; changeable after opening.
+const MDB_NOSYNC: c_uint = 0x10000;
+const MDB_RDONLY: c_uint = 0x20000;
+/// Read transactions aren't tied to the thread that began them.
+const MDB_NOTLS: c_uint = 0x200000;
+const MDB_CREATE: c_uint = 0x40000;
+const MDB_NOTFOUND: c_int = -30798;
+
+pub struct Lmdb {
+    path: PathBuf,
+    env: *mut MdbEnv,
+    dbi: MdbDbi,
+}
+
+// LMDB environments are safe to share between threads; transactions are what aren't.
+unsafe impl Send for Lmdb {}
+unsafe impl Sync for Lmdb {}
+
+impl Lmdb {
+    /// Opens the environment at `path`, creating it if needed, with room for `map_size`
+    /// bytes of database; the file only grows as pages are used.
+    pub fn open(path: impl Into<PathBuf>, map_size: usize) -> io::Result<Lmdb> {
+        let path = path.into();
+        let name = CString::new(path.to_string_lossy().into_owned()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut env = ptr::null_mut();
+        check(unsafe { mdb_env_create(&mut env) }, &path)?;
+        // From here on, dropping the half-built environment closes it.
+        let mut lmdb = Lmdb { path, env, dbi: 0 };
+        check(unsafe { mdb_env_set_mapsize(env, map_size) }, &lmdb.path)?;
+        check(unsafe { mdb_env_open(env, name.as_ptr(), MDB_NOSUBDIR | MDB_NOTLS, 0o644) }, &lmdb.path)?;
+        let mut txn = ptr::null_mut();
+        check(unsafe { mdb_txn_begin(env, ptr::null_mut(), 0, &mut txn) }, &lmdb.path)?;
+        if let Err(e) = check(unsafe { mdb_dbi_open(txn, ptr::null(), MDB_CREATE, &mut lmdb.dbi) }, &lmdb.path) {
+            unsafe { mdb_txn_abort(txn) };
+            return Err(e);
+        }
+        check(unsafe { mdb_txn_commit(txn) }, &lmdb.path)?;
+        Ok(lmdb)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether each commit waits for the device, or leaves writeback to the kernel.
+    pub fn set_durable(&self, durable: bool) -> io::Result<()> {
+        check(unsafe { mdb_env_set_flags(self.env, MDB_NOSYNC, c_int::from(!durable)) }, &self.path)
+    }
+
+    /// Flushes every commit to the device.
+    pub fn sync(&self) -> io::Result<()> {
+        check(unsafe { mdb_env_sync(self.env, 1) }, &self.path)
+    }
+
+    /// Begins the write transaction, waiting while another thread or process has one.
+    pub fn write(&self) -> io::Result<WriteTxn<'_>> {
+        let mut txn = ptr::null_mut();
+        check(unsafe { mdb_txn_begin(self.env, ptr::null_mut(), 0, &mut txn) }, &self.path)?;
+        Ok(WriteTxn { lmdb: self, txn, _thread: PhantomData })
+    }
+
+    /// Stores `bytes` as `name` in a transaction of its own.
+    pub fn put(&self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        let mut txn = self.write()?;
+        txn.put(name, bytes)?;
+        txn.commit()
+    }
+
+    /// Removes `name` in a transaction of its own; false if it didn't exist.
+    pub fn delete(&self, name: &str) -> io::Result<bool> {
+        let mut txn = self.write()?;
+        let deleted = txn.delete(name)?;
+        txn.commit()?;
+        Ok(deleted)
+    }
+
+    /// What `name` holds, if it exists.
+    pub fn get(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let mut buf = Vec::new();
+        Ok(self.get_into(name, &mut buf)?.then_some(buf))
+    }
+
+    /// Reads what `name` holds into `buf`, replacing its contents; false if it doesn't exist.
+    pub fn get_into(&self, name: &str, buf: &mut Vec<u8>) -> io::Result<bool> {
+        self.read(|txn| {
+            let mut value = MdbVal { size: 0, data: ptr::null_mut() };
+            match unsafe { mdb_get(txn, self.dbi, &mut key(name), &mut value) } {
+                MDB_NOTFOUND => Ok(false),
+                code => {
+                    check(code, &self.path)?;
+                    buf.clear();
+                    if value.size > 0 {
+                        // The value points into the map, valid until the transaction ends.
+                        buf.extend_from_slice(unsafe { std::slice::from_raw_parts(value.data.cast::<u8>(), value.size) });
+                    }
+                    Ok(true)
+                }
+            }
+        })
+    }
+
+    /// How many names are stored.
+    pub fn len(&self) -> io::Result<usize> {
+        self.read(|txn| {
+            let mut stat = MdbStat::default();
+            check(unsafe { mdb_stat(txn, self.dbi, &mut stat) }, &self.path)?;
+            Ok(stat.entries)
+        })
+    }
+
+    pub fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Runs `f` in a read-only transaction.
+    fn read<R>(&self, f: impl FnOnce(*mut MdbTxn) -> io::Result<R>) -> io::Result<R> {
+        let mut txn = ptr::null_mut();
+        check(unsafe { mdb_txn_begin(self.env, ptr::null_mut(), MDB_RDONLY, &mut txn) }, &self.path)?;
+        let result = f(txn);
+        unsafe { mdb_txn_abort(txn) };
+        result
+    }
+}
+
+impl Drop for Lmdb {
+    fn drop(&mut self) {
+        unsafe { mdb_env_close(self.env) };
+    }
+}
+
+/// The environment's write transaction: nothing it writes is visible or durable until
+/// [`WriteTxn::commit`], and dropping it uncommitted discards the writes. LMDB ties it to
+/// the thread that began it.
+pub struct WriteTxn<'a> {
+    lmdb: &'a Lmdb,
+    txn: *mut MdbTxn,
+    _thread: PhantomData<*mut ()>,
+}
+
+impl WriteTxn<'_> {
+    /// Stores `bytes` as `name`, replacing what it held.
+    pub fn put(&mut self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        let mut value = MdbVal { size: bytes.len(), data: bytes.as_ptr().cast_mut().cast() };
+        check(unsafe { mdb_put(self.txn, self.lmdb.dbi, &mut key(name), &mut value, 0) }, &self.lmdb.path)
+    }
+
+    /// Removes `name`; false if it didn't exist.
+    pub fn delete(&mut self, name: &str) -> io::Result<bool> {
+        match unsafe { mdb_del(self.txn, self.lmdb.dbi, &mut key(name), ptr::null_mut()) } {
+            MDB_NOTFOUND => Ok(false),
+            code => check(code, &self.lmdb.path).map(|()| true),
+        }
+    }
+
+    pub fn commit(mut self) -> io::Result<()> {
+        let txn = std::mem::replace(&mut self.txn, ptr::null_mut());
+        check(unsafe { mdb_txn_commit(txn) }, &self.lmdb.path)
+    }
+}
+
+impl Drop for WriteTxn<'_> {
+    fn drop(&mut self) {
+        if !self.txn.is_null() {
+            unsafe { mdb_txn_abort(self.txn) };
+        }
+    }
+}
+
+/// `name` as a key; LMDB only reads it.
+fn key(name: &str) -> MdbVal {
+    MdbVal { size: name.len(), data: name.as_ptr().cast_mut().cast() }
+}
+
+/// LMDB's result codes are `errno` values where positive and its own below zero.
+fn check(code: c_int, path: &Path) -> io::Result<()> {
+    match code {
+        0 => Ok(()),
+        code if code > 0 => {
+            let error = io::Error::from_raw_os_error(code);
+            Err(io::Error::new(error.kind(), format!("{}: {}", path.display(), error)))
+        }
+        code => {
+            let message = unsafe { CStr::from_ptr(mdb_strerror(code)) }.to_string_lossy();
+            Err(io::Error::other(format!("{}: lmdb: {}", path.display(), message)))
+        }
+    }
+}
+
+/// Deletes the environment at `path` with its lock file.
+pub fn remove(path: &Path) -> io::Result<()> {
+    let mut lock = path.as_os_str().to_owned();
+    lock.push("-lock");
+    for file in [PathBuf::from(lock), path.to_path_buf()] {
+        match fs::remove_file(file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,314 @@
+//! Advisory file locks, for package managers and build caches that guard shared state with
+//! lock files: one lock at a time with an optional timeout, and [`lock_many`] for taking a
+//! set of them without deadlocking against another process taking an overlapping set.
+//!
+//! Both kinds lock a whole file and belong to the open file, not the process, so threads
+//! of one process exclude each other as separate processes do: `flock` always, and `fcntl`
+//! through open file description locks (`F_OFD_SETLK`), which need Linux. Elsewhere `fcntl`
+//! falls back to classic POSIX record locks, which a process holds for all its threads and
+//! loses when it closes any descriptor of the file. The locks are advisory: only code that
+//! takes them is kept out.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(feature = "bench")]
+use std::fs;
+#[cfg(feature = "bench")]
+use std::os::unix::fs::FileExt;
+#[cfg(feature = "bench")]
+use std::sync::Mutex;
+#[cfg(feature = "bench")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "bench")]
+use rayon::prelude::*;
+
+#[cfg(feature = "bench")]
+use crate::bench::{Latency, Options};
+
+/// The first wait between tries while a lock with a timeout is held elsewhere, doubled up
+/// to [`MAX_BACKOFF`].
+const MIN_BACKOFF: Duration = Duration::from_micros(50);
+const MAX_BACKOFF: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    /// `flock(2)`.
+    Flock,
+    /// `fcntl(2)` whole-file locks.
+    Fcntl,
+}
+
+impl LockKind {
+    pub const ALL: [LockKind; 2] = [LockKind::Flock, LockKind::Fcntl];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LockKind::Flock => "flock",
+            LockKind::Fcntl => "fcntl",
+        }
+    }
+}
+
+impl fmt::Display for LockKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for LockKind {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<LockKind> {
+        LockKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("unknown lock kind '{}' (expected flock or fcntl)", s)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Held by any number of holders at once, excluding exclusive ones.
+    Shared,
+    Exclusive,
+}
+
+/// A held lock on an open file, released when dropped or [`FileLock::unlock`]ed.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+    kind: LockKind,
+}
+
+impl FileLock {
+    /// Opens `path`, creating it if needed, and locks it, waiting as long as it takes with
+    /// no `timeout` and failing with [`io::ErrorKind::TimedOut`] once `timeout` passes.
+    pub fn acquire(path: &Path, kind: LockKind, mode: LockMode, timeout: Option<Duration>) -> io::Result<FileLock> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let Some(timeout) = timeout else {
+            lock(&file, kind, mode, true)?;
+            return Ok(FileLock { file, path: path.to_path_buf(), kind });
+        };
+        let deadline = Instant::now() + timeout;
+        let mut backoff = MIN_BACKOFF;
+        while !lock(&file, kind, mode, false)? {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, format!("{} still locked after {:?}", path.display(), timeout)));
+            }
+            thread::sleep(backoff.min(deadline - now));
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+        Ok(FileLock { file, path: path.to_path_buf(), kind })
+    }
+
+    /// Locks `path` if no one else holds a conflicting lock, without waiting.
+    pub fn try_acquire(path: &Path, kind: LockKind, mode: LockMode) -> io::Result<Option<FileLock>> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        Ok(lock(&file, kind, mode, false)?.then(|| FileLock { file, path: path.to_path_buf(), kind }))
+    }
+
+    /// The locked file, open for reading and writing.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Releases the lock, reporting what closing the file alone would not.
+    pub fn unlock(self) -> io::Result<()> {
+        unlock(&self.file, self.kind)
+    }
+}
+
+/// Locks every one of `paths` in `mode`, in sorted order so that callers locking
+/// overlapping sets can't deadlock, with `timeout` covering the whole set. On failure the
+/// locks already taken are released.
+pub fn lock_many(paths: &[PathBuf], kind: LockKind, mode: LockMode, timeout: Option<Duration>) -> io::Result<Vec<FileLock>> {
+    let mut sorted: Vec<&PathBuf> = paths.iter().collect();
+    sorted.sort();
+    sorted.dedup();
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    sorted.into_iter().map(|path| FileLock::acquire(path, kind, mode, deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())))).collect()
+}
+
+/// Takes the lock, returning false when `wait` is unset and someone else holds it.
+fn lock(file: &File, kind: LockKind, mode: LockMode, wait: bool) -> io::Result<bool> {
+    let result = match kind {
+        LockKind::Flock => {
+            let operation = match mode {
+                LockMode::Shared => libc::LOCK_SH,
+                LockMode::Exclusive => libc::LOCK_EX,
+            };
+            unsafe { libc::flock(file.as_raw_fd(), if wait { operation } else { operation | libc::LOCK_NB }) }
+        }
+        LockKind::Fcntl => {
+            let lock_type = match mode {
+                LockMode::Shared => libc::F_RDLCK,
+                LockMode::Exclusive => libc::F_WRLCK,
+            };
+            fcntl_lock(file, lock_type, wait)
+        }
+    };
+    if result == 0 {
+        return Ok(true);
+    }
+    match io::Error::last_os_error() {
+        // Held elsewhere; `fcntl` may say so with `EACCES`.
+        e if !wait && (e.kind() == io::ErrorKind::WouldBlock || e.raw_os_error() == Some(libc::EACCES)) => Ok(false),
+        e if e.kind() == io::ErrorKind::Interrupted && wait => lock(file, kind, mode, wait),
+        e => Err(e),
+    }
+}
+
+fn unlock(file: &File, kind: LockKind) -> io::Result<()> {
+    let result = match kind {
+        LockKind::Flock => unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) },
+        LockKind::Fcntl => fcntl_lock(file, libc::F_UNLCK, false),
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// A whole-file `fcntl` lock of `lock_type`, per open file description where there are such.
+fn fcntl_lock(file: &File, lock_type: libc::c_int, wait: bool) -> libc::c_int {
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = lock_type as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    #[cfg(target_os = "linux")]
+    let command = if wait { libc::F_OFD_SETLKW } else { libc::F_OFD_SETLK };
+    #[cfg(not(target_os = "linux"))]
+    let command = if wait { libc::F_SETLKW } else { libc::F_SETLK };
+    unsafe { libc::fcntl(file.as_raw_fd(), command, &lock) }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy)]
+pub struct LockConfig {
+    /// The lock files contended for.
+    pub lock_files: usize,
+    /// Lock files each acquisition takes together with [`lock_many`].
+    pub per_acquisition: usize,
+    /// Acquisitions in all, spread over the threads.
+    pub acquisitions: usize,
+    /// How long an acquisition may wait before it gives up.
+    pub timeout: Duration,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct KindResult {
+    pub kind: LockKind,
+    pub elapsed: Duration,
+    /// Acquisitions that got every lock.
+    pub acquired: u64,
+    /// Acquisitions that gave up at the timeout.
+    pub timed_out: u64,
+    /// Increments missing from the counters: nonzero when the locks didn't exclude.
+    pub lost_updates: u64,
+    /// From asking for a set of locks to holding them all, in seconds.
+    pub wait: Latency,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct LockResult {
+    /// The threads contending.
+    pub threads: usize,
+    pub kinds: Vec<KindResult>,
+}
+
+/// The lock files acquisition `n` of `thread` takes: `per_acquisition` of `lock_files`,
+/// consecutive from a start that moves with both.
+#[cfg(feature = "bench")]
+fn pick(config: &LockConfig, thread: usize, n: usize) -> impl Iterator<Item = usize> {
+    let start = (thread.wrapping_mul(7919) + n.wrapping_mul(104_729)) % config.lock_files;
+    let (files, take) = (config.lock_files, config.per_acquisition.min(config.lock_files));
+    (0..take).map(move |i| (start + i) % files)
+}
+
+/// For each of `kinds`, has every thread of the current rayon pool repeatedly lock a few of
+/// `config.lock_files` lock files in `dir_path` exclusively with [`lock_many`] and, while
+/// holding them, increment a counter stored in each. The counters are checked afterwards:
+/// an increment lost means two holders overlapped. Each kind's lock files are removed
+/// before the next.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, config: LockConfig, kinds: &[LockKind], options: &Options) -> io::Result<LockResult> {
+    if config.lock_files == 0 || config.per_acquisition == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "contention needs at least one lock file per acquisition"));
+    }
+    let threads = rayon::current_num_threads();
+    let mut result = LockResult { threads, ..LockResult::default() };
+    for &kind in kinds {
+        let dir = dir_path.join(kind.name());
+        fs::create_dir_all(&dir)?;
+        let paths: Vec<PathBuf> = (0..config.lock_files).map(|i| dir.join(format!("lock_{}", i))).collect();
+        options.progress.set_stage(format!("Lock {}", kind));
+        options.op_scope.set(format!("lock/{}", kind));
+        options.progress.begin(threads);
+        let (acquired, timed_out) = (AtomicU64::new(0), AtomicU64::new(0));
+        let wait = Mutex::new(Latency::default());
+
+        let start = Instant::now();
+        (0..threads).into_par_iter().try_for_each(|thread| -> io::Result<()> {
+            let mut waits = Vec::new();
+            for n in 0..config.acquisitions / threads + usize::from(thread < config.acquisitions % threads) {
+                let set: Vec<PathBuf> = pick(&config, thread, n).map(|i| paths[i].clone()).collect();
+                let asked = Instant::now();
+                let locks = match lock_many(&set, kind, LockMode::Exclusive, Some(config.timeout)) {
+                    Ok(locks) => locks,
+                    Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                        timed_out.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                waits.push(asked.elapsed().as_secs_f64());
+                for lock in &locks {
+                    let mut counter = [0; 8];
+                    let read = lock.file().read_at(&mut counter, 0)?;
+                    let count = if read == 8 { u64::from_le_bytes(counter) } else { 0 };
+                    lock.file().write_all_at(&(count + 1).to_le_bytes(), 0)?;
+                }
+                locks.into_iter().try_for_each(FileLock::unlock)?;
+                acquired.fetch_add(1, Ordering::Relaxed);
+            }
+            let mut wait = wait.lock().unwrap_or_else(|e| e.into_inner());
+            for seconds in waits {
+                wait.running.add(seconds);
+                wait.digest.add(seconds);
+            }
+            options.progress.advance();
+            Ok(())
+        })?;
+        let elapsed = start.elapsed();
+
+        let acquired = acquired.into_inner();
+        let expected = acquired * config.per_acquisition.min(config.lock_files) as u64;
+        // A lock file no acquisition happened to take was never created, and counts 0.
+        let counted: u64 = paths
+            .iter()
+            .map(|path| match fs::read(path) {
+                Ok(bytes) => Ok(bytes.get(..8).map_or(0, |counter| u64::from_le_bytes(counter.try_into().unwrap()))),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+                Err(e) => Err(e),
+            })
+            .sum::<io::Result<u64>>()?;
+        let wait = wait.into_inner().unwrap_or_else(|e| e.into_inner());
+        result.kinds.push(KindResult { kind, elapsed, acquired, timed_out: timed_out.into_inner(), lost_updates: expected.saturating_sub(counted), wait });
+        fs::remove_dir_all(&dir)?;
+    }
+    Ok(result)
+}
@@ -0,0 +1,215 @@
+//! Structured diagnostics: the warnings and notes the crate's runs emit besides their
+//! results, as events with a level, a target (the module they come from), a message and
+//! key-value fields, each carrying the spans open on its thread, such as the strategy and
+//! phase a benchmark was in ([`span`]).
+//!
+//! Events at [`level`] or above go to stderr, as text or, with [`Format::Json`], one JSON
+//! object per line for log collectors. A library consumer with observability of its own
+//! installs a [`set_sink`] instead and forwards the events to it, `tracing` or otherwise;
+//! the crate doesn't depend on a logging framework itself. A span logs its close at
+//! [`Level::Debug`] with how long it was open, and the benchmark's workers log each file's
+//! backend and time at [`Level::Trace`].
+
+use std::cell::RefCell;
+use std::fmt::{self, Write};
+use std::io;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Instant;
+
+use crate::human;
+use crate::json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub const ALL: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Level> {
+        Level::ALL
+            .into_iter()
+            .find(|level| level.name() == s)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("unknown log level '{}', expected error|warn|info|debug|trace", s)))
+    }
+}
+
+/// How events written to stderr look.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// `warn [Smart/update] watchdog: message key=value`.
+    #[default]
+    Text,
+    /// `{"level":"warn","target":"watchdog","message":..,"spans":{..},"fields":{..}}`.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Format> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown log format '{}', expected text|json", s))),
+        }
+    }
+}
+
+/// One thing that happened.
+#[derive(Debug, Clone, Copy)]
+pub struct Event<'a> {
+    pub level: Level,
+    pub target: &'static str,
+    pub message: &'a str,
+    pub fields: &'a [(&'static str, String)],
+    /// The spans open on the thread, outermost first.
+    pub spans: &'a [(&'static str, String)],
+}
+
+impl Event<'_> {
+    pub fn to_json(&self) -> Value {
+        let pairs = |pairs: &[(&'static str, String)]| Value::Object(pairs.iter().map(|(key, value)| (key.to_string(), Value::from(value.as_str()))).collect());
+        Value::object()
+            .with("level", self.level.name())
+            .with("target", self.target)
+            .with("message", self.message)
+            .with("spans", pairs(self.spans))
+            .with("fields", pairs(self.fields))
+    }
+}
+
+impl fmt::Display for Event<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.level.name())?;
+        if !self.spans.is_empty() {
+            let spans: Vec<&str> = self.spans.iter().map(|(_, value)| value.as_str()).collect();
+            write!(f, "[{}] ", spans.join("/"))?;
+        }
+        write!(f, "{}: {}", self.target, self.message)?;
+        for (key, value) in self.fields {
+            f.write_char(' ')?;
+            if value.contains(char::is_whitespace) || value.is_empty() {
+                write!(f, "{}={:?}", key, value)?;
+            } else {
+                write!(f, "{}={}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where [`set_sink`] sends events.
+pub type Sink = Box<dyn Fn(&Event) + Send + Sync>;
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static FORMAT: AtomicU8 = AtomicU8::new(0);
+static SINK: RwLock<Option<Sink>> = RwLock::new(None);
+
+thread_local! {
+    static SPANS: RefCell<Vec<(&'static str, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Events less severe than `level` are dropped before they are formatted; [`Level::Info`]
+/// to begin with.
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn level() -> Level {
+    Level::ALL[LEVEL.load(Ordering::Relaxed) as usize - 1]
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn set_format(format: Format) {
+    FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+/// Sends events at [`level`] or above to `sink` instead of stderr, or to stderr again with
+/// `None`. The sink may be called from any thread, several at once.
+pub fn set_sink(sink: Option<Sink>) {
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = sink;
+}
+
+/// Emits an event if `level` is enabled.
+pub fn event(level: Level, target: &'static str, message: &str, fields: &[(&'static str, String)]) {
+    if !enabled(level) {
+        return;
+    }
+    // A copy, so a sink may open spans of its own.
+    let spans = SPANS.with_borrow(|spans| spans.clone());
+    let event = Event { level, target, message, fields, spans: &spans };
+    match &*SINK.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(sink) => sink(&event),
+        None if FORMAT.load(Ordering::Relaxed) == Format::Json as u8 => eprintln!("{}", event.to_json()),
+        None => eprintln!("{}", event),
+    }
+}
+
+pub fn error(target: &'static str, message: impl fmt::Display) {
+    if enabled(Level::Error) {
+        event(Level::Error, target, &message.to_string(), &[]);
+    }
+}
+
+pub fn warn(target: &'static str, message: impl fmt::Display) {
+    if enabled(Level::Warn) {
+        event(Level::Warn, target, &message.to_string(), &[]);
+    }
+}
+
+pub fn info(target: &'static str, message: impl fmt::Display) {
+    if enabled(Level::Info) {
+        event(Level::Info, target, &message.to_string(), &[]);
+    }
+}
+
+/// An open span; dropping it closes it.
+#[derive(Debug)]
+#[must_use = "the span closes when dropped"]
+pub struct Span {
+    name: &'static str,
+    opened: Instant,
+}
+
+/// Opens span `name`, such as `phase`, with `value`, such as `update`, on this thread until
+/// the returned [`Span`] is dropped. Spans nest; drop them in reverse order.
+pub fn span(name: &'static str, value: impl Into<String>) -> Span {
+    SPANS.with_borrow_mut(|spans| spans.push((name, value.into())));
+    Span { name, opened: Instant::now() }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        event(Level::Debug, "log", "span closed", &[("span", self.name.to_string()), ("elapsed", human::duration(self.opened.elapsed()))]);
+        SPANS.with_borrow_mut(|spans| {
+            if let Some(at) = spans.iter().rposition(|(name, _)| *name == self.name) {
+                spans.remove(at);
+            }
+        });
+    }
+}
@@ -0,0 +1,247 @@
+//! LZ4 (the block format and the frame format around it), written by hand like
+//! [`crate::deflate`] so content can be compressed without dependencies.
+//!
+//! The compressor is LZ4's fast one: a single hash probe per position and skipping ahead
+//! faster the longer it goes without a match, so it trades ratio for speed the way `lz4 -1`
+//! does. [`compress`] writes frames of independent blocks that record the content size and
+//! that the `lz4` tool reads; [`decompress`] reads any frame, checking whatever checksums
+//! it carries.
+
+use std::io;
+
+use crate::digest::xxh32;
+
+const MAGIC: u32 = 0x184d_2204;
+/// Version 1, independent blocks, content size present.
+const FLAGS: u8 = 0b0110_1000;
+const FLAG_BLOCK_CHECKSUM: u8 = 0b0001_0000;
+const FLAG_CONTENT_SIZE: u8 = 0b0000_1000;
+const FLAG_CONTENT_CHECKSUM: u8 = 0b0000_0100;
+const FLAG_DICTIONARY: u8 = 0b0000_0001;
+/// 4 MiB blocks.
+const BLOCK_DESCRIPTOR: u8 = 7 << 4;
+const BLOCK_MAX: usize = 4 << 20;
+/// A block's size with this bit set means it is stored uncompressed.
+const UNCOMPRESSED: u32 = 1 << 31;
+
+const MIN_MATCH: usize = 4;
+/// The last five bytes of a block are always literals, and the last match starts at
+/// least twelve from the end.
+const LAST_LITERALS: usize = 5;
+const MATCH_LIMIT: usize = 12;
+const MAX_OFFSET: usize = 65_535;
+const HASH_BITS: u32 = 12;
+/// Misses before the compressor starts skipping ahead, as a power of two.
+const SKIP_TRIGGER: u32 = 6;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+/// A literal or match length beyond the token's four bits, as 255s and a remainder.
+fn put_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn sequence(out: &mut Vec<u8>, literals: &[u8], offset: usize, match_len: usize) {
+    let literal_token = literals.len().min(15);
+    let match_token = match_len.saturating_sub(MIN_MATCH).min(15);
+    out.push((literal_token << 4 | match_token) as u8);
+    if literals.len() >= 15 {
+        put_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if match_len == 0 {
+        return;
+    }
+    out.extend_from_slice(&(offset as u16).to_le_bytes());
+    if match_len - MIN_MATCH >= 15 {
+        put_length(out, match_len - MIN_MATCH - 15);
+    }
+}
+
+/// Appends `data` compressed as one LZ4 block to `out`.
+pub fn compress_block(data: &[u8], out: &mut Vec<u8>) {
+    let mut table = vec![u32::MAX; 1 << HASH_BITS];
+    let hash = |word: u32| (word.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize;
+    let mut anchor = 0;
+    if data.len() > MATCH_LIMIT {
+        let limit = data.len() - MATCH_LIMIT;
+        let match_end = data.len() - LAST_LITERALS;
+        let mut pos = 0;
+        while pos < limit {
+            let word = read_u32(data, pos);
+            let slot = &mut table[hash(word)];
+            let candidate = std::mem::replace(slot, pos as u32) as usize;
+            if candidate == u32::MAX as usize || pos - candidate > MAX_OFFSET || read_u32(data, candidate) != word {
+                pos += 1 + ((pos - anchor) >> SKIP_TRIGGER);
+                continue;
+            }
+            let len = MIN_MATCH + data[candidate + MIN_MATCH..].iter().zip(&data[pos + MIN_MATCH..match_end]).take_while(|(a, b)| a == b).count();
+            sequence(out, &data[anchor..pos], pos - candidate, len);
+            pos += len;
+            anchor = pos;
+        }
+    }
+    sequence(out, &data[anchor..], 0, 0);
+}
+
+/// Decompresses one LZ4 block onto the end of `out`, which matches may reach back into,
+/// failing if that would make `out` longer than `limit`.
+pub fn decompress_block(block: &[u8], out: &mut Vec<u8>, limit: usize) -> io::Result<()> {
+    let ends = || invalid("lz4 block ends early");
+    let length = |at: &mut usize, mut len: usize| -> io::Result<usize> {
+        loop {
+            let byte = *block.get(*at).ok_or_else(ends)?;
+            *at += 1;
+            len += byte as usize;
+            if byte != 255 {
+                return Ok(len);
+            }
+        }
+    };
+    let mut at = 0;
+    loop {
+        let token = *block.get(at).ok_or_else(ends)?;
+        at += 1;
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals = length(&mut at, literals)?;
+        }
+        let bytes = block.get(at..at + literals).ok_or_else(ends)?;
+        if out.len() + literals > limit {
+            return Err(invalid("lz4 block decompresses past its size"));
+        }
+        out.extend_from_slice(bytes);
+        at += literals;
+        if at == block.len() {
+            return Ok(());
+        }
+        let offset = u16::from_le_bytes(block.get(at..at + 2).ok_or_else(ends)?.try_into().unwrap()) as usize;
+        at += 2;
+        let mut len = (token & 15) as usize;
+        if len == 15 {
+            len = length(&mut at, len)?;
+        }
+        len += MIN_MATCH;
+        if offset == 0 || offset > out.len() {
+            return Err(invalid("lz4 match reaches before the start"));
+        }
+        if out.len() + len > limit {
+            return Err(invalid("lz4 block decompresses past its size"));
+        }
+        let start = out.len() - offset;
+        if offset >= len {
+            out.extend_from_within(start..start + len);
+        } else {
+            for i in 0..len {
+                out.push(out[start + i]);
+            }
+        }
+    }
+}
+
+/// `data` as an LZ4 frame.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2 + 32);
+    out.extend_from_slice(&MAGIC.to_le_bytes());
+    let descriptor_at = out.len();
+    out.extend_from_slice(&[FLAGS, BLOCK_DESCRIPTOR]);
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    out.push((xxh32(&out[descriptor_at..], 0) >> 8) as u8);
+    for chunk in data.chunks(BLOCK_MAX) {
+        let size_at = out.len();
+        out.extend_from_slice(&[0; 4]);
+        compress_block(chunk, &mut out);
+        let compressed = out.len() - size_at - 4;
+        let size = if compressed < chunk.len() {
+            compressed as u32
+        } else {
+            out.truncate(size_at + 4);
+            out.extend_from_slice(chunk);
+            chunk.len() as u32 | UNCOMPRESSED
+        };
+        out[size_at..size_at + 4].copy_from_slice(&size.to_le_bytes());
+    }
+    out.extend_from_slice(&[0; 4]);
+    out
+}
+
+/// Decompresses every frame in `data`.
+pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let ends = || invalid("lz4 frame ends early");
+    let mut out = Vec::new();
+    let mut at = 0;
+    while at < data.len() {
+        let magic = read_u32(data.get(at..at + 4).ok_or_else(ends)?, 0);
+        // Skippable frames carry metadata for other tools.
+        if magic & 0xffff_fff0 == 0x184d_2a50 {
+            let len = read_u32(data.get(at + 4..at + 8).ok_or_else(ends)?, 0) as usize;
+            at += 8 + len;
+            continue;
+        }
+        if magic != MAGIC {
+            return Err(invalid("not an lz4 frame"));
+        }
+        let descriptor_at = at + 4;
+        let (flags, descriptor) = (*data.get(descriptor_at).ok_or_else(ends)?, *data.get(descriptor_at + 1).ok_or_else(ends)?);
+        if flags >> 6 != 1 || flags & FLAG_DICTIONARY != 0 {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "lz4 frame version or dictionary not supported"));
+        }
+        let block_max = 1 << (8 + 2 * ((descriptor >> 4) & 7) as usize);
+        at = descriptor_at + 2;
+        let mut content_size = None;
+        if flags & FLAG_CONTENT_SIZE != 0 {
+            content_size = Some(u64::from_le_bytes(data.get(at..at + 8).ok_or_else(ends)?.try_into().unwrap()));
+            at += 8;
+        }
+        if *data.get(at).ok_or_else(ends)? != (xxh32(&data[descriptor_at..at], 0) >> 8) as u8 {
+            return Err(invalid("lz4 frame header checksum mismatch"));
+        }
+        at += 1;
+        let start = out.len();
+        if let Some(size) = content_size {
+            out.reserve(size as usize);
+        }
+        loop {
+            let size = read_u32(data.get(at..at + 4).ok_or_else(ends)?, 0);
+            at += 4;
+            if size == 0 {
+                break;
+            }
+            let len = (size & !UNCOMPRESSED) as usize;
+            let block = data.get(at..at + len).ok_or_else(ends)?;
+            at += len;
+            if flags & FLAG_BLOCK_CHECKSUM != 0 {
+                if read_u32(data.get(at..at + 4).ok_or_else(ends)?, 0) != xxh32(block, 0) {
+                    return Err(invalid("lz4 block checksum mismatch"));
+                }
+                at += 4;
+            }
+            if size & UNCOMPRESSED != 0 {
+                out.extend_from_slice(block);
+            } else {
+                let limit = out.len() + block_max;
+                decompress_block(block, &mut out, limit)?;
+            }
+        }
+        if flags & FLAG_CONTENT_CHECKSUM != 0 {
+            if read_u32(data.get(at..at + 4).ok_or_else(ends)?, 0) != xxh32(&out[start..], 0) {
+                return Err(invalid("lz4 content checksum mismatch"));
+            }
+            at += 4;
+        }
+        if content_size.is_some_and(|size| size != (out.len() - start) as u64) {
+            return Err(invalid("lz4 frame doesn't hold its recorded size"));
+        }
+    }
+    Ok(out)
+}
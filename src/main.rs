@@ -1,10 +1,27 @@
+use std::env;
 use std::fs;
 use std::io::Write;
 use std::path::Path;
 use std::time::Instant;
 
+use ::io::log;
+
+mod baseline;
+mod cli;
+mod distributed;
+mod job;
+mod processes;
+mod recommend;
+mod record;
+mod report;
+mod serve;
+mod tui;
+
 const NUM_FILES: usize = 10000;
 
+/// Exit status after the watchdog gives up on stuck operations, as `timeout(1)` uses.
+const EXIT_TIMED_OUT: i32 = 124;
+
 mod file_operations {
     use super::*;
 
@@ -42,14 +59,14 @@ where
     result
 }
 
-fn main() {
+/// The original sequential benchmark: one thread, one file at a time.
+fn run_sequential() {
     let total_start_time = Instant::now();
     let dir_path = Path::new("modules");
 
     time_operation("Create Directory", || {
         if let Err(e) = directory_operations::create_directory(dir_path) {
-            eprintln!("Failed to create directory: {}", e);
-            return;
+            log::error("sequential", format_args!("failed to create directory: {}", e));
         }
     });
 
@@ -58,7 +75,7 @@ fn main() {
             let file_path = dir_path.join(format!("file_{}.txt", i));
             let content = format!("Hello from file {}!", i);
             if let Err(e) = file_operations::create_and_write_file(&file_path, &content) {
-                eprintln!("Failed to create file {}: {}", i, e);
+                log::error("sequential", format_args!("failed to create file {}: {}", i, e));
             }
         }
         println!("{} files created.", NUM_FILES);
@@ -69,7 +86,7 @@ fn main() {
             let file_path = dir_path.join(format!("file_{}.txt", i));
             let content = format!("This is updated content for file {}!", i);
             if let Err(e) = file_operations::create_and_write_file(&file_path, &content) {
-                eprintln!("Failed to update file {}: {}", i, e);
+                log::error("sequential", format_args!("failed to update file {}: {}", i, e));
             }
         }
         println!("{} files updated.", NUM_FILES);
@@ -79,7 +96,7 @@ fn main() {
         for i in 0..NUM_FILES {
             let file_path = dir_path.join(format!("file_{}.txt", i));
             if let Err(e) = file_operations::read_file(&file_path) {
-                eprintln!("Failed to read file {}: {}", i, e);
+                log::error("sequential", format_args!("failed to read file {}: {}", i, e));
             }
         }
     });
@@ -88,14 +105,14 @@ fn main() {
         for i in 0..NUM_FILES {
             let file_path = dir_path.join(format!("file_{}.txt", i));
             if let Err(e) = fs::remove_file(&file_path) {
-                eprintln!("Failed to delete file {}: {}", i, e);
+                log::error("sequential", format_args!("failed to delete file {}: {}", i, e));
             }
         }
     });
 
     time_operation("Delete Directory", || {
         if let Err(e) = fs::remove_dir(dir_path) {
-            eprintln!("Failed to delete directory: {}", e);
+            log::error("sequential", format_args!("failed to delete directory: {}", e));
         }
     });
 
@@ -105,3 +122,36 @@ fn main() {
         total_elapsed_time.as_secs_f64() * 1000.0
     );
 }
+
+fn main() -> std::io::Result<()> {
+    let mut args = cli::log_flags(env::args().skip(1))?.into_iter();
+    let command = args.next();
+    if matches!(command.as_deref(), Some("bench" | "calibrate" | "selftest" | "serve" | "agent" | "orchestrate")) {
+        // First, so every thread started later leaves the signals to the handler.
+        #[cfg(unix)]
+        ::io::rundir::handle_interrupts()?;
+    }
+    let result = match command.as_deref() {
+        None => {
+            run_sequential();
+            Ok(())
+        }
+        Some("bench") => cli::bench(args),
+        Some("calibrate") => cli::calibrate(args),
+        Some("serve") => serve::serve(args),
+        Some("clean") => cli::clean(args),
+        Some("probe") => cli::probe(args),
+        Some("selftest") => cli::selftest(args),
+        Some("dedupe") => cli::dedupe(args),
+        Some("agent") => distributed::agent(args),
+        Some("orchestrate") => distributed::orchestrate(args),
+        Some("record") => record::record(args),
+        Some(command) => Err(cli::invalid_input(format!("unknown command: {}", command))),
+    };
+    if result.as_ref().is_err_and(|e| ::io::cancel::is_interrupted() || e.kind() == std::io::ErrorKind::Interrupted) {
+        // What finished is printed and the run directories are gone; exit as shells expect
+        // after Ctrl-C.
+        std::process::exit(130);
+    }
+    result
+}
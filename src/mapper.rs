@@ -0,0 +1,229 @@
+//! Mapping logical names to physical paths, so layouts built on the batch APIs can shard,
+//! prefix or hide their file names without every caller knowing how.
+//!
+//! A [`PathMapper`] turns a name into a path relative to some root; [`paths`] applies one
+//! to a batch of names, ready for `Strategy::run_phase` or
+//! [`read_many_mmap`](crate::contents::read_many_mmap), and [`create_dirs`] makes the
+//! directories a sharded layout needs. Mappers compose with [`PathMapper::then`]:
+//!
+//! ```
+//! use io::mapper::{EncryptedNames, PathMapper, Prefix, Sharded};
+//!
+//! let layout = EncryptedNames::new(0x5eed).then(Sharded::default()).then(Prefix::new("tenant-a"));
+//! let path = layout.map("invoices/2024.csv");
+//! assert!(path.starts_with("tenant-a"));
+//! assert_eq!(layout.unmap(&path).as_deref(), Some("invoices/2024.csv"));
+//! ```
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::pattern::{GAMMA, mix};
+use crate::population::hash_bytes;
+
+/// A reversible mapping from logical names to paths relative to a layout root.
+pub trait PathMapper: Send + Sync {
+    /// The path that stores `name`.
+    fn map(&self, name: &str) -> PathBuf;
+
+    /// The name stored at `path`, or `None` when `path` isn't one this mapper produces.
+    fn unmap(&self, path: &Path) -> Option<String>;
+
+    /// Applies `next` to the paths this mapper produces.
+    fn then<M: PathMapper>(self, next: M) -> Then<Self, M>
+    where
+        Self: Sized,
+    {
+        Then { first: self, next }
+    }
+}
+
+impl<M: PathMapper + ?Sized> PathMapper for Box<M> {
+    fn map(&self, name: &str) -> PathBuf {
+        (**self).map(name)
+    }
+
+    fn unmap(&self, path: &Path) -> Option<String> {
+        (**self).unmap(path)
+    }
+}
+
+/// Stores every name at the path it spells.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Identity;
+
+impl PathMapper for Identity {
+    fn map(&self, name: &str) -> PathBuf {
+        PathBuf::from(name)
+    }
+
+    fn unmap(&self, path: &Path) -> Option<String> {
+        path.to_str().map(str::to_owned)
+    }
+}
+
+/// Places names under a fixed directory, e.g. one per tenant.
+#[derive(Clone, Debug)]
+pub struct Prefix {
+    prefix: PathBuf,
+}
+
+impl Prefix {
+    pub fn new(prefix: impl Into<PathBuf>) -> Prefix {
+        Prefix { prefix: prefix.into() }
+    }
+}
+
+impl PathMapper for Prefix {
+    fn map(&self, name: &str) -> PathBuf {
+        self.prefix.join(name)
+    }
+
+    fn unmap(&self, path: &Path) -> Option<String> {
+        path.strip_prefix(&self.prefix).ok()?.to_str().map(str::to_owned)
+    }
+}
+
+/// Spreads names over `levels` nested directories named by `width` hex digits of the
+/// name's hash, keeping any one directory small; the default is two levels of two digits
+/// (`ab/cd/name`), 65536 leaf directories.
+#[derive(Clone, Copy, Debug)]
+pub struct Sharded {
+    pub levels: usize,
+    pub width: usize,
+}
+
+impl Default for Sharded {
+    fn default() -> Self {
+        Sharded { levels: 2, width: 2 }
+    }
+}
+
+impl PathMapper for Sharded {
+    fn map(&self, name: &str) -> PathBuf {
+        // FNV-1a barely changes its top digits between short, similar names, so they are
+        // mixed before being split into shards.
+        let digits = format!("{:016x}", mix(hash_bytes(name.as_bytes())));
+        let mut path = PathBuf::new();
+        for level in 0..self.levels {
+            let start = (level * self.width).min(digits.len());
+            path.push(&digits[start..(start + self.width).min(digits.len())]);
+        }
+        path.push(name);
+        path
+    }
+
+    fn unmap(&self, path: &Path) -> Option<String> {
+        let mut components = path.components();
+        for _ in 0..self.levels {
+            components.next()?;
+        }
+        let name = components.as_path().to_str()?.to_owned();
+        (!name.is_empty() && self.map(&name) == path).then_some(name)
+    }
+}
+
+/// Replaces each name with a keyed, hex-encoded ciphertext, so listing the directory
+/// doesn't reveal the names. The same name and key always give the same file name, and
+/// [`unmap`](PathMapper::unmap) rejects names that weren't produced with this key.
+///
+/// This is a keystream cipher with a synthetic nonce built from FNV hashing, enough to keep
+/// names out of casual view; it is not a vetted cipher. Names grow to `16 + 2 * len` hex
+/// digits, so with a 255-byte file name limit they can be at most 119 bytes long.
+#[derive(Clone, Copy, Debug)]
+pub struct EncryptedNames {
+    key: u64,
+}
+
+impl EncryptedNames {
+    pub fn new(key: u64) -> EncryptedNames {
+        EncryptedNames { key }
+    }
+
+    fn nonce(&self, name: &[u8]) -> u64 {
+        hash_bytes(&[&self.key.to_le_bytes()[..], name].concat())
+    }
+
+    /// XORs `bytes` with the splitmix64 stream seeded by the key and `nonce`.
+    fn apply(&self, nonce: u64, bytes: &mut [u8]) {
+        let state = self.key ^ nonce;
+        for (i, chunk) in bytes.chunks_mut(8).enumerate() {
+            let z = mix(state.wrapping_add(GAMMA.wrapping_mul(i as u64)));
+            for (byte, key) in chunk.iter_mut().zip(z.to_le_bytes()) {
+                *byte ^= key;
+            }
+        }
+    }
+}
+
+impl PathMapper for EncryptedNames {
+    fn map(&self, name: &str) -> PathBuf {
+        let nonce = self.nonce(name.as_bytes());
+        let mut bytes = name.as_bytes().to_vec();
+        self.apply(nonce, &mut bytes);
+        let mut hex = format!("{:016x}", nonce);
+        for byte in bytes {
+            hex.push_str(&format!("{:02x}", byte));
+        }
+        PathBuf::from(hex)
+    }
+
+    fn unmap(&self, path: &Path) -> Option<String> {
+        let hex = path.to_str()?;
+        if hex.len() < 16 || hex.len() % 2 != 0 {
+            return None;
+        }
+        let nonce = u64::from_str_radix(&hex[..16], 16).ok()?;
+        let mut bytes = (16..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect::<Option<Vec<u8>>>()?;
+        self.apply(nonce, &mut bytes);
+        (self.nonce(&bytes) == nonce).then(|| String::from_utf8(bytes).ok()).flatten()
+    }
+}
+
+/// Two mappers in sequence: `first` maps the name, then `next` maps the result as if it
+/// were a name. Built with [`PathMapper::then`].
+#[derive(Clone, Copy, Debug)]
+pub struct Then<A, B> {
+    first: A,
+    next: B,
+}
+
+impl<A: PathMapper, B: PathMapper> PathMapper for Then<A, B> {
+    fn map(&self, name: &str) -> PathBuf {
+        let inner = self.first.map(name);
+        match inner.to_str() {
+            Some(inner) => self.next.map(inner),
+            None => self.next.map(&inner.to_string_lossy()),
+        }
+    }
+
+    fn unmap(&self, path: &Path) -> Option<String> {
+        self.first.unmap(Path::new(&self.next.unmap(path)?))
+    }
+}
+
+/// The paths under `root` that store `names`, in the same order.
+pub fn paths<'a>(root: &Path, mapper: &dyn PathMapper, names: impl IntoIterator<Item = &'a str>) -> Vec<PathBuf> {
+    names.into_iter().map(|name| root.join(mapper.map(name))).collect()
+}
+
+/// Creates every distinct parent directory of `paths`, once each.
+pub fn create_dirs(paths: &[PathBuf]) -> io::Result<()> {
+    let parents: BTreeSet<&Path> = paths.iter().filter_map(|path| path.parent()).collect();
+    for parent in parents {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+/// Rejects names that would escape the layout root or name a directory: empty names,
+/// absolute paths and `.`/`..` components.
+pub fn check_name(name: &str) -> io::Result<()> {
+    let path = Path::new(name);
+    if name.is_empty() || name.ends_with('/') || path.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} is not a relative file name", name)));
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! Peak resident memory and mapping count while a phase runs.
+//!
+//! `getrusage`'s `ru_maxrss` is a high-water mark for the whole process, so once one phase
+//! (or strategy) has peaked it says nothing about the next. [`PeakSampler`] instead polls
+//! `/proc/self/status` and `/proc/self/maps` from a background thread for the duration of
+//! one phase, which catches strategies like per-file `mmap` that hold thousands of
+//! mappings at once. Linux only; elsewhere there is nothing to report.
+
+use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often [`PeakSampler`] polls.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Resident memory and mapped regions at one moment, or the largest of each seen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Memory {
+    /// Resident set size in bytes.
+    pub rss: u64,
+    /// Mapped regions, as listed in `/proc/self/maps`.
+    pub regions: usize,
+}
+
+impl Memory {
+    /// The process's memory now, or `None` without `/proc`.
+    pub fn current() -> Option<Memory> {
+        let status = fs::read_to_string("/proc/self/status").ok()?;
+        let kib: u64 = status.lines().find_map(|line| line.strip_prefix("VmRSS:"))?.trim().trim_end_matches("kB").trim().parse().ok()?;
+        let regions = fs::read_to_string("/proc/self/maps").ok()?.lines().count();
+        Some(Memory { rss: kib * 1024, regions })
+    }
+
+    /// The larger of each field.
+    pub fn max(self, other: Memory) -> Memory {
+        Memory { rss: self.rss.max(other.rss), regions: self.regions.max(other.regions) }
+    }
+}
+
+/// Polls [`Memory::current`] on its own thread until [`finish`](PeakSampler::finish).
+pub struct PeakSampler {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Option<Memory>>,
+}
+
+impl PeakSampler {
+    pub fn start(interval: Duration) -> PeakSampler {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || {
+                // A sample `/proc` fails to give is skipped; the peak so far still stands.
+                let mut peak = Memory::current();
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(interval);
+                    if let Some(now) = Memory::current() {
+                        peak = Some(peak.map_or(now, |peak| peak.max(now)));
+                    }
+                }
+                peak
+            }
+        });
+        PeakSampler { stop, thread }
+    }
+
+    /// Stops polling and returns the peak, including a last sample taken now.
+    pub fn finish(self) -> Option<Memory> {
+        self.stop.store(true, Ordering::Relaxed);
+        let peak = self.thread.join().ok()?;
+        match (peak, Memory::current()) {
+            (Some(peak), Some(now)) => Some(peak.max(now)),
+            (peak, now) => peak.or(now),
+        }
+    }
+}
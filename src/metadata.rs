@@ -0,0 +1,306 @@
+//! Metadata-only phases over existing files: stat, chmod, batch mtime updates and touch,
+//! for tools dominated by metadata calls rather than data transfer.
+//!
+//! Every [`MetaStrategy`] does the same work a different way: one full-path syscall per
+//! file (`stat`, `chmod`, `utimensat`), `statx` with a narrow mask and the `*at` calls
+//! relative to an open directory, or `statx` batched through io_uring. io_uring has no
+//! chmod or utimensat operation, so that strategy only runs the stat phase.
+
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::time::{Duration, Instant};
+
+use crate::bench::{self, Failure, Options};
+use crate::dirs::{Dirs, c_string, check};
+
+/// The mode the chmod phase sets, different from the mode files are created with.
+const CHMOD_MODE: libc::mode_t = 0o600;
+/// The mtime the utimes phase gives every file, 2001-09-09; atimes are left alone.
+const BATCH_MTIME: libc::time_t = 1_000_000_000;
+
+/// One timed metadata phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaPhase {
+    Stat,
+    Chmod,
+    /// Sets every file's mtime to the same fixed time.
+    Utimes,
+    /// Sets every file's atime and mtime to now.
+    Touch,
+}
+
+impl MetaPhase {
+    pub const ALL: [MetaPhase; 4] = [MetaPhase::Stat, MetaPhase::Chmod, MetaPhase::Utimes, MetaPhase::Touch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MetaPhase::Stat => "stat",
+            MetaPhase::Chmod => "chmod",
+            MetaPhase::Utimes => "utimes",
+            MetaPhase::Touch => "touch",
+        }
+    }
+}
+
+type MetaFn = fn(&[PathBuf], &Options) -> io::Result<()>;
+
+#[derive(Debug)]
+pub struct MetaStrategy {
+    pub name: &'static str,
+    pub label: &'static str,
+    stat: MetaFn,
+    chmod: Option<MetaFn>,
+    utimes: Option<MetaFn>,
+    touch: Option<MetaFn>,
+}
+
+impl MetaStrategy {
+    fn phase(&self, phase: MetaPhase) -> Option<MetaFn> {
+        match phase {
+            MetaPhase::Stat => Some(self.stat),
+            MetaPhase::Chmod => self.chmod,
+            MetaPhase::Utimes => self.utimes,
+            MetaPhase::Touch => self.touch,
+        }
+    }
+
+    pub fn supports(&self, phase: MetaPhase) -> bool {
+        self.phase(phase).is_some()
+    }
+
+    /// Runs a single phase over `paths`, which must exist.
+    pub fn run_phase(&self, phase: MetaPhase, paths: &[PathBuf], options: &Options) -> io::Result<()> {
+        match self.phase(phase) {
+            Some(run) => run(paths, options),
+            None => Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} has no {} phase", self.label, phase.name()))),
+        }
+    }
+}
+
+pub const META_STRATEGIES: &[MetaStrategy] = &[
+    MetaStrategy {
+        name: "syscalls (stat/chmod/utimensat per path)",
+        label: "Syscalls",
+        stat: stat_paths,
+        chmod: Some(chmod_paths),
+        utimes: Some(utimes_paths),
+        touch: Some(touch_paths),
+    },
+    MetaStrategy {
+        name: "statx (statx and *at calls from the directory)",
+        label: "Statx",
+        stat: stat_statx,
+        chmod: Some(chmod_at),
+        utimes: Some(utimes_at),
+        touch: Some(touch_at),
+    },
+    MetaStrategy {
+        name: "io_uring statx (batched statx)",
+        label: "Uring",
+        stat: stat_uring,
+        chmod: None,
+        utimes: None,
+        touch: None,
+    },
+];
+
+fn batch_times() -> [libc::timespec; 2] {
+    [libc::timespec { tv_sec: 0, tv_nsec: libc::UTIME_OMIT }, libc::timespec { tv_sec: BATCH_MTIME, tv_nsec: 0 }]
+}
+
+fn stat_paths(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    bench::each_file(paths, options, |path| {
+        let path = c_string(path.as_os_str().as_bytes())?;
+        let mut stat = MaybeUninit::<libc::stat>::uninit();
+        check(unsafe { libc::stat(path.as_ptr(), stat.as_mut_ptr()) })
+    })
+}
+
+fn chmod_paths(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    bench::each_file(paths, options, |path| {
+        let path = c_string(path.as_os_str().as_bytes())?;
+        check(unsafe { libc::chmod(path.as_ptr(), CHMOD_MODE) })
+    })
+}
+
+fn utimes_paths(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let times = batch_times();
+    bench::each_file(paths, options, |path| {
+        let path = c_string(path.as_os_str().as_bytes())?;
+        check(unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), times.as_ptr(), 0) })
+    })
+}
+
+fn touch_paths(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    bench::each_file(paths, options, |path| {
+        let path = c_string(path.as_os_str().as_bytes())?;
+        check(unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), ptr::null(), 0) })
+    })
+}
+
+/// What a build tool's up-to-date check needs; the kernel may skip the rest.
+#[cfg(target_os = "linux")]
+const STATX_MASK: u32 = libc::STATX_TYPE | libc::STATX_MODE | libc::STATX_SIZE | libc::STATX_MTIME;
+/// Don't stat symlink targets, and don't make network filesystems revalidate.
+#[cfg(target_os = "linux")]
+const STATX_FLAGS: i32 = libc::AT_SYMLINK_NOFOLLOW | libc::AT_STATX_DONT_SYNC;
+
+fn stat_statx(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        let dirs = Dirs::open(paths)?;
+        bench::each_file(paths, options, |path| {
+            let (dir, name) = dirs.entry(path)?;
+            let mut statx = MaybeUninit::<libc::statx>::uninit();
+            check(unsafe { libc::statx(dir, name.as_ptr(), STATX_FLAGS, STATX_MASK, statx.as_mut_ptr()) })
+        })
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (paths, options);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "statx is only available on Linux"))
+    }
+}
+
+fn chmod_at(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let dirs = Dirs::open(paths)?;
+    bench::each_file(paths, options, |path| {
+        let (dir, name) = dirs.entry(path)?;
+        check(unsafe { libc::fchmodat(dir, name.as_ptr(), CHMOD_MODE, 0) })
+    })
+}
+
+fn utimes_at(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let dirs = Dirs::open(paths)?;
+    let times = batch_times();
+    bench::each_file(paths, options, |path| {
+        let (dir, name) = dirs.entry(path)?;
+        check(unsafe { libc::utimensat(dir, name.as_ptr(), times.as_ptr(), 0) })
+    })
+}
+
+fn touch_at(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    let dirs = Dirs::open(paths)?;
+    bench::each_file(paths, options, |path| {
+        let (dir, name) = dirs.entry(path)?;
+        check(unsafe { libc::utimensat(dir, name.as_ptr(), ptr::null(), 0) })
+    })
+}
+
+/// `statx` on every path through one io_uring ring per worker, [`Options::queue_depth`] files per
+/// `io_uring_enter`. Per-file times aren't recorded, since files complete as a batch.
+fn stat_uring(paths: &[PathBuf], options: &Options) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use rayon::prelude::*;
+
+        use crate::uring::{Entry, Ring};
+
+        let dirs = Dirs::open(paths)?;
+        options.progress.begin(paths.len());
+        let depth = options.queue_depth();
+        paths.par_chunks(depth as usize).try_for_each_init(
+            || Ring::new(depth),
+            |ring, batch| {
+                let ring = ring.as_mut().map_err(|e| io::Error::new(io::ErrorKind::Unsupported, format!("io_uring is unavailable: {}", e)))?;
+                options.pause.wait();
+                let names = batch.iter().map(|path| dirs.entry(path)).collect::<io::Result<Vec<_>>>()?;
+                let mut buffers: Vec<MaybeUninit<libc::statx>> = (0..batch.len()).map(|_| MaybeUninit::uninit()).collect();
+                for (i, ((dir, name), buffer)) in names.iter().zip(&mut buffers).enumerate() {
+                    let entry = Entry::statx(*dir, name, STATX_FLAGS, STATX_MASK, buffer.as_mut_ptr()).user_data(i as u64);
+                    // Batches are no bigger than the ring, and `names` and `buffers` live
+                    // until every completion is reaped below.
+                    assert!(unsafe { ring.push(&entry) }, "io_uring submission queue is full");
+                }
+                let mut first_error = None;
+                let mut pending = batch.len();
+                while pending > 0 {
+                    let Some(completion) = ring.pop() else {
+                        if let Err(e) = ring.submit(1) {
+                            // The kernel may still write to the buffers.
+                            std::mem::forget((names, buffers));
+                            return Err(e);
+                        }
+                        continue;
+                    };
+                    pending -= 1;
+                    options.progress.advance();
+                    if let Err(e) = completion.result() {
+                        let path = &batch[completion.user_data as usize];
+                        first_error.get_or_insert_with(|| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)));
+                    }
+                }
+                first_error.map_or(Ok(()), Err)
+            },
+        )
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (paths, options);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "io_uring is only available on Linux"))
+    }
+}
+
+/// Wall-clock time of each metadata phase; `None` for phases the strategy can't run.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetaTimes {
+    pub stat: Option<Duration>,
+    pub chmod: Option<Duration>,
+    pub utimes: Option<Duration>,
+    pub touch: Option<Duration>,
+}
+
+impl MetaTimes {
+    pub fn get(&self, phase: MetaPhase) -> Option<Duration> {
+        match phase {
+            MetaPhase::Stat => self.stat,
+            MetaPhase::Chmod => self.chmod,
+            MetaPhase::Utimes => self.utimes,
+            MetaPhase::Touch => self.touch,
+        }
+    }
+
+    fn get_mut(&mut self, phase: MetaPhase) -> &mut Option<Duration> {
+        match phase {
+            MetaPhase::Stat => &mut self.stat,
+            MetaPhase::Chmod => &mut self.chmod,
+            MetaPhase::Utimes => &mut self.utimes,
+            MetaPhase::Touch => &mut self.touch,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MetaResult {
+    pub times: MetaTimes,
+    pub failures: Vec<Failure>,
+}
+
+fn phase<T>(options: &Options, strategy: &MetaStrategy, phase: &'static str, failures: &mut Vec<Failure>, f: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    bench::phase(options, format!("{} {}", strategy.label, phase), format!("{}/{}", strategy.label, phase), phase, failures, f)
+}
+
+/// Creates the configured workload inside `dir_path`, runs every phase `strategy` supports
+/// on it, and deletes it again. Only the metadata phases are timed.
+pub fn run_strategy(strategy: &MetaStrategy, dir_path: &Path, options: &Options) -> io::Result<MetaResult> {
+    let paths = options.workload.paths(dir_path);
+    let mut result = MetaResult::default();
+    options.failures.take("");
+
+    phase(options, strategy, "create", &mut result.failures, || bench::create_files(&paths, options))?;
+
+    for meta_phase in MetaPhase::ALL {
+        let Some(run) = strategy.phase(meta_phase) else {
+            continue;
+        };
+        let start = Instant::now();
+        phase(options, strategy, meta_phase.name(), &mut result.failures, || run(&paths, options))?;
+        *result.times.get_mut(meta_phase) = Some(start.elapsed());
+    }
+
+    phase(options, strategy, "delete", &mut result.failures, || bench::delete_files(&paths, options))?;
+    Ok(result)
+}
@@ -0,0 +1,344 @@
+//! Making one tree match another by changing only what differs, as `rsync -a --delete`
+//! does within one machine. [`sync_trees`] lists both trees, deletes what the destination
+//! has and the source doesn't, and copies the files that are new or changed, in parallel
+//! with the `rayon` feature.
+//!
+//! Whether a file changed is decided by [`Compare`]: by size and modification time, which
+//! costs a `stat` per file and is what `rsync` does by default, or by size and then the
+//! bytes of both files, read side by side until they differ, which costs a read of every
+//! file of the same size but also finds a rewrite within the filesystem's timestamp
+//! granularity. Copies, and files the byte compare finds equal, take the source's
+//! modification time, so the next size-and-time sync sees them unchanged. Each copy is
+//! written beside its target and renamed over it, so a reader of the destination never
+//! sees half a file; the sync as a whole isn't atomic.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::atomic;
+use crate::contents;
+use crate::links;
+use crate::retry::RetryPolicy;
+use crate::stash::Listing;
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+#[cfg(feature = "bench")]
+use crate::names;
+
+/// How [`sync_trees`] decides whether a file present in both trees changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compare {
+    /// Sizes or modification times differ.
+    #[default]
+    SizeTime,
+    /// Sizes or contents differ.
+    Contents,
+}
+
+impl Compare {
+    pub const ALL: [Compare; 2] = [Compare::SizeTime, Compare::Contents];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compare::SizeTime => "size+mtime",
+            Compare::Contents => "contents",
+        }
+    }
+}
+
+/// What a [`sync_trees`] changed. The counts are of files and symlinks; directories are
+/// created and removed along with them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub unchanged: usize,
+    /// Bytes copied by creates and updates.
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Dir,
+    File(u64),
+    Symlink(PathBuf),
+}
+
+fn kinds(listing: Listing) -> HashMap<PathBuf, Kind> {
+    let dirs = listing.dirs.into_iter().map(|path| (path, Kind::Dir));
+    let files = listing.files.into_iter().map(|(path, size)| (path, Kind::File(size)));
+    let symlinks = listing.symlinks.into_iter().map(|(path, target)| (path, Kind::Symlink(target)));
+    dirs.chain(files).chain(symlinks).collect()
+}
+
+/// Makes the tree at `dst`, created if missing, hold exactly what the tree at `src` does:
+/// the same directories, files with the same contents and modification times, and
+/// symlinks with the same targets. Fails on the first entry that still can't be changed
+/// after retrying, with the destination partly synced; running it again finishes the job.
+pub fn sync_trees(src: &Path, dst: &Path, compare: Compare) -> io::Result<SyncReport> {
+    let source = kinds(Listing::of(src)?);
+    let dest = match Listing::of(dst) {
+        Ok(listing) => kinds(listing),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            fs::create_dir_all(dst)?;
+            HashMap::new()
+        }
+        Err(e) => return Err(e),
+    };
+    let policy = RetryPolicy::default();
+    let mut report = SyncReport::default();
+
+    // Whatever the source lacks, or has as another kind of entry, goes. A directory goes
+    // whole, so nothing under one that goes is deleted on its own.
+    let mut stale_dirs: Vec<&PathBuf> = dest.iter().filter(|(path, kind)| **kind == Kind::Dir && source.get(*path) != Some(&Kind::Dir)).map(|(path, _)| path).collect();
+    stale_dirs.sort();
+    stale_dirs.dedup_by(|child, parent| child.starts_with(&**parent));
+    let under_stale = |path: &Path| stale_dirs.iter().any(|dir| path.starts_with(dir));
+    let stale: Vec<&PathBuf> = dest
+        .iter()
+        .filter(|(path, kind)| **kind != Kind::Dir && !under_stale(path))
+        .filter(|(path, kind)| match (kind, source.get(*path)) {
+            (Kind::File(_), Some(Kind::File(_))) => false,
+            (Kind::Symlink(target), Some(Kind::Symlink(source_target))) => target != source_target,
+            _ => true,
+        })
+        .map(|(path, _)| path)
+        .collect();
+    // A symlink pointing elsewhere now is updated, below, rather than deleted.
+    let retargeted = |path: &PathBuf| matches!(source.get(path), Some(Kind::Symlink(_))) && matches!(dest.get(path), Some(Kind::Symlink(_)));
+    report.deleted += dest.iter().filter(|(path, kind)| **kind != Kind::Dir && under_stale(path)).count();
+    report.deleted += stale.iter().filter(|path| !retargeted(path)).count();
+    for dir in &stale_dirs {
+        fs::remove_dir_all(dst.join(dir))?;
+    }
+    contents::delete_many(&stale.iter().map(|path| dst.join(path)).collect::<Vec<_>>(), &policy)?;
+
+    let mut dirs: Vec<&PathBuf> = source.iter().filter(|(path, kind)| **kind == Kind::Dir && dest.get(*path) != Some(&Kind::Dir)).map(|(path, _)| path).collect();
+    dirs.sort();
+    for dir in dirs {
+        fs::create_dir(dst.join(dir))?;
+    }
+
+    // Files whose sizes match need `compare` to tell; the rest are copied regardless.
+    let (mut copies, mut both) = (Vec::new(), Vec::new());
+    for (path, kind) in &source {
+        let Kind::File(size) = kind else { continue };
+        match dest.get(path) {
+            Some(Kind::File(dest_size)) if dest_size == size => both.push(path),
+            Some(Kind::File(_)) => {
+                copies.push(path);
+                report.updated += 1;
+            }
+            _ => {
+                copies.push(path);
+                report.created += 1;
+            }
+        }
+    }
+    let changed = contents::each_retried(&both, &policy, |path| path.as_path(), |path| differs(&src.join(path), &dst.join(path), compare))?;
+    for (path, changed) in both.into_iter().zip(changed) {
+        if changed {
+            copies.push(path);
+            report.updated += 1;
+        } else {
+            report.unchanged += 1;
+        }
+    }
+    let copied = contents::each_retried(&copies, &policy, |path| path.as_path(), |path| copy_file(&src.join(path), &dst.join(path)))?;
+    report.bytes = copied.into_iter().sum();
+
+    for (path, kind) in &source {
+        let Kind::Symlink(target) = kind else { continue };
+        match dest.get(path) {
+            Some(Kind::Symlink(dest_target)) if dest_target == target => report.unchanged += 1,
+            Some(Kind::Symlink(_)) => {
+                links::symlink(target, &dst.join(path))?;
+                report.updated += 1;
+            }
+            _ => {
+                links::symlink(target, &dst.join(path))?;
+                report.created += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Whether two files of the same size differ by `compare`. Equal contents with different
+/// modification times get the source's, as a copy would.
+fn differs(src: &Path, dst: &Path, compare: Compare) -> io::Result<bool> {
+    let modified = fs::metadata(src)?.modified()?;
+    match compare {
+        Compare::SizeTime => Ok(modified != fs::metadata(dst)?.modified()?),
+        Compare::Contents => {
+            if !same_contents(src, dst)? {
+                return Ok(true);
+            }
+            if fs::metadata(dst)?.modified()? != modified {
+                #[cfg(unix)]
+                let file = File::open(dst)?;
+                #[cfg(not(unix))]
+                let file = File::options().write(true).open(dst)?;
+                file.set_modified(modified)?;
+            }
+            Ok(false)
+        }
+    }
+}
+
+/// Whether `a` and `b` hold the same bytes, read a block of each at a time and stopping at
+/// the first difference.
+fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    const BLOCK: usize = 64 * 1024;
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let (mut a_buf, mut b_buf) = (vec![0; BLOCK], vec![0; BLOCK]);
+    loop {
+        let read = fill(&mut a, &mut a_buf)?;
+        if read != fill(&mut b, &mut b_buf)? || a_buf[..read] != b_buf[..read] {
+            return Ok(false);
+        }
+        if read < BLOCK {
+            return Ok(true);
+        }
+    }
+}
+
+/// Reads into `buf` until it is full or the file ends, returning how much was read.
+fn fill(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Copies `src` to a temporary file beside `dst` with the source's modification time,
+/// renames it over `dst`, and returns the bytes copied.
+fn copy_file(src: &Path, dst: &Path) -> io::Result<u64> {
+    let temp = atomic::temp_path(dst)?;
+    let copied = fs::copy(src, &temp).and_then(|bytes| {
+        // The owner may set times through a read-only descriptor, and a copy of a read-only
+        // file can't be opened for writing.
+        #[cfg(unix)]
+        let file = File::open(&temp)?;
+        #[cfg(not(unix))]
+        let file = File::options().write(true).open(&temp)?;
+        file.set_modified(fs::metadata(src)?.modified()?)?;
+        fs::rename(&temp, dst)?;
+        Ok(bytes)
+    });
+    if copied.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    copied
+}
+
+/// One in how many workload files the benchmark changes between syncs, by rewriting it,
+/// deleting it or adding a new file beside it.
+#[cfg(feature = "bench")]
+pub const CHANGE_EVERY: usize = 10;
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct CompareResult {
+    pub compare: Compare,
+    /// Syncing into an empty tree, syncing again with nothing changed, and syncing after
+    /// the changes.
+    pub full: Duration,
+    pub unchanged: Duration,
+    pub incremental: Duration,
+    /// What the incremental sync did.
+    pub report: SyncReport,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct MirrorResult {
+    pub compares: Vec<CompareResult>,
+    /// The files the benchmark rewrote, deleted and added between syncs.
+    pub changes: SyncReport,
+    pub failures: Vec<Failure>,
+}
+
+/// Writes the configured workload to `dir_path/src` and, for each [`Compare`], times a
+/// full sync into a tree of its own and a sync with nothing to do. It then rewrites,
+/// deletes and adds one in [`CHANGE_EVERY`] files and times each tree's incremental sync,
+/// which must find exactly those changes. Rewritten files keep their size and get a
+/// modification time a second later, as a later edit would.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<MirrorResult> {
+    let src = dir_path.join("src");
+    fs::create_dir_all(&src)?;
+    let paths = options.workload.paths(&src);
+    let mut result = MirrorResult::default();
+    options.failures.take("");
+    bench::phase(options, "Mirror create", "mirror/create", "create", &mut result.failures, || {
+        bench::each_indexed(&paths, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+    })?;
+
+    let tree = |compare: Compare| dir_path.join(format!("dst-{}", compare.name()));
+    let mut timings = Vec::new();
+    for compare in Compare::ALL {
+        options.progress.set_stage(format!("Mirror {} full", compare.name()));
+        let start = Instant::now();
+        sync_trees(&src, &tree(compare), compare)?;
+        let full = start.elapsed();
+        options.progress.set_stage(format!("Mirror {} unchanged", compare.name()));
+        let start = Instant::now();
+        let report = sync_trees(&src, &tree(compare), compare)?;
+        let unchanged = start.elapsed();
+        if report.unchanged != paths.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} found changes in a synced tree: {:?}", compare.name(), report)));
+        }
+        timings.push((full, unchanged));
+    }
+
+    let changed: Vec<PathBuf> = paths.iter().step_by(CHANGE_EVERY).cloned().collect();
+    let deleted: Vec<PathBuf> = paths.iter().skip(CHANGE_EVERY / 2).step_by(CHANGE_EVERY).cloned().collect();
+    let added: Vec<PathBuf> = changed.iter().map(|path| names::sibling(path, "added-", "")).collect();
+    bench::phase(options, "Mirror change", "mirror/change", "change", &mut result.failures, || {
+        bench::each_indexed(&changed, options, |index, path| {
+            let modified = fs::metadata(path)?.modified()?;
+            bench::with_content(options, index * CHANGE_EVERY, true, |content| fs::write(path, content))?;
+            File::options().write(true).open(path)?.set_modified(modified.max(SystemTime::now()) + Duration::from_secs(1))
+        })?;
+        contents::delete_many(&deleted, &RetryPolicy::default())?;
+        bench::each_indexed(&added, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+    })?;
+    result.changes = SyncReport { created: added.len(), updated: changed.len(), deleted: deleted.len(), unchanged: paths.len() - changed.len() - deleted.len(), bytes: 0 };
+
+    for (compare, (full, unchanged)) in Compare::ALL.into_iter().zip(timings) {
+        options.progress.set_stage(format!("Mirror {} incremental", compare.name()));
+        let start = Instant::now();
+        let report = sync_trees(&src, &tree(compare), compare)?;
+        let incremental = start.elapsed();
+        if (SyncReport { bytes: 0, ..report }) != result.changes {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} found {:?}, but {:?} changed", compare.name(), report, result.changes)));
+        }
+        result.compares.push(CompareResult { compare, full, unchanged, incremental, report });
+    }
+    if options.verify {
+        options.progress.set_stage("Mirror verify");
+        for compare in Compare::ALL {
+            let report = sync_trees(&src, &tree(compare), Compare::Contents)?;
+            if report.unchanged != paths.len() - deleted.len() + added.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("the {} sync left differences: {:?}", compare.name(), report)));
+            }
+        }
+    }
+    for compare in Compare::ALL {
+        fs::remove_dir_all(tree(compare))?;
+    }
+    fs::remove_dir_all(&src)?;
+    Ok(result)
+}
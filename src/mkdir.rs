@@ -0,0 +1,137 @@
+//! Creating many directories at once. Calling `fs::create_dir_all` for each of ten
+//! thousand leaves of a deep tree asks the filesystem about their shared ancestors over
+//! and over, each call walking up until it finds one that exists. [`create_dirs_many`]
+//! collects every directory the leaves need, each once, and creates them a level at a
+//! time, each level in parallel with the `rayon` feature: by the time a level starts, the
+//! one above it exists, so every `mkdir` is the only one its directory gets.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+#[cfg(feature = "bench")]
+use std::path::PathBuf;
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+use crate::contents;
+use crate::retry::RetryPolicy;
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+
+/// Creates every directory in `paths` and their missing ancestors, and returns how many
+/// it created. A directory that already exists, or that another process creates in the
+/// meantime, is fine; anything else in the way fails with
+/// [`io::ErrorKind::AlreadyExists`]. Each `mkdir` is retried on transient errors as
+/// `policy` says, and the call fails on the first that still can't be made, leaving the
+/// levels above it created.
+pub fn create_dirs_many<P>(paths: &[P], policy: &RetryPolicy) -> io::Result<usize>
+where
+    P: AsRef<Path> + Sync,
+{
+    // Roots and the empty parent of a relative path exist by definition.
+    let mut needed: HashSet<&Path> = HashSet::new();
+    for path in paths {
+        for dir in path.as_ref().ancestors() {
+            if dir.as_os_str().is_empty() || dir.parent().is_none() || !needed.insert(dir) {
+                break;
+            }
+        }
+    }
+    let mut levels: Vec<Vec<&Path>> = Vec::new();
+    for dir in needed {
+        let depth = dir.components().count();
+        levels.resize_with(levels.len().max(depth), Vec::new);
+        levels[depth - 1].push(dir);
+    }
+    let mut created = 0;
+    for level in &levels {
+        created += contents::each_retried(level, policy, |dir| dir, |dir| make_dir(dir))?.into_iter().filter(|&made| made).count();
+    }
+    Ok(created)
+}
+
+/// Whether `dir` was created, as opposed to found.
+fn make_dir(dir: &Path) -> io::Result<bool> {
+    match fs::create_dir(dir) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => match fs::metadata(dir) {
+            Ok(metadata) if metadata.is_dir() => Ok(false),
+            _ => Err(io::Error::new(io::ErrorKind::AlreadyExists, "exists and isn't a directory")),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// How many directories each one below the benchmark's root holds, down to the leaves.
+#[cfg(feature = "bench")]
+pub const FAN_OUT: usize = 8;
+
+/// The `index`th leaf directory `depth` levels below `root`, [`FAN_OUT`] to a directory: the tree branches near its leaves and the levels above are shared, as in
+/// a deep source tree or a package cache.
+#[cfg(feature = "bench")]
+pub fn leaf_path(root: &Path, depth: usize, index: usize) -> PathBuf {
+    let mut path = root.to_path_buf();
+    for level in (0..depth).rev() {
+        let digit = FAN_OUT.checked_pow(level as u32).map_or(0, |place| index / place % FAN_OUT);
+        path.push(format!("d{}", digit));
+    }
+    path
+}
+
+/// The ways the benchmark makes its tree.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// `fs::create_dir_all` for each leaf, the leaves in parallel.
+    CreateDirAll,
+    /// [`create_dirs_many`] over all the leaves.
+    Many,
+}
+
+#[cfg(feature = "bench")]
+impl Method {
+    pub const ALL: [Method; 2] = [Method::CreateDirAll, Method::Many];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::CreateDirAll => "create_dir_all",
+            Method::Many => "create_dirs_many",
+        }
+    }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct MkdirResult {
+    /// Directories in the tree, leaves and ancestors.
+    pub dirs: usize,
+    pub times: Vec<(Method, Duration)>,
+    pub failures: Vec<Failure>,
+}
+
+/// Times making a tree whose leaves, as many as the workload has files, are `depth`
+/// levels below `dir_path/tree`, by each [`Method`], removing it untimed in between.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, depth: usize, options: &Options) -> io::Result<MkdirResult> {
+    let root = dir_path.join("tree");
+    let leaves: Vec<PathBuf> = (0..options.workload.files).map(|index| leaf_path(&root, depth, index)).collect();
+    let mut result = MkdirResult::default();
+    options.failures.take("");
+    for method in Method::ALL {
+        let start = Instant::now();
+        let dirs = bench::phase(options, format!("Mkdir {}", method.name()), format!("mkdir/{}", method.name()), method.name(), &mut result.failures, || match method {
+            Method::CreateDirAll => bench::each_file(&leaves, options, |leaf| fs::create_dir_all(leaf)).map(|()| None),
+            Method::Many => create_dirs_many(&leaves, &RetryPolicy::default()).map(Some),
+        })?;
+        result.times.push((method, start.elapsed()));
+        if let Some(dirs) = dirs {
+            result.dirs = dirs;
+        }
+        if let Some(missing) = leaves.iter().find(|leaf| !leaf.is_dir()) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} didn't make {}", method.name(), missing.display())));
+        }
+        fs::remove_dir_all(&root)?;
+    }
+    Ok(result)
+}
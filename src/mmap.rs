@@ -0,0 +1,188 @@
+//! Helpers for file mappings.
+
+use std::fs;
+#[cfg(feature = "mmap")]
+use std::fs::{File, OpenOptions};
+use std::io;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+
+#[cfg(all(feature = "mmap", target_os = "linux"))]
+use memmap2::RemapOptions;
+#[cfg(feature = "mmap")]
+use memmap2::{MmapMut, MmapOptions};
+
+const DEFAULT_HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// Size of a transparent huge page, as reported by the kernel.
+pub fn huge_page_size() -> usize {
+    fs::read_to_string("/sys/kernel/mm/transparent_hugepage/hpage_pmd_size")
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(DEFAULT_HUGE_PAGE_SIZE)
+}
+
+/// Asks the kernel to back `region` with transparent huge pages.
+///
+/// Returns `false` when the region is too small to hold a huge page or the kernel refused
+/// the advice (THP disabled, or not supported for this filesystem); the mapping keeps
+/// working with regular pages in that case.
+pub fn advise_huge_pages(region: &[u8]) -> bool {
+    if region.len() < huge_page_size() {
+        return false;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let result = unsafe { libc::madvise(region.as_ptr() as *mut libc::c_void, region.len(), libc::MADV_HUGEPAGE) };
+        result == 0
+    }
+    #[cfg(not(target_os = "linux"))]
+    false
+}
+
+/// Bytes of `region` currently backed by huge pages, read from `/proc/self/smaps`.
+///
+/// Only meaningful after the pages have been touched; `region` must start at the
+/// beginning of a mapping.
+pub fn huge_page_bytes(region: &[u8]) -> io::Result<usize> {
+    let start = format!("{:x}-", region.as_ptr() as usize);
+    let smaps = fs::read_to_string("/proc/self/smaps")?;
+    let mut in_region = false;
+    let mut kib = 0;
+    for line in smaps.lines() {
+        let is_header = line.split_whitespace().next().is_some_and(|field| field.contains('-') && !field.ends_with(':'));
+        if is_header {
+            if in_region {
+                break;
+            }
+            in_region = line.starts_with(&start);
+            continue;
+        }
+        if !in_region {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        if let (Some("AnonHugePages:" | "ShmemPmdMapped:" | "FilePmdMapped:"), Some(value)) = (fields.next(), fields.next()) {
+            kib += value.parse::<usize>().unwrap_or(0);
+        }
+    }
+    Ok(kib * 1024)
+}
+
+/// A file written through a shared, writable mapping of all of it, kept in step with the
+/// file's length: growing the file remaps it larger, shrinking unmaps the tail before the
+/// file is cut (so no page of the mapping ever lies past the end of the file), and an empty
+/// file has no mapping at all, since a zero-length mapping can't be made.
+///
+/// On Linux the mapping is resized with `mremap`, which may move it; elsewhere it is
+/// unmapped and mapped again. Either way slices from [`MmapFile::as_slice`] don't outlive a
+/// resize.
+#[cfg(feature = "mmap")]
+#[derive(Debug)]
+pub struct MmapFile {
+    file: File,
+    map: Option<MmapMut>,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapFile {
+    /// Maps `file`, which must be open for reading and writing, at its current length.
+    pub fn new(file: File) -> io::Result<MmapFile> {
+        let len = file.metadata()?.len();
+        let mut mapped = MmapFile { file, map: None };
+        mapped.map = mapped.map_len(len)?;
+        Ok(mapped)
+    }
+
+    /// Opens `path` for reading and writing, creating it empty if it doesn't exist, and maps it.
+    pub fn open(path: &Path) -> io::Result<MmapFile> {
+        MmapFile::new(OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?)
+    }
+
+    fn map_len(&self, len: u64) -> io::Result<Option<MmapMut>> {
+        if len == 0 {
+            return Ok(None);
+        }
+        let len = usize::try_from(len).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))?;
+        Ok(Some(unsafe { MmapOptions::new().len(len).map_mut(&self.file)? }))
+    }
+
+    /// The file's length, which the mapping always covers.
+    pub fn len(&self) -> u64 {
+        self.map.as_ref().map_or(0, |map| map.len() as u64)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_none()
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// The whole file, through the mapping; empty when the file is.
+    pub fn as_slice(&self) -> &[u8] {
+        self.map.as_deref().unwrap_or(&[])
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.map.as_deref_mut().unwrap_or(&mut [])
+    }
+
+    /// Grows or shrinks the file to `len` bytes and the mapping with it. Bytes past the old
+    /// end read as zeros.
+    pub fn set_len(&mut self, len: u64) -> io::Result<()> {
+        let current = self.len();
+        if len == current {
+            return Ok(());
+        }
+        if len < current {
+            self.resize_map(len)?;
+            self.file.set_len(len)
+        } else {
+            self.file.set_len(len)?;
+            self.resize_map(len)
+        }
+    }
+
+    fn resize_map(&mut self, len: u64) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        if let (Some(map), Ok(new_len @ 1..)) = (self.map.as_mut(), usize::try_from(len)) {
+            // The file already covers `len` when growing, and the tail past it is dropped
+            // before the file is cut when shrinking.
+            return unsafe { map.remap(new_len, RemapOptions::new().may_move(true)) };
+        }
+        self.map = None;
+        self.map = self.map_len(len)?;
+        Ok(())
+    }
+
+    /// Writes `data` at `offset`, first growing the file when it ends past the end.
+    pub fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let end = offset.checked_add(data.len() as u64).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "write past the largest file offset"))?;
+        if end > self.len() {
+            self.set_len(end)?;
+        }
+        self.as_mut_slice()[offset as usize..end as usize].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Replaces the file's contents with `data`, shrinking it when `data` is shorter.
+    pub fn replace(&mut self, data: &[u8]) -> io::Result<()> {
+        self.set_len(data.len() as u64)?;
+        self.write_at(0, data)
+    }
+
+    /// `msync(MS_SYNC)`: waits for the mapping's dirty pages to reach the device.
+    pub fn flush(&self) -> io::Result<()> {
+        self.map.as_ref().map_or(Ok(()), |map| map.flush())
+    }
+
+    /// `msync(MS_ASYNC)`: starts writing the dirty pages back without waiting.
+    pub fn flush_async(&self) -> io::Result<()> {
+        self.map.as_ref().map_or(Ok(()), |map| map.flush_async())
+    }
+}
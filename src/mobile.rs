@@ -0,0 +1,54 @@
+//! Running the suite on phones, where an app's storage is its sandbox. On Android,
+//! `env::temp_dir` falls back to `/data/local/tmp`, which only the shell user can write;
+//! an app has its own cache directory under `/data/data/<package>` instead, and its
+//! process is named after the package. On iOS, `TMPDIR` already points into the app's
+//! container, and `Library/Caches` beside it is where apps keep their caches.
+//!
+//! Neither lets an app choose its threads' CPUs: Android confines apps to the cpusets of
+//! their scheduling group and SELinux refuses `sched_setaffinity` beyond them, and iOS has
+//! no affinity call at all, so [`crate::pinning::pin_thread`] refuses there rather than
+//! leave workers unpinned while claiming otherwise.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Whether this was built for Android or iOS.
+pub const MOBILE: bool = cfg!(any(target_os = "android", target_os = "ios"));
+
+/// The package an Android app's process runs as, from the contents of
+/// `/proc/self/cmdline`: the process name, without the `:suffix` of an app's secondary
+/// processes. `None` for processes that aren't named like a package, such as binaries
+/// run from `adb shell`, whose name is their path.
+pub fn android_package(cmdline: &[u8]) -> Option<String> {
+    let name = cmdline.split(|&byte| byte == 0).next()?;
+    let name = std::str::from_utf8(name).ok()?;
+    let package = name.split(':').next()?;
+    let valid = package.contains('.') && package.split('.').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+    valid.then(|| package.to_string())
+}
+
+/// The app's cache directory: `/data/data/<package>/cache` on Android, if the process is
+/// an app's, and `$HOME/Library/Caches` on iOS. `None` elsewhere.
+pub fn cache_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "android") {
+        let package = android_package(&fs::read("/proc/self/cmdline").ok()?)?;
+        return ["/data/data", "/data/user/0"].iter().map(|root| PathBuf::from(root).join(&package).join("cache")).find(|dir| dir.is_dir());
+    }
+    if cfg!(target_os = "ios") {
+        return env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Caches")).filter(|dir| dir.is_dir());
+    }
+    None
+}
+
+/// Where the benchmark puts its files unless told otherwise: `env::temp_dir`, except on
+/// Android without `TMPDIR`, where an app's process uses its [`cache_dir`] instead.
+pub fn temp_dir() -> PathBuf {
+    if cfg!(target_os = "android")
+        && env::var_os("TMPDIR").is_none()
+        && let Some(dir) = cache_dir()
+    {
+        return dir;
+    }
+    env::temp_dir()
+}
@@ -0,0 +1,154 @@
+//! The names benchmark files get. Real trees aren't all `file_N.txt`: package caches hold
+//! non-Latin scripts and emoji, user documents have spaces, generated code runs into the
+//! 255-byte name limit, and files copied from elsewhere end up named `CON` or `aux.c`,
+//! which Windows reserves for devices. Each [`Names`] pattern produces one of those, with
+//! the file's index in every name so they stay unique.
+//!
+//! On Windows, paths of the hostile patterns go through [`long_path`], the `\\?\` form
+//! that skips the Win32 path rewriting: without it paths longer than 260 characters fail,
+//! reserved names open the device, and trailing spaces and dots are stripped.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Longest file name, in bytes, most filesystems take (`NAME_MAX`).
+pub const MAX_NAME: usize = 255;
+
+/// Names Windows reserves for devices, with or without an extension.
+pub const RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Scripts and characters the unicode pattern cycles through: Cyrillic, CJK, Arabic
+/// (right to left), Devanagari, an emoji outside the Basic Multilingual Plane, and an `é`
+/// both precomposed and decomposed, which normalizing filesystems treat as one name.
+const UNICODE: [&str; 6] = ["файл", "文件", "ملف", "फ़ाइल", "🚀file", "caf\u{e9}_cafe\u{301}"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Names {
+    /// `file_N.txt`.
+    #[default]
+    Ascii,
+    /// Non-Latin scripts, emoji and combining characters.
+    Unicode,
+    /// Spaces inside the name, and leading and trailing ones.
+    Spaces,
+    /// Names [`MAX_NAME`] bytes long.
+    Long,
+    /// Windows device names, and names ending in a dot.
+    Reserved,
+    /// Each of the others in turn.
+    Mixed,
+}
+
+impl Names {
+    pub const ALL: [Names; 6] = [Names::Ascii, Names::Unicode, Names::Spaces, Names::Long, Names::Reserved, Names::Mixed];
+
+    /// The name of file `index`.
+    pub fn name(self, index: usize) -> String {
+        match self {
+            Names::Ascii => format!("file_{}.txt", index),
+            Names::Unicode => format!("{}_{}.txt", UNICODE[index % UNICODE.len()], index),
+            Names::Spaces => match index % 3 {
+                0 => format!("file {} with  spaces.txt", index),
+                1 => format!(" leading {}.txt", index),
+                _ => format!("trailing {}.txt ", index),
+            },
+            Names::Long => {
+                let name = format!("file_{}_", index);
+                format!("{}{}.txt", name, "x".repeat(MAX_NAME - name.len() - 4))
+            }
+            Names::Reserved => match index % 3 {
+                // Whatever follows a reserved name's first dot doesn't unreserve it.
+                0 | 1 => format!("{}.{}.txt", RESERVED[index / 3 % RESERVED.len()], index),
+                _ => format!("file_{}.", index),
+            },
+            Names::Mixed => Names::ALL[index % 5].name(index),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Names::Ascii => "ascii",
+            Names::Unicode => "unicode",
+            Names::Spaces => "spaces",
+            Names::Long => "long",
+            Names::Reserved => "reserved",
+            Names::Mixed => "mixed",
+        }
+    }
+}
+
+impl FromStr for Names {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Names> {
+        Names::ALL
+            .into_iter()
+            .find(|names| names.label() == s)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("unknown names '{}', expected ascii|unicode|spaces|long|reserved|mixed", s)))
+    }
+}
+
+impl fmt::Display for Names {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// The paths of `files` files named by `names`, striped round-robin over `dirs`: file `i`
+/// goes to directory `i % dirs.len()`.
+pub fn paths(dirs: &[PathBuf], files: usize, names: Names) -> Vec<PathBuf> {
+    (0..files)
+        .map(|i| {
+            let path = dirs[i % dirs.len()].join(names.name(i));
+            if names == Names::Ascii { path } else { long_path(&path) }
+        })
+        .collect()
+}
+
+/// The path beside `path` named by its file name with `prefix` and `suffix` around it, as
+/// temporary files, packs and sidecars are. Where that would pass [`MAX_NAME`] the file
+/// name is cut short and its hash added, so names that differ only past the cut stay apart.
+pub fn sibling(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default();
+    let mut sibling = std::ffi::OsString::from(prefix);
+    if prefix.len() + name.len() + suffix.len() <= MAX_NAME {
+        sibling.push(name);
+    } else {
+        let hash = format!("~{:016x}", crate::population::hash_bytes(name.as_encoded_bytes()));
+        let budget = MAX_NAME.saturating_sub(prefix.len() + hash.len() + suffix.len());
+        let lossy = name.to_string_lossy();
+        let cut = (0..=budget.min(lossy.len())).rev().find(|&end| lossy.is_char_boundary(end)).unwrap_or(0);
+        sibling.push(&lossy[..cut]);
+        sibling.push(hash);
+    }
+    sibling.push(suffix);
+    path.with_file_name(sibling)
+}
+
+/// `path` in the form that reaches the filesystem as written: on Windows an absolute path
+/// gets the `\\?\` prefix (`\\?\UNC\` for shares), lifting the 260-character limit and
+/// keeping reserved names and trailing dots and spaces. Elsewhere, and for relative or
+/// already prefixed paths, it is unchanged.
+pub fn long_path(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    verbatim(&path.to_string_lossy()).map_or_else(|| path.to_path_buf(), PathBuf::from)
+}
+
+/// The `\\?\` form of the Windows path `path`, or `None` if it has none or already is.
+pub fn verbatim(path: &str) -> Option<String> {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return None;
+    }
+    let path = path.replace('/', r"\");
+    if let Some(share) = path.strip_prefix(r"\\") {
+        return Some(format!(r"\\?\UNC\{}", share));
+    }
+    let bytes = path.as_bytes();
+    (bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\').then(|| format!(r"\\?\{}", path))
+}
@@ -0,0 +1,191 @@
+//! Network filesystems, where every lookup, open and unlink is a round trip to a server
+//! and costs milliseconds rather than the microseconds a local disk's metadata does.
+//! Strategies that touch one file per operation pay that on every file, while those
+//! keeping files in one container pay it once, so rankings measured locally can flip.
+//!
+//! [`SimulatedNetwork`] shows that without a server: the benchmark charges it one round
+//! trip, of a [`Latency`] drawn afresh each time, before every metadata operation (each
+//! file opened, created or unlinked, and each container opened). Data transfers aren't
+//! simulated. On a real NFS mount, [`RpcCounter`] counts the RPCs the client actually
+//! sent, per operation, from `/proc/self/mountstats`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::population::Rng;
+
+/// One round trip's time: `mean`, give or take up to `jitter`, uniformly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Latency {
+    pub mean: Duration,
+    pub jitter: Duration,
+}
+
+impl Latency {
+    /// A round trip drawn from `rng`.
+    fn sample(&self, rng: &mut Rng) -> Duration {
+        let jitter = self.jitter.min(self.mean).as_nanos() as u64;
+        if jitter == 0 {
+            return self.mean;
+        }
+        let offset = rng.next() % (2 * jitter + 1);
+        (self.mean + Duration::from_nanos(offset)).saturating_sub(Duration::from_nanos(jitter))
+    }
+}
+
+/// Seed of the round trips' jitter, fixed so every run draws alike.
+const SEED: u64 = 0x4e75;
+
+/// Injected round trips, shared by every worker.
+#[derive(Debug)]
+pub struct SimulatedNetwork {
+    latency: Latency,
+    rng: Mutex<Rng>,
+    round_trips: AtomicU64,
+    waited_nanos: AtomicU64,
+}
+
+impl SimulatedNetwork {
+    pub fn new(latency: Latency) -> SimulatedNetwork {
+        SimulatedNetwork { latency, rng: Mutex::new(Rng(SEED)), round_trips: AtomicU64::new(0), waited_nanos: AtomicU64::new(0) }
+    }
+
+    pub fn latency(&self) -> Latency {
+        self.latency
+    }
+
+    /// Sleeps for one round trip and returns how long it was.
+    pub fn round_trip(&self) -> Duration {
+        let wait = self.latency.sample(&mut self.rng.lock().unwrap_or_else(|e| e.into_inner()));
+        thread::sleep(wait);
+        self.round_trips.fetch_add(1, Ordering::Relaxed);
+        self.waited_nanos.fetch_add(wait.as_nanos() as u64, Ordering::Relaxed);
+        wait
+    }
+
+    pub fn round_trips(&self) -> u64 {
+        self.round_trips.load(Ordering::Relaxed)
+    }
+
+    /// Time workers spent in round trips, added up over all of them.
+    pub fn waited(&self) -> Duration {
+        Duration::from_nanos(self.waited_nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// RPCs one NFS operation took, cumulative or over an interval.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rpc {
+    /// As the client names it: `OPEN`, `LOOKUP`, `WRITE`, ...
+    pub op: String,
+    pub count: u64,
+    /// Summed round-trip time of those RPCs, in milliseconds as the client accounts it.
+    pub rtt_ms: u64,
+}
+
+/// A mount's RPCs per operation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RpcSample(pub Vec<Rpc>);
+
+impl RpcSample {
+    /// What happened between `earlier` and this sample.
+    pub fn since(&self, earlier: &RpcSample) -> RpcSample {
+        let then = |op: &str| earlier.0.iter().find(|rpc| rpc.op == op);
+        RpcSample(
+            self.0
+                .iter()
+                .map(|rpc| Rpc {
+                    op: rpc.op.clone(),
+                    count: rpc.count.saturating_sub(then(&rpc.op).map_or(0, |then| then.count)),
+                    rtt_ms: rpc.rtt_ms.saturating_sub(then(&rpc.op).map_or(0, |then| then.rtt_ms)),
+                })
+                .collect(),
+        )
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.iter().map(|rpc| rpc.count).sum()
+    }
+
+    /// Mean round trip over all RPCs, if there were any.
+    pub fn mean_rtt(&self) -> Option<Duration> {
+        let total = self.total();
+        (total > 0).then(|| Duration::from_secs_f64(self.0.iter().map(|rpc| rpc.rtt_ms).sum::<u64>() as f64 / 1000.0 / total as f64))
+    }
+
+    /// The `n` operations with the most RPCs, busiest first, leaving out those with none.
+    pub fn top(&self, n: usize) -> Vec<&Rpc> {
+        let mut rpcs: Vec<&Rpc> = self.0.iter().filter(|rpc| rpc.count > 0).collect();
+        rpcs.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.op.cmp(&b.op)));
+        rpcs.truncate(n);
+        rpcs
+    }
+}
+
+/// The NFS mount holding `dir` in the contents of `/proc/self/mountstats`: the deepest
+/// `nfs` or `nfs4` mount point `dir` is under.
+pub fn nfs_mount(mountstats: &str, dir: &Path) -> Option<PathBuf> {
+    mountstats
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("device ")?;
+            let (_, rest) = rest.split_once(" mounted on ")?;
+            let (mount, rest) = rest.split_once(" with fstype ")?;
+            rest.split_whitespace().next().filter(|fstype| fstype.starts_with("nfs")).map(|_| PathBuf::from(mount))
+        })
+        .filter(|mount| dir.starts_with(mount))
+        .max_by_key(|mount| mount.components().count())
+}
+
+/// The per-operation counts of the mount at `mount` in the contents of
+/// `/proc/self/mountstats`, or `None` if it isn't listed with any.
+pub fn parse_rpcs(mountstats: &str, mount: &Path) -> Option<RpcSample> {
+    let mut lines = mountstats.lines().skip_while(|line| {
+        let device = line.strip_prefix("device ").and_then(|rest| rest.split_once(" mounted on "));
+        device.and_then(|(_, rest)| rest.split_once(" with fstype ")).is_none_or(|(point, _)| Path::new(point) != mount)
+    });
+    lines.next()?;
+    let rpcs: Vec<Rpc> = lines
+        .take_while(|line| !line.starts_with("device "))
+        .skip_while(|line| line.trim() != "per-op statistics")
+        .skip(1)
+        .filter_map(|line| {
+            // `OPEN: ops transmissions timeouts bytes_sent bytes_received queue rtt execute ...`
+            let (op, counts) = line.trim().split_once(':')?;
+            let counts: Vec<u64> = counts.split_whitespace().map_while(|count| count.parse().ok()).collect();
+            Some(Rpc { op: op.to_string(), count: *counts.first()?, rtt_ms: counts.get(6).copied().unwrap_or(0) })
+        })
+        .collect();
+    (!rpcs.is_empty()).then_some(RpcSample(rpcs))
+}
+
+/// Reads the RPC counts of the NFS mount a directory is on.
+#[derive(Debug, Clone)]
+pub struct RpcCounter {
+    mount: PathBuf,
+}
+
+impl RpcCounter {
+    /// A counter for the NFS mount holding `dir`, or where `dir` would be created, or
+    /// `None` when it isn't on one or the kernel doesn't report per-operation counts.
+    pub fn new(dir: &Path) -> Option<RpcCounter> {
+        let dir = dir.ancestors().find_map(|dir| fs::canonicalize(dir).ok())?;
+        let mountstats = fs::read_to_string("/proc/self/mountstats").ok()?;
+        let mount = nfs_mount(&mountstats, &dir)?;
+        parse_rpcs(&mountstats, &mount)?;
+        Some(RpcCounter { mount })
+    }
+
+    pub fn mount(&self) -> &Path {
+        &self.mount
+    }
+
+    /// Counts so far; differences between two samples give a phase's share.
+    pub fn sample(&self) -> RpcSample {
+        fs::read_to_string("/proc/self/mountstats").ok().and_then(|mountstats| parse_rpcs(&mountstats, &self.mount)).unwrap_or_default()
+    }
+}
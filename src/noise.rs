@@ -0,0 +1,182 @@
+//! Whether the machine is quiet enough to measure on. A compile in another terminal, a
+//! CPU governor ramping down between phases, memory too short for the page cache or an
+//! SSD already hot enough to throttle all slow a run down without anything in its results
+//! saying so.
+//!
+//! [`Readings::sample`] takes what Linux exposes without privileges: each CPU's
+//! `cpufreq` governor and frequency, the load average, available memory, and the
+//! temperature of the device under test from its hwmon sensors. [`Readings::concerns`]
+//! holds them against a [`Quiet`], and [`quiesce`] waits until the ones that pass with
+//! time do. Readings that can't be taken, on other platforms or in VMs without `cpufreq`,
+//! are `None` and raise no concern.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::health::Device;
+use crate::human;
+use crate::json::Value;
+use crate::log;
+use crate::platform;
+use crate::soak::meminfo_bytes;
+
+/// What counts as quiet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quiet {
+    /// The 1-minute load average per CPU this process may use.
+    pub max_load_per_cpu: f64,
+    /// Available memory as a share of the total, below which the page cache is squeezed.
+    pub min_available_memory: f64,
+    /// The hottest sensor of the device under test, in °C. NVMe drives start throttling
+    /// somewhere around 70.
+    pub max_temperature: f64,
+}
+
+impl Default for Quiet {
+    fn default() -> Quiet {
+        Quiet { max_load_per_cpu: 0.25, min_available_memory: 0.1, max_temperature: 60.0 }
+    }
+}
+
+/// The machine's state at one moment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Readings {
+    /// The `cpufreq` governors of the CPUs this process may use, each once.
+    pub governors: Vec<String>,
+    /// Their current frequency, averaged, and the highest any of them reaches, in MHz.
+    pub frequency_mhz: Option<f64>,
+    pub max_frequency_mhz: Option<f64>,
+    /// The 1-minute load average.
+    pub load: Option<f64>,
+    /// CPUs this process may use.
+    pub cpus: usize,
+    pub available_memory: Option<u64>,
+    pub total_memory: Option<u64>,
+    /// The hottest sensor of the device holding the directory sampled, in °C.
+    pub temperature: Option<f64>,
+}
+
+/// Something in [`Readings`] that can make a run noisy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Concern {
+    /// A governor other than `performance`, which may lower the frequency mid-run.
+    Governor(String),
+    /// The load average, above [`Quiet::max_load_per_cpu`] for this many CPUs.
+    Load { load: f64, cpus: usize },
+    Memory { available: u64, total: u64 },
+    Temperature(f64),
+}
+
+impl Concern {
+    /// Whether waiting can make it pass; a governor stays as set.
+    pub fn passes_with_time(&self) -> bool {
+        !matches!(self, Concern::Governor(_))
+    }
+}
+
+impl fmt::Display for Concern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Concern::Governor(governor) => write!(f, "CPU governor is {}, not performance", governor),
+            Concern::Load { load, cpus } => write!(f, "load average {:.2} on {} CPUs", load, cpus),
+            Concern::Memory { available, total } => write!(f, "{} MiB of {} MiB memory available", available >> 20, total >> 20),
+            Concern::Temperature(celsius) => write!(f, "device at {:.0} °C", celsius),
+        }
+    }
+}
+
+/// A sysfs file of CPU `cpu`'s `cpufreq` directory.
+fn cpufreq(cpu: usize, file: &str) -> Option<String> {
+    fs::read_to_string(format!("/sys/devices/system/cpu/cpu{}/cpufreq/{}", cpu, file)).ok().map(|value| value.trim().to_string())
+}
+
+impl Readings {
+    /// The machine now, with the temperature of the device `dir` is on.
+    pub fn sample(dir: &Path) -> Readings {
+        let cpus = platform::allowed_cpus();
+        let mut governors: Vec<String> = cpus.iter().filter_map(|&cpu| cpufreq(cpu, "scaling_governor")).collect();
+        governors.sort();
+        governors.dedup();
+        let khz = |file: &str| cpus.iter().filter_map(|&cpu| cpufreq(cpu, file)?.parse::<f64>().ok()).collect::<Vec<_>>();
+        let current = khz("scaling_cur_freq");
+        let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+        Readings {
+            governors,
+            frequency_mhz: (!current.is_empty()).then(|| current.iter().sum::<f64>() / current.len() as f64 / 1000.0),
+            max_frequency_mhz: khz("cpuinfo_max_freq").into_iter().max_by(f64::total_cmp).map(|khz| khz / 1000.0),
+            load: fs::read_to_string("/proc/loadavg").ok().and_then(|loadavg| loadavg.split_whitespace().next()?.parse().ok()),
+            cpus: cpus.len(),
+            available_memory: meminfo_bytes(&meminfo, "MemAvailable"),
+            total_memory: meminfo_bytes(&meminfo, "MemTotal"),
+            temperature: Device::of(dir).and_then(|device| device.temperatures().readings.into_iter().map(|(_, celsius)| celsius).max_by(f64::total_cmp)),
+        }
+    }
+
+    /// What about these readings could make a run noisy, by `quiet`.
+    pub fn concerns(&self, quiet: &Quiet) -> Vec<Concern> {
+        let mut concerns: Vec<Concern> = self.governors.iter().filter(|governor| *governor != "performance").map(|governor| Concern::Governor(governor.clone())).collect();
+        if let Some(load) = self.load.filter(|&load| load > quiet.max_load_per_cpu * self.cpus.max(1) as f64) {
+            concerns.push(Concern::Load { load, cpus: self.cpus });
+        }
+        if let (Some(available), Some(total)) = (self.available_memory, self.total_memory)
+            && (available as f64) < quiet.min_available_memory * total as f64
+        {
+            concerns.push(Concern::Memory { available, total });
+        }
+        if let Some(celsius) = self.temperature.filter(|&celsius| celsius > quiet.max_temperature) {
+            concerns.push(Concern::Temperature(celsius));
+        }
+        concerns
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::object()
+            .with("governors", self.governors.iter().map(String::as_str).collect::<Vec<_>>())
+            .with("frequency_mhz", self.frequency_mhz)
+            .with("max_frequency_mhz", self.max_frequency_mhz)
+            .with("load", self.load)
+            .with("cpus", self.cpus)
+            .with("available_memory_bytes", self.available_memory)
+            .with("total_memory_bytes", self.total_memory)
+            .with("temperature_c", self.temperature)
+    }
+
+    pub fn from_json(value: &Value) -> Option<Readings> {
+        let number = |key: &str| value.get(key).and_then(Value::as_f64);
+        let bytes = |key: &str| value.get(key).and_then(Value::as_u64);
+        Some(Readings {
+            governors: value.get("governors")?.as_array()?.iter().map(|governor| Some(governor.as_str()?.to_string())).collect::<Option<_>>()?,
+            frequency_mhz: number("frequency_mhz"),
+            max_frequency_mhz: number("max_frequency_mhz"),
+            load: number("load"),
+            cpus: value.get("cpus")?.as_u64()? as usize,
+            available_memory: bytes("available_memory_bytes"),
+            total_memory: bytes("total_memory_bytes"),
+            temperature: number("temperature_c"),
+        })
+    }
+}
+
+/// Samples the machine every `poll` until nothing but what time can't fix is of concern
+/// by `quiet`, for at most `timeout` or until the run is cancelled. Returns the last
+/// readings and how long it waited; the readings' concerns say whether it gave up.
+pub fn quiesce(dir: &Path, quiet: &Quiet, timeout: Duration, poll: Duration) -> (Readings, Duration) {
+    let start = Instant::now();
+    let mut logged = false;
+    loop {
+        let readings = Readings::sample(dir);
+        let waiting: Vec<String> = readings.concerns(quiet).iter().filter(|concern| concern.passes_with_time()).map(Concern::to_string).collect();
+        if waiting.is_empty() || start.elapsed() >= timeout || cancel::is_interrupted() {
+            return (readings, start.elapsed());
+        }
+        if !logged {
+            log::info("noise", format!("waiting up to {} for the machine to quiet down: {}", human::duration(timeout), waiting.join(", ")));
+            logged = true;
+        }
+        thread::sleep(poll.min(timeout.saturating_sub(start.elapsed())));
+    }
+}
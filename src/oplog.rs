@@ -0,0 +1,811 @@
+//! Recorded I/O traces, replayed in a scratch tree so a benchmark can do what a real tool
+//! (cargo, npm, biome) actually did instead of a synthetic pattern.
+//!
+//! Two formats are read. `strace -f` output, with or without `-tt`/`-ttt` timestamps, keeps
+//! the calls that succeeded among `open`, `openat`, `creat`, `read`, `pread64`, `readv`,
+//! `write`, `pwrite64`, `writev`, `close`, `rename`, `renameat`, `renameat2`, `unlink`,
+//! `unlinkat`, `mkdir`, `mkdirat`, `rmdir`, `stat`, `lstat`, `newfstatat` and `statx`,
+//! with descriptors mapped back to the paths they were opened on; everything else is
+//! skipped. A JSONL op log has one operation per line:
+//!
+//! ```text
+//! {"t": 0.000, "op": "open", "path": "src/main.rs"}
+//! {"t": 0.001, "op": "read", "path": "src/main.rs", "bytes": 4096}
+//! {"t": 0.002, "op": "close", "path": "src/main.rs"}
+//! {"t": 0.010, "op": "open", "path": "target/out.o", "mode": "w"}
+//! {"t": 0.011, "op": "write", "path": "target/out.o", "bytes": 65536}
+//! {"t": 0.012, "op": "rename", "path": "target/out.o", "to": "target/lib.o"}
+//! ```
+//!
+//! `t` is seconds since the trace started, the previous line's when missing. `op` is one of
+//! the [`Kind`] names, and `mode` one of `r` (the default), `w` (create and truncate), `a`
+//! (create and append) or `rw`.
+//!
+//! Every path lands under the replay root, absolute ones with their leading `/` dropped
+//! and relative ones as if the tool had run in the root. [`prepare`] first creates what the
+//! trace uses without having made it itself, files as large as the trace reads from them,
+//! so the replay finds what the tool found. [`replay`] then runs the operations in their
+//! recorded order on one thread; an operation that fails is counted and the replay goes on.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::json::{self, Value};
+
+/// The most a single read or write moves at once.
+const CHUNK: usize = 1 << 20;
+
+/// Slowest [`Timing::Scaled`] replay, `0.001x`, so scaled times stay within a [`Duration`].
+const MIN_SPEED_UP: f64 = 0.001;
+
+/// What an operation does, for grouping them in reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Open,
+    Read,
+    Write,
+    Close,
+    Rename,
+    Unlink,
+    Mkdir,
+    Rmdir,
+    Stat,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 9] = [Kind::Open, Kind::Read, Kind::Write, Kind::Close, Kind::Rename, Kind::Unlink, Kind::Mkdir, Kind::Rmdir, Kind::Stat];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Open => "open",
+            Kind::Read => "read",
+            Kind::Write => "write",
+            Kind::Close => "close",
+            Kind::Rename => "rename",
+            Kind::Unlink => "unlink",
+            Kind::Mkdir => "mkdir",
+            Kind::Rmdir => "rmdir",
+            Kind::Stat => "stat",
+        }
+    }
+}
+
+/// How a file is opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Read,
+    /// Created if missing and truncated.
+    Write,
+    /// Created if missing, written at the end.
+    Append,
+    /// Read and written in place.
+    ReadWrite,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Open { path: PathBuf, mode: Mode },
+    /// Reads from the file's most recently opened handle, opening one if there is none.
+    Read { path: PathBuf, bytes: u64 },
+    Write { path: PathBuf, bytes: u64 },
+    Close { path: PathBuf },
+    Rename { from: PathBuf, to: PathBuf },
+    Unlink { path: PathBuf },
+    Mkdir { path: PathBuf },
+    Rmdir { path: PathBuf },
+    Stat { path: PathBuf },
+}
+
+impl Op {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Op::Open { .. } => Kind::Open,
+            Op::Read { .. } => Kind::Read,
+            Op::Write { .. } => Kind::Write,
+            Op::Close { .. } => Kind::Close,
+            Op::Rename { .. } => Kind::Rename,
+            Op::Unlink { .. } => Kind::Unlink,
+            Op::Mkdir { .. } => Kind::Mkdir,
+            Op::Rmdir { .. } => Kind::Rmdir,
+            Op::Stat { .. } => Kind::Stat,
+        }
+    }
+
+    /// The path the operation acts on; a rename's source.
+    pub fn path(&self) -> &Path {
+        match self {
+            Op::Open { path, .. }
+            | Op::Read { path, .. }
+            | Op::Write { path, .. }
+            | Op::Close { path }
+            | Op::Unlink { path }
+            | Op::Mkdir { path }
+            | Op::Rmdir { path }
+            | Op::Stat { path } => path,
+            Op::Rename { from, .. } => from,
+        }
+    }
+}
+
+/// One recorded operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// When it started, from the start of the trace.
+    pub at: Duration,
+    pub op: Op,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub events: Vec<Event>,
+    /// Lines or calls that weren't replayable: other calls, failed ones, descriptors that
+    /// weren't files.
+    pub skipped: usize,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// `path` without `.` components and with `..` taken back lexically.
+fn clean(path: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                cleaned.pop();
+            }
+            _ => cleaned.push(component),
+        }
+    }
+    cleaned
+}
+
+/// Where `path` of the trace lands under `root`.
+fn under(root: &Path, path: &Path) -> PathBuf {
+    let mut mapped = root.to_path_buf();
+    mapped.extend(path.components().filter(|c| matches!(c, Component::Normal(_))));
+    mapped
+}
+
+impl Trace {
+    /// Reads a trace file, telling the formats apart by whether it starts with `{`.
+    pub fn load(path: &Path) -> io::Result<Trace> {
+        let text = fs::read_to_string(path)?;
+        let parsed = if text.trim_start().starts_with('{') { Trace::parse_jsonl(&text) } else { Trace::parse_strace(&text) };
+        let trace = parsed.map_err(|e| invalid(format!("{}: {}", path.display(), e)))?;
+        if trace.events.is_empty() {
+            return Err(invalid(format!("{}: no replayable operations", path.display())));
+        }
+        Ok(trace)
+    }
+
+    /// From the start of the trace to the start of its last operation.
+    pub fn duration(&self) -> Duration {
+        self.events.last().map_or(Duration::ZERO, |event| event.at)
+    }
+
+    pub fn parse_jsonl(text: &str) -> Result<Trace, String> {
+        let mut trace = Trace::default();
+        let mut at = Duration::ZERO;
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            let value = json::parse(line).map_err(|e| error(&e))?;
+            if let Some(t) = value.get("t") {
+                at = t.as_f64().filter(|t| *t >= 0.0 && t.is_finite()).map(Duration::from_secs_f64).ok_or_else(|| error("\"t\" must be seconds"))?;
+            }
+            let text = |key: &str| value.get(key).and_then(Value::as_str);
+            let path = || text("path").map(|path| clean(Path::new(path))).ok_or_else(|| error("needs a \"path\""));
+            let bytes = || value.get("bytes").and_then(Value::as_u64).ok_or_else(|| error("needs a numeric \"bytes\""));
+            let op = match text("op") {
+                Some("open") => {
+                    let mode = match text("mode").unwrap_or("r") {
+                        "r" => Mode::Read,
+                        "w" => Mode::Write,
+                        "a" => Mode::Append,
+                        "rw" => Mode::ReadWrite,
+                        _ => return Err(error("\"mode\" must be r, w, a or rw")),
+                    };
+                    Op::Open { path: path()?, mode }
+                }
+                Some("read") => Op::Read { path: path()?, bytes: bytes()? },
+                Some("write") => Op::Write { path: path()?, bytes: bytes()? },
+                Some("close") => Op::Close { path: path()? },
+                Some("rename") => Op::Rename { from: path()?, to: text("to").map(|to| clean(Path::new(to))).ok_or_else(|| error("rename needs a \"to\""))? },
+                Some("unlink") => Op::Unlink { path: path()? },
+                Some("mkdir") => Op::Mkdir { path: path()? },
+                Some("rmdir") => Op::Rmdir { path: path()? },
+                Some("stat") => Op::Stat { path: path()? },
+                _ => return Err(error("unknown or missing \"op\"")),
+            };
+            trace.events.push(Event { at, op });
+        }
+        Ok(trace)
+    }
+
+    pub fn parse_strace(text: &str) -> Result<Trace, String> {
+        let mut parser = Strace::default();
+        for line in text.lines() {
+            parser.line(line);
+        }
+        let mut trace = parser.trace;
+        if let Some(first) = parser.first {
+            for event in &mut trace.events {
+                event.at = event.at.saturating_sub(first);
+            }
+        }
+        // Processes writing to one log can land a little out of order.
+        trace.events.sort_by_key(|event| event.at);
+        Ok(trace)
+    }
+
+    /// The trace as a JSONL op log, which [`Trace::parse_jsonl`] reads back.
+    pub fn to_jsonl(&self) -> String {
+        let path = |path: &Path| Value::from(path.to_string_lossy().into_owned());
+        let mut text = String::new();
+        for event in &self.events {
+            let line = Value::object().with("t", event.at.as_secs_f64()).with("op", event.op.kind().name()).with("path", path(event.op.path()));
+            let line = match &event.op {
+                Op::Open { mode, .. } => line.with(
+                    "mode",
+                    match mode {
+                        Mode::Read => "r",
+                        Mode::Write => "w",
+                        Mode::Append => "a",
+                        Mode::ReadWrite => "rw",
+                    },
+                ),
+                Op::Read { bytes, .. } | Op::Write { bytes, .. } => line.with("bytes", *bytes),
+                Op::Rename { to, .. } => line.with("to", path(to)),
+                _ => line,
+            };
+            text.push_str(&line.to_string());
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// What a descriptor of the traced process refers to.
+#[derive(Debug, Clone)]
+struct Descriptor {
+    path: PathBuf,
+    directory: bool,
+}
+
+#[derive(Debug, Default)]
+struct Strace {
+    trace: Trace,
+    /// Open descriptors by process and number.
+    descriptors: HashMap<(u32, i64), Descriptor>,
+    /// The latest descriptor of each number in any process, for threads, which share
+    /// their process's descriptors but are traced under their own ids.
+    latest: HashMap<i64, Descriptor>,
+    /// The start of calls each process left `<unfinished ...>`.
+    unfinished: HashMap<u32, String>,
+    /// The earliest timestamp, which the others are taken from.
+    first: Option<Duration>,
+    at: Duration,
+}
+
+/// The time in a `-ttt` (`1697040000.123456`) or `-tt` (`14:03:05.123456`) timestamp, kept
+/// exact rather than as a float, which loses microseconds at epoch scale.
+fn timestamp(word: &str) -> Option<Duration> {
+    let (whole, fraction) = word.split_once('.')?;
+    let seconds = whole.split(':').try_fold(0u64, |total, part| Some(total * 60 + part.parse::<u64>().ok()?))?;
+    if fraction.is_empty() || fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos: u32 = format!("{:0<9}", fraction).parse().ok()?;
+    Some(Duration::new(seconds, nanos))
+}
+
+/// Splits a call's arguments at top-level commas.
+fn arguments(text: &str) -> Vec<&str> {
+    let (mut arguments, mut depth, mut quoted, mut escaped, mut start) = (Vec::new(), 0, false, false, 0);
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '[' | '{' | '(' if !quoted => depth += 1,
+            ']' | '}' | ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                arguments.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    arguments.push(text[start..].trim());
+    arguments
+}
+
+/// The string of a quoted argument, escapes decoded.
+fn string(argument: &str) -> Option<PathBuf> {
+    let text = argument.strip_prefix('"')?.as_bytes();
+    let (mut bytes, mut i) = (Vec::new(), 0);
+    let digits = |from: usize, radix: u32, most: usize| {
+        let end = (from..text.len().min(from + most)).find(|&j| !(text[j] as char).is_digit(radix)).unwrap_or(text.len().min(from + most));
+        Some((u32::from_str_radix(std::str::from_utf8(&text[from..end]).ok()?, radix).ok()? as u8, end))
+    };
+    loop {
+        match *text.get(i)? {
+            b'"' => return Some(PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())),
+            b'\\' => {
+                let (byte, next) = match *text.get(i + 1)? {
+                    b'n' => (b'\n', i + 2),
+                    b't' => (b'\t', i + 2),
+                    b'r' => (b'\r', i + 2),
+                    b'v' => (0x0b, i + 2),
+                    b'f' => (0x0c, i + 2),
+                    b'x' => digits(i + 2, 16, 2)?,
+                    b'0'..=b'7' => digits(i + 1, 8, 3)?,
+                    other => (other, i + 2),
+                };
+                bytes.push(byte);
+                i = next;
+            }
+            b => {
+                bytes.push(b);
+                i += 1;
+            }
+        }
+    }
+}
+
+impl Strace {
+    fn line(&mut self, line: &str) {
+        let mut rest = line.trim();
+        if rest.is_empty() {
+            return;
+        }
+        let mut pid = 0;
+        if let Some(tail) = rest.strip_prefix("[pid") {
+            let Some((id, tail)) = tail.split_once(']') else {
+                self.trace.skipped += 1;
+                return;
+            };
+            pid = id.trim().parse().unwrap_or(0);
+            rest = tail.trim_start();
+        }
+        loop {
+            let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if pid == 0 && !word.is_empty() && word.bytes().all(|b| b.is_ascii_digit()) {
+                pid = word.parse().unwrap_or(0);
+            } else if let Some(at) = timestamp(word) {
+                self.at = at;
+                self.first = Some(self.first.map_or(at, |first| first.min(at)));
+            } else {
+                break;
+            }
+            rest = tail.trim_start();
+        }
+
+        let call = if let Some(start) = rest.strip_suffix("<unfinished ...>") {
+            self.unfinished.insert(pid, start.to_string());
+            return;
+        } else if let Some(resumed) = rest.strip_prefix("<...") {
+            match (self.unfinished.remove(&pid), resumed.split_once("resumed>")) {
+                (Some(start), Some((_, end))) => start + end,
+                _ => {
+                    self.trace.skipped += 1;
+                    return;
+                }
+            }
+        } else {
+            rest.to_string()
+        };
+        if !self.call(pid, &call) {
+            self.trace.skipped += 1;
+        }
+    }
+
+    fn descriptor(&self, pid: u32, fd: i64) -> Option<&Descriptor> {
+        self.descriptors.get(&(pid, fd)).or_else(|| self.latest.get(&fd))
+    }
+
+    /// `path` relative to the directory descriptor `dirfd`, or as it is.
+    fn resolve(&self, pid: u32, dirfd: &str, path: &str) -> Option<PathBuf> {
+        let path = string(path)?;
+        if path.is_absolute() || dirfd == "AT_FDCWD" {
+            return Some(clean(&path));
+        }
+        let dir = self.descriptor(pid, dirfd.parse().ok()?)?;
+        Some(clean(&dir.path.join(path)))
+    }
+
+    /// Records one complete call, returning whether it was replayable or moved a
+    /// descriptor the replay follows.
+    fn call(&mut self, pid: u32, call: &str) -> bool {
+        let Some((name, tail)) = call.split_once('(') else {
+            return false;
+        };
+        // strace pads the result out to a column: `close(3)      = 0`.
+        let Some((args, result)) = tail.rsplit_once('=').and_then(|(args, result)| Some((args.trim_end().strip_suffix(')')?, result))) else {
+            return false;
+        };
+        let Some(result) = result.split_whitespace().next().and_then(|r| r.parse::<i64>().ok()).filter(|r| *r >= 0) else {
+            return false;
+        };
+        let args = arguments(args);
+        let arg = |i: usize| args.get(i).copied().unwrap_or("");
+        if let "dup" | "dup2" | "dup3" = name.trim() {
+            // A shell's `> file` moves the file to descriptor 1 before the command writes.
+            let Ok(fd) = arg(0).parse() else {
+                return false;
+            };
+            match self.descriptor(pid, fd).cloned() {
+                Some(descriptor) => {
+                    self.descriptors.insert((pid, result), descriptor.clone());
+                    self.latest.insert(result, descriptor);
+                }
+                None => {
+                    self.descriptors.remove(&(pid, result));
+                    self.latest.remove(&result);
+                }
+            }
+            return true;
+        }
+        let op = match name.trim() {
+            name @ ("open" | "openat" | "creat") => {
+                let (path, flags) = match name {
+                    "openat" => (self.resolve(pid, arg(0), arg(1)), arg(2)),
+                    "creat" => (self.resolve(pid, "AT_FDCWD", arg(0)), "O_WRONLY|O_CREAT|O_TRUNC"),
+                    _ => (self.resolve(pid, "AT_FDCWD", arg(0)), arg(1)),
+                };
+                let Some(path) = path else {
+                    return false;
+                };
+                let directory = flags.contains("O_DIRECTORY");
+                let descriptor = Descriptor { path: path.clone(), directory };
+                self.descriptors.insert((pid, result), descriptor.clone());
+                self.latest.insert(result, descriptor);
+                if directory {
+                    return false;
+                }
+                let mode = if flags.contains("O_RDWR") {
+                    Mode::ReadWrite
+                } else if flags.contains("O_WRONLY") && flags.contains("O_APPEND") {
+                    Mode::Append
+                } else if flags.contains("O_WRONLY") {
+                    Mode::Write
+                } else {
+                    Mode::Read
+                };
+                Op::Open { path, mode }
+            }
+            "read" | "pread64" | "readv" | "preadv" | "write" | "pwrite64" | "writev" | "pwritev" => {
+                let Some(file) = arg(0).parse().ok().and_then(|fd| self.descriptor(pid, fd)).filter(|d| !d.directory) else {
+                    return false;
+                };
+                let (path, bytes) = (file.path.clone(), result as u64);
+                if name.contains("read") { Op::Read { path, bytes } } else { Op::Write { path, bytes } }
+            }
+            "close" => {
+                let Ok(fd) = arg(0).parse() else {
+                    return false;
+                };
+                let Some(file) = self.descriptors.remove(&(pid, fd)).or_else(|| self.latest.get(&fd).cloned()) else {
+                    return false;
+                };
+                self.latest.remove(&fd);
+                if file.directory {
+                    return false;
+                }
+                Op::Close { path: file.path }
+            }
+            "rename" | "renameat" | "renameat2" => {
+                let (from, to) = match name {
+                    "rename" => (self.resolve(pid, "AT_FDCWD", arg(0)), self.resolve(pid, "AT_FDCWD", arg(1))),
+                    _ => (self.resolve(pid, arg(0), arg(1)), self.resolve(pid, arg(2), arg(3))),
+                };
+                let (Some(from), Some(to)) = (from, to) else {
+                    return false;
+                };
+                Op::Rename { from, to }
+            }
+            "unlink" | "rmdir" | "unlinkat" => {
+                let (path, directory) = match name {
+                    "unlinkat" => (self.resolve(pid, arg(0), arg(1)), arg(2).contains("AT_REMOVEDIR")),
+                    _ => (self.resolve(pid, "AT_FDCWD", arg(0)), name == "rmdir"),
+                };
+                let Some(path) = path else {
+                    return false;
+                };
+                if directory { Op::Rmdir { path } } else { Op::Unlink { path } }
+            }
+            "mkdir" | "mkdirat" => {
+                let path = if name == "mkdir" { self.resolve(pid, "AT_FDCWD", arg(0)) } else { self.resolve(pid, arg(0), arg(1)) };
+                let Some(path) = path else {
+                    return false;
+                };
+                Op::Mkdir { path }
+            }
+            "stat" | "lstat" | "newfstatat" | "fstatat64" | "statx" => {
+                let path = if name.ends_with("stat") { self.resolve(pid, "AT_FDCWD", arg(0)) } else { self.resolve(pid, arg(0), arg(1)) };
+                // An empty path with AT_EMPTY_PATH is an fstat.
+                let Some(path) = path.filter(|path| !path.as_os_str().is_empty()) else {
+                    return false;
+                };
+                Op::Stat { path }
+            }
+            _ => return false,
+        };
+        self.trace.events.push(Event { at: self.at, op });
+        true
+    }
+}
+
+/// How the replay is paced.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Timing {
+    /// Every operation as soon as the one before it finishes.
+    #[default]
+    Compressed,
+    /// Every operation at its recorded time, or later if the ones before it ran long.
+    Original,
+    /// The recorded times divided by this factor: `2x` replays twice as fast.
+    Scaled(f64),
+}
+
+impl Timing {
+    /// When the operation recorded at `at` is due, if the replay is paced.
+    fn due(self, at: Duration) -> Option<Duration> {
+        match self {
+            Timing::Compressed => None,
+            Timing::Original => Some(at),
+            Timing::Scaled(factor) => Some(at.div_f64(factor)),
+        }
+    }
+}
+
+impl FromStr for Timing {
+    type Err = io::Error;
+
+    /// `compressed`, `original`, or a speed-up such as `2x` or `0.5x`.
+    fn from_str(s: &str) -> io::Result<Timing> {
+        match s {
+            "compressed" => Ok(Timing::Compressed),
+            "original" => Ok(Timing::Original),
+            _ => match s.strip_suffix('x').and_then(|factor| factor.parse::<f64>().ok()) {
+                Some(factor) if (MIN_SPEED_UP..=f64::MAX).contains(&factor) => Ok(Timing::Scaled(factor)),
+                Some(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("timing '{}' is out of range; speed-ups go from {}x", s, MIN_SPEED_UP))),
+                _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown timing '{}', expected compressed, original or a speed-up such as 2x", s))),
+            },
+        }
+    }
+}
+
+/// Creates under `root` what the trace uses before making it itself: the files it opens
+/// for reading, stats, renames or removes, as large as the furthest any one open reads
+/// into them before rewriting them, and the directories they are in. Returns how many files and
+/// directories it made.
+pub fn prepare(trace: &Trace, root: &Path) -> io::Result<usize> {
+    // Paths the trace creates, or finds, in the order it first touches them.
+    let mut made = HashSet::new();
+    let mut found: Vec<PathBuf> = Vec::new();
+    let mut seen: HashSet<&Path> = HashSet::new();
+    // Reads carry no offset: each continues where the last one through the open left off.
+    let mut positions: HashMap<&Path, u64> = HashMap::new();
+    let mut sizes: HashMap<&Path, u64> = HashMap::new();
+    fn find<'a>(path: &'a Path, made: &HashSet<&Path>, found: &mut Vec<PathBuf>, seen: &mut HashSet<&'a Path>) {
+        if !made.contains(path) && seen.insert(path) {
+            found.push(path.to_path_buf());
+        }
+    }
+    for event in &trace.events {
+        match &event.op {
+            Op::Open { path, mode: Mode::Write | Mode::Append } | Op::Mkdir { path } => {
+                made.insert(path.as_path());
+            }
+            Op::Open { path, .. } => {
+                find(path, &made, &mut found, &mut seen);
+                positions.insert(path, 0);
+            }
+            Op::Rename { from, to } => {
+                find(from, &made, &mut found, &mut seen);
+                made.insert(to.as_path());
+            }
+            Op::Read { path, bytes } => {
+                find(path, &made, &mut found, &mut seen);
+                if !made.contains(path.as_path()) {
+                    let position = positions.entry(path).or_default();
+                    *position += bytes;
+                    let size = sizes.entry(path).or_default();
+                    *size = (*size).max(*position);
+                }
+            }
+            Op::Write { path, .. } => {
+                find(path, &made, &mut found, &mut seen);
+                made.insert(path.as_path());
+            }
+            op => find(op.path(), &made, &mut found, &mut seen),
+        }
+    }
+    // A path with others beneath it is a directory, whatever was done to it.
+    let mut directories: HashSet<&Path> = trace
+        .events
+        .iter()
+        .flat_map(|event| match &event.op {
+            Op::Rename { from, to } => vec![from.as_path(), to.as_path()],
+            op => vec![op.path()],
+        })
+        .flat_map(|path| path.ancestors().skip(1))
+        .collect();
+    for event in &trace.events {
+        if let Op::Rmdir { path } = &event.op {
+            directories.insert(path);
+        }
+    }
+
+    let mut count = 0;
+    for path in found.iter().filter(|path| !path.as_os_str().is_empty()) {
+        let target = under(root, path);
+        if directories.contains(path.as_path()) {
+            if !target.is_dir() {
+                fs::create_dir_all(&target)?;
+                count += 1;
+            }
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = File::create(&target)?;
+        file.set_len(sizes.get(path.as_path()).copied().unwrap_or(0))?;
+        count += 1;
+    }
+    // What the trace makes needs the directory it goes in, unless the trace makes that too.
+    for path in made.iter().filter_map(|path| path.parent()).filter(|parent| !made.contains(parent)) {
+        fs::create_dir_all(under(root, path))?;
+    }
+    Ok(count)
+}
+
+/// Operations of one kind in a replay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KindStats {
+    pub kind: Kind,
+    pub count: u64,
+    /// Bytes read or written.
+    pub bytes: u64,
+    pub time: Duration,
+    pub max: Duration,
+}
+
+/// An operation that failed in the replay.
+#[derive(Debug)]
+pub struct Failure {
+    /// Index into [`Trace::events`].
+    pub event: usize,
+    pub kind: Kind,
+    pub path: PathBuf,
+    pub error: io::Error,
+}
+
+#[derive(Debug)]
+pub struct Report {
+    /// The kinds that occurred, in [`Kind::ALL`] order.
+    pub stats: Vec<KindStats>,
+    pub elapsed: Duration,
+    /// The most any operation started after it was due, when paced.
+    pub behind: Duration,
+    pub failures: Vec<Failure>,
+}
+
+/// The files the replay holds open, the latest of each path last.
+#[derive(Default)]
+struct Handles(HashMap<PathBuf, Vec<File>>);
+
+impl Handles {
+    fn latest(&mut self, path: &Path, open: impl FnOnce() -> io::Result<File>) -> io::Result<&mut File> {
+        let files = self.0.entry(path.to_path_buf()).or_default();
+        if files.is_empty() {
+            files.push(open()?);
+        }
+        Ok(files.last_mut().expect("a handle was just opened"))
+    }
+}
+
+fn run(op: &Op, root: &Path, handles: &mut Handles, buffer: &mut [u8]) -> io::Result<u64> {
+    match op {
+        Op::Open { path, mode } => {
+            let mut options = OpenOptions::new();
+            match mode {
+                Mode::Read => options.read(true),
+                Mode::Write => options.write(true).create(true).truncate(true),
+                Mode::Append => options.append(true).create(true),
+                Mode::ReadWrite => options.read(true).write(true),
+            };
+            let file = options.open(under(root, path))?;
+            handles.0.entry(path.clone()).or_default().push(file);
+            Ok(0)
+        }
+        Op::Read { path, bytes } => {
+            let file = handles.latest(path, || File::open(under(root, path)))?;
+            let mut done = 0;
+            while done < *bytes {
+                let want = (*bytes - done).min(buffer.len() as u64) as usize;
+                match file.read(&mut buffer[..want])? {
+                    0 => break,
+                    n => done += n as u64,
+                }
+            }
+            Ok(done)
+        }
+        Op::Write { path, bytes } => {
+            let file = handles.latest(path, || OpenOptions::new().write(true).create(true).truncate(false).open(under(root, path)))?;
+            let mut done = 0;
+            while done < *bytes {
+                let chunk = (*bytes - done).min(buffer.len() as u64) as usize;
+                file.write_all(&buffer[..chunk])?;
+                done += chunk as u64;
+            }
+            Ok(done)
+        }
+        Op::Close { path } => {
+            if let Some(files) = handles.0.get_mut(path) {
+                files.pop();
+            }
+            Ok(0)
+        }
+        Op::Rename { from, to } => {
+            fs::rename(under(root, from), under(root, to))?;
+            // Open handles follow the file.
+            if let Some(files) = handles.0.remove(from) {
+                handles.0.entry(to.clone()).or_default().extend(files);
+            }
+            Ok(0)
+        }
+        Op::Unlink { path } => fs::remove_file(under(root, path)).map(|()| 0),
+        Op::Mkdir { path } => fs::create_dir(under(root, path)).map(|()| 0),
+        Op::Rmdir { path } => fs::remove_dir(under(root, path)).map(|()| 0),
+        Op::Stat { path } => fs::metadata(under(root, path)).map(|_| 0),
+    }
+}
+
+/// Runs the trace's operations in order under `root`, paced by `timing`, timing each.
+/// Failed operations are recorded and skipped; stops with [`cancel::cancelled`] if the
+/// process is interrupted.
+pub fn replay(trace: &Trace, root: &Path, timing: Timing) -> io::Result<Report> {
+    let mut stats: Vec<KindStats> = Kind::ALL.iter().map(|&kind| KindStats { kind, count: 0, bytes: 0, time: Duration::ZERO, max: Duration::ZERO }).collect();
+    let (mut handles, mut buffer) = (Handles::default(), vec![0u8; CHUNK]);
+    let (mut behind, mut failures) = (Duration::ZERO, Vec::new());
+    let start = Instant::now();
+    for (index, event) in trace.events.iter().enumerate() {
+        if cancel::is_interrupted() {
+            return Err(cancel::cancelled());
+        }
+        if let Some(due) = timing.due(event.at) {
+            let now = start.elapsed();
+            match due.checked_sub(now) {
+                Some(wait) => thread::sleep(wait),
+                None => behind = behind.max(now - due),
+            }
+        }
+        let kind = event.op.kind();
+        let began = Instant::now();
+        let result = run(&event.op, root, &mut handles, &mut buffer);
+        let elapsed = began.elapsed();
+        let entry = &mut stats[Kind::ALL.iter().position(|&k| k == kind).expect("every kind is listed")];
+        entry.count += 1;
+        entry.time += elapsed;
+        entry.max = entry.max.max(elapsed);
+        match result {
+            Ok(bytes) => entry.bytes += bytes,
+            Err(error) => failures.push(Failure { event: index, kind, path: event.op.path().to_path_buf(), error }),
+        }
+    }
+    let elapsed = start.elapsed();
+    stats.retain(|entry| entry.count > 0);
+    Ok(Report { stats, elapsed, behind, failures })
+}
@@ -0,0 +1,237 @@
+//! The order a batch of files is read in. Devices and filesystems prefetch differently: a
+//! disk gains from reading in inode (roughly on-disk) order, readahead from files laid out
+//! together, and some SSDs from nothing at all, so the best order is measured rather than
+//! assumed.
+//!
+//! [`arrange`] puts paths in a [`ReadOrder`] and [`crate::readonly::DataSet::arrange`] a
+//! listed tree. With the `bench` feature, `run` reads a data set in every order with a cold
+//! cache and reports which was fastest, which `io bench readonly --read-order auto` then
+//! reads in.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "bench")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+#[cfg(feature = "bench")]
+use crate::buffers;
+#[cfg(feature = "bench")]
+use crate::cache;
+use crate::population::Rng;
+#[cfg(feature = "bench")]
+use crate::readonly::DataSet;
+#[cfg(feature = "bench")]
+use crate::trace::OpId;
+
+/// Seed of [`ReadOrder::Random`], fixed so every run shuffles alike.
+pub const SEED: u64 = 0x5eed;
+
+/// An order to read files in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOrder {
+    /// As each directory lists its entries, directories in the order first reached.
+    Directory,
+    /// By inode number, which most filesystems allocate close to where the data lands.
+    Inode,
+    /// Smallest file first.
+    Size,
+    /// Shuffled, as a baseline no prefetcher can help.
+    Random,
+}
+
+impl ReadOrder {
+    pub const ALL: [ReadOrder; 4] = [ReadOrder::Directory, ReadOrder::Inode, ReadOrder::Size, ReadOrder::Random];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ReadOrder::Directory => "directory",
+            ReadOrder::Inode => "inode",
+            ReadOrder::Size => "size",
+            ReadOrder::Random => "random",
+        }
+    }
+}
+
+impl std::str::FromStr for ReadOrder {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<ReadOrder> {
+        ReadOrder::ALL
+            .into_iter()
+            .find(|order| order.name() == s)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("unknown read order '{}', expected directory|inode|size|random", s)))
+    }
+}
+
+/// The indices of `paths` in `order`. Ties keep their relative order. Inode order falls
+/// back to directory order where inodes aren't available. Files that vanished or can't be
+/// examined go last, so a tree changing under a run doesn't stop it; reading them fails
+/// and is recorded like any other unreadable file.
+pub fn arrange(order: ReadOrder, paths: &[PathBuf]) -> Vec<usize> {
+    arrange_by(order, paths, |i| fs::symlink_metadata(&paths[i]).ok().map(|metadata| metadata.len()))
+}
+
+/// [`arrange`] with sizes already known, as a listed [`crate::readonly::DataSet`] has them,
+/// so size order doesn't examine every file again.
+pub fn arrange_sized(order: ReadOrder, paths: &[PathBuf], sizes: &[u64]) -> Vec<usize> {
+    arrange_by(order, paths, |i| Some(sizes[i]))
+}
+
+fn arrange_by(order: ReadOrder, paths: &[PathBuf], size: impl Fn(usize) -> Option<u64>) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..paths.len()).collect();
+    match order {
+        ReadOrder::Directory => {
+            let positions = listing_positions(paths);
+            indices.sort_by_key(|&i| positions[i]);
+        }
+        ReadOrder::Inode => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+
+                let inodes: Vec<Option<u64>> = paths.iter().map(|path| fs::symlink_metadata(path).ok().map(|metadata| metadata.ino())).collect();
+                indices.sort_by_key(|&i| (inodes[i].is_none(), inodes[i]));
+            }
+            #[cfg(not(unix))]
+            return arrange_by(ReadOrder::Directory, paths, size);
+        }
+        ReadOrder::Size => {
+            let sizes: Vec<Option<u64>> = (0..paths.len()).map(size).collect();
+            indices.sort_by_key(|&i| (sizes[i].is_none(), sizes[i]));
+        }
+        ReadOrder::Random => {
+            let mut rng = Rng(SEED);
+            for i in (1..indices.len()).rev() {
+                indices.swap(i, rng.below(i + 1));
+            }
+        }
+    }
+    indices
+}
+
+/// Each path's `(directory, entry)` position: directories numbered as `paths` first reach
+/// them, entries as `read_dir` returns them. Paths their directory no longer lists, or
+/// whose directory can't be listed, go last.
+fn listing_positions(paths: &[PathBuf]) -> Vec<(usize, usize)> {
+    let mut dirs: HashMap<&Path, (usize, HashMap<std::ffi::OsString, usize>)> = HashMap::new();
+    let mut positions = Vec::with_capacity(paths.len());
+    for path in paths {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        if !dirs.contains_key(dir) {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries.flatten().enumerate().map(|(i, entry)| (entry.file_name(), i)).collect(),
+                Err(_) => HashMap::new(),
+            };
+            let number = dirs.len();
+            dirs.insert(dir, (number, entries));
+        }
+        let (number, entries) = &dirs[dir];
+        match path.file_name().and_then(|name| entries.get(name)) {
+            Some(&entry) => positions.push((*number, entry)),
+            None => positions.push((usize::MAX, usize::MAX)),
+        }
+    }
+    positions
+}
+
+/// Rounds of every order; each order keeps its fastest, and the orders take turns within a
+/// round so drift in the device affects them alike.
+#[cfg(feature = "bench")]
+pub const ROUNDS: usize = 3;
+
+/// One order's fastest read of the whole data set.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct OrderRun {
+    pub order: ReadOrder,
+    pub elapsed: Duration,
+    pub bytes: u64,
+    /// Pages eviction left cached before the read; where it can't evict (tmpfs), every
+    /// order reads from memory and they differ little.
+    pub cached_pages: u64,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct OrderResult {
+    /// Every order's run, in [`ReadOrder::ALL`] order.
+    pub runs: Vec<OrderRun>,
+    pub failures: Vec<Failure>,
+}
+
+#[cfg(feature = "bench")]
+impl OrderResult {
+    /// The order that read the data set fastest.
+    pub fn best(&self) -> Option<&OrderRun> {
+        self.runs.iter().min_by_key(|run| run.elapsed)
+    }
+}
+
+/// Reads every file of `set` in each [`ReadOrder`], [`ROUNDS`] times, evicting it from the
+/// page cache before each read. The workers read concurrently, each taking a contiguous
+/// share of the order, so the device sees interleaved streams as it would from any batch
+/// read. Files that can't be read are recorded as failures.
+#[cfg(feature = "bench")]
+pub fn run(set: &DataSet, options: &Options) -> io::Result<OrderResult> {
+    let mut result = OrderResult::default();
+    options.failures.take("");
+    let mut arranged = Vec::new();
+    for order in ReadOrder::ALL {
+        let paths: Vec<PathBuf> = arrange_sized(order, set.paths(), set.sizes()).into_iter().map(|i| set.paths()[i].clone()).collect();
+        arranged.push(paths);
+    }
+    for round in 0..ROUNDS {
+        for (order, paths) in ReadOrder::ALL.into_iter().zip(&arranged) {
+            let mut failures = Vec::new();
+            let run = bench::phase(options, format!("Read order {} ({}/{})", order.name(), round + 1, ROUNDS), format!("order/{}", order.name()), order.name(), &mut failures, || {
+                let scope = options.op_scope.get();
+                let cached_pages = cache::evict_from_cache(paths)?.resident_pages as u64;
+                let bytes = AtomicU64::new(0);
+                let start = Instant::now();
+                bench::each_indexed(paths, options, |_, path| {
+                    let read = set.open(path).and_then(|mut file| buffers::with_buffer(|buf| io::Read::read_to_end(&mut file, buf)));
+                    match read {
+                        Ok(read) => {
+                            bytes.fetch_add(read as u64, Ordering::Relaxed);
+                        }
+                        Err(e) => options.failures.record(OpId::new(&scope, path), path, e.to_string()),
+                    }
+                    Ok(())
+                })?;
+                Ok(OrderRun { order, elapsed: start.elapsed(), bytes: bytes.into_inner(), cached_pages })
+            })?;
+            match result.runs.iter_mut().find(|fastest| fastest.order == order) {
+                Some(fastest) if fastest.elapsed <= run.elapsed => {}
+                Some(fastest) => *fastest = run,
+                None => result.runs.push(run),
+            }
+            // Every round fails on the same files; the first round's failures say it all.
+            if round == 0 {
+                result.failures.extend(failures);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Creates the workload's files in `dir_path`, reads them in every order with [`run`] and
+/// deletes them.
+#[cfg(feature = "bench")]
+pub fn run_workload(dir_path: &Path, options: &Options) -> io::Result<OrderResult> {
+    let paths = options.workload.paths(dir_path);
+    options.failures.take("");
+    let mut failures = Vec::new();
+    bench::phase(options, "Read order create", "order/create", "create", &mut failures, || bench::create_files(&paths, options))?;
+
+    let mut result = run(&DataSet::discover(dir_path)?, options)?;
+    failures.append(&mut result.failures);
+    bench::phase(options, "Read order delete", "order/delete", "delete", &mut failures, || bench::delete_files(&paths, options))?;
+    result.failures = failures;
+    Ok(result)
+}
@@ -0,0 +1,91 @@
+//! Fixed-rate load, for latency under load rather than throughput. Issuing operations as
+//! fast as the workers go hides queueing: when one stalls, the operations that would have
+//! arrived meanwhile are simply issued later, and the tail never shows the wait (coordinated
+//! omission). A [`Pacer`] instead gives every operation an intended start on a fixed
+//! schedule, shared by all workers, and latency is measured from that start, so an
+//! operation issued late because the ones before it backed up counts its wait.
+
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Slowest [`Rate`] accepted: one operation every 1000 seconds. Slower schedules would
+/// put intended starts past what an [`Instant`] can hold.
+pub const MIN_RATE: f64 = 0.001;
+
+/// Operations per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate(pub f64);
+
+impl Rate {
+    /// Time between intended starts, at most that of [`MIN_RATE`].
+    pub fn interval(self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.0.max(MIN_RATE))
+    }
+}
+
+impl std::str::FromStr for Rate {
+    type Err = io::Error;
+
+    /// `5000`, `5k`, `5k/s` or `2.5M/s`.
+    fn from_str(s: &str) -> io::Result<Rate> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is not a rate in operations per second", s));
+        let number = s.trim().strip_suffix("/s").unwrap_or(s.trim());
+        let (number, multiplier) = match number.strip_suffix(['k', 'K']) {
+            Some(number) => (number, 1e3),
+            None => match number.strip_suffix('M') {
+                Some(number) => (number, 1e6),
+                None => (number, 1.0),
+            },
+        };
+        let rate = number.parse::<f64>().map_err(|_| invalid())? * multiplier;
+        if rate.is_finite() && rate >= MIN_RATE {
+            Ok(Rate(rate))
+        } else if rate > 0.0 {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, format!("rate '{}' is below the slowest, {}/s", s, MIN_RATE)))
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// Hands out intended start times at a fixed [`Rate`] from when it was created; see the
+/// module docs. Make one per phase.
+#[derive(Debug)]
+pub struct Pacer {
+    start: Instant,
+    interval: Duration,
+    issued: AtomicU64,
+    /// Operations that began after their intended start, behind schedule.
+    late: AtomicU64,
+}
+
+impl Pacer {
+    pub fn new(rate: Rate) -> Pacer {
+        Pacer { start: Instant::now(), interval: rate.interval(), issued: AtomicU64::new(0), late: AtomicU64::new(0) }
+    }
+
+    /// Takes the next slot on the schedule and sleeps until it is due, returning its
+    /// intended start. A slot already past returns at once: the operation is behind.
+    pub fn next(&self) -> Instant {
+        let slot = self.issued.fetch_add(1, Ordering::Relaxed);
+        let intended = self.start + self.interval.mul_f64(slot as f64);
+        let now = Instant::now();
+        if intended > now {
+            thread::sleep(intended - now);
+        } else if now - intended > self.interval {
+            self.late.fetch_add(1, Ordering::Relaxed);
+        }
+        intended
+    }
+
+    pub fn issued(&self) -> u64 {
+        self.issued.load(Ordering::Relaxed)
+    }
+
+    /// Operations that started more than one interval after their intended start.
+    pub fn late(&self) -> u64 {
+        self.late.load(Ordering::Relaxed)
+    }
+}
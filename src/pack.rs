@@ -0,0 +1,292 @@
+//! A pack file: many small logical files kept as records of one append-only physical file,
+//! found through an in-memory index of offsets and lengths. For tiny-file workloads this
+//! trades one inode, directory entry and open per file for one `pwrite` or `pread` at a
+//! known offset, which is often the right answer when nothing outside the program needs to
+//! see the files.
+//!
+//! Each record is a 16-byte header (name length as a little-endian `u32`, flags as a `u32`
+//! whose lowest bit marks a deletion, data length as a `u64`), the name and the data. Writes
+//! never overwrite: [`Pack::put`] appends a new version and [`Pack::delete`] a tombstone, so
+//! the newest record of a name wins and the older ones are garbage until [`Pack::compact`]
+//! rewrites the file. Puts reserve their space with an atomic add and write in place, so
+//! any number of threads can append at once. [`Pack::open`] rebuilds the index by scanning
+//! the records, and cuts the file off at the first one a crash left incomplete.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::names;
+
+const HEADER: u64 = 16;
+const TOMBSTONE: u32 = 1;
+
+/// Where the newest record of a name starts, and its data unless it is a tombstone.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    at: u64,
+    data: Option<(u64, u64)>,
+}
+
+#[derive(Debug)]
+pub struct Pack {
+    path: PathBuf,
+    file: File,
+    index: RwLock<HashMap<String, Entry>>,
+    /// Where the next record goes.
+    end: AtomicU64,
+}
+
+impl Pack {
+    /// Creates an empty pack at `path`, replacing any file there.
+    pub fn create(path: impl Into<PathBuf>) -> io::Result<Pack> {
+        let path = path.into();
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+        Ok(Pack { path, file, index: RwLock::new(HashMap::new()), end: AtomicU64::new(0) })
+    }
+
+    /// Opens the pack at `path` and indexes its records.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Pack> {
+        let path = path.into();
+        let file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let (index, end) = scan(&file).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        if end < file.metadata()?.len() {
+            file.set_len(end)?;
+        }
+        Ok(Pack { path, file, index: RwLock::new(index), end: AtomicU64::new(end) })
+    }
+
+    /// Opens the pack at `path` for reading only, as a pack without write permission can
+    /// be. A record a crash left incomplete is ignored rather than cut off, and puts and
+    /// deletes fail.
+    pub fn open_read_only(path: impl Into<PathBuf>) -> io::Result<Pack> {
+        let path = path.into();
+        let file = File::open(&path)?;
+        let (index, end) = scan(&file).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        Ok(Pack { path, file, index: RwLock::new(index), end: AtomicU64::new(end) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The physical file, to sync or inspect.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Stores `bytes` as `name`, replacing what it held.
+    pub fn put(&self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        let at = self.append(name, 0, bytes)?;
+        let data = Some((at + HEADER + name.len() as u64, bytes.len() as u64));
+        self.index_record(name, Entry { at, data });
+        Ok(())
+    }
+
+    /// What `name` holds, if it exists.
+    pub fn get(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let mut buf = Vec::new();
+        Ok(self.get_into(name, &mut buf)?.then_some(buf))
+    }
+
+    /// Reads what `name` holds into `buf`, replacing its contents; false if it doesn't exist.
+    pub fn get_into(&self, name: &str, buf: &mut Vec<u8>) -> io::Result<bool> {
+        let Some((offset, len)) = self.read_index().get(name).and_then(|entry| entry.data) else {
+            return Ok(false);
+        };
+        buf.resize(len as usize, 0);
+        read_at(&self.file, buf, offset)?;
+        Ok(true)
+    }
+
+    /// Removes `name`; false if it didn't exist.
+    pub fn delete(&self, name: &str) -> io::Result<bool> {
+        if !self.contains(name) {
+            return Ok(false);
+        }
+        let at = self.append(name, TOMBSTONE, &[])?;
+        self.index_record(name, Entry { at, data: None });
+        Ok(true)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.read_index().get(name).is_some_and(|entry| entry.data.is_some())
+    }
+
+    /// The names stored, in no particular order.
+    pub fn names(&self) -> Vec<String> {
+        self.read_index().iter().filter(|(_, entry)| entry.data.is_some()).map(|(name, _)| name.clone()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read_index().values().filter(|entry| entry.data.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes of the file taken by replaced versions and tombstones, which [`Pack::compact`]
+    /// would reclaim.
+    pub fn garbage(&self) -> u64 {
+        let live: u64 = self.read_index().iter().filter_map(|(name, entry)| entry.data.map(|(_, len)| HEADER + name.len() as u64 + len)).sum();
+        self.end.load(Ordering::Acquire) - live
+    }
+
+    /// Flushes the records to the device.
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+
+    /// Rewrites the pack with only the newest version of each name, through a temporary
+    /// file renamed over it, and returns the bytes reclaimed.
+    pub fn compact(&mut self) -> io::Result<u64> {
+        let before = self.end.load(Ordering::Acquire);
+        let temporary = names::sibling(&self.path.with_extension(""), "", ".compacting");
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        let mut index = HashMap::new();
+        let (mut end, mut buf) = (0, Vec::new());
+        let mut live: Vec<(&String, &Entry)> = self.index.get_mut().unwrap_or_else(|e| e.into_inner()).iter().filter(|(_, entry)| entry.data.is_some()).collect();
+        // Keep the survivors in their old order, so reading them back stays sequential.
+        live.sort_by_key(|(_, entry)| entry.at);
+        for (name, entry) in live {
+            let Some((offset, len)) = entry.data else { continue };
+            buf.resize(len as usize, 0);
+            read_at(&self.file, &mut buf, offset)?;
+            writer.write_all(&record(name, 0, &buf)?)?;
+            index.insert(name.clone(), Entry { at: end, data: Some((end + HEADER + name.len() as u64, len)) });
+            end += HEADER + name.len() as u64 + len;
+        }
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temporary, &self.path)?;
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        *self.index.get_mut().unwrap_or_else(|e| e.into_inner()) = index;
+        *self.end.get_mut() = end;
+        Ok(before - end)
+    }
+
+    fn read_index(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Entry>> {
+        self.index.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Writes a record at the end of the file and returns where it starts.
+    fn append(&self, name: &str, flags: u32, data: &[u8]) -> io::Result<u64> {
+        let record = record(name, flags, data)?;
+        let at = self.end.fetch_add(record.len() as u64, Ordering::AcqRel);
+        write_at(&self.file, &record, at)?;
+        Ok(at)
+    }
+
+    /// Points `name` at the record at `entry.at`, unless a racing put or delete of the
+    /// same name wrote a later one.
+    fn index_record(&self, name: &str, entry: Entry) {
+        let mut index = self.index.write().unwrap_or_else(|e| e.into_inner());
+        match index.get_mut(name) {
+            Some(existing) if existing.at > entry.at => {}
+            Some(existing) => *existing = entry,
+            None => {
+                index.insert(name.to_string(), entry);
+            }
+        }
+    }
+}
+
+/// One record: header, name, data.
+fn record(name: &str, flags: u32, data: &[u8]) -> io::Result<Vec<u8>> {
+    let name_len = u32::try_from(name.len()).ok().filter(|&len| len > 0).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "pack names must be 1 to 4 GiB long"))?;
+    let mut record = Vec::with_capacity(HEADER as usize + name.len() + data.len());
+    record.extend_from_slice(&name_len.to_le_bytes());
+    record.extend_from_slice(&flags.to_le_bytes());
+    record.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    record.extend_from_slice(name.as_bytes());
+    record.extend_from_slice(data);
+    Ok(record)
+}
+
+/// Indexes the records of `file` and returns where the last complete one ends. A header
+/// of zeros is space a put reserved but never wrote; nothing after it is trusted.
+fn scan(file: &File) -> io::Result<(HashMap<String, Entry>, u64)> {
+    let len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    reader.rewind()?;
+    let mut index = HashMap::new();
+    let mut at = 0;
+    let mut header = [0; HEADER as usize];
+    while at + HEADER <= len {
+        reader.read_exact(&mut header)?;
+        let name_len = u32::from_le_bytes(header[..4].try_into().unwrap()) as u64;
+        let flags = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let data_len = u64::from_le_bytes(header[8..].try_into().unwrap());
+        let end = at.checked_add(HEADER + name_len).and_then(|end| end.checked_add(data_len));
+        let Some(end) = end.filter(|&end| name_len > 0 && end <= len) else {
+            break;
+        };
+        let mut name = vec![0; name_len as usize];
+        reader.read_exact(&mut name)?;
+        let Ok(name) = String::from_utf8(name) else {
+            break;
+        };
+        reader.seek_relative(data_len as i64)?;
+        let data = (flags & TOMBSTONE == 0).then_some((at + HEADER + name_len, data_len));
+        index.insert(name, Entry { at, data });
+        at = end;
+    }
+    Ok((index, at))
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, bytes: &[u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, bytes, offset)
+}
+
+#[cfg(unix)]
+pub(crate) fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn write_at(file: &File, mut bytes: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !bytes.is_empty() {
+        match file.seek_write(bytes, offset)? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            n => {
+                bytes = &bytes[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub(crate) fn read_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn write_at(_file: &File, _bytes: &[u8], _offset: u64) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "pack files need positioned writes"))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn read_at(_file: &File, _buf: &mut [u8], _offset: u64) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "pack files need positioned reads"))
+}
@@ -0,0 +1,299 @@
+//! A minimal Parquet writer, enough to hand per-file records to Polars, DuckDB or pandas.
+//!
+//! Files hold one row group with one data page per column, every column required (no
+//! nulls), values PLAIN-encoded and uncompressed. Metadata is Thrift's compact protocol,
+//! written by hand like [`crate::json`] so the export needs no dependencies; readers don't
+//! mind the missing statistics and compression.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8] = b"PAR1";
+
+// Thrift compact protocol type ids.
+const BOOLEAN_TRUE: u8 = 1;
+const BOOLEAN_FALSE: u8 = 2;
+const BYTE: u8 = 3;
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+// Parquet enums.
+const TYPE_INT32: i32 = 1;
+const TYPE_INT64: i32 = 2;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const REQUIRED: i32 = 0;
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_UINT_64: i32 = 14;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const UNCOMPRESSED: i32 = 0;
+const DATA_PAGE: i32 = 0;
+
+/// One column's values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Values {
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    /// Stored as INT64 annotated unsigned, for hashes and IDs.
+    UInt64(Vec<u64>),
+    /// Stored as BYTE_ARRAY annotated as strings.
+    Utf8(Vec<String>),
+}
+
+impl Values {
+    pub fn len(&self) -> usize {
+        match self {
+            Values::Int32(values) => values.len(),
+            Values::Int64(values) => values.len(),
+            Values::UInt64(values) => values.len(),
+            Values::Utf8(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn physical_type(&self) -> i32 {
+        match self {
+            Values::Int32(_) => TYPE_INT32,
+            Values::Int64(_) | Values::UInt64(_) => TYPE_INT64,
+            Values::Utf8(_) => TYPE_BYTE_ARRAY,
+        }
+    }
+
+    /// PLAIN encoding: little-endian numbers, and strings as a 4-byte length then bytes.
+    fn plain(&self) -> io::Result<Vec<u8>> {
+        Ok(match self {
+            Values::Int32(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Values::Int64(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Values::UInt64(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Values::Utf8(values) => {
+                let mut out = Vec::with_capacity(values.iter().map(|v| 4 + v.len()).sum());
+                for value in values {
+                    let len = u32::try_from(value.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("a {}-byte string is too long for Parquet", value.len())))?;
+                    out.extend_from_slice(&len.to_le_bytes());
+                    out.extend_from_slice(value.as_bytes());
+                }
+                out
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub values: Values,
+}
+
+impl Column {
+    pub fn new(name: impl Into<String>, values: Values) -> Column {
+        Column { name: name.into(), values }
+    }
+}
+
+/// Thrift compact protocol output. Field ids are written as deltas from the previous field
+/// of the same struct, so each open struct keeps its last id.
+#[derive(Default)]
+struct Compact {
+    out: Vec<u8>,
+    last: Vec<i16>,
+    current: i16,
+}
+
+impl Compact {
+    fn varint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.out.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        self.out.push(n as u8);
+    }
+
+    fn zigzag(&mut self, n: i64) {
+        self.varint(((n << 1) ^ (n >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, kind: u8) {
+        let delta = id - self.current;
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | kind);
+        } else {
+            self.out.push(kind);
+            self.zigzag(id as i64);
+        }
+        self.current = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, I32);
+        self.zigzag(value as i64);
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, I64);
+        self.zigzag(value);
+    }
+
+    fn byte(&mut self, id: i16, value: i8) {
+        self.field(id, BYTE);
+        self.out.push(value as u8);
+    }
+
+    fn bool(&mut self, id: i16, value: bool) {
+        self.field(id, if value { BOOLEAN_TRUE } else { BOOLEAN_FALSE });
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.out.extend_from_slice(value);
+    }
+
+    fn string(&mut self, id: i16, value: &str) {
+        self.field(id, BINARY);
+        self.bytes(value.as_bytes());
+    }
+
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | kind);
+        } else {
+            self.out.push(0xf0 | kind);
+            self.varint(len as u64);
+        }
+    }
+
+    /// Starts a struct, as field `id` of the enclosing one or, with `None`, as a list
+    /// element.
+    fn begin(&mut self, id: Option<i16>) {
+        if let Some(id) = id {
+            self.field(id, STRUCT);
+        }
+        self.last.push(self.current);
+        self.current = 0;
+    }
+
+    fn end(&mut self) {
+        self.out.push(0);
+        self.current = self.last.pop().unwrap_or(0);
+    }
+}
+
+/// A column's offset in the file and sizes, for its metadata.
+struct Chunk {
+    offset: usize,
+    size: usize,
+}
+
+/// Encodes `columns`, which must all have the same number of rows, as a Parquet file.
+pub fn encode(columns: &[Column]) -> io::Result<Vec<u8>> {
+    let rows = columns.first().map_or(0, |column| column.values.len());
+    if let Some(column) = columns.iter().find(|column| column.values.len() != rows) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("column {} has {} rows, expected {}", column.name, column.values.len(), rows)));
+    }
+    let too_big = |what: String| io::Error::new(io::ErrorKind::InvalidInput, format!("{} don't fit one data page", what));
+    let page_rows = i32::try_from(rows).map_err(|_| too_big(format!("{} rows", rows)))?;
+    let schema_len = i32::try_from(columns.len()).map_err(|_| too_big(format!("{} columns", columns.len())))?;
+    let mut out = MAGIC.to_vec();
+    let mut chunks = Vec::with_capacity(columns.len());
+    for column in columns {
+        let data = column.values.plain()?;
+        let page_len = i32::try_from(data.len()).map_err(|_| too_big(format!("column {}'s {} bytes", column.name, data.len())))?;
+        let mut header = Compact::default();
+        header.begin(None);
+        header.i32(1, DATA_PAGE);
+        header.i32(2, page_len);
+        header.i32(3, page_len);
+        header.begin(Some(5));
+        header.i32(1, page_rows);
+        header.i32(2, ENCODING_PLAIN);
+        header.i32(3, ENCODING_RLE);
+        header.i32(4, ENCODING_RLE);
+        header.end();
+        header.end();
+        let offset = out.len();
+        out.extend_from_slice(&header.out);
+        out.extend_from_slice(&data);
+        chunks.push(Chunk { offset, size: out.len() - offset });
+    }
+
+    let mut meta = Compact::default();
+    meta.begin(None);
+    meta.i32(1, 1);
+    meta.list(2, STRUCT, columns.len() + 1);
+    meta.begin(None);
+    meta.string(4, "schema");
+    meta.i32(5, schema_len);
+    meta.end();
+    for column in columns {
+        meta.begin(None);
+        meta.i32(1, column.values.physical_type());
+        meta.i32(3, REQUIRED);
+        meta.string(4, &column.name);
+        match column.values {
+            Values::Utf8(_) => {
+                meta.i32(6, CONVERTED_UTF8);
+                // LogicalType::STRING(StringType {}).
+                meta.begin(Some(10));
+                meta.begin(Some(1));
+                meta.end();
+                meta.end();
+            }
+            Values::UInt64(_) => {
+                meta.i32(6, CONVERTED_UINT_64);
+                // LogicalType::INTEGER(IntType { bitWidth: 64, isSigned: false }).
+                meta.begin(Some(10));
+                meta.begin(Some(10));
+                meta.byte(1, 64);
+                meta.bool(2, false);
+                meta.end();
+                meta.end();
+            }
+            Values::Int32(_) | Values::Int64(_) => {}
+        }
+        meta.end();
+    }
+    meta.i64(3, rows as i64);
+    meta.list(4, STRUCT, 1);
+    meta.begin(None);
+    meta.list(1, STRUCT, columns.len());
+    for (column, chunk) in columns.iter().zip(&chunks) {
+        meta.begin(None);
+        meta.i64(2, chunk.offset as i64);
+        meta.begin(Some(3));
+        meta.i32(1, column.values.physical_type());
+        meta.list(2, I32, 1);
+        meta.zigzag(ENCODING_PLAIN as i64);
+        meta.list(3, BINARY, 1);
+        meta.bytes(column.name.as_bytes());
+        meta.i32(4, UNCOMPRESSED);
+        meta.i64(5, rows as i64);
+        meta.i64(6, chunk.size as i64);
+        meta.i64(7, chunk.size as i64);
+        meta.i64(9, chunk.offset as i64);
+        meta.end();
+        meta.end();
+    }
+    meta.i64(2, chunks.iter().map(|chunk| chunk.size as i64).sum());
+    meta.i64(3, rows as i64);
+    meta.end();
+    meta.string(6, concat!("io version ", env!("CARGO_PKG_VERSION")));
+    meta.end();
+
+    out.extend_from_slice(&meta.out);
+    let footer_len = u32::try_from(meta.out.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "the file's metadata is over 4 GiB"))?;
+    out.extend_from_slice(&footer_len.to_le_bytes());
+    out.extend_from_slice(MAGIC);
+    Ok(out)
+}
+
+/// Writes `columns` to a Parquet file at `path`.
+pub fn write(path: &Path, columns: &[Column]) -> io::Result<()> {
+    fs::write(path, encode(columns)?)
+}
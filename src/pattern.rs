@@ -0,0 +1,162 @@
+//! Deterministic file contents from a seed and a file index, so runs are reproducible
+//! across machines and what was written can be checked afterwards.
+//!
+//! Contents are built in blocks ([`DEFAULT_BLOCK`] bytes unless set otherwise), each derived
+//! only from the seed, the file index and the block number, so any block can be regenerated
+//! without the ones before it. Two knobs shape how storage that compresses or deduplicates
+//! sees the data: [`Pattern::Compressible`] sets how far each block compresses, and
+//! [`Generator::dedupe`] makes a share of blocks repeat exactly, across and within files.
+//! Match the block size to the filesystem's record or extent size (`recordsize` on ZFS,
+//! 4K on btrfs) so duplicate blocks line up with what it deduplicates.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// The block size contents are generated in unless [`Generator::block`] says otherwise.
+pub const DEFAULT_BLOCK: usize = 4096;
+
+/// Distinct blocks that duplicated blocks are drawn from.
+const DEDUPE_POOL: u64 = 64;
+
+/// A ratio such as `4`, `4:1` or `2.5`, at least 1.
+pub fn parse_ratio(s: &str) -> Option<f64> {
+    let ratio: f64 = s.strip_suffix(":1").unwrap_or(s).parse().ok()?;
+    (ratio >= 1.0).then_some(ratio)
+}
+
+/// What the bytes of a generated file look like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// All zeros.
+    Zero,
+    /// Incompressible pseudo-random bytes.
+    Random,
+    /// Random bytes for `1 / ratio` of every block and zeros for the rest, so a compressor
+    /// shrinks it by about `ratio`.
+    Compressible(f64),
+}
+
+impl FromStr for Pattern {
+    type Err = io::Error;
+
+    /// `zero`, `random`, or `compressible:<ratio>` such as `compressible:4`.
+    fn from_str(s: &str) -> io::Result<Pattern> {
+        let invalid = || {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("unknown pattern '{}', expected zero|random|compressible:<ratio>", s))
+        };
+        match s.split_once(':') {
+            None if s == "zero" => Ok(Pattern::Zero),
+            None if s == "random" => Ok(Pattern::Random),
+            Some(("compressible", ratio)) => parse_ratio(ratio).map(Pattern::Compressible).ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Zero => write!(f, "zero"),
+            Pattern::Random => write!(f, "random"),
+            Pattern::Compressible(ratio) => write!(f, "compressible:{}", ratio),
+        }
+    }
+}
+
+/// The step splitmix64 adds to its state between outputs.
+pub(crate) const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// splitmix64, a fast generator whose every output depends on all bits of its state.
+pub(crate) fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(GAMMA);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Per-file contents for a seed and pattern.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Generator {
+    pub seed: u64,
+    pub pattern: Pattern,
+    /// Target deduplication ratio: all but `1 / dedupe` of the blocks are copies of a small
+    /// shared set. 1 keeps every block unique.
+    pub dedupe: f64,
+    /// Bytes per generated block, the unit of both compressibility and deduplication.
+    pub block: usize,
+}
+
+impl Generator {
+    pub fn new(seed: u64, pattern: Pattern) -> Generator {
+        Generator { seed, pattern, dedupe: 1.0, block: DEFAULT_BLOCK }
+    }
+
+    /// A generator for a second version of every file, e.g. what an update writes.
+    pub fn next_version(&self) -> Generator {
+        Generator { seed: mix(self.seed ^ 0x7570_6461_7465), ..*self }
+    }
+
+    /// What block `number` of file `index` is generated from: its own key, or with
+    /// deduplication, often the key of one of the shared blocks.
+    fn block_key(&self, index: u64, number: u64) -> u64 {
+        let key = mix(mix(self.seed ^ mix(index)) ^ number);
+        if self.dedupe > 1.0 {
+            let draw = mix(key ^ 0x6465_6475_7065);
+            // The top 53 bits as a uniform fraction in [0, 1).
+            if (draw >> 11) as f64 / (1u64 << 53) as f64 >= 1.0 / self.dedupe {
+                return mix(self.seed ^ mix(draw % DEDUPE_POOL));
+            }
+        }
+        key
+    }
+
+    /// Fills `block`, block number `number` of file `index`.
+    fn fill_block(&self, index: u64, number: u64, block: &mut [u8]) {
+        let random = match self.pattern {
+            Pattern::Zero => 0,
+            Pattern::Random => block.len(),
+            Pattern::Compressible(ratio) => ((self.block as f64 / ratio).ceil() as usize).min(block.len()),
+        };
+        let mut state = self.block_key(index, number);
+        for chunk in block[..random].chunks_mut(8) {
+            state = mix(state);
+            chunk.copy_from_slice(&state.to_le_bytes()[..chunk.len()]);
+        }
+        block[random..].fill(0);
+    }
+
+    /// Fills `buf` with the first `buf.len()` bytes of file `index`.
+    pub fn fill(&self, index: u64, buf: &mut [u8]) {
+        for (number, block) in buf.chunks_mut(self.block).enumerate() {
+            self.fill_block(index, number as u64, block);
+        }
+    }
+
+    /// The first `len` bytes of file `index`.
+    pub fn contents(&self, index: u64, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        self.fill(index, &mut buf);
+        buf
+    }
+
+    /// Whether `bytes` are exactly the contents of file `index`, checked a block at a time.
+    pub fn matches(&self, index: u64, bytes: &[u8]) -> bool {
+        let mut expected = vec![0; self.block.min(bytes.len())];
+        bytes.chunks(self.block).enumerate().all(|(number, block)| {
+            let expected = &mut expected[..block.len()];
+            self.fill_block(index, number as u64, expected);
+            expected == block
+        })
+    }
+}
+
+impl fmt::Display for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} contents from seed {}", self.pattern, self.seed)?;
+        if self.dedupe > 1.0 {
+            write!(f, ", dedupe {}:1", self.dedupe)?;
+        }
+        write!(f, " in {}-byte blocks", self.block)
+    }
+}
@@ -0,0 +1,180 @@
+//! Permissions and ownership in bulk, for deployment tools that fix up the mode and owner
+//! of thousands of files they just extracted.
+//!
+//! [`chmod_many`] and [`chown_many`] go by path, resolving every path again. A tool that
+//! still holds the files open, as an extractor does while writing them, can skip that
+//! with [`fchmod_many`] and [`fchown_many`], which change the open descriptors instead.
+//! Changing the owner to anyone but yourself needs privileges; an owner or group of
+//! `None` is left as it is.
+
+use std::borrow::Borrow;
+use std::fs::{self, File, Permissions};
+use std::io;
+use std::os::unix::fs::{self as unix_fs, MetadataExt, PermissionsExt};
+use std::path::Path;
+#[cfg(feature = "bench")]
+use std::fs::OpenOptions;
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+use crate::contents;
+use crate::retry::RetryPolicy;
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+
+/// Sets the permission bits of every file in `paths` to `mode`, in parallel with the
+/// `rayon` feature, retrying each on transient errors as `policy` says. Fails on the first
+/// file that still can't be changed.
+pub fn chmod_many<P>(paths: &[P], mode: u32, policy: &RetryPolicy) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+{
+    contents::each_retried(paths, policy, |path| path.as_ref(), |path| fs::set_permissions(path, Permissions::from_mode(mode))).map(drop)
+}
+
+/// Gives every file in `paths` owner `uid` and group `gid`, following symlinks, in
+/// parallel and retried like [`chmod_many`].
+pub fn chown_many<P>(paths: &[P], uid: Option<u32>, gid: Option<u32>, policy: &RetryPolicy) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+{
+    contents::each_retried(paths, policy, |path| path.as_ref(), |path| unix_fs::chown(path, uid, gid)).map(drop)
+}
+
+/// [`chmod_many`] on files already open: each `(path, file)` pair has its descriptor's
+/// permission bits set with `fchmod`. The path only names the file in errors.
+pub fn fchmod_many<P, F>(files: &[(P, F)], mode: u32, policy: &RetryPolicy) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+    F: Borrow<File> + Sync,
+{
+    contents::each_retried(files, policy, |(path, _)| path.as_ref(), |(_, file)| fchmod(file.borrow(), mode)).map(drop)
+}
+
+/// [`chown_many`] on files already open, with `fchown`.
+pub fn fchown_many<P, F>(files: &[(P, F)], uid: Option<u32>, gid: Option<u32>, policy: &RetryPolicy) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+    F: Borrow<File> + Sync,
+{
+    contents::each_retried(files, policy, |(path, _)| path.as_ref(), |(_, file)| unix_fs::fchown(file.borrow(), uid, gid)).map(drop)
+}
+
+/// `File::set_permissions` is `fchmod`.
+fn fchmod(file: &File, mode: u32) -> io::Result<()> {
+    file.set_permissions(Permissions::from_mode(mode))
+}
+
+/// The permission bits of `path`, without the file type.
+pub fn mode(path: &Path) -> io::Result<u32> {
+    Ok(fs::metadata(path)?.mode() & 0o7777)
+}
+
+/// Mode the benchmark sets, as an extractor restoring an archive's read-only files would.
+#[cfg(feature = "bench")]
+pub const BENCH_MODE: u32 = 0o444;
+
+/// Files the descriptor method holds open at once, at most; it goes through the workload
+/// in batches of this many, or of the open-file budget when that is smaller.
+#[cfg(feature = "bench")]
+const BATCH: usize = 4096;
+
+/// How the benchmark reaches each file.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// `chmod` and `chown` on the path.
+    Path,
+    /// `fchmod` and `fchown` on a descriptor opened beforehand.
+    Descriptor,
+}
+
+#[cfg(feature = "bench")]
+impl Method {
+    pub const ALL: [Method; 2] = [Method::Path, Method::Descriptor];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Path => "path",
+            Method::Descriptor => "descriptor",
+        }
+    }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct MethodResult {
+    pub method: Method,
+    /// Setting every file's mode.
+    pub chmod: Duration,
+    /// Setting every file's owner and group, to the ones it already has.
+    pub chown: Duration,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct PermissionsResult {
+    pub methods: Vec<MethodResult>,
+    pub failures: Vec<Failure>,
+}
+
+/// Creates the configured workload in `dir_path` untimed, then for each method times
+/// setting every file's mode and then its owner. Ownership is set to the user and group
+/// the files were created with, which needs no privileges but still does the work. The
+/// descriptor method opens its files before the clock starts, as a tool that just wrote
+/// them has them open, a batch at a time. A file whose mode didn't change is an error.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<PermissionsResult> {
+    let paths = options.workload.paths(dir_path);
+    let mut result = PermissionsResult::default();
+    options.failures.take("");
+    bench::phase(options, "Permissions create", "permissions/create", "create", &mut result.failures, || {
+        bench::each_indexed(&paths, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+    })?;
+    let Some(first) = paths.first() else {
+        return Ok(result);
+    };
+    let metadata = fs::metadata(first)?;
+    let (uid, gid) = (Some(metadata.uid()), Some(metadata.gid()));
+
+    for (round, method) in Method::ALL.into_iter().enumerate() {
+        // Each method changes the mode away from what the last one left.
+        let mode = if round % 2 == 0 { BENCH_MODE } else { BENCH_MODE | 0o200 };
+        let batch = options.open_files.max().clamp(1, BATCH);
+        let (mut chmod, mut chown) = (Duration::ZERO, Duration::ZERO);
+        for batch in paths.chunks(if method == Method::Path { paths.len() } else { batch }) {
+            let files = match method {
+                Method::Path => Vec::new(),
+                Method::Descriptor => batch.iter().map(|path| OpenOptions::new().read(true).open(path)).collect::<io::Result<Vec<File>>>()?,
+            };
+            let start = Instant::now();
+            bench::phase(options, format!("Permissions {} chmod", method.name()), format!("permissions/{}/chmod", method.name()), "chmod", &mut result.failures, || {
+                bench::each_indexed(batch, options, |index, path| match method {
+                    Method::Path => fs::set_permissions(path, Permissions::from_mode(mode)),
+                    Method::Descriptor => fchmod(&files[index], mode),
+                })
+            })?;
+            chmod += start.elapsed();
+
+            let start = Instant::now();
+            bench::phase(options, format!("Permissions {} chown", method.name()), format!("permissions/{}/chown", method.name()), "chown", &mut result.failures, || {
+                bench::each_indexed(batch, options, |index, path| match method {
+                    Method::Path => unix_fs::chown(path, uid, gid),
+                    Method::Descriptor => unix_fs::fchown(&files[index], uid, gid),
+                })
+            })?;
+            chown += start.elapsed();
+        }
+
+        bench::phase(options, format!("Permissions {} check", method.name()), format!("permissions/{}/check", method.name()), "check", &mut result.failures, || {
+            bench::each_file(&paths, options, |path| match self::mode(path)? {
+                found if found == mode => Ok(()),
+                found => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has mode {:o} after setting {:o}", path.display(), found, mode))),
+            })
+        })?;
+        result.methods.push(MethodResult { method, chmod, chown });
+    }
+    // Leave the files writable so the directory can be removed everywhere.
+    chmod_many(&paths, 0o644, &RetryPolicy::default())?;
+    Ok(result)
+}
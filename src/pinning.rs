@@ -0,0 +1,185 @@
+//! CPU affinity for worker threads, and NUMA placement of workers and the memory they
+//! allocate.
+//!
+//! On a multi-socket machine a worker reading into a buffer on the other socket's memory
+//! pays for every byte crossing the interconnect. [`NumaPlacement`] keeps workers and
+//! their buffers on one node, or spreads both evenly over all of them. Buffers come from
+//! the per-thread pools in [`crate::buffers`], which a worker allocates itself, so binding
+//! a worker's memory policy places its buffers too.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Where the NUMA nodes are described on Linux.
+const NODE_DIR: &str = "/sys/devices/system/node";
+
+/// Pins the calling thread to `core_id`. A no-op on platforms without `sched_setaffinity`;
+/// fails on Android and iOS, where apps can't choose their CPUs (see [`crate::mobile`]).
+pub fn pin_thread(core_id: usize) -> io::Result<()> {
+    if crate::mobile::MOBILE {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "apps can't set their threads' CPU affinity on Android or iOS"));
+    }
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        use libc::{CPU_SET, CPU_SETSIZE, cpu_set_t, sched_setaffinity};
+
+        if core_id >= CPU_SETSIZE as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("CPU {} is beyond the affinity mask", core_id)));
+        }
+        unsafe {
+            let mut cpu_set: cpu_set_t = std::mem::zeroed();
+            CPU_SET(core_id, &mut cpu_set);
+            let result = sched_setaffinity(0, std::mem::size_of::<cpu_set_t>(), &cpu_set);
+            if result != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    let _ = core_id;
+    Ok(())
+}
+
+/// The CPU the calling thread is running on at this moment, where the platform says.
+/// For a pinned thread that is the CPU it was pinned to.
+pub fn current_cpu() -> Option<usize> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        let cpu = unsafe { libc::sched_getcpu() };
+        if cpu >= 0 {
+            return Some(cpu as usize);
+        }
+    }
+    None
+}
+
+/// One NUMA node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumaNode {
+    pub id: usize,
+    pub cpus: Vec<usize>,
+    /// Memory attached to the node, where the kernel reports it.
+    pub memory: Option<u64>,
+}
+
+/// Parses a kernel CPU or node list such as `0-3,8,10-11`.
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',').filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => cpus.extend(start.parse::<usize>().ok()?..=end.parse().ok()?),
+            None => cpus.push(part.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+/// The machine's NUMA nodes in id order, from sysfs. Empty where the kernel doesn't
+/// describe any, which callers treat like a single node.
+pub fn numa_nodes() -> Vec<NumaNode> {
+    let Ok(entries) = fs::read_dir(NODE_DIR) else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<NumaNode> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let id = entry.file_name().to_str()?.strip_prefix("node")?.parse().ok()?;
+            let cpus = parse_cpu_list(&fs::read_to_string(entry.path().join("cpulist")).ok()?)?;
+            Some(NumaNode { id, cpus, memory: node_memory(&entry.path()) })
+        })
+        .collect();
+    nodes.sort_by_key(|node| node.id);
+    nodes
+}
+
+/// `MemTotal` from a node's `meminfo`, which reads `Node 0 MemTotal:  16318480 kB`.
+fn node_memory(node: &Path) -> Option<u64> {
+    let meminfo = fs::read_to_string(node.join("meminfo")).ok()?;
+    let line = meminfo.lines().find(|line| line.contains("MemTotal:"))?;
+    let kib: u64 = line.split_whitespace().rev().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// How workers and their memory are laid out over NUMA nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumaPlacement {
+    /// Every worker on this node's CPUs, allocating from its memory.
+    Node(usize),
+    /// Workers dealt over the nodes in turn, memory interleaved page by page over all of
+    /// them.
+    Interleave,
+}
+
+impl FromStr for NumaPlacement {
+    type Err = io::Error;
+
+    /// `interleave`, or a node id such as `1` or `node1`.
+    fn from_str(s: &str) -> io::Result<NumaPlacement> {
+        if s == "interleave" {
+            return Ok(NumaPlacement::Interleave);
+        }
+        s.strip_prefix("node").unwrap_or(s).parse().map(NumaPlacement::Node).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("unknown NUMA placement '{}', expected a node id or interleave", s))
+        })
+    }
+}
+
+/// The CPUs workers are pinned to, worker `i` taking entry `i % len`: `allowed` as it is
+/// without a placement, only the chosen node's, or alternating between nodes when
+/// interleaving. Fails if the chosen node has none of `allowed`.
+pub fn worker_cpus(placement: Option<NumaPlacement>, nodes: &[NumaNode], allowed: &[usize]) -> io::Result<Vec<usize>> {
+    let on_node = |node: &NumaNode| -> Vec<usize> { node.cpus.iter().copied().filter(|cpu| allowed.contains(cpu)).collect() };
+    match placement {
+        None => Ok(allowed.to_vec()),
+        Some(NumaPlacement::Node(id)) => {
+            let node = nodes.iter().find(|node| node.id == id).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("no NUMA node {}", id)))?;
+            let cpus = on_node(node);
+            if cpus.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("NUMA node {} has no CPUs this process may run on", id)));
+            }
+            Ok(cpus)
+        }
+        Some(NumaPlacement::Interleave) => {
+            let per_node: Vec<Vec<usize>> = nodes.iter().map(on_node).filter(|cpus| !cpus.is_empty()).collect();
+            let deepest = per_node.iter().map(Vec::len).max().unwrap_or(0);
+            let cpus: Vec<usize> = (0..deepest).flat_map(|i| per_node.iter().filter_map(move |cpus| cpus.get(i).copied())).collect();
+            Ok(if cpus.is_empty() { allowed.to_vec() } else { cpus })
+        }
+    }
+}
+
+/// Sets the calling thread's memory policy so what it allocates from now on comes from
+/// the chosen node, or is interleaved over `nodes`. A no-op without `set_mempolicy`.
+pub fn bind_memory(placement: NumaPlacement, nodes: &[NumaNode]) -> io::Result<()> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        const MPOL_BIND: libc::c_long = 2;
+        const MPOL_INTERLEAVE: libc::c_long = 3;
+        const MASK_BITS: usize = 1024;
+
+        let (mode, ids): (_, Vec<usize>) = match placement {
+            NumaPlacement::Node(id) => (MPOL_BIND, vec![id]),
+            NumaPlacement::Interleave => (MPOL_INTERLEAVE, nodes.iter().filter(|node| node.memory != Some(0)).map(|node| node.id).collect()),
+        };
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let mut mask = [0 as libc::c_ulong; MASK_BITS / libc::c_ulong::BITS as usize];
+        for id in ids {
+            if id >= MASK_BITS {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("NUMA node {} is beyond the node mask", id)));
+            }
+            mask[id / libc::c_ulong::BITS as usize] |= 1 << (id % libc::c_ulong::BITS as usize);
+        }
+        // maxnode counts one more than the bits the kernel reads.
+        let result = unsafe { libc::syscall(libc::SYS_set_mempolicy, mode, mask.as_ptr(), MASK_BITS + 1) };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    let _ = (placement, nodes);
+    Ok(())
+}
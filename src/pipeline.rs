@@ -0,0 +1,138 @@
+//! The create phase as a pipeline: a pool of open threads creates the files and hands the
+//! descriptors over a channel to the workers, which only write. With small files `open(2)`,
+//! which takes the directory lock and allocates the inode, costs more than the write; here
+//! the workers never wait on it while files are already open, and more threads can sit in
+//! `open(2)` than there are workers.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::bench::{self, Failure, Options};
+use crate::cancel;
+use crate::dirs::{self, Access};
+use crate::fdlimit::{self, OpenFilePermit};
+
+/// Open threads when none are asked for.
+pub const DEFAULT_OPEN_THREADS: usize = 2;
+
+/// A created file on its way to a writer, holding its place in the open-file budget.
+type Opened<'a> = (usize, File, OpenFilePermit<'a>);
+
+/// Opens files for the writers, claiming the next index until every file is claimed or a
+/// thread failed.
+fn open_files<'a>(paths: &[PathBuf], options: &'a Options, dirs: Option<&dirs::Dirs>, next: &AtomicUsize, failed: &AtomicBool, opened: SyncSender<Opened<'a>>) -> io::Result<()> {
+    loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        if index >= paths.len() || failed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        options.pause.wait();
+        if options.cancel.is_cancelled() {
+            return Err(cancel::cancelled());
+        }
+        let permit = options.open_files.acquire();
+        let file = dirs::open_file(dirs, &paths[index], Access::Create).map_err(|e| fdlimit::explain(e, &options.open_files))?;
+        if opened.send((index, file, permit)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Writes what arrives on `opened` until the open threads are done, returning how long it
+/// waited for files and the first error. After an error it keeps taking files, dropping
+/// them, so the open threads never block on a full channel.
+fn write_files(options: &Options, failed: &AtomicBool, done: &AtomicUsize, opened: &Mutex<Receiver<Opened<'_>>>) -> (Duration, io::Result<()>) {
+    let (mut starved, mut result) = (Duration::ZERO, Ok(()));
+    loop {
+        let start = Instant::now();
+        let next = opened.lock().unwrap_or_else(|e| e.into_inner()).recv();
+        starved += start.elapsed();
+        let Ok((index, file, _permit)) = next else {
+            return (starved, result);
+        };
+        if result.is_err() {
+            continue;
+        }
+        let mut writer = BufWriter::new(file);
+        let written = bench::with_content(options, index, false, |content| writer.write_all(content))
+            .and_then(|()| writer.flush())
+            .and_then(|()| options.sync.sync(writer.get_ref()));
+        done.fetch_add(1, Ordering::Relaxed);
+        options.progress.advance();
+        if let Err(e) = written {
+            failed.store(true, Ordering::Relaxed);
+            result = Err(e);
+        }
+    }
+}
+
+/// Creates and writes every file in `paths` like the create phase, with `open_threads`
+/// threads opening them and the current pool's workers writing. Up to
+/// [`Options::queue_depth`] opened files wait for a writer. Returns the time the workers
+/// spent waiting for files, summed over them; the first error stops the opens.
+pub fn create_files_pipelined(paths: &[PathBuf], options: &Options, open_threads: usize) -> io::Result<Duration> {
+    let dirs = bench::open_dirs(paths, options)?;
+    let (next, failed, done) = (AtomicUsize::new(0), AtomicBool::new(false), AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::sync_channel(options.queue_depth() as usize);
+    options.progress.begin(paths.len());
+    let (written, opened) = thread::scope(|scope| {
+        // Dropped before the scope joins the open threads should a writer panic, so none is
+        // left blocked on a full channel.
+        let receiver = Mutex::new(receiver);
+        let openers: Vec<_> = (0..open_threads.max(1))
+            .map(|_| {
+                let sender = sender.clone();
+                let (dirs, next, failed) = (dirs.as_ref(), &next, &failed);
+                scope.spawn(move || open_files(paths, options, dirs, next, failed, sender).inspect_err(|_| failed.store(true, Ordering::Relaxed)))
+            })
+            .collect();
+        drop(sender);
+        let written = rayon::broadcast(|_| write_files(options, &failed, &done, &receiver));
+        let opened = openers.into_iter().try_for_each(|opener| opener.join().unwrap_or_else(|_| Err(io::Error::other("an open thread panicked"))));
+        (written, opened)
+    });
+    // Files skipped after an error still count as done, as in every other phase.
+    options.progress.skip(paths.len() - done.into_inner());
+    opened?;
+    let starved = written.iter().map(|(starved, _)| *starved).sum();
+    written.into_iter().try_for_each(|(_, result)| result)?;
+    Ok(starved)
+}
+
+#[derive(Debug, Clone)]
+pub struct PipelineResult {
+    pub open_threads: usize,
+    /// Opening and writing each file in the same task, as the create phase does.
+    pub per_task: Duration,
+    /// Opening in the open threads and writing in the workers.
+    pub pipelined: Duration,
+    /// How long the workers waited for opened files, summed over them.
+    pub starved: Duration,
+    pub failures: Vec<Failure>,
+}
+
+/// Times creating the configured workload in `dir_path` with the create phase, then, after
+/// deleting it untimed, with [`create_files_pipelined`] and `open_threads` open threads.
+pub fn run(dir_path: &Path, open_threads: usize, options: &Options) -> io::Result<PipelineResult> {
+    let paths = options.workload.paths(dir_path);
+    let mut failures = Vec::new();
+    options.failures.take("");
+    let start = Instant::now();
+    bench::phase(options, "Pipeline per-task create", "pipeline/per-task", "create", &mut failures, || bench::create_files(&paths, options))?;
+    let per_task = start.elapsed();
+
+    bench::phase(options, "Pipeline delete", "pipeline/delete", "delete", &mut failures, || bench::delete_files(&paths, options))?;
+
+    options.progress.set_stage("Pipeline pipelined create");
+    options.op_scope.set("pipeline/pipelined");
+    let start = Instant::now();
+    let starved = create_files_pipelined(&paths, options, open_threads)?;
+    let pipelined = start.elapsed();
+    Ok(PipelineResult { open_threads: open_threads.max(1), per_task, pipelined, starved, failures })
+}
@@ -0,0 +1,233 @@
+//! Dry runs of the bulk calls: what [`contents::write_many`](crate::contents::write_many)
+//! and [`contents::delete_many`](crate::contents::delete_many) would do with the same
+//! arguments, worked out without touching the filesystem, so a tool can show the user the
+//! plan and ask before overwriting or deleting ten thousand files.
+//!
+//! Planning checks what would make the real call fail halfway or do something unintended:
+//! files to delete that are missing or are directories, files to write whose directory
+//! doesn't exist, the same file named twice (also through different spellings of its
+//! directory), and filesystems without room for what would be written. Everything found
+//! is listed, not just the first. The filesystem can still change between the plan and
+//! the call.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::filesystem::Filesystem;
+use crate::human;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    /// Replace the contents of a file that exists.
+    Overwrite,
+    Delete,
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Create => "create",
+            Action::Overwrite => "overwrite",
+            Action::Delete => "delete",
+        }
+    }
+}
+
+/// What would happen to one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub path: PathBuf,
+    pub action: Action,
+    /// Bytes written, or for a delete the bytes freed.
+    pub bytes: u64,
+    /// Size of the file the step replaces or deletes.
+    pub existing: Option<u64>,
+}
+
+/// Something that would make the bulk call fail or misbehave.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A file to delete doesn't exist.
+    Missing(PathBuf),
+    /// A file to write is in a directory that doesn't exist.
+    MissingParent(PathBuf),
+    /// The path is a directory (or other non-file) the call would fail on.
+    NotAFile(PathBuf),
+    /// The path names a file an earlier one already did.
+    Duplicate(PathBuf),
+    /// The filesystem mounted at `mount_point` has less free than the plan writes there.
+    NoSpace { mount_point: PathBuf, needed: u64, available: u64 },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Missing(path) => write!(f, "{} doesn't exist", path.display()),
+            Problem::MissingParent(path) => write!(f, "the directory of {} doesn't exist", path.display()),
+            Problem::NotAFile(path) => write!(f, "{} isn't a regular file", path.display()),
+            Problem::Duplicate(path) => write!(f, "{} is named more than once", path.display()),
+            Problem::NoSpace { mount_point, needed, available } => {
+                write!(f, "{} needs {} more but has {} free", mount_point.display(), human::bytes(*needed), human::bytes(*available))
+            }
+        }
+    }
+}
+
+/// What a bulk call would do, in the order it was given, and what stands in its way.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+    pub problems: Vec<Problem>,
+}
+
+impl Plan {
+    /// Whether the call can go ahead as planned.
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn count(&self, action: Action) -> usize {
+        self.steps.iter().filter(|step| step.action == action).count()
+    }
+
+    /// Bytes the call writes.
+    pub fn bytes_written(&self) -> u64 {
+        self.steps.iter().filter(|step| step.action != Action::Delete).map(|step| step.bytes).sum()
+    }
+
+    /// Bytes the call removes, overwritten contents included.
+    pub fn bytes_removed(&self) -> u64 {
+        self.steps.iter().filter_map(|step| step.existing).sum()
+    }
+
+    /// The plan, or an [`io::ErrorKind::InvalidInput`] error listing its problems.
+    pub fn check(self) -> io::Result<Plan> {
+        if self.is_clean() {
+            return Ok(self);
+        }
+        let shown: Vec<String> = self.problems.iter().take(5).map(Problem::to_string).collect();
+        let more = if self.problems.len() > shown.len() { format!(" and {} more", self.problems.len() - shown.len()) } else { String::new() };
+        Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} problems: {}{}", self.problems.len(), shown.join("; "), more)))
+    }
+}
+
+impl fmt::Display for Plan {
+    /// A summary line per action, then every problem.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for action in [Action::Create, Action::Overwrite, Action::Delete] {
+            let steps: Vec<&Step> = self.steps.iter().filter(|step| step.action == action).collect();
+            if steps.is_empty() {
+                continue;
+            }
+            let bytes: u64 = steps.iter().map(|step| if action == Action::Delete { step.existing.unwrap_or(0) } else { step.bytes }).sum();
+            writeln!(f, "{} {} files ({})", action.name(), human::thousands(steps.len() as u64), human::bytes(bytes))?;
+        }
+        for problem in &self.problems {
+            writeln!(f, "problem: {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// The plan for [`contents::write_many`](crate::contents::write_many) with `files`.
+pub fn plan_write<P, B>(files: &[(P, B)]) -> Plan
+where
+    P: AsRef<Path>,
+    B: AsRef<[u8]>,
+{
+    let mut plan = Plan::default();
+    let mut seen = Seen::default();
+    // Growth per filesystem, by mount point, with its free space.
+    let mut growth: HashMap<PathBuf, (i128, Option<u64>)> = HashMap::new();
+    let mut mounts: HashMap<PathBuf, Option<Filesystem>> = HashMap::new();
+    for (path, contents) in files {
+        let path = path.as_ref();
+        let bytes = contents.as_ref().len() as u64;
+        let parent = parent(path);
+        if !parent.is_dir() {
+            plan.problems.push(Problem::MissingParent(path.to_path_buf()));
+            continue;
+        }
+        if !seen.insert(path) {
+            plan.problems.push(Problem::Duplicate(path.to_path_buf()));
+            continue;
+        }
+        let existing = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => Some(metadata.len()),
+            Ok(_) => {
+                plan.problems.push(Problem::NotAFile(path.to_path_buf()));
+                continue;
+            }
+            Err(_) => None,
+        };
+        if let Some(filesystem) = mounts.entry(parent.to_path_buf()).or_insert_with(|| Filesystem::of(parent)) {
+            let (grows, _) = growth.entry(filesystem.mount_point.clone()).or_insert((0, filesystem.available));
+            *grows += bytes as i128 - existing.unwrap_or(0) as i128;
+        }
+        let action = if existing.is_some() { Action::Overwrite } else { Action::Create };
+        plan.steps.push(Step { path: path.to_path_buf(), action, bytes, existing });
+    }
+    let mut full: Vec<Problem> = growth
+        .into_iter()
+        .filter_map(|(mount_point, (grows, available))| {
+            let (needed, available) = (u64::try_from(grows).ok()?, available?);
+            (needed > available).then_some(Problem::NoSpace { mount_point, needed, available })
+        })
+        .collect();
+    full.sort_by_key(Problem::to_string);
+    plan.problems.extend(full);
+    plan
+}
+
+/// The plan for [`contents::delete_many`](crate::contents::delete_many) with `paths`.
+pub fn plan_delete<P>(paths: &[P]) -> Plan
+where
+    P: AsRef<Path>,
+{
+    let mut plan = Plan::default();
+    let mut seen = Seen::default();
+    for path in paths {
+        let path = path.as_ref();
+        // Not following a symlink: deleting one removes the link.
+        match fs::symlink_metadata(path) {
+            Err(_) => plan.problems.push(Problem::Missing(path.to_path_buf())),
+            Ok(metadata) if metadata.is_dir() => plan.problems.push(Problem::NotAFile(path.to_path_buf())),
+            Ok(_) if !seen.insert(path) => plan.problems.push(Problem::Duplicate(path.to_path_buf())),
+            Ok(metadata) => plan.steps.push(Step { path: path.to_path_buf(), action: Action::Delete, bytes: metadata.len(), existing: Some(metadata.len()) }),
+        }
+    }
+    plan
+}
+
+fn parent(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Files named so far, by their directory's canonical path and their name, so `a/x` and
+/// `a/../a/x` are the same file.
+#[derive(Default)]
+struct Seen {
+    files: HashSet<PathBuf>,
+    /// Canonical directories, looked up once each.
+    dirs: HashMap<PathBuf, PathBuf>,
+}
+
+impl Seen {
+    /// Whether `path` is new.
+    fn insert(&mut self, path: &Path) -> bool {
+        let parent = parent(path);
+        let dir = self.dirs.entry(parent.to_path_buf()).or_insert_with(|| fs::canonicalize(parent).unwrap_or_else(|_| parent.to_path_buf()));
+        let key = match path.file_name() {
+            Some(name) => dir.join(name),
+            None => path.to_path_buf(),
+        };
+        self.files.insert(key)
+    }
+}
@@ -0,0 +1,156 @@
+//! What the harness needs to know about the machine: cache-line size, the CPUs this
+//! process may run on and how cheap and fine the clock is, with per-architecture defaults
+//! where detection comes up empty.
+//!
+//! x86_64, aarch64 (Graviton, Raspberry Pi) and riscv64 are the targets this is checked on.
+//! Nothing here uses architecture-specific instructions: cache geometry comes from sysfs,
+//! affinity from `sched_getaffinity` and time from the OS monotonic clock, which on older
+//! riscv64 kernels has no vDSO and costs a full syscall per reading.
+//!
+//! [`Host`] names the machine, its kernel and CPU, for results that have to say where they
+//! were measured.
+
+use std::fs;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Defaults for one architecture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tuning {
+    /// Cache-line size assumed when the kernel doesn't report one. 128 on aarch64, where
+    /// Apple and some Neoverse cores fetch lines in pairs, so padding by it also avoids
+    /// false sharing there.
+    pub cache_line: usize,
+    /// Clock readings slower than this make per-file times (`--html`, `--embedded`)
+    /// mostly measure the clock, and are warned about.
+    pub slow_clock: Duration,
+}
+
+impl Tuning {
+    pub fn for_arch(arch: &str) -> Tuning {
+        match arch {
+            "aarch64" => Tuning { cache_line: 128, slow_clock: Duration::from_nanos(500) },
+            // Small in-order cores; a syscall per reading is a few microseconds.
+            "riscv64" => Tuning { cache_line: 64, slow_clock: Duration::from_micros(1) },
+            _ => Tuning { cache_line: 64, slow_clock: Duration::from_nanos(500) },
+        }
+    }
+
+    /// The defaults for the architecture this was built for.
+    pub fn current() -> Tuning {
+        Tuning::for_arch(std::env::consts::ARCH)
+    }
+}
+
+/// The L1 data cache line size in bytes, from sysfs or else [`Tuning::cache_line`].
+pub fn cache_line_size() -> usize {
+    static SIZE: OnceLock<usize> = OnceLock::new();
+    *SIZE.get_or_init(|| {
+        fs::read_to_string("/sys/devices/system/cpu/cpu0/cache/index0/coherency_line_size")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .filter(|&size: &usize| size.is_power_of_two())
+            .unwrap_or(Tuning::current().cache_line)
+    })
+}
+
+/// `len` rounded up to a whole number of cache lines.
+pub fn align_to_cache_line(len: usize) -> usize {
+    len.next_multiple_of(cache_line_size())
+}
+
+/// The CPUs this process may be scheduled on, in order. Containers, cgroup cpusets and
+/// boards with cores offline often allow fewer CPUs than the machine has, and not
+/// necessarily starting at 0.
+pub fn allowed_cpus() -> Vec<usize> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        use libc::{CPU_ISSET, CPU_SETSIZE, cpu_set_t, sched_getaffinity};
+
+        unsafe {
+            let mut set: cpu_set_t = std::mem::zeroed();
+            if sched_getaffinity(0, size_of::<cpu_set_t>(), &mut set) == 0 {
+                let cpus: Vec<usize> = (0..CPU_SETSIZE as usize).filter(|&cpu| CPU_ISSET(cpu, &set)).collect();
+                if !cpus.is_empty() {
+                    return cpus;
+                }
+            }
+        }
+    }
+    (0..thread::available_parallelism().map_or(1, |n| n.get())).collect()
+}
+
+/// The machine a run happens on, as far as it tells.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Host {
+    pub hostname: Option<String>,
+    /// `linux`, `macos` and so on, as `std::env::consts::OS`.
+    pub os: String,
+    pub arch: String,
+    /// The kernel release, such as `6.8.0-45-generic`.
+    pub kernel: Option<String>,
+    pub cpu_model: Option<String>,
+}
+
+impl Host {
+    pub fn current() -> Host {
+        let read = |path: &str| fs::read_to_string(path).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        Host {
+            hostname: read("/proc/sys/kernel/hostname").or_else(|| std::env::var("HOSTNAME").ok()).or_else(|| std::env::var("COMPUTERNAME").ok()),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            kernel: read("/proc/sys/kernel/osrelease"),
+            cpu_model: fs::read_to_string("/proc/cpuinfo").ok().and_then(|cpuinfo| cpu_model(&cpuinfo)),
+        }
+    }
+}
+
+/// The CPU model in `/proc/cpuinfo` text: `model name` on x86, `Model` on boards like the
+/// Raspberry Pi and `uarch` on riscv64. Graviton and most other aarch64 servers give only
+/// part numbers, and get `None`.
+pub fn cpu_model(cpuinfo: &str) -> Option<String> {
+    ["model name", "Model", "uarch"].into_iter().find_map(|wanted| {
+        cpuinfo.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == wanted && !value.trim().is_empty()).then(|| value.trim().to_string())
+        })
+    })
+}
+
+/// How fine and how expensive readings of the monotonic clock are.
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    /// The smallest step the clock reports, per `clock_getres`; zero where unknown.
+    pub resolution: Duration,
+    /// Average cost of one `Instant::now()`.
+    pub overhead: Duration,
+}
+
+impl Clock {
+    /// Measures the clock with a short burst of readings.
+    pub fn measure() -> Clock {
+        const READINGS: u32 = 10_000;
+        let start = Instant::now();
+        for _ in 0..READINGS {
+            std::hint::black_box(Instant::now());
+        }
+        Clock { resolution: clock_resolution(), overhead: start.elapsed() / READINGS }
+    }
+
+    /// Whether the clock is too slow for per-file timing on this architecture.
+    pub fn is_slow(&self) -> bool {
+        self.overhead > Tuning::current().slow_clock
+    }
+}
+
+fn clock_resolution() -> Duration {
+    #[cfg(all(unix, feature = "libc"))]
+    {
+        let mut res = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        if unsafe { libc::clock_getres(libc::CLOCK_MONOTONIC, &mut res) } == 0 {
+            return Duration::new(res.tv_sec as u64, res.tv_nsec as u32);
+        }
+    }
+    Duration::ZERO
+}
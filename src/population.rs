@@ -0,0 +1,413 @@
+//! Named file populations that outlive a single run, recorded in manifests so later runs
+//! can reuse them instead of regenerating the files.
+//!
+//! A store is a directory holding `<name>.json` manifests and, for generated populations,
+//! the files themselves under `<name>/`. Discovered populations list files of an existing
+//! tree in place.
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::json::{self, Value};
+
+const VERSION: u64 = 1;
+
+/// Where a population's files came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// `files` files of `size` bytes written by a create phase.
+    Generated { files: usize, size: usize },
+    /// Every regular file found under a directory.
+    Discovered(PathBuf),
+}
+
+/// One file of a population: its path relative to the population directory, its size and,
+/// for generated populations, a hash of its contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub hash: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub name: String,
+    pub dir: PathBuf,
+    pub source: Source,
+    pub entries: Vec<Entry>,
+    /// Aging rounds applied since the files were generated.
+    pub rounds: u64,
+}
+
+/// Whether `name` can name a population: a single plain path component, so its manifest
+/// and files stay inside the store.
+pub fn valid_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(only)), None) if only == name)
+}
+
+fn check_name(name: &str) -> io::Result<()> {
+    match valid_name(name) {
+        true => Ok(()),
+        false => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("population name {:?} must be a single file name", name))),
+    }
+}
+
+/// `path` if it only descends, as a manifest entry must to stay inside its population.
+fn descending(path: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(path);
+    let plain = path.components().next().is_some() && path.components().all(|c| matches!(c, Component::Normal(_)));
+    plain.then_some(path)
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// 64-bit FNV-1a of `bytes`, the same hash [`hash_file`] gives a file holding them.
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    fnv1a(FNV_OFFSET, bytes)
+}
+
+/// 64-bit FNV-1a of a file's contents.
+pub fn hash_file(path: &Path) -> io::Result<u64> {
+    hash_reader(File::open(path)?)
+}
+
+/// 64-bit FNV-1a of everything `reader` yields.
+pub fn hash_reader(mut reader: impl Read) -> io::Result<u64> {
+    let mut hash = FNV_OFFSET;
+    let mut buf = [0; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            return Ok(hash);
+        }
+        hash = fnv1a(hash, &buf[..n]);
+    }
+}
+
+/// A sampled file that no longer matches its manifest entry.
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub path: PathBuf,
+    pub problem: String,
+}
+
+/// How [`Manifest::age`] churns a population, per round.
+#[derive(Debug, Clone, Copy)]
+pub struct Aging {
+    pub rounds: u64,
+    /// Bytes appended to each chosen file.
+    pub append: usize,
+    /// Fraction of files appended to, 0 to 1.
+    pub append_fraction: f64,
+    /// Fraction of files deleted and replaced by new files, 0 to 1.
+    pub delete_fraction: f64,
+    pub seed: u64,
+}
+
+impl Default for Aging {
+    fn default() -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64);
+        Aging { rounds: 1, append: 4096, append_fraction: 0.1, delete_fraction: 0.05, seed }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AgingReport {
+    pub appended: usize,
+    pub replaced: usize,
+}
+
+/// xorshift64*, enough to pick files reproducibly from a seed.
+#[derive(Debug)]
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn entry(dir: &Path, path: &Path, hash: bool) -> io::Result<Entry> {
+    Ok(Entry {
+        path: path.strip_prefix(dir).unwrap_or(path).to_path_buf(),
+        size: fs::metadata(path)?.len(),
+        hash: if hash { Some(hash_file(path)?) } else { None },
+    })
+}
+
+impl Manifest {
+    /// Lists and hashes the files of a generated population as they are now on disk.
+    pub fn generated(name: &str, dir: &Path, paths: &[PathBuf], files: usize, size: usize) -> io::Result<Manifest> {
+        let entries = paths.iter().map(|path| entry(dir, path, true)).collect::<io::Result<_>>()?;
+        Ok(Manifest { name: name.to_string(), dir: dir.to_path_buf(), source: Source::Generated { files, size }, entries, rounds: 0 })
+    }
+
+    /// Re-reads the size and hash of every entry, after the files were rewritten.
+    pub fn refresh(&mut self) -> io::Result<()> {
+        for e in &mut self.entries {
+            *e = entry(&self.dir, &self.dir.join(&e.path), e.hash.is_some())?;
+        }
+        Ok(())
+    }
+
+    /// Checks up to `sample` entries, spread evenly over the population, against the files
+    /// on disk.
+    pub fn verify(&self, sample: usize) -> Vec<Mismatch> {
+        let step = self.entries.len().div_ceil(sample.max(1)).max(1);
+        let mut mismatches = Vec::new();
+        for e in self.entries.iter().step_by(step) {
+            let path = self.dir.join(&e.path);
+            let problem = match fs::metadata(&path) {
+                Err(err) => Some(err.to_string()),
+                Ok(metadata) if metadata.len() != e.size => Some(format!("size {} instead of {}", metadata.len(), e.size)),
+                Ok(_) => match e.hash.map(|expected| (expected, hash_file(&path))) {
+                    Some((_, Err(err))) => Some(err.to_string()),
+                    Some((expected, Ok(actual))) if actual != expected => Some("contents changed".to_string()),
+                    _ => None,
+                },
+            };
+            if let Some(problem) = problem {
+                mismatches.push(Mismatch { path, problem });
+            }
+        }
+        mismatches
+    }
+
+    /// Churns a generated population like long-lived trees do: each round appends to some
+    /// files, and deletes others and writes new files in their place. The manifest is
+    /// updated to match.
+    pub fn age(&mut self, aging: &Aging) -> io::Result<AgingReport> {
+        let Source::Generated { size, .. } = self.source else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "only generated populations can be aged"));
+        };
+        let mut rng = Rng(aging.seed.max(1));
+        let mut report = AgingReport::default();
+        let count = |fraction: f64, len: usize| ((fraction.clamp(0.0, 1.0) * len as f64).round() as usize).min(len);
+        let mut next_id = self.entries.len() as u64 + self.rounds * 1_000_000;
+        for _ in 0..aging.rounds {
+            let len = self.entries.len();
+            if len == 0 {
+                break;
+            }
+            let tail = vec![b'a'; aging.append];
+            for _ in 0..count(aging.append_fraction, len) {
+                let i = rng.below(len);
+                let path = self.dir.join(&self.entries[i].path);
+                OpenOptions::new().append(true).open(&path)?.write_all(&tail)?;
+                self.entries[i] = entry(&self.dir, &path, true)?;
+                report.appended += 1;
+            }
+            let content = vec![b'n'; size];
+            for _ in 0..count(aging.delete_fraction, len) {
+                let i = rng.below(len);
+                fs::remove_file(self.dir.join(&self.entries[i].path))?;
+                let path = loop {
+                    next_id += 1;
+                    let candidate = self.dir.join(format!("aged_{}.txt", next_id));
+                    if !candidate.exists() {
+                        break candidate;
+                    }
+                };
+                fs::write(&path, &content)?;
+                self.entries[i] = entry(&self.dir, &path, true)?;
+                report.replaced += 1;
+            }
+            self.rounds += 1;
+        }
+        Ok(report)
+    }
+
+    /// Walks `dir` and records every regular file under it, without following symlinks.
+    pub fn discover(name: &str, dir: &Path) -> io::Result<Manifest> {
+        let mut entries = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            for entry in fs::read_dir(&current)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    pending.push(entry.path());
+                } else if file_type.is_file() {
+                    let path = entry.path();
+                    entries.push(Entry { path: path.strip_prefix(dir).unwrap_or(&path).to_path_buf(), size: entry.metadata()?.len(), hash: None });
+                }
+            }
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Manifest { name: name.to_string(), dir: dir.to_path_buf(), source: Source::Discovered(dir.to_path_buf()), entries, rounds: 0 })
+    }
+
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.entries.iter().map(|entry| self.dir.join(&entry.path)).collect()
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+
+    fn to_json(&self) -> Value {
+        let source = match &self.source {
+            Source::Generated { files, size } => Value::object().with("generated", Value::object().with("files", *files).with("size", *size)),
+            Source::Discovered(dir) => Value::object().with("discovered", dir.to_string_lossy().into_owned()),
+        };
+        let entries: Vec<Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let value = Value::object().with("path", entry.path.to_string_lossy().into_owned()).with("size", entry.size);
+                // Hex, since JSON numbers can't hold every u64 exactly.
+                match entry.hash {
+                    Some(hash) => value.with("fnv1a", format!("{:016x}", hash)),
+                    None => value,
+                }
+            })
+            .collect();
+        Value::object()
+            .with("version", VERSION)
+            .with("name", self.name.as_str())
+            .with("dir", self.dir.to_string_lossy().into_owned())
+            .with("source", source)
+            .with("rounds", self.rounds)
+            .with("entries", entries)
+    }
+
+    fn from_json(value: &Value) -> Option<Manifest> {
+        if value.get("version")?.as_u64()? != VERSION {
+            return None;
+        }
+        let source = value.get("source")?;
+        let source = match (source.get("generated"), source.get("discovered")) {
+            (Some(generated), _) => Source::Generated {
+                files: generated.get("files")?.as_u64()? as usize,
+                size: generated.get("size")?.as_u64()? as usize,
+            },
+            (None, Some(dir)) => Source::Discovered(PathBuf::from(dir.as_str()?)),
+            (None, None) => return None,
+        };
+        let entries = value
+            .get("entries")?
+            .as_array()?
+            .iter()
+            .map(|entry| {
+                let hash = match entry.get("fnv1a") {
+                    Some(hash) => Some(u64::from_str_radix(hash.as_str()?, 16).ok()?),
+                    None => None,
+                };
+                Some(Entry { path: descending(entry.get("path")?.as_str()?)?, size: entry.get("size")?.as_u64()?, hash })
+            })
+            .collect::<Option<_>>()?;
+        Some(Manifest {
+            name: value.get("name")?.as_str().filter(|name| valid_name(name))?.to_string(),
+            dir: PathBuf::from(value.get("dir")?.as_str()?),
+            source,
+            entries,
+            rounds: value.get("rounds").and_then(Value::as_u64).unwrap_or(0),
+        })
+    }
+}
+
+/// A directory of population manifests.
+#[derive(Debug, Clone)]
+pub struct Store {
+    pub dir: PathBuf,
+}
+
+impl Default for Store {
+    /// `$TMPDIR/io-populations`.
+    fn default() -> Self {
+        Store { dir: env::temp_dir().join("io-populations") }
+    }
+}
+
+impl Store {
+    pub fn new(dir: impl Into<PathBuf>) -> Store {
+        Store { dir: dir.into() }
+    }
+
+    /// Where a generated population's files live.
+    pub fn population_dir(&self, name: &str) -> io::Result<PathBuf> {
+        check_name(name)?;
+        Ok(self.dir.join(name))
+    }
+
+    fn manifest_path(&self, name: &str) -> io::Result<PathBuf> {
+        check_name(name)?;
+        Ok(self.dir.join(format!("{}.json", name)))
+    }
+
+    pub fn load(&self, name: &str) -> io::Result<Option<Manifest>> {
+        let path = self.manifest_path(name)?;
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+        let value = json::parse(&text).map_err(invalid)?;
+        Manifest::from_json(&value).map(Some).ok_or_else(|| invalid("not a population manifest".to_string()))
+    }
+
+    pub fn save(&self, manifest: &Manifest) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.manifest_path(&manifest.name)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, format!("{}\n", manifest.to_json()))?;
+        fs::rename(tmp, path)
+    }
+
+    /// Forgets a population, deleting its files if it was generated into the store.
+    pub fn remove(&self, name: &str) -> io::Result<()> {
+        if let Some(Manifest { source: Source::Generated { .. }, dir, .. }) = self.load(name)?
+            && dir == self.population_dir(name)?
+        {
+            match fs::remove_dir_all(&dir) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        match fs::remove_file(self.manifest_path(name)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Every manifest in the store, by name.
+    pub fn list(&self) -> io::Result<Vec<Manifest>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut manifests = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json")
+                && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+                && let Some(manifest) = self.load(name)?
+            {
+                manifests.push(manifest);
+            }
+        }
+        manifests.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(manifests)
+    }
+}
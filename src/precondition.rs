@@ -0,0 +1,151 @@
+//! Aging the filesystem under test before a benchmark: filling it to a target utilization
+//! and deleting part of what was written, so the run allocates from scattered free space
+//! the way it would on a volume that has been in use for months. A fresh filesystem hands
+//! out long contiguous extents and empty directories, and its numbers flatter everything.
+//!
+//! Filler files are written a chunk at a time to several files in turn, each round synced
+//! so filesystems that delay allocation place the chunks as they arrive and their extents
+//! interleave on disk, then a random share of them (the fragmentation) is deleted, leaving
+//! holes between the survivors. Sizes vary around [`Precondition::file_size`], and the
+//! contents are random so compressing or deduplicating filesystems store them in full.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::filesystem::Filesystem;
+use crate::population::Rng;
+
+/// The fullest a filesystem may be made, leaving room for the benchmark itself.
+pub const MAX_UTILIZATION: f64 = 0.95;
+pub const DEFAULT_FRAGMENTATION: f64 = 0.25;
+pub const DEFAULT_FILE_SIZE: usize = 256 * 1024;
+
+/// Bytes written to one file before moving on to the next of its group.
+const CHUNK: usize = 64 * 1024;
+/// Files written in turn.
+const GROUP: usize = 16;
+/// Filler files per directory.
+const FILES_PER_DIR: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Precondition {
+    /// Fraction of the filesystem used once aged, 0 to [`MAX_UTILIZATION`].
+    pub utilization: f64,
+    /// Fraction of the filler deleted again, 0 to 1.
+    pub fragmentation: f64,
+    /// Average filler file size; sizes range from half to one and a half times it.
+    pub file_size: usize,
+    pub seed: u64,
+}
+
+impl Precondition {
+    /// Aging to `utilization` with the default fragmentation and file size.
+    pub fn new(utilization: f64) -> Precondition {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64);
+        Precondition { utilization, fragmentation: DEFAULT_FRAGMENTATION, file_size: DEFAULT_FILE_SIZE, seed }
+    }
+
+    /// Filler to write on a filesystem of `total` bytes with `available` free so that,
+    /// after the fragmentation's share is deleted, the target utilization is reached.
+    /// Nothing when it's already that full.
+    pub fn filler_bytes(&self, total: u64, available: u64) -> u64 {
+        let used = total.saturating_sub(available) as f64;
+        let missing = self.utilization * total as f64 - used;
+        if missing <= 0.0 {
+            return 0;
+        }
+        let filler = missing / (1.0 - self.fragmentation.min(0.99));
+        // Deleting comes after writing, so the filler itself must fit.
+        filler.min(available as f64 - (1.0 - MAX_UTILIZATION) * total as f64).max(0.0) as u64
+    }
+
+    /// Ages the filesystem `dir` is on, writing the filler into `dir`, which should be a
+    /// directory of its own that is removed after the benchmark.
+    pub fn run(&self, dir: &Path) -> io::Result<Report> {
+        if !(0.0..=MAX_UTILIZATION).contains(&self.utilization) || !(0.0..=1.0).contains(&self.fragmentation) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("utilization must be 0 to {}% and fragmentation 0 to 100%", MAX_UTILIZATION * 100.0),
+            ));
+        }
+        let space = |dir: &Path| Filesystem::of(dir).and_then(|filesystem| filesystem.available.zip(filesystem.total));
+        let (available, total) = space(dir).ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, format!("can't tell how full the filesystem of {} is", dir.display())))?;
+        let mut report = fill(dir, self.filler_bytes(total, available), self.file_size, self.fragmentation, self.seed)?;
+        report.utilization_before = utilization(total, available);
+        report.utilization_after = space(dir).map_or(report.utilization_before, |(available, total)| utilization(total, available));
+        Ok(report)
+    }
+}
+
+/// What preconditioning wrote and deleted.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Report {
+    pub files_written: usize,
+    pub bytes_written: u64,
+    pub files_deleted: usize,
+    pub bytes_deleted: u64,
+    /// Fractions of the filesystem in use before and after.
+    pub utilization_before: f64,
+    pub utilization_after: f64,
+}
+
+fn utilization(total: u64, available: u64) -> f64 {
+    if total == 0 { 0.0 } else { total.saturating_sub(available) as f64 / total as f64 }
+}
+
+/// Writes `bytes` of filler files averaging `file_size` into `dir`, interleaving their
+/// extents, and deletes a random `fragmentation` share of them. The utilizations of the
+/// report are left at zero.
+pub fn fill(dir: &Path, bytes: u64, file_size: usize, fragmentation: f64, seed: u64) -> io::Result<Report> {
+    let mut rng = Rng(seed.max(1));
+    let mut sizes = Vec::new();
+    let mut planned = 0;
+    let file_size = file_size.max(2);
+    while planned < bytes {
+        let size = ((file_size / 2 + rng.below(file_size)) as u64).min(bytes - planned);
+        sizes.push(size);
+        planned += size;
+    }
+    let mut data = vec![0; CHUNK];
+    data.chunks_mut(8).for_each(|word| word.copy_from_slice(&rng.next().to_le_bytes()[..word.len()]));
+
+    let path = |index: usize| dir.join(format!("d{:04}", index / FILES_PER_DIR)).join(format!("f{:06}", index));
+    let mut report = Report::default();
+    for (group, group_sizes) in sizes.chunks(GROUP).enumerate() {
+        let first = group * GROUP;
+        let mut files = Vec::with_capacity(group_sizes.len());
+        for index in first..first + group_sizes.len() {
+            let path = path(index);
+            if index % FILES_PER_DIR == 0 {
+                fs::create_dir_all(path.parent().unwrap_or(dir))?;
+            }
+            files.push(File::create(path)?);
+        }
+        let mut written = vec![0u64; group_sizes.len()];
+        while written.iter().zip(group_sizes).any(|(written, size)| written < size) {
+            for ((file, written), &size) in files.iter_mut().zip(&mut written).zip(group_sizes) {
+                let n = (size - *written).min(CHUNK as u64) as usize;
+                if n > 0 {
+                    file.write_all(&data[..n])?;
+                    *written += n as u64;
+                    file.sync_data()?;
+                }
+            }
+        }
+        report.files_written += group_sizes.len();
+        report.bytes_written += group_sizes.iter().sum::<u64>();
+    }
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    let delete = (sizes.len() as f64 * fragmentation.clamp(0.0, 1.0)).round() as usize;
+    for i in 0..delete {
+        let pick = i + rng.below(order.len() - i);
+        order.swap(i, pick);
+        fs::remove_file(path(order[i]))?;
+        report.files_deleted += 1;
+        report.bytes_deleted += sizes[order[i]];
+    }
+    Ok(report)
+}
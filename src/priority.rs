@@ -0,0 +1,223 @@
+//! CPU nice levels and I/O scheduling classes for worker threads, so a benchmark or a bulk
+//! operation can run as a background job on a shared machine.
+//!
+//! Both are per thread on Linux and inherited by the threads a thread spawns, so applying
+//! a [`Priority`] to the engine's workers (see [`crate::engine::Engine::priority`]) covers
+//! whatever they start too. The I/O class only orders requests in schedulers that honour
+//! it (`bfq`, `mq-deadline`), and buffered writes reach the disk from the kernel's flusher
+//! threads at their own priority; reads, syncs and `O_DIRECT` writes are the ones it slows.
+//! Lowering either needs no privilege; raising the nice level needs `CAP_SYS_NICE` and the
+//! realtime class `CAP_SYS_ADMIN`.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Levels within the realtime and best-effort classes, 0 being served first.
+pub const IO_LEVELS: u8 = 8;
+
+/// The level `best-effort` and `realtime` mean without one, the kernel's default.
+const DEFAULT_IO_LEVEL: u8 = 4;
+
+/// An I/O scheduling class, as `ionice -c` takes them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Served before every other class, at a level below [`IO_LEVELS`].
+    Realtime(u8),
+    /// The default class, at a level below [`IO_LEVELS`].
+    BestEffort(u8),
+    /// Served only when no other class has requests waiting.
+    Idle,
+}
+
+impl IoPriority {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    const CLASS_SHIFT: u32 = 13;
+
+    /// The `ioprio` value `ioprio_set` takes.
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    fn encode(self) -> libc::c_int {
+        let (class, level) = match self {
+            IoPriority::Realtime(level) => (1, level),
+            IoPriority::BestEffort(level) => (2, level),
+            IoPriority::Idle => (3, 0),
+        };
+        (class << Self::CLASS_SHIFT) | level as libc::c_int
+    }
+
+    /// The class an `ioprio_get` result names, `None` for a thread that never set one.
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    fn decode(ioprio: libc::c_int) -> Option<IoPriority> {
+        let level = (ioprio & 0xff) as u8;
+        match ioprio >> Self::CLASS_SHIFT {
+            1 => Some(IoPriority::Realtime(level)),
+            2 => Some(IoPriority::BestEffort(level)),
+            3 => Some(IoPriority::Idle),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for IoPriority {
+    type Err = io::Error;
+
+    /// `idle`, or `best-effort` or `realtime` with an optional level, as in `best-effort:7`.
+    fn from_str(s: &str) -> io::Result<IoPriority> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("unknown I/O priority '{}', expected idle, best-effort[:<0-7>] or realtime[:<0-7>]", s));
+        let (class, level) = match s.split_once(':') {
+            Some((class, level)) => (class, Some(level.parse::<u8>().ok().filter(|&level| level < IO_LEVELS).ok_or_else(invalid)?)),
+            None => (s, None),
+        };
+        match (class, level) {
+            ("idle", None) => Ok(IoPriority::Idle),
+            ("best-effort", level) => Ok(IoPriority::BestEffort(level.unwrap_or(DEFAULT_IO_LEVEL))),
+            ("realtime", level) => Ok(IoPriority::Realtime(level.unwrap_or(DEFAULT_IO_LEVEL))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for IoPriority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IoPriority::Realtime(level) => write!(f, "realtime:{}", level),
+            IoPriority::BestEffort(level) => write!(f, "best-effort:{}", level),
+            IoPriority::Idle => f.write_str("idle"),
+        }
+    }
+}
+
+/// What to set on a thread; `None` leaves that part as inherited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Priority {
+    /// The nice level, -20 to 19, higher yielding the CPU more.
+    pub nice: Option<i32>,
+    pub io: Option<IoPriority>,
+}
+
+impl Priority {
+    /// Whether this changes anything.
+    pub fn is_set(&self) -> bool {
+        self.nice.is_some() || self.io.is_some()
+    }
+
+    /// Sets the calling thread's nice level and I/O class. Fails on platforms without
+    /// per-thread priorities when either is set.
+    pub fn apply(&self) -> io::Result<()> {
+        if let Some(nice) = self.nice {
+            set_nice(nice)?;
+        }
+        if let Some(io) = self.io {
+            set_io_priority(io)?;
+        }
+        Ok(())
+    }
+
+    /// The calling thread's nice level and I/O class.
+    pub fn current() -> io::Result<Priority> {
+        Ok(Priority { nice: Some(nice()?), io: Some(io_priority()?) })
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.nice, self.io) {
+            (Some(nice), Some(io)) => write!(f, "nice {}, {} I/O", nice, io),
+            (Some(nice), None) => write!(f, "nice {}", nice),
+            (None, Some(io)) => write!(f, "{} I/O", io),
+            (None, None) => f.write_str("inherited priority"),
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", feature = "libc")))]
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "per-thread nice levels and I/O classes need Linux and the libc feature")
+}
+
+/// Says which capability a refused change needs.
+#[cfg(all(target_os = "linux", feature = "libc"))]
+fn explain(e: io::Error, what: &str, capability: &str) -> io::Error {
+    match e.raw_os_error() {
+        Some(libc::EPERM) | Some(libc::EACCES) => io::Error::new(e.kind(), format!("setting {} failed ({}); it needs {}", what, e, capability)),
+        _ => io::Error::new(e.kind(), format!("setting {} failed: {}", what, e)),
+    }
+}
+
+/// Sets the calling thread's nice level, clamped to -20..=19.
+pub fn set_nice(nice: i32) -> io::Result<()> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        let nice = nice.clamp(-20, 19);
+        // With PRIO_PROCESS, Linux takes a thread id, 0 being the calling thread.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+            return Err(explain(io::Error::last_os_error(), &format!("nice level {}", nice), "CAP_SYS_NICE to go below the current level"));
+        }
+        Ok(())
+    }
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    {
+        let _ = nice;
+        Err(unsupported())
+    }
+}
+
+/// The calling thread's nice level.
+pub fn nice() -> io::Result<i32> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        // -1 is a valid level, so errno tells failure apart.
+        unsafe { *libc::__errno_location() = 0 };
+        let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+        match io::Error::last_os_error() {
+            e if nice == -1 && e.raw_os_error() != Some(0) => Err(e),
+            _ => Ok(nice),
+        }
+    }
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    Err(unsupported())
+}
+
+/// `IOPRIO_WHO_PROCESS`, which like `PRIO_PROCESS` takes a thread id.
+#[cfg(all(target_os = "linux", feature = "libc"))]
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+
+/// Sets the calling thread's I/O scheduling class.
+pub fn set_io_priority(priority: IoPriority) -> io::Result<()> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        if let IoPriority::Realtime(level) | IoPriority::BestEffort(level) = priority
+            && level >= IO_LEVELS
+        {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("I/O priority level {} is beyond {}", level, IO_LEVELS - 1)));
+        }
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority.encode()) } != 0 {
+            return Err(explain(io::Error::last_os_error(), &format!("the {} I/O class", priority), "CAP_SYS_ADMIN for the realtime class"));
+        }
+        Ok(())
+    }
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    {
+        let _ = priority;
+        Err(unsupported())
+    }
+}
+
+/// The calling thread's I/O scheduling class.
+pub fn io_priority() -> io::Result<IoPriority> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        let ioprio = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0) };
+        if ioprio < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Without a class of its own a thread is best effort at a level following its nice
+        // level, as the kernel schedules it.
+        match IoPriority::decode(ioprio as libc::c_int) {
+            Some(priority) => Ok(priority),
+            None => Ok(IoPriority::BestEffort(((nice()?.clamp(-20, 19) + 20) / 5) as u8)),
+        }
+    }
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    Err(unsupported())
+}
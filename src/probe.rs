@@ -0,0 +1,275 @@
+//! Empirical checks of what a filesystem actually does, for the properties the fast paths
+//! rely on: that renaming over a file swaps it for readers in one step, that holes are
+//! kept and reported, that reflinks work, how aligned `O_DIRECT` I/O must be, that a
+//! mapping and `write(2)` see each other's bytes, and how long a file name may be.
+//!
+//! Mount options and the filesystem type say what should hold; FUSE filesystems, network
+//! mounts and overlays often differ, so each property is tried in a scratch directory on
+//! the filesystem itself. [`Probe::adjust`] turns the results into the adaptive strategy's
+//! backend choice.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use memmap2::{Mmap, MmapMut};
+
+use crate::cas;
+use crate::crossover::Thresholds;
+use crate::sparse;
+
+/// Renames over the target while a reader watches it.
+const RENAMES: usize = 200;
+
+/// The alignments `O_DIRECT` is tried at, smallest first.
+const DIRECT_ALIGNMENTS: [usize; 8] = [512, 1024, 2048, 4096, 8192, 16384, 32768, 65536];
+
+/// The longest name tried, past any filesystem's limit.
+const LONGEST_NAME: usize = 4096;
+
+/// What one check found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Yes,
+    /// The property doesn't hold, and how that showed.
+    No(String),
+    /// The check couldn't run here.
+    Unknown(String),
+}
+
+impl Verdict {
+    pub fn holds(&self) -> bool {
+        *self == Verdict::Yes
+    }
+
+    fn of(result: io::Result<Verdict>) -> Verdict {
+        result.unwrap_or_else(|e| Verdict::Unknown(e.to_string()))
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::Yes => f.write_str("yes"),
+            Verdict::No(why) => write!(f, "no ({})", why),
+            Verdict::Unknown(why) => write!(f, "unknown ({})", why),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    /// Renaming over an existing file replaces it for readers in one step: they never find
+    /// it missing or half written.
+    pub atomic_rename: Verdict,
+    /// Holes in files are kept unallocated and found again with `SEEK_DATA`.
+    pub sparse_files: Verdict,
+    /// `FICLONE` shares extents between files.
+    pub reflink: Verdict,
+    /// The smallest alignment of buffer, offset and length `O_DIRECT` accepts; `None` when
+    /// it isn't supported (or not on Linux).
+    pub direct_io_alignment: Option<usize>,
+    /// A shared mapping sees `write(2)` to its file, and `read(2)` sees stores to the mapping.
+    pub mmap_coherent: Verdict,
+    /// The longest file name, in bytes, a file could be created with.
+    pub max_name_len: Option<usize>,
+}
+
+impl Probe {
+    /// Tries every property in a scratch directory created in `dir` and removed afterwards.
+    pub fn run(dir: &Path) -> io::Result<Probe> {
+        let scratch = dir.join(format!("io-probe-{}", std::process::id()));
+        fs::create_dir_all(&scratch)?;
+        let probe = Probe {
+            atomic_rename: Verdict::of(atomic_rename(&scratch)),
+            sparse_files: Verdict::of(sparse_files(&scratch)),
+            reflink: reflink(&scratch),
+            direct_io_alignment: direct_io_alignment(&scratch).ok().flatten(),
+            mmap_coherent: Verdict::of(mmap_coherent(&scratch)),
+            max_name_len: max_name_len(&scratch).ok(),
+        };
+        fs::remove_dir_all(&scratch)?;
+        Ok(probe)
+    }
+
+    /// `thresholds` with what this filesystem can't do taken out: no mmap backend where
+    /// mappings and `write(2)` don't agree, since the adaptive strategy mixes them.
+    pub fn adjust(&self, thresholds: Thresholds) -> Thresholds {
+        if self.mmap_coherent.holds() {
+            return thresholds;
+        }
+        Thresholds { mmap_read: None, mmap_update: None }
+    }
+}
+
+/// Renames new versions over a file while another thread keeps reading it.
+fn atomic_rename(dir: &Path) -> io::Result<Verdict> {
+    let target = dir.join("rename-target");
+    let line = |n: usize| format!("version {:08}\n", n);
+    let version = |n: usize| line(n).repeat(512);
+    fs::write(&target, version(0))?;
+    let done = AtomicBool::new(false);
+    let seen = thread::scope(|scope| {
+        let reader = scope.spawn(|| -> Option<String> {
+            while !done.load(Ordering::Relaxed) {
+                match fs::read_to_string(&target) {
+                    Err(e) => return Some(format!("a reader got {}", e)),
+                    Ok(content) if content.len() != version(0).len() || content != content[..line(0).len()].repeat(512) => return Some("a reader saw a partial file".to_string()),
+                    Ok(_) => {}
+                }
+            }
+            None
+        });
+        let renamed = (1..=RENAMES).try_for_each(|n| {
+            let temp = dir.join("rename-temp");
+            fs::write(&temp, version(n))?;
+            fs::rename(&temp, &target)
+        });
+        done.store(true, Ordering::Relaxed);
+        renamed.map(|()| reader.join().unwrap_or(Some("the reader panicked".to_string())))
+    })?;
+    if let Some(why) = seen {
+        return Ok(Verdict::No(why));
+    }
+    Ok(match fs::read_to_string(&target)? == version(RENAMES) {
+        true => Verdict::Yes,
+        false => Verdict::No("the last rename didn't stick".to_string()),
+    })
+}
+
+/// Writes a 1 MiB file with two small extents and looks for the hole between them.
+fn sparse_files(dir: &Path) -> io::Result<Verdict> {
+    let path = dir.join("sparse");
+    let len = 1 << 20;
+    sparse::write_extents(&path, len, &[(0, &[1; 4096]), (len - 4096, &[2; 4096])])?;
+    let file = File::open(&path)?;
+    let data: u64 = sparse::data_ranges(&file)?.iter().map(|range| range.end - range.start).sum();
+    Ok(match (data < len, sparse::allocated_bytes(&file)? < len) {
+        (true, true) => Verdict::Yes,
+        (false, true) => Verdict::No("holes are kept but SEEK_DATA reports them as data".to_string()),
+        (_, false) => Verdict::No("holes are allocated".to_string()),
+    })
+}
+
+fn reflink(dir: &Path) -> Verdict {
+    let source = dir.join("reflink-source");
+    let result = fs::write(&source, [7; 8192]).and_then(|()| cas::reflink(&source, &dir.join("reflink-copy")));
+    match result {
+        Ok(()) => Verdict::Yes,
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => Verdict::No(e.to_string()),
+        Err(e) => Verdict::Unknown(e.to_string()),
+    }
+}
+
+/// A heap buffer at an address aligned to `align` but not to twice that.
+struct Aligned {
+    base: *mut u8,
+    layout: std::alloc::Layout,
+    align: usize,
+}
+
+impl Aligned {
+    fn new(align: usize) -> Aligned {
+        let layout = std::alloc::Layout::from_size_align(align * 3, align * 2).expect("power of two");
+        let base = unsafe { std::alloc::alloc_zeroed(layout) };
+        if base.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+        Aligned { base, layout, align }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.base.add(self.align), self.align) }
+    }
+}
+
+impl Drop for Aligned {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.base, self.layout) };
+    }
+}
+
+/// The smallest alignment at which an `O_DIRECT` write of that many bytes, from a buffer
+/// and at an offset aligned only that much, succeeds.
+fn direct_io_alignment(dir: &Path) -> io::Result<Option<usize>> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let path = dir.join("direct");
+        let file = match OpenOptions::new().write(true).create(true).truncate(true).custom_flags(libc::O_DIRECT).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        for align in DIRECT_ALIGNMENTS {
+            let buffer = Aligned::new(align);
+            match file.write_at(buffer.bytes(), align as u64) {
+                Ok(written) if written == align => return Ok(Some(align)),
+                Ok(_) => {}
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (dir, DIRECT_ALIGNMENTS, Aligned::new, Aligned::bytes);
+        Ok(None)
+    }
+}
+
+/// Writes through `write(2)` and reads the mapping, then stores to the mapping and reads
+/// with `read(2)`.
+fn mmap_coherent(dir: &Path) -> io::Result<Verdict> {
+    let path = dir.join("mapped");
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+    file.set_len(8192)?;
+    let map = unsafe { Mmap::map(&file)? };
+    file.write_all_at(b"written", 100)?;
+    if &map[100..107] != b"written" {
+        return Ok(Verdict::No("a mapping missed write(2)".to_string()));
+    }
+    let mut writable = unsafe { MmapMut::map_mut(&file)? };
+    writable[5000..6005].copy_from_slice(&[9; 1005]);
+    let mut read = [0; 1005];
+    File::open(&path)?.read_exact_at(&mut read, 5000)?;
+    if read != [9; 1005] {
+        return Ok(Verdict::No("read(2) missed stores to a mapping".to_string()));
+    }
+    Ok(Verdict::Yes)
+}
+
+/// The longest name a file could be created with, found by bisection.
+fn max_name_len(dir: &Path) -> io::Result<usize> {
+    let creates = |len: usize| -> io::Result<bool> {
+        let path: PathBuf = dir.join("n".repeat(len));
+        match File::create(&path) {
+            Ok(mut file) => {
+                file.flush()?;
+                fs::remove_file(&path)?;
+                Ok(true)
+            }
+            Err(e) if e.raw_os_error() == Some(libc::ENAMETOOLONG) => Ok(false),
+            Err(e) => Err(e),
+        }
+    };
+    let (mut fits, mut too_long) = (1, LONGEST_NAME + 1);
+    if !creates(fits)? {
+        return Err(io::Error::other("can't create a one-byte name"));
+    }
+    while too_long - fits > 1 {
+        let middle = (fits + too_long) / 2;
+        if creates(middle)? {
+            fits = middle;
+        } else {
+            too_long = middle;
+        }
+    }
+    Ok(fits)
+}
@@ -0,0 +1,338 @@
+//! `io bench --processes N`: runs each strategy in N worker processes at once, each owning
+//! a contiguous slice of the files, instead of on one process's thread pool.
+//!
+//! Threads of one process share an allocator, a file descriptor table and the locks around
+//! them; separate processes share only the kernel and the device. When a strategy scales
+//! across processes but not across threads, the ceiling is in the process.
+//!
+//! The workers are this binary run as `io bench worker`. Each reads one phase name per
+//! line on stdin, runs that phase on its slice and answers on stdout with
+//! `done <phase> <nanos> <failures>`; the coordinator starts every phase on all workers
+//! together and times it from the first start to the last answer, so the phases line up
+//! as they do in-process. `io agent` speaks the same protocol over TCP (see
+//! [`crate::distributed`]).
+
+use std::env;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ::io::bench::{self, Phase, PhaseTimes, STRATEGIES, Workload};
+use ::io::engine::Engine;
+use ::io::fdlimit::{self, OpenFileLimiter};
+use ::io::filesystem::Filesystem;
+use ::io::human::{self, Table};
+use ::io::log;
+
+use crate::baseline::{self, Baseline, Metadata};
+use crate::cli::{self, BenchArgs, flag_value, invalid_input};
+
+/// Flags the coordinator handles itself instead of passing on to its workers. Those whose
+/// output only a single process could write are refused with `--processes` up front; see
+/// [`check_args`].
+const COORDINATOR_FLAGS: [&str; 2] = ["--processes", "--threads"];
+
+/// One worker, a child process or a remote agent, and the two directions of its protocol.
+pub struct Worker {
+    /// What the worker is called in errors: its process id or host name.
+    pub name: String,
+    child: Option<Child>,
+    input: Box<dyn Write>,
+    output: BufReader<Box<dyn Read>>,
+}
+
+impl Worker {
+    fn spawn(args: &[String]) -> io::Result<Worker> {
+        let mut child = Command::new(env::current_exe()?).arg("bench").arg("worker").args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let input = Box::new(child.stdin.take().expect("stdin is piped"));
+        let output = BufReader::new(Box::new(child.stdout.take().expect("stdout is piped")) as Box<dyn Read>);
+        Ok(Worker { name: format!("worker process {}", child.id()), child: Some(child), input, output })
+    }
+
+    /// A worker on the other end of `input` and `output`, such as a connection to an agent.
+    pub fn remote(name: String, input: Box<dyn Write>, output: Box<dyn Read>) -> Worker {
+        Worker { name, child: None, input, output: BufReader::new(output) }
+    }
+
+    /// Sends one line.
+    pub fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.input, "{}", line)?;
+        self.input.flush()
+    }
+
+    /// The worker's next answer, failing if it reported an error or went away instead.
+    /// Anything else it printed, such as warnings, is passed on.
+    fn read_line(&mut self) -> io::Result<String> {
+        loop {
+            let mut line = String::new();
+            if self.output.read_line(&mut line)? == 0 {
+                let status = match &mut self.child {
+                    Some(child) => format!("exited ({})", child.wait()?),
+                    None => "closed the connection".to_string(),
+                };
+                return Err(io::Error::other(format!("{} {}", self.name, status)));
+            }
+            let line = line.trim_end();
+            if let Some(message) = line.strip_prefix("error ") {
+                return Err(io::Error::other(format!("{}: {}", self.name, message)));
+            }
+            if line == "ready" || line.starts_with("done ") {
+                return Ok(line.to_string());
+            }
+            println!("{}", line);
+        }
+    }
+
+    fn ready(&mut self) -> io::Result<()> {
+        let line = self.read_line()?;
+        if line != "ready" {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected answer from {}: {}", self.name, line)));
+        }
+        Ok(())
+    }
+
+    /// Waits for `done <phase> <nanos> <failures>` and returns the time and failure count.
+    fn finished(&mut self, phase: Phase) -> io::Result<(Duration, usize)> {
+        let line = self.read_line()?;
+        let unexpected = || io::Error::new(io::ErrorKind::InvalidData, format!("unexpected answer from {}: {}", self.name, line));
+        let mut fields = line.split(' ');
+        if fields.next() != Some("done") || fields.next() != Some(phase.name()) {
+            return Err(unexpected());
+        }
+        let nanos: u64 = fields.next().and_then(|nanos| nanos.parse().ok()).ok_or_else(unexpected)?;
+        let failures = fields.next().and_then(|failures| failures.parse().ok()).ok_or_else(unexpected)?;
+        Ok((Duration::from_nanos(nanos), failures))
+    }
+
+    /// Ends the protocol, and for a child process waits for it to exit cleanly.
+    fn finish(self) -> io::Result<()> {
+        drop(self.input);
+        if let Some(mut child) = self.child {
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(io::Error::other(format!("{} exited ({})", self.name, status)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a strategy's phases took when run in lockstep over several workers.
+#[derive(Debug, Default)]
+pub struct Lockstep {
+    /// Each phase from starting it on every worker to the last one finishing.
+    pub times: PhaseTimes,
+    /// Each worker's own phase times, in worker order.
+    pub workers: Vec<PhaseTimes>,
+    pub failures: usize,
+}
+
+impl Lockstep {
+    /// The slowest worker's phase time over the fastest's, worst over the phases.
+    pub fn spread(&self) -> f64 {
+        let mut spread: f64 = 1.0;
+        for phase in Phase::ALL {
+            let times = self.workers.iter().map(|times| times.phase(phase));
+            if let (Some(fastest), Some(slowest)) = (times.clone().min(), times.max())
+                && !fastest.is_zero()
+            {
+                spread = spread.max(slowest.as_secs_f64() / fastest.as_secs_f64());
+            }
+        }
+        spread
+    }
+}
+
+/// Waits for every worker to be ready, runs each phase on all of them together, and ends
+/// the protocol.
+pub fn lockstep(mut workers: Vec<Worker>) -> io::Result<Lockstep> {
+    for worker in &mut workers {
+        worker.ready()?;
+    }
+    let mut result = Lockstep { workers: vec![PhaseTimes::default(); workers.len()], ..Lockstep::default() };
+    for phase in Phase::ALL {
+        let start = Instant::now();
+        for worker in &mut workers {
+            worker.send(phase.name())?;
+        }
+        for (worker, times) in workers.iter_mut().zip(&mut result.workers) {
+            let (elapsed, failures) = worker.finished(phase)?;
+            *times.phase_mut(phase) = elapsed;
+            result.failures += failures;
+        }
+        *result.times.phase_mut(phase) = start.elapsed();
+    }
+    for worker in workers {
+        worker.finish()?;
+    }
+    Ok(result)
+}
+
+/// Fails if `args` ask for something `--processes` doesn't do: output built from each
+/// file's times or operations, which stay in the workers, or a layout of the files other
+/// than one directory split into slices. Baselines, reports and the recommendation come
+/// from the phase times, which the coordinator has.
+pub fn check_args(args: &BenchArgs) -> io::Result<()> {
+    let unsupported: Vec<&str> = [
+        ("--csv", args.csv.is_some()),
+        ("--html", args.html.is_some()),
+        ("--parquet", args.parquet.is_some()),
+        ("--anomalies", args.anomalies),
+        ("--record", args.record.is_some()),
+        ("--bundle", args.bundle.is_some()),
+        ("--target", !args.targets.is_empty()),
+        ("--precondition", args.precondition.is_some()),
+        ("--schedule both", args.schedules.len() > 1),
+        ("--tui", args.tui),
+        ("--crossover", args.crossover),
+        ("--probe", args.probe),
+    ]
+    .into_iter()
+    .filter_map(|(flag, given)| given.then_some(flag))
+    .collect();
+    match unsupported.is_empty() {
+        true => Ok(()),
+        false => Err(invalid_input(format!("--processes doesn't support {}", unsupported.join(", ")))),
+    }
+}
+
+/// `args`, the arguments after `io bench`, minus those in [`COORDINATOR_FLAGS`] and their
+/// values.
+fn worker_args(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut args = args.into_iter();
+    let mut kept = Vec::new();
+    while let Some(arg) = args.next() {
+        if COORDINATOR_FLAGS.contains(&arg.as_str()) {
+            args.next();
+        } else {
+            kept.push(arg);
+        }
+    }
+    kept
+}
+
+/// Runs every strategy in `processes` worker processes, the threads split between them,
+/// returning each strategy's lockstep phase times.
+pub fn run(args: &mut BenchArgs, processes: usize) -> io::Result<Baseline> {
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("--processes runs a single thread count, file count and size".to_string()));
+    };
+    cli::limit_open_files(args)?;
+    let processes = processes.min(files.max(1));
+    let per_process = threads.div_ceil(processes);
+    let dir_path = cli::bench_dir()?;
+    let metadata = Some(Metadata::current(cli::workload_config(args)));
+    let mut results = Baseline { threads, files, size, filesystem: Filesystem::of(&dir_path), targets: Vec::new(), cgroup: None, results: Vec::new(), metadata, anomalies: Vec::new(), conditions: None };
+    println!(
+        "Running {} processes of {} threads, up to {} files of {} each",
+        processes,
+        per_process,
+        human::thousands(files.div_ceil(processes) as u64),
+        human::bytes(size as u64)
+    );
+
+    let mut summary = Table::new(["Strategy"].into_iter().chain(cli::PHASE_COLUMNS).chain(["Spread"]));
+    let mut failed = 0;
+    for strategy in STRATEGIES {
+        println!("Running {}...", strategy.name);
+        let workers = (0..processes)
+            .map(|slice| {
+                let mut args = worker_args(env::args().skip(2));
+                args.extend(["--threads", &per_process.to_string(), "--dir", &dir_path.to_string_lossy(), "--slice", &format!("{}/{}", slice, processes), "--strategy", strategy.label].map(String::from));
+                Worker::spawn(&args)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let result = lockstep(workers)?;
+        failed += result.failures;
+        println!("{} times: {}", strategy.label, cli::phase_summary(&result.times));
+        results.results.push((strategy.label.to_string(), baseline::phase_ms(&result.times)));
+        summary.row(
+            [format!("{} ({} processes)", strategy.label, processes)]
+                .into_iter()
+                .chain(cli::phase_cells(&result.times))
+                .chain([format!("{}x", human::decimal(result.spread(), 2))]),
+        );
+    }
+    println!();
+    print!("{}", summary.render());
+    println!("Spread is the slowest process's phase time over the fastest's, worst phase shown.");
+    if failed > 0 {
+        log::error("processes", format_args!("{} files failed; the worker processes logged them", failed));
+    }
+    drop(dir_path);
+    Ok(results)
+}
+
+/// `io bench worker --dir <dir> --slice <k>/<n> --strategy <label> [bench options]`: one
+/// worker process; see the module docs.
+pub fn worker(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let (mut dir, mut rest) = (None, Vec::new());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => dir = Some(flag_value::<PathBuf>(&mut args, &arg)?),
+            _ => rest.push(arg),
+        }
+    }
+    let dir = dir.ok_or_else(|| invalid_input("io bench worker needs --dir".to_string()))?;
+    // Not locked across phases: workers may print warnings of their own meanwhile.
+    let answer = |line: &str| -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        writeln!(out, "{}", line)?;
+        out.flush()
+    };
+    serve_phases(rest, &dir, io::stdin().lock(), answer)
+}
+
+/// The worker's side of the protocol: sets up `--strategy` on `--slice <k>/<n>` of the
+/// workload `args` describe, in `dir`, then runs each phase `input` names, sending
+/// `answer` the lines the coordinator expects.
+pub fn serve_phases(args: Vec<String>, dir: &Path, input: impl BufRead, mut answer: impl FnMut(&str) -> io::Result<()>) -> io::Result<()> {
+    let (mut slice, mut strategy, mut rest) = (None, None, Vec::new());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--slice" => slice = Some(flag_value::<String>(&mut args, &arg)?),
+            "--strategy" => strategy = Some(flag_value::<String>(&mut args, &arg)?),
+            _ => rest.push(arg),
+        }
+    }
+    let missing = |flag: &str| invalid_input(format!("io bench worker needs {}", flag));
+    let (index, count) = slice
+        .as_deref()
+        .and_then(|slice| slice.split_once('/'))
+        .and_then(|(index, count)| Some((index.parse::<usize>().ok()?, count.parse::<usize>().ok()?)))
+        .filter(|&(index, count)| index < count)
+        .ok_or_else(|| missing("--slice <k>/<n>"))?;
+    let strategy = strategy.ok_or_else(|| missing("--strategy"))?;
+    let strategy = STRATEGIES.iter().find(|candidate| candidate.label == strategy).ok_or_else(|| invalid_input(format!("unknown strategy {}", strategy)))?;
+
+    let mut args: BenchArgs = cli::parse_bench_args(rest.into_iter())?;
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io bench worker runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size);
+    // As `cli::limit_open_files` does, without the report every worker would repeat.
+    let limit = if args.raise_nofile { fdlimit::raise_nofile_limit()? } else { fdlimit::nofile_limit()? };
+    let budget = fdlimit::open_file_budget(limit);
+    args.options.open_files = Arc::new(OpenFileLimiter::new(args.max_open.map_or(budget, |max| max.min(budget))));
+    let paths = bench::file_paths(dir, files);
+    let paths = bench::slice(&paths, index, count);
+    let mut engine = Engine::new(threads).numa(args.numa).priority(args.priority);
+    engine.warm_up()?;
+
+    answer("ready")?;
+    for line in input.lines() {
+        let line = line?;
+        let phase = Phase::ALL.into_iter().find(|phase| phase.name() == line).ok_or_else(|| invalid_input(format!("unknown phase {}", line)))?;
+        let mut failures = Vec::new();
+        let start = Instant::now();
+        bench::phase(&args.options, format!("{} {}", strategy.label, phase.name()), format!("{}/{}", strategy.label, phase.name()), phase.name(), &mut failures, || engine.install(|| strategy.run_phase(phase, paths, &args.options)))?;
+        let elapsed = start.elapsed();
+        cli::report_failures(&failures);
+        answer(&format!("done {} {} {}", phase.name(), elapsed.as_nanos(), failures.len()))?;
+    }
+    Ok(())
+}
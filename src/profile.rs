@@ -0,0 +1,261 @@
+//! Flamegraphs of each benchmark phase, recorded with `perf` and drawn here, so a slow
+//! phase can be looked into without setting up profiling tools.
+//!
+//! A [`Profiler`] attaches `perf record` to this process for the length of one phase, then
+//! folds the samples' call stacks (`perf script`) and writes them next to an SVG flamegraph:
+//! `<strategy>-<phase>.folded` and `<strategy>-<phase>.svg`. The folded stacks are the
+//! format `flamegraph.pl` and `inferno` read, for diffing two runs. Stacks are walked by
+//! frame pointer, so build with `RUSTFLAGS=-Cforce-frame-pointers=yes` for complete ones.
+//! `perf` must be installed, and `kernel.perf_event_paranoid` must allow profiling this
+//! process (2 or lower for its own user).
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::treemap::escape;
+
+/// Samples per second per CPU.
+const FREQUENCY: u32 = 997;
+/// How long `perf record` may take to attach before the phase starts anyway.
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(2);
+
+const WIDTH: f64 = 1200.0;
+const FRAME_HEIGHT: f64 = 16.0;
+/// Frames narrower than this many pixels aren't drawn.
+const MIN_WIDTH: f64 = 0.1;
+/// About how wide a character of the frame labels is.
+const CHAR_WIDTH: f64 = 7.0;
+
+/// What `--profile` records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileKind {
+    Flamegraph,
+}
+
+impl FromStr for ProfileKind {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<ProfileKind> {
+        match s {
+            "flamegraph" => Ok(ProfileKind::Flamegraph),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown profile '{}', expected flamegraph", s))),
+        }
+    }
+}
+
+/// Records a profile per phase into a directory.
+#[derive(Debug)]
+pub struct Profiler {
+    dir: PathBuf,
+    /// The flamegraphs written so far.
+    written: Mutex<Vec<PathBuf>>,
+}
+
+impl Profiler {
+    /// A profiler writing into `dir`, created if missing. Fails if `perf` can't be run.
+    pub fn new(dir: &Path) -> io::Result<Profiler> {
+        let version = Command::new("perf").arg("--version").stdout(Stdio::null()).stderr(Stdio::null()).status();
+        if !version.is_ok_and(|status| status.success()) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "profiling needs perf (linux-tools) on the PATH"));
+        }
+        fs::create_dir_all(dir)?;
+        Ok(Profiler { dir: dir.to_path_buf(), written: Mutex::default() })
+    }
+
+    /// Starts sampling this process for `phase` of `strategy`, returning once `perf` is
+    /// recording.
+    pub fn start(&self, strategy: &str, phase: &str) -> io::Result<Recording<'_>> {
+        let name = format!("{}-{}", strategy, phase).replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "_");
+        let data = self.dir.join(format!("{}.perf.data", name));
+        let _ = fs::remove_file(&data);
+        let mut perf = Command::new("perf")
+            .args(["record", "--quiet", "-g", "-F", &FREQUENCY.to_string(), "-p", &std::process::id().to_string(), "-o"])
+            .arg(&data)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let start = Instant::now();
+        while !data.exists() && start.elapsed() < ATTACH_TIMEOUT {
+            if perf.try_wait()?.is_some() {
+                return Err(perf_failed("perf record", &mut perf));
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        Ok(Recording { profiler: self, name, data, perf })
+    }
+
+    /// The flamegraphs written so far.
+    pub fn written(&self) -> Vec<PathBuf> {
+        self.written.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// `perf` having exited early, with what it said.
+fn perf_failed(what: &str, perf: &mut Child) -> io::Error {
+    let mut stderr = String::new();
+    if let Some(mut pipe) = perf.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    io::Error::other(format!("{} failed: {}", what, stderr.trim()))
+}
+
+/// `perf record` running for one phase.
+#[derive(Debug)]
+pub struct Recording<'a> {
+    profiler: &'a Profiler,
+    name: String,
+    data: PathBuf,
+    perf: Child,
+}
+
+impl Recording<'_> {
+    /// Stops sampling and writes the folded stacks and flamegraph, returning the SVG's
+    /// path. A strategy profiled twice gets numbered files.
+    pub fn finish(mut self) -> io::Result<PathBuf> {
+        // perf writes its data out on SIGINT.
+        if unsafe { libc::kill(self.perf.id() as libc::pid_t, libc::SIGINT) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.perf.wait()?;
+        let script = Command::new("perf").args(["script", "-F", "comm,ip,sym", "-i"]).arg(&self.data).stderr(Stdio::null()).output()?;
+        let _ = fs::remove_file(&self.data);
+        if !script.status.success() {
+            return Err(io::Error::other(format!("perf script failed on {}", self.data.display())));
+        }
+        let folded = fold(&String::from_utf8_lossy(&script.stdout));
+
+        let mut written = self.profiler.written.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = &self.profiler.dir;
+        let svg = (1..).map(|n| if n == 1 { dir.join(format!("{}.svg", self.name)) } else { dir.join(format!("{}-{}.svg", self.name, n)) }).find(|path| !written.contains(path)).expect("some number is free");
+        let lines: String = folded.iter().map(|(stack, count)| format!("{} {}\n", stack, count)).collect();
+        fs::write(svg.with_extension("folded"), lines)?;
+        fs::write(&svg, flamegraph(&folded, &self.name))?;
+        written.push(svg.clone());
+        Ok(svg)
+    }
+}
+
+/// Folds `perf script -F comm,ip,sym` output into one line per distinct stack, root first
+/// and `;`-separated under the thread's name, with how many samples had it.
+pub fn fold(script: &str) -> Vec<(String, u64)> {
+    let mut stacks: BTreeMap<String, u64> = BTreeMap::new();
+    let mut add = |comm: &str, frames: &mut Vec<String>| {
+        if !comm.is_empty() {
+            frames.push(comm.to_string());
+            frames.reverse();
+            *stacks.entry(frames.join(";")).or_default() += 1;
+        }
+        frames.clear();
+    };
+    let (mut comm, mut frames) = (String::new(), Vec::new());
+    for line in script.lines() {
+        if line.trim().is_empty() {
+            add(&comm, &mut frames);
+            comm.clear();
+        } else if line.starts_with(char::is_whitespace) {
+            // `55d0c0a1b2c3 std::fs::write+0x23`, or `[unknown]` without symbols.
+            let symbol = line.trim().split_once(' ').map_or("[unknown]", |(_, symbol)| symbol.trim());
+            let symbol = match symbol.rsplit_once("+0x") {
+                Some((name, offset)) if offset.chars().all(|c| c.is_ascii_hexdigit()) => name,
+                _ => symbol,
+            };
+            frames.push(symbol.replace(';', ":"));
+        } else {
+            add(&comm, &mut frames);
+            comm = line.trim().trim_end_matches(':').replace(';', ":");
+        }
+    }
+    add(&comm, &mut frames);
+    stacks.into_iter().collect()
+}
+
+#[derive(Default)]
+struct Frame {
+    samples: u64,
+    children: BTreeMap<String, Frame>,
+}
+
+/// A warm colour from the frame's name, so a function keeps its colour across graphs.
+fn colour(name: &str) -> String {
+    let hash = name.bytes().fold(5381u32, |hash, byte| hash.wrapping_mul(33) ^ byte as u32);
+    format!("rgb({},{},{})", 205 + hash % 50, 80 + (hash >> 8) % 130, 40 + (hash >> 16) % 50)
+}
+
+fn draw(svg: &mut String, frames: &BTreeMap<String, Frame>, total: u64, x: f64, depth: usize, height: f64) {
+    let mut x = x;
+    for (name, frame) in frames {
+        let width = frame.samples as f64 / total as f64 * WIDTH;
+        if width >= MIN_WIDTH {
+            let y = height - (depth + 1) as f64 * FRAME_HEIGHT;
+            let percent = frame.samples as f64 / total as f64 * 100.0;
+            let fits = (width / CHAR_WIDTH) as usize;
+            let label = match name.chars().count() {
+                _ if fits < 3 => String::new(),
+                len if len <= fits => name.clone(),
+                _ => format!("{}..", name.chars().take(fits - 2).collect::<String>()),
+            };
+            let _ = writeln!(
+                svg,
+                "<g><title>{} ({} samples, {:.2}%)</title><rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"/><text x=\"{:.1}\" y=\"{:.1}\">{}</text></g>",
+                escape(name),
+                frame.samples,
+                percent,
+                x,
+                y,
+                width,
+                FRAME_HEIGHT - 1.0,
+                colour(name),
+                x + 3.0,
+                y + FRAME_HEIGHT - 4.0,
+                escape(&label)
+            );
+            draw(svg, &frame.children, total, x, depth + 1, height);
+        }
+        x += width;
+    }
+}
+
+/// An SVG flamegraph of `folded` stacks: each frame as wide as its share of the samples,
+/// callers below callees, siblings in name order. Hovering a frame shows its name and
+/// share.
+pub fn flamegraph(folded: &[(String, u64)], title: &str) -> String {
+    let mut root = Frame::default();
+    for (stack, samples) in folded {
+        root.samples += samples;
+        let mut frame = &mut root;
+        for name in stack.split(';') {
+            frame = frame.children.entry(name.to_string()).or_default();
+            frame.samples += samples;
+        }
+    }
+    fn depth(frame: &Frame) -> usize {
+        frame.children.values().map(|child| 1 + depth(child)).max().unwrap_or(0)
+    }
+    let height = (depth(&root) + 2) as f64 * FRAME_HEIGHT;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"11\">\n<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"14\">{} ({} samples)</text>\n",
+        WIDTH,
+        height,
+        WIDTH / 2.0,
+        FRAME_HEIGHT,
+        escape(title),
+        root.samples
+    );
+    if root.samples > 0 {
+        draw(&mut svg, &root.children, root.samples, 0.0, 0, height);
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
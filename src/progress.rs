@@ -0,0 +1,122 @@
+//! Progress reporting for the bulk phases.
+//!
+//! [`Progress`] only counts and calls back, leaving the drawing to its caller: the `io`
+//! binary's `--progress` bar draws on stderr itself, and a program that already uses a
+//! progress crate such as indicatif can set its bar's position from
+//! [`Progress::on_progress`] without this library depending on one.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+type Callback = Box<dyn Fn(usize, usize) + Send + Sync>;
+
+/// Counts files finished against files started. A batch that begins after the previous ones
+/// finished starts the count from zero; batches running side by side share the count.
+/// Targets run side by side each count on a [`Progress::target`] of their own, which also
+/// adds to the shared count.
+#[derive(Default)]
+pub struct Progress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+    begin: Mutex<()>,
+    stage: Mutex<String>,
+    callback: Option<Callback>,
+    /// The progress this one counts toward too, and the target it counts.
+    parent: Option<(Arc<Progress>, String)>,
+    /// Targets counting toward this one, whose stages make up its own.
+    targets: Mutex<Vec<Weak<Progress>>>,
+}
+
+impl Progress {
+    /// Calls `f(done, total)` from the worker threads after every file, so it must be cheap.
+    pub fn on_progress<F>(f: F) -> Progress
+    where
+        F: Fn(usize, usize) + Send + Sync + 'static,
+    {
+        Progress { callback: Some(Box::new(f)), ..Progress::default() }
+    }
+
+    /// A progress of its own for `label`, one of several targets run side by side, whose
+    /// files count toward `parent` as well. While targets are live, `parent`'s
+    /// [`Progress::stage`] names each target's stage.
+    pub fn target(parent: &Arc<Progress>, label: impl Into<String>) -> Arc<Progress> {
+        let target = Arc::new(Progress { parent: Some((parent.clone(), label.into())), ..Progress::default() });
+        let mut targets = parent.targets.lock().unwrap_or_else(|e| e.into_inner());
+        targets.retain(|target| target.strong_count() > 0);
+        targets.push(Arc::downgrade(&target));
+        target
+    }
+
+    /// Names the work in progress, e.g. `traditional_io create`, for live displays.
+    pub fn set_stage(&self, stage: impl Into<String>) {
+        *self.stage.lock().unwrap_or_else(|e| e.into_inner()) = stage.into();
+    }
+
+    /// The work in progress, or each live target's as `label: stage`, separated by commas.
+    pub fn stage(&self) -> String {
+        let targets: Vec<String> = self
+            .targets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter_map(|target| Some(format!("{}: {}", target.parent.as_ref()?.1, target.stage())))
+            .collect();
+        if targets.is_empty() {
+            return self.stage.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        }
+        targets.join(", ")
+    }
+
+    /// Announces `files` more files of work.
+    pub fn begin(&self, files: usize) {
+        if let Some((parent, _)) = &self.parent {
+            parent.begin(files);
+        }
+        let _guard = self.begin.lock().unwrap_or_else(|e| e.into_inner());
+        if self.done.load(Ordering::Acquire) >= self.total.load(Ordering::Acquire) {
+            self.done.store(0, Ordering::Release);
+            self.total.store(0, Ordering::Release);
+        }
+        self.total.fetch_add(files, Ordering::AcqRel);
+    }
+
+    /// Records one finished file.
+    pub fn advance(&self) {
+        if let Some((parent, _)) = &self.parent {
+            parent.advance();
+        }
+        let done = self.done.fetch_add(1, Ordering::AcqRel) + 1;
+        if let Some(callback) = &self.callback {
+            callback(done, self.total.load(Ordering::Acquire));
+        }
+    }
+
+    /// Counts `files` as finished without reporting them, for work abandoned after an error.
+    pub fn skip(&self, files: usize) {
+        if let Some((parent, _)) = &self.parent {
+            parent.skip(files);
+        }
+        self.done.fetch_add(files, Ordering::AcqRel);
+    }
+
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Acquire)
+    }
+
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Acquire)
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("done", &self.done())
+            .field("total", &self.total())
+            .field("callback", &self.callback.is_some())
+            .field("target", &self.parent.as_ref().map(|(_, label)| label))
+            .finish()
+    }
+}
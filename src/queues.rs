@@ -0,0 +1,156 @@
+//! How a block device's I/O spreads over its hardware queues, for telling when more
+//! workers stop helping because every queue is already busy.
+//!
+//! blk-mq gives NVMe (and virtio-blk) devices one hardware queue per group of CPUs, listed
+//! under `/sys/block/<dev>/mq`. The kernel keeps no per-queue I/O counters in sysfs, so
+//! completions are counted from each queue's interrupt in `/proc/interrupts`
+//! (`nvme0q3`, `virtio1-req.2`), or from blk-mq's debugfs `hctx*/queued` when interrupt
+//! names don't match, as with polled queues; debugfs needs root.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::health::Device;
+
+/// One hardware queue and the CPUs that submit to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Queue {
+    pub index: usize,
+    pub cpus: Vec<usize>,
+}
+
+/// The device's hardware queues, in order; empty for devices that aren't blk-mq.
+pub fn hardware_queues(device: &Device) -> Vec<Queue> {
+    let Ok(entries) = fs::read_dir(device.sysfs.join("mq")) else {
+        return Vec::new();
+    };
+    let mut queues: Vec<Queue> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let index = entry.file_name().to_str()?.parse().ok()?;
+            let cpus = fs::read_to_string(entry.path().join("cpu_list")).map_or_else(|_| Vec::new(), |list| parse_cpu_list(&list));
+            Some(Queue { index, cpus })
+        })
+        .collect();
+    queues.sort_by_key(|queue| queue.index);
+    queues
+}
+
+/// CPUs in `0, 1, 2` or `0-3,8` form.
+fn parse_cpu_list(list: &str) -> Vec<usize> {
+    list.split(',')
+        .filter_map(|part| {
+            let part = part.trim();
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            Some(start.trim().parse::<usize>().ok()?..=end.trim().parse::<usize>().ok()?)
+        })
+        .flatten()
+        .collect()
+}
+
+/// Where a device's per-queue counts are read from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// Interrupt names for queues 0, 1, ... in `/proc/interrupts`.
+    Interrupts(Vec<String>),
+    /// `queued` files of the debugfs hardware contexts.
+    Debugfs(Vec<PathBuf>),
+}
+
+/// Reads per-queue completion counts of one device.
+#[derive(Debug, Clone)]
+pub struct QueueCounter {
+    source: Source,
+}
+
+impl QueueCounter {
+    /// A counter for `device`, or `None` when neither source has its queues.
+    pub fn new(device: &Device) -> Option<QueueCounter> {
+        let queues = hardware_queues(device).len();
+        if queues == 0 {
+            return None;
+        }
+        // The controller (`nvme0`) or virtio device (`virtio1`) names the interrupts.
+        let parent = fs::canonicalize(device.sysfs.join("device")).ok()?.file_name()?.to_string_lossy().into_owned();
+        let names: Vec<String> = if parent.starts_with("nvme") {
+            // nvme0q0 is the admin queue; I/O queues start at q1.
+            (1..=queues).map(|i| format!("{}q{}", parent, i)).collect()
+        } else {
+            (0..queues).map(|i| format!("{}-req.{}", parent, i)).collect()
+        };
+        let interrupts = read_interrupts();
+        if names.iter().all(|name| interrupts.iter().any(|(n, _)| n == name)) {
+            return Some(QueueCounter { source: Source::Interrupts(names) });
+        }
+        let debugfs = PathBuf::from("/sys/kernel/debug/block").join(&device.name);
+        let files: Vec<PathBuf> = (0..queues).map(|i| debugfs.join(format!("hctx{}", i)).join("queued")).collect();
+        if files.iter().all(|file| fs::read_to_string(file).is_ok()) {
+            return Some(QueueCounter { source: Source::Debugfs(files) });
+        }
+        None
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    /// Counts per queue so far; differences between two samples give a phase's share.
+    pub fn sample(&self) -> QueueSample {
+        let counts = match &self.source {
+            Source::Interrupts(names) => {
+                let interrupts = read_interrupts();
+                names.iter().map(|name| interrupts.iter().find(|(n, _)| n == name).map_or(0, |&(_, count)| count)).collect()
+            }
+            Source::Debugfs(files) => files.iter().map(|file| fs::read_to_string(file).ok().and_then(|s| s.trim().parse().ok()).unwrap_or(0)).collect(),
+        };
+        QueueSample(counts)
+    }
+}
+
+/// Every interrupt's name (its last column) and total count over all CPUs.
+fn read_interrupts() -> Vec<(String, u64)> {
+    let Ok(text) = fs::read_to_string("/proc/interrupts") else {
+        return Vec::new();
+    };
+    let mut lines = text.lines();
+    let cpus = lines.next().map_or(0, |header| header.split_whitespace().count());
+    lines
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(':')?;
+            let fields: Vec<&str> = rest.split_whitespace().collect();
+            let count = fields.iter().take(cpus).map_while(|field| field.parse::<u64>().ok()).sum();
+            Some((fields.last()?.to_string(), count))
+        })
+        .collect()
+}
+
+/// Completions per hardware queue, cumulative or over an interval.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueueSample(pub Vec<u64>);
+
+impl QueueSample {
+    /// What happened between `earlier` and this sample.
+    pub fn since(&self, earlier: &QueueSample) -> QueueSample {
+        QueueSample(self.0.iter().zip(earlier.0.iter().chain(std::iter::repeat(&0))).map(|(now, then)| now.saturating_sub(*then)).collect())
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+
+    /// Queues that completed anything.
+    pub fn active(&self) -> usize {
+        self.0.iter().filter(|&&count| count > 0).count()
+    }
+
+    /// The busiest queue's share of completions over an even share: 1 when the load is
+    /// spread evenly, the queue count when one queue takes it all; 0 without completions.
+    pub fn imbalance(&self) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+        let max = self.0.iter().copied().max().unwrap_or(0);
+        max as f64 * self.0.len() as f64 / total as f64
+    }
+}
@@ -0,0 +1,138 @@
+//! A tmpfs directory to run the benchmarks in, as the upper bound with storage taken out:
+//! what the same phases cost when every write lands in the page cache and nothing is ever
+//! written back.
+//!
+//! [`Ramdisk::setup`] mounts a tmpfs of the requested size when the process may mount
+//! (root, or `CAP_SYS_ADMIN`), and otherwise borrows a directory on a tmpfs that is
+//! already mounted and has the room: `/dev/shm`, then `$XDG_RUNTIME_DIR`. Dropping the
+//! [`Ramdisk`] removes what it created, unmounting its own tmpfs, and so does
+//! [`crate::rundir::remove_live`] for a process exiting without unwinding, as on a second
+//! Ctrl-C. Mounting is Linux only; elsewhere only an existing tmpfs can be used.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::filesystem::Filesystem;
+
+/// Existing tmpfs mounts tried when mounting isn't allowed, in order.
+const SHARED_TMPFS: &[&str] = &["/dev/shm"];
+
+/// Every [`Ramdisk`] not yet dropped, and whether it is mounted, for [`remove_live`].
+static LIVE: Mutex<Vec<(PathBuf, bool)>> = Mutex::new(Vec::new());
+
+#[derive(Debug)]
+pub struct Ramdisk {
+    dir: PathBuf,
+    /// Whether `dir` is a tmpfs this process mounted, rather than a directory on a shared one.
+    mounted: bool,
+}
+
+impl Ramdisk {
+    /// A tmpfs directory with room for `size` bytes.
+    pub fn setup(size: u64) -> io::Result<Ramdisk> {
+        let name = format!("io-ramdisk-{}", std::process::id());
+        let dir = env::temp_dir().join(&name);
+        fs::create_dir_all(&dir)?;
+        match mount_tmpfs(&dir, size) {
+            Ok(()) => return Ok(Ramdisk::live(dir, true)),
+            Err(_) => fs::remove_dir(&dir)?,
+        }
+
+        let runtime_dir = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+        let candidates = SHARED_TMPFS.iter().map(PathBuf::from).chain(runtime_dir);
+        let mut tried = Vec::new();
+        for base in candidates {
+            let Some(filesystem) = Filesystem::of(&base).filter(|filesystem| filesystem.fs_type == "tmpfs") else {
+                continue;
+            };
+            if filesystem.available.is_some_and(|available| available < size) {
+                tried.push(format!("{} has {} bytes free", base.display(), filesystem.available.unwrap_or(0)));
+                continue;
+            }
+            let dir = base.join(&name);
+            fs::create_dir_all(&dir)?;
+            return Ok(Ramdisk::live(dir, false));
+        }
+        let reason = if tried.is_empty() { "no tmpfs is mounted at /dev/shm or $XDG_RUNTIME_DIR".to_string() } else { tried.join(", ") };
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("can't mount a {}-byte tmpfs (needs root or CAP_SYS_ADMIN) and {}", size, reason),
+        ))
+    }
+
+    fn live(dir: PathBuf, mounted: bool) -> Ramdisk {
+        LIVE.lock().unwrap_or_else(|e| e.into_inner()).push((dir.clone(), mounted));
+        Ramdisk { dir, mounted }
+    }
+
+    /// Where to put files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether this is a tmpfs of its own, sized as asked, rather than a shared one.
+    pub fn mounted(&self) -> bool {
+        self.mounted
+    }
+}
+
+impl Drop for Ramdisk {
+    fn drop(&mut self) {
+        LIVE.lock().unwrap_or_else(|e| e.into_inner()).retain(|(dir, _)| *dir != self.dir);
+        remove(&self.dir, self.mounted);
+    }
+}
+
+fn remove(dir: &Path, mounted: bool) {
+    let _ = fs::remove_dir_all(dir);
+    if mounted {
+        unmount(dir);
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+/// Removes every live [`Ramdisk`] now, unmounting those this process mounted, for a
+/// process about to exit without unwinding.
+pub fn remove_live() {
+    let live = std::mem::take(&mut *LIVE.lock().unwrap_or_else(|e| e.into_inner()));
+    for (dir, mounted) in live {
+        remove(&dir, mounted);
+    }
+}
+
+/// Mounts a private tmpfs of `size` bytes on `dir`, readable only by this user.
+#[cfg(target_os = "linux")]
+fn mount_tmpfs(dir: &Path, size: u64) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let target = CString::new(dir.as_os_str().as_bytes()).map_err(io::Error::other)?;
+    let data = CString::new(format!("size={},mode=0700", size)).map_err(io::Error::other)?;
+    let result = unsafe { libc::mount(c"tmpfs".as_ptr(), target.as_ptr(), c"tmpfs".as_ptr(), libc::MS_NOSUID | libc::MS_NODEV, data.as_ptr().cast()) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mount_tmpfs(_dir: &Path, _size: u64) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "mounting tmpfs is only implemented on Linux"))
+}
+
+#[cfg(target_os = "linux")]
+fn unmount(dir: &Path) {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    if let Ok(dir) = CString::new(dir.as_os_str().as_bytes()) {
+        // Lazily, so a straggling open file can't keep the mount around.
+        unsafe { libc::umount2(dir.as_ptr(), libc::MNT_DETACH) };
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unmount(_dir: &Path) {}
@@ -0,0 +1,235 @@
+//! One large file written by several threads at once, for dataset and model-file tooling
+//! whose outputs run to gigabytes. A single writer streams the file front to back, and one
+//! core copying into the page cache can be what limits it well before the device does.
+//! [`write_parallel`] instead sets the file's length and gives each writer a disjoint range
+//! of whole [`SEGMENT`]s, written with positioned writes or copied into a shared mapping.
+//! Whether that wins depends on the filesystem: some serialise writers of one file on its
+//! inode lock, and a mapping's page faults can cost more than the copies they replace,
+//! which `io bench ranged` measures.
+//!
+//! The data comes from host memory; nothing here moves it from a GPU or bypasses the page
+//! cache.
+
+use std::fs::OpenOptions;
+#[cfg(feature = "bench")]
+use std::fs::{self, File};
+use std::io::{self, Write};
+#[cfg(feature = "bench")]
+use std::io::Read;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::thread;
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "mmap")]
+use memmap2::MmapMut;
+
+#[cfg(feature = "bench")]
+use crate::bench::{Options, SyncMode};
+#[cfg(feature = "bench")]
+#[cfg(feature = "bench")]
+use crate::order;
+#[cfg(feature = "bench")]
+use crate::pattern::{Generator, Pattern};
+
+/// Bytes each write call covers, and the unit the file is divided among writers in.
+pub const SEGMENT: usize = 8 << 20;
+
+/// The file `io bench ranged` writes unless given a size.
+#[cfg(feature = "bench")]
+pub const DEFAULT_LEN: u64 = 1 << 30;
+
+/// How a file is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// One thread writing front to back at the file position; the baseline.
+    Sequential,
+    /// Each writer `pwrite`s its range into the file, whose length is set first.
+    Pwrite,
+    /// Each writer copies its range into one shared writable mapping of the file.
+    #[cfg(feature = "mmap")]
+    Mmap,
+}
+
+impl Method {
+    pub const ALL: &[Method] = &[
+        Method::Sequential,
+        Method::Pwrite,
+        #[cfg(feature = "mmap")]
+        Method::Mmap,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Sequential => "sequential",
+            Method::Pwrite => "pwrite",
+            #[cfg(feature = "mmap")]
+            Method::Mmap => "mmap",
+        }
+    }
+
+    /// Whether this method spreads the file over several writers.
+    pub fn is_parallel(self) -> bool {
+        self != Method::Sequential
+    }
+}
+
+/// The byte range writer `writer` of `writers` covers in a file of `len` bytes: an equal
+/// share of its [`SEGMENT`]s, the first writers taking one more when they don't divide
+/// evenly. Writers beyond the number of segments get an empty range.
+pub fn range(len: u64, writers: usize, writer: usize) -> Range<u64> {
+    let segments = len.div_ceil(SEGMENT as u64);
+    let writers = writers.max(1) as u64;
+    let (share, extra) = (segments / writers, segments % writers);
+    let writer = writer as u64;
+    let first = writer * share + writer.min(extra);
+    let count = share + u64::from(writer < extra);
+    (first * SEGMENT as u64).min(len)..((first + count) * SEGMENT as u64).min(len)
+}
+
+/// The segments of `range`, each at most [`SEGMENT`] bytes.
+fn segments(range: Range<u64>) -> impl Iterator<Item = Range<u64>> {
+    range.clone().step_by(SEGMENT).map(move |start| start..(start + SEGMENT as u64).min(range.end))
+}
+
+/// Writes `bytes` as the whole of `path`, creating or truncating it, with `writers` threads
+/// each writing a [`range`] of it as `method` says; [`Method::Sequential`] ignores
+/// `writers`. With `durable`, the data is synced before this returns.
+pub fn write_parallel(path: &Path, bytes: &[u8], writers: usize, method: Method, durable: bool) -> io::Result<()> {
+    write_with(path, bytes.len() as u64, writers, method, durable, |range| &bytes[range.start as usize..range.end as usize])
+}
+
+/// [`write_parallel`] for a file of `len` bytes whose contents `source` gives a segment at
+/// a time.
+fn write_with<'a, F>(path: &Path, len: u64, writers: usize, method: Method, durable: bool, source: F) -> io::Result<()>
+where
+    F: Fn(Range<u64>) -> &'a [u8] + Sync,
+{
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+    let writers = writers.max(1).min(len.div_ceil(SEGMENT as u64).max(1) as usize);
+    match method {
+        Method::Sequential => {
+            for segment in segments(0..len) {
+                file.write_all(source(segment))?;
+            }
+        }
+        Method::Pwrite => {
+            // Set first, so no writer extends the file under another.
+            file.set_len(len)?;
+            let file = &file;
+            let source = &source;
+            thread::scope(|scope| {
+                let handles: Vec<_> = (0..writers)
+                    .map(|writer| scope.spawn(move || segments(range(len, writers, writer)).try_for_each(|segment| file.write_all_at(source(segment.clone()), segment.start))))
+                    .collect();
+                handles.into_iter().try_for_each(|handle| handle.join().unwrap_or_else(|_| Err(io::Error::other("a writer panicked"))))
+            })?;
+        }
+        #[cfg(feature = "mmap")]
+        Method::Mmap => {
+            file.set_len(len)?;
+            if len > 0 {
+                let mut map = unsafe { MmapMut::map_mut(&file)? };
+                let source = &source;
+                thread::scope(|scope| {
+                    let mut rest = &mut map[..];
+                    for writer in 0..writers {
+                        let range = range(len, writers, writer);
+                        let (mine, after) = rest.split_at_mut((range.end - range.start) as usize);
+                        rest = after;
+                        scope.spawn(move || {
+                            for segment in segments(range.clone()) {
+                                let at = (segment.start - range.start) as usize;
+                                let bytes = source(segment);
+                                mine[at..at + bytes.len()].copy_from_slice(bytes);
+                            }
+                        });
+                    }
+                });
+                if durable {
+                    map.flush()?;
+                }
+            }
+        }
+    }
+    if durable {
+        file.sync_data()?;
+    }
+    Ok(())
+}
+
+/// Every segment of `file` is `segment`, cut short at the end.
+#[cfg(feature = "bench")]
+fn check(mut file: File, len: u64, segment: &[u8]) -> io::Result<bool> {
+    let mut buf = vec![0; SEGMENT];
+    for range in segments(0..len) {
+        let buf = &mut buf[..(range.end - range.start) as usize];
+        file.read_exact(buf)?;
+        if buf[..] != segment[..buf.len()] {
+            return Ok(false);
+        }
+    }
+    Ok(file.read(&mut [0])? == 0)
+}
+
+/// One method at one number of writers.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy)]
+pub struct RangedRun {
+    pub method: Method,
+    pub writers: usize,
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Default)]
+pub struct RangedResult {
+    pub len: u64,
+    /// Sequential first, then each parallel method at each number of writers, with its
+    /// fastest time.
+    pub runs: Vec<RangedRun>,
+}
+
+/// Writes a file of `len` bytes in `dir_path` sequentially, then with each parallel
+/// [`Method`] at each of `writers`, removing it after each; [`order::ROUNDS`] times with
+/// the configurations taking turns, each keeping its fastest. Every segment holds the same
+/// generated bytes, so producing them costs nothing during the timing. `options.sync`
+/// other than none syncs the file inside the timing; `options.verify` reads it back after
+/// each write.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, len: u64, writers: &[usize], options: &Options) -> io::Result<RangedResult> {
+    let generator = options.generator.unwrap_or(Generator::new(order::SEED, Pattern::Random));
+    let segment = generator.contents(0, SEGMENT.min(len as usize));
+    let path = dir_path.join("ranged.bin");
+    let durable = options.sync != SyncMode::None;
+    let mut result = RangedResult { len, runs: Vec::new() };
+    let plan: Vec<(Method, usize)> = Method::ALL
+        .iter()
+        .flat_map(|&method| {
+            let counts: &[usize] = if method.is_parallel() { writers } else { &[1] };
+            counts.iter().map(move |&writers| (method, writers))
+        })
+        .collect();
+    for round in 0..order::ROUNDS {
+        for &(method, writers) in &plan {
+            if options.cancel.is_cancelled() {
+                return Ok(result);
+            }
+            options.progress.set_stage(format!("Ranged {} with {} writers ({}/{})", method.name(), writers, round + 1, order::ROUNDS));
+            let start = Instant::now();
+            write_with(&path, len, writers, method, durable, |range| &segment[..(range.end - range.start) as usize])?;
+            let elapsed = start.elapsed();
+            if options.verify && !check(File::open(&path)?, len, &segment)? {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} with {} writers left {} with the wrong contents", method.name(), writers, path.display())));
+            }
+            fs::remove_file(&path)?;
+            match result.runs.iter_mut().find(|run| run.method == method && run.writers == writers) {
+                Some(fastest) => fastest.elapsed = fastest.elapsed.min(elapsed),
+                None => result.runs.push(RangedRun { method, writers, elapsed }),
+            }
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,84 @@
+//! The read phase on its own, once per [`ReadMethod`]: `read(2)` into a buffer, copying out
+//! of a mapping, and faulting a mapping's pages in without copying. The strategies only map
+//! files to update them (`adaptive_io` aside); this covers reads the same way `io bench
+//! update` covers updates.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::bench::{self, Failure, Options, ReadMethod};
+use crate::cache;
+use crate::readonly::DataSet;
+
+#[derive(Debug, Clone)]
+pub struct MethodResult {
+    pub method: ReadMethod,
+    /// Reading every file.
+    pub read: Duration,
+    /// Reading every file again with [`Options::prefetch`], when it is set.
+    pub prefetched: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReadResult {
+    /// The workload's bytes, which every method reads.
+    pub read_bytes: u64,
+    pub methods: Vec<MethodResult>,
+    pub failures: Vec<Failure>,
+}
+
+/// Creates the configured workload in `dir_path` untimed, then times reading every file with
+/// each of `methods` in turn, evicting the files from the page cache before each when
+/// `options.cold_read` is set (otherwise all but the first find them cached). With
+/// `options.prefetch` each method reads the files twice, without prefetching and then with
+/// it, so the difference is the pipeline's win. With `options.verify` every method checks
+/// what it read.
+pub fn run(dir_path: &Path, methods: &[ReadMethod], options: &Options) -> io::Result<ReadResult> {
+    let files = options.workload.files;
+    let mut result = ReadResult { read_bytes: files as u64 * options.workload.size() as u64, ..ReadResult::default() };
+    options.failures.take("");
+    let paths = bench::file_paths(dir_path, files);
+    bench::phase(options, "Read create", "read/create", "create", &mut result.failures, || {
+        bench::each_indexed(&paths, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+    })?;
+
+    time_methods(&paths, methods, options, &mut result)?;
+    Ok(result)
+}
+
+/// Times reading the files of `set` in place with each of `methods`, as [`run`] does the
+/// generated workload, for a user's own tree picked with [`DataSet::select`]. Nothing is
+/// written. `options.verify` is refused: there is no known content to check against.
+pub fn run_set(set: &DataSet, methods: &[ReadMethod], options: &Options) -> io::Result<ReadResult> {
+    if options.verify {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "verifying reads needs the generated workload; an existing tree's contents aren't known"));
+    }
+    let mut result = ReadResult { read_bytes: set.bytes(), ..ReadResult::default() };
+    options.failures.take("");
+    time_methods(set.paths(), methods, options, &mut result)?;
+    Ok(result)
+}
+
+/// Reads `paths` with each of `methods`, adding their times and failures to `result`.
+fn time_methods(paths: &[PathBuf], methods: &[ReadMethod], options: &Options, result: &mut ReadResult) -> io::Result<()> {
+    let plain = Options { prefetch: None, verify: options.verify, ..options.with_workload(options.workload.clone()) };
+    let time = |options: &Options, method: ReadMethod, stage: String, name: &'static str, failures: &mut Vec<Failure>| -> io::Result<Duration> {
+        if options.cold_read {
+            cache::evict_from_cache(paths)?;
+        }
+        let start = Instant::now();
+        bench::phase(options, format!("Read {}", stage), format!("read/{}", stage), name, failures, || bench::read_files_by(paths, options, method))?;
+        Ok(start.elapsed())
+    };
+    for &method in methods {
+        let read = time(&plain, method, method.name().to_string(), "read", &mut result.failures)?;
+        let prefetched = match options.prefetch {
+            Some(how) => Some(time(options, method, format!("{}+{}", method, how.name()), "read prefetched", &mut result.failures)?),
+            None => None,
+        };
+        result.methods.push(MethodResult { method, read, prefetched });
+    }
+    Ok(())
+}
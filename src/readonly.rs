@@ -0,0 +1,260 @@
+//! Read-only benchmarks over existing data, safe to point at production trees.
+//!
+//! A [`DataSet`] is built only by [`DataSet::discover`], which lists an existing tree, or
+//! [`DataSet::select`], which lists the files of one a [`Selector`] picks, and hands out
+//! nothing but [`ReadOnlyFile`]s: handles opened `O_RDONLY` that implement `Read` and
+//! `Seek` but not `Write`, and don't expose the `File` inside. The phases here take a
+//! `DataSet`, so no create, update or delete backend can be reached from them. On
+//! Linux files are opened with `O_NOATIME` where the caller owns them, so reading doesn't
+//! even update access times.
+//!
+//! Trees too big to list in reasonable time can be sampled instead with [`run_sampled`],
+//! which extrapolates the totals and per-phase work from random walks (see
+//! [`crate::sample`]) and opens files the same read-only way.
+
+use std::fs::{File, Metadata, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "bench")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+#[cfg(feature = "bench")]
+use crate::breakdown::Breakdown;
+#[cfg(feature = "bench")]
+use crate::buffers;
+use crate::order::{self, ReadOrder};
+#[cfg(feature = "bench")]
+use crate::population;
+use crate::population::Manifest;
+use crate::select::Selector;
+#[cfg(feature = "bench")]
+use crate::sample::{Estimate, Sampler, TreeSample};
+#[cfg(feature = "bench")]
+use crate::trace::OpId;
+
+/// A file of a [`DataSet`], open for reading only.
+#[derive(Debug)]
+pub struct ReadOnlyFile(File);
+
+impl ReadOnlyFile {
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        self.0.metadata()
+    }
+}
+
+impl Read for ReadOnlyFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Seek for ReadOnlyFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+/// The regular files under a directory, which can only be read.
+#[derive(Debug, Clone)]
+pub struct DataSet {
+    root: PathBuf,
+    paths: Vec<PathBuf>,
+    sizes: Vec<u64>,
+    bytes: u64,
+}
+
+impl DataSet {
+    /// Lists every regular file under `root` without following symlinks.
+    pub fn discover(root: &Path) -> io::Result<DataSet> {
+        let manifest = Manifest::discover("readonly", root)?;
+        let sizes = manifest.entries.iter().map(|entry| entry.size).collect();
+        Ok(DataSet { root: root.to_path_buf(), paths: manifest.paths(), sizes, bytes: manifest.total_bytes() })
+    }
+
+    /// Lists the regular files under `root` that `selector` picks.
+    pub fn select(root: &Path, selector: &Selector) -> io::Result<DataSet> {
+        let mut manifest = Manifest::discover("readonly", root)?;
+        manifest.entries.retain(|entry| selector.matches(&entry.path, entry.size));
+        let sizes = manifest.entries.iter().map(|entry| entry.size).collect();
+        Ok(DataSet { root: root.to_path_buf(), paths: manifest.paths(), sizes, bytes: manifest.total_bytes() })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Each file's size when it was listed, in the order of [`DataSet::paths`].
+    pub fn sizes(&self) -> &[u64] {
+        &self.sizes
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Total size of the files when they were listed.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Puts the files in `order` instead of the path order they were listed in.
+    pub fn arrange(&mut self, order: ReadOrder) {
+        let indices = order::arrange_sized(order, &self.paths, &self.sizes);
+        self.paths = indices.iter().map(|&i| self.paths[i].clone()).collect();
+        self.sizes = indices.iter().map(|&i| self.sizes[i]).collect();
+    }
+
+    /// Opens `path`, which should be one of [`DataSet::paths`], for reading.
+    pub fn open(&self, path: &Path) -> io::Result<ReadOnlyFile> {
+        open(path)
+    }
+}
+
+/// Opens `path` read-only, without updating its access time where allowed.
+fn open(path: &Path) -> io::Result<ReadOnlyFile> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        // O_NOATIME is refused with EPERM for files the caller doesn't own.
+        match OpenOptions::new().read(true).custom_flags(libc::O_NOATIME).open(path) {
+            Err(e) if e.raw_os_error() == Some(libc::EPERM) => {}
+            opened => return opened.map(ReadOnlyFile),
+        }
+    }
+    OpenOptions::new().read(true).open(path).map(ReadOnlyFile)
+}
+
+/// One timed read-only phase.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadPhase {
+    /// `fstat` of every file through an open handle.
+    Stat,
+    /// Every file read whole.
+    Read,
+    /// Every file read and hashed (FNV-1a), as backup and sync tools do.
+    Hash,
+}
+
+#[cfg(feature = "bench")]
+impl ReadPhase {
+    pub const ALL: [ReadPhase; 3] = [ReadPhase::Stat, ReadPhase::Read, ReadPhase::Hash];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ReadPhase::Stat => "stat",
+            ReadPhase::Read => "read",
+            ReadPhase::Hash => "hash",
+        }
+    }
+
+    /// Does the phase's work on `file`, returning the bytes read.
+    fn run(self, mut file: ReadOnlyFile) -> io::Result<u64> {
+        match self {
+            ReadPhase::Stat => file.metadata().map(|_| 0),
+            ReadPhase::Read => buffers::with_buffer(|buf| file.read_to_end(buf).map(|n| n as u64)),
+            ReadPhase::Hash => {
+                let len = file.metadata()?.len();
+                population::hash_reader(&mut file).map(|_| len)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct ReadOnlyResult {
+    /// Each phase's time and the bytes it read.
+    pub times: Vec<(ReadPhase, Duration, u64)>,
+    /// Files by extension and depth, with the time every phase spent on them together.
+    pub breakdown: Breakdown,
+    pub failures: Vec<Failure>,
+}
+
+/// Times each [`ReadPhase`] over `set`. Files that vanish or can't be read are recorded as
+/// failures rather than ending the run, since the data set is live.
+#[cfg(feature = "bench")]
+pub fn run(set: &DataSet, options: &Options) -> io::Result<ReadOnlyResult> {
+    let mut result = ReadOnlyResult::default();
+    let nanos: Vec<AtomicU64> = set.paths().iter().map(|_| AtomicU64::new(0)).collect();
+    options.failures.take("");
+    for phase in ReadPhase::ALL {
+        let bytes = AtomicU64::new(0);
+        let start = Instant::now();
+        bench::phase(options, format!("Read-only {}", phase.name()), format!("readonly/{}", phase.name()), phase.name(), &mut result.failures, || {
+            let scope = options.op_scope.get();
+            bench::each_indexed(set.paths(), options, |index, path| {
+                let file_start = Instant::now();
+                let outcome = set.open(path).and_then(|file| phase.run(file));
+                nanos[index].fetch_add(file_start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                match outcome {
+                    Ok(read) => {
+                        bytes.fetch_add(read, Ordering::Relaxed);
+                    }
+                    Err(e) => options.failures.record(OpId::new(&scope, path), path, e.to_string()),
+                }
+                Ok(())
+            })
+        })?;
+        result.times.push((phase, start.elapsed(), bytes.into_inner()));
+    }
+    for ((path, size), nanos) in set.paths().iter().zip(set.sizes()).zip(nanos) {
+        result.breakdown.add(set.root(), path, *size, Duration::from_nanos(nanos.into_inner()));
+    }
+    Ok(result)
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct SampledResult {
+    pub tree: TreeSample,
+    /// Each phase's estimated work, the sum of its per-file times in seconds, and the
+    /// bytes it would read.
+    pub times: Vec<(ReadPhase, Estimate, Estimate)>,
+    pub failures: Vec<Failure>,
+}
+
+/// Estimates the tree's totals and each [`ReadPhase`]'s work over `root` from `sampler`'s
+/// random walks, without listing the whole tree. Every phase runs on each sampled file on
+/// one thread, in the same order as [`run`]; files that can't be read count as taking no
+/// time and are recorded as failures.
+#[cfg(feature = "bench")]
+pub fn run_sampled(root: &Path, sampler: &Sampler, options: &Options) -> io::Result<SampledResult> {
+    options.failures.take("");
+    let mut failures = Vec::new();
+    let tree = bench::phase(options, "Read-only sample", "readonly/sample", "sample", &mut failures, || {
+        let scope = options.op_scope.get();
+        sampler.run_measuring(root, |path| {
+            options.pause.wait();
+            let mut values = Vec::with_capacity(ReadPhase::ALL.len() * 2);
+            for phase in ReadPhase::ALL {
+                let start = Instant::now();
+                let outcome = open(path).and_then(|file| phase.run(file));
+                let elapsed = start.elapsed();
+                match outcome {
+                    Ok(read) => values.extend([elapsed.as_secs_f64(), read as f64]),
+                    Err(e) => {
+                        options.failures.record(OpId::new(&scope, path), path, e.to_string());
+                        values.extend([0.0, 0.0]);
+                    }
+                }
+            }
+            values
+        })
+    })?;
+    let times = ReadPhase::ALL.iter().enumerate().map(|(i, &phase)| (phase, tree.measured(2 * i), tree.measured(2 * i + 1))).collect();
+    Ok(SampledResult { tree, times, failures })
+}
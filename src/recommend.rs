@@ -0,0 +1,131 @@
+//! Picking the fastest strategy for each phase of a run, for people reading the results
+//! and for tools that configure themselves from a calibration run.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use ::io::filesystem::Filesystem;
+use ::io::human::{self, Align, Table};
+use ::io::json::Value;
+
+use crate::baseline::{Baseline, PHASES};
+use crate::cli::ByteSize;
+
+/// The fastest strategy for one phase, and how far ahead of the others it was.
+#[derive(Debug, Clone)]
+pub struct Choice {
+    pub phase: &'static str,
+    pub strategy: String,
+    pub ms: f64,
+    /// The second fastest, when more than one strategy ran the phase.
+    pub runner_up: Option<(String, f64)>,
+    /// The slowest strategy's time.
+    pub slowest_ms: f64,
+}
+
+impl Choice {
+    /// How much longer `other_ms` took than the choice, in percent.
+    fn ahead_of(&self, other_ms: f64) -> f64 {
+        if self.ms > 0.0 { (other_ms / self.ms - 1.0) * 100.0 } else { 0.0 }
+    }
+
+    fn to_json(&self) -> Value {
+        Value::object()
+            .with("phase", self.phase)
+            .with("strategy", self.strategy.as_str())
+            .with("ms", self.ms)
+            .with("runner_up", self.runner_up.as_ref().map(|(label, _)| label.as_str()))
+            .with("runner_up_ms", self.runner_up.as_ref().map(|&(_, ms)| ms))
+            .with("runner_up_slower_percent", self.runner_up.as_ref().map(|&(_, ms)| self.ahead_of(ms)))
+            .with("slowest_slower_percent", self.ahead_of(self.slowest_ms))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Recommendation {
+    pub files: usize,
+    pub size: usize,
+    pub threads: usize,
+    /// Such as `ext4/NVMe`, when the filesystem was found.
+    pub storage: Option<String>,
+    pub choices: Vec<Choice>,
+}
+
+/// `ext4/NVMe`, `xfs/HDD`, or only the type for filesystems without a device.
+fn storage(filesystem: &Filesystem) -> String {
+    let kind = match (&filesystem.device, filesystem.rotational) {
+        (Some(device), Some(false)) if device.name.starts_with("nvme") => "/NVMe",
+        (Some(_), Some(false)) => "/SSD",
+        (Some(_), Some(true)) => "/HDD",
+        _ => "",
+    };
+    format!("{}{}", filesystem.fs_type, kind)
+}
+
+impl Recommendation {
+    /// The fastest strategy per phase of `results`, or `None` with fewer than two
+    /// strategies to choose between.
+    pub fn from_results(results: &Baseline) -> Option<Recommendation> {
+        if results.results.len() < 2 {
+            return None;
+        }
+        let choices = PHASES
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &phase)| {
+                let mut times: Vec<(&str, f64)> = results.results.iter().map(|(label, ms)| (label.as_str(), ms[i])).collect();
+                times.sort_by(|a, b| a.1.total_cmp(&b.1));
+                let &(strategy, ms) = times.first()?;
+                Some(Choice {
+                    phase,
+                    strategy: strategy.to_string(),
+                    ms,
+                    runner_up: times.get(1).map(|&(label, ms)| (label.to_string(), ms)),
+                    slowest_ms: times.last().map_or(ms, |&(_, ms)| ms),
+                })
+            })
+            .collect();
+        Some(Recommendation { files: results.files, size: results.size, threads: results.threads, storage: results.filesystem.as_ref().map(storage), choices })
+    }
+
+    /// `For 10,000 files of 100 B on ext4/NVMe with 8 threads: Traditional create, ...`.
+    pub fn headline(&self) -> String {
+        let storage = self.storage.as_ref().map_or_else(String::new, |storage| format!(" on {}", storage));
+        let choices: Vec<String> = self.choices.iter().map(|choice| format!("{} {}", choice.strategy, choice.phase)).collect();
+        let threads = if self.threads == 1 { "1 thread".to_string() } else { format!("{} threads", self.threads) };
+        format!("For {} files of {}{} with {}: {}", human::thousands(self.files as u64), ByteSize(self.size), storage, threads, choices.join(", "))
+    }
+
+    pub fn print(&self) {
+        println!("\nRecommendation");
+        println!("{}", self.headline());
+        let mut table = Table::new(["Phase", "Fastest", "ms", "Runner-up", "ms", "Slower by", "Slowest slower by"]).align(0, Align::Left).align(1, Align::Left).align(3, Align::Left);
+        for choice in &self.choices {
+            let (runner_up, runner_up_ms, ahead) = match &choice.runner_up {
+                Some((label, ms)) => (label.clone(), human::decimal(*ms, 2), human::change(choice.ahead_of(*ms), 1)),
+                None => Default::default(),
+            };
+            table.row([choice.phase.to_string(), choice.strategy.clone(), human::decimal(choice.ms, 2), runner_up, runner_up_ms, ahead, human::change(choice.ahead_of(choice.slowest_ms), 1)]);
+        }
+        print!("{}", table.render());
+    }
+
+    /// The fastest strategy's label per phase, in [`PHASES`] order.
+    pub fn strategies(&self) -> [String; 4] {
+        std::array::from_fn(|i| self.choices[i].strategy.clone())
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::object()
+            .with("files", self.files)
+            .with("size_bytes", self.size)
+            .with("threads", self.threads)
+            .with("storage", self.storage.as_deref())
+            .with("phases", self.choices.iter().map(Choice::to_json).collect::<Vec<_>>())
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, format!("{:#}\n", self.to_json()))
+    }
+}
@@ -0,0 +1,103 @@
+//! `io record [--output <file>] [--shim <library>] -- <command> [args...]`: runs a command
+//! with the `io-record` shim preloaded and writes the file operations it and its children
+//! made as a JSONL op log, which `io bench trace` replays.
+//!
+//! The shim (the `record` crate of this workspace, built by `cargo build --workspace`)
+//! appends each call to a log in strace's syntax; that log is read with the strace parser
+//! of [`::io::oplog`], put in time order and written out as the op log. The log is created
+//! empty, in a directory only this user can enter, before the command starts; the shim
+//! only appends to it, so nothing else in the temp directory can be opened in its place.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io;
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::PathBuf;
+use std::process::{self, Command};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ::io::human;
+use ::io::oplog::Trace;
+
+use crate::cli::{flag_value, invalid_input};
+
+/// The shim's file name, as cargo builds it next to the `io` binary.
+const SHIM: &str = "libio_record.so";
+/// Where the op log goes unless told otherwise.
+const DEFAULT_OUTPUT: &str = "trace.jsonl";
+
+pub fn record(mut args: impl Iterator<Item = String>) -> io::Result<()> {
+    let (mut output, mut shim) = (PathBuf::from(DEFAULT_OUTPUT), None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => output = flag_value(&mut args, &arg)?,
+            "--shim" => shim = Some(flag_value::<PathBuf>(&mut args, &arg)?),
+            "--" => break,
+            _ => return Err(invalid_input(format!("unknown argument: {}", arg))),
+        }
+    }
+    let command: Vec<String> = args.collect();
+    let Some((program, arguments)) = command.split_first() else {
+        return Err(invalid_input("io record needs a command after --".to_string()));
+    };
+    let shim = match shim {
+        Some(shim) => shim,
+        None => env::current_exe()?.with_file_name(SHIM),
+    };
+    if !shim.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no recording shim at {}; build it with cargo build -p io-record, or pass --shim", shim.display()),
+        ));
+    }
+    let shim = fs::canonicalize(&shim)?;
+
+    let dir = private_dir()?;
+    let log = dir.join("calls.log");
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let created = options.open(&log);
+    if let Err(e) = created {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(e);
+    }
+    let mut preload = OsString::from(shim.as_os_str());
+    if let Some(existing) = env::var_os("LD_PRELOAD").filter(|existing| !existing.is_empty()) {
+        preload.push(":");
+        preload.push(existing);
+    }
+    let status = Command::new(program).args(arguments).env("LD_PRELOAD", preload).env("IO_RECORD_LOG", &log).status();
+    let text = fs::read_to_string(&log);
+    let _ = fs::remove_dir_all(&dir);
+    let status = status.map_err(|e| io::Error::new(e.kind(), format!("{}: {}", program, e)))?;
+    let trace = Trace::parse_strace(&text?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(&output, trace.to_jsonl())?;
+    println!(
+        "Recorded {} operations over {} to {} ({} calls on pipes, sockets and other descriptors skipped); replay them with io bench trace {}",
+        human::thousands(trace.events.len() as u64),
+        human::duration(trace.duration()),
+        output.display(),
+        human::thousands(trace.skipped as u64),
+        output.display()
+    );
+    if !status.success() {
+        return Err(io::Error::other(format!("{} exited with {}", program, status)));
+    }
+    Ok(())
+}
+
+/// A new directory in the temp directory that only this user can enter. Creating it fails
+/// rather than reuse anything already at its name.
+fn private_dir() -> io::Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.subsec_nanos());
+    let dir = env::temp_dir().join(format!("io-record-{}-{:08x}", process::id(), nanos));
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(&dir)?;
+    Ok(dir)
+}
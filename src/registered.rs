@@ -0,0 +1,503 @@
+//! Reading small files through io_uring, plain and with registered files and buffers.
+//!
+//! A plain io_uring read still costs the kernel a descriptor lookup and a page pin per
+//! operation; for small files those are a large share of the work. The registered tier
+//! keeps one file table and one pinned buffer per ring slot for the whole run, swapping
+//! each batch's descriptors into the table with a single `IORING_REGISTER_FILES_UPDATE`.
+//! Both tiers open files with ordinary `open` calls, so the difference between them is
+//! only what registration saves.
+//!
+//! Each ring and AIO batch takes [`Options::queue_depth`] files, and the tokio tier (with the
+//! `tokio` feature) keeps that many reads in flight on the blocking pool. Every async tier
+//! reports the concurrency it achieved, the mean number of operations in flight while it
+//! waited, which shows where deeper queues stop helping. A worker buffers a whole file per
+//! slot, so a depth and file size whose buffers come to more than [`MAX_BUFFER_BYTES`] is
+//! refused.
+//!
+//! Where io_uring is refused, two fallbacks keep the comparison going: POSIX AIO, which
+//! glibc runs on its own threads, submitting each batch with one `lio_listio`, and
+//! `preadv2(RWF_NOWAIT)`, which reads what the page cache holds without blocking and hands
+//! the rest to an ordinary `pread` on the worker, as a thread-pool backend would.
+//! [`RegisteredResult::nowait`] counts how often the fast path was enough.
+//!
+//! The per-core tier is the share-nothing design of thread-per-core executors such as
+//! glommio: one pinned thread per worker, each with its own registered ring, owning a
+//! contiguous shard of the files outright. Nothing is stolen, so a slow shard isn't helped
+//! by the others; [`RegisteredResult::shards`] shows how far apart they finished.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::fs::FileExt;
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::Barrier;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::bench::{self, Failure, Options};
+use crate::uring::{self, Entry, Ring};
+use crate::{buffers, human, pinning, platform};
+
+/// One way of reading every file whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    /// `open` and `read` syscalls per file.
+    Read,
+    /// `IORING_OP_READ` batches over ordinary descriptors and buffers.
+    Uring,
+    /// `IORING_OP_READ_FIXED` batches over registered files and buffers.
+    Registered,
+    /// Reads on tokio's blocking pool through [`crate::stream::Completions`]; skipped
+    /// without the `tokio` feature.
+    Tokio,
+    /// `lio_listio` batches of POSIX AIO reads.
+    PosixAio,
+    /// `preadv2(RWF_NOWAIT)`, falling back to `pread` when the data isn't cached.
+    NoWait,
+    /// One pinned thread and registered ring per worker, each reading only its own shard.
+    PerCore,
+}
+
+impl Tier {
+    pub const ALL: [Tier; 7] = [Tier::Read, Tier::Uring, Tier::Registered, Tier::Tokio, Tier::PosixAio, Tier::NoWait, Tier::PerCore];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tier::Read => "read",
+            Tier::Uring => "io_uring",
+            Tier::Registered => "registered",
+            Tier::Tokio => "tokio",
+            Tier::PosixAio => "posix-aio",
+            Tier::NoWait => "nowait",
+            Tier::PerCore => "per-core",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RegisteredResult {
+    /// Each tier's time and the bytes it read.
+    pub times: Vec<(Tier, Duration, u64)>,
+    /// The queue depth each async tier asked for and the mean it achieved, per ring for
+    /// io_uring.
+    pub concurrency: Vec<(Tier, u32, f64)>,
+    /// When each per-core shard finished, from the tier's start, in shard order.
+    pub shards: Vec<Duration>,
+    /// Files the nowait tier read without blocking, and those it fell back to `pread` for.
+    pub nowait: Option<(u64, u64)>,
+    /// Why io_uring can't be used here, when it can't.
+    pub uring_unavailable: Option<io::Error>,
+    /// Tiers the kernel refused, with why.
+    pub skipped: Vec<(Tier, io::Error)>,
+    pub failures: Vec<Failure>,
+}
+
+/// Operations in flight, sampled whenever a tier waits for completions.
+#[derive(Debug, Default)]
+struct InFlight {
+    total: AtomicU64,
+    samples: AtomicU64,
+}
+
+impl InFlight {
+    fn sample(&self, in_flight: usize) {
+        self.total.fetch_add(in_flight as u64, Ordering::Relaxed);
+        self.samples.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn mean(&self) -> f64 {
+        let samples = self.samples.load(Ordering::Relaxed);
+        if samples == 0 { 0.0 } else { self.total.load(Ordering::Relaxed) as f64 / samples as f64 }
+    }
+}
+
+/// The most the ring and AIO tiers may allocate for whole-file buffers across their
+/// workers. Each worker holds [`Options::queue_depth`] files' worth, and the registered
+/// tiers' buffers are pinned and count against `RLIMIT_MEMLOCK`.
+pub const MAX_BUFFER_BYTES: u64 = 1 << 30;
+
+/// Refuses a workload whose buffers, one file per queue slot on each of `workers`, would
+/// come to more than [`MAX_BUFFER_BYTES`].
+fn check_buffers(workers: usize, options: &Options) -> io::Result<()> {
+    let (size, depth) = (options.workload.size().max(1) as u64, u64::from(options.queue_depth()));
+    let total = (workers as u64).saturating_mul(depth).saturating_mul(size);
+    if total > MAX_BUFFER_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} workers × queue depth {} × {} files needs {} of buffers, more than {}; lower --queue-depth or --threads",
+                workers,
+                depth,
+                human::bytes(size),
+                human::bytes(total),
+                human::bytes(MAX_BUFFER_BYTES)
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// A ring and one buffer per slot. The ring is declared first so it's dropped, and the
+/// buffers unregistered, before they're freed.
+struct Worker {
+    ring: Ring,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Worker {
+    fn new(size: usize, registered: bool, depth: u32) -> io::Result<Worker> {
+        let unavailable = |what: &str, e: io::Error| io::Error::new(io::ErrorKind::Unsupported, format!("{} failed: {}", what, e));
+        let ring = Ring::new(depth).map_err(|e| unavailable("io_uring setup", e))?;
+        let mut worker = Worker { ring, buffers: (0..depth).map(|_| vec![0; size.max(1)]).collect() };
+        if registered {
+            worker.ring.register_files(&vec![-1; depth as usize]).map_err(|e| unavailable("registering files", e))?;
+            // The buffers live in the same `Worker` as the ring and are dropped after it.
+            unsafe { worker.ring.register_buffers(&mut worker.buffers) }.map_err(|e| unavailable("registering buffers", e))?;
+        }
+        Ok(worker)
+    }
+
+    /// Reads the files of `batch`, the workload's from file `first` on, whole, returning
+    /// the bytes read. With [`Options::verify`], each must hold what the create phase wrote.
+    fn read_batch(&mut self, batch: &[PathBuf], first: usize, registered: bool, in_flight: &InFlight, options: &Options) -> io::Result<u64> {
+        let files = batch.iter().map(|path| File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))).collect::<io::Result<Vec<_>>>()?;
+        if registered {
+            let fds: Vec<RawFd> = files.iter().map(AsRawFd::as_raw_fd).collect();
+            self.ring.update_files(0, &fds)?;
+        }
+        for (i, file) in files.iter().enumerate() {
+            let buffer = &mut self.buffers[i];
+            let (ptr, len) = (buffer.as_mut_ptr(), buffer.len() as u32);
+            let entry = if registered {
+                Entry::read_fixed(0, ptr, len, 0, i as u16).fixed_file(i as u32)
+            } else {
+                Entry::read(file.as_raw_fd(), ptr, len, 0)
+            };
+            // Batches are no bigger than the ring, and the files and buffers outlive the
+            // completions reaped below.
+            assert!(unsafe { self.ring.push(&entry.user_data(i as u64)) }, "io_uring submission queue is full");
+        }
+        let (mut bytes, mut first_error) = (0, None);
+        let mut pending = batch.len();
+        while pending > 0 {
+            let Some(completion) = self.ring.pop() else {
+                in_flight.sample(pending);
+                if let Err(e) = self.ring.submit(1) {
+                    // The kernel may still read into the buffers and through the files.
+                    std::mem::forget(std::mem::take(&mut self.buffers));
+                    std::mem::forget(files);
+                    return Err(e);
+                }
+                continue;
+            };
+            pending -= 1;
+            options.progress.advance();
+            let i = completion.user_data as usize;
+            match completion.result() {
+                Ok(read) => {
+                    bytes += read as u64;
+                    if let Err(e) = bench::verify_content(options, first + i, &batch[i], &self.buffers[i][..read as usize]) {
+                        first_error.get_or_insert(e);
+                    }
+                }
+                Err(e) => {
+                    first_error.get_or_insert_with(|| io::Error::new(e.kind(), format!("{}: {}", batch[i].display(), e)));
+                }
+            }
+        }
+        first_error.map_or(Ok(bytes), Err)
+    }
+}
+
+/// Reads every path whole through one ring per worker, [`Options::queue_depth`] files per
+/// `io_uring_enter`.
+fn read_uring(paths: &[PathBuf], registered: bool, in_flight: &InFlight, options: &Options) -> io::Result<u64> {
+    let size = options.workload.size();
+    let depth = options.queue_depth();
+    let bytes = AtomicU64::new(0);
+    options.progress.begin(paths.len());
+    paths.par_chunks(depth as usize).enumerate().try_for_each_init(
+        || Worker::new(size, registered, depth),
+        |worker, (chunk, batch)| {
+            let worker = worker.as_mut().map_err(|e| io::Error::new(e.kind(), e.to_string()))?;
+            options.pause.wait();
+            bytes.fetch_add(worker.read_batch(batch, chunk * depth as usize, registered, in_flight, options)?, Ordering::Relaxed);
+            Ok::<(), io::Error>(())
+        },
+    )?;
+    Ok(bytes.into_inner())
+}
+
+/// Reads every path whole through io_uring, with registered files and buffers or without,
+/// outside any timing.
+pub(crate) fn read_all(paths: &[PathBuf], registered: bool, options: &Options) -> io::Result<u64> {
+    read_uring(paths, registered, &InFlight::default(), options)
+}
+
+/// Reads every path whole with one pinned thread per rayon worker, each owning a contiguous
+/// shard and a registered ring of its own. The threads are spawned, pinned and their rings
+/// set up before `start` is reset, so like an executor's, that cost isn't timed. Returns
+/// the bytes read and when each shard finished.
+fn read_per_core(paths: &[PathBuf], in_flight: &InFlight, start: &mut Instant, options: &Options) -> io::Result<(u64, Vec<Duration>)> {
+    let size = options.workload.size();
+    let depth = options.queue_depth();
+    let cpus = platform::allowed_cpus();
+    let cores = rayon::current_num_threads().min(paths.len()).max(1);
+    let shard_len = paths.len().div_ceil(cores).max(1);
+    let shards: Vec<&[PathBuf]> = paths.chunks(shard_len).collect();
+    let ready = Barrier::new(shards.len() + 1);
+    options.progress.begin(paths.len());
+    thread::scope(|scope| {
+        let threads: Vec<_> = shards
+            .iter()
+            .enumerate()
+            .map(|(core, shard)| {
+                let (cpu, ready) = (cpus[core % cpus.len()], &ready);
+                scope.spawn(move || {
+                    // Pinning is best effort, as for the rayon pool; the shard is still owned.
+                    let _ = pinning::pin_thread(cpu);
+                    let worker = Worker::new(size, true, depth);
+                    ready.wait();
+                    let mut worker = worker?;
+                    let mut bytes = 0;
+                    for (chunk, batch) in shard.chunks(depth as usize).enumerate() {
+                        options.pause.wait();
+                        bytes += worker.read_batch(batch, core * shard_len + chunk * depth as usize, true, in_flight, options)?;
+                    }
+                    Ok::<_, io::Error>((bytes, Instant::now()))
+                })
+            })
+            .collect();
+        ready.wait();
+        *start = Instant::now();
+        let (mut bytes, mut finished, mut first_error) = (0, Vec::new(), None);
+        for thread in threads {
+            match thread.join().unwrap_or_else(|_| Err(io::Error::other("per-core thread panicked"))) {
+                Ok((read, end)) => {
+                    bytes += read;
+                    finished.push(end.saturating_duration_since(*start));
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok((bytes, finished)), Err)
+    })
+}
+
+/// A runtime for the tokio tier, built before the tier is timed.
+#[cfg(feature = "tokio")]
+fn tokio_runtime(options: &Options) -> io::Result<tokio::runtime::Runtime> {
+    tokio::runtime::Builder::new_multi_thread().worker_threads(1).max_blocking_threads(options.queue_depth() as usize).build()
+}
+
+/// Reads every path whole through a [`crate::stream::Completions`] of
+/// [`Options::queue_depth`]. A file that can't be read is recorded as a failure rather than
+/// ending the tier.
+#[cfg(feature = "tokio")]
+fn read_tokio(paths: &[PathBuf], runtime: &tokio::runtime::Runtime, in_flight: &InFlight, options: &Options) -> io::Result<u64> {
+    use futures::StreamExt;
+
+    use crate::stream::{Completions, Op, Output};
+    use crate::trace::OpId;
+
+    options.progress.begin(paths.len());
+    let scope = options.op_scope.get();
+    let mut completions = Completions::new(paths.iter().cloned().map(Op::Read), options.queue_depth() as usize);
+    runtime.block_on(async {
+        let mut bytes = 0;
+        while let Some(completion) = completions.next().await {
+            // What was in flight while this one was awaited, itself included.
+            in_flight.sample(completions.in_flight() + 1);
+            options.progress.advance();
+            match completion.result {
+                Ok(Output::Read(read)) => bytes += read.len() as u64,
+                Ok(_) => {}
+                Err(e) => options.failures.record(OpId::new(&scope, &completion.path), &completion.path, e.to_string()),
+            }
+        }
+        Ok::<_, io::Error>(bytes)
+    })
+}
+
+/// Reads every path whole with POSIX AIO, each worker submitting [`Options::queue_depth`]
+/// files with one `lio_listio` and waiting on them with `aio_suspend`.
+fn read_aio(paths: &[PathBuf], in_flight: &InFlight, options: &Options) -> io::Result<u64> {
+    let size = options.workload.size().max(1);
+    let depth = options.queue_depth() as usize;
+    let bytes = AtomicU64::new(0);
+    options.progress.begin(paths.len());
+    paths.par_chunks(depth).try_for_each_init(
+        || vec![vec![0u8; size]; depth],
+        |buffers, batch| {
+            options.pause.wait();
+            let files = batch.iter().map(|path| File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))).collect::<io::Result<Vec<_>>>()?;
+            let mut blocks: Vec<libc::aiocb> = files
+                .iter()
+                .zip(buffers.iter_mut())
+                .map(|(file, buffer)| {
+                    // `aiocb` has private padding; zeroed is what the man pages start from.
+                    let mut block: libc::aiocb = unsafe { std::mem::zeroed() };
+                    block.aio_fildes = file.as_raw_fd();
+                    block.aio_buf = buffer.as_mut_ptr().cast();
+                    block.aio_nbytes = buffer.len();
+                    block.aio_lio_opcode = libc::LIO_READ;
+                    block.aio_sigevent.sigev_notify = libc::SIGEV_NONE;
+                    block
+                })
+                .collect();
+            let list: Vec<*mut libc::aiocb> = blocks.iter_mut().map(|block| block as *mut libc::aiocb).collect();
+            let submitted = unsafe { libc::lio_listio(libc::LIO_NOWAIT, list.as_ptr(), list.len() as i32, std::ptr::null_mut()) };
+            // Some requests may have been queued even when the call failed, so every one is
+            // waited for before the buffers can be reused.
+            let submit_error = (submitted != 0).then(io::Error::last_os_error);
+            loop {
+                let pending: Vec<*const libc::aiocb> = blocks.iter().filter(|block| unsafe { libc::aio_error(*block) } == libc::EINPROGRESS).map(|block| block as *const libc::aiocb).collect();
+                if pending.is_empty() {
+                    break;
+                }
+                in_flight.sample(pending.len());
+                if unsafe { libc::aio_suspend(pending.as_ptr(), pending.len() as i32, std::ptr::null()) } != 0 {
+                    let e = io::Error::last_os_error();
+                    if !matches!(e.raw_os_error(), Some(libc::EINTR | libc::EAGAIN)) {
+                        return Err(e);
+                    }
+                }
+            }
+            match submit_error {
+                Some(e) if e.raw_os_error() == Some(libc::ENOSYS) => return Err(io::Error::new(io::ErrorKind::Unsupported, format!("POSIX AIO isn't available: {}", e))),
+                // EIO means individual requests failed, which their own status reports.
+                Some(e) if e.raw_os_error() != Some(libc::EIO) => return Err(e),
+                _ => {}
+            }
+            for (block, path) in blocks.iter_mut().zip(batch) {
+                let read = unsafe { libc::aio_return(block) };
+                if read < 0 {
+                    let e = io::Error::from_raw_os_error(unsafe { libc::aio_error(block) });
+                    return Err(io::Error::new(e.kind(), format!("{}: {}", path.display(), e)));
+                }
+                bytes.fetch_add(read as u64, Ordering::Relaxed);
+                options.progress.advance();
+            }
+            Ok::<(), io::Error>(())
+        },
+    )?;
+    Ok(bytes.into_inner())
+}
+
+/// Reads every path whole with `preadv2(RWF_NOWAIT)`, finishing with `pread` whatever that
+/// couldn't read without blocking. Returns the bytes read and the files read without and
+/// with the fallback.
+fn read_nowait(paths: &[PathBuf], options: &Options) -> io::Result<(u64, u64, u64)> {
+    let (bytes, served, punted) = (AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0));
+    let size = options.workload.size();
+    bench::each_file(paths, options, |path| {
+        let file = File::open(path)?;
+        let read = buffers::with_buffer(|buf| -> io::Result<usize> {
+            buf.resize(size, 0);
+            let iovec = libc::iovec { iov_base: buf.as_mut_ptr().cast(), iov_len: buf.len() };
+            let fast = unsafe { libc::preadv2(file.as_raw_fd(), &iovec, 1, 0, libc::RWF_NOWAIT) };
+            let mut read = match fast {
+                0.. => fast as usize,
+                _ => match io::Error::last_os_error() {
+                    e if e.kind() == io::ErrorKind::WouldBlock => 0,
+                    e if matches!(e.raw_os_error(), Some(libc::EOPNOTSUPP | libc::EINVAL | libc::ENOSYS)) => {
+                        return Err(io::Error::new(io::ErrorKind::Unsupported, format!("preadv2(RWF_NOWAIT) isn't supported here: {}", e)));
+                    }
+                    e => return Err(e),
+                },
+            };
+            // A short read may only mean the rest isn't cached yet.
+            if read < buf.len() {
+                punted.fetch_add(1, Ordering::Relaxed);
+                loop {
+                    match file.read_at(&mut buf[read..], read as u64)? {
+                        0 => break,
+                        more => read += more,
+                    }
+                    if read == buf.len() {
+                        break;
+                    }
+                }
+            } else {
+                served.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(read)
+        })?;
+        bytes.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(())
+    })?;
+    Ok((bytes.into_inner(), served.into_inner(), punted.into_inner()))
+}
+
+fn read_files(paths: &[PathBuf], options: &Options) -> io::Result<u64> {
+    let bytes = AtomicU64::new(0);
+    bench::each_file(paths, options, |path| {
+        let read = buffers::with_buffer(|buf| File::open(path)?.read_to_end(buf))?;
+        bytes.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(())
+    })?;
+    Ok(bytes.into_inner())
+}
+
+/// Creates the configured workload inside `dir_path`, times reading it whole by each
+/// [`Tier`], and deletes it. The files are read from the page cache, so the times are the
+/// per-operation overhead each tier pays rather than device speed.
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<RegisteredResult> {
+    let paths = options.workload.paths(dir_path);
+    let mut result = RegisteredResult::default();
+    options.failures.take("");
+    check_buffers(rayon::current_num_threads(), options)?;
+
+    bench::phase(options, "io_uring create", "uring/create", "create", &mut result.failures, || bench::create_files(&paths, options))?;
+
+    #[cfg(feature = "tokio")]
+    let runtime = tokio_runtime(options);
+    result.uring_unavailable = uring::unavailable();
+    for tier in Tier::ALL {
+        if let (Tier::Uring | Tier::Registered | Tier::PerCore, Some(why)) = (tier, &result.uring_unavailable) {
+            result.skipped.push((tier, io::Error::new(why.kind(), why.to_string())));
+            continue;
+        }
+        let in_flight = InFlight::default();
+        let mut start = Instant::now();
+        let read = bench::phase(options, format!("io_uring {}", tier.name()), format!("uring/{}", tier.name()), tier.name(), &mut result.failures, || match tier {
+            Tier::Read => read_files(&paths, options),
+            Tier::Uring => read_uring(&paths, false, &in_flight, options),
+            Tier::Registered => read_uring(&paths, true, &in_flight, options),
+            #[cfg(feature = "tokio")]
+            Tier::Tokio => match &runtime {
+                Ok(runtime) => read_tokio(&paths, runtime, &in_flight, options),
+                Err(e) => Err(io::Error::new(io::ErrorKind::Unsupported, format!("tokio runtime failed: {}", e))),
+            },
+            #[cfg(not(feature = "tokio"))]
+            Tier::Tokio => Err(io::Error::new(io::ErrorKind::Unsupported, "built without the tokio feature")),
+            Tier::PosixAio => read_aio(&paths, &in_flight, options),
+            Tier::NoWait => read_nowait(&paths, options).map(|(bytes, served, punted)| {
+                result.nowait = Some((served, punted));
+                bytes
+            }),
+            Tier::PerCore => read_per_core(&paths, &in_flight, &mut start, options).map(|(bytes, shards)| {
+                result.shards = shards;
+                bytes
+            }),
+        });
+        match read {
+            Ok(bytes) => {
+                result.times.push((tier, start.elapsed(), bytes));
+                if !matches!(tier, Tier::Read | Tier::NoWait) {
+                    result.concurrency.push((tier, options.queue_depth(), in_flight.mean()));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => result.skipped.push((tier, e)),
+            Err(e) => return Err(e),
+        }
+    }
+
+    bench::phase(options, "io_uring delete", "uring/delete", "delete", &mut result.failures, || bench::delete_files(&paths, options))?;
+    Ok(result)
+}
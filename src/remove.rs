@@ -0,0 +1,210 @@
+//! Deleting many files, and whole trees, with `unlinkat` relative to open directories
+//! instead of resolving every full path again as `fs::remove_file` does.
+//!
+//! [`remove_files`] opens each parent directory once (see [`Dirs`]), so removing a file
+//! costs one lookup of its name. [`remove_tree_fast`] unlinks each directory's entries relative to its descriptor
+//! and, with the `rayon` feature, clears sibling directories in parallel, where
+//! `fs::remove_dir_all` walks the tree on one thread.
+
+use std::ffi::{CStr, CString, OsStr};
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+use crate::dirs::{Dirs, c_string, check};
+
+fn with_path<T>(path: &Path, result: io::Result<T>) -> io::Result<T> {
+    result.map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+/// Removes every file in `paths`, in parallel with the `rayon` feature. The error names
+/// the file that failed.
+pub fn remove_files(paths: &[PathBuf]) -> io::Result<()> {
+    let dirs = Dirs::open(paths)?;
+    let remove = |path: &PathBuf| with_path(path, dirs.unlink(path));
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        paths.par_iter().try_for_each(remove)
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        paths.iter().try_for_each(remove)
+    }
+}
+
+fn open_dir(parent: RawFd, name: &CStr) -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::openat(parent, name.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC) };
+    if fd < 0 { Err(io::Error::last_os_error()) } else { Ok(unsafe { OwnedFd::from_raw_fd(fd) }) }
+}
+
+/// The names in the directory open as `dir`, split into subdirectories and everything
+/// else. Listing goes through the descriptor, so a directory renamed meanwhile is still
+/// the one listed; `d_type` saves a stat per entry where the filesystem has it.
+fn list(dir: &OwnedFd) -> io::Result<(Vec<CString>, Vec<CString>)> {
+    // The stream takes over the descriptor it's given and closes it, so it gets a copy.
+    let copy = dir.try_clone()?;
+    let stream = unsafe { libc::fdopendir(copy.as_raw_fd()) };
+    if stream.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    let _ = copy.into_raw_fd();
+    let (mut subdirs, mut others) = (Vec::new(), Vec::new());
+    let read = loop {
+        clear_errno();
+        let entry = unsafe { libc::readdir(stream) };
+        if entry.is_null() {
+            let e = io::Error::last_os_error();
+            break if e.raw_os_error() == Some(0) { Ok(()) } else { Err(e) };
+        }
+        let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+        if name == c"." || name == c".." {
+            continue;
+        }
+        let is_dir = match unsafe { (*entry).d_type } {
+            libc::DT_DIR => true,
+            libc::DT_UNKNOWN => {
+                let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+                if let Err(e) = check(unsafe { libc::fstatat(dir.as_raw_fd(), name.as_ptr(), &mut stat, libc::AT_SYMLINK_NOFOLLOW) }) {
+                    break Err(e);
+                }
+                stat.st_mode & libc::S_IFMT == libc::S_IFDIR
+            }
+            _ => false,
+        };
+        if is_dir { subdirs.push(name.to_owned()) } else { others.push(name.to_owned()) }
+    };
+    unsafe { libc::closedir(stream) };
+    read.map(|()| (subdirs, others))
+}
+
+/// Zeroes `errno`, so a null from `readdir` tells the end from an error.
+fn clear_errno() {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    unsafe {
+        *libc::__errno_location() = 0
+    };
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    unsafe {
+        *libc::__error() = 0
+    };
+}
+
+/// Empties the directory open as `dir` at `path`, removing its subdirectories once each
+/// is empty.
+fn clear(dir: &OwnedFd, path: &Path) -> io::Result<()> {
+    let (subdirs, others) = with_path(path, list(dir))?;
+    for name in &others {
+        check(unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), 0) }).map_err(|e| {
+            let file = path.join(OsStr::from_bytes(name.as_bytes()));
+            io::Error::new(e.kind(), format!("{}: {}", file.display(), e))
+        })?;
+    }
+    let remove_subdir = |name: &CString| {
+        let sub_path = path.join(OsStr::from_bytes(name.as_bytes()));
+        let sub = with_path(&sub_path, open_dir(dir.as_raw_fd(), name))?;
+        clear(&sub, &sub_path)?;
+        drop(sub);
+        with_path(&sub_path, check(unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), libc::AT_REMOVEDIR) }))
+    };
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        subdirs.par_iter().try_for_each(remove_subdir)
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        subdirs.iter().try_for_each(remove_subdir)
+    }
+}
+
+/// Removes `root` and everything under it without following symlinks, like
+/// `fs::remove_dir_all`, clearing sibling directories in parallel with the `rayon` feature.
+pub fn remove_tree_fast(root: &Path) -> io::Result<()> {
+    let dir = with_path(root, open_dir(libc::AT_FDCWD, &c_string(root.as_os_str().as_bytes())?))?;
+    clear(&dir, root)?;
+    drop(dir);
+    with_path(root, fs::remove_dir(root))
+}
+
+/// A way of deleting the workload's tree.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Removal {
+    /// `fs::remove_file` per path, resolving the full path each time.
+    RemoveFile,
+    /// `unlinkat` per file relative to its open parent directory.
+    Unlinkat,
+    /// `fs::remove_dir_all` on the root.
+    RemoveDirAll,
+    /// [`remove_tree_fast`] on the root.
+    RemoveTreeFast,
+}
+
+#[cfg(feature = "bench")]
+impl Removal {
+    pub const ALL: [Removal; 4] = [Removal::RemoveFile, Removal::Unlinkat, Removal::RemoveDirAll, Removal::RemoveTreeFast];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Removal::RemoveFile => "remove_file",
+            Removal::Unlinkat => "unlinkat",
+            Removal::RemoveDirAll => "remove_dir_all",
+            Removal::RemoveTreeFast => "remove_tree_fast",
+        }
+    }
+
+    /// Whether this removes the directories too, not just the files.
+    pub fn removes_tree(self) -> bool {
+        matches!(self, Removal::RemoveDirAll | Removal::RemoveTreeFast)
+    }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct RemoveResult {
+    pub times: Vec<(Removal, Duration)>,
+    pub failures: Vec<Failure>,
+}
+
+/// For each removal method, creates the configured workload as a nested tree in
+/// `dir_path/tree` (see [`crate::scan::tree_paths`]) and times deleting it. Per-file
+/// methods leave the emptied directories, which are removed untimed.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<RemoveResult> {
+    let root = dir_path.join("tree");
+    let paths = crate::scan::tree_paths(&root, options.workload.files);
+    let mut result = RemoveResult::default();
+    options.failures.take("");
+
+    for removal in Removal::ALL {
+        for path in &paths {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        bench::phase(options, format!("Remove {} create", removal.name()), "remove/create", "create", &mut result.failures, || bench::create_files(&paths, options))?;
+
+        let start = Instant::now();
+        bench::phase(options, format!("Remove {}", removal.name()), format!("remove/{}", removal.name()), removal.name(), &mut result.failures, || match removal {
+            Removal::RemoveFile => bench::each_file(&paths, options, |path| fs::remove_file(path)),
+            Removal::Unlinkat => {
+                let dirs = Dirs::open(&paths)?;
+                bench::each_file(&paths, options, |path| dirs.unlink(path))
+            }
+            Removal::RemoveDirAll => fs::remove_dir_all(&root),
+            Removal::RemoveTreeFast => remove_tree_fast(&root),
+        })?;
+        result.times.push((removal, start.elapsed()));
+        if !removal.removes_tree() {
+            fs::remove_dir_all(&root)?;
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,144 @@
+//! Rename and move phases, as package managers and build caches do when they stage files
+//! and publish them: every file renamed into a sibling directory, then moved to another
+//! directory that may sit on a different filesystem.
+//!
+//! `rename(2)` can't cross mount points; when it fails with `EXDEV`, [`move_file`] copies
+//! the file, syncs the copy as [`Options::sync`] asks, and unlinks the original, which is
+//! what `mv` does and costs a full data transfer per file.
+
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::bench::{self, Failure, Options};
+
+/// Where the cross-filesystem phase moves files when no directory is given.
+pub const DEFAULT_CROSS_DIRS: &[&str] = &["/dev/shm"];
+
+/// How a file got to its new path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Moved {
+    Renamed,
+    /// Copied and unlinked because the paths are on different filesystems.
+    Copied,
+}
+
+/// Moves `from` to `to`, falling back to copy and unlink across filesystems.
+pub fn move_file(from: &Path, to: &Path, options: &Options) -> io::Result<Moved> {
+    match fs::rename(from, to) {
+        Ok(()) => return Ok(Moved::Renamed),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(e),
+    }
+    let copied = fs::copy(from, to).and_then(|_| options.sync.sync(&File::open(to)?));
+    if let Err(e) = copied {
+        let _ = fs::remove_file(to);
+        return Err(e);
+    }
+    fs::remove_file(from)?;
+    Ok(Moved::Copied)
+}
+
+/// How many files of a phase were renamed and how many copied.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MoveCounts {
+    pub renamed: usize,
+    pub copied: usize,
+}
+
+/// Moves every `from[i]` to `to[i]` in parallel.
+pub fn move_files(from: &[PathBuf], to: &[PathBuf], options: &Options) -> io::Result<MoveCounts> {
+    let copied = AtomicUsize::new(0);
+    bench::each_indexed(from, options, |index, path| {
+        if move_file(path, &to[index], options)? == Moved::Copied {
+            copied.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    })?;
+    let copied = copied.into_inner();
+    Ok(MoveCounts { renamed: from.len() - copied, copied })
+}
+
+/// Whether `a` and `b` are on the same filesystem.
+pub fn same_filesystem(a: &Path, b: &Path) -> io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        Ok(fs::metadata(a)?.dev() == fs::metadata(b)?.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (a, b);
+        Ok(true)
+    }
+}
+
+/// The first of [`DEFAULT_CROSS_DIRS`] (or the temp dir) on a different filesystem than
+/// `dir`, if any.
+pub fn default_cross_dir(dir: &Path) -> Option<PathBuf> {
+    DEFAULT_CROSS_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .chain([env::temp_dir()])
+        .find(|candidate| same_filesystem(dir, candidate).is_ok_and(|same| !same))
+}
+
+/// One timed move phase.
+#[derive(Debug, Clone)]
+pub struct MovePhase {
+    pub name: &'static str,
+    /// The directory the files were moved into.
+    pub dir: PathBuf,
+    pub elapsed: Duration,
+    pub counts: MoveCounts,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MoveResult {
+    pub phases: Vec<MovePhase>,
+    pub failures: Vec<Failure>,
+}
+
+fn timed_move(name: &'static str, from: &[PathBuf], dir: &Path, options: &Options, failures: &mut Vec<Failure>) -> io::Result<(MovePhase, Vec<PathBuf>)> {
+    fs::create_dir_all(dir)?;
+    let to: Vec<PathBuf> = from.iter().map(|path| dir.join(path.file_name().unwrap_or(path.as_os_str()))).collect();
+    let start = Instant::now();
+    let counts = bench::phase(options, format!("Move {}", name), format!("move/{}", name), name, failures, || move_files(from, &to, options))?;
+    let phase = MovePhase { name, dir: dir.to_path_buf(), elapsed: start.elapsed(), counts };
+    Ok((phase, to))
+}
+
+/// Creates the configured workload in `dir_path/staged`, times renaming it into the
+/// sibling `dir_path/published`, then, with `cross_dir`, times moving it on into a
+/// directory there, and deletes it. Creating and deleting aren't timed.
+pub fn run(dir_path: &Path, cross_dir: Option<&Path>, options: &Options) -> io::Result<MoveResult> {
+    let staged = dir_path.join("staged");
+    fs::create_dir_all(&staged)?;
+    let paths = options.workload.paths(&staged);
+    let mut result = MoveResult::default();
+    options.failures.take("");
+
+    bench::phase(options, "Move create", "move/create", "create", &mut result.failures, || bench::create_files(&paths, options))?;
+
+    let (phase, mut paths) = timed_move("rename", &paths, &dir_path.join("published"), options, &mut result.failures)?;
+    result.phases.push(phase);
+
+    let mut cross_target = None;
+    if let Some(cross_dir) = cross_dir {
+        let target = cross_dir.join(format!("io-move-{}", std::process::id()));
+        let (phase, moved) = timed_move("cross-fs", &paths, &target, options, &mut result.failures)?;
+        result.phases.push(phase);
+        paths = moved;
+        cross_target = Some(target);
+    }
+
+    bench::phase(options, "Move delete", "move/delete", "delete", &mut result.failures, || bench::delete_files(&paths, options))?;
+    if let Some(target) = cross_target {
+        fs::remove_dir_all(target)?;
+    }
+    Ok(result)
+}
@@ -0,0 +1,219 @@
+//! Markdown and HTML comparison reports of strategy phase times, from one run or several
+//! saved result files, for pasting into pull requests and issues.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use ::io::human;
+use ::io::treemap::escape;
+
+use crate::baseline::{Anomaly, Baseline, PHASES};
+use crate::cli::ByteSize;
+
+const BAR_WIDTH: f64 = 480.0;
+const BAR_HEIGHT: f64 = 14.0;
+const LABEL_WIDTH: f64 = 160.0;
+/// Bar colours, one per run, repeating past the last.
+const COLOURS: [&str; 6] = ["#4e79a7", "#f28e2b", "#59a14f", "#e15759", "#76b7b2", "#b07aa1"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ReportFormat, String> {
+        match s {
+            "md" | "markdown" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(format!("unknown report format '{}', expected md or html", s)),
+        }
+    }
+}
+
+/// A run's phase times, named for the report.
+pub struct Run<'a> {
+    pub name: String,
+    pub results: &'a Baseline,
+}
+
+/// The strategies of every run, in the order they first appear.
+fn strategies(runs: &[Run]) -> Vec<String> {
+    let mut labels: Vec<String> = Vec::new();
+    for (label, _) in runs.iter().flat_map(|run| &run.results.results) {
+        if !labels.contains(label) {
+            labels.push(label.clone());
+        }
+    }
+    labels
+}
+
+/// Each column of the report: a phase, or the total.
+fn columns() -> impl Iterator<Item = (&'static str, Option<usize>)> {
+    PHASES.into_iter().enumerate().map(|(i, phase)| (phase, Some(i))).chain([("total", None)])
+}
+
+/// `strategy`'s time in `run` for the phase at `phase`, or its total, in milliseconds.
+fn ms(run: &Run, strategy: &str, phase: Option<usize>) -> Option<f64> {
+    let (_, times) = run.results.results.iter().find(|(label, _)| label == strategy)?;
+    Some(phase.map_or_else(|| times.iter().sum(), |i| times[i]))
+}
+
+/// `10,000 files of 4K, 8 threads, on ext4`.
+fn workload(results: &Baseline) -> String {
+    let filesystem = results.filesystem.as_ref().map_or_else(String::new, |filesystem| format!(", on {}", filesystem.fs_type));
+    let threads = if results.threads == 1 { "1 thread".to_string() } else { format!("{} threads", results.threads) };
+    format!("{} files of {}, {}{}", human::thousands(results.files as u64), ByteSize(results.size), threads, filesystem)
+}
+
+/// What stood out in each run's phases, with the run's name.
+fn anomalies<'a>(runs: &'a [Run]) -> Vec<(&'a str, &'a Anomaly)> {
+    runs.iter().flat_map(|run| run.results.anomalies.iter().map(|anomaly| (run.name.as_str(), anomaly))).collect()
+}
+
+/// The fastest time of `times`, to highlight it.
+fn fastest(times: impl IntoIterator<Item = Option<f64>>) -> Option<f64> {
+    times.into_iter().flatten().min_by(f64::total_cmp)
+}
+
+/// `ms` with two decimals, bold in the format's own way when it is `best`.
+fn cell(ms: Option<f64>, best: Option<f64>, bold: fn(&str) -> String) -> String {
+    match ms {
+        Some(ms) if Some(ms) == best => bold(&human::decimal(ms, 2)),
+        Some(ms) => human::decimal(ms, 2),
+        None => "-".to_string(),
+    }
+}
+
+/// A Markdown table of a row per strategy, the fastest of each column bold.
+fn markdown_table(header: &[String], rows: &[(String, Vec<Option<f64>>)], out: &mut String) {
+    let best: Vec<Option<f64>> = (0..header.len() - 1).map(|column| fastest(rows.iter().map(|(_, times)| times[column]))).collect();
+    let _ = writeln!(out, "| {} |", header.join(" | "));
+    let _ = writeln!(out, "| --- |{}", " ---: |".repeat(header.len() - 1));
+    for (label, times) in rows {
+        let cells: Vec<String> = times.iter().zip(&best).map(|(&ms, &best)| cell(ms, best, |text| format!("**{}**", text))).collect();
+        let _ = writeln!(out, "| {} | {} |", label, cells.join(" | "));
+    }
+}
+
+/// A Markdown report: one table of every strategy's phases for a single run, or one table
+/// per phase comparing the runs side by side. The fastest strategy of each column is bold,
+/// and what stood out in the runs' per-file times is listed after.
+pub fn markdown(runs: &[Run]) -> String {
+    let mut out = String::from("## io benchmark report\n\n");
+    for run in runs {
+        let _ = writeln!(out, "- **{}**: {}", run.name, workload(run.results));
+    }
+    out.push('\n');
+    let strategies = strategies(runs);
+    if let [run] = runs {
+        let header: Vec<String> = ["Strategy".to_string()].into_iter().chain(columns().map(|(name, _)| format!("{} ms", name))).collect();
+        let rows: Vec<(String, Vec<Option<f64>>)> = strategies.iter().map(|strategy| (strategy.clone(), columns().map(|(_, phase)| ms(run, strategy, phase)).collect())).collect();
+        markdown_table(&header, &rows, &mut out);
+    } else {
+        let header: Vec<String> = ["Strategy".to_string()].into_iter().chain(runs.iter().map(|run| format!("{} ms", run.name))).collect();
+        for (name, phase) in columns() {
+            let _ = writeln!(out, "### {}\n", name);
+            let rows: Vec<(String, Vec<Option<f64>>)> = strategies.iter().map(|strategy| (strategy.clone(), runs.iter().map(|run| ms(run, strategy, phase)).collect())).collect();
+            markdown_table(&header, &rows, &mut out);
+            out.push('\n');
+        }
+    }
+    let anomalies = anomalies(runs);
+    if !anomalies.is_empty() {
+        out.push_str(if runs.len() == 1 { "\n### Anomalies\n\n" } else { "### Anomalies\n\n" });
+        for (run, anomaly) in anomalies {
+            let _ = writeln!(out, "- **{}**: {}", run, anomaly);
+        }
+    }
+    out
+}
+
+/// A horizontal bar chart of one column: a group per strategy, a bar per run.
+fn bar_chart(runs: &[Run], strategies: &[String], phase: Option<usize>) -> String {
+    let longest = runs.iter().flat_map(|run| strategies.iter().filter_map(move |strategy| ms(run, strategy, phase))).fold(0.0, f64::max);
+    let group = BAR_HEIGHT * runs.len() as f64 + 6.0;
+    let height = group * strategies.len() as f64;
+    let mut svg = format!("<svg width=\"{}\" height=\"{}\" font-size=\"11\">\n", LABEL_WIDTH + BAR_WIDTH + 80.0, height);
+    for (row, strategy) in strategies.iter().enumerate() {
+        let top = row as f64 * group;
+        let _ = writeln!(svg, "<text x=\"0\" y=\"{:.1}\">{}</text>", top + BAR_HEIGHT, escape(strategy));
+        for (i, run) in runs.iter().enumerate() {
+            let Some(ms) = ms(run, strategy, phase) else { continue };
+            let width = if longest > 0.0 { ms / longest * BAR_WIDTH } else { 0.0 };
+            let y = top + i as f64 * BAR_HEIGHT;
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"><title>{}: {} ms</title></rect><text x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+                LABEL_WIDTH,
+                y,
+                width,
+                BAR_HEIGHT - 2.0,
+                COLOURS[i % COLOURS.len()],
+                escape(&run.name),
+                human::decimal(ms, 2),
+                LABEL_WIDTH + width + 4.0,
+                y + BAR_HEIGHT - 4.0,
+                human::decimal(ms, 2)
+            );
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// An HTML report: the runs' workloads, a table of every strategy's phase times per run
+/// with the fastest of each column bold, what stood out in them, and a bar chart per phase.
+pub fn html(runs: &[Run]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>io benchmark report</title>\n<style>\
+         body{font-family:sans-serif}td,th{padding:2px 8px;text-align:right}td:first-child,th:first-child{text-align:left}</style></head><body>\n\
+         <h1>io benchmark report</h1>\n<ul>\n",
+    );
+    for (i, run) in runs.iter().enumerate() {
+        let _ = writeln!(html, "<li><span style=\"color:{}\">&#9632;</span> <b>{}</b>: {}</li>", COLOURS[i % COLOURS.len()], escape(&run.name), escape(&workload(run.results)));
+    }
+    html.push_str("</ul>\n<table>\n<tr><th>Strategy</th>");
+    let strategies = strategies(runs);
+    for (name, _) in columns() {
+        for run in runs {
+            let heading = if runs.len() > 1 { format!("{} ms ({})", name, run.name) } else { format!("{} ms", name) };
+            let _ = write!(html, "<th>{}</th>", escape(&heading));
+        }
+    }
+    html.push_str("</tr>\n");
+    for strategy in &strategies {
+        let _ = write!(html, "<tr><td>{}</td>", escape(strategy));
+        for (_, phase) in columns() {
+            for run in runs {
+                let best = fastest(strategies.iter().map(|other| ms(run, other, phase)));
+                let _ = write!(html, "<td>{}</td>", cell(ms(run, strategy, phase), best, |text| format!("<b>{}</b>", text)));
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+    let anomalies = anomalies(runs);
+    if !anomalies.is_empty() {
+        html.push_str("<h2>Anomalies</h2>\n<ul>\n");
+        for (run, anomaly) in anomalies {
+            let _ = writeln!(html, "<li><b>{}</b>: {}</li>", escape(run), escape(&anomaly.to_string()));
+        }
+        html.push_str("</ul>\n");
+    }
+    for (name, phase) in columns() {
+        let _ = write!(html, "<h2>{}</h2>\n{}", name, bar_chart(runs, &strategies, phase));
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+pub fn render(format: ReportFormat, runs: &[Run]) -> String {
+    match format {
+        ReportFormat::Markdown => markdown(runs),
+        ReportFormat::Html => html(runs),
+    }
+}
@@ -0,0 +1,74 @@
+//! Retrying transient errors. At ten thousand files some opens and writes fail for reasons
+//! that pass on their own, such as a signal (`EINTR`), a non-blocking resource
+//! (`EAGAIN`) or the system running out of file descriptors for a moment (`ENFILE`), and
+//! without retries one of them aborts the whole batch.
+//!
+//! A [`RetryPolicy`] says how often to try, how long to wait between tries and which errors
+//! are worth another try; [`RetryPolicy::run`] applies it to one operation, and the bulk
+//! APIs such as [`crate::contents::read_many`] to each file.
+
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries per operation, the first included; 1 never retries.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for each one after it.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Error kinds worth retrying.
+    pub kinds: Vec<io::ErrorKind>,
+    /// Raw OS error codes worth retrying, for errors with no kind of their own such as
+    /// `ENFILE`.
+    pub codes: Vec<i32>,
+}
+
+impl Default for RetryPolicy {
+    /// Four tries from 1 ms apart to 100 ms, on `EINTR`, `EAGAIN`, `ENFILE` and `EMFILE`.
+    fn default() -> RetryPolicy {
+        #[cfg(all(unix, feature = "libc"))]
+        let codes = vec![libc::ENFILE, libc::EMFILE];
+        #[cfg(not(all(unix, feature = "libc")))]
+        let codes = Vec::new();
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(100),
+            kinds: vec![io::ErrorKind::Interrupted, io::ErrorKind::WouldBlock],
+            codes,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Tries every operation once.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy { max_attempts: 1, ..RetryPolicy::default() }
+    }
+
+    pub fn is_retryable(&self, err: &io::Error) -> bool {
+        self.kinds.contains(&err.kind()) || err.raw_os_error().is_some_and(|code| self.codes.contains(&code))
+    }
+
+    /// The wait before retry number `retry`, counting from 0.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff.saturating_mul(1 << retry.min(31)).min(self.max_backoff)
+    }
+
+    /// Runs `op` until it succeeds, fails with an error not worth retrying, or has been
+    /// tried `max_attempts` times, and returns its last result.
+    pub fn run<T>(&self, mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        let mut retry = 0;
+        loop {
+            match op() {
+                Err(e) if retry + 1 < self.max_attempts && self.is_retryable(&e) => {
+                    thread::sleep(self.backoff(retry));
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Working directories of their own for every run, so two runs at once never touch each
+//! other's files, and the cleanup of the ones a run leaves behind.
+//!
+//! A run directory is named `bench_files-<pid>-<start time>`. [`RunDir`] creates one and
+//! removes it when dropped, including while unwinding from a panic or an error; with
+//! `handle_interrupts`, Ctrl-C and `SIGTERM` cancel the run, which then drops them, and a
+//! second Ctrl-C removes every live one before the process exits. A run killed outright
+//! (`SIGKILL`, a power cut) still leaves its directory, which [`stale`] finds by checking
+//! whether the process in its name is still running.
+
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::log::{self, Level};
+
+/// Start of every run directory's name.
+pub const PREFIX: &str = "bench_files-";
+
+/// Directory names runs used before they had IDs. Nothing marks them as this tool's, so
+/// [`legacy`] only lists them for a cleanup the user insists on.
+const LEGACY: &[&str] = &["bench_files", "bench_files_strategies", "temp_files"];
+
+/// Times [`remove_live`] tries to remove each directory.
+const REMOVE_ATTEMPTS: usize = 20;
+
+/// Directories to remove if the process is interrupted.
+static LIVE: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// This process's run ID: `<pid>-<start time in hex nanoseconds>`, the same for every call.
+pub fn run_id() -> &'static str {
+    static ID: OnceLock<String> = OnceLock::new();
+    ID.get_or_init(|| {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+        format!("{}-{:x}", std::process::id(), nanos)
+    })
+}
+
+/// This run's directory inside `parent`.
+pub fn run_dir(parent: &Path) -> PathBuf {
+    parent.join(format!("{}{}", PREFIX, run_id()))
+}
+
+/// A directory that is removed, with everything in it, when dropped or when the process
+/// is interrupted.
+#[derive(Debug)]
+pub struct RunDir {
+    path: PathBuf,
+}
+
+impl RunDir {
+    /// Creates `path` and its parents.
+    pub fn create(path: PathBuf) -> io::Result<RunDir> {
+        fs::create_dir_all(&path)?;
+        LIVE.lock().unwrap_or_else(|e| e.into_inner()).push(path.clone());
+        Ok(RunDir { path })
+    }
+
+}
+
+impl Deref for RunDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for RunDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RunDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+        LIVE.lock().unwrap_or_else(|e| e.into_inner()).retain(|path| *path != self.path);
+    }
+}
+
+/// Handles `SIGINT`, `SIGTERM` and `SIGHUP`: the first [`interrupt`]s every run, so it stops
+/// after the operations in flight, reports what finished and removes its directories on
+/// the way out; a second removes every live [`RunDir`] at once and exits with the shell's
+/// status for the signal. The signals are blocked and waited for on a thread of their own,
+/// so this must run before any other thread starts: threads inherit the blocked set, and
+/// one that doesn't would take the signal itself.
+///
+/// [`interrupt`]: crate::cancel::interrupt
+#[cfg(all(unix, feature = "libc"))]
+pub fn handle_interrupts() -> io::Result<()> {
+    let mut signals: libc::sigset_t = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::sigemptyset(&mut signals);
+        for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+            libc::sigaddset(&mut signals, signal);
+        }
+    }
+    let result = unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut()) };
+    if result != 0 {
+        return Err(io::Error::from_raw_os_error(result));
+    }
+    std::thread::Builder::new().name("io-signals".to_string()).spawn(move || {
+        let mut signal = 0;
+        if unsafe { libc::sigwait(&signals, &mut signal) } != 0 {
+            return;
+        }
+        crate::cancel::interrupt();
+        log::warn("rundir", "interrupted; finishing the operations in flight (again to stop at once)");
+        if unsafe { libc::sigwait(&signals, &mut signal) } != 0 {
+            return;
+        }
+        log::warn("rundir", "stopping; removing run directories");
+        remove_live();
+        std::process::exit(128 + signal);
+    })?;
+    Ok(())
+}
+
+/// Removes every live [`RunDir`] now, and the ramdisks they may be on, for a process about
+/// to exit without unwinding.
+pub fn remove_live() {
+    let live = std::mem::take(&mut *LIVE.lock().unwrap_or_else(|e| e.into_inner()));
+    for dir in &live {
+        // Workers may still be creating files in it, so a pass can find it not empty.
+        for _ in 0..REMOVE_ATTEMPTS {
+            if fs::remove_dir_all(dir).is_ok() || !dir.exists() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        if dir.exists() {
+            log::event(Level::Warn, "rundir", "could not remove a run directory; `io clean` will once this process is gone", &[("path", dir.display().to_string())]);
+        }
+    }
+    #[cfg(all(unix, feature = "libc"))]
+    crate::ramdisk::remove_live();
+}
+
+/// Whether the process `pid` is running. Where that can't be told, it is assumed to be, so
+/// nothing in use is ever reported stale.
+fn alive(pid: u32) -> bool {
+    #[cfg(all(unix, feature = "libc"))]
+    {
+        // 0 and negative pids name process groups, not a process.
+        match libc::pid_t::try_from(pid) {
+            Ok(pid) if pid > 0 => unsafe { libc::kill(pid, 0) == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) },
+            _ => false,
+        }
+    }
+    #[cfg(not(all(unix, feature = "libc")))]
+    {
+        let proc = Path::new("/proc");
+        !proc.is_dir() || proc.join(pid.to_string()).exists()
+    }
+}
+
+/// Run directories in `parent` left by processes that are gone.
+pub fn stale(parent: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut stale = Vec::new();
+    for entry in fs::read_dir(parent)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let pid = name.strip_prefix(PREFIX).and_then(|id| id.split('-').next()).and_then(|pid| pid.parse::<u32>().ok());
+        if pid.is_some_and(|pid| pid != std::process::id() && !alive(pid)) {
+            stale.push(entry.path());
+        }
+    }
+    stale.sort();
+    Ok(stale)
+}
+
+/// Directories in `parent` with the fixed names older versions used, which may as well
+/// be the user's own.
+pub fn legacy(parent: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut legacy = Vec::new();
+    for name in LEGACY {
+        let path = parent.join(name);
+        if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_dir()) {
+            legacy.push(path);
+        }
+    }
+    Ok(legacy)
+}
@@ -0,0 +1,155 @@
+//! Process resource usage from `getrusage`, for per-phase accounting, plus the
+//! `/proc/self/io` counters on Linux.
+
+use std::io;
+use std::ops::Add;
+use std::time::Duration;
+
+/// Resource usage of the whole process (all threads), or the difference between two samples.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Usage {
+    pub user: Duration,
+    pub system: Duration,
+    /// Peak resident set size in KiB. A high-water mark, so differences keep the later value.
+    pub max_rss_kib: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+    /// Blocks (512 bytes) read from and written to storage.
+    pub blocks_in: u64,
+    pub blocks_out: u64,
+    /// `None` where `/proc/self/io` isn't available.
+    pub io: Option<IoCounters>,
+}
+
+/// The process's I/O accounting from `/proc/self/io`, summed over all its threads.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IoCounters {
+    /// Bytes passed to `read`-like and `write`-like syscalls, cached or not.
+    pub chars_read: u64,
+    pub chars_written: u64,
+    /// `read`-like and `write`-like syscalls made.
+    pub read_calls: u64,
+    pub write_calls: u64,
+    /// Bytes fetched from and sent towards storage.
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Bytes written but truncated or deleted before writeback, so never stored.
+    pub cancelled_write_bytes: u64,
+}
+
+impl IoCounters {
+    pub fn current() -> Option<IoCounters> {
+        let text = std::fs::read_to_string("/proc/self/io").ok()?;
+        let mut counters = IoCounters::default();
+        for line in text.lines() {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let Ok(value) = value.trim().parse() else {
+                continue;
+            };
+            match name {
+                "rchar" => counters.chars_read = value,
+                "wchar" => counters.chars_written = value,
+                "syscr" => counters.read_calls = value,
+                "syscw" => counters.write_calls = value,
+                "read_bytes" => counters.bytes_read = value,
+                "write_bytes" => counters.bytes_written = value,
+                "cancelled_write_bytes" => counters.cancelled_write_bytes = value,
+                _ => {}
+            }
+        }
+        Some(counters)
+    }
+
+    pub fn since(&self, earlier: &IoCounters) -> IoCounters {
+        IoCounters {
+            chars_read: self.chars_read.saturating_sub(earlier.chars_read),
+            chars_written: self.chars_written.saturating_sub(earlier.chars_written),
+            read_calls: self.read_calls.saturating_sub(earlier.read_calls),
+            write_calls: self.write_calls.saturating_sub(earlier.write_calls),
+            bytes_read: self.bytes_read.saturating_sub(earlier.bytes_read),
+            bytes_written: self.bytes_written.saturating_sub(earlier.bytes_written),
+            cancelled_write_bytes: self.cancelled_write_bytes.saturating_sub(earlier.cancelled_write_bytes),
+        }
+    }
+}
+
+impl Add for IoCounters {
+    type Output = IoCounters;
+
+    fn add(self, other: IoCounters) -> IoCounters {
+        IoCounters {
+            chars_read: self.chars_read + other.chars_read,
+            chars_written: self.chars_written + other.chars_written,
+            read_calls: self.read_calls + other.read_calls,
+            write_calls: self.write_calls + other.write_calls,
+            bytes_read: self.bytes_read + other.bytes_read,
+            bytes_written: self.bytes_written + other.bytes_written,
+            cancelled_write_bytes: self.cancelled_write_bytes + other.cancelled_write_bytes,
+        }
+    }
+}
+
+fn timeval(tv: libc::timeval) -> Duration {
+    Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+}
+
+impl Usage {
+    pub fn current() -> io::Result<Usage> {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Usage {
+            user: timeval(usage.ru_utime),
+            system: timeval(usage.ru_stime),
+            // ru_maxrss is in KiB on Linux but bytes on macOS.
+            max_rss_kib: if cfg!(target_vendor = "apple") { usage.ru_maxrss as u64 / 1024 } else { usage.ru_maxrss as u64 },
+            minor_faults: usage.ru_minflt as u64,
+            major_faults: usage.ru_majflt as u64,
+            voluntary_switches: usage.ru_nvcsw as u64,
+            involuntary_switches: usage.ru_nivcsw as u64,
+            blocks_in: usage.ru_inblock as u64,
+            blocks_out: usage.ru_oublock as u64,
+            io: IoCounters::current(),
+        })
+    }
+
+    /// What was used between `earlier` and this sample.
+    pub fn since(&self, earlier: &Usage) -> Usage {
+        Usage {
+            user: self.user.saturating_sub(earlier.user),
+            system: self.system.saturating_sub(earlier.system),
+            max_rss_kib: self.max_rss_kib,
+            minor_faults: self.minor_faults.saturating_sub(earlier.minor_faults),
+            major_faults: self.major_faults.saturating_sub(earlier.major_faults),
+            voluntary_switches: self.voluntary_switches.saturating_sub(earlier.voluntary_switches),
+            involuntary_switches: self.involuntary_switches.saturating_sub(earlier.involuntary_switches),
+            blocks_in: self.blocks_in.saturating_sub(earlier.blocks_in),
+            blocks_out: self.blocks_out.saturating_sub(earlier.blocks_out),
+            io: self.io.zip(earlier.io).map(|(now, then)| now.since(&then)),
+        }
+    }
+}
+
+impl Add for Usage {
+    type Output = Usage;
+
+    fn add(self, other: Usage) -> Usage {
+        Usage {
+            user: self.user + other.user,
+            system: self.system + other.system,
+            max_rss_kib: self.max_rss_kib.max(other.max_rss_kib),
+            minor_faults: self.minor_faults + other.minor_faults,
+            major_faults: self.major_faults + other.major_faults,
+            voluntary_switches: self.voluntary_switches + other.voluntary_switches,
+            involuntary_switches: self.involuntary_switches + other.involuntary_switches,
+            blocks_in: self.blocks_in + other.blocks_in,
+            blocks_out: self.blocks_out + other.blocks_out,
+            io: self.io.zip(other.io).map(|(a, b)| a + b),
+        }
+    }
+}
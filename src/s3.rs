@@ -0,0 +1,503 @@
+//! An S3-compatible object store as the backend of the create/read/update/delete workload,
+//! for teams moving artifact storage to object stores who want it measured against a local
+//! filesystem on the same workload.
+//!
+//! [`Client`] speaks path-style S3 over plain HTTP/1.1 with keep-alive connections, signing
+//! every request with AWS Signature Version 4. Objects larger than [`Config::part_size`] go
+//! up as a multipart upload with their parts sent in parallel. Everything is written
+//! against the standard library, so there is no TLS: point it at an `http://` endpoint
+//! (MinIO, Ceph RGW, a local gateway, or a TLS-terminating proxy in front of S3).
+
+use std::env;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+#[cfg(feature = "bench")]
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+#[cfg(feature = "bench")]
+use std::time::Instant;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options, Phase, PhaseTimes};
+/// SHA-256, which signing needs and the standard library lacks.
+pub use crate::digest::sha256;
+
+/// Where multipart uploads start, and the size of their parts.
+pub const DEFAULT_PART_SIZE: usize = 8 << 20;
+/// The smallest part S3 accepts, except for the last.
+pub const MIN_PART_SIZE: usize = 5 << 20;
+/// Parts of one object in flight at once.
+const PART_THREADS: usize = 4;
+/// How long a connection waits on the endpoint before failing.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    /// For temporary credentials.
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Reads `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`.
+    pub fn from_env() -> io::Result<Credentials> {
+        let var = |name: &str| env::var(name).map_err(|_| io::Error::new(io::ErrorKind::NotFound, format!("{} is not set", name)));
+        Ok(Credentials { access_key: var("AWS_ACCESS_KEY_ID")?, secret_key: var("AWS_SECRET_ACCESS_KEY")?, session_token: env::var("AWS_SESSION_TOKEN").ok() })
+    }
+
+    /// The `Authorization` header for `request`, signed for `region` at `amz_date`
+    /// (`20130524T000000Z`). `request.headers` must include every header to be signed,
+    /// `host`, `x-amz-date` and `x-amz-content-sha256` among them.
+    pub fn authorization(&self, region: &str, amz_date: &str, request: &Signed) -> String {
+        let mut headers: Vec<(String, &str)> = request.headers.iter().map(|(name, value)| (name.to_ascii_lowercase(), value.trim())).collect();
+        headers.sort();
+        let signed_headers = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(";");
+        let mut canonical = format!("{}\n{}\n{}\n", request.method, request.path, canonical_query(request.query));
+        for (name, value) in &headers {
+            let _ = writeln!(canonical, "{}:{}", name, value);
+        }
+        let _ = write!(canonical, "\n{}\n{}", signed_headers, request.payload_hash);
+
+        let date = &amz_date[..amz_date.len().min(8)];
+        let scope = format!("{}/{}/s3/aws4_request", date, region);
+        let to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, hex(&sha256(canonical.as_bytes())));
+        let mut key = hmac(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes());
+        for part in [region, "s3", "aws4_request"] {
+            key = hmac(&key, part.as_bytes());
+        }
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key,
+            scope,
+            signed_headers,
+            hex(&hmac(&key, to_sign.as_bytes()))
+        )
+    }
+}
+
+/// The parts of a request that its signature covers; `path` is already URI-encoded.
+pub struct Signed<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub query: &'a [(&'a str, &'a str)],
+    pub headers: &'a [(&'a str, &'a str)],
+    /// Hex SHA-256 of the body.
+    pub payload_hash: &'a str,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// `http://host[:port]`.
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    /// `None` reads them from the environment (see [`Credentials::from_env`]).
+    pub credentials: Option<Credentials>,
+    /// Objects larger than this are uploaded in parts of this size.
+    pub part_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config { endpoint: String::new(), bucket: String::new(), region: "us-east-1".to_string(), credentials: None, part_size: DEFAULT_PART_SIZE }
+    }
+}
+
+/// A response's status, headers (names in lower case) and, for errors, body.
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct Client {
+    /// `host[:port]`, as sent in the `Host` header.
+    host: String,
+    /// `host:port`, to connect to.
+    address: String,
+    bucket: String,
+    region: String,
+    credentials: Credentials,
+    part_size: usize,
+    /// Open connections waiting for the next request.
+    idle: Mutex<Vec<TcpStream>>,
+}
+
+impl Client {
+    pub fn new(config: Config) -> io::Result<Client> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let rest = match config.endpoint.split_once("://") {
+            Some(("http", rest)) => rest,
+            Some(("https", _)) => return Err(io::Error::new(io::ErrorKind::Unsupported, "no TLS here; use an http:// endpoint, or a TLS-terminating proxy in front of one")),
+            _ => return Err(invalid(format!("endpoint {} is not an http:// URL", config.endpoint))),
+        };
+        let host = rest.trim_end_matches('/');
+        if host.is_empty() || host.contains('/') {
+            return Err(invalid(format!("endpoint {} must be just a scheme, host and port", config.endpoint)));
+        }
+        if config.bucket.is_empty() {
+            return Err(invalid("no bucket given".to_string()));
+        }
+        if config.part_size < MIN_PART_SIZE {
+            return Err(invalid(format!("parts must be at least {} bytes", MIN_PART_SIZE)));
+        }
+        let has_port = host.rsplit_once(':').is_some_and(|(_, port)| !port.contains(']'));
+        let address = if has_port { host.to_string() } else { format!("{}:80", host) };
+        let credentials = match config.credentials {
+            Some(credentials) => credentials,
+            None => Credentials::from_env()?,
+        };
+        Ok(Client { host: host.to_string(), address, bucket: config.bucket, region: config.region, credentials, part_size: config.part_size, idle: Mutex::new(Vec::new()) })
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Creates the bucket, unless this account already has it.
+    pub fn create_bucket(&self) -> io::Result<()> {
+        let response = self.send("PUT", "", &[], &[], &mut Vec::new())?;
+        match response.status {
+            200..=299 => Ok(()),
+            409 if String::from_utf8_lossy(&response.body).contains("BucketAlreadyOwnedByYou") => Ok(()),
+            _ => Err(self.error(&response, "")),
+        }
+    }
+
+    /// Stores `bytes` as `key`, in parallel parts if it is larger than the part size.
+    pub fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        if bytes.len() > self.part_size {
+            return self.put_multipart(key, bytes);
+        }
+        let response = self.send("PUT", key, &[], bytes, &mut Vec::new())?;
+        self.check(response, key).map(drop)
+    }
+
+    /// What `key` holds, if it exists.
+    pub fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let mut buf = Vec::new();
+        Ok(self.get_into(key, &mut buf)?.then_some(buf))
+    }
+
+    /// Reads what `key` holds into `buf`, replacing its contents; false if it doesn't exist.
+    pub fn get_into(&self, key: &str, buf: &mut Vec<u8>) -> io::Result<bool> {
+        buf.clear();
+        let response = self.send("GET", key, &[], &[], buf)?;
+        match response.status {
+            404 => Ok(false),
+            _ => self.check(response, key).map(|_| true),
+        }
+    }
+
+    /// Removes `key`; S3 doesn't say whether it existed.
+    pub fn delete(&self, key: &str) -> io::Result<()> {
+        let response = self.send("DELETE", key, &[], &[], &mut Vec::new())?;
+        self.check(response, key).map(drop)
+    }
+
+    fn put_multipart(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        let response = self.send("POST", key, &[("uploads", "")], &[], &mut Vec::new())?;
+        let response = self.check(response, key)?;
+        let body = String::from_utf8_lossy(&response.body);
+        let upload = xml_value(&body, "UploadId").ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{}: no UploadId in {}", key, body)))?.to_string();
+
+        let parts: Vec<&[u8]> = bytes.chunks(self.part_size).collect();
+        let tags: Vec<Mutex<Option<String>>> = parts.iter().map(|_| Mutex::new(None)).collect();
+        let next = AtomicUsize::new(0);
+        let upload_part = || -> io::Result<()> {
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(part) = parts.get(index) else { return Ok(()) };
+                let number = (index + 1).to_string();
+                let response = self.send("PUT", key, &[("partNumber", &number), ("uploadId", &upload)], part, &mut Vec::new())?;
+                let response = self.check(response, key)?;
+                let tag = response.header("etag").ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{}: part {} came back without an ETag", key, number)))?;
+                *tags[index].lock().unwrap_or_else(|e| e.into_inner()) = Some(tag.to_string());
+            }
+        };
+        let uploaded: io::Result<()> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..PART_THREADS.min(parts.len())).map(|_| scope.spawn(upload_part)).collect();
+            workers.into_iter().try_for_each(|worker| worker.join().unwrap_or_else(|_| Err(io::Error::other("part upload panicked"))))
+        });
+
+        let completed = uploaded.and_then(|()| {
+            let mut body = String::from("<CompleteMultipartUpload>");
+            for (index, tag) in tags.into_iter().enumerate() {
+                let tag = tag.into_inner().unwrap_or_else(|e| e.into_inner()).unwrap_or_default();
+                let _ = write!(body, "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", index + 1, tag);
+            }
+            body.push_str("</CompleteMultipartUpload>");
+            let response = self.send("POST", key, &[("uploadId", &upload)], body.as_bytes(), &mut Vec::new())?;
+            let response = self.check(response, key)?;
+            // Completing can fail after the 200 has been sent, with the error in the body.
+            match String::from_utf8_lossy(&response.body).contains("<Error>") {
+                true => Err(self.error(&response, key)),
+                false => Ok(()),
+            }
+        });
+        if completed.is_err() {
+            let _ = self.send("DELETE", key, &[("uploadId", &upload)], &[], &mut Vec::new());
+        }
+        completed
+    }
+
+    /// The response, if its status is a success.
+    fn check(&self, response: Response, key: &str) -> io::Result<Response> {
+        match response.status {
+            200..=299 => Ok(response),
+            _ => Err(self.error(&response, key)),
+        }
+    }
+
+    fn error(&self, response: &Response, key: &str) -> io::Error {
+        let body = String::from_utf8_lossy(&response.body);
+        let code = xml_value(&body, "Code").unwrap_or("");
+        let kind = match response.status {
+            403 => io::ErrorKind::PermissionDenied,
+            404 => io::ErrorKind::NotFound,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, format!("s3://{}/{}: HTTP {} {}", self.bucket, key, response.status, code))
+    }
+
+    /// Sends a signed request for `key` (the bucket itself when empty) and reads the
+    /// response; a successful GET's body goes to `body`, any other body to the response.
+    /// A connection the server closed while idle is replaced once.
+    fn send(&self, method: &str, key: &str, query: &[(&str, &str)], payload: &[u8], body: &mut Vec<u8>) -> io::Result<Response> {
+        let mut path = format!("/{}", encode(&self.bucket, false));
+        if !key.is_empty() {
+            path.push('/');
+            path.push_str(&encode(key, true));
+        }
+        let amz_date = amz_date(SystemTime::now());
+        let payload_hash = hex(&sha256(payload));
+        let length = payload.len().to_string();
+        let mut headers = vec![("host", self.host.as_str()), ("x-amz-content-sha256", payload_hash.as_str()), ("x-amz-date", amz_date.as_str())];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.as_str()));
+        }
+        let authorization = self.credentials.authorization(&self.region, &amz_date, &Signed { method, path: &path, query, headers: &headers, payload_hash: &payload_hash });
+
+        let mut request = format!("{} {}", method, path);
+        if !query.is_empty() {
+            request.push('?');
+            request.push_str(&canonical_query(query));
+        }
+        request.push_str(" HTTP/1.1\r\n");
+        for (name, value) in headers.iter().chain([&("authorization", authorization.as_str()), &("content-length", length.as_str())]) {
+            let _ = write!(request, "{}: {}\r\n", name, value);
+        }
+        request.push_str("\r\n");
+
+        let pooled = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let reused = pooled.is_some();
+        let stream = match pooled {
+            Some(stream) => stream,
+            None => self.connect()?,
+        };
+        match self.exchange(stream, &request, payload, method, body) {
+            Err(e) if reused && matches!(e.kind(), io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::UnexpectedEof) => {
+                body.clear();
+                self.exchange(self.connect()?, &request, payload, method, body)
+            }
+            result => result,
+        }
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let stream = TcpStream::connect(&self.address).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", self.address, e)))?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        Ok(stream)
+    }
+
+    /// Writes one request on `stream` and reads its response, returning the connection to
+    /// the pool if the server keeps it open.
+    fn exchange(&self, stream: TcpStream, head: &str, payload: &[u8], method: &str, body: &mut Vec<u8>) -> io::Result<Response> {
+        let mut writer = &stream;
+        writer.write_all(head.as_bytes())?;
+        writer.write_all(payload)?;
+        let mut reader = BufReader::new(&stream);
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let status = line.split(' ').nth(1).and_then(|status| status.parse().ok()).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("not an HTTP status line: {:?}", line.trim_end())))?;
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+        let mut response = Response { status, headers, body: Vec::new() };
+        let chunked = response.header("transfer-encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+        let length = match response.header("content-length") {
+            Some(length) => Some(length.parse::<usize>().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("bad Content-Length {}", length)))?),
+            None => None,
+        };
+        let closing = response.header("connection").is_some_and(|connection| connection.eq_ignore_ascii_case("close"));
+        let target = if method == "GET" && (200..300).contains(&status) { &mut *body } else { &mut response.body };
+        if chunked {
+            read_chunked(&mut reader, target)?;
+        } else if let Some(length) = length {
+            let start = target.len();
+            target.resize(start + length, 0);
+            reader.read_exact(&mut target[start..])?;
+        } else if status >= 200 && status != 204 && status != 304 {
+            // No length: the body runs to the end of the connection.
+            reader.read_to_end(target)?;
+            return Ok(response);
+        }
+        if !closing && reader.buffer().is_empty() {
+            drop(reader);
+            self.idle.lock().unwrap_or_else(|e| e.into_inner()).push(stream);
+        }
+        Ok(response)
+    }
+}
+
+fn read_chunked(reader: &mut impl BufRead, target: &mut Vec<u8>) -> io::Result<()> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let size = line.trim().split(';').next().unwrap_or("");
+        let size = usize::from_str_radix(size, 16).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("bad chunk size {:?}", size)))?;
+        if size == 0 {
+            // Trailers, then the empty line.
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                    return Ok(());
+                }
+            }
+        }
+        let start = target.len();
+        target.resize(start + size, 0);
+        reader.read_exact(&mut target[start..])?;
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+}
+
+/// The text of the first `<tag>` element in `xml`.
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(&xml[start..end])
+}
+
+/// Percent-encodes all but unreserved characters, and `/` too unless `keep_slashes`.
+fn encode(s: &str, keep_slashes: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(b as char),
+            b'/' if keep_slashes => encoded.push('/'),
+            _ => {
+                let _ = write!(encoded, "%{:02X}", b);
+            }
+        }
+    }
+    encoded
+}
+
+/// Query parameters encoded and sorted, as signing and the request line both want them.
+fn canonical_query(query: &[(&str, &str)]) -> String {
+    let mut pairs: Vec<(String, String)> = query.iter().map(|(name, value)| (encode(name, false), encode(value, false))).collect();
+    pairs.sort();
+    pairs.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("&")
+}
+
+/// `time` as `YYYYMMDDTHHMMSSZ`.
+fn amz_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, of_day) = ((secs / 86_400) as i64, secs % 86_400);
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, of_day / 3600, of_day / 60 % 60, of_day % 60)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut s, b| {
+        let _ = write!(s, "{:02x}", b);
+        s
+    })
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0; 64];
+    if key.len() > 64 {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    inner.extend_from_slice(message);
+    let mut outer: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct S3Result {
+    pub times: PhaseTimes,
+    pub failures: Vec<Failure>,
+}
+
+/// Runs the create, read, update and delete phases of the configured workload against
+/// `client`'s bucket, one object per file under `prefix/`, on the current rayon pool.
+#[cfg(feature = "bench")]
+pub fn run(client: &Client, prefix: &str, options: &Options) -> io::Result<S3Result> {
+    let keys = options.workload.paths(Path::new(prefix));
+    let key = |path: &Path| path.to_str().map(|key| key.replace('\\', "/")).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not UTF-8", path.display())));
+    let mut result = S3Result::default();
+    options.failures.take("");
+    for phase in Phase::ALL {
+        let start = Instant::now();
+        bench::phase(options, format!("S3 {}", phase.name()), format!("s3/{}", phase.name()), phase.name(), &mut result.failures, || {
+            bench::each_indexed(&keys, options, |index, path| {
+                let key = key(path)?;
+                match phase {
+                    Phase::Create | Phase::Update => bench::with_content(options, index, phase == Phase::Update, |content| client.put(&key, content)),
+                    Phase::Read => crate::buffers::with_buffer(|buf| match client.get_into(&key, buf)? {
+                        true => bench::read_done(options, index, path, buf),
+                        false => Err(io::Error::new(io::ErrorKind::NotFound, format!("s3://{}/{} is missing", client.bucket(), key))),
+                    }),
+                    Phase::Delete => client.delete(&key),
+                }
+            })
+        })?;
+        *result.times.phase_mut(phase) = start.elapsed();
+    }
+    Ok(result)
+}
@@ -0,0 +1,191 @@
+//! Estimating totals of trees too big to list, from random walks down the tree.
+//!
+//! A probe starts at the root and keeps stepping into one subdirectory picked uniformly at
+//! random until it reaches a directory without any. Each directory it passes is weighted by
+//! the inverse of the chance of reaching it, the product of the subdirectory counts above
+//! it, which makes a probe's weighted sums unbiased estimates of the whole tree's totals
+//! (Knuth's estimator for the size of a search tree). Averaging independent probes narrows
+//! the estimate, and their spread gives its confidence interval. Per-file measurements,
+//! such as how long a file takes to hash, are estimated the same way from one random file
+//! of each directory passed.
+//!
+//! Lopsided trees, where a few deep branches hold most of the files, need more probes for
+//! the same interval; the interval widens to show it rather than the estimate staying
+//! quietly off.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::population::Rng;
+
+/// Probes [`Sampler::new`] takes unless told otherwise.
+pub const DEFAULT_PROBES: usize = 1000;
+
+/// z for a two-sided 95% interval under the normal approximation, sound from a few dozen
+/// probes on.
+const Z_95: f64 = 1.96;
+
+/// An extrapolated total and the half-width of its 95% confidence interval.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Estimate {
+    pub mean: f64,
+    pub margin: f64,
+}
+
+impl Estimate {
+    /// The mean of `values` and the interval from their standard error.
+    pub fn of(values: impl IntoIterator<Item = f64>) -> Estimate {
+        let values: Vec<f64> = values.into_iter().collect();
+        let n = values.len() as f64;
+        if values.is_empty() {
+            return Estimate::default();
+        }
+        let mean = values.iter().sum::<f64>() / n;
+        if values.len() < 2 {
+            return Estimate { mean, margin: f64::INFINITY };
+        }
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        Estimate { mean, margin: Z_95 * (variance / n).sqrt() }
+    }
+
+    /// The interval's ends, the lower clamped at zero since every total here is a count or
+    /// a size.
+    pub fn interval(&self) -> (f64, f64) {
+        ((self.mean - self.margin).max(0.0), self.mean + self.margin)
+    }
+
+    /// The margin as a fraction of the estimate.
+    pub fn relative(&self) -> f64 {
+        if self.mean == 0.0 { 0.0 } else { self.margin / self.mean }
+    }
+}
+
+/// What one directory holds, listed once and reused by later probes through it.
+#[derive(Debug, Default)]
+struct Listing {
+    subdirs: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+    bytes: u64,
+}
+
+/// One random walk's estimates of the tree's totals.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Probe {
+    pub dirs: f64,
+    pub files: f64,
+    pub bytes: f64,
+    /// Estimated totals of each per-file measurement.
+    pub measured: Vec<f64>,
+}
+
+/// The probes over one tree.
+#[derive(Debug, Clone, Default)]
+pub struct TreeSample {
+    pub probes: Vec<Probe>,
+    /// Distinct directories listed.
+    pub listed: usize,
+    /// Directories that couldn't be listed and were counted as empty.
+    pub unreadable: usize,
+}
+
+impl TreeSample {
+    pub fn dirs(&self) -> Estimate {
+        Estimate::of(self.probes.iter().map(|probe| probe.dirs))
+    }
+
+    pub fn files(&self) -> Estimate {
+        Estimate::of(self.probes.iter().map(|probe| probe.files))
+    }
+
+    pub fn bytes(&self) -> Estimate {
+        Estimate::of(self.probes.iter().map(|probe| probe.bytes))
+    }
+
+    /// The total of the `index`th per-file measurement.
+    pub fn measured(&self, index: usize) -> Estimate {
+        Estimate::of(self.probes.iter().map(|probe| probe.measured.get(index).copied().unwrap_or(0.0)))
+    }
+}
+
+/// Random walks over a tree.
+#[derive(Debug, Clone, Copy)]
+pub struct Sampler {
+    pub probes: usize,
+    pub seed: u64,
+}
+
+impl Sampler {
+    /// `probes` walks from a seed taken from the clock.
+    pub fn new(probes: usize) -> Sampler {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64);
+        Sampler { probes: probes.max(1), seed }
+    }
+
+    /// Estimates the directories, files and bytes under `root` without following symlinks.
+    pub fn run(&self, root: &Path) -> io::Result<TreeSample> {
+        self.run_measuring(root, |_| Vec::new())
+    }
+
+    /// Like [`run`](Sampler::run), also estimating the totals of `measure` over every file.
+    /// `measure` is called on one random file per directory a probe passes and returns the
+    /// same number of values each time.
+    pub fn run_measuring(&self, root: &Path, mut measure: impl FnMut(&Path) -> Vec<f64>) -> io::Result<TreeSample> {
+        let mut listings: HashMap<PathBuf, Listing> = HashMap::new();
+        let mut sample = TreeSample::default();
+        // The root must be readable; directories below may not be.
+        listings.insert(root.to_path_buf(), list(root)?);
+        let mut rng = Rng(self.seed.max(1));
+        for _ in 0..self.probes {
+            let mut probe = Probe::default();
+            let (mut dir, mut weight) = (root.to_path_buf(), 1.0);
+            loop {
+                let listing = listings.entry(dir.clone()).or_insert_with(|| {
+                    list(&dir).unwrap_or_else(|_| {
+                        sample.unreadable += 1;
+                        Listing::default()
+                    })
+                });
+                probe.dirs += weight;
+                probe.files += weight * listing.files.len() as f64;
+                probe.bytes += weight * listing.bytes as f64;
+                if !listing.files.is_empty() {
+                    let file = &listing.files[rng.below(listing.files.len())];
+                    let values = measure(file);
+                    probe.measured.resize(probe.measured.len().max(values.len()), 0.0);
+                    for (total, value) in probe.measured.iter_mut().zip(values) {
+                        *total += weight * listing.files.len() as f64 * value;
+                    }
+                }
+                if listing.subdirs.is_empty() {
+                    break;
+                }
+                weight *= listing.subdirs.len() as f64;
+                dir = listing.subdirs[rng.below(listing.subdirs.len())].clone();
+            }
+            sample.probes.push(probe);
+        }
+        sample.listed = listings.len();
+        Ok(sample)
+    }
+}
+
+fn list(dir: &Path) -> io::Result<Listing> {
+    let mut listing = Listing::default();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            listing.subdirs.push(entry.path());
+        } else if file_type.is_file() {
+            listing.bytes += entry.metadata().map_or(0, |metadata| metadata.len());
+            listing.files.push(entry.path());
+        }
+    }
+    // Sorted so a seed picks the same walks whatever order the filesystem lists in.
+    listing.subdirs.sort();
+    listing.files.sort();
+    Ok(listing)
+}
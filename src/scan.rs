@@ -0,0 +1,262 @@
+//! Directory traversal phases: enumerating a nested tree of the workload's files the ways
+//! linters, formatters and build tools discover their inputs, which on large trees can cost
+//! as much as reading the files.
+//!
+//! Every [`Walker`] counts the same entries (files and directories, not the root) without
+//! following symlinks:
+//!
+//! - [`Walker::ReadDir`] recurses with `fs::read_dir` and stats every entry to learn
+//!   whether it is a directory, as naive code using `Path::is_dir` does.
+//! - [`Walker::Walkdir`] walks depth-first from an explicit stack and takes entry types
+//!   from `d_type`, as the `walkdir` crate does, so it only stats on filesystems that don't
+//!   report types.
+//! - [`Walker::Parallel`] reads each directory in its own rayon task, as `jwalk` does.
+//! - [`Walker::Getdents`] calls `getdents64` with a large buffer and opens subdirectories
+//!   with `openat` relative to their parent, never building a path. Linux only.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::bench::{self, Failure, Options};
+
+/// Files per leaf directory of the generated tree.
+pub const FILES_PER_DIR: usize = 64;
+/// Leaf directories per top-level directory.
+pub const FANOUT: usize = 16;
+
+/// Paths for `files` files under `root`, [`FILES_PER_DIR`] to a directory and
+/// [`FANOUT`] directories to a parent: `root/d{a}/d{b}/file_{i}.txt`.
+pub fn tree_paths(root: &Path, files: usize) -> Vec<PathBuf> {
+    (0..files)
+        .map(|i| {
+            let leaf = i / FILES_PER_DIR;
+            root.join(format!("d{}", leaf / FANOUT)).join(format!("d{}", leaf % FANOUT)).join(format!("file_{}.txt", i))
+        })
+        .collect()
+}
+
+/// A way of enumerating a tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Walker {
+    ReadDir,
+    Walkdir,
+    Parallel,
+    Getdents,
+}
+
+impl Walker {
+    pub const ALL: [Walker; 4] = [Walker::ReadDir, Walker::Walkdir, Walker::Parallel, Walker::Getdents];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Walker::ReadDir => "read_dir",
+            Walker::Walkdir => "walkdir",
+            Walker::Parallel => "parallel",
+            Walker::Getdents => "getdents64",
+        }
+    }
+
+    /// Counts the entries under `root`.
+    pub fn walk(self, root: &Path, options: &Options) -> io::Result<usize> {
+        match self {
+            Walker::ReadDir => walk_read_dir(root, options),
+            Walker::Walkdir => walk_stack(root, options),
+            Walker::Parallel => walk_parallel(root, options),
+            Walker::Getdents => walk_getdents(root, options),
+        }
+    }
+}
+
+fn walk_read_dir(dir: &Path, options: &Options) -> io::Result<usize> {
+    options.pause.wait();
+    let mut entries = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        entries += 1;
+        if fs::symlink_metadata(&path)?.is_dir() {
+            entries += walk_read_dir(&path, options)?;
+        }
+    }
+    Ok(entries)
+}
+
+fn walk_stack(root: &Path, options: &Options) -> io::Result<usize> {
+    let mut entries = 0;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        options.pause.wait();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            entries += 1;
+            if entry.file_type()?.is_dir() {
+                pending.push(entry.path());
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn walk_parallel(root: &Path, options: &Options) -> io::Result<usize> {
+    fn visit<'s>(scope: &rayon::Scope<'s>, dir: PathBuf, entries: &'s AtomicUsize, error: &'s Mutex<Option<io::Error>>, options: &'s Options) {
+        options.pause.wait();
+        let read = || -> io::Result<()> {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                entries.fetch_add(1, Ordering::Relaxed);
+                if entry.file_type()?.is_dir() {
+                    let path = entry.path();
+                    scope.spawn(move |scope| visit(scope, path, entries, error, options));
+                }
+            }
+            Ok(())
+        };
+        if let Err(e) = read() {
+            error.lock().unwrap().get_or_insert(e);
+        }
+    }
+
+    let entries = AtomicUsize::new(0);
+    let error = Mutex::new(None);
+    rayon::scope(|scope| visit(scope, root.to_path_buf(), &entries, &error, options));
+    match error.into_inner().unwrap() {
+        Some(e) => Err(e),
+        None => Ok(entries.into_inner()),
+    }
+}
+
+fn walk_getdents(root: &Path, options: &Options) -> io::Result<usize> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::ffi::{CStr, CString};
+        use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+        use std::os::unix::ffi::OsStrExt;
+
+        /// Bytes read per `getdents64` call; enough for a few hundred entries.
+        const BUFFER_LEN: usize = 32 * 1024;
+
+        fn open_dir(parent: RawFd, name: &CStr) -> io::Result<OwnedFd> {
+            let fd = unsafe { libc::openat(parent, name.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC) };
+            if fd < 0 { Err(io::Error::last_os_error()) } else { Ok(unsafe { OwnedFd::from_raw_fd(fd) }) }
+        }
+
+        fn visit(dir: &OwnedFd, buffer: &mut [u64], options: &Options) -> io::Result<usize> {
+            options.pause.wait();
+            let mut entries = 0;
+            let mut subdirs = Vec::new();
+            loop {
+                let read = unsafe { libc::syscall(libc::SYS_getdents64, dir.as_raw_fd(), buffer.as_mut_ptr(), size_of_val(buffer)) };
+                if read < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if read == 0 {
+                    break;
+                }
+                let bytes = unsafe { std::slice::from_raw_parts(buffer.as_ptr().cast::<u8>(), read as usize) };
+                let mut offset = 0;
+                // struct linux_dirent64 { u64 d_ino; i64 d_off; u16 d_reclen; u8 d_type; char d_name[]; }
+                while offset < bytes.len() {
+                    let record = &bytes[offset..];
+                    let reclen = u16::from_ne_bytes([record[16], record[17]]) as usize;
+                    let kind = record[18];
+                    let name = CStr::from_bytes_until_nul(&record[19..reclen]).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "unterminated directory entry name"))?;
+                    offset += reclen;
+                    if name == c"." || name == c".." {
+                        continue;
+                    }
+                    entries += 1;
+                    let is_dir = match kind {
+                        libc::DT_DIR => true,
+                        libc::DT_UNKNOWN => {
+                            let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+                            if unsafe { libc::fstatat(dir.as_raw_fd(), name.as_ptr(), stat.as_mut_ptr(), libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+                                return Err(io::Error::last_os_error());
+                            }
+                            unsafe { stat.assume_init() }.st_mode & libc::S_IFMT == libc::S_IFDIR
+                        }
+                        _ => false,
+                    };
+                    if is_dir {
+                        subdirs.push(name.to_owned());
+                    }
+                }
+            }
+            // Subdirectories are opened after the parent is fully read, so only one
+            // descriptor per level is open at a time.
+            for name in subdirs {
+                entries += visit(&open_dir(dir.as_raw_fd(), &name)?, buffer, options)?;
+            }
+            Ok(entries)
+        }
+
+        let root = CString::new(root.as_os_str().as_bytes()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))?;
+        let dir = open_dir(libc::AT_FDCWD, &root)?;
+        // u64s keep the records 8-byte aligned, as the kernel lays them out.
+        let mut buffer = vec![0u64; BUFFER_LEN / 8];
+        visit(&dir, &mut buffer, options)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (root, options);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "getdents64 is only available on Linux"))
+    }
+}
+
+/// One timed walk of the tree.
+#[derive(Debug, Clone, Copy)]
+pub struct Walk {
+    pub walker: Walker,
+    pub entries: usize,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub walks: Vec<Walk>,
+    /// Walkers that couldn't run here, with why.
+    pub skipped: Vec<(Walker, io::Error)>,
+    pub failures: Vec<Failure>,
+}
+
+/// Creates the configured workload as a nested tree in `dir_path/tree` (see
+/// [`tree_paths`]), times each walker enumerating it, then deletes it. Every walker must
+/// find the same number of entries.
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<ScanResult> {
+    let root = dir_path.join("tree");
+    let paths = tree_paths(&root, options.workload.files);
+    for path in &paths {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    let mut result = ScanResult::default();
+    options.failures.take("");
+
+    bench::phase(options, "Scan create", "scan/create", "create", &mut result.failures, || bench::create_files(&paths, options))?;
+
+    for walker in Walker::ALL {
+        options.progress.set_stage(format!("Scan {}", walker.name()));
+        options.op_scope.set(format!("scan/{}", walker.name()));
+        let start = Instant::now();
+        let entries = match walker.walk(&root, options) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                result.skipped.push((walker, e));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let elapsed = start.elapsed();
+        if let Some(first) = result.walks.first().filter(|first| first.entries != entries) {
+            return Err(io::Error::other(format!("{} found {} entries but {} found {}", walker.name(), entries, first.walker.name(), first.entries)));
+        }
+        result.walks.push(Walk { walker, entries, elapsed });
+    }
+
+    bench::phase(options, "Scan delete", "scan/delete", "delete", &mut result.failures, || bench::delete_files(&paths, options))?;
+    fs::remove_dir_all(&root)?;
+    Ok(result)
+}
@@ -0,0 +1,195 @@
+//! Pausing bulk work, and restricting it to a daily time window.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+
+/// How often a paused worker checks whether the run was cancelled.
+const CANCEL_CHECK: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Default)]
+struct PauseState {
+    paused_since: Option<Instant>,
+    paused_total: Duration,
+}
+
+/// Lets a controller pause workers between operations. Workers call [`PauseGate::wait`]
+/// before each unit of work; it returns immediately unless the gate is paused.
+#[derive(Debug, Default)]
+pub struct PauseGate {
+    state: Mutex<PauseState>,
+    resumed: Condvar,
+}
+
+impl PauseGate {
+    pub fn pause(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.paused_since.get_or_insert_with(Instant::now);
+    }
+
+    pub fn resume(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(since) = state.paused_since.take() {
+            state.paused_total += since.elapsed();
+        }
+        self.resumed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).paused_since.is_some()
+    }
+
+    /// Total time spent paused so far, including a pause still in progress.
+    pub fn paused_total(&self) -> Duration {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.paused_total + state.paused_since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    /// Blocks while the gate is paused, unless the run is cancelled.
+    pub fn wait(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.paused_since.is_some() && !cancel::is_interrupted() {
+            // Cancelling doesn't know about the gate, so the wait wakes now and then to look.
+            state = self.resumed.wait_timeout(state, CANCEL_CHECK).unwrap_or_else(|e| e.into_inner()).0;
+        }
+    }
+}
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// A daily window such as `02:00-04:00`, in local time. The end may be earlier than the
+/// start for windows that cross midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    start: u16,
+    end: u16,
+}
+
+fn parse_time(s: &str) -> Result<u16, String> {
+    let (hours, minutes) = s.trim().split_once(':').ok_or_else(|| format!("'{}' is not HH:MM", s))?;
+    let hours: u16 = hours.parse().map_err(|_| format!("'{}' is not HH:MM", s))?;
+    let minutes: u16 = minutes.parse().map_err(|_| format!("'{}' is not HH:MM", s))?;
+    if hours > 23 || minutes > 59 {
+        return Err(format!("'{}' is not a time of day", s));
+    }
+    Ok(hours * 60 + minutes)
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (start, end) = s.split_once('-').ok_or_else(|| format!("'{}' is not HH:MM-HH:MM", s))?;
+        let window = Window { start: parse_time(start)?, end: parse_time(end)? };
+        if window.start == window.end {
+            return Err("window start and end must differ".to_string());
+        }
+        Ok(window)
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}-{:02}:{:02}", self.start / 60, self.start % 60, self.end / 60, self.end % 60)
+    }
+}
+
+impl Window {
+    pub fn contains(&self, minute_of_day: u16) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute_of_day)
+        } else {
+            minute_of_day >= self.start || minute_of_day < self.end
+        }
+    }
+
+    /// Minutes from `minute_of_day` until the window next opens (0 if it is open).
+    pub fn minutes_until_open(&self, minute_of_day: u16) -> u16 {
+        if self.contains(minute_of_day) {
+            return 0;
+        }
+        (self.start + MINUTES_PER_DAY - minute_of_day) % MINUTES_PER_DAY
+    }
+}
+
+/// The current local time as minutes since midnight.
+pub fn local_minute_of_day() -> u16 {
+    #[cfg(all(unix, feature = "libc"))]
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if !libc::localtime_r(&now, &mut tm).is_null() {
+            return (tm.tm_hour * 60 + tm.tm_min) as u16;
+        }
+    }
+    // Without a timezone database, fall back to UTC.
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    ((secs / 60) % MINUTES_PER_DAY as u64) as u16
+}
+
+/// Background thread that polls a condition and pauses a gate while it reports a reason
+/// to hold off. Stops, and resumes the gate, when dropped.
+pub struct Scheduler {
+    stop: Arc<AtomicBool>,
+    gate: Arc<PauseGate>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    /// Checks `hold` every `interval`; while it returns a reason, `gate` stays paused.
+    /// `on_change` is called with the reason when work pauses and `None` when it resumes.
+    pub fn spawn<C, F>(gate: Arc<PauseGate>, interval: Duration, mut hold: C, on_change: F) -> io::Result<Scheduler>
+    where
+        C: FnMut() -> Option<String> + Send + 'static,
+        F: Fn(Option<&str>) + Send + 'static,
+    {
+        // Apply the condition before any work starts rather than up to one interval later.
+        if let Some(reason) = hold() {
+            gate.pause();
+            on_change(Some(&reason));
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let (stop, gate) = (stop.clone(), gate.clone());
+            thread::Builder::new().name("io-scheduler".to_string()).spawn(move || {
+                loop {
+                    thread::park_timeout(interval);
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    match hold() {
+                        Some(reason) if !gate.is_paused() => {
+                            gate.pause();
+                            on_change(Some(&reason));
+                        }
+                        None if gate.is_paused() => {
+                            gate.resume();
+                            on_change(None);
+                        }
+                        _ => {}
+                    }
+                }
+            })?
+        };
+        Ok(Scheduler { stop, gate, handle: Some(handle) })
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        self.gate.resume();
+    }
+}
@@ -0,0 +1,202 @@
+//! Picking files out of an existing tree by glob patterns and predicates, so read phases
+//! can run against a user's real files (`--root ./src --glob "**/*.rs"`) instead of the
+//! numbered files the benchmark generates.
+//!
+//! Patterns match paths relative to the root, with `/` between components on every
+//! platform: `*` matches within one component, `?` one character, `[abc]`, `[a-z]` and
+//! `[!abc]` one character of (or not of) a set, `{rs,toml}` any of the alternatives, and a
+//! whole component `**` any number of directories, none included. A pattern without a `/`
+//! matches the file name at any depth, so `*.rs` and `**/*.rs` select the same files.
+
+use std::fmt;
+use std::io;
+use std::path::{Component, Path};
+use std::sync::Arc;
+
+/// One glob pattern, compiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: String,
+    /// Each `{}` alternative, split into components.
+    alternatives: Vec<Vec<String>>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> io::Result<Glob> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid glob '{}': {}", pattern, message));
+        if pattern.is_empty() {
+            return Err(invalid("it is empty"));
+        }
+        let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+        // A bare name matches at any depth.
+        let rooted = if pattern.contains('/') { pattern.to_string() } else { format!("**/{}", pattern) };
+        let alternatives = expand(&rooted).map_err(invalid)?;
+        for components in &alternatives {
+            for component in components {
+                check_classes(component).map_err(invalid)?;
+            }
+        }
+        Ok(Glob { pattern: pattern.to_string(), alternatives })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether `path`, relative to the root, matches.
+    pub fn matches(&self, path: &Path) -> bool {
+        let components: Vec<String> = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        let components: Vec<&str> = components.iter().map(String::as_str).collect();
+        self.alternatives.iter().any(|pattern| {
+            let pattern: Vec<&str> = pattern.iter().map(String::as_str).collect();
+            match_components(&pattern, &components)
+        })
+    }
+}
+
+impl std::str::FromStr for Glob {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Glob> {
+        Glob::new(s)
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+/// Every pattern `{a,b}` alternatives expand to, split into components. Braces don't
+/// nest.
+fn expand(pattern: &str) -> Result<Vec<Vec<String>>, &'static str> {
+    let mut expanded = vec![String::new()];
+    let mut rest = pattern;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..].find('}').ok_or("an unclosed '{'")? + open;
+        if rest[open + 1..close].contains('{') {
+            return Err("nested '{'");
+        }
+        let options: Vec<&str> = rest[open + 1..close].split(',').collect();
+        expanded = expanded.iter().flat_map(|prefix| options.iter().map(move |option| format!("{}{}{}", prefix, &rest[..open], option))).collect();
+        rest = &rest[close + 1..];
+    }
+    if rest.contains('}') {
+        return Err("a '}' without its '{'");
+    }
+    Ok(expanded.into_iter().map(|prefix| format!("{}{}", prefix, rest).split('/').filter(|component| !component.is_empty()).map(str::to_string).collect()).collect())
+}
+
+fn check_classes(component: &str) -> Result<(), &'static str> {
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        if c == '[' && !chars.any(|c| c == ']') {
+            return Err("an unclosed '['");
+        }
+    }
+    Ok(())
+}
+
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_components(rest, &path[skip..])),
+        Some((first, rest)) => path.split_first().is_some_and(|(name, path)| match_name(&first.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>()) && match_components(rest, path)),
+    }
+}
+
+fn match_name(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| match_name(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && match_name(rest, &name[1..]),
+        Some(('[', rest)) => {
+            let Some(close) = rest.iter().skip(1).position(|&c| c == ']').map(|i| i + 1) else {
+                return false;
+            };
+            let (negated, set) = match rest[0] {
+                '!' | '^' => (true, &rest[1..close]),
+                _ => (false, &rest[..close]),
+            };
+            name.split_first().is_some_and(|(&c, name)| in_set(set, c) != negated && match_name(&rest[close + 1..], name))
+        }
+        Some((&c, rest)) => name.split_first().is_some_and(|(&n, name)| n == c && match_name(rest, name)),
+    }
+}
+
+fn in_set(set: &[char], c: char) -> bool {
+    let mut i = 0;
+    while i < set.len() {
+        if i + 2 < set.len() && set[i + 1] == '-' {
+            if (set[i]..=set[i + 2]).contains(&c) {
+                return true;
+            }
+            i += 3;
+        } else {
+            if set[i] == c {
+                return true;
+            }
+            i += 1;
+        }
+    }
+    false
+}
+
+/// A test on a file's path, relative to the root, and its size.
+pub type Predicate = Arc<dyn Fn(&Path, u64) -> bool + Send + Sync>;
+
+/// Which files of a tree to use: those matching any included glob (every file when there
+/// are none), no excluded glob, and every predicate.
+#[derive(Clone, Default)]
+pub struct Selector {
+    pub include: Vec<Glob>,
+    pub exclude: Vec<Glob>,
+    predicates: Vec<Predicate>,
+}
+
+impl Selector {
+    pub fn new() -> Selector {
+        Selector::default()
+    }
+
+    pub fn include(mut self, glob: Glob) -> Selector {
+        self.include.push(glob);
+        self
+    }
+
+    pub fn exclude(mut self, glob: Glob) -> Selector {
+        self.exclude.push(glob);
+        self
+    }
+
+    /// Keeps only files `predicate` accepts.
+    pub fn filter(mut self, predicate: impl Fn(&Path, u64) -> bool + Send + Sync + 'static) -> Selector {
+        self.predicates.push(Arc::new(predicate));
+        self
+    }
+
+    /// Whether nothing narrows the selection.
+    pub fn is_all(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.predicates.is_empty()
+    }
+
+    /// Whether the file at `path`, relative to the root, of `size` bytes is selected.
+    pub fn matches(&self, path: &Path, size: u64) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|glob| glob.matches(path)))
+            && !self.exclude.iter().any(|glob| glob.matches(path))
+            && self.predicates.iter().all(|predicate| predicate(path, size))
+    }
+}
+
+impl fmt::Debug for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Selector").field("include", &self.include).field("exclude", &self.exclude).field("predicates", &self.predicates.len()).finish()
+    }
+}
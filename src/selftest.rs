@@ -0,0 +1,492 @@
+//! Conformance of every I/O backend in the build, for `io selftest`. A benchmark number
+//! only means something if the backend behind it did the work it claims on this
+//! platform: a mapping of an empty file refused, a short read taken for a whole file, or a
+//! ring that swallows an error would otherwise pass as a fast result. [`check`] runs a
+//! [`Backend`] over [`FILES`] files of each of a few awkward [`SIZES`], verifying as it
+//! goes, and reports per [`Check`] whether it held:
+//!
+//! - content and sizes: files hold exactly what was written, read back through the backend
+//!   and, where files are kept as named, with plain `std::fs` too;
+//! - timestamps, where promised: created files aren't stamped before their creation, reads
+//!   leave modification times alone, and updates of some bytes through `write` or a synced
+//!   mapping move them forward. A mapping not yet synced may stamp the file later, and the
+//!   strategies may update through one, so those updates aren't held to it;
+//! - deletes leave nothing behind;
+//! - a missing parent directory fails with `NotFound`, rather than being created, panicking
+//!   or passing;
+//! - a read-only target refuses writes and deletes with `PermissionDenied`, leaving the
+//!   files as they were, and can still be read. Where permissions aren't enforced, as for
+//!   root, that is skipped.
+//!
+//! A phase a backend has no way of its own to do, such as creating files for a read
+//! method, is done the create, read, update or delete phase's default way and only checked
+//! for what it leaves the backend to work with.
+
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::bench::{self, Options, Phase, ReadMethod, STRATEGIES, Strategy, UpdateMethod, Workload};
+use crate::contents;
+#[cfg(target_os = "linux")]
+use crate::{linked, registered, uring};
+use crate::order;
+use crate::pattern::{Generator, Pattern};
+use crate::retry::RetryPolicy;
+
+/// File sizes every backend is run at: empty, one byte, a page and a byte, and a mebibyte
+/// and a few bytes, so no size is a whole number of pages or buffers.
+pub const SIZES: [usize; 4] = [0, 1, 4097, (1 << 20) + 3];
+
+/// Files of each size.
+pub const FILES: usize = 8;
+
+/// Files an io_uring linked create submits per `io_uring_enter`, fewer than [`FILES`] so
+/// there is more than one batch.
+#[cfg(target_os = "linux")]
+const LINKED_BATCH: u32 = 3;
+
+/// What files are stamped with before a phase whose effect on the stamps is checked.
+fn long_ago() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(86_400)
+}
+
+/// One property a backend is checked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Content,
+    Sizes,
+    Timestamps,
+    Delete,
+    MissingParent,
+    ReadOnly,
+}
+
+impl Check {
+    pub const ALL: [Check; 6] = [Check::Content, Check::Sizes, Check::Timestamps, Check::Delete, Check::MissingParent, Check::ReadOnly];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Content => "content",
+            Check::Sizes => "sizes",
+            Check::Timestamps => "timestamps",
+            Check::Delete => "delete",
+            Check::MissingParent => "missing parent",
+            Check::ReadOnly => "read-only",
+        }
+    }
+}
+
+/// How a backend did on one [`Check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Held every time it was checked.
+    Pass,
+    /// Didn't, with what went wrong the first time.
+    Fail(String),
+    /// Couldn't be checked here, with why.
+    Skipped(String),
+    /// The backend promises nothing the check covers.
+    NotApplicable,
+}
+
+/// Something the benchmark can do I/O with.
+#[derive(Debug, Clone, Copy)]
+pub enum Backend {
+    /// A benchmark strategy, every phase its own way.
+    Strategy(&'static Strategy),
+    /// The read phase by one [`ReadMethod`].
+    Read(ReadMethod),
+    /// The update phase by one [`UpdateMethod`].
+    Update(UpdateMethod),
+    /// Reads through io_uring, with registered files and buffers or without.
+    #[cfg(target_os = "linux")]
+    Uring { registered: bool },
+    /// Creates through linked io_uring chains.
+    #[cfg(target_os = "linux")]
+    Linked,
+    /// [`crate::contents`]' bulk writes, reads and deletes.
+    Contents,
+    /// [`contents::read_many_mmap`].
+    ContentsMapped,
+}
+
+impl Backend {
+    /// Every backend in this build.
+    pub fn all() -> Vec<Backend> {
+        let mut all: Vec<Backend> = STRATEGIES.iter().map(Backend::Strategy).collect();
+        all.extend(ReadMethod::ALL.map(Backend::Read));
+        all.extend(UpdateMethod::ALL.map(Backend::Update));
+        #[cfg(target_os = "linux")]
+        all.extend([Backend::Uring { registered: false }, Backend::Uring { registered: true }, Backend::Linked]);
+        all.extend([Backend::Contents, Backend::ContentsMapped]);
+        all
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Backend::Strategy(strategy) => strategy.label.to_string(),
+            Backend::Read(method) => format!("read {}", method.name()),
+            Backend::Update(method) => format!("update {}", method.name()),
+            #[cfg(target_os = "linux")]
+            Backend::Uring { registered: false } => "io_uring read".to_string(),
+            #[cfg(target_os = "linux")]
+            Backend::Uring { registered: true } => "io_uring registered read".to_string(),
+            #[cfg(target_os = "linux")]
+            Backend::Linked => "io_uring linked create".to_string(),
+            Backend::Contents => "contents".to_string(),
+            Backend::ContentsMapped => "contents mmap read".to_string(),
+        }
+    }
+
+    /// Why the backend can't run here, if it can't.
+    fn unavailable(&self) -> Option<String> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Backend::Uring { .. } | Backend::Linked => uring::unavailable().map(|e| format!("io_uring is unavailable: {}", e)),
+            _ => None,
+        }
+    }
+
+    /// Whether the backend's updates promise to move the files' modification times.
+    fn stamps_updates(&self) -> bool {
+        !matches!(self, Backend::Strategy(_) | Backend::Update(UpdateMethod::Mmap))
+    }
+
+    /// The files holding `paths` between create and delete.
+    fn stored(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        match self {
+            Backend::Strategy(strategy) => strategy.stored_paths(paths),
+            _ => paths.to_vec(),
+        }
+    }
+
+    /// Runs `phase` over `paths` the backend's own way, with a file's panic as its error,
+    /// or `None` if it has none.
+    fn own(&self, phase: Phase, paths: &[PathBuf], options: &Options) -> Option<io::Result<()>> {
+        let policy = RetryPolicy::default();
+        let result = match (self, phase) {
+            (Backend::Strategy(strategy), _) => strategy.run_phase(phase, paths, options),
+            (Backend::Read(method), Phase::Read) => bench::read_files_by(paths, options, *method),
+            (Backend::Update(method), Phase::Update) => bench::update_files(paths, options, *method),
+            #[cfg(target_os = "linux")]
+            (Backend::Uring { registered }, Phase::Read) => registered::read_all(paths, *registered, options).map(drop),
+            #[cfg(target_os = "linux")]
+            (Backend::Linked, Phase::Create) => linked::create_files_linked(paths, options, LINKED_BATCH).map(drop),
+            (Backend::Contents, Phase::Create | Phase::Update) => {
+                let files: Vec<(PathBuf, Vec<u8>)> = paths.iter().enumerate().map(|(i, path)| (path.clone(), bench::with_content(options, i, phase == Phase::Update, <[u8]>::to_vec))).collect();
+                contents::write_many(&files, &policy)
+            }
+            (Backend::Contents, Phase::Read) => contents::read_many(paths, &policy).and_then(|read| read_back(paths, options, &read)),
+            (Backend::Contents, Phase::Delete) => contents::delete_many(paths, &policy),
+            (Backend::ContentsMapped, Phase::Read) => contents::read_many_mmap(paths).and_then(|read| read_back(paths, options, &read)),
+            _ => return None,
+        };
+        let failures = options.failures.take("");
+        Some(match failures.first() {
+            Some(failure) if result.is_ok() => Err(io::Error::other(format!("{}: {}", failure.path.display(), failure.message))),
+            _ => result,
+        })
+    }
+
+    /// Runs `phase` the backend's own way, or the default way when it has none. Says
+    /// which, with the result.
+    fn attempt(&self, phase: Phase, paths: &[PathBuf], options: &Options) -> (bool, io::Result<()>) {
+        if let Some(result) = self.own(phase, paths, options) {
+            return (true, result);
+        }
+        let result = match phase {
+            Phase::Create => bench::create_files(paths, options),
+            Phase::Read => bench::read_files_by(paths, options, ReadMethod::Read),
+            Phase::Update => bench::update_files(paths, options, UpdateMethod::Truncate),
+            Phase::Delete => bench::delete_files(paths, options),
+        };
+        options.failures.take("");
+        (false, result)
+    }
+}
+
+/// How one backend did on every [`Check`].
+#[derive(Debug, Clone)]
+pub struct Conformance {
+    pub backend: Backend,
+    /// In [`Check::ALL`] order.
+    pub outcomes: [Outcome; 6],
+}
+
+impl Conformance {
+    pub fn outcome(&self, check: Check) -> &Outcome {
+        &self.outcomes[check as usize]
+    }
+
+    /// Whether any check failed.
+    pub fn failed(&self) -> bool {
+        self.outcomes.iter().any(|outcome| matches!(outcome, Outcome::Fail(_)))
+    }
+
+    /// Passes `check` unless it failed already, or fails it for the reason `held` gives.
+    fn judge(&mut self, check: Check, at: &str, held: Result<(), String>) {
+        let outcome = &mut self.outcomes[check as usize];
+        match held {
+            Ok(()) if matches!(outcome, Outcome::NotApplicable) => *outcome = Outcome::Pass,
+            Err(why) if !matches!(outcome, Outcome::Fail(_)) => *outcome = Outcome::Fail(format!("{}: {}", at, why)),
+            _ => {}
+        }
+    }
+
+    /// Judges `check` by what a phase of the backend returned, but skips it when the
+    /// platform refuses the backend as unsupported, as io_uring is without enough memory
+    /// to lock for registered buffers.
+    fn judge_result(&mut self, check: Check, at: &str, result: io::Result<()>) {
+        match result {
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                let outcome = &mut self.outcomes[check as usize];
+                if !matches!(outcome, Outcome::Fail(_)) {
+                    *outcome = Outcome::Skipped(e.to_string());
+                }
+            }
+            result => self.judge(check, at, result.map_err(|e| e.to_string())),
+        }
+    }
+}
+
+/// Checks every backend of `backends` in turn, in directories made inside `dir`.
+pub fn run(dir: &Path, backends: &[Backend]) -> io::Result<Vec<Conformance>> {
+    backends.iter().map(|&backend| check(dir, backend)).collect()
+}
+
+/// Checks `backend` in a directory made inside `dir` and removed afterwards. Fails only
+/// when the checks themselves can't run; what the backend gets wrong is in the outcomes.
+pub fn check(dir: &Path, backend: Backend) -> io::Result<Conformance> {
+    let mut conformance = Conformance { backend, outcomes: Check::ALL.map(|_| Outcome::NotApplicable) };
+    if let Some(why) = backend.unavailable() {
+        conformance.outcomes = Check::ALL.map(|_| Outcome::Skipped(why.clone()));
+        return Ok(conformance);
+    }
+    let dir = dir.join(format!("selftest-{}", backend.name().replace([' ', '+'], "-")));
+    let _ = fs::remove_dir_all(&dir);
+    for size in SIZES {
+        let sized = dir.join(size.to_string());
+        fs::create_dir_all(&sized)?;
+        run_size(&backend, &sized, size, &mut conformance)?;
+    }
+    missing_parent(&backend, &dir, &mut conformance);
+    let read_only = read_only(&backend, &dir, &mut conformance);
+    fs::remove_dir_all(&dir)?;
+    read_only?;
+    Ok(conformance)
+}
+
+/// Options for `size`-byte files of generated contents, verified on every read.
+fn options(size: usize) -> Options {
+    Options { generator: Some(Generator::new(order::SEED, Pattern::Random)), verify: true, ..Options::default().with_workload(Workload::new(FILES, size)) }
+}
+
+/// Fails unless `read[i]` is what the create phase wrote to file `i`.
+fn read_back<B: AsRef<[u8]>>(paths: &[PathBuf], options: &Options, read: &[B]) -> io::Result<()> {
+    if read.len() != paths.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} files read of {}", read.len(), paths.len())));
+    }
+    paths.iter().zip(read).enumerate().try_for_each(|(i, (path, bytes))| bench::verify_content(options, i, path, bytes.as_ref()))
+}
+
+/// Fails unless every file of `paths` holds its created contents, or its updated ones.
+fn holds(paths: &[PathBuf], options: &Options, updated: bool) -> Result<(), String> {
+    for (i, path) in paths.iter().enumerate() {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if !bench::with_content(options, i, updated, |expected| bytes == expected) {
+            return Err(format!("{} doesn't hold what was written, read back with std::fs", path.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Fails unless every file of `paths` is `size` bytes long.
+fn sized(paths: &[PathBuf], size: usize) -> Result<(), String> {
+    for path in paths {
+        let len = fs::metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?.len();
+        if len != size as u64 {
+            return Err(format!("{} is {} bytes, not {}", path.display(), len, size));
+        }
+    }
+    Ok(())
+}
+
+fn mtime(path: &Path) -> Result<SystemTime, String> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Stamps every file of `paths` with `time`.
+fn stamp(paths: &[PathBuf], time: SystemTime) -> io::Result<()> {
+    paths.iter().try_for_each(|path| File::options().write(true).open(path)?.set_modified(time))
+}
+
+/// The time the filesystem stamps a file created in `dir` now with, which may be coarser
+/// than the clock.
+fn now_on(dir: &Path) -> io::Result<SystemTime> {
+    let path = dir.join(".selftest-now");
+    File::create(&path)?;
+    let now = fs::metadata(&path)?.modified()?;
+    fs::remove_file(&path)?;
+    Ok(now)
+}
+
+/// Fails unless every file of `paths` has a modification time that `held` accepts.
+fn stamped(paths: &[PathBuf], held: impl Fn(SystemTime) -> bool, what: &str) -> Result<(), String> {
+    for path in paths {
+        if !held(mtime(path)?) {
+            return Err(format!("{} {}", path.display(), what));
+        }
+    }
+    Ok(())
+}
+
+/// Runs every phase over `size`-byte files in `dir`, judging what `backend` does itself.
+fn run_size(backend: &Backend, dir: &Path, size: usize, conformance: &mut Conformance) -> io::Result<()> {
+    let options = options(size);
+    let paths = options.workload.paths(dir);
+    let stored = backend.stored(&paths);
+    let named = stored == paths;
+    let at = |phase: Phase| format!("{} of {}-byte files", phase.name(), size);
+
+    let started = now_on(dir)?;
+    let created = match backend.attempt(Phase::Create, &paths, &options) {
+        (true, Err(e)) => {
+            conformance.judge_result(Check::Content, &at(Phase::Create), Err(e));
+            return Ok(());
+        }
+        (false, Err(e)) => return Err(e),
+        (own, Ok(())) => {
+            if own && named {
+                conformance.judge(Check::Content, &at(Phase::Create), holds(&paths, &options, false));
+                conformance.judge(Check::Sizes, &at(Phase::Create), sized(&paths, size));
+            }
+            if own {
+                conformance.judge(Check::Timestamps, &at(Phase::Create), stamped(&stored, |time| time >= started, "is stamped before it was created"));
+            }
+            own
+        }
+    };
+
+    // Files the backend created must read back as written, whoever reads them.
+    stamp(&stored, long_ago())?;
+    let (own, result) = backend.attempt(Phase::Read, &paths, &options);
+    match own || created {
+        true => conformance.judge_result(Check::Content, &at(Phase::Read), result),
+        false => result?,
+    }
+    if own {
+        conformance.judge(Check::Timestamps, &at(Phase::Read), stamped(&stored, |time| time == long_ago(), "was stamped by reading it"));
+    }
+
+    stamp(&stored, long_ago())?;
+    let (own, result) = backend.attempt(Phase::Update, &paths, &options);
+    match own {
+        true => conformance.judge_result(Check::Content, &at(Phase::Update), result),
+        false => result?,
+    }
+    if own && named {
+        conformance.judge(Check::Content, &at(Phase::Update), holds(&paths, &options, true));
+        conformance.judge(Check::Sizes, &at(Phase::Update), sized(&paths, size));
+    } else if own {
+        let updated = Options { generator: options.generator.map(|generator| generator.next_version()), verify: true, ..options.with_workload(options.workload.clone()) };
+        let (_, result) = backend.attempt(Phase::Read, &paths, &updated);
+        conformance.judge_result(Check::Content, &format!("{}, read back", at(Phase::Update)), result);
+    }
+    // Writing nothing promises no new stamp.
+    if own && backend.stamps_updates() && size > 0 {
+        conformance.judge(Check::Timestamps, &at(Phase::Update), stamped(&stored, |time| time > long_ago(), "kept its old modification time"));
+    }
+
+    match backend.attempt(Phase::Delete, &paths, &options) {
+        (true, result) => {
+            let left = stored.iter().find(|path| path.exists());
+            match (result, left) {
+                (Ok(()), Some(path)) => conformance.judge(Check::Delete, &at(Phase::Delete), Err(format!("{} is still there", path.display()))),
+                (result, _) => conformance.judge_result(Check::Delete, &at(Phase::Delete), result),
+            }
+        }
+        (false, result) => result?,
+    }
+    Ok(())
+}
+
+/// Runs the create, read and update phases the backend does itself on files whose
+/// directory doesn't exist.
+fn missing_parent(backend: &Backend, dir: &Path, conformance: &mut Conformance) {
+    let options = options(1);
+    let missing = dir.join("missing");
+    let paths = options.workload.paths(&missing.join("parent"));
+    for phase in [Phase::Create, Phase::Read, Phase::Update] {
+        let Some(result) = backend.own(phase, &paths, &options) else { continue };
+        let held = match result {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("{} failed with {:?} rather than NotFound: {}", phase.name(), e.kind(), e)),
+            Ok(()) => Err(format!("{} succeeded", phase.name())),
+        };
+        let held = held.and_then(|()| if missing.exists() { Err(format!("{} created {}", phase.name(), missing.display())) } else { Ok(()) });
+        conformance.judge(Check::MissingParent, "without a parent directory", held);
+    }
+}
+
+/// `result` of `phase` is a refusal for lack of permission.
+fn denied(phase: Phase, result: io::Result<()>) -> Result<(), String> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(()),
+        Err(e) => Err(format!("{} failed with {:?} rather than PermissionDenied: {}", phase.name(), e.kind(), e)),
+        Ok(()) => Err(format!("{} succeeded", phase.name())),
+    }
+}
+
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+/// Runs each phase the backend does itself on files, and in directories, without write
+/// permission. The permissions are restored whatever happens, so `dir` can be removed.
+fn read_only(backend: &Backend, dir: &Path, conformance: &mut Conformance) -> io::Result<()> {
+    let options = options(1);
+    let (target, empty) = (dir.join("read-only"), dir.join("read-only-empty"));
+    fs::create_dir_all(&target)?;
+    fs::create_dir_all(&empty)?;
+    let paths = options.workload.paths(&target);
+    if backend.attempt(Phase::Create, &paths, &options).1.is_err() {
+        // Already judged at every size.
+        return Ok(());
+    }
+    let stored = backend.stored(&paths);
+    let modes = [(&target, 0o555), (&empty, 0o555)].into_iter().chain(stored.iter().map(|path| (path, 0o444)));
+    let locked = modes.clone().try_for_each(|(path, mode)| set_mode(path, mode));
+    if locked.is_ok() {
+        judge_read_only(backend, &paths, &stored, &options.workload.paths(&empty), &options, conformance);
+    }
+    let unlocked = modes.rev().try_for_each(|(path, mode)| set_mode(path, mode | 0o200));
+    locked.and(unlocked)
+}
+
+fn judge_read_only(backend: &Backend, paths: &[PathBuf], stored: &[PathBuf], new: &[PathBuf], options: &Options, conformance: &mut Conformance) {
+    let probe = new[0].with_file_name(".selftest-probe");
+    if File::create(&probe).is_ok() {
+        let _ = fs::remove_file(&probe);
+        conformance.outcomes[Check::ReadOnly as usize] = Outcome::Skipped("permissions aren't enforced here, as for root".to_string());
+        return;
+    }
+    if let Some(result) = backend.own(Phase::Create, new, options) {
+        conformance.judge(Check::ReadOnly, "in a read-only directory", denied(Phase::Create, result));
+    }
+    if let Some(result) = backend.own(Phase::Read, paths, options) {
+        conformance.judge_result(Check::ReadOnly, "reading read-only files", result);
+    }
+    let named = stored == paths;
+    if let Some(result) = backend.own(Phase::Update, paths, options) {
+        let held = denied(Phase::Update, result).and_then(|()| if named { holds(paths, options, false) } else { Ok(()) });
+        conformance.judge(Check::ReadOnly, "on read-only files", held);
+    }
+    if let Some(result) = backend.own(Phase::Delete, paths, options) {
+        let left = stored.iter().all(|path| path.exists());
+        let held = denied(Phase::Delete, result).and_then(|()| if left { Ok(()) } else { Err("delete removed files anyway".to_string()) });
+        conformance.judge(Check::ReadOnly, "on read-only files", held);
+    }
+}
@@ -0,0 +1,178 @@
+//! `io serve`: reruns the benchmark on an interval and exposes the results as Prometheus
+//! metrics, for continuous monitoring of a machine's filesystem performance.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ::io::bench::{self, Phase, STRATEGIES, Workload};
+use ::io::cancel;
+use ::io::log::{self, Level};
+use ::io::rundir::RunDir;
+
+use crate::baseline;
+use crate::cli::{self, BenchArgs, DurationArg, flag_value, invalid_input};
+
+#[derive(Default)]
+struct PhaseMetrics {
+    last_seconds: f64,
+    seconds_total: f64,
+    runs: u64,
+}
+
+#[derive(Default)]
+struct Metrics {
+    /// Indexed by strategy, then phase in [`Phase::ALL`] order.
+    phases: Vec<[PhaseMetrics; 4]>,
+    files: usize,
+    iterations: u64,
+    errors: u64,
+    file_failures: u64,
+    last_run: f64,
+}
+
+fn label(strategy: usize, phase: Phase) -> String {
+    format!("strategy=\"{}\",phase=\"{}\"", STRATEGIES[strategy].label.to_ascii_lowercase(), phase.name())
+}
+
+impl Metrics {
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let mut family = |name: &str, kind: &str, help: &str, value: &dyn Fn(&PhaseMetrics) -> f64| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+            for (strategy, phases) in self.phases.iter().enumerate() {
+                for (phase, metrics) in Phase::ALL.into_iter().zip(phases) {
+                    if metrics.runs > 0 {
+                        let _ = writeln!(out, "{}{{{}}} {}", name, label(strategy, phase), value(metrics));
+                    }
+                }
+            }
+        };
+        family("io_phase_duration_seconds", "gauge", "Wall time of the latest run of each phase.", &|m| m.last_seconds);
+        family("io_phase_seconds_total", "counter", "Wall time summed over all runs of each phase.", &|m| m.seconds_total);
+        family("io_phase_runs_total", "counter", "Completed runs of each phase.", &|m| m.runs as f64);
+        let files = self.files as f64;
+        family("io_phase_files_per_second", "gauge", "Files processed per second in the latest run of each phase.", &|m| {
+            if m.last_seconds > 0.0 { files / m.last_seconds } else { 0.0 }
+        });
+        let scalars = [
+            ("io_iterations_total", "counter", "Benchmark iterations started.", self.iterations as f64),
+            ("io_run_errors_total", "counter", "Strategy runs that failed with an error.", self.errors as f64),
+            ("io_file_failures_total", "counter", "Files whose work panicked.", self.file_failures as f64),
+            ("io_workload_files", "gauge", "Files per phase.", files),
+            ("io_last_run_timestamp_seconds", "gauge", "When the latest iteration finished.", self.last_run),
+        ];
+        for (name, kind, help, value) in scalars {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}", name, help, name, kind, name, value);
+        }
+        out
+    }
+}
+
+fn respond(mut stream: TcpStream, metrics: &Mutex<Metrics>) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    let (status, content_type, body) = match path {
+        "/metrics" => {
+            let body = metrics.lock().unwrap_or_else(|e| e.into_inner()).render();
+            ("200 OK", "text/plain; version=0.0.4", body)
+        }
+        "/" => ("200 OK", "text/plain", "io serve: metrics at /metrics\n".to_string()),
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// `io serve [--metrics-port <port>] [--interval <duration>] [--iterations <n>] [bench options]`
+pub fn serve(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let mut port = 9100u16;
+    let mut interval = Duration::from_secs(60);
+    let mut iterations = None;
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--metrics-port" => port = flag_value(&mut args, &arg)?,
+            "--interval" => interval = flag_value::<DurationArg>(&mut args, &arg)?.0,
+            "--iterations" => iterations = Some(flag_value::<u64>(&mut args, &arg)?),
+            _ => rest.push(arg),
+        }
+    }
+    let mut args: BenchArgs = cli::parse_bench_args(rest.into_iter())?;
+    let (&[threads], &[files], &[size]) = (&args.threads[..], &args.files[..], &args.sizes[..]) else {
+        return Err(invalid_input("io serve runs a single thread count, file count and size".to_string()));
+    };
+    args.options.workload = Workload::new(files, size);
+    cli::limit_open_files(&mut args)?;
+    let _scheduler = cli::start_scheduler(&args)?;
+
+    let metrics = Arc::new(Mutex::new(Metrics { files, ..Metrics::default() }));
+    metrics.lock().unwrap_or_else(|e| e.into_inner()).phases = STRATEGIES.iter().map(|_| Default::default()).collect();
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    println!("Serving metrics on http://{}/metrics, running every {:.0} s", listener.local_addr()?, interval.as_secs_f64());
+    {
+        let metrics = metrics.clone();
+        thread::Builder::new().name("io-metrics".to_string()).spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = respond(stream, &metrics) {
+                    log::error("serve", format_args!("metrics request failed: {}", e));
+                }
+            }
+        })?;
+    }
+
+    let dir_path = RunDir::create(bench::get_dir())?;
+    let mut engine = cli::warm_engine(threads, args.numa, args.priority)?;
+    let mut iteration = 0;
+    while iterations.is_none_or(|n| iteration < n) {
+        iteration += 1;
+        let started = Instant::now();
+        fs::create_dir_all(&dir_path)?;
+        metrics.lock().unwrap_or_else(|e| e.into_inner()).iterations += 1;
+        for (i, strategy) in STRATEGIES.iter().enumerate() {
+            let result = engine.install(|| bench::run_strategy(strategy, &dir_path, &args.options));
+            let mut metrics = metrics.lock().unwrap_or_else(|e| e.into_inner());
+            match result {
+                // A cancelled run's phases aren't all timed; it's left out of the metrics.
+                Ok(result) if result.interrupted.is_some() => break,
+                Ok(result) => {
+                    cli::report_failures(&result.failures);
+                    metrics.file_failures += result.failures.len() as u64;
+                    for (phase, ms) in metrics.phases[i].iter_mut().zip(baseline::phase_ms(&result.times)) {
+                        phase.last_seconds = ms / 1000.0;
+                        phase.seconds_total += ms / 1000.0;
+                        phase.runs += 1;
+                    }
+                }
+                Err(e) => {
+                    log::event(Level::Error, "serve", &e.to_string(), &[("strategy", strategy.name.to_string())]);
+                    metrics.errors += 1;
+                }
+            }
+        }
+        let _ = fs::remove_dir_all(&dir_path);
+        metrics.lock().unwrap_or_else(|e| e.into_inner()).last_run = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64());
+        println!("Iteration {} finished in {:.1} s", iteration, started.elapsed().as_secs_f64());
+        // In steps, so Ctrl-C stops the server between runs without waiting out the interval.
+        while iterations.is_none_or(|n| iteration < n) && !cancel::is_interrupted() && started.elapsed() < interval {
+            thread::sleep(interval.saturating_sub(started.elapsed()).min(Duration::from_millis(100)));
+        }
+        if cancel::is_interrupted() {
+            break;
+        }
+    }
+    Ok(())
+}
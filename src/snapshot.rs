@@ -0,0 +1,219 @@
+//! Best-effort consistent reads of a directory tree that other processes may be writing,
+//! for backup-style consumers.
+//!
+//! [`read`] first lists the tree and records every file's [`Generation`], then reads the
+//! contents in parallel. A file whose generation differs from the listing, changes while
+//! it is read, or is replaced by another inode is read again, up to [`Config::retries`]
+//! times; files that never settle, disappear, or show up only after the listing are
+//! reported as [`Unstable`] instead of being part of the snapshot.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use rayon::prelude::*;
+
+use crate::population::hash_bytes;
+use crate::trace::OpId;
+
+/// The scope of the [`OpId`]s of snapshot reads.
+pub const OP_SCOPE: &str = "snapshot";
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// Extra reads of a file that changed before it is reported as unstable.
+    pub retries: usize,
+    /// Also hash every file while listing, catching rewrites that keep size and mtime.
+    /// Reads the tree twice.
+    pub hash: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { retries: 3, hash: false }
+    }
+}
+
+/// What identifies one version of a file: size, modification and status-change times,
+/// inode and, optionally, a hash of the contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Generation {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// `st_ctime` in nanoseconds, which unlike the mtime can't be set back; 0 off Unix.
+    pub changed: i128,
+    pub inode: u64,
+    pub hash: Option<u64>,
+}
+
+impl Generation {
+    fn of(metadata: &fs::Metadata) -> Generation {
+        #[cfg(unix)]
+        let (changed, inode) = {
+            use std::os::unix::fs::MetadataExt;
+
+            (metadata.ctime() as i128 * 1_000_000_000 + metadata.ctime_nsec() as i128, metadata.ino())
+        };
+        #[cfg(not(unix))]
+        let (changed, inode) = (0, 0);
+        Generation { size: metadata.len(), modified: metadata.modified().ok(), changed, inode, hash: None }
+    }
+
+    /// Whether two generations describe the same version, ignoring hashes.
+    fn same_version(&self, other: &Generation) -> bool {
+        (self.size, self.modified, self.changed, self.inode) == (other.size, other.modified, other.changed, other.inode)
+    }
+}
+
+/// One file as it was read, with the generation it had throughout the read.
+#[derive(Debug)]
+pub struct SnapshotFile {
+    pub op: OpId,
+    /// Relative to the snapshot root.
+    pub path: PathBuf,
+    pub contents: Vec<u8>,
+    pub generation: Generation,
+    /// Reads it took to get a stable copy; 1 unless the file changed.
+    pub attempts: usize,
+}
+
+/// Why a file isn't part of the snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instability {
+    /// Kept changing through every attempt.
+    Changed { attempts: usize },
+    /// Listed, but removed before it could be read.
+    Vanished,
+    /// Not in the initial listing but present afterwards.
+    Appeared,
+}
+
+#[derive(Debug, Clone)]
+pub struct Unstable {
+    pub op: OpId,
+    /// Relative to the snapshot root.
+    pub path: PathBuf,
+    pub reason: Instability,
+}
+
+#[derive(Debug)]
+pub struct Snapshot {
+    pub root: PathBuf,
+    pub files: Vec<SnapshotFile>,
+    pub unstable: Vec<Unstable>,
+}
+
+impl Snapshot {
+    pub fn bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.contents.len() as u64).sum()
+    }
+
+    /// Files that needed more than one read.
+    pub fn retried(&self) -> usize {
+        self.files.iter().filter(|file| file.attempts > 1).count()
+    }
+}
+
+/// Every regular file under `root`, without following symlinks, sorted.
+fn list(root: &Path) -> io::Result<Vec<(PathBuf, Generation)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            // A directory removed after its parent was listed.
+            Err(e) if e.kind() == io::ErrorKind::NotFound && dir != root => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                match entry.metadata() {
+                    Ok(metadata) => files.push((entry.path(), Generation::of(&metadata))),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// One read of `path`, returning the contents only if the file kept the `expected`
+/// generation before, during and after the read.
+fn read_once(path: &Path, expected: &Generation) -> io::Result<Result<(Vec<u8>, Generation), Generation>> {
+    let mut file = File::open(path)?;
+    let before = Generation::of(&file.metadata()?);
+    if !before.same_version(expected) {
+        return Ok(Err(before));
+    }
+    let mut contents = Vec::with_capacity(before.size as usize);
+    file.read_to_end(&mut contents)?;
+    let after = Generation::of(&file.metadata()?);
+    // A rename over the path swaps in a different inode without touching ours.
+    let at_path = Generation::of(&fs::symlink_metadata(path)?);
+    if !after.same_version(&before) || !at_path.same_version(&before) || contents.len() as u64 != before.size {
+        return Ok(Err(at_path));
+    }
+    let hash = hash_bytes(&contents);
+    if expected.hash.is_some_and(|expected| expected != hash) {
+        return Ok(Err(at_path));
+    }
+    Ok(Ok((contents, Generation { hash: Some(hash), ..before })))
+}
+
+fn read_file(root: &Path, path: &Path, listed: Generation, retries: usize) -> io::Result<Result<SnapshotFile, Unstable>> {
+    let relative = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+    let op = OpId::new(OP_SCOPE, &relative);
+    let mut expected = listed;
+    for attempts in 1..=retries + 1 {
+        match read_once(path, &expected) {
+            Ok(Ok((contents, generation))) => return Ok(Ok(SnapshotFile { op, path: relative, contents, generation, attempts })),
+            // Later attempts only need to be self-consistent; the listed hash is stale.
+            Ok(Err(current)) => expected = current,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Err(Unstable { op, path: relative, reason: Instability::Vanished })),
+            Err(e) => return Err(io::Error::new(e.kind(), format!("{} (op {}): {}", path.display(), op, e))),
+        }
+    }
+    Ok(Err(Unstable { op, path: relative, reason: Instability::Changed { attempts: retries + 1 } }))
+}
+
+/// Reads every regular file under `root` as described in the module docs.
+pub fn read(root: &Path, config: &Config) -> io::Result<Snapshot> {
+    let mut listed = list(root)?;
+    if config.hash {
+        listed.par_iter_mut().try_for_each(|(path, generation)| {
+            match fs::read(&*path) {
+                Ok(contents) => generation.hash = Some(hash_bytes(&contents)),
+                // Left unhashed; the read will find it gone.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            Ok(())
+        })?;
+    }
+    let results = listed
+        .par_iter()
+        .map(|(path, generation)| read_file(root, path, *generation, config.retries))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut snapshot = Snapshot { root: root.to_path_buf(), files: Vec::new(), unstable: Vec::new() };
+    for result in results {
+        match result {
+            Ok(file) => snapshot.files.push(file),
+            Err(unstable) => snapshot.unstable.push(unstable),
+        }
+    }
+    for (path, _) in list(root)? {
+        if listed.binary_search_by(|(listed, _)| listed.cmp(&path)).is_err() {
+            let path = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            snapshot.unstable.push(Unstable { op: OpId::new(OP_SCOPE, &path), path, reason: Instability::Appeared });
+        }
+    }
+    Ok(snapshot)
+}
@@ -0,0 +1,170 @@
+//! Results of long soak runs, one line per strategy per iteration, appended as each
+//! finishes so a run of hours can be watched, and cut short, without losing what it
+//! measured. Laid out over time they show throughput an SSD loses once its SLC cache
+//! fills, or a filesystem as it ages.
+//!
+//! Every line carries the state the iteration ended in: the device's temperatures, the
+//! page cache's size and dirty data, and the filesystem's free space. A log whose name
+//! ends in `.csv` is CSV, with the hottest sensor's temperature; anything else is JSON
+//! Lines with every sensor.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::filesystem::Filesystem;
+use crate::health::Device;
+use crate::json::Value;
+
+/// The CSV log's header; its rows follow [`Iteration::csv_row`].
+pub const CSV_HEADER: &str = "iteration,timestamp,elapsed_s,strategy,create_ms,read_ms,update_ms,delete_ms,total_ms,temperature_c,cached_bytes,dirty_bytes,writeback_bytes,available_bytes";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Jsonl,
+}
+
+impl Format {
+    /// CSV for `.csv` files, JSON Lines otherwise.
+    pub fn of(path: &Path) -> Format {
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv")) { Format::Csv } else { Format::Jsonl }
+    }
+}
+
+/// The machine's state when an iteration finished; what couldn't be read is `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct State {
+    /// Each sensor of the device under test, in °C.
+    pub temperatures: Vec<(String, f64)>,
+    /// The page cache, dirty data waiting for writeback, and data being written back.
+    pub cached: Option<u64>,
+    pub dirty: Option<u64>,
+    pub writeback: Option<u64>,
+    /// Free space on the filesystem under test.
+    pub available: Option<u64>,
+}
+
+impl State {
+    /// The state of the device and filesystem `dir` is on, and of the page cache, now.
+    pub fn sample(dir: &Path) -> State {
+        let temperatures = Device::of(dir).map_or_else(Vec::new, |device| device.temperatures().readings);
+        let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+        State {
+            temperatures,
+            cached: meminfo_bytes(&meminfo, "Cached"),
+            dirty: meminfo_bytes(&meminfo, "Dirty"),
+            writeback: meminfo_bytes(&meminfo, "Writeback"),
+            available: Filesystem::of(dir).and_then(|filesystem| filesystem.available),
+        }
+    }
+
+    /// The hottest sensor, in °C.
+    pub fn temperature(&self) -> Option<f64> {
+        self.temperatures.iter().map(|&(_, celsius)| celsius).max_by(f64::total_cmp)
+    }
+}
+
+/// A `/proc/meminfo` field in bytes, from a line such as `Dirty:  1234 kB`.
+pub fn meminfo_bytes(meminfo: &str, field: &str) -> Option<u64> {
+    let line = meminfo.lines().find(|line| line.split_once(':').is_some_and(|(name, _)| name == field))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// One strategy's phase times in one iteration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Iteration {
+    /// Counted from 1.
+    pub iteration: usize,
+    pub timestamp: SystemTime,
+    /// Since the soak started.
+    pub elapsed: Duration,
+    pub strategy: String,
+    /// Create, read, update and delete, in milliseconds.
+    pub ms: [f64; 4],
+    pub state: State,
+}
+
+impl Iteration {
+    pub fn total_ms(&self) -> f64 {
+        self.ms.iter().sum()
+    }
+
+    /// Seconds since the Unix epoch.
+    fn unix_time(&self) -> f64 {
+        self.timestamp.duration_since(UNIX_EPOCH).map_or(0.0, |since| since.as_secs_f64())
+    }
+
+    pub fn to_json(&self) -> Value {
+        let [create, read, update, delete] = self.ms;
+        let temperatures = self.state.temperatures.iter().fold(Value::object(), |temperatures, (name, celsius)| temperatures.with(name, *celsius));
+        Value::object()
+            .with("iteration", self.iteration)
+            .with("timestamp", self.unix_time())
+            .with("elapsed_s", self.elapsed.as_secs_f64())
+            .with("strategy", self.strategy.as_str())
+            .with("create_ms", create)
+            .with("read_ms", read)
+            .with("update_ms", update)
+            .with("delete_ms", delete)
+            .with("total_ms", self.total_ms())
+            .with("temperatures_c", temperatures)
+            .with("cached_bytes", self.state.cached)
+            .with("dirty_bytes", self.state.dirty)
+            .with("writeback_bytes", self.state.writeback)
+            .with("available_bytes", self.state.available)
+    }
+
+    /// A row under [`CSV_HEADER`], empty where a reading is missing.
+    pub fn csv_row(&self) -> String {
+        let optional = |value: Option<u64>| value.map_or_else(String::new, |value| value.to_string());
+        let [create, read, update, delete] = self.ms;
+        format!(
+            "{},{:.3},{:.3},{},{:.3},{:.3},{:.3},{:.3},{:.3},{},{},{},{},{}",
+            self.iteration,
+            self.unix_time(),
+            self.elapsed.as_secs_f64(),
+            self.strategy,
+            create,
+            read,
+            update,
+            delete,
+            self.total_ms(),
+            self.state.temperature().map_or_else(String::new, |celsius| format!("{:.1}", celsius)),
+            optional(self.state.cached),
+            optional(self.state.dirty),
+            optional(self.state.writeback),
+            optional(self.state.available)
+        )
+    }
+}
+
+/// A soak log open for appending.
+#[derive(Debug)]
+pub struct Log {
+    file: File,
+    format: Format,
+}
+
+impl Log {
+    /// Opens `path` to append to, creating it; a new or empty CSV log gets the header.
+    pub fn open(path: &Path) -> io::Result<Log> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let format = Format::of(path);
+        if format == Format::Csv && file.metadata()?.len() == 0 {
+            writeln!(file, "{}", CSV_HEADER)?;
+        }
+        Ok(Log { file, format })
+    }
+
+    /// Appends `iteration` as one line, written straight to the file.
+    pub fn append(&mut self, iteration: &Iteration) -> io::Result<()> {
+        let line = match self.format {
+            Format::Csv => iteration.csv_row(),
+            Format::Jsonl => iteration.to_json().to_string(),
+        };
+        self.file.write_all(format!("{}\n", line).as_bytes())
+    }
+}
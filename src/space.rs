@@ -0,0 +1,113 @@
+//! Making sure a run has the disk space it needs: a check of the workload's projected size
+//! against the free space before anything is written, a reservation that claims the space
+//! up front, and telling when an error means the disk filled up.
+//!
+//! A run that fills the disk halfway through a phase measures nothing useful and leaves
+//! other software on the machine short of space, so both the check and the reservation
+//! fail before the first phase. When the disk fills anyway (another process wrote to it),
+//! the phase stops at the file that failed, whose partial contents are removed at once.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::filesystem::Filesystem;
+use crate::human;
+
+/// Allocation unit assumed for the projection; most filesystems use 4 KiB blocks.
+const BLOCK: u64 = 4096;
+
+/// Name of the placeholder a [`Reservation`] allocates.
+const PLACEHOLDER: &str = ".io-reserved";
+
+/// The most space `files` files of `size` bytes take at once: every file rounded up to
+/// whole blocks, twice over for the update phases that write a copy before replacing.
+pub fn projected(files: usize, size: usize) -> u64 {
+    2 * files as u64 * (size as u64).next_multiple_of(BLOCK)
+}
+
+/// Fails with [`io::ErrorKind::StorageFull`] when the filesystem of `dir` has less than
+/// `needed` bytes free. Filesystems whose free space can't be read pass.
+pub fn check(dir: &Path, needed: u64) -> io::Result<()> {
+    let Some(filesystem) = Filesystem::of(dir) else {
+        return Ok(());
+    };
+    match filesystem.available {
+        Some(available) if available < needed => Err(io::Error::new(
+            io::ErrorKind::StorageFull,
+            format!("the workload needs {} but {} at {} has {} free", human::bytes(needed), filesystem.fs_type, filesystem.mount_point.display(), human::bytes(available)),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Whether `error` means the disk or the user's quota is full.
+pub fn is_no_space(error: &io::Error) -> bool {
+    matches!(error.kind(), io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded)
+}
+
+/// Space claimed in a directory by allocating a placeholder file, so other writers can't
+/// take it, until [`release`](Reservation::release) or drop hands it back.
+#[derive(Debug)]
+pub struct Reservation {
+    placeholder: Mutex<Option<PathBuf>>,
+    bytes: u64,
+}
+
+impl Reservation {
+    /// Allocates `bytes` in `dir`. Fails with [`io::ErrorKind::StorageFull`] when they
+    /// aren't free, and [`io::ErrorKind::Unsupported`] on filesystems or platforms that
+    /// can't allocate without writing.
+    pub fn new(dir: &Path, bytes: u64) -> io::Result<Reservation> {
+        let path = dir.join(PLACEHOLDER);
+        let file = OpenOptions::new().write(true).create(true).truncate(true).open(&path)?;
+        // Dropped on failure, which removes the placeholder.
+        let reservation = Reservation { placeholder: Mutex::new(Some(path)), bytes };
+        allocate(&file, bytes).map_err(|e| match e.kind() {
+            io::ErrorKind::StorageFull => io::Error::new(io::ErrorKind::StorageFull, format!("can't reserve {} in {}: {}", human::bytes(bytes), dir.display(), e)),
+            _ => e,
+        })?;
+        Ok(reservation)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Frees the space; later calls do nothing.
+    pub fn release(&self) {
+        if let Some(path) = self.placeholder.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.placeholder.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+fn allocate(file: &File, bytes: u64) -> io::Result<()> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let len = libc::off_t::try_from(bytes).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "reservation too large"))?;
+        if unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, len) } != 0 {
+            let e = io::Error::last_os_error();
+            return Err(if e.raw_os_error() == Some(libc::EOPNOTSUPP) { io::Error::new(io::ErrorKind::Unsupported, "the filesystem can't reserve space without writing it") } else { e });
+        }
+        Ok(())
+    }
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    {
+        let _ = (file, bytes);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "reserving space needs fallocate (Linux)"))
+    }
+}
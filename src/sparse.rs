@@ -0,0 +1,252 @@
+//! Sparse files, for VM-image and database-file tooling: creating them by writing only the
+//! data extents past a hole, punching holes into files already written, and finding the
+//! data again on read with `SEEK_DATA`/`SEEK_HOLE` so holes are skipped rather than read
+//! back as zeros.
+//!
+//! Holes are the filesystem's to keep: one that doesn't support them (or a punch smaller
+//! than its block size) simply stores the zeros, and [`data_ranges`] then reports the whole
+//! file as data. Punching holes is Linux only.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::ops::Range;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+#[cfg(feature = "bench")]
+use std::fs;
+#[cfg(feature = "bench")]
+use std::path::PathBuf;
+#[cfg(feature = "bench")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+#[cfg(feature = "bench")]
+use crate::cache;
+
+/// Creates `path` as a file of `len` bytes holding each `(offset, bytes)` extent and holes
+/// everywhere else: the length is set first, then each extent written at its offset.
+pub fn write_extents(path: &Path, len: u64, extents: &[(u64, &[u8])]) -> io::Result<()> {
+    let file = OpenOptions::new().write(true).create(true).truncate(true).open(path)?;
+    file.set_len(len)?;
+    for &(offset, bytes) in extents {
+        file.write_all_at(bytes, offset)?;
+    }
+    Ok(())
+}
+
+/// Deallocates `len` bytes of `file` from `offset`, which then read as zeros, keeping its
+/// size.
+pub fn punch_hole(file: &File, offset: u64, len: u64) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+        if unsafe { libc::fallocate(file.as_raw_fd(), mode, offset as libc::off_t, len as libc::off_t) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (file, offset, len);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "punching holes needs Linux"))
+    }
+}
+
+/// `lseek` to the next data or hole at or after `offset`, `None` when there is no more data.
+fn seek(file: &File, offset: u64, whence: libc::c_int) -> io::Result<Option<u64>> {
+    match unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) } {
+        -1 => match io::Error::last_os_error() {
+            err if err.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+            err => Err(err),
+        },
+        found => Ok(Some(found as u64)),
+    }
+}
+
+/// The byte ranges of `file` that hold data, in order; everything between them is a hole.
+/// A filesystem that doesn't track holes reports the whole file.
+pub fn data_ranges(file: &File) -> io::Result<Vec<Range<u64>>> {
+    let len = file.metadata()?.len();
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    while offset < len {
+        let start = match seek(file, offset, libc::SEEK_DATA) {
+            Ok(Some(start)) => start,
+            Ok(None) => break,
+            // Without SEEK_DATA every byte counts as data.
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) && ranges.is_empty() => return Ok(std::iter::once(0..len).collect()),
+            Err(err) => return Err(err),
+        };
+        let end = seek(file, start, libc::SEEK_HOLE)?.unwrap_or(len).min(len);
+        ranges.push(start..end);
+        offset = end;
+    }
+    Ok(ranges)
+}
+
+/// The bytes the filesystem has allocated to `file`, which is less than its length when it
+/// has holes.
+pub fn allocated_bytes(file: &File) -> io::Result<u64> {
+    Ok(file.metadata()?.blocks() * 512)
+}
+
+/// The data extents of `file`, read with one positioned read each and holes skipped.
+pub fn read_data(file: &File) -> io::Result<Vec<(u64, Vec<u8>)>> {
+    data_ranges(file)?
+        .into_iter()
+        .map(|range| {
+            let mut bytes = vec![0; (range.end - range.start) as usize];
+            file.read_exact_at(&mut bytes, range.start)?;
+            Ok((range.start, bytes))
+        })
+        .collect()
+}
+
+/// The benchmark's files are this size of extent at a time, one of every [`DATA_EVERY`]
+/// holding data.
+#[cfg(feature = "bench")]
+pub const EXTENT: u64 = 64 << 10;
+#[cfg(feature = "bench")]
+pub const DATA_EVERY: u64 = 4;
+
+/// The data extents of a benchmark file of `len` bytes.
+#[cfg(feature = "bench")]
+pub fn layout(len: u64) -> Vec<Range<u64>> {
+    (0..len.div_ceil(EXTENT)).step_by(DATA_EVERY as usize).map(|extent| extent * EXTENT..((extent + 1) * EXTENT).min(len)).collect()
+}
+
+/// The holes of a benchmark file of `len` bytes, between its [`layout`] extents.
+#[cfg(feature = "bench")]
+fn holes(len: u64) -> Vec<Range<u64>> {
+    let data = layout(len);
+    let mut holes: Vec<Range<u64>> = data.windows(2).map(|pair| pair[0].end..pair[1].start).collect();
+    if let Some(last) = data.last().filter(|last| last.end < len) {
+        holes.push(last.end..len);
+    }
+    holes
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct SparseResult {
+    /// Every file's length, summed.
+    pub logical_bytes: u64,
+    /// What the data extents hold, summed.
+    pub data_bytes: u64,
+    /// Writing every file whole, zeros included.
+    pub create_dense: Duration,
+    /// Setting every file's length and writing only its data extents.
+    pub create_sparse: Duration,
+    /// Punching the zero extents out of the dense files.
+    pub punch: Duration,
+    /// Reading the sparse files whole, holes coming back as zeros.
+    pub read_whole: Duration,
+    /// Finding the sparse files' data with `SEEK_DATA` and reading only that.
+    pub read_data: Duration,
+    pub dense_allocated: u64,
+    pub sparse_allocated: u64,
+    pub punched_allocated: u64,
+    /// Whether `SEEK_DATA` found exactly the extents written in every file; false on a
+    /// filesystem that reports files as all data.
+    pub holes_detected: bool,
+    pub failures: Vec<Failure>,
+}
+
+/// Creates the configured workload in `dir_path` twice, with one [`EXTENT`] of every
+/// [`DATA_EVERY`] holding the file's content and the rest zeros: once written whole and
+/// once sparse. Then times punching the zeros out of the whole copies and reading the sparse
+/// ones, whole and data only, evicting them from the page cache before each read when
+/// `options.cold_read` is set.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<SparseResult> {
+    let files = options.workload.files;
+    let len = options.workload.size() as u64;
+    let extents = layout(len);
+    let data_len: u64 = extents.iter().map(|range| range.end - range.start).sum();
+    let mut result = SparseResult { logical_bytes: files as u64 * len, data_bytes: files as u64 * data_len, ..SparseResult::default() };
+    options.failures.take("");
+    let dense = bench::file_paths(&dir_path.join("dense"), files);
+    let sparse = bench::file_paths(&dir_path.join("sparse"), files);
+    fs::create_dir_all(dir_path.join("dense"))?;
+    fs::create_dir_all(dir_path.join("sparse"))?;
+    // Files a failure left out of the phase don't count.
+    let allocated = |paths: &[PathBuf]| -> io::Result<u64> { paths.iter().filter_map(|path| File::open(path).ok()).map(|file| allocated_bytes(&file)).sum() };
+
+    let start = Instant::now();
+    bench::phase(options, "Sparse create dense", "sparse/create-dense", "create dense", &mut result.failures, || {
+        bench::each_indexed(&dense, options, |index, path| {
+            bench::with_content(options, index, false, |content| {
+                let mut whole = vec![0; content.len()];
+                for range in &extents {
+                    let range = range.start as usize..range.end as usize;
+                    whole[range.clone()].copy_from_slice(&content[range]);
+                }
+                fs::write(path, whole)
+            })
+        })
+    })?;
+    result.create_dense = start.elapsed();
+
+    let start = Instant::now();
+    bench::phase(options, "Sparse create sparse", "sparse/create-sparse", "create sparse", &mut result.failures, || {
+        bench::each_indexed(&sparse, options, |index, path| {
+            bench::with_content(options, index, false, |content| {
+                let writes: Vec<(u64, &[u8])> = extents.iter().map(|range| (range.start, &content[range.start as usize..range.end as usize])).collect();
+                write_extents(path, len, &writes)
+            })
+        })
+    })?;
+    result.create_sparse = start.elapsed();
+    result.dense_allocated = allocated(&dense)?;
+    result.sparse_allocated = allocated(&sparse)?;
+
+    let holes = holes(len);
+    let start = Instant::now();
+    bench::phase(options, "Sparse punch", "sparse/punch", "punch", &mut result.failures, || {
+        bench::each_indexed(&dense, options, |_, path| {
+            let file = OpenOptions::new().write(true).open(path)?;
+            holes.iter().try_for_each(|hole| punch_hole(&file, hole.start, hole.end - hole.start))
+        })
+    })?;
+    result.punch = start.elapsed();
+    result.punched_allocated = allocated(&dense)?;
+
+    if options.cold_read {
+        cache::evict_from_cache(&sparse)?;
+    }
+    let start = Instant::now();
+    bench::phase(options, "Sparse read whole", "sparse/read-whole", "read whole", &mut result.failures, || bench::each_indexed(&sparse, options, |_, path| fs::read(path).map(drop)))?;
+    result.read_whole = start.elapsed();
+
+    if options.cold_read {
+        cache::evict_from_cache(&sparse)?;
+    }
+    let detected = AtomicBool::new(true);
+    let start = Instant::now();
+    bench::phase(options, "Sparse read data", "sparse/read-data", "read data", &mut result.failures, || {
+        bench::each_indexed(&sparse, options, |index, path| {
+            let data = read_data(&File::open(path)?)?;
+            if !data.iter().map(|(offset, bytes)| *offset..*offset + bytes.len() as u64).eq(extents.iter().cloned()) {
+                detected.store(false, Ordering::Relaxed);
+            } else if options.verify {
+                bench::with_content(options, index, false, |content| {
+                    match data.iter().all(|(offset, bytes)| content[*offset as usize..*offset as usize + bytes.len()] == bytes[..]) {
+                        true => Ok(()),
+                        false => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} does not hold the extents written to it", path.display()))),
+                    }
+                })?;
+            }
+            Ok(())
+        })
+    })?;
+    result.read_data = start.elapsed();
+    result.holes_detected = detected.into_inner();
+
+    fs::remove_dir_all(dir_path.join("dense"))?;
+    fs::remove_dir_all(dir_path.join("sparse"))?;
+    Ok(result)
+}
@@ -0,0 +1,272 @@
+//! Small files as rows of a SQLite table, for testing the claim that SQLite stores small
+//! blobs faster than the filesystem does. A [`BlobStore`] is one connection to a database
+//! holding `files(name TEXT PRIMARY KEY, data BLOB)`, opened in WAL mode with its insert,
+//! select and delete statements prepared once.
+//!
+//! This binds the system's `libsqlite3` directly rather than through a wrapper crate, so
+//! the `sqlite` feature needs it (and a linker that finds it) but nothing from crates.io.
+//! A connection is used by one thread at a time; share one behind a mutex or give each
+//! thread its own.
+
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+#[repr(C)]
+struct Sqlite3 {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct Stmt {
+    _private: [u8; 0],
+}
+
+#[link(name = "sqlite3")]
+unsafe extern "C" {
+    fn sqlite3_open_v2(filename: *const c_char, db: *mut *mut Sqlite3, flags: c_int, vfs: *const c_char) -> c_int;
+    fn sqlite3_close_v2(db: *mut Sqlite3) -> c_int;
+    fn sqlite3_errmsg(db: *mut Sqlite3) -> *const c_char;
+    fn sqlite3_system_errno(db: *mut Sqlite3) -> c_int;
+    fn sqlite3_busy_timeout(db: *mut Sqlite3, ms: c_int) -> c_int;
+    fn sqlite3_exec(db: *mut Sqlite3, sql: *const c_char, callback: *const c_void, arg: *mut c_void, errmsg: *mut *mut c_char) -> c_int;
+    fn sqlite3_changes(db: *mut Sqlite3) -> c_int;
+    fn sqlite3_prepare_v2(db: *mut Sqlite3, sql: *const c_char, len: c_int, stmt: *mut *mut Stmt, tail: *mut *const c_char) -> c_int;
+    fn sqlite3_bind_text(stmt: *mut Stmt, index: c_int, text: *const c_char, len: c_int, destructor: *const c_void) -> c_int;
+    fn sqlite3_bind_blob64(stmt: *mut Stmt, index: c_int, data: *const c_void, len: u64, destructor: *const c_void) -> c_int;
+    fn sqlite3_step(stmt: *mut Stmt) -> c_int;
+    fn sqlite3_column_blob(stmt: *mut Stmt, column: c_int) -> *const c_void;
+    fn sqlite3_column_bytes(stmt: *mut Stmt, column: c_int) -> c_int;
+    fn sqlite3_column_int64(stmt: *mut Stmt, column: c_int) -> i64;
+    fn sqlite3_reset(stmt: *mut Stmt) -> c_int;
+    fn sqlite3_finalize(stmt: *mut Stmt) -> c_int;
+}
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_OPEN_READWRITE: c_int = 0x2;
+const SQLITE_OPEN_CREATE: c_int = 0x4;
+/// No mutex inside the connection: the caller keeps it on one thread at a time.
+const SQLITE_OPEN_NOMUTEX: c_int = 0x8000;
+/// How long a connection waits for another's write lock before failing.
+const BUSY_TIMEOUT_MS: c_int = 10_000;
+
+/// A statement's prepared SQL, reset after every use.
+struct Statement(*mut Stmt);
+
+impl Drop for Statement {
+    fn drop(&mut self) {
+        unsafe { sqlite3_finalize(self.0) };
+    }
+}
+
+pub struct BlobStore {
+    path: PathBuf,
+    db: *mut Sqlite3,
+    put: Statement,
+    get: Statement,
+    delete: Statement,
+}
+
+// The connection is opened without SQLite's mutex, which allows moving it between threads
+// as long as only one uses it at a time; `&mut self` on every call sees to that.
+unsafe impl Send for BlobStore {}
+
+impl BlobStore {
+    /// Opens the database at `path`, creating it and the table if needed.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<BlobStore> {
+        let path = path.into();
+        let name = CString::new(path.to_string_lossy().into_owned()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut db = ptr::null_mut();
+        let code = unsafe { sqlite3_open_v2(name.as_ptr(), &mut db, SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE | SQLITE_OPEN_NOMUTEX, ptr::null()) };
+        if code != SQLITE_OK {
+            let error = open_error(db, &path);
+            unsafe { sqlite3_close_v2(db) };
+            return Err(error);
+        }
+        // Everything below closes `db` on failure by dropping the half-built store.
+        let mut store = BlobStore { path, db, put: Statement(ptr::null_mut()), get: Statement(ptr::null_mut()), delete: Statement(ptr::null_mut()) };
+        unsafe { sqlite3_busy_timeout(db, BUSY_TIMEOUT_MS) };
+        store.execute("PRAGMA journal_mode = WAL; CREATE TABLE IF NOT EXISTS files (name TEXT PRIMARY KEY, data BLOB NOT NULL)")?;
+        store.put = store.prepare("INSERT OR REPLACE INTO files (name, data) VALUES (?1, ?2)")?;
+        store.get = store.prepare("SELECT data FROM files WHERE name = ?1")?;
+        store.delete = store.prepare("DELETE FROM files WHERE name = ?1")?;
+        Ok(store)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Runs one or more statements that return no rows, such as a `PRAGMA`.
+    pub fn execute(&mut self, sql: &str) -> io::Result<()> {
+        let sql = CString::new(sql).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        match unsafe { sqlite3_exec(self.db, sql.as_ptr(), ptr::null(), ptr::null_mut(), ptr::null_mut()) } {
+            SQLITE_OK => Ok(()),
+            _ => Err(error(self.db, &self.path)),
+        }
+    }
+
+    /// Starts a transaction; until [`BlobStore::commit`], writes are neither visible to other
+    /// connections nor synced.
+    pub fn begin(&mut self) -> io::Result<()> {
+        self.execute("BEGIN IMMEDIATE")
+    }
+
+    pub fn commit(&mut self) -> io::Result<()> {
+        self.execute("COMMIT")
+    }
+
+    /// Whether each commit waits for the device (`PRAGMA synchronous = FULL`) or leaves
+    /// writeback to the kernel (`OFF`).
+    pub fn set_durable(&mut self, durable: bool) -> io::Result<()> {
+        self.execute(if durable { "PRAGMA synchronous = FULL" } else { "PRAGMA synchronous = OFF" })
+    }
+
+    /// Stores `bytes` as `name`, replacing what it held.
+    pub fn put(&mut self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        let statement = self.put.0;
+        self.bind_name(statement, name)?;
+        let code = unsafe { sqlite3_bind_blob64(statement, 2, bytes.as_ptr().cast(), bytes.len() as u64, ptr::null()) };
+        self.check(statement, code)?;
+        self.finish(statement).map(drop)
+    }
+
+    /// What `name` holds, if it exists.
+    pub fn get(&mut self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let mut buf = Vec::new();
+        Ok(self.get_into(name, &mut buf)?.then_some(buf))
+    }
+
+    /// Reads what `name` holds into `buf`, replacing its contents; false if it doesn't exist.
+    pub fn get_into(&mut self, name: &str, buf: &mut Vec<u8>) -> io::Result<bool> {
+        let statement = self.get.0;
+        self.bind_name(statement, name)?;
+        let found = match unsafe { sqlite3_step(statement) } {
+            SQLITE_ROW => {
+                let (data, len) = unsafe { (sqlite3_column_blob(statement, 0), sqlite3_column_bytes(statement, 0)) };
+                buf.clear();
+                if len > 0 {
+                    // Valid until the statement is reset below.
+                    buf.extend_from_slice(unsafe { std::slice::from_raw_parts(data.cast::<u8>(), len as usize) });
+                }
+                true
+            }
+            SQLITE_DONE => false,
+            _ => {
+                unsafe { sqlite3_reset(statement) };
+                return Err(error(self.db, &self.path));
+            }
+        };
+        unsafe { sqlite3_reset(statement) };
+        Ok(found)
+    }
+
+    /// Removes `name`; false if it didn't exist.
+    pub fn delete(&mut self, name: &str) -> io::Result<bool> {
+        let statement = self.delete.0;
+        self.bind_name(statement, name)?;
+        Ok(self.finish(statement)? > 0)
+    }
+
+    /// How many names are stored.
+    pub fn len(&mut self) -> io::Result<usize> {
+        let statement = self.prepare("SELECT count(*) FROM files")?;
+        match unsafe { sqlite3_step(statement.0) } {
+            SQLITE_ROW => Ok(unsafe { sqlite3_column_int64(statement.0, 0) } as usize),
+            _ => Err(error(self.db, &self.path)),
+        }
+    }
+
+    pub fn is_empty(&mut self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    fn prepare(&self, sql: &str) -> io::Result<Statement> {
+        let sql = CString::new(sql).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut statement = ptr::null_mut();
+        match unsafe { sqlite3_prepare_v2(self.db, sql.as_ptr(), -1, &mut statement, ptr::null_mut()) } {
+            SQLITE_OK => Ok(Statement(statement)),
+            _ => Err(error(self.db, &self.path)),
+        }
+    }
+
+    /// Binds `name` as the first parameter. SQLite doesn't copy it, which is fine: the
+    /// statement runs and is reset before `name` goes away.
+    fn bind_name(&self, statement: *mut Stmt, name: &str) -> io::Result<()> {
+        let len = c_int::try_from(name.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "name too long for SQLite"))?;
+        let code = unsafe { sqlite3_bind_text(statement, 1, name.as_ptr().cast(), len, ptr::null()) };
+        self.check(statement, code)
+    }
+
+    fn check(&self, statement: *mut Stmt, code: c_int) -> io::Result<()> {
+        if code == SQLITE_OK {
+            return Ok(());
+        }
+        unsafe { sqlite3_reset(statement) };
+        Err(error(self.db, &self.path))
+    }
+
+    /// Runs a statement that returns no rows and resets it; returns the rows it changed.
+    fn finish(&self, statement: *mut Stmt) -> io::Result<usize> {
+        let code = unsafe { sqlite3_step(statement) };
+        let result = match code {
+            SQLITE_DONE => Ok(unsafe { sqlite3_changes(self.db) } as usize),
+            _ => Err(error(self.db, &self.path)),
+        };
+        unsafe { sqlite3_reset(statement) };
+        result
+    }
+}
+
+impl Drop for BlobStore {
+    fn drop(&mut self) {
+        // The statements go first; `close_v2` would otherwise keep the connection open
+        // until they did.
+        for statement in [&mut self.put, &mut self.get, &mut self.delete] {
+            drop(std::mem::replace(statement, Statement(ptr::null_mut())));
+        }
+        unsafe { sqlite3_close_v2(self.db) };
+    }
+}
+
+/// The connection's last error, naming the database.
+fn error(db: *mut Sqlite3, path: &Path) -> io::Error {
+    let message = match db.is_null() {
+        true => "out of memory".into(),
+        false => unsafe { CStr::from_ptr(sqlite3_errmsg(db)) }.to_string_lossy(),
+    };
+    io::Error::other(format!("{}: sqlite: {}", path.display(), message))
+}
+
+/// Why the database couldn't be opened, of the kind of the system call that failed under
+/// it (`NotFound` for a database whose directory doesn't exist).
+fn open_error(db: *mut Sqlite3, path: &Path) -> io::Error {
+    let error = error(db, path);
+    match db.is_null() {
+        true => error,
+        false => match unsafe { sqlite3_system_errno(db) } {
+            0 => error,
+            errno => io::Error::new(io::Error::from_raw_os_error(errno).kind(), error.to_string()),
+        },
+    }
+}
+
+/// Deletes the database at `path` with its write-ahead log and shared-memory index.
+pub fn remove(path: &Path) -> io::Result<()> {
+    for suffix in ["-wal", "-shm"] {
+        let mut side = path.as_os_str().to_owned();
+        side.push(suffix);
+        match fs::remove_file(side) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,205 @@
+//! Saving a directory tree and putting it back, the "save and restore the cache
+//! directory" step every CI system reimplements: [`snapshot`] copies a tree somewhere
+//! aside, and [`restore`] copies it back after the job has changed or removed it.
+//!
+//! Both make each file by one of the [`Checkout`] methods of the content store. Reflinks
+//! share extents copy-on-write, so they are nearly free and the copies stay independent;
+//! hard links are free too, but leave the saved file and the restored one the same inode,
+//! so a tool that edits a file in place (rather than replacing it) changes the saved copy
+//! as well. Left to choose, both calls use reflinks where the filesystem has them and full
+//! copies otherwise, and never hard links. Files are made in parallel with the `rayon`
+//! feature; directories are created first and symlinks recreated as symlinks.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+use crate::cas::{self, Checkout};
+use crate::contents;
+use crate::links;
+use crate::retry::RetryPolicy;
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+#[cfg(feature = "bench")]
+use crate::names;
+
+/// What a [`snapshot`] or [`restore`] made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+    /// How the files were made.
+    pub method: Checkout,
+    pub files: usize,
+    pub bytes: u64,
+    pub dirs: usize,
+    pub symlinks: usize,
+}
+
+/// The entries of a tree, relative to its root.
+#[derive(Debug, Default)]
+pub(crate) struct Listing {
+    pub(crate) dirs: Vec<PathBuf>,
+    /// Each file and its size.
+    pub(crate) files: Vec<(PathBuf, u64)>,
+    /// Each symlink and its target.
+    pub(crate) symlinks: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Listing {
+    pub(crate) fn of(root: &Path) -> io::Result<Listing> {
+        let mut listing = Listing::default();
+        let mut pending = vec![PathBuf::new()];
+        while let Some(relative) = pending.pop() {
+            for entry in fs::read_dir(root.join(&relative))? {
+                let entry = entry?;
+                let path = relative.join(entry.file_name());
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    listing.dirs.push(path.clone());
+                    pending.push(path);
+                } else if file_type.is_symlink() {
+                    listing.symlinks.push((path, fs::read_link(entry.path())?));
+                } else if file_type.is_file() {
+                    listing.files.push((path, entry.metadata()?.len()));
+                }
+            }
+        }
+        // Parents before children.
+        listing.dirs.sort();
+        Ok(listing)
+    }
+}
+
+/// Creates `dir` if it's missing, and fails with [`io::ErrorKind::AlreadyExists`] if it
+/// has entries.
+pub(crate) fn empty_dir(dir: &Path) -> io::Result<()> {
+    match fs::read_dir(dir) {
+        Ok(mut entries) => match entries.next() {
+            Some(_) => Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} isn't empty", dir.display()))),
+            None => Ok(()),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => fs::create_dir_all(dir),
+        Err(e) => Err(e),
+    }
+}
+
+/// Saves the tree at `dir` to `archive`, which must not exist or be an empty directory,
+/// making its files by `method`, or with `None` by reflink where the filesystem allows
+/// and by copy otherwise. A call that fails leaves what it made in `archive`.
+pub fn snapshot(dir: &Path, archive: &Path, method: Option<Checkout>) -> io::Result<Report> {
+    clone_tree(dir, archive, method)
+}
+
+/// Puts the tree saved at `archive` back at `dir`, which must not exist or be an empty
+/// directory (remove what the job left there first), choosing the method as [`snapshot`]
+/// does.
+pub fn restore(archive: &Path, dir: &Path, method: Option<Checkout>) -> io::Result<Report> {
+    clone_tree(archive, dir, method)
+}
+
+fn clone_tree(from: &Path, to: &Path, method: Option<Checkout>) -> io::Result<Report> {
+    let listing = Listing::of(from)?;
+    empty_dir(to)?;
+    for dir in &listing.dirs {
+        fs::create_dir(to.join(dir))?;
+    }
+    let make = |method: Checkout, (path, _): &(PathBuf, u64)| match method {
+        Checkout::HardLink => fs::hard_link(from.join(path), to.join(path)),
+        Checkout::Reflink => cas::reflink(&from.join(path), &to.join(path)),
+        Checkout::Copy => fs::copy(from.join(path), to.join(path)).map(drop),
+    };
+    // Trying the first file tells whether reflinks work here.
+    let (method, rest) = match (method, listing.files.split_first()) {
+        (Some(method), _) => (method, &listing.files[..]),
+        (None, None) => (Checkout::Copy, &listing.files[..]),
+        (None, Some((first, rest))) => match make(Checkout::Reflink, first) {
+            Ok(()) => (Checkout::Reflink, rest),
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => (Checkout::Copy, &listing.files[..]),
+            Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {}", from.join(&first.0).display(), e))),
+        },
+    };
+    contents::each_retried(rest, &RetryPolicy::default(), |(path, _)| path, |file| make(method, file))?;
+    for (path, target) in &listing.symlinks {
+        links::symlink(target, &to.join(path))?;
+    }
+    Ok(Report { method, files: listing.files.len(), bytes: listing.files.iter().map(|(_, size)| size).sum(), dirs: listing.dirs.len(), symlinks: listing.symlinks.len() })
+}
+
+/// Directories the benchmark spreads the workload over, as a cache tree would be.
+#[cfg(feature = "bench")]
+pub const BENCH_DIRS: usize = 16;
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct MethodResult {
+    pub method: Checkout,
+    /// Saving the tree and restoring it; `None` where the filesystem can't use the method.
+    pub snapshot: Option<Duration>,
+    pub restore: Option<Duration>,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct StashResult {
+    pub methods: Vec<MethodResult>,
+    pub failures: Vec<Failure>,
+}
+
+/// Writes the configured workload as a tree of [`BENCH_DIRS`] directories under
+/// `dir_path/cache`, then for each method times saving it to `dir_path/saved`, and,
+/// after the tree is removed untimed, restoring it. The restored tree must hold what was
+/// saved. A method the filesystem refuses is skipped.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, options: &Options) -> io::Result<StashResult> {
+    let (cache, saved) = (dir_path.join("cache"), dir_path.join("saved"));
+    let dirs: Vec<PathBuf> = (0..BENCH_DIRS).map(|i| cache.join(format!("d{:02}", i))).collect();
+    for dir in &dirs {
+        fs::create_dir_all(dir)?;
+    }
+    let paths = names::paths(&dirs, options.workload.files, options.workload.names);
+    let mut result = StashResult::default();
+    options.failures.take("");
+    bench::phase(options, "Stash create", "stash/create", "create", &mut result.failures, || {
+        bench::each_indexed(&paths, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+    })?;
+
+    for method in Checkout::ALL {
+        options.progress.set_stage(format!("Stash {} snapshot", method.name()));
+        options.op_scope.set(format!("stash/{}/snapshot", method.name()));
+        let start = Instant::now();
+        let snapshot = match self::snapshot(&cache, &saved, Some(method)) {
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => None,
+            other => other.map(|_| start.elapsed()).map(Some)?,
+        };
+        let restore = match snapshot {
+            Some(_) => {
+                fs::remove_dir_all(&cache)?;
+                options.progress.set_stage(format!("Stash {} restore", method.name()));
+                options.op_scope.set(format!("stash/{}/restore", method.name()));
+                let start = Instant::now();
+                let report = self::restore(&saved, &cache, Some(method))?;
+                let elapsed = start.elapsed();
+                if report.files != paths.len() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("restored {} of {} files", report.files, paths.len())));
+                }
+                Some(elapsed)
+            }
+            None => None,
+        };
+        let _ = fs::remove_dir_all(&saved);
+        result.methods.push(MethodResult { method, snapshot, restore });
+    }
+    if options.verify {
+        bench::phase(options, "Stash verify", "stash/verify", "verify", &mut result.failures, || {
+            bench::each_indexed(&paths, options, |index, path| {
+                let data = fs::read(path)?;
+                bench::with_content(options, index, false, |content| match data == content {
+                    true => Ok(()),
+                    false => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} differs after restoring", path.display()))),
+                })
+            })
+        })?;
+    }
+    Ok(result)
+}
@@ -0,0 +1,510 @@
+//! Batch file operations as a `Stream` of completions, for async services that already
+//! apply backpressure: an axum handler or tokio task polls [`Completions`] like any other
+//! stream instead of spawning a blocking task per file.
+//!
+//! At most `capacity` operations run at once, each on tokio's blocking pool, and the next
+//! one starts only when the stream is polled after another completes, so a consumer that
+//! stops polling stops the work too and nothing queues up without bound. Completions come
+//! in the order they finish; [`Completion::index`] says which operation each is.
+//!
+//! [`read_many_stream`], [`write_many_stream`] and [`delete_many_stream`] each do one kind
+//! of operation, in input order if asked, on the [`Backend`] the [`StreamOptions`] name:
+//! tokio's blocking pool a file per task, or on Linux an io_uring a batch of files per
+//! task. [`read_many`], [`write_many`] and [`delete_many`] are the same as futures that
+//! fail on the first error, like their synchronous counterparts in [`crate::contents`].
+
+use std::collections::VecDeque;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+#[cfg(all(target_os = "linux", feature = "libc"))]
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{self, BoxStream, FuturesUnordered, Stream, StreamExt};
+
+use crate::contents::Order;
+use crate::retry::RetryPolicy;
+
+/// One file operation of a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Read(PathBuf),
+    /// Creates or truncates the file.
+    Write(PathBuf, Vec<u8>),
+    Copy { from: PathBuf, to: PathBuf },
+    Remove(PathBuf),
+    /// `stat`, following symlinks.
+    Metadata(PathBuf),
+}
+
+impl Op {
+    /// The file the operation acts on, the destination for a copy.
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            Op::Read(path) | Op::Write(path, _) | Op::Remove(path) | Op::Metadata(path) => path,
+            Op::Copy { to, .. } => to,
+        }
+    }
+
+    fn run(self) -> io::Result<Output> {
+        match self {
+            Op::Read(path) => fs::read(path).map(Output::Read),
+            Op::Write(path, bytes) => fs::write(path, &bytes).map(|()| Output::Written(bytes.len() as u64)),
+            Op::Copy { from, to } => fs::copy(from, to).map(Output::Written),
+            Op::Remove(path) => fs::remove_file(path).map(|()| Output::Removed),
+            Op::Metadata(path) => fs::metadata(path).map(Output::Metadata),
+        }
+    }
+}
+
+/// What a successful [`Op`] produced.
+#[derive(Debug)]
+pub enum Output {
+    Read(Vec<u8>),
+    /// Bytes written by a write or copy.
+    Written(u64),
+    Removed,
+    Metadata(Metadata),
+}
+
+#[derive(Debug)]
+pub struct Completion {
+    /// The operation's position in the batch.
+    pub index: usize,
+    pub path: PathBuf,
+    pub result: io::Result<Output>,
+}
+
+/// A batch of operations run with bounded concurrency; see the module docs.
+#[derive(Debug)]
+pub struct Completions {
+    pending: VecDeque<(usize, Op)>,
+    running: FuturesUnordered<BoxFuture<'static, Completion>>,
+    capacity: usize,
+}
+
+impl Completions {
+    /// Runs `ops` with at most `capacity` in flight, at least one. Nothing starts until the
+    /// stream is first polled, which must happen within a tokio runtime.
+    pub fn new(ops: impl IntoIterator<Item = Op>, capacity: usize) -> Completions {
+        Completions { pending: ops.into_iter().enumerate().collect(), running: FuturesUnordered::new(), capacity: capacity.max(1) }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Operations started and not yet returned by the stream.
+    pub fn in_flight(&self) -> usize {
+        self.running.len()
+    }
+
+    /// Operations not started yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+impl Stream for Completions {
+    type Item = Completion;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Completion>> {
+        while self.running.len() < self.capacity {
+            let Some((index, op)) = self.pending.pop_front() else { break };
+            let path = op.path().clone();
+            let task = tokio::task::spawn_blocking(move || op.run());
+            self.running.push(async move { Completion { index, path, result: task.await.unwrap_or_else(|e| Err(io::Error::other(e))) } }.boxed());
+        }
+        Pin::new(&mut self.running).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.pending.len() + self.running.len();
+        (left, Some(left))
+    }
+}
+
+/// Files a blocking-pool task takes through the ring at once with [`Backend::Uring`].
+pub const URING_BATCH: usize = 64;
+
+/// Where the bulk streams do their I/O.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// `std::fs`, one file per task on tokio's blocking pool.
+    Blocking,
+    /// Up to [`URING_BATCH`] files per blocking-pool task, whose opens, reads or writes and
+    /// unlinks go to the thread's io_uring a round at a time. A file the ring fails, or
+    /// every file where no ring can be set up, is done again the blocking way. Needs Linux
+    /// and the `libc` feature; elsewhere it is the same as [`Backend::Blocking`].
+    Uring,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 2] = [Backend::Blocking, Backend::Uring];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Blocking => "blocking",
+            Backend::Uring => "io_uring",
+        }
+    }
+
+    /// [`Backend::Uring`] where a ring can be set up, checked once per process, otherwise
+    /// [`Backend::Blocking`].
+    pub fn best() -> Backend {
+        #[cfg(all(target_os = "linux", feature = "libc"))]
+        {
+            static BEST: OnceLock<Backend> = OnceLock::new();
+            *BEST.get_or_init(|| if crate::uring::unavailable().is_none() { Backend::Uring } else { Backend::Blocking })
+        }
+        #[cfg(not(all(target_os = "linux", feature = "libc")))]
+        {
+            Backend::Blocking
+        }
+    }
+}
+
+/// How a bulk stream runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamOptions {
+    /// Files being worked on or done and not yet yielded, at most; at least one. With
+    /// [`Backend::Uring`] a batch is never larger.
+    pub in_flight: usize,
+    pub order: Order,
+    pub backend: Backend,
+}
+
+impl Default for StreamOptions {
+    /// 256 files in flight, in input order, on [`Backend::best`].
+    fn default() -> StreamOptions {
+        StreamOptions { in_flight: 256, order: Order::Input, backend: Backend::best() }
+    }
+}
+
+/// Reads every file in `paths`, yielding each path and its contents as `options` say, as
+/// `crate::contents::read_many_stream` does for synchronous code. Each file is retried on
+/// transient errors as `policy` says. Must be polled within a tokio runtime.
+pub fn read_many_stream(paths: Vec<PathBuf>, options: &StreamOptions, policy: &RetryPolicy) -> BoxStream<'static, (PathBuf, io::Result<Vec<u8>>)> {
+    bulk(paths, options, policy, |path| path, read_batch)
+}
+
+/// Writes each `(path, contents)` pair, creating or truncating the file, yielding each
+/// path and the bytes written as `options` say. Each file is retried on transient errors
+/// as `policy` says. Must be polled within a tokio runtime.
+pub fn write_many_stream(files: Vec<(PathBuf, Vec<u8>)>, options: &StreamOptions, policy: &RetryPolicy) -> BoxStream<'static, (PathBuf, io::Result<u64>)> {
+    bulk(files, options, policy, |(path, _)| path, write_batch)
+}
+
+/// Deletes every file in `paths`, yielding each path and whether it went as `options` say.
+/// Each file is retried on transient errors as `policy` says. Must be polled within a
+/// tokio runtime.
+pub fn delete_many_stream(paths: Vec<PathBuf>, options: &StreamOptions, policy: &RetryPolicy) -> BoxStream<'static, (PathBuf, io::Result<()>)> {
+    bulk(paths, options, policy, |path| path, delete_batch)
+}
+
+/// Reads every file in `paths` with the default [`StreamOptions`], returning the contents
+/// in the same order. Fails on the first file that still can't be read after retrying.
+pub async fn read_many(paths: Vec<PathBuf>, policy: &RetryPolicy) -> io::Result<Vec<Vec<u8>>> {
+    first_error(read_many_stream(paths, &StreamOptions::default(), policy)).await
+}
+
+/// Writes each `(path, contents)` pair with the default [`StreamOptions`]. Fails on the
+/// first file that still can't be written after retrying.
+pub async fn write_many(files: Vec<(PathBuf, Vec<u8>)>, policy: &RetryPolicy) -> io::Result<()> {
+    first_error(write_many_stream(files, &StreamOptions::default(), policy)).await.map(drop)
+}
+
+/// Deletes every file in `paths` with the default [`StreamOptions`]. Fails on the first
+/// file that still can't be deleted after retrying, by which time others may be gone.
+pub async fn delete_many(paths: Vec<PathBuf>, policy: &RetryPolicy) -> io::Result<()> {
+    first_error(delete_many_stream(paths, &StreamOptions::default(), policy)).await.map(drop)
+}
+
+/// Every result, or the first error with its path. Work still in flight is dropped.
+async fn first_error<R>(mut results: BoxStream<'static, (PathBuf, io::Result<R>)>) -> io::Result<Vec<R>> {
+    let mut collected = Vec::new();
+    while let Some((path, result)) = results.next().await {
+        collected.push(result.map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?);
+    }
+    Ok(collected)
+}
+
+/// Each file of a batch and what became of it.
+type Batch<R> = Vec<(PathBuf, io::Result<R>)>;
+
+/// Runs `items` through `run` in batches on the blocking pool, as many batches at once as
+/// fit in `options.in_flight`, and yields the results one file at a time.
+fn bulk<T, R>(
+    items: Vec<T>,
+    options: &StreamOptions,
+    policy: &RetryPolicy,
+    path: fn(&T) -> &PathBuf,
+    run: fn(Vec<T>, Backend, &RetryPolicy) -> Batch<R>,
+) -> BoxStream<'static, (PathBuf, io::Result<R>)>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+{
+    let in_flight = options.in_flight.max(1);
+    let batch = match options.backend {
+        Backend::Blocking => 1,
+        Backend::Uring => URING_BATCH.min(in_flight),
+    };
+    let mut items = items.into_iter();
+    let batches: Vec<Vec<T>> = std::iter::from_fn(|| Some(items.by_ref().take(batch).collect::<Vec<T>>()).filter(|batch| !batch.is_empty())).collect();
+    let (backend, policy) = (options.backend, policy.clone());
+    let tasks = stream::iter(batches).map(move |batch| {
+        let policy = policy.clone();
+        // Kept to name the files should the task panic.
+        let paths: Vec<PathBuf> = batch.iter().map(|item| path(item).clone()).collect();
+        async move {
+            match tokio::task::spawn_blocking(move || run(batch, backend, &policy)).await {
+                Ok(results) => results,
+                Err(e) => {
+                    let message = e.to_string();
+                    paths.into_iter().map(|path| (path, Err(io::Error::other(message.clone())))).collect()
+                }
+            }
+        }
+    });
+    let done = match options.order {
+        Order::Input => tasks.buffered(in_flight / batch).boxed(),
+        Order::Completion => tasks.buffer_unordered(in_flight / batch).boxed(),
+    };
+    done.flat_map(stream::iter).boxed()
+}
+
+fn read_batch(paths: Vec<PathBuf>, backend: Backend, policy: &RetryPolicy) -> Vec<(PathBuf, io::Result<Vec<u8>>)> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    if backend == Backend::Uring {
+        return ring::read(paths, policy);
+    }
+    let _ = backend;
+    paths
+        .into_iter()
+        .map(|path| {
+            let read = policy.run(|| fs::read(&path));
+            (path, read)
+        })
+        .collect()
+}
+
+fn write_batch(files: Vec<(PathBuf, Vec<u8>)>, backend: Backend, policy: &RetryPolicy) -> Vec<(PathBuf, io::Result<u64>)> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    if backend == Backend::Uring {
+        return ring::write(files, policy);
+    }
+    let _ = backend;
+    files
+        .into_iter()
+        .map(|(path, bytes)| {
+            let written = policy.run(|| fs::write(&path, &bytes)).map(|()| bytes.len() as u64);
+            (path, written)
+        })
+        .collect()
+}
+
+fn delete_batch(paths: Vec<PathBuf>, backend: Backend, policy: &RetryPolicy) -> Vec<(PathBuf, io::Result<()>)> {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    if backend == Backend::Uring {
+        return ring::delete(paths, policy);
+    }
+    let _ = backend;
+    paths
+        .into_iter()
+        .map(|path| {
+            let removed = policy.run(|| fs::remove_file(&path));
+            (path, removed)
+        })
+        .collect()
+}
+
+/// [`Backend::Uring`]'s batches. Each blocking-pool thread keeps a ring for the tasks it
+/// runs. What the ring fails comes back as `None` and is done again with `std::fs`, which
+/// retries and reports the error properly.
+#[cfg(all(target_os = "linux", feature = "libc"))]
+mod ring {
+    use std::cell::RefCell;
+    use std::ffi::CString;
+    use std::fs;
+    use std::io;
+    use std::mem;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    use super::URING_BATCH;
+    use crate::retry::RetryPolicy;
+    use crate::uring::{Entry, Ring};
+
+    thread_local! {
+        static RING: RefCell<Option<Ring>> = const { RefCell::new(None) };
+    }
+
+    /// Whether this thread has a ring, setting one up if not.
+    fn ready() -> bool {
+        RING.with_borrow_mut(|ring| {
+            if ring.is_none() {
+                // Reads take two entries a file in their first round.
+                *ring = Ring::new(2 * URING_BATCH as u32).ok();
+            }
+            ring.is_some()
+        })
+    }
+
+    fn c_path(path: &Path) -> Option<CString> {
+        CString::new(path.as_os_str().as_bytes()).ok()
+    }
+
+    /// Runs `entries` on this thread's ring, returning each one's result in order.
+    ///
+    /// # Safety
+    ///
+    /// [`ready`] must have been true. Every name and buffer the entries point to must stay
+    /// valid until this returns, and be leaked if it fails: the kernel may still use them.
+    unsafe fn round(entries: &[Entry]) -> io::Result<Vec<io::Result<u32>>> {
+        RING.with_borrow_mut(|slot| {
+            let ring = slot.as_mut().expect("no ring on this thread");
+            let mut results: Vec<Option<io::Result<u32>>> = entries.iter().map(|_| None).collect();
+            let (mut pushed, mut reaped) = (0, 0);
+            while reaped < entries.len() {
+                while pushed < entries.len() && unsafe { ring.push(&entries[pushed].user_data(pushed as u64)) } {
+                    pushed += 1;
+                }
+                if let Err(e) = ring.submit(1) {
+                    // The ring may still hold some of the round; the next task gets a new one.
+                    *slot = None;
+                    return Err(e);
+                }
+                while let Some(completion) = ring.pop() {
+                    results[completion.user_data as usize] = Some(completion.result());
+                    reaped += 1;
+                }
+            }
+            Ok(results.into_iter().flatten().collect())
+        })
+    }
+
+    /// Opens the files in one round, for those `names` has.
+    fn open(names: Vec<Option<CString>>, flags: i32) -> Vec<Option<OwnedFd>> {
+        let mut fds: Vec<Option<OwnedFd>> = names.iter().map(|_| None).collect();
+        let opening: Vec<usize> = (0..names.len()).filter(|&i| names[i].is_some()).collect();
+        let opens: Vec<Entry> = opening.iter().filter_map(|&i| names[i].as_deref()).map(|name| Entry::openat(libc::AT_FDCWD, name, flags | libc::O_CLOEXEC, 0o666)).collect();
+        match unsafe { round(&opens) } {
+            Ok(opened) => {
+                for (i, fd) in opening.into_iter().zip(opened) {
+                    fds[i] = fd.ok().map(|fd| unsafe { OwnedFd::from_raw_fd(fd as i32) });
+                }
+            }
+            Err(_) => mem::forget(names),
+        }
+        fds
+    }
+
+    pub fn write(files: Vec<(PathBuf, Vec<u8>)>, policy: &RetryPolicy) -> Vec<(PathBuf, io::Result<u64>)> {
+        let (paths, mut contents): (Vec<PathBuf>, Vec<Vec<u8>>) = files.into_iter().unzip();
+        let mut written: Vec<Option<io::Result<u64>>> = paths.iter().map(|_| None).collect();
+        if ready() {
+            let names = paths.iter().zip(&contents).map(|(path, bytes)| c_path(path).filter(|_| u32::try_from(bytes.len()).is_ok())).collect();
+            let fds = open(names, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC);
+            let open: Vec<(usize, &OwnedFd)> = fds.iter().enumerate().filter_map(|(i, fd)| Some((i, fd.as_ref()?))).collect();
+            let writes: Vec<Entry> = open.iter().map(|&(i, fd)| Entry::write(fd.as_raw_fd(), contents[i].as_ptr(), contents[i].len() as u32, 0)).collect();
+            match unsafe { round(&writes) } {
+                Ok(results) => {
+                    for (&(i, _), result) in open.iter().zip(results) {
+                        if let Ok(bytes) = result
+                            && bytes as usize == contents[i].len()
+                        {
+                            written[i] = Some(Ok(bytes as u64));
+                        }
+                    }
+                }
+                Err(e) => {
+                    for &(i, _) in &open {
+                        mem::forget(mem::take(&mut contents[i]));
+                        written[i] = Some(Err(io::Error::new(e.kind(), format!("io_uring submission failed: {}", e))));
+                    }
+                }
+            }
+        }
+        paths
+            .into_iter()
+            .zip(contents)
+            .zip(written)
+            .map(|((path, bytes), written)| {
+                let written = written.unwrap_or_else(|| policy.run(|| fs::write(&path, &bytes)).map(|()| bytes.len() as u64));
+                (path, written)
+            })
+            .collect()
+    }
+
+    pub fn read(paths: Vec<PathBuf>, policy: &RetryPolicy) -> Vec<(PathBuf, io::Result<Vec<u8>>)> {
+        let mut read: Vec<Option<Vec<u8>>> = paths.iter().map(|_| None).collect();
+        if ready() {
+            let fds = open(paths.iter().map(|path| c_path(path)).collect(), libc::O_RDONLY);
+            // Only regular files with something in them: the rest are read the ordinary way.
+            let mut buffers: Vec<(usize, &OwnedFd, Vec<u8>)> = fds
+                .iter()
+                .enumerate()
+                .filter_map(|(i, fd)| {
+                    let fd = fd.as_ref()?;
+                    let mut stat: libc::stat = unsafe { mem::zeroed() };
+                    let regular = unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } == 0 && stat.st_mode & libc::S_IFMT == libc::S_IFREG;
+                    let size = stat.st_size as u64;
+                    (regular && size > 0 && u32::try_from(size).is_ok()).then(|| (i, fd, Vec::with_capacity(size as usize)))
+                })
+                .collect();
+            let reads: Vec<Entry> = buffers.iter_mut().map(|(_, fd, buffer)| Entry::read(fd.as_raw_fd(), buffer.as_mut_ptr(), buffer.capacity() as u32, 0)).collect();
+            match unsafe { round(&reads) } {
+                Ok(results) => {
+                    for ((i, _, mut buffer), result) in buffers.into_iter().zip(results) {
+                        if let Ok(bytes) = result
+                            && bytes as usize == buffer.capacity()
+                        {
+                            // The kernel filled all of it.
+                            unsafe { buffer.set_len(bytes as usize) };
+                            read[i] = Some(buffer);
+                        }
+                    }
+                }
+                Err(_) => mem::forget(buffers.into_iter().map(|(_, _, buffer)| buffer).collect::<Vec<_>>()),
+            }
+        }
+        paths
+            .into_iter()
+            .zip(read)
+            .map(|(path, read)| {
+                let read = read.map_or_else(|| policy.run(|| fs::read(&path)), Ok);
+                (path, read)
+            })
+            .collect()
+    }
+
+    pub fn delete(paths: Vec<PathBuf>, policy: &RetryPolicy) -> Vec<(PathBuf, io::Result<()>)> {
+        let mut removed = vec![false; paths.len()];
+        if ready() {
+            let names: Vec<Option<CString>> = paths.iter().map(|path| c_path(path)).collect();
+            let removing: Vec<usize> = (0..names.len()).filter(|&i| names[i].is_some()).collect();
+            let unlinks: Vec<Entry> = removing.iter().filter_map(|&i| names[i].as_deref()).map(|name| Entry::unlinkat(libc::AT_FDCWD, name, 0)).collect();
+            match unsafe { round(&unlinks) } {
+                Ok(results) => {
+                    for (i, result) in removing.into_iter().zip(results) {
+                        removed[i] = result.is_ok();
+                    }
+                }
+                Err(_) => mem::forget(names),
+            }
+        }
+        paths
+            .into_iter()
+            .zip(removed)
+            .map(|(path, removed)| {
+                let result = if removed { Ok(()) } else { policy.run(|| fs::remove_file(&path)) };
+                (path, result)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,95 @@
+//! Bandwidth and IOPS limits shared by every worker, to simulate constrained storage or keep
+//! a run from saturating a shared CI machine while latency is still collected.
+//!
+//! Each limit is a [`TokenBucket`] that refills at its rate and holds a tenth of a second's
+//! worth. Taking more than the bucket holds is allowed and leaves it in debt, which the
+//! caller sleeps off, so one large file can't slip through a small burst and later takers
+//! queue up behind it in the order they arrived.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How much of a second's rate a bucket holds when full.
+const BURST: f64 = 0.1;
+
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// Tokens added per second.
+    rate: f64,
+    capacity: f64,
+    /// Tokens left, negative when in debt, as of the instant beside it.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// A full bucket refilling at `rate` tokens a second.
+    pub fn new(rate: f64) -> TokenBucket {
+        let capacity = (rate * BURST).max(1.0);
+        TokenBucket { rate, capacity, state: Mutex::new((capacity, Instant::now())) }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Takes `tokens`, sleeping until the bucket is out of debt, and returns the time slept.
+    pub fn take(&self, tokens: f64) -> Duration {
+        let wait = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let (available, last) = &mut *state;
+            let now = Instant::now();
+            *available = (*available + now.duration_since(*last).as_secs_f64() * self.rate).min(self.capacity) - tokens;
+            *last = now;
+            if *available >= 0.0 { Duration::ZERO } else { Duration::from_secs_f64(-*available / self.rate) }
+        };
+        thread::sleep(wait);
+        wait
+    }
+}
+
+/// A bytes-per-second and an operations-per-second limit, either optional.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    bytes: Option<TokenBucket>,
+    ops: Option<TokenBucket>,
+    waited_nanos: AtomicU64,
+}
+
+impl Throttle {
+    pub fn new(bytes_per_second: Option<f64>, ops_per_second: Option<f64>) -> Throttle {
+        Throttle { bytes: bytes_per_second.map(TokenBucket::new), ops: ops_per_second.map(TokenBucket::new), waited_nanos: AtomicU64::new(0) }
+    }
+
+    pub fn bytes_per_second(&self) -> Option<f64> {
+        self.bytes.as_ref().map(TokenBucket::rate)
+    }
+
+    pub fn ops_per_second(&self) -> Option<f64> {
+        self.ops.as_ref().map(TokenBucket::rate)
+    }
+
+    /// Waits for one operation's turn.
+    pub fn op(&self) {
+        if let Some(ops) = &self.ops {
+            self.note(ops.take(1.0));
+        }
+    }
+
+    /// Accounts for `bytes` read or written, waiting if they went over the limit.
+    pub fn bytes(&self, bytes: u64) {
+        if let Some(limit) = &self.bytes {
+            self.note(limit.take(bytes as f64));
+        }
+    }
+
+    fn note(&self, waited: Duration) {
+        self.waited_nanos.fetch_add(waited.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Time workers spent waiting on the limits, added up over all of them.
+    pub fn waited(&self) -> Duration {
+        Duration::from_nanos(self.waited_nanos.load(Ordering::Relaxed))
+    }
+}
@@ -0,0 +1,52 @@
+//! Stable IDs for logical file operations, so one file can be followed through every
+//! report that mentions it.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::population::hash_bytes;
+use crate::rundir;
+
+/// Identifies one logical file operation in every report that mentions it: a hash of the
+/// operation's scope (such as `Smart/update`) and the file's path, so the same operation
+/// gets the same ID in every artifact and in every run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpId(pub u64);
+
+impl OpId {
+    /// A path inside a run directory is hashed relative to it, as a snapshot hashes its
+    /// files relative to its root, so the ID doesn't change with the run ID.
+    pub fn new(scope: &str, path: &Path) -> OpId {
+        let path = path
+            .ancestors()
+            .find(|dir| dir.file_name().is_some_and(|name| name.as_encoded_bytes().starts_with(rundir::PREFIX.as_bytes())))
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+        let mut key = Vec::with_capacity(scope.len() + 1 + path.as_os_str().len());
+        key.extend_from_slice(scope.as_bytes());
+        key.push(0);
+        key.extend_from_slice(path.as_os_str().as_encoded_bytes());
+        OpId(hash_bytes(&key))
+    }
+}
+
+impl fmt::Display for OpId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// The scope of the [`OpId`]s of the phase about to run, set by whoever runs it.
+#[derive(Debug, Default)]
+pub struct OpScope(Mutex<String>);
+
+impl OpScope {
+    pub fn set(&self, scope: impl Into<String>) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = scope.into();
+    }
+
+    pub fn get(&self) -> String {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
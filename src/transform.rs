@@ -0,0 +1,210 @@
+//! Copying files while rewriting them, in one pass: line endings converted, placeholders
+//! filled in, or any byte-level rewrite a migration tool needs, instead of copying a tree
+//! and rewriting it in a second pass.
+//!
+//! A [`Transform`] sees each file as [`CHUNK`]-sized pieces and may keep state between
+//! them in its [`Transform::State`], so a pattern split over two chunks is still found and
+//! memory stays at a chunk or two per worker however large the file. [`copy_with`] copies
+//! one file through a transform and [`copy_many_with`] many, in parallel with the `rayon`
+//! feature. Destinations are created afresh, so they get default permissions and none of
+//! the source's timestamps or extended attributes.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::buffers;
+
+/// Bytes read from a source at a time.
+pub const CHUNK: usize = 64 * 1024;
+
+/// Rewrites the bytes of a file on their way from source to destination. One value serves
+/// every file and worker; what it needs to remember within a file goes in `State`.
+pub trait Transform: Sync {
+    type State: Default;
+
+    /// Transforms `input`, the next piece of `path`, appending the result to `output`.
+    fn chunk(&self, state: &mut Self::State, path: &Path, input: &[u8], output: &mut Vec<u8>);
+
+    /// Appends whatever `state` still holds once `path` has been read.
+    fn finish(&self, state: &mut Self::State, path: &Path, output: &mut Vec<u8>) {
+        let _ = (state, path, output);
+    }
+}
+
+/// Copies bytes unchanged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl Transform for Identity {
+    type State = ();
+
+    fn chunk(&self, _: &mut (), _: &Path, input: &[u8], output: &mut Vec<u8>) {
+        output.extend_from_slice(input);
+    }
+}
+
+/// A closure applied to every chunk on its own, for rewrites that can't span chunks, such
+/// as mapping single bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct EachChunk<F>(pub F);
+
+impl<F> Transform for EachChunk<F>
+where
+    F: Fn(&Path, &[u8], &mut Vec<u8>) + Sync,
+{
+    type State = ();
+
+    fn chunk(&self, _: &mut (), path: &Path, input: &[u8], output: &mut Vec<u8>) {
+        (self.0)(path, input, output)
+    }
+}
+
+/// Converts line endings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEndings {
+    /// `\r\n` to `\n`; lone `\r`s are kept.
+    Lf,
+    /// `\n` to `\r\n`, leaving existing `\r\n`s alone.
+    Crlf,
+}
+
+impl Transform for LineEndings {
+    /// Whether the previous chunk ended with `\r`.
+    type State = bool;
+
+    fn chunk(&self, after_cr: &mut bool, _: &Path, input: &[u8], output: &mut Vec<u8>) {
+        for &byte in input {
+            match (self, byte) {
+                // Held back until it is known whether a `\n` follows.
+                (LineEndings::Lf, b'\r') => {
+                    if *after_cr {
+                        output.push(b'\r');
+                    }
+                }
+                (LineEndings::Lf, _) => {
+                    if *after_cr && byte != b'\n' {
+                        output.push(b'\r');
+                    }
+                    output.push(byte);
+                }
+                (LineEndings::Crlf, b'\n') => {
+                    if !*after_cr {
+                        output.push(b'\r');
+                    }
+                    output.push(b'\n');
+                }
+                (LineEndings::Crlf, _) => output.push(byte),
+            }
+            *after_cr = byte == b'\r';
+        }
+    }
+
+    fn finish(&self, after_cr: &mut bool, _: &Path, output: &mut Vec<u8>) {
+        if *self == LineEndings::Lf && *after_cr {
+            output.push(b'\r');
+        }
+    }
+}
+
+/// Replaces every occurrence of each pattern with its replacement, such as `{{name}}` with
+/// a value when filling in templates. Where patterns overlap, the first listed wins.
+#[derive(Debug, Clone, Default)]
+pub struct Replace {
+    pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    longest: usize,
+}
+
+impl Replace {
+    pub fn new(pairs: impl IntoIterator<Item = (impl Into<Vec<u8>>, impl Into<Vec<u8>>)>) -> Replace {
+        let pairs: Vec<(Vec<u8>, Vec<u8>)> = pairs.into_iter().map(|(from, to)| (from.into(), to.into())).filter(|(from, _)| !from.is_empty()).collect();
+        let longest = pairs.iter().map(|(from, _)| from.len()).max().unwrap_or(0);
+        Replace { pairs, longest }
+    }
+
+    /// Replaces in `carry` and moves the result to `output`, keeping back a tail that could
+    /// still be the start of a pattern unless `at_end`.
+    fn drain(&self, carry: &mut Vec<u8>, output: &mut Vec<u8>, at_end: bool) {
+        let mut i = 0;
+        while i < carry.len() {
+            let rest = &carry[i..];
+            if let Some((from, to)) = self.pairs.iter().find(|(from, _)| rest.starts_with(from)) {
+                output.extend_from_slice(to);
+                i += from.len();
+            } else if !at_end && rest.len() < self.longest && self.pairs.iter().any(|(from, _)| from.starts_with(rest)) {
+                break;
+            } else {
+                output.push(carry[i]);
+                i += 1;
+            }
+        }
+        carry.drain(..i);
+    }
+}
+
+impl Transform for Replace {
+    /// Bytes of the previous chunks that may begin a pattern.
+    type State = Vec<u8>;
+
+    fn chunk(&self, carry: &mut Vec<u8>, _: &Path, input: &[u8], output: &mut Vec<u8>) {
+        carry.extend_from_slice(input);
+        self.drain(carry, output, false);
+    }
+
+    fn finish(&self, carry: &mut Vec<u8>, _: &Path, output: &mut Vec<u8>) {
+        self.drain(carry, output, true);
+    }
+}
+
+fn with_path<T>(path: &Path, result: io::Result<T>) -> io::Result<T> {
+    result.map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+/// Copies `from` to `to` through `transform`, creating or truncating `to`, and returns the
+/// bytes written. Errors name the file they happened on.
+pub fn copy_with<T: Transform>(from: &Path, to: &Path, transform: &T) -> io::Result<u64> {
+    let mut source = with_path(from, File::open(from))?;
+    let mut destination = BufWriter::with_capacity(CHUNK, with_path(to, File::create(to))?);
+    let mut state = T::State::default();
+    let mut output = Vec::with_capacity(CHUNK);
+    let mut written = 0;
+    buffers::with_buffer(|input| -> io::Result<()> {
+        input.resize(CHUNK, 0);
+        loop {
+            let read = with_path(from, source.read(input))?;
+            output.clear();
+            if read == 0 {
+                transform.finish(&mut state, from, &mut output);
+            } else {
+                transform.chunk(&mut state, from, &input[..read], &mut output);
+            }
+            with_path(to, destination.write_all(&output))?;
+            written += output.len() as u64;
+            if read == 0 {
+                return Ok(());
+            }
+        }
+    })?;
+    with_path(to, destination.flush())?;
+    Ok(written)
+}
+
+/// [`copy_with`] for every `(from, to)` pair, returning the bytes written in all. The
+/// destinations' directories must exist.
+pub fn copy_many_with<P, Q, T>(pairs: &[(P, Q)], transform: &T) -> io::Result<u64>
+where
+    P: AsRef<Path> + Sync,
+    Q: AsRef<Path> + Sync,
+    T: Transform,
+{
+    let copy = |(from, to): &(P, Q)| copy_with(from.as_ref(), to.as_ref(), transform);
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        pairs.par_iter().map(copy).try_reduce(|| 0, |a, b| Ok(a + b))
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        pairs.iter().map(copy).sum()
+    }
+}
@@ -0,0 +1,138 @@
+//! Recoverable bulk deletes: instead of unlinking, [`Trash::delete_many`] renames every file
+//! into a staging directory on the same filesystem, and the caller then commits (the files
+//! are removed for good) or rolls back (each goes back where it was). Unlinking ten
+//! thousand files in parallel can't be undone when the list came from the wrong glob; a
+//! rename only moves a directory entry, so staging costs about as much as the delete.
+//!
+//! Each call stages into a batch directory of its own inside the trash, the files named
+//! by their position in the list so names from different directories can't collide. A
+//! [`Staged`] batch that is dropped without a decision stays in the trash, so nothing is
+//! lost to a crash in between; remove the trash directory to empty it.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::contents;
+use crate::retry::RetryPolicy;
+use crate::rundir;
+
+/// A staging directory deleted files are moved to.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    dir: PathBuf,
+}
+
+impl Trash {
+    /// The trash at `dir`, created if missing. It must be on the filesystem of the files
+    /// deleted through it, since renames can't cross filesystems.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Trash> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Trash { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Moves every file in `paths` into a new batch in the trash, in parallel with the
+    /// `rayon` feature, retrying each rename on transient errors as `policy` says. If any
+    /// file can't be moved, the ones that were are put back and the error returned, so
+    /// either the whole list is staged or none of it. A file on another filesystem fails
+    /// with [`io::ErrorKind::CrossesDevices`].
+    pub fn delete_many<P>(&self, paths: &[P], policy: &RetryPolicy) -> io::Result<Staged>
+    where
+        P: AsRef<Path> + Sync,
+    {
+        static BATCHES: AtomicUsize = AtomicUsize::new(0);
+        let batch = self.dir.join(format!("{}-{}", rundir::run_id(), BATCHES.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir(&batch)?;
+        let indexed: Vec<(usize, &Path)> = paths.iter().map(AsRef::as_ref).enumerate().collect();
+        // Every rename reports back, so a failure still knows which files moved.
+        let outcomes = contents::each_retried(&indexed, &RetryPolicy::never(), |(_, path)| *path, |&(index, path)| Ok(policy.run(|| fs::rename(path, staged_path(&batch, index)))))?;
+        let mut staged = Staged { batch, files: Vec::with_capacity(paths.len()) };
+        let mut failure = None;
+        for ((index, path), outcome) in indexed.into_iter().zip(outcomes) {
+            match outcome {
+                Ok(()) => staged.files.push((path.to_path_buf(), staged_path(&staged.batch, index))),
+                Err(e) if failure.is_none() => {
+                    let message = match e.kind() {
+                        io::ErrorKind::CrossesDevices => format!("{}: not on the trash's filesystem ({}): {}", path.display(), self.dir.display(), e),
+                        _ => format!("{}: {}", path.display(), e),
+                    };
+                    failure = Some(io::Error::new(e.kind(), message));
+                }
+                Err(_) => {}
+            }
+        }
+        match failure {
+            None => Ok(staged),
+            Some(e) => {
+                staged.rollback()?;
+                Err(e)
+            }
+        }
+    }
+}
+
+fn staged_path(batch: &Path, index: usize) -> PathBuf {
+    batch.join(index.to_string())
+}
+
+/// Files moved to the trash by one [`Trash::delete_many`], waiting for a decision.
+#[derive(Debug)]
+#[must_use = "a staged delete stays in the trash until committed or rolled back"]
+pub struct Staged {
+    batch: PathBuf,
+    /// Where each file was, and where it is now.
+    files: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Staged {
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Where the staged files were.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(original, _)| original.as_path())
+    }
+
+    /// The batch's directory in the trash.
+    pub fn dir(&self) -> &Path {
+        &self.batch
+    }
+
+    /// Deletes the staged files for good.
+    pub fn commit(self) -> io::Result<()> {
+        fs::remove_dir_all(&self.batch)
+    }
+
+    /// Moves every staged file back where it was, then removes the empty batch. A file
+    /// whose old path has been taken again in the meantime isn't overwritten: it stays in
+    /// the trash and the rollback fails with [`io::ErrorKind::AlreadyExists`] naming it,
+    /// after putting back all the others.
+    pub fn rollback(self) -> io::Result<()> {
+        let mut blocked = Vec::new();
+        for (original, staged) in &self.files {
+            if fs::symlink_metadata(original).is_ok() {
+                blocked.push(original.display().to_string());
+                continue;
+            }
+            fs::rename(staged, original)?;
+        }
+        if !blocked.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} files left in {} because their paths exist again: {}", blocked.len(), self.batch.display(), blocked.join(", ")),
+            ));
+        }
+        fs::remove_dir(&self.batch)
+    }
+}
@@ -0,0 +1,193 @@
+//! Treemaps of where time went, by directory or by file-size bucket, rendered as HTML.
+
+use std::fmt::Write as _;
+use std::path::{Component, Path};
+
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+    pub name: String,
+    /// The node's own value plus its children's.
+    pub value: f64,
+    pub children: Vec<Tree>,
+}
+
+impl Tree {
+    fn child(&mut self, name: &str) -> &mut Tree {
+        let i = match self.children.iter().position(|c| c.name == name) {
+            Some(i) => i,
+            None => {
+                self.children.push(Tree { name: name.to_string(), ..Tree::default() });
+                self.children.len() - 1
+            }
+        };
+        &mut self.children[i]
+    }
+
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| b.value.total_cmp(&a.value));
+        self.children.iter_mut().for_each(Tree::sort);
+    }
+
+    /// Sums `value` per directory below `root`. Files are folded into their directory, which
+    /// keeps trees of many small files readable; time spent directly in a directory that also
+    /// has subdirectories shows as a `(files)` child.
+    pub fn by_directory<'a>(root: &Path, entries: impl IntoIterator<Item = (&'a Path, f64)>) -> Tree {
+        let mut tree = Tree { name: root.display().to_string(), ..Tree::default() };
+        for (path, value) in entries {
+            let dir = path.parent().unwrap_or(path);
+            let relative = dir.strip_prefix(root).unwrap_or(dir);
+            let mut node = &mut tree;
+            node.value += value;
+            for component in relative.components() {
+                if let Component::Normal(name) = component {
+                    node = node.child(&name.to_string_lossy());
+                    node.value += value;
+                }
+            }
+            node.child("(files)").value += value;
+        }
+        fn collapse(tree: &mut Tree) {
+            if tree.children.len() == 1 && tree.children[0].name == "(files)" {
+                tree.children.clear();
+            }
+            tree.children.iter_mut().for_each(collapse);
+        }
+        collapse(&mut tree);
+        tree.sort();
+        tree
+    }
+
+    /// Sums `value` per power-of-four file-size bucket.
+    pub fn by_size(name: &str, entries: impl IntoIterator<Item = (u64, f64)>) -> Tree {
+        let mut tree = Tree { name: name.to_string(), ..Tree::default() };
+        for (size, value) in entries {
+            let mut upper = 1024u64;
+            while upper <= size && upper < 1 << 40 {
+                upper *= 4;
+            }
+            let bucket = if upper == 1024 { "< 1K".to_string() } else { format!("{}–{}", human(upper / 4), human(upper)) };
+            tree.value += value;
+            tree.child(&bucket).value += value;
+        }
+        tree.sort();
+        tree
+    }
+}
+
+fn human(bytes: u64) -> String {
+    const UNITS: [(&str, u64); 4] = [("T", 1 << 40), ("G", 1 << 30), ("M", 1 << 20), ("K", 1 << 10)];
+    UNITS.iter().find(|(_, unit)| bytes >= *unit).map_or(format!("{}B", bytes), |(suffix, unit)| format!("{}{}", bytes / unit, suffix))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+}
+
+/// Worst aspect ratio of a row of `areas` laid along a side of length `side`.
+fn worst(areas: &[f64], side: f64) -> f64 {
+    let sum: f64 = areas.iter().sum();
+    let (min, max) = areas.iter().fold((f64::MAX, 0.0f64), |(lo, hi), &a| (lo.min(a), hi.max(a)));
+    let s2 = sum * sum;
+    let side2 = side * side;
+    (side2 * max / s2).max(s2 / (side2 * min))
+}
+
+/// Squarified layout (Bruls, Huizing and van Wijk) of `values`, sorted largest first, in `rect`.
+fn squarify(values: &[f64], rect: Rect) -> Vec<Rect> {
+    let total: f64 = values.iter().sum();
+    if total <= 0.0 || rect.w <= 0.0 || rect.h <= 0.0 {
+        return values.iter().map(|_| Rect { w: 0.0, h: 0.0, ..rect }).collect();
+    }
+    let scale = rect.w * rect.h / total;
+    let areas: Vec<f64> = values.iter().map(|v| v * scale).collect();
+    let mut rects = Vec::with_capacity(areas.len());
+    let mut free = rect;
+    let mut start = 0;
+    while start < areas.len() {
+        let side = free.w.min(free.h);
+        let mut end = start + 1;
+        while end < areas.len() && worst(&areas[start..=end], side) <= worst(&areas[start..end], side) {
+            end += 1;
+        }
+        let row = &areas[start..end];
+        let row_sum: f64 = row.iter().sum();
+        if free.w >= free.h {
+            let width = row_sum / free.h;
+            let mut y = free.y;
+            for area in row {
+                let h = area / width;
+                rects.push(Rect { x: free.x, y, w: width, h });
+                y += h;
+            }
+            free = Rect { x: free.x + width, w: free.w - width, ..free };
+        } else {
+            let height = row_sum / free.w;
+            let mut x = free.x;
+            for area in row {
+                let w = area / height;
+                rects.push(Rect { x, y: free.y, w, h: height });
+                x += w;
+            }
+            free = Rect { y: free.y + height, h: free.h - height, ..free };
+        }
+        start = end;
+    }
+    rects
+}
+
+const HEADER: f64 = 16.0;
+const COLORS: [&str; 6] = ["#4a7ebb", "#6a9f58", "#d08a3c", "#a45aa4", "#c65555", "#4aa3a3"];
+
+/// `text` with the characters HTML gives meaning to replaced by entities, safe inside an
+/// element or a quoted attribute.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn render_node(out: &mut String, tree: &Tree, rect: Rect, total: f64, depth: usize, unit: &str) {
+    if rect.w < 1.0 || rect.h < 1.0 {
+        return;
+    }
+    let share = if total > 0.0 { tree.value / total * 100.0 } else { 0.0 };
+    let _ = write!(
+        out,
+        "<div class=\"tm\" style=\"left:{:.1}px;top:{:.1}px;width:{:.1}px;height:{:.1}px;background:{}\" title=\"{}: {:.2} {} ({:.1}%)\">",
+        rect.x,
+        rect.y,
+        rect.w,
+        rect.h,
+        COLORS[depth % COLORS.len()],
+        escape(&tree.name),
+        tree.value,
+        unit,
+        share
+    );
+    if rect.h >= HEADER && rect.w >= 30.0 {
+        let _ = write!(out, "<span>{} {:.0}%</span>", escape(&tree.name), share);
+    }
+    let children: Vec<&Tree> = tree.children.iter().filter(|c| c.value > 0.0).collect();
+    if !children.is_empty() && rect.h > HEADER + 4.0 && rect.w > 8.0 {
+        let inner = Rect { x: 2.0, y: HEADER, w: rect.w - 4.0, h: rect.h - HEADER - 2.0 };
+        let values: Vec<f64> = children.iter().map(|c| c.value).collect();
+        for (child, child_rect) in children.iter().zip(squarify(&values, inner)) {
+            render_node(out, child, child_rect, total, depth + 1, unit);
+        }
+    }
+    out.push_str("</div>");
+}
+
+/// Styles the `render` output needs; include once per page.
+pub const STYLE: &str = ".tm{position:absolute;box-sizing:border-box;border:1px solid #fff;overflow:hidden;color:#fff;font:11px sans-serif}\
+.tm span{display:block;padding:1px 3px;white-space:nowrap}.treemap{position:relative}";
+
+/// An HTML fragment of `tree` laid out in a `width` by `height` pixel box; `unit` labels values.
+pub fn render(tree: &Tree, width: f64, height: f64, unit: &str) -> String {
+    let mut out = format!("<div class=\"treemap\" style=\"width:{:.0}px;height:{:.0}px\">", width, height);
+    render_node(&mut out, tree, Rect { x: 0.0, y: 0.0, w: width, h: height }, tree.value, 0, unit);
+    out.push_str("</div>");
+    out
+}
@@ -0,0 +1,258 @@
+//! `--tui`: a live dashboard on stderr while a benchmark runs.
+//!
+//! The dashboard takes over the terminal's alternate screen. Standard output is captured to
+//! an unlinked temporary file meanwhile, shown in the dashboard's output pane, and written
+//! out again once the dashboard closes.
+//!
+//! It is drawn with plain ANSI escapes rather than ratatui, which with its terminal
+//! backend would be the binary's largest dependency for one screen of text. What ratatui
+//! would bring is done here directly: each frame is fitted to the terminal's current size,
+//! lines are overwritten in place instead of clearing the screen so nothing flickers, and
+//! the thread list gives way to the output pane when rows run short.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ::io::log;
+use ::io::progress::Progress;
+
+const REFRESH: Duration = Duration::from_millis(250);
+const OUTPUT_LINES: usize = 8;
+const BAR_WIDTH: usize = 30;
+
+/// Standard output redirected into a temporary file.
+struct Capture {
+    saved: OwnedFd,
+    file: File,
+}
+
+impl Capture {
+    fn start() -> io::Result<Capture> {
+        io::stdout().flush()?;
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.subsec_nanos());
+        let path = env::temp_dir().join(format!("io-tui-{}-{:08x}.log", std::process::id(), nanos));
+        // Never one that already exists, and gone from the directory once open.
+        let file = File::options().read(true).write(true).create_new(true).mode(0o600).open(&path)?;
+        fs::remove_file(&path)?;
+        let saved = unsafe { libc::dup(libc::STDOUT_FILENO) };
+        if saved < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let saved = unsafe { OwnedFd::from_raw_fd(saved) };
+        if unsafe { libc::dup2(file.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Capture { saved, file })
+    }
+
+    /// The last `n` lines written so far.
+    fn tail(&self, n: usize) -> Vec<String> {
+        // Standard output writes through the same open file; reading at an offset leaves
+        // its position alone.
+        let mut bytes = vec![0; self.file.metadata().map_or(0, |metadata| metadata.len() as usize)];
+        let read = self.file.read_at(&mut bytes, 0).unwrap_or(0);
+        let text = String::from_utf8_lossy(&bytes[..read]);
+        let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+        lines[lines.len().saturating_sub(n)..].iter().map(|l| l.to_string()).collect()
+    }
+
+    /// Restores standard output and replays everything captured.
+    fn finish(self) -> io::Result<()> {
+        io::stdout().flush()?;
+        if unsafe { libc::dup2(self.saved.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(0))?;
+        let mut captured = Vec::new();
+        file.read_to_end(&mut captured)?;
+        io::stdout().write_all(&captured)?;
+        io::stdout().flush()
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct IoCounters {
+    read: u64,
+    written: u64,
+}
+
+fn io_counters() -> IoCounters {
+    let text = fs::read_to_string("/proc/self/io").unwrap_or_default();
+    let field = |name: &str| {
+        text.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(": ")?.trim().parse().ok())
+            .unwrap_or(0)
+    };
+    IoCounters { read: field("rchar"), written: field("wchar") }
+}
+
+fn open_fds() -> usize {
+    fs::read_dir("/proc/self/fd").map_or(0, |entries| entries.count())
+}
+
+struct ThreadSample {
+    name: String,
+    state: char,
+    ticks: u64,
+}
+
+fn threads() -> HashMap<u32, ThreadSample> {
+    let mut threads = HashMap::new();
+    let Ok(entries) = fs::read_dir("/proc/self/task") else {
+        return threads;
+    };
+    for entry in entries.flatten() {
+        let Some(tid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        let Some((head, rest)) = stat.rsplit_once(')') else {
+            continue;
+        };
+        let name = head.split_once('(').map_or("", |(_, name)| name).to_string();
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        let ticks = |i: usize| fields.get(i).and_then(|f| f.parse::<u64>().ok()).unwrap_or(0);
+        let state = fields.first().and_then(|f| f.chars().next()).unwrap_or('?');
+        threads.insert(tid, ThreadSample { name, state, ticks: ticks(11) + ticks(12) });
+    }
+    threads
+}
+
+fn bar(fraction: f64, width: usize) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    format!("{}{}", "#".repeat(filled), "-".repeat(width - filled))
+}
+
+fn mb_per_sec(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 / elapsed.as_secs_f64().max(1e-9) / (1024.0 * 1024.0)
+}
+
+/// The terminal's columns and rows, or 80 by 24 when stderr can't say.
+fn terminal_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size) } != 0 || size.ws_col == 0 || size.ws_row == 0 {
+        return (80, 24);
+    }
+    (size.ws_col as usize, size.ws_row as usize)
+}
+
+/// `lines` cut to the terminal's width and height, each overwriting the last frame's line
+/// in place, with whatever that frame had below cleared.
+fn frame(lines: &[String], (width, height): (usize, usize)) -> String {
+    let mut screen = String::from("\x1b[H");
+    for line in lines.iter().take(height) {
+        screen.extend(line.chars().take(width));
+        screen.push_str("\x1b[K\n");
+    }
+    screen.push_str("\x1b[J");
+    screen
+}
+
+fn run(progress: Arc<Progress>, capture: Arc<Capture>, stop: Arc<AtomicBool>) {
+    let started = Instant::now();
+    let tick = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+    let mut last = (Instant::now(), progress.done(), io_counters(), threads());
+    let mut stderr = io::stderr();
+    let _ = write!(stderr, "\x1b[?1049h\x1b[?25l\x1b[2J");
+    while !stop.load(Ordering::Relaxed) {
+        thread::park_timeout(REFRESH);
+        let (now, done, counters, samples) = (Instant::now(), progress.done(), io_counters(), threads());
+        let interval = now - last.0;
+        let total = progress.total();
+        let size = terminal_size();
+        let mut lines = vec![
+            format!("io bench — {:<40} elapsed {:.1} s", progress.stage(), started.elapsed().as_secs_f64()),
+            String::new(),
+            format!("Files    [{}] {}/{} ({:.0}%)", bar(done as f64 / total.max(1) as f64, BAR_WIDTH), done, total, done as f64 * 100.0 / total.max(1) as f64),
+            format!("Ops/s    {:.0}", done.saturating_sub(last.1) as f64 / interval.as_secs_f64().max(1e-9)),
+            format!(
+                "MB/s     read {:.1}, write {:.1}",
+                mb_per_sec(counters.read.saturating_sub(last.2.read), interval),
+                mb_per_sec(counters.written.saturating_sub(last.2.written), interval)
+            ),
+            format!("Open FDs {}", open_fds()),
+            String::new(),
+            "Threads".to_string(),
+        ];
+        let output = capture.tail(OUTPUT_LINES);
+        // The output pane keeps its rows; threads get what is left.
+        let rows = size.1.saturating_sub(lines.len() + 2 + output.len());
+        let mut tids: Vec<&u32> = samples.keys().collect();
+        tids.sort();
+        let shown = if tids.len() > rows { rows.saturating_sub(1) } else { tids.len() };
+        for tid in &tids[..shown] {
+            let sample = &samples[*tid];
+            let before = last.3.get(*tid).map_or(sample.ticks, |s| s.ticks);
+            let cpu = sample.ticks.saturating_sub(before) as f64 / tick / interval.as_secs_f64().max(1e-9);
+            lines.push(format!("  {:>7} {:<16} {} [{}] {:>3.0}%", tid, sample.name, sample.state, bar(cpu, 20), cpu * 100.0));
+        }
+        if shown < tids.len() {
+            lines.push(format!("  ... {} more", tids.len() - shown));
+        }
+        lines.push(String::new());
+        lines.push("Output".to_string());
+        lines.extend(output.into_iter().map(|line| format!("  {}", line)));
+        let _ = stderr.write_all(frame(&lines, size).as_bytes());
+        let _ = stderr.flush();
+        last = (now, done, counters, samples);
+    }
+    let _ = write!(stderr, "\x1b[?25h\x1b[?1049l");
+    let _ = stderr.flush();
+}
+
+/// The running dashboard; closing it (or dropping it) restores the terminal and output.
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    capture: Option<Arc<Capture>>,
+}
+
+impl Dashboard {
+    /// Starts the dashboard, or returns `None` when stderr is not a terminal.
+    pub fn start(progress: Arc<Progress>) -> io::Result<Option<Dashboard>> {
+        if !io::stderr().is_terminal() {
+            log::warn("tui", "--tui needs a terminal on stderr; continuing without it");
+            return Ok(None);
+        }
+        let capture = Arc::new(Capture::start()?);
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let (capture, stop) = (capture.clone(), stop.clone());
+            thread::Builder::new().name("io-tui".to_string()).spawn(move || run(progress, capture, stop))?
+        };
+        Ok(Some(Dashboard { stop, handle: Some(handle), capture: Some(capture) }))
+    }
+
+    pub fn close(mut self) -> io::Result<()> {
+        self.shut_down()
+    }
+
+    fn shut_down(&mut self) -> io::Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+        match self.capture.take().and_then(Arc::into_inner) {
+            Some(capture) => capture.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = self.shut_down();
+    }
+}
@@ -0,0 +1,72 @@
+//! The update phase on its own, once per [`UpdateMethod`]: truncating and rewriting,
+//! positioned writes over the old content, and copying into a mapping with or without
+//! `msync`. The strategies each pick one; this times them side by side on the same files.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::bench::{self, Failure, Options, UpdateMethod};
+use crate::cache;
+
+#[derive(Debug, Clone)]
+pub struct MethodResult {
+    pub method: UpdateMethod,
+    /// Updating every file.
+    pub update: Duration,
+    /// Of the update, the time workers spent waiting on `msync` and `fsync`, summed over them.
+    pub writeback: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UpdateResult {
+    /// The workload's bytes, which every method rewrites.
+    pub updated_bytes: u64,
+    pub methods: Vec<MethodResult>,
+    pub failures: Vec<Failure>,
+}
+
+/// For each of `methods`, creates the configured workload in `dir_path` untimed, then times
+/// updating every file with that method, evicting the files from the page cache in between
+/// when `options.cold_read` is set. With `options.verify` every file is read back after the
+/// update and must hold its updated content. Each method's files are removed before the next.
+pub fn run(dir_path: &Path, methods: &[UpdateMethod], options: &Options) -> io::Result<UpdateResult> {
+    let files = options.workload.files;
+    let mut result = UpdateResult { updated_bytes: files as u64 * options.workload.size() as u64, ..UpdateResult::default() };
+    options.failures.take("");
+    for &method in methods {
+        let dir = dir_path.join(method.name().replace('+', "-"));
+        fs::create_dir_all(&dir)?;
+        let paths = bench::file_paths(&dir, files);
+
+        bench::phase(options, format!("Update {} create", method), format!("update/{}/create", method), "create", &mut result.failures, || {
+            bench::each_indexed(&paths, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+        })?;
+
+        if options.cold_read {
+            cache::evict_from_cache(&paths)?;
+        }
+        options.writeback.take();
+        let start = Instant::now();
+        bench::phase(options, format!("Update {}", method), format!("update/{}", method), "update", &mut result.failures, || bench::update_files(&paths, options, method))?;
+        let update = start.elapsed();
+        let writeback = options.writeback.take();
+
+        if options.verify {
+            bench::phase(options, format!("Update {} verify", method), format!("update/{}/verify", method), "verify", &mut result.failures, || {
+                bench::each_indexed(&paths, options, |index, path| {
+                    let bytes = fs::read(path)?;
+                    match bench::with_content(options, index, true, |content| bytes == content) {
+                        true => Ok(()),
+                        false => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} does not hold its updated contents", path.display()))),
+                    }
+                })
+            })?;
+        }
+
+        result.methods.push(MethodResult { method, update, writeback });
+        fs::remove_dir_all(&dir)?;
+    }
+    Ok(result)
+}
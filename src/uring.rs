@@ -0,0 +1,425 @@
+//! A minimal io_uring ring over the raw syscalls, for strategies that batch many small
+//! operations into one `io_uring_enter`.
+//!
+//! Only what the benchmarks need is covered: one ring per thread without SQPOLL, entries
+//! built by the [`Entry`] constructors, and completions reaped in whatever order the kernel
+//! finishes them. [`Ring::new`] fails on kernels or sandboxes without io_uring, which
+//! callers report instead of falling back silently.
+//!
+//! A ring can also hold registered files and buffers ([`Ring::register_files`],
+//! [`Ring::register_buffers`]). Entries then name a file by its slot in the table
+//! ([`Entry::fixed_file`]) and read into a pinned buffer ([`Entry::read_fixed`]), which
+//! spares the kernel looking up the descriptor and pinning the pages on every operation.
+//!
+//! Entries can be linked ([`Entry::link`]) so the next one starts only once this one
+//! succeeded, and an open can put its file straight into a table slot ([`Entry::direct`],
+//! Linux 5.15), so a whole open, write and close chain goes in one submission without the
+//! descriptor ever reaching user space.
+
+use std::ffi::CStr;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x800_0000;
+const IORING_OFF_SQES: libc::off_t = 0x1000_0000;
+const IORING_FEAT_SINGLE_MMAP: u32 = 1;
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_OP_FSYNC: u8 = 3;
+const IORING_OP_READ_FIXED: u8 = 4;
+const IORING_OP_OPENAT: u8 = 18;
+const IORING_OP_CLOSE: u8 = 19;
+const IORING_OP_STATX: u8 = 21;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+const IORING_OP_UNLINKAT: u8 = 36;
+const IORING_FSYNC_DATASYNC: u32 = 1;
+const IOSQE_FIXED_FILE: u8 = 1;
+const IOSQE_IO_LINK: u8 = 4;
+const IORING_REGISTER_BUFFERS: u32 = 0;
+const IORING_REGISTER_FILES: u32 = 2;
+const IORING_REGISTER_FILES_UPDATE: u32 = 6;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+/// `struct io_uring_params`.
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqOffsets,
+    cq_off: CqOffsets,
+}
+
+/// One submission, laid out as the kernel's `struct io_uring_sqe`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Entry {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+impl Entry {
+    /// `statx(dirfd, path, flags, mask, buf)`.
+    pub fn statx(dirfd: RawFd, path: &CStr, flags: i32, mask: u32, buf: *mut libc::statx) -> Entry {
+        Entry {
+            opcode: IORING_OP_STATX,
+            fd: dirfd,
+            addr: path.as_ptr() as u64,
+            len: mask,
+            off: buf as u64,
+            op_flags: flags as u32,
+            ..Entry::default()
+        }
+    }
+
+    /// `pread(fd, buf, len, offset)`.
+    pub fn read(fd: RawFd, buf: *mut u8, len: u32, offset: u64) -> Entry {
+        Entry {
+            opcode: IORING_OP_READ,
+            fd,
+            addr: buf as u64,
+            len,
+            off: offset,
+            ..Entry::default()
+        }
+    }
+
+    /// `pread` into registered buffer `index`; `buf..buf + len` must lie inside it.
+    pub fn read_fixed(fd: RawFd, buf: *mut u8, len: u32, offset: u64, index: u16) -> Entry {
+        Entry {
+            opcode: IORING_OP_READ_FIXED,
+            buf_index: index,
+            ..Entry::read(fd, buf, len, offset)
+        }
+    }
+
+    /// `pwrite(fd, buf, len, offset)`.
+    pub fn write(fd: RawFd, buf: *const u8, len: u32, offset: u64) -> Entry {
+        Entry { opcode: IORING_OP_WRITE, ..Entry::read(fd, buf.cast_mut(), len, offset) }
+    }
+
+    /// `openat(dirfd, path, flags, mode)`.
+    pub fn openat(dirfd: RawFd, path: &CStr, flags: i32, mode: u32) -> Entry {
+        Entry {
+            opcode: IORING_OP_OPENAT,
+            fd: dirfd,
+            addr: path.as_ptr() as u64,
+            len: mode,
+            op_flags: flags as u32,
+            ..Entry::default()
+        }
+    }
+
+    /// `unlinkat(dirfd, path, flags)`. Needs Linux 5.11.
+    pub fn unlinkat(dirfd: RawFd, path: &CStr, flags: i32) -> Entry {
+        Entry {
+            opcode: IORING_OP_UNLINKAT,
+            fd: dirfd,
+            addr: path.as_ptr() as u64,
+            op_flags: flags as u32,
+            ..Entry::default()
+        }
+    }
+
+    /// `fsync(fd)`, or `fdatasync(fd)` with `data_only`.
+    pub fn fsync(fd: RawFd, data_only: bool) -> Entry {
+        Entry {
+            opcode: IORING_OP_FSYNC,
+            fd,
+            op_flags: if data_only { IORING_FSYNC_DATASYNC } else { 0 },
+            ..Entry::default()
+        }
+    }
+
+    /// `close(fd)`; with [`direct`](Entry::direct), empties that table slot instead.
+    pub fn close(fd: RawFd) -> Entry {
+        Entry { opcode: IORING_OP_CLOSE, fd, ..Entry::default() }
+    }
+
+    /// Makes an [`openat`](Entry::openat) install its file in registered table slot `slot`
+    /// instead of returning a descriptor, or a [`close`](Entry::close) (of descriptor 0)
+    /// empty that slot. Needs Linux 5.15, and an open without `O_CLOEXEC`.
+    pub fn direct(self, slot: u32) -> Entry {
+        Entry { splice_fd_in: (slot + 1) as i32, ..self }
+    }
+
+    /// Makes the entry's descriptor a slot of the registered file table instead.
+    pub fn fixed_file(self, slot: u32) -> Entry {
+        Entry { fd: slot as i32, flags: self.flags | IOSQE_FIXED_FILE, ..self }
+    }
+
+    /// Starts the next entry pushed only once this one has succeeded; if it fails, or a
+    /// read or write comes up short, the rest of the chain completes with `ECANCELED`.
+    pub fn link(self) -> Entry {
+        Entry { flags: self.flags | IOSQE_IO_LINK, ..self }
+    }
+
+    /// Tags the entry so its [`Completion`] can be matched to it.
+    pub fn user_data(self, user_data: u64) -> Entry {
+        Entry { user_data, ..self }
+    }
+}
+
+/// One finished operation, laid out as the kernel's `struct io_uring_cqe`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Completion {
+    pub user_data: u64,
+    /// What the syscall would have returned, or `-errno`.
+    pub res: i32,
+    pub flags: u32,
+}
+
+impl Completion {
+    pub fn result(&self) -> io::Result<u32> {
+        if self.res < 0 {
+            Err(io::Error::from_raw_os_error(-self.res))
+        } else {
+            Ok(self.res as u32)
+        }
+    }
+}
+
+struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: RawFd, len: usize, offset: libc::off_t) -> io::Result<Mapping> {
+        let ptr = unsafe { libc::mmap(ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED | libc::MAP_POPULATE, fd, offset) };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping { ptr, len })
+    }
+
+    /// The value at `offset` bytes into the mapping.
+    fn at<T>(&self, offset: u32) -> *mut T {
+        unsafe { self.ptr.cast::<u8>().add(offset as usize).cast() }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+/// Why io_uring can't be used here, or `None` when a ring can be set up. Containers often
+/// refuse it with a seccomp filter, and `kernel.io_uring_disabled` can turn it off.
+pub fn unavailable() -> Option<io::Error> {
+    let e = Ring::new(2).err()?;
+    let why = match e.raw_os_error() {
+        Some(libc::ENOSYS) => "the kernel has no io_uring (or a seccomp filter hides it)",
+        Some(libc::EPERM) => "io_uring is disabled for this process (seccomp or kernel.io_uring_disabled)",
+        _ => "io_uring setup failed",
+    };
+    Some(io::Error::new(io::ErrorKind::Unsupported, format!("{}: {}", why, e)))
+}
+
+/// A submission and completion queue pair.
+pub struct Ring {
+    // Only held to keep the rings mapped; unmapped before the descriptor is closed.
+    _sq_ring: Mapping,
+    _cq_ring: Option<Mapping>,
+    sqes: Mapping,
+    fd: OwnedFd,
+    sq_head: *const AtomicU32,
+    sq_tail: *const AtomicU32,
+    sq_mask: u32,
+    sq_entries: u32,
+    sq_array: *mut u32,
+    cq_head: *const AtomicU32,
+    cq_tail: *const AtomicU32,
+    cq_mask: u32,
+    cqes: *const Completion,
+    /// Entries pushed but not yet handed to the kernel.
+    unsubmitted: u32,
+}
+
+// The ring's memory is only reached through `&mut self` or atomics.
+unsafe impl Send for Ring {}
+
+impl Ring {
+    /// A ring with room for `entries` submissions in flight, rounded up to a power of two.
+    pub fn new(entries: u32) -> io::Result<Ring> {
+        let mut params = Params::default();
+        let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, entries, &mut params as *mut Params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+        let mut sq_len = params.sq_off.array as usize + params.sq_entries as usize * size_of::<u32>();
+        let mut cq_len = params.cq_off.cqes as usize + params.cq_entries as usize * size_of::<Completion>();
+        let single_mmap = params.features & IORING_FEAT_SINGLE_MMAP != 0;
+        if single_mmap {
+            sq_len = sq_len.max(cq_len);
+            cq_len = sq_len;
+        }
+        let sq_ring = Mapping::new(fd.as_raw_fd(), sq_len, IORING_OFF_SQ_RING)?;
+        let cq_ring = if single_mmap { None } else { Some(Mapping::new(fd.as_raw_fd(), cq_len, IORING_OFF_CQ_RING)?) };
+        let sqes = Mapping::new(fd.as_raw_fd(), params.sq_entries as usize * size_of::<Entry>(), IORING_OFF_SQES)?;
+        let cq = cq_ring.as_ref().unwrap_or(&sq_ring);
+        let (sq, cq_off) = (&params.sq_off, &params.cq_off);
+        Ok(Ring {
+            sq_head: sq_ring.at(sq.head),
+            sq_tail: sq_ring.at(sq.tail),
+            sq_mask: unsafe { *sq_ring.at::<u32>(sq.ring_mask) },
+            sq_entries: params.sq_entries,
+            sq_array: sq_ring.at(sq.array),
+            cq_head: cq.at(cq_off.head),
+            cq_tail: cq.at(cq_off.tail),
+            cq_mask: unsafe { *cq.at::<u32>(cq_off.ring_mask) },
+            cqes: cq.at(cq_off.cqes),
+            unsubmitted: 0,
+            _sq_ring: sq_ring,
+            _cq_ring: cq_ring,
+            sqes,
+            fd,
+        })
+    }
+
+    fn register(&self, opcode: u32, arg: *const libc::c_void, count: u32) -> io::Result<()> {
+        let result = unsafe { libc::syscall(libc::SYS_io_uring_register, self.fd.as_raw_fd(), opcode, arg, count) };
+        if result < 0 { Err(io::Error::last_os_error()) } else { Ok(()) }
+    }
+
+    /// Registers `fds` as the ring's file table, slot `i` holding `fds[i]`. Slots may be
+    /// `-1` and filled later with [`update_files`](Ring::update_files); that needs Linux 5.5.
+    pub fn register_files(&mut self, fds: &[RawFd]) -> io::Result<()> {
+        self.register(IORING_REGISTER_FILES, fds.as_ptr().cast(), fds.len() as u32)
+    }
+
+    /// Replaces the registered files from slot `offset` on with `fds`; `-1` empties a slot.
+    /// The ring holds its own reference, so the descriptors may be closed afterwards.
+    pub fn update_files(&mut self, offset: u32, fds: &[RawFd]) -> io::Result<()> {
+        // `struct io_uring_files_update`.
+        #[repr(C)]
+        struct FilesUpdate {
+            offset: u32,
+            resv: u32,
+            fds: u64,
+        }
+        let update = FilesUpdate { offset, resv: 0, fds: fds.as_ptr() as u64 };
+        self.register(IORING_REGISTER_FILES_UPDATE, (&update as *const FilesUpdate).cast(), fds.len() as u32)
+    }
+
+    /// Pins `buffers` for [`Entry::read_fixed`], buffer `i` having index `i`. Pinned memory
+    /// counts against `RLIMIT_MEMLOCK` before Linux 5.12.
+    ///
+    /// # Safety
+    ///
+    /// The buffers must stay allocated until the ring is dropped.
+    pub unsafe fn register_buffers(&mut self, buffers: &mut [Vec<u8>]) -> io::Result<()> {
+        let iovecs: Vec<libc::iovec> = buffers.iter_mut().map(|buffer| libc::iovec { iov_base: buffer.as_mut_ptr().cast(), iov_len: buffer.len() }).collect();
+        self.register(IORING_REGISTER_BUFFERS, iovecs.as_ptr().cast(), iovecs.len() as u32)
+    }
+
+    /// Submissions the ring holds at once.
+    pub fn capacity(&self) -> u32 {
+        self.sq_entries
+    }
+
+    /// Queues `entry` for the next [`submit`](Ring::submit), returning `false` when the
+    /// submission queue is full.
+    ///
+    /// # Safety
+    ///
+    /// Every buffer and path `entry` points to must stay valid and unaliased until its
+    /// completion has been reaped with [`pop`](Ring::pop).
+    pub unsafe fn push(&mut self, entry: &Entry) -> bool {
+        let (head, tail) = unsafe { ((*self.sq_head).load(Ordering::Acquire), (*self.sq_tail).load(Ordering::Relaxed)) };
+        if tail.wrapping_sub(head) == self.sq_entries {
+            return false;
+        }
+        let index = tail & self.sq_mask;
+        unsafe {
+            self.sqes.at::<Entry>(0).add(index as usize).write(*entry);
+            self.sq_array.add(index as usize).write(index);
+            (*self.sq_tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+        self.unsubmitted += 1;
+        true
+    }
+
+    /// Hands the queued entries to the kernel and waits until at least `wait` operations
+    /// have completed, returning how many entries were submitted.
+    pub fn submit(&mut self, wait: u32) -> io::Result<u32> {
+        let flags = if wait > 0 { IORING_ENTER_GETEVENTS } else { 0 };
+        loop {
+            let submitted = unsafe {
+                libc::syscall(libc::SYS_io_uring_enter, self.fd.as_raw_fd(), self.unsubmitted, wait, flags, ptr::null::<libc::sigset_t>(), 0usize)
+            };
+            if submitted >= 0 {
+                self.unsubmitted -= submitted as u32;
+                return Ok(submitted as u32);
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+
+    /// The next completed operation, if any.
+    pub fn pop(&mut self) -> Option<Completion> {
+        let (head, tail) = unsafe { ((*self.cq_head).load(Ordering::Relaxed), (*self.cq_tail).load(Ordering::Acquire)) };
+        if head == tail {
+            return None;
+        }
+        unsafe {
+            let completion = self.cqes.add((head & self.cq_mask) as usize).read();
+            (*self.cq_head).store(head.wrapping_add(1), Ordering::Release);
+            Some(completion)
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! What watching a tree costs while it is rewritten in bulk, the way editors, build tools
+//! and dev servers watch the files a build or checkout touches.
+//!
+//! The workload is spread over a tree of directories, every directory in it is watched the
+//! way a recursive watcher has to on each backend, and the update phase runs once without
+//! and once with the watches. Each file's first close-after-write event is matched to the
+//! moment its update returned, which gives the delivery latency; files with no event by the
+//! end count as dropped, as do the events a queue overflow lost.
+//!
+//! inotify is the Linux backend. FSEvents (macOS) and `ReadDirectoryChangesW` (Windows) are
+//! named so results and flags can refer to them, but need platform bindings this crate
+//! doesn't carry, so [`Watcher::new`] reports them as unsupported.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::bench::{self, Failure, Latency, Options};
+
+/// How long the event reader waits for events before checking whether to stop.
+const POLL: Duration = Duration::from_millis(50);
+
+/// Leaf directories per directory above them.
+const FANOUT: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchBackend {
+    Inotify,
+    FsEvents,
+    ReadDirectoryChanges,
+}
+
+impl WatchBackend {
+    pub const ALL: [WatchBackend; 3] = [WatchBackend::Inotify, WatchBackend::FsEvents, WatchBackend::ReadDirectoryChanges];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WatchBackend::Inotify => "inotify",
+            WatchBackend::FsEvents => "fsevents",
+            WatchBackend::ReadDirectoryChanges => "readdirectorychanges",
+        }
+    }
+
+    /// The backend this platform's watchers use.
+    pub fn native() -> WatchBackend {
+        if cfg!(target_os = "macos") {
+            WatchBackend::FsEvents
+        } else if cfg!(windows) {
+            WatchBackend::ReadDirectoryChanges
+        } else {
+            WatchBackend::Inotify
+        }
+    }
+}
+
+impl fmt::Display for WatchBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for WatchBackend {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<WatchBackend> {
+        WatchBackend::ALL.into_iter().find(|backend| backend.name() == s).ok_or_else(|| {
+            let names: Vec<&str> = WatchBackend::ALL.iter().map(|backend| backend.name()).collect();
+            io::Error::new(io::ErrorKind::InvalidInput, format!("unknown watch backend '{}' (expected {})", s, names.join(", ")))
+        })
+    }
+}
+
+/// What a watcher reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A file, named relative to its directory, was closed after being written.
+    Written(OsString),
+    /// The backend's queue overflowed and events were lost.
+    Overflow,
+}
+
+/// Watches on a set of directories, non-recursive on every backend this crate supports.
+pub struct Watcher {
+    #[cfg(target_os = "linux")]
+    inotify: inotify::Inotify,
+    watches: usize,
+}
+
+impl Watcher {
+    #[cfg(target_os = "linux")]
+    pub fn new(backend: WatchBackend) -> io::Result<Watcher> {
+        match backend {
+            WatchBackend::Inotify => Ok(Watcher { inotify: inotify::Inotify::new()?, watches: 0 }),
+            other => Err(unsupported(other)),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(backend: WatchBackend) -> io::Result<Watcher> {
+        Err(unsupported(backend))
+    }
+
+    /// Watches `root` and every directory under it, returning how many were added.
+    pub fn watch_tree(&mut self, root: &Path) -> io::Result<usize> {
+        let before = self.watches;
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            self.watch(&dir)?;
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    pending.push(entry.path());
+                }
+            }
+        }
+        Ok(self.watches - before)
+    }
+
+    /// The directories watched.
+    pub fn watches(&self) -> usize {
+        self.watches
+    }
+
+    #[cfg(target_os = "linux")]
+    fn watch(&mut self, dir: &Path) -> io::Result<()> {
+        self.inotify.add_watch(dir)?;
+        self.watches += 1;
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn watch(&mut self, _dir: &Path) -> io::Result<()> {
+        unreachable!("no watcher is created on this platform")
+    }
+
+    /// The events that arrive within `timeout`, or none.
+    #[cfg(target_os = "linux")]
+    pub fn events(&mut self, timeout: Duration) -> io::Result<Vec<Event>> {
+        self.inotify.read(timeout)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn events(&mut self, _timeout: Duration) -> io::Result<Vec<Event>> {
+        unreachable!("no watcher is created on this platform")
+    }
+}
+
+fn unsupported(backend: WatchBackend) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, format!("{} watching isn't supported on this platform or by this build", backend))
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::ffi::{CString, OsString};
+    use std::io;
+    use std::mem;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::Event;
+
+    /// Room for many events with names up to `NAME_MAX`.
+    const BUFFER: usize = 64 * 1024;
+
+    pub struct Inotify {
+        fd: OwnedFd,
+    }
+
+    impl Inotify {
+        pub fn new() -> io::Result<Inotify> {
+            let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Inotify { fd: unsafe { OwnedFd::from_raw_fd(fd) } })
+        }
+
+        pub fn add_watch(&self, dir: &Path) -> io::Result<()> {
+            let path = CString::new(dir.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_ONLYDIR;
+            if unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), mask) } < 0 {
+                let e = io::Error::last_os_error();
+                if e.raw_os_error() == Some(libc::ENOSPC) {
+                    return Err(io::Error::new(e.kind(), format!("out of inotify watches at {} (raise fs.inotify.max_user_watches)", dir.display())));
+                }
+                return Err(e);
+            }
+            Ok(())
+        }
+
+        pub fn read(&self, timeout: Duration) -> io::Result<Vec<Event>> {
+            let mut poll = libc::pollfd { fd: self.fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            match unsafe { libc::poll(&mut poll, 1, timeout.as_millis().min(i32::MAX as u128) as i32) } {
+                -1 => {
+                    let e = io::Error::last_os_error();
+                    return if e.kind() == io::ErrorKind::Interrupted { Ok(Vec::new()) } else { Err(e) };
+                }
+                0 => return Ok(Vec::new()),
+                _ => {}
+            }
+            let mut buffer = vec![0u8; BUFFER];
+            let mut events = Vec::new();
+            loop {
+                let read = unsafe { libc::read(self.fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) };
+                if read < 0 {
+                    let e = io::Error::last_os_error();
+                    if e.kind() == io::ErrorKind::WouldBlock {
+                        return Ok(events);
+                    }
+                    return Err(e);
+                }
+                let mut offset = 0;
+                while offset < read as usize {
+                    let header: libc::inotify_event = unsafe { buffer.as_ptr().add(offset).cast::<libc::inotify_event>().read_unaligned() };
+                    let start = offset + mem::size_of::<libc::inotify_event>();
+                    let name = &buffer[start..start + header.len as usize];
+                    let name = &name[..name.iter().position(|&byte| byte == 0).unwrap_or(name.len())];
+                    if header.mask & libc::IN_Q_OVERFLOW != 0 {
+                        events.push(Event::Overflow);
+                    } else if !name.is_empty() {
+                        events.push(Event::Written(OsString::from_vec(name.to_vec())));
+                    }
+                    offset = start + header.len as usize;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WatchConfig {
+    pub backend: WatchBackend,
+    /// Leaf directories the files are spread over.
+    pub dirs: usize,
+    /// How long after the update returns events may still arrive before the files without
+    /// one count as dropped.
+    pub settle: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchResult {
+    pub backend: WatchBackend,
+    /// Directories watched, leaves and the ones above them.
+    pub watches: usize,
+    /// Adding every watch.
+    pub setup: Duration,
+    /// The update phase with nothing watching.
+    pub unwatched: Duration,
+    /// The update phase with every directory watched.
+    pub watched: Duration,
+    pub files: usize,
+    /// Events read, including repeats for the same file.
+    pub events: u64,
+    /// Files with at least one event.
+    pub delivered: usize,
+    /// Times the backend's queue overflowed.
+    pub overflows: u64,
+    /// From each file's update returning to its first event, in seconds.
+    pub latency: Latency,
+    pub failures: Vec<Failure>,
+}
+
+impl WatchResult {
+    /// The share of files whose update no event reported.
+    pub fn drop_rate(&self) -> f64 {
+        if self.files == 0 {
+            return 0.0;
+        }
+        (self.files - self.delivered) as f64 / self.files as f64
+    }
+}
+
+/// `dirs` leaf directories under `root`, grouped [`FANOUT`] to a parent so the tree is two
+/// levels deep.
+pub fn leaf_dirs(root: &Path, dirs: usize) -> Vec<PathBuf> {
+    (0..dirs.max(1)).map(|leaf| root.join(format!("d_{}", leaf / FANOUT)).join(format!("d_{}", leaf))).collect()
+}
+
+/// The file number in a name from [`bench::striped_paths`].
+fn file_index(name: &OsString) -> Option<usize> {
+    name.to_str()?.strip_prefix("file_")?.strip_suffix(".txt")?.parse().ok()
+}
+
+/// Creates the configured workload in a tree under `dir_path`, times the update phase
+/// without watches, then watches every directory in the tree and times the update phase
+/// again while a thread reads the events.
+pub fn run(dir_path: &Path, config: WatchConfig, options: &Options) -> io::Result<WatchResult> {
+    let mut watcher = Watcher::new(config.backend)?;
+    let files = options.workload.files;
+    let leaves = leaf_dirs(dir_path, config.dirs);
+    leaves.iter().try_for_each(fs::create_dir_all)?;
+    let paths = bench::striped_paths(&leaves, files);
+    let mut failures = Vec::new();
+    options.failures.take("");
+
+    bench::phase(options, "Watch create", "watch/create", "create", &mut failures, || {
+        bench::each_indexed(&paths, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+    })?;
+
+    let update = |stamps: Option<(&Instant, &[AtomicU64])>| {
+        bench::each_indexed(&paths, options, |index, path| {
+            bench::with_content(options, index, true, |content| fs::write(path, content))?;
+            if let Some((base, stamps)) = stamps {
+                stamps[index].store(base.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            }
+            Ok(())
+        })
+    };
+    let start = Instant::now();
+    bench::phase(options, "Watch update unwatched", "watch/unwatched", "unwatched", &mut failures, || update(None))?;
+    let unwatched = start.elapsed();
+
+    let start = Instant::now();
+    let watches = watcher.watch_tree(dir_path)?;
+    let setup = start.elapsed();
+
+    let base = Instant::now();
+    let stamps: Vec<AtomicU64> = (0..files).map(|_| AtomicU64::new(u64::MAX)).collect();
+    let updated = AtomicBool::new(false);
+    let (watched, seen) = bench::phase(options, "Watch update watched", "watch/watched", "watched", &mut failures, || {
+        thread::scope(|scope| {
+            let reader = scope.spawn(|| -> io::Result<(Vec<Option<Duration>>, u64, u64)> {
+                let mut seen = vec![None; files];
+                let (mut events, mut overflows, mut delivered) = (0, 0, 0);
+                let mut deadline = None;
+                while deadline.is_none_or(|deadline| Instant::now() < deadline) && delivered < files {
+                    for event in watcher.events(POLL)? {
+                        events += 1;
+                        match event {
+                            Event::Overflow => overflows += 1,
+                            Event::Written(name) => {
+                                if let Some(index) = file_index(&name).filter(|&index| index < files)
+                                    && seen[index].is_none()
+                                {
+                                    seen[index] = Some(base.elapsed());
+                                    delivered += 1;
+                                }
+                            }
+                        }
+                    }
+                    if deadline.is_none() && updated.load(Ordering::Acquire) {
+                        deadline = Some(Instant::now() + config.settle);
+                    }
+                }
+                Ok((seen, events, overflows))
+            });
+            let start = Instant::now();
+            let result = update(Some((&base, &stamps)));
+            let watched = start.elapsed();
+            updated.store(true, Ordering::Release);
+            let seen = reader.join().unwrap_or_else(|_| Err(io::Error::other("the event reader panicked")));
+            result.map(|()| (watched, seen))
+        })
+    })?;
+    let (seen, events, overflows) = seen?;
+
+    let mut latency = Latency::default();
+    let mut delivered = 0;
+    for (stamp, seen) in stamps.iter().zip(&seen) {
+        let stamp = stamp.load(Ordering::Relaxed);
+        if let Some(seen) = seen
+            && stamp != u64::MAX
+        {
+            // The event can beat the stamp: the file is closed before its update returns.
+            let delay = seen.saturating_sub(Duration::from_nanos(stamp)).as_secs_f64();
+            latency.running.add(delay);
+            latency.digest.add(delay);
+            delivered += 1;
+        }
+    }
+    Ok(WatchResult { backend: config.backend, watches, setup, unwatched, watched, files, events, delivered, overflows, latency, failures })
+}
@@ -0,0 +1,232 @@
+//! Hang detection: on a flaky network filesystem one `open` or `read` can block for
+//! minutes, and with it a rayon worker and, at the end of the phase, the whole run, with
+//! nothing printed. A [`Watchdog`] watches every file operation and phase from a thread of
+//! its own and names the path that is stuck.
+//!
+//! An operation over [`Timeouts::op`] is reported once while it is still running and, if
+//! it returns, recorded as a failure of its phase. With [`OnTimeout::Skip`] the other
+//! workers carry on; with [`OnTimeout::Abort`], or when a phase runs past
+//! [`Timeouts::phase`], the run's [`Token`] is cancelled so it stops issuing files and
+//! reports what finished. A syscall stuck in the kernel can't be interrupted, so if operations are
+//! still stuck a grace period after an abort, the watchdog gives up: it calls the handler
+//! given to [`Watchdog::start`] once, from its own thread, since the thread that would
+//! return an error is the one stuck. The `io` binary removes the run directories there and
+//! exits with status 124, as `timeout(1)` does.
+//!
+//! [`Token`]: crate::cancel::Token
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::human;
+use crate::log::{self, Level};
+
+/// How often the watchdog looks at most; shorter timeouts are checked four times as often.
+const MAX_TICK: Duration = Duration::from_millis(250);
+
+/// What happens when an operation runs past its timeout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnTimeout {
+    /// Report it and let the other workers carry on.
+    #[default]
+    Skip,
+    /// Cancel the run.
+    Abort,
+}
+
+impl std::str::FromStr for OnTimeout {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<OnTimeout> {
+        match s {
+            "skip" => Ok(OnTimeout::Skip),
+            "abort" => Ok(OnTimeout::Abort),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown timeout action '{}', expected skip|abort", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Longest one file's operation may take.
+    pub op: Option<Duration>,
+    /// Longest one phase may take; past it the run is always cancelled.
+    pub phase: Option<Duration>,
+    pub on_timeout: OnTimeout,
+}
+
+#[derive(Debug)]
+struct Op {
+    path: PathBuf,
+    scope: Arc<str>,
+    started: Instant,
+    reported: bool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    ops: HashMap<u64, Op>,
+    /// Phases running, several at once when targets run in parallel.
+    phases: HashMap<u64, Op>,
+    aborted: Option<Instant>,
+    gave_up: bool,
+}
+
+/// Watches the operations and phases of a run; see the module docs.
+pub struct Watchdog {
+    timeouts: Timeouts,
+    state: Mutex<State>,
+    /// Numbers operations and phases alike.
+    next_id: AtomicU64,
+    timed_out: AtomicUsize,
+    cancel: cancel::Token,
+    give_up: Box<dyn Fn() + Send + Sync>,
+}
+
+impl std::fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watchdog").field("timeouts", &self.timeouts).field("state", &self.state).field("timed_out", &self.timed_out).finish_non_exhaustive()
+    }
+}
+
+impl Watchdog {
+    /// Starts the watchdog's thread, which ends once the returned handle and its clones are
+    /// dropped. An abort cancels `cancel`, and `give_up` is called once if operations are
+    /// still stuck a grace period after; see the module docs.
+    pub fn start(timeouts: Timeouts, cancel: cancel::Token, give_up: impl Fn() + Send + Sync + 'static) -> io::Result<Arc<Watchdog>> {
+        let give_up = Box::new(give_up);
+        let watchdog = Arc::new(Watchdog { timeouts, state: Mutex::default(), next_id: AtomicU64::new(0), timed_out: AtomicUsize::new(0), cancel, give_up });
+        let shortest = [timeouts.op, timeouts.phase].into_iter().flatten().min().unwrap_or(MAX_TICK);
+        let tick = (shortest / 4).clamp(Duration::from_millis(1), MAX_TICK);
+        let weak = Arc::downgrade(&watchdog);
+        thread::Builder::new().name("io-watchdog".to_string()).spawn(move || watch(weak, tick))?;
+        Ok(watchdog)
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// Operations that ran past [`Timeouts::op`] so far, whether or not they returned.
+    pub fn timed_out(&self) -> usize {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+
+    /// Whether the watchdog gave up on operations stuck after the run was cancelled.
+    pub fn gave_up(&self) -> bool {
+        self.lock().gave_up
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Watches the phase `scope` until the returned guard drops.
+    pub fn phase(&self, scope: &Arc<str>) -> PhaseWatch<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let phase = Op { path: PathBuf::new(), scope: scope.clone(), started: Instant::now(), reported: false };
+        self.lock().phases.insert(id, phase);
+        PhaseWatch { watchdog: self, id }
+    }
+
+    /// Watches the operation on `path` until [`OpWatch::finish`] or the guard drops.
+    pub fn op(&self, scope: &Arc<str>, path: &Path) -> OpWatch<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let op = Op { path: path.to_path_buf(), scope: scope.clone(), started: Instant::now(), reported: false };
+        self.lock().ops.insert(id, op);
+        OpWatch { watchdog: self, id }
+    }
+
+    /// Looks for operations and a phase over their timeouts, reporting each once.
+    fn check(&self) {
+        let mut state = self.lock();
+        let mut abort = false;
+        if let Some(limit) = self.timeouts.op {
+            for op in state.ops.values_mut().filter(|op| !op.reported && op.started.elapsed() > limit) {
+                op.reported = true;
+                self.timed_out.fetch_add(1, Ordering::Relaxed);
+                let fields = [("path", op.path.display().to_string()), ("scope", op.scope.to_string())];
+                log::event(Level::Warn, "watchdog", &format!("operation stuck for over {}", human::duration(limit)), &fields);
+                abort |= self.timeouts.on_timeout == OnTimeout::Abort;
+            }
+        }
+        if let Some(limit) = self.timeouts.phase {
+            for phase in state.phases.values_mut().filter(|phase| !phase.reported && phase.started.elapsed() > limit) {
+                phase.reported = true;
+                log::event(Level::Warn, "watchdog", &format!("phase still running after {}", human::duration(limit)), &[("scope", phase.scope.to_string())]);
+                abort = true;
+            }
+        }
+        if abort && state.aborted.is_none() {
+            log::warn("watchdog", "cancelling the run");
+            state.aborted = Some(Instant::now());
+            self.cancel.cancel();
+        }
+        let grace = [self.timeouts.op, self.timeouts.phase].into_iter().flatten().min().unwrap_or(MAX_TICK);
+        if !state.gave_up && state.aborted.is_some_and(|aborted| aborted.elapsed() > grace) && !state.ops.is_empty() {
+            for op in state.ops.values() {
+                let fields = [("path", op.path.display().to_string()), ("scope", op.scope.to_string())];
+                log::event(Level::Error, "watchdog", &format!("giving up on an operation after {}", human::duration(op.started.elapsed())), &fields);
+            }
+            state.gave_up = true;
+            drop(state);
+            (self.give_up)();
+        }
+    }
+}
+
+fn watch(watchdog: Weak<Watchdog>, tick: Duration) {
+    loop {
+        thread::sleep(tick);
+        match watchdog.upgrade() {
+            Some(watchdog) => watchdog.check(),
+            None => return,
+        }
+    }
+}
+
+/// A phase being watched; see [`Watchdog::phase`].
+#[derive(Debug)]
+pub struct PhaseWatch<'a> {
+    watchdog: &'a Watchdog,
+    id: u64,
+}
+
+impl Drop for PhaseWatch<'_> {
+    fn drop(&mut self) {
+        self.watchdog.lock().phases.remove(&self.id);
+    }
+}
+
+/// An operation being watched; see [`Watchdog::op`].
+#[derive(Debug)]
+pub struct OpWatch<'a> {
+    watchdog: &'a Watchdog,
+    id: u64,
+}
+
+impl OpWatch<'_> {
+    /// Stops watching, returning how long the operation took if that was over its timeout.
+    pub fn finish(self) -> Option<Duration> {
+        let op = self.watchdog.lock().ops.remove(&self.id)?;
+        let elapsed = op.started.elapsed();
+        let late = self.watchdog.timeouts.op.is_some_and(|limit| elapsed > limit);
+        if late && !op.reported {
+            // Finished between two checks; count it all the same.
+            self.watchdog.timed_out.fetch_add(1, Ordering::Relaxed);
+        }
+        late.then_some(elapsed)
+    }
+}
+
+impl Drop for OpWatch<'_> {
+    fn drop(&mut self) {
+        self.watchdog.lock().ops.remove(&self.id);
+    }
+}
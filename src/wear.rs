@@ -0,0 +1,57 @@
+//! Estimated flash wear, for comparing strategies by how long they leave an SD card or
+//! eMMC device alive rather than only by speed.
+//!
+//! Cheap flash media map storage in erase blocks (allocation units) of a few MiB rather
+//! than in pages: a write that doesn't fill whole erase blocks makes the controller read,
+//! erase and reprogram the rest of the block it lands in. [`Flash::amplification`] models
+//! that worst case, in which every write starts its own erase block, so writes sized and
+//! aligned to erase blocks cost 1× and small writes cost up to a whole block each.
+
+/// Erase block assumed by the flash preset; most SD cards and eMMC use 4 MiB.
+pub const DEFAULT_ERASE_BLOCK: usize = 4 * 1024 * 1024;
+
+/// Geometry of the flash device being estimated for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flash {
+    pub erase_block: usize,
+}
+
+impl Default for Flash {
+    fn default() -> Self {
+        Flash { erase_block: DEFAULT_ERASE_BLOCK }
+    }
+}
+
+impl Flash {
+    /// Bytes the device programs for one write of `len` bytes starting on an erase-block
+    /// boundary: whole blocks for the data, plus the rest of a partly written last block.
+    pub fn programmed(&self, len: u64) -> u64 {
+        let block = self.erase_block.max(1) as u64;
+        len.div_ceil(block) * block
+    }
+
+    /// Programmed bytes per written byte for writes of `len` bytes; 1 for empty writes.
+    pub fn amplification(&self, len: u64) -> f64 {
+        if len == 0 { 1.0 } else { self.programmed(len) as f64 / len as f64 }
+    }
+
+    /// The wear of writing `written` bytes in writes of `write_len` bytes each.
+    pub fn estimate(&self, written: u64, write_len: u64) -> Wear {
+        Wear { written, amplification: self.amplification(write_len) }
+    }
+}
+
+/// Bytes written and how much the device multiplies them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wear {
+    pub written: u64,
+    pub amplification: f64,
+}
+
+impl Wear {
+    /// Bytes the device programs, `written × amplification`: what its endurance rating
+    /// (total bytes written) is spent on.
+    pub fn programmed(&self) -> f64 {
+        self.written as f64 * self.amplification
+    }
+}
@@ -0,0 +1,430 @@
+//! Workload files: named file populations and a DAG of phases over them.
+//!
+//! ```json
+//! {
+//!   "populations": {
+//!     "small": { "files": 10000, "size": 100 },
+//!     "large": { "files": 4, "size": 67108864, "persist": true },
+//!     "repo": { "discover": "/home/me/src/project" }
+//!   },
+//!   "phases": [
+//!     { "name": "make-small", "op": "create", "population": "small" },
+//!     { "name": "make-large", "op": "create", "population": "large", "strategy": "vectored" },
+//!     { "name": "scan", "op": "stat", "population": "small", "after": ["make-small"] },
+//!     { "name": "rewrite-large", "op": "update", "population": "large", "strategy": "smart", "after": ["make-large"] },
+//!     { "name": "cleanup", "op": "delete", "population": "small", "after": ["scan"] },
+//!     { "name": "read-repo", "op": "read", "population": "repo" }
+//!   ]
+//! }
+//! ```
+//!
+//! Phases whose dependencies have finished run concurrently, each spreading its files over
+//! the rayon pool. `op` is one of `create`, `read`, `update`, `delete` or `stat` (metadata
+//! only); `strategy` picks the implementation from [`STRATEGIES`] and defaults to traditional.
+//! A population created by `pack` is one pack file, so its other phases must use `pack` too.
+//!
+//! `persist` keeps a generated population in the population [`Store`] after the run, and
+//! later runs skip its create phases once a sample of its files matches the manifest. `discover` uses the files already under a directory;
+//! such populations can only be read or stat'ed.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::bench::{self, Failure, Options, Phase, STRATEGIES, Strategy, Workload};
+use crate::json::{self, Value};
+use crate::population::{Manifest, Source, Store, valid_name};
+use crate::trace::OpId;
+
+#[derive(Debug, Clone)]
+pub struct Population {
+    pub name: String,
+    pub kind: PopulationKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PopulationKind {
+    /// Written by a create phase. Persisted populations live in the [`Store`] and are kept
+    /// between runs; the rest live under the run's scratch directory.
+    Generated { files: usize, size: usize, persist: bool },
+    /// An existing tree, only read or stat'ed.
+    Discovered(PathBuf),
+}
+
+/// How many files of a reused population are checked against its manifest before a run.
+pub const VERIFY_SAMPLE: usize = 64;
+
+/// A population's files for one run.
+struct Resolved {
+    dir: PathBuf,
+    paths: Vec<PathBuf>,
+    /// File sizes, parallel to `paths`.
+    sizes: Vec<u64>,
+    workload: Workload,
+    /// A persisted population whose manifest matches, so its create phases are skipped.
+    reused: bool,
+}
+
+impl Population {
+    fn resolve(&self, root: &Path, store: &Store) -> io::Result<Resolved> {
+        match &self.kind {
+            &PopulationKind::Generated { files, size, persist } => {
+                let dir = if persist { store.population_dir(&self.name)? } else { root.join(&self.name) };
+                let workload = Workload::new(files, size);
+                let stored = if persist { store.load(&self.name)? } else { None };
+                match stored.filter(|m| m.source == Source::Generated { files, size } && m.dir == dir) {
+                    Some(manifest) => {
+                        let mismatches = manifest.verify(VERIFY_SAMPLE);
+                        if let Some(first) = mismatches.first() {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!(
+                                    "population {} no longer matches its manifest ({} of the sampled files, e.g. {}: {}); remove it to regenerate",
+                                    self.name,
+                                    mismatches.len(),
+                                    first.path.display(),
+                                    first.problem
+                                ),
+                            ));
+                        }
+                        let sizes = manifest.entries.iter().map(|e| e.size).collect();
+                        Ok(Resolved { paths: manifest.paths(), sizes, dir, workload, reused: true })
+                    }
+                    None => Ok(Resolved { paths: bench::file_paths(&dir, files), sizes: vec![size as u64; files], dir, workload, reused: false }),
+                }
+            }
+            PopulationKind::Discovered(dir) => {
+                // A tree that changed since it was listed is listed again rather than rejected.
+                let manifest = match store.load(&self.name)? {
+                    Some(manifest) if manifest.source == Source::Discovered(dir.clone()) && manifest.verify(VERIFY_SAMPLE).is_empty() => manifest,
+                    _ => {
+                        let manifest = Manifest::discover(&self.name, dir)?;
+                        store.save(&manifest)?;
+                        manifest
+                    }
+                };
+                let paths = manifest.paths();
+                let sizes = manifest.entries.iter().map(|e| e.size).collect();
+                Ok(Resolved { workload: Workload::new(paths.len(), 0), paths, sizes, dir: dir.clone(), reused: true })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Phase(Phase),
+    /// `stat` every file without opening it.
+    Stat,
+}
+
+impl Op {
+    pub fn name(self) -> &'static str {
+        match self {
+            Op::Phase(phase) => phase.name(),
+            Op::Stat => "stat",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: String,
+    pub op: Op,
+    /// Index into [`Config::populations`].
+    pub population: usize,
+    pub strategy: &'static Strategy,
+    /// Indices into [`Config::nodes`] that must finish first.
+    pub after: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub populations: Vec<Population>,
+    pub nodes: Vec<Node>,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Finds a strategy by label (`smart`) or name (`smart_io`), ignoring case.
+pub fn strategy(name: &str) -> Option<&'static Strategy> {
+    STRATEGIES.iter().find(|s| s.label.eq_ignore_ascii_case(name) || s.name.split(' ').next() == Some(name))
+}
+
+fn parse_op(op: &str) -> Option<Op> {
+    match op {
+        "stat" => Some(Op::Stat),
+        _ => Phase::ALL.into_iter().find(|p| p.name() == op).map(Op::Phase),
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> io::Result<Config> {
+        let text = fs::read_to_string(path)?;
+        let value = json::parse(&text).map_err(|e| invalid(format!("{}: {}", path.display(), e)))?;
+        Config::from_json(&value).map_err(|e| invalid(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn from_json(value: &Value) -> Result<Config, String> {
+        let Some(Value::Object(entries)) = value.get("populations") else {
+            return Err("\"populations\" must be an object".to_string());
+        };
+        let mut populations = Vec::new();
+        for (name, population) in entries {
+            if !valid_name(name) {
+                return Err(format!("population name {:?} must be a single file name", name));
+            }
+            let field = |key: &str| {
+                population.get(key).and_then(Value::as_u64).map(|n| n as usize).ok_or_else(|| format!("population {} needs a numeric \"{}\"", name, key))
+            };
+            let kind = match population.get("discover") {
+                Some(dir) => PopulationKind::Discovered(PathBuf::from(dir.as_str().ok_or_else(|| format!("population {}: \"discover\" must be a path", name))?)),
+                None => PopulationKind::Generated {
+                    files: field("files")?,
+                    size: field("size")?,
+                    persist: population.get("persist") == Some(&Value::Bool(true)),
+                },
+            };
+            populations.push(Population { name: name.clone(), kind });
+        }
+
+        let phases = value.get("phases").and_then(Value::as_array).ok_or("\"phases\" must be an array")?;
+        let names: Vec<&str> = phases.iter().map(|p| p.get("name").and_then(Value::as_str).unwrap_or("")).collect();
+        let mut nodes = Vec::new();
+        for (i, phase) in phases.iter().enumerate() {
+            let name = names[i];
+            if name.is_empty() || names[..i].contains(&name) {
+                return Err(format!("phase {} needs a unique \"name\"", i));
+            }
+            let text = |key: &str| phase.get(key).and_then(Value::as_str);
+            let op = text("op").and_then(parse_op).ok_or_else(|| format!("phase {} has an unknown or missing \"op\"", name))?;
+            let population = text("population")
+                .and_then(|p| populations.iter().position(|pop| pop.name == p))
+                .ok_or_else(|| format!("phase {} names an unknown or missing \"population\"", name))?;
+            if matches!(populations[population].kind, PopulationKind::Discovered(_)) && !matches!(op, Op::Phase(Phase::Read) | Op::Stat) {
+                return Err(format!("phase {} would modify discovered population {}", name, populations[population].name));
+            }
+            let strategy = match text("strategy") {
+                Some(s) => strategy(s).ok_or_else(|| format!("phase {} names an unknown strategy {}", name, s))?,
+                None => &STRATEGIES[0],
+            };
+            let after = match phase.get("after") {
+                None => Vec::new(),
+                Some(after) => after
+                    .as_array()
+                    .ok_or_else(|| format!("phase {}: \"after\" must be an array", name))?
+                    .iter()
+                    .map(|dep| {
+                        let dep = dep.as_str().unwrap_or("");
+                        names.iter().position(|n| *n == dep).ok_or_else(|| format!("phase {} depends on unknown phase {}", name, dep))
+                    })
+                    .collect::<Result<_, _>>()?,
+            };
+            nodes.push(Node { name: name.to_string(), op, population, strategy, after });
+        }
+        let config = Config { populations, nodes };
+        config.order()?;
+        Ok(config)
+    }
+
+    /// A topological order of the nodes, or the name of one caught in a cycle.
+    fn order(&self) -> Result<Vec<usize>, String> {
+        let mut remaining: Vec<usize> = self.nodes.iter().map(|n| n.after.len()).collect();
+        let mut ready: Vec<usize> = (0..self.nodes.len()).filter(|&i| remaining[i] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(i) = ready.pop() {
+            order.push(i);
+            for (j, node) in self.nodes.iter().enumerate() {
+                for _ in node.after.iter().filter(|&&dep| dep == i) {
+                    remaining[j] -= 1;
+                    if remaining[j] == 0 {
+                        ready.push(j);
+                    }
+                }
+            }
+        }
+        match (0..self.nodes.len()).find(|&i| remaining[i] > 0) {
+            Some(i) => Err(format!("phase {} is part of a dependency cycle", self.nodes[i].name)),
+            None => Ok(order),
+        }
+    }
+}
+
+/// When a node ran, relative to the start of the whole workload.
+#[derive(Debug, Clone)]
+pub struct NodeTiming {
+    /// A create phase skipped because its persisted population was reused.
+    pub reused: bool,
+    pub start: Duration,
+    pub elapsed: Duration,
+    pub failures: Vec<Failure>,
+    /// Each file's time, when [`Options::file_times`] is set.
+    pub files: Vec<FileTiming>,
+}
+
+/// How long one file of a node took.
+#[derive(Debug, Clone)]
+pub struct FileTiming {
+    pub op: OpId,
+    pub path: PathBuf,
+    pub size: u64,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Indexed like [`Config::nodes`].
+    pub timings: Vec<NodeTiming>,
+    pub total: Duration,
+    /// The chain of dependent nodes with the largest summed time, first to last.
+    pub critical_path: Vec<usize>,
+    /// Where each population's files were, indexed like [`Config::populations`].
+    pub population_dirs: Vec<PathBuf>,
+}
+
+struct NodeOutput {
+    failures: Vec<Failure>,
+    files: Vec<FileTiming>,
+}
+
+fn run_node(node: &Node, population: &Population, resolved: &Resolved, store: &Store, options: &Options) -> io::Result<NodeOutput> {
+    let options = options.with_workload(resolved.workload.clone());
+    options.progress.set_stage(node.name.as_str());
+    options.op_scope.set(node.name.as_str());
+    let paths = &resolved.paths;
+    let persist = matches!(population.kind, PopulationKind::Generated { persist: true, .. });
+    match node.op {
+        Op::Phase(Phase::Create) => {
+            fs::create_dir_all(&resolved.dir)?;
+            node.strategy.run_phase(Phase::Create, paths, &options)?;
+            if let PopulationKind::Generated { files, size, persist: true } = population.kind {
+                store.save(&Manifest::generated(&population.name, &resolved.dir, paths, files, size)?)?;
+            }
+        }
+        Op::Phase(Phase::Delete) => {
+            node.strategy.run_phase(Phase::Delete, paths, &options)?;
+            if persist {
+                store.remove(&population.name)?;
+            }
+        }
+        Op::Phase(Phase::Update) => {
+            node.strategy.run_phase(Phase::Update, paths, &options)?;
+            if persist && let Some(mut manifest) = store.load(&population.name)? {
+                manifest.refresh()?;
+                store.save(&manifest)?;
+            }
+        }
+        Op::Phase(phase) => node.strategy.run_phase(phase, paths, &options)?,
+        Op::Stat => bench::each_file(paths, &options, |path| fs::metadata(path).map(drop))?,
+    }
+    let files = match &options.file_times {
+        Some(times) => {
+            let sizes: HashMap<&Path, u64> = resolved.paths.iter().map(PathBuf::as_path).zip(resolved.sizes.iter().copied()).collect();
+            times
+                .take()
+                .into_iter()
+                .map(|time| {
+                    let size = sizes.get(time.path.as_path()).copied().unwrap_or(0);
+                    FileTiming { op: time.op, path: time.path, size, elapsed: time.elapsed }
+                })
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    Ok(NodeOutput { failures: options.failures.take(node.op.name()), files })
+}
+
+fn critical_path(config: &Config, timings: &[NodeTiming]) -> Vec<usize> {
+    let order = config.order().unwrap_or_default();
+    let mut finish = vec![Duration::ZERO; config.nodes.len()];
+    let mut via = vec![None; config.nodes.len()];
+    for &i in &order {
+        let before = config.nodes[i].after.iter().copied().max_by_key(|&dep| finish[dep]);
+        finish[i] = timings[i].elapsed + before.map_or(Duration::ZERO, |dep| finish[dep]);
+        via[i] = before;
+    }
+    let mut path = Vec::new();
+    let mut next = (0..config.nodes.len()).max_by_key(|&i| finish[i]);
+    while let Some(i) = next {
+        path.push(i);
+        next = via[i];
+    }
+    path.reverse();
+    path
+}
+
+/// Runs every node once its dependencies have finished, several at a time when independent,
+/// with their files spread over `pool`, or the global pool without one. Scratch populations live under `root`, persisted
+/// and discovered ones are recorded in `store`, and create phases of persisted populations
+/// that are already in the store are skipped. Stops starting nodes after the first error,
+/// waits for the running ones, and returns that error.
+pub fn execute(config: &Config, root: &Path, store: &Store, options: &Options, pool: Option<&rayon::ThreadPool>) -> io::Result<Report> {
+    let resolved: Vec<Resolved> = config.populations.iter().map(|p| p.resolve(root, store)).collect::<io::Result<_>>()?;
+    let started = Instant::now();
+    let mut timings: Vec<Option<NodeTiming>> = vec![None; config.nodes.len()];
+    let mut waiting: Vec<usize> = config.nodes.iter().map(|n| n.after.len()).collect();
+    let mut launched = vec![false; config.nodes.len()];
+    let mut error = None;
+
+    thread::scope(|scope| {
+        let (done, finished) = mpsc::channel();
+        let mut running = 0;
+        loop {
+            if error.is_none() {
+                for (i, node) in config.nodes.iter().enumerate() {
+                    if launched[i] || waiting[i] > 0 {
+                        continue;
+                    }
+                    launched[i] = true;
+                    running += 1;
+                    let done = done.clone();
+                    let (population, resolved) = (&config.populations[node.population], &resolved[node.population]);
+                    if node.op == Op::Phase(Phase::Create) && resolved.reused {
+                        let _ = done.send((i, true, started.elapsed(), Duration::ZERO, Ok(NodeOutput { failures: Vec::new(), files: Vec::new() })));
+                        continue;
+                    }
+                    scope.spawn(move || {
+                        let start = started.elapsed();
+                        let result = match pool {
+                            Some(pool) => pool.install(|| run_node(node, population, resolved, store, options)),
+                            None => run_node(node, population, resolved, store, options),
+                        };
+                        let _ = done.send((i, false, start, started.elapsed() - start, result));
+                    });
+                }
+            }
+            if running == 0 {
+                break;
+            }
+            let Ok((i, reused, start, elapsed, result)) = finished.recv() else {
+                break;
+            };
+            running -= 1;
+            match result {
+                Ok(NodeOutput { failures, files }) => {
+                    timings[i] = Some(NodeTiming { reused, start, elapsed, failures, files });
+                    for (j, node) in config.nodes.iter().enumerate() {
+                        waiting[j] -= node.after.iter().filter(|&&dep| dep == i).count();
+                    }
+                }
+                Err(e) => {
+                    error.get_or_insert(io::Error::new(e.kind(), format!("phase {}: {}", config.nodes[i].name, e)));
+                }
+            }
+        }
+    });
+
+    if let Some(e) = error {
+        return Err(e);
+    }
+    let timings: Vec<NodeTiming> = timings.into_iter().map(|t| t.expect("every node ran")).collect();
+    let critical_path = critical_path(config, &timings);
+    let population_dirs = resolved.into_iter().map(|r| r.dir).collect();
+    Ok(Report { timings, total: started.elapsed(), critical_path, population_dirs })
+}
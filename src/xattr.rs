@@ -0,0 +1,266 @@
+//! Extended attributes, for tools that keep per-file metadata such as cache keys or content
+//! hashes on the file itself rather than in a sidecar file next to it.
+//!
+//! Attributes live in the `user.` namespace on Linux and Android, which regular files on
+//! ext4, XFS, btrfs and f2fs support; tmpfs only since Linux 6.6, and some filesystems (and
+//! `nouser_xattr` mounts) not at all, which shows as [`io::ErrorKind::Unsupported`]. macOS
+//! and iOS take any name.
+//! Other platforms have no attributes through this module.
+
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+#[cfg(feature = "bench")]
+use std::fs;
+#[cfg(feature = "bench")]
+use std::time::{Duration, Instant};
+
+use crate::contents;
+use crate::retry::RetryPolicy;
+#[cfg(feature = "bench")]
+use crate::bench::{self, Failure, Options};
+#[cfg(feature = "bench")]
+use crate::names;
+
+/// The attribute the benchmark stores its metadata in.
+#[cfg(feature = "bench")]
+pub const BENCH_NAME: &str = "user.io.meta";
+
+fn c_string(bytes: &[u8]) -> io::Result<CString> {
+    CString::new(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// `ENOTSUP` as the unsupported error kind, whatever the platform calls it.
+fn last_error() -> io::Error {
+    let e = io::Error::last_os_error();
+    match e.raw_os_error() {
+        Some(libc::ENOTSUP) => io::Error::new(io::ErrorKind::Unsupported, format!("extended attributes aren't supported here: {}", e)),
+        _ => e,
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn setxattr(path: &CString, name: &CString, value: &[u8]) -> libc::c_int {
+    unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0) }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn getxattr(path: &CString, name: &CString, buffer: &mut [u8]) -> isize {
+    unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len()) }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn listxattr(path: &CString, buffer: &mut [u8]) -> isize {
+    unsafe { libc::listxattr(path.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len()) }
+}
+
+#[cfg(target_vendor = "apple")]
+unsafe fn setxattr(path: &CString, name: &CString, value: &[u8]) -> libc::c_int {
+    unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0, 0) }
+}
+
+#[cfg(target_vendor = "apple")]
+unsafe fn getxattr(path: &CString, name: &CString, buffer: &mut [u8]) -> isize {
+    unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len(), 0, 0) }
+}
+
+#[cfg(target_vendor = "apple")]
+unsafe fn listxattr(path: &CString, buffer: &mut [u8]) -> isize {
+    unsafe { libc::listxattr(path.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len(), 0) }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+unsafe fn setxattr(_: &CString, _: &CString, _: &[u8]) -> libc::c_int {
+    unreachable!("checked by the callers")
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+unsafe fn getxattr(_: &CString, _: &CString, _: &mut [u8]) -> isize {
+    unreachable!("checked by the callers")
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+unsafe fn listxattr(_: &CString, _: &mut [u8]) -> isize {
+    unreachable!("checked by the callers")
+}
+
+fn supported() -> io::Result<()> {
+    match cfg!(any(target_os = "linux", target_os = "android", target_vendor = "apple")) {
+        true => Ok(()),
+        false => Err(io::Error::new(io::ErrorKind::Unsupported, "extended attributes need Linux, Android, macOS or iOS")),
+    }
+}
+
+/// Sets attribute `name` of `path` to `value`, creating or replacing it.
+pub fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    supported()?;
+    let (path, name) = (c_string(path.as_os_str().as_bytes())?, c_string(name.as_bytes())?);
+    if unsafe { setxattr(&path, &name, value) } != 0 {
+        return Err(last_error());
+    }
+    Ok(())
+}
+
+/// Attribute `name` of `path`, `None` when the file doesn't have it.
+pub fn get_xattr(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    supported()?;
+    let (path, name) = (c_string(path.as_os_str().as_bytes())?, c_string(name.as_bytes())?);
+    let get = |buffer: &mut [u8]| unsafe { getxattr(&path, &name, buffer) };
+    // Most values fit the first try; a larger one is asked for its size and read again,
+    // unless it grew in between.
+    let mut buffer = vec![0; 256];
+    loop {
+        let read = get(&mut buffer);
+        if read >= 0 {
+            buffer.truncate(read as usize);
+            return Ok(Some(buffer));
+        }
+        let e = io::Error::last_os_error();
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let missing = libc::ENODATA;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let missing = libc::ENOATTR;
+        match e.raw_os_error() {
+            Some(code) if code == missing => return Ok(None),
+            Some(libc::ERANGE) => {
+                let size = get(&mut []);
+                if size < 0 {
+                    return Err(last_error());
+                }
+                buffer.resize(size as usize, 0);
+            }
+            _ => return Err(last_error()),
+        }
+    }
+}
+
+/// The names of every attribute of `path` this process may see.
+pub fn list_xattrs(path: &Path) -> io::Result<Vec<String>> {
+    supported()?;
+    let path = c_string(path.as_os_str().as_bytes())?;
+    loop {
+        let size = unsafe { listxattr(&path, &mut []) };
+        if size < 0 {
+            return Err(last_error());
+        }
+        let mut buffer = vec![0; size as usize];
+        let read = unsafe { listxattr(&path, &mut buffer) };
+        if read >= 0 {
+            buffer.truncate(read as usize);
+            return Ok(buffer.split(|&byte| byte == 0).filter(|name| !name.is_empty()).map(|name| String::from_utf8_lossy(name).into_owned()).collect());
+        }
+        // An attribute added in between makes the list longer than asked for.
+        if io::Error::last_os_error().raw_os_error() != Some(libc::ERANGE) {
+            return Err(last_error());
+        }
+    }
+}
+
+/// Sets attribute `name` of each `(path, value)` pair, in parallel with the `rayon` feature,
+/// retrying each on transient errors as `policy` says. Fails on the first file whose
+/// attribute still can't be set.
+pub fn set_xattr_many<P, B>(files: &[(P, B)], name: &str, policy: &RetryPolicy) -> io::Result<()>
+where
+    P: AsRef<Path> + Sync,
+    B: AsRef<[u8]> + Sync,
+{
+    contents::each_retried(files, policy, |(path, _)| path.as_ref(), |(path, value)| set_xattr(path.as_ref(), name, value.as_ref())).map(drop)
+}
+
+/// Attribute `name` of every file in `paths`, in the same order, `None` for files without
+/// it. Each file is retried on transient errors as `policy` says.
+pub fn get_xattr_many<P>(paths: &[P], name: &str, policy: &RetryPolicy) -> io::Result<Vec<Option<Vec<u8>>>>
+where
+    P: AsRef<Path> + Sync,
+{
+    contents::each_retried(paths, policy, |path| path.as_ref(), |path| get_xattr(path.as_ref(), name))
+}
+
+/// The metadata the benchmark stores for file `index`: its number, repeated to `size` bytes.
+#[cfg(feature = "bench")]
+pub fn metadata(index: usize, size: usize) -> Vec<u8> {
+    format!("{:016x}", index).bytes().cycle().take(size).collect()
+}
+
+/// Where a file's metadata is kept.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaStore {
+    Xattr,
+    /// A `.meta` file next to it.
+    Sidecar,
+}
+
+#[cfg(feature = "bench")]
+impl MetaStore {
+    pub const ALL: [MetaStore; 2] = [MetaStore::Xattr, MetaStore::Sidecar];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MetaStore::Xattr => "xattr",
+            MetaStore::Sidecar => "sidecar",
+        }
+    }
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone)]
+pub struct StoreResult {
+    pub store: MetaStore,
+    /// Storing every file's metadata.
+    pub write: Duration,
+    /// Reading it all back, checking it against what was stored.
+    pub read: Duration,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Default)]
+pub struct XattrResult {
+    /// Bytes of metadata per file.
+    pub value_size: usize,
+    pub stores: Vec<StoreResult>,
+    pub failures: Vec<Failure>,
+}
+
+/// Creates the configured workload in `dir_path` untimed, then for each store times writing
+/// `value_size` bytes of metadata for every file and reading it back. A read that doesn't
+/// return what was written is an error.
+#[cfg(feature = "bench")]
+pub fn run(dir_path: &Path, value_size: usize, options: &Options) -> io::Result<XattrResult> {
+    let paths = options.workload.paths(dir_path);
+    let mut result = XattrResult { value_size, ..XattrResult::default() };
+    options.failures.take("");
+    bench::phase(options, "Xattr create", "xattr/create", "create", &mut result.failures, || {
+        bench::each_indexed(&paths, options, |index, path| bench::with_content(options, index, false, |content| fs::write(path, content)))
+    })?;
+
+    let sidecar = |path: &Path| names::sibling(&path.with_extension(""), "", ".meta");
+    for store in MetaStore::ALL {
+        let start = Instant::now();
+        bench::phase(options, format!("Xattr {} write", store.name()), format!("xattr/{}/write", store.name()), "write", &mut result.failures, || {
+            bench::each_indexed(&paths, options, |index, path| match store {
+                MetaStore::Xattr => set_xattr(path, BENCH_NAME, &metadata(index, value_size)),
+                MetaStore::Sidecar => fs::write(sidecar(path), metadata(index, value_size)),
+            })
+        })?;
+        let write = start.elapsed();
+
+        let start = Instant::now();
+        bench::phase(options, format!("Xattr {} read", store.name()), format!("xattr/{}/read", store.name()), "read", &mut result.failures, || {
+            bench::each_indexed(&paths, options, |index, path| {
+                let value = match store {
+                    MetaStore::Xattr => get_xattr(path, BENCH_NAME)?,
+                    MetaStore::Sidecar => Some(fs::read(sidecar(path))?),
+                };
+                match value == Some(metadata(index, value_size)) {
+                    true => Ok(()),
+                    false => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} metadata of {} differs from what was stored", store.name(), path.display()))),
+                }
+            })
+        })?;
+        let read = start.elapsed();
+        result.stores.push(StoreResult { store, write, read });
+    }
+    Ok(result)
+}
@@ -0,0 +1,58 @@
+//! Zstandard compression through the system's `libzstd`, for the codecs on the write path.
+//! [`crate::bundle`] writes zstd frames without compressing them; this compresses for real.
+//!
+//! Like [`crate::sqlite`], this binds the library directly, so the `zstd` feature needs it
+//! (and a linker that finds it) but nothing from crates.io. Frames are made and read whole,
+//! which suits files that are already in memory.
+
+use std::ffi::{CStr, c_char, c_int, c_uint, c_ulonglong, c_void};
+use std::io;
+
+#[link(name = "zstd")]
+unsafe extern "C" {
+    fn ZSTD_compressBound(size: usize) -> usize;
+    fn ZSTD_compress(dst: *mut c_void, capacity: usize, src: *const c_void, size: usize, level: c_int) -> usize;
+    fn ZSTD_decompress(dst: *mut c_void, capacity: usize, src: *const c_void, size: usize) -> usize;
+    fn ZSTD_findDecompressedSize(src: *const c_void, size: usize) -> c_ulonglong;
+    fn ZSTD_isError(code: usize) -> c_uint;
+    fn ZSTD_getErrorName(code: usize) -> *const c_char;
+}
+
+/// zstd's own default, a good balance of speed and ratio.
+pub const DEFAULT_LEVEL: i32 = 3;
+const CONTENTSIZE_UNKNOWN: c_ulonglong = c_ulonglong::MAX;
+const CONTENTSIZE_ERROR: c_ulonglong = c_ulonglong::MAX - 1;
+
+/// `data` as one zstd frame at compression `level` (1 to 22, or negative for faster).
+pub fn compress(data: &[u8], level: i32) -> io::Result<Vec<u8>> {
+    let mut out = Vec::<u8>::with_capacity(unsafe { ZSTD_compressBound(data.len()) });
+    let written = unsafe { ZSTD_compress(out.as_mut_ptr().cast(), out.capacity(), data.as_ptr().cast(), data.len(), level) };
+    check(written)?;
+    // zstd initialized exactly the bytes it reports.
+    unsafe { out.set_len(written) };
+    Ok(out)
+}
+
+/// Decompresses `frames`, one or more zstd frames that each record their content size, as
+/// [`compress`] and `zstd` itself write them.
+pub fn decompress(frames: &[u8]) -> io::Result<Vec<u8>> {
+    let size = match unsafe { ZSTD_findDecompressedSize(frames.as_ptr().cast(), frames.len()) } {
+        CONTENTSIZE_ERROR => return Err(io::Error::new(io::ErrorKind::InvalidData, "not a zstd frame")),
+        CONTENTSIZE_UNKNOWN => return Err(io::Error::new(io::ErrorKind::Unsupported, "zstd frame doesn't record its size")),
+        size => usize::try_from(size).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "zstd frame too large"))?,
+    };
+    let mut out = Vec::<u8>::with_capacity(size);
+    let written = unsafe { ZSTD_decompress(out.as_mut_ptr().cast(), out.capacity(), frames.as_ptr().cast(), frames.len()) };
+    check(written)?;
+    unsafe { out.set_len(written) };
+    Ok(out)
+}
+
+/// zstd returns errors as sizes it can tell apart.
+fn check(code: usize) -> io::Result<()> {
+    if unsafe { ZSTD_isError(code) } == 0 {
+        return Ok(());
+    }
+    let name = unsafe { CStr::from_ptr(ZSTD_getErrorName(code)) }.to_string_lossy();
+    Err(io::Error::new(io::ErrorKind::InvalidData, format!("zstd: {}", name)))
+}
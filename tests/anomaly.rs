@@ -0,0 +1,55 @@
+use io::anomaly::{self, Criteria, Finding};
+
+/// Lognormal-ish times around `median`, reproducible: the exponential of a sum of uniform
+/// draws, which is close enough to normal.
+fn times(count: usize, median: f64, seed: u64) -> Vec<f64> {
+    let mut state = seed;
+    let mut uniform = || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+    (0..count).map(|_| median * (((0..12).map(|_| uniform()).sum::<f64>() - 6.0) * 0.4).exp()).collect()
+}
+
+#[test]
+fn a_single_cloud_of_file_times_is_unremarkable() {
+    assert_eq!(anomaly::examine(&times(10_000, 0.001, 1), &Criteria::FILES), vec![]);
+    assert_eq!(anomaly::examine(&[0.001; 20], &Criteria::FILES), vec![], "too few to judge");
+}
+
+#[test]
+fn a_slow_cluster_of_files_is_bimodal() {
+    let mut samples = times(9_000, 0.001, 2);
+    samples.extend(times(1_000, 0.02, 3));
+    let findings = anomaly::examine(&samples, &Criteria::FILES);
+    let [Finding::Bimodal { slow_share, ratio }] = findings[..] else { panic!("{:?}", findings) };
+    assert!((slow_share - 0.1).abs() < 0.01, "{}", slow_share);
+    assert!((15.0..25.0).contains(&ratio), "{}", ratio);
+    assert!(findings[0].to_string().starts_with("bimodal"));
+}
+
+#[test]
+fn a_few_stalled_files_are_outliers() {
+    let mut samples = times(5_000, 0.001, 4);
+    samples.extend([0.1, 0.2]);
+    let findings = anomaly::examine(&samples, &Criteria::FILES);
+    let [Finding::Outliers { count, of, worst }] = findings[..] else { panic!("{:?}", findings) };
+    assert!(count >= 2 && of == 5_002, "{} of {}", count, of);
+    assert!(worst > 150.0, "{}", worst);
+}
+
+#[test]
+fn one_slow_iteration_stands_out() {
+    let findings = anomaly::examine(&[100.0, 102.0, 99.0, 1000.0, 101.0], &Criteria::ITERATIONS);
+    assert!(matches!(findings[..], [Finding::Outliers { count: 1, of: 5, .. }]), "{:?}", findings);
+    let findings = anomaly::examine(&[100.0, 140.0, 70.0, 160.0, 90.0, 60.0], &Criteria::ITERATIONS);
+    assert!(matches!(findings[..], [Finding::Spread { .. }]), "{:?}", findings);
+    assert_eq!(anomaly::examine(&[100.0, 103.0, 98.0, 101.0], &Criteria::ITERATIONS), vec![]);
+}
+
+#[test]
+fn findings_round_trip_through_json() {
+    for finding in [Finding::Outliers { count: 3, of: 1000, worst: 12.5 }, Finding::Bimodal { slow_share: 0.25, ratio: 6.0 }, Finding::Spread { cv: 0.4 }] {
+        assert_eq!(Finding::from_json(&io::json::parse(&finding.to_json().to_string()).unwrap()), Some(finding));
+    }
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+
+use io::append::{self, Interleaving};
+
+#[test]
+fn checking_logs_finds_torn_missing_and_reordered_records() {
+    let dir = std::env::temp_dir().join(format!("io-append-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let size = 32;
+    let record = |thread, sequence| {
+        let mut buf = Vec::new();
+        append::encode_record(thread, sequence, size, &mut buf);
+        buf
+    };
+    assert_eq!(record(3, 7).len(), size);
+    assert_eq!(append::decode_record(&record(3, 7)), Some((3, 7)));
+
+    let clean = dir.join("clean");
+    fs::write(&clean, [record(0, 0), record(1, 0), record(0, 1), record(1, 1)].concat()).unwrap();
+    assert!(append::check(std::slice::from_ref(&clean), size, 4).unwrap().is_clean());
+
+    // Half of one record written into another, a thread's records swapped, and one lost.
+    let mut torn = record(0, 0);
+    torn[16..].copy_from_slice(&record(1, 0)[16..]);
+    let broken = dir.join("broken");
+    fs::write(&broken, [torn, record(1, 1), record(1, 0)].concat()).unwrap();
+    let check = append::check(&[clean, broken], size, 8).unwrap();
+    assert_eq!(check, Interleaving { records: 6, torn: 1, missing: 2, out_of_order: 1 });
+
+    // A log cut off a few bytes into its last record, as a crash leaves it.
+    let cut = dir.join("cut");
+    for tail in [1, 5, 12, size - 1] {
+        fs::write(&cut, [&record(0, 0)[..], &record(0, 1)[..tail]].concat()).unwrap();
+        let check = append::check(std::slice::from_ref(&cut), size, 2).unwrap();
+        assert_eq!(check, Interleaving { records: 1, torn: 1, missing: 1, out_of_order: 0 }, "{} bytes of the last record", tail);
+        assert_eq!(append::decode_record(&record(0, 1)[..tail]), None);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
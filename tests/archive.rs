@@ -0,0 +1,81 @@
+use std::fs;
+use std::io::ErrorKind;
+
+use io::archive::{self, Format};
+
+#[test]
+fn every_format_unpacks_what_it_packed() {
+    let dir = std::env::temp_dir().join(format!("io-archive-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let source = dir.join("source");
+    fs::create_dir_all(source.join("nested/deeper")).unwrap();
+    let mut names = Vec::new();
+    for i in 0..40 {
+        let name = if i % 3 == 0 { format!("nested/deeper/{}.rs", i) } else { format!("{}.txt", i) };
+        fs::write(source.join(&name), format!("file {} ", i).repeat(i * 20)).unwrap();
+        names.push(name);
+    }
+
+    let total: u64 = names.iter().map(|name| fs::metadata(source.join(name)).unwrap().len()).sum();
+    for format in Format::ALL {
+        let path = dir.join(format!("{}.{}", format.name(), format.extension()));
+        let bytes = archive::pack(&source, &names, &path, format).unwrap();
+        assert_eq!(bytes, fs::metadata(&path).unwrap().len());
+        let dest = dir.join(format.name());
+        assert_eq!(archive::unpack(&path, format, &dest).unwrap(), names.len(), "{}", format.name());
+        for name in &names {
+            assert_eq!(fs::read(dest.join(name)).unwrap(), fs::read(source.join(name)).unwrap(), "{} in {}", name, format.name());
+        }
+        if format == Format::Tar {
+            // Names are padded to whole blocks, the data too, and two empty blocks end it.
+            assert_eq!(bytes % 512, 0);
+        }
+        if format == Format::TarGz || format == Format::ZipDeflate {
+            assert!(bytes < total / 4, "{} is {} bytes of {}", format.name(), bytes, total);
+        }
+    }
+
+    // A member reaching outside the destination is refused before anything is written.
+    let evil = dir.join("evil.zip");
+    fs::create_dir_all(source.join("up")).unwrap();
+    fs::write(source.join("up/ok.txt"), "fine").unwrap();
+    archive::pack(&source, &["up/ok.txt".to_string()], &evil, Format::Zip).unwrap();
+    let mut bytes = fs::read(&evil).unwrap();
+    for at in 0..bytes.len() - 1 {
+        if &bytes[at..at + 2] == b"up" {
+            bytes[at..at + 2].copy_from_slice(b"..");
+        }
+    }
+    fs::write(&evil, &bytes).unwrap();
+    let error = archive::unpack(&evil, Format::Zip, &dir.join("evil")).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert!(!dir.join("evil").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn long_and_unicode_names_survive_every_format() {
+    let dir = std::env::temp_dir().join(format!("io-archive-names-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let source = dir.join("source");
+    let deep = format!("{}/{}", "d".repeat(120), "e".repeat(120));
+    fs::create_dir_all(source.join(&deep)).unwrap();
+    // Over tar's 100-byte name field, and multibyte characters straddling the cut.
+    let names = vec![format!("{}/{}.txt", deep, "f".repeat(200)), format!("{}/{}", deep, "文件".repeat(30)), "file with  spaces .txt".to_string()];
+    for (i, name) in names.iter().enumerate() {
+        fs::write(source.join(name), format!("file {}", i)).unwrap();
+    }
+
+    for format in Format::ALL {
+        let path = dir.join(format!("{}.{}", format.name(), format.extension()));
+        archive::pack(&source, &names, &path, format).unwrap();
+        let dest = dir.join(format.name());
+        assert_eq!(archive::unpack(&path, format, &dest).unwrap(), names.len(), "{}", format.name());
+        for (i, name) in names.iter().enumerate() {
+            assert_eq!(fs::read_to_string(dest.join(name)).unwrap(), format!("file {}", i), "{} in {}", name, format.name());
+        }
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
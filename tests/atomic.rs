@@ -0,0 +1,84 @@
+//! Checks that atomic writes replace contents, keep permissions and leave no temporary
+//! files behind, and that batch commits make the syncs they report.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use io::atomic::{self, Durability};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("io-atomic-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("failed to create scratch directory");
+    dir
+}
+
+#[test]
+fn replaces_contents_and_cleans_up() {
+    let dir = scratch("single");
+    let path = dir.join("config.json");
+    atomic::write_file_atomic(&path, b"first", Durability::Full).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+    }
+    atomic::write_file_atomic(&path, b"second", Durability::Data).unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), b"second");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn batch_writes_every_file_and_fails_cleanly() {
+    let dir = scratch("batch");
+    let files: Vec<(PathBuf, Vec<u8>)> = (0..5).map(|i| (dir.join(format!("{}.txt", i)), vec![b'a' + i as u8; 100])).collect();
+    atomic::write_files_atomic(&files, Durability::Full).unwrap();
+    for (path, bytes) in &files {
+        assert_eq!(&fs::read(path).unwrap(), bytes);
+    }
+
+    let missing = [(dir.join("missing/file.txt"), b"x")];
+    assert!(atomic::write_files_atomic(&missing, Durability::Full).is_err());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), files.len());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn commit_batch_syncs_each_directory_once() {
+    let dir = scratch("commit");
+    for sub in ["a", "b"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    let few: Vec<(PathBuf, &[u8])> = (0..3).map(|i| (dir.join(["a", "b"][i % 2]).join(format!("{}.txt", i)), &b"few"[..])).collect();
+    let report = atomic::commit_batch(&few).unwrap();
+    assert_eq!((report.files, report.file_syncs, report.dir_syncs, report.filesystem_syncs), (3, 3, 2, 0));
+
+    let many: Vec<(PathBuf, &[u8])> = (0..atomic::SYNCFS_MIN_FILES).map(|i| (dir.join(["a", "b"][i % 2]).join(format!("{}.txt", i)), &b"many"[..])).collect();
+    let report = atomic::commit_batch(&many).unwrap();
+    if atomic::syncfs_reports_errors() {
+        assert_eq!((report.file_syncs, report.dir_syncs, report.filesystem_syncs), (0, 0, 2));
+    } else {
+        // A kernel whose syncfs hides writeback errors gets a sync per file instead.
+        assert_eq!((report.file_syncs, report.dir_syncs, report.filesystem_syncs), (many.len(), 2, 0));
+    }
+    for (path, bytes) in &many {
+        assert_eq!(&fs::read(path).unwrap(), bytes);
+    }
+
+    // The threshold is the caller's to move.
+    let report = atomic::commit_batch_with(&many, usize::MAX).unwrap();
+    assert_eq!((report.file_syncs, report.dir_syncs, report.filesystem_syncs), (many.len(), 2, 0));
+    let report = atomic::commit_batch_with(&few, 1).unwrap();
+    assert_eq!(report.filesystem_syncs, if atomic::syncfs_reports_errors() { 2 } else { 0 });
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn io_bench(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_io")).arg("bench").args(args).current_dir(dir).output().unwrap()
+}
+
+/// `baseline` with every phase time set to `ms`.
+fn with_times(baseline: &str, ms: f64) -> String {
+    baseline
+        .lines()
+        .map(|line| match line.split_once("_ms\": ") {
+            Some((key, value)) => format!("{}_ms\": {}{}", key, ms, if value.ends_with(',') { "," } else { "" }),
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn compare_fails_only_on_regressions_past_the_threshold() {
+    let dir = std::env::temp_dir().join(format!("io-baseline-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let saved = io_bench(&dir, &["--files", "20", "--size", "1K", "--threads", "2", "--save-baseline", "saved.json"]);
+    assert!(saved.status.success(), "{}", String::from_utf8_lossy(&saved.stderr));
+    let baseline = fs::read_to_string(dir.join("saved.json")).unwrap();
+    assert!(baseline.contains("\"create_ms\""));
+
+    // A baseline far slower than any run can't be regressed from.
+    let slow: PathBuf = dir.join("slow.json");
+    fs::write(&slow, with_times(&baseline, 1e6)).unwrap();
+    let output = io_bench(&dir, &["compare", "--baseline", slow.to_str().unwrap()]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("No phase regressed by more than 10%"), "{}", stdout);
+    assert!(!stdout.contains("REGRESSED"), "{}", stdout);
+
+    // One far faster than any run regresses every phase, unless the threshold allows it.
+    let fast = dir.join("fast.json");
+    fs::write(&fast, with_times(&baseline, 1e-6)).unwrap();
+    let output = io_bench(&dir, &["compare", "--baseline", fast.to_str().unwrap(), "--threshold", "25%"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("REGRESSED"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("regressed by more than 25%"), "{}", String::from_utf8_lossy(&output.stderr));
+    let output = io_bench(&dir, &["compare", "--baseline", fast.to_str().unwrap(), "--threshold", "1000000000000%"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let output = io_bench(&dir, &["compare"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs --baseline"));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use io::atomic::Durability;
+use io::batch::{self, Batch};
+
+#[test]
+fn batches_apply_all_or_nothing() {
+    let dir = std::env::temp_dir().join(format!("io-batch-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let journal = dir.join(".journal");
+    fs::write(dir.join("old"), "old").unwrap();
+    fs::write(dir.join("doomed"), "doomed").unwrap();
+
+    let mut batch = Batch::new(&journal);
+    batch.create(dir.join("new"), "new").update(dir.join("old"), "updated").delete(dir.join("doomed"));
+    let report = batch.commit().unwrap();
+    assert_eq!((report.created, report.updated, report.deleted, report.bytes), (1, 1, 1, 10));
+    assert_eq!(fs::read_to_string(dir.join("new")).unwrap(), "new");
+    assert_eq!(fs::read_to_string(dir.join("old")).unwrap(), "updated");
+    assert!(!dir.join("doomed").exists());
+    // Only the files themselves are left: no temporary files, backups or journal.
+    let mut names: Vec<String> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).collect();
+    names.sort();
+    assert_eq!(names, [".journal", "new", "old"]);
+    assert_eq!(fs::read_dir(&journal).unwrap().count(), 0);
+
+    // One change that can't be made leaves every file as it was.
+    let mut batch = Batch::new(&journal);
+    batch.durability(Durability::None).update(dir.join("old"), "again").create(dir.join("other"), "other").create(dir.join("new"), "clash");
+    assert_eq!(batch.commit().unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(fs::read_to_string(dir.join("old")).unwrap(), "updated");
+    assert!(!dir.join("other").exists());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn an_interrupted_batch_is_rolled_back() {
+    let dir = std::env::temp_dir().join(format!("io-batch-recover-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    assert_eq!(batch::recover(&dir.join("none")).unwrap(), 0);
+
+    // As a process dying mid-commit leaves it: the first update applied, the second's old
+    // file moved to its backup, and the create not yet renamed into place.
+    let (a, b, c) = (dir.join("a"), dir.join("b"), dir.join("c"));
+    fs::write(&a, "new a").unwrap();
+    fs::write(dir.join("a.bak"), "old a").unwrap();
+    fs::write(dir.join("b.bak"), "old b").unwrap();
+    fs::write(dir.join("b.tmp"), "new b").unwrap();
+    fs::write(dir.join("c.tmp"), "new c").unwrap();
+    let entry = |target: &std::path::Path, temp: &str, backup: Option<&str>| {
+        format!(
+            r#"{{"target": "{}", "temp": "{}", "backup": {}}}"#,
+            target.display(),
+            dir.join(temp).display(),
+            backup.map_or("null".to_string(), |backup| format!("\"{}\"", dir.join(backup).display()))
+        )
+    };
+    let journal = format!(r#"{{"entries": [{}, {}, {}]}}"#, entry(&a, "a.tmp", Some("a.bak")), entry(&b, "b.tmp", Some("b.bak")), entry(&c, "c.tmp", None));
+    fs::write(dir.join(".io-batch.1.0.json"), journal).unwrap();
+
+    assert_eq!(batch::recover(&dir).unwrap(), 1);
+    assert_eq!(fs::read_to_string(&a).unwrap(), "old a");
+    assert_eq!(fs::read_to_string(&b).unwrap(), "old b");
+    assert!(!c.exists());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_batch_that_fails_before_its_journal_is_removed_rolls_back() {
+    let dir = std::env::temp_dir().join(format!("io-batch-late-{}", std::process::id()));
+    let journal = dir.join(".journal");
+    let held = dir.join("held.json");
+    for attempt in 0..50 {
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&journal).unwrap();
+        let files: Vec<PathBuf> = (0..500).map(|i| dir.join(format!("f{}", i))).collect();
+        for path in &files {
+            fs::write(path, "old").unwrap();
+        }
+        let mut batch = Batch::new(&journal);
+        for path in &files {
+            batch.update(path, "new");
+        }
+        batch.create(dir.join("created"), "created");
+
+        // Swaps the journal for a directory as soon as it's written, so removing it, the
+        // last step before the batch counts as committed, fails.
+        let done = AtomicBool::new(false);
+        let swapped = thread::scope(|scope| {
+            let watcher = scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    if let Some(entry) = fs::read_dir(&journal).unwrap().next() {
+                        let path = entry.unwrap().path();
+                        fs::rename(&path, &held).unwrap();
+                        fs::create_dir(&path).unwrap();
+                        fs::write(path.join("busy"), "").unwrap();
+                        return Some(path);
+                    }
+                }
+                None
+            });
+            let result = batch.commit();
+            done.store(true, Ordering::Relaxed);
+            let swapped = watcher.join().unwrap();
+            assert_eq!(result.is_err(), swapped.is_some(), "{:?}", result);
+            swapped
+        });
+        let Some(path) = swapped else { continue };
+
+        // As a crash there would leave it: the journal back in place for recovery.
+        fs::remove_dir_all(&path).unwrap();
+        fs::rename(&held, &path).unwrap();
+        assert_eq!(batch::recover(&journal).unwrap(), 1);
+        assert!(files.iter().all(|path| fs::read_to_string(path).unwrap() == "old"));
+        assert!(!dir.join("created").exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), files.len() + 1, "attempt {}", attempt);
+        fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+    panic!("the journal was never caught before the batch finished");
+}
@@ -0,0 +1,41 @@
+use std::path::Path;
+use std::time::Duration;
+
+use io::breakdown::{self, Breakdown, Slice};
+
+#[test]
+fn extensions_and_depths_are_read_from_the_path() {
+    let root = Path::new("/repo");
+    assert_eq!(breakdown::extension(Path::new("/repo/src/lib.RS")), "rs");
+    assert_eq!(breakdown::extension(Path::new("/repo/archive.tar.gz")), "gz");
+    assert_eq!(breakdown::extension(Path::new("/repo/.gitignore")), "(none)");
+    assert_eq!(breakdown::extension(Path::new("/repo/Makefile")), "(none)");
+    assert_eq!(breakdown::depth(root, Path::new("/repo/README.md")), 0);
+    assert_eq!(breakdown::depth(root, Path::new("/repo/src/a/b.rs")), 2);
+    // A path outside the root counts as the top.
+    assert_eq!(breakdown::depth(root, Path::new("/elsewhere/x/y")), 0);
+}
+
+#[test]
+fn files_are_summed_by_extension_and_depth() {
+    let root = Path::new("/repo");
+    let mut breakdown = Breakdown::default();
+    let ms = Duration::from_millis;
+    breakdown.add(root, Path::new("/repo/README.md"), 100, ms(1));
+    breakdown.add(root, Path::new("/repo/src/lib.rs"), 1000, ms(2));
+    breakdown.add(root, Path::new("/repo/src/main.rs"), 500, ms(3));
+    breakdown.add(root, Path::new("/repo/assets/img/logo.png"), 5000, ms(4));
+    breakdown.add(root, Path::new("/repo/docs/guide.md"), 300, ms(5));
+
+    assert_eq!(breakdown.by_extension["rs"], Slice { files: 2, bytes: 1500, time: ms(5) });
+    assert_eq!(breakdown.by_extension["md"], Slice { files: 2, bytes: 400, time: ms(6) });
+    assert_eq!(breakdown.by_depth[&0], Slice { files: 1, bytes: 100, time: ms(1) });
+    assert_eq!(breakdown.by_depth[&1], Slice { files: 3, bytes: 1800, time: ms(10) });
+    assert_eq!(breakdown.by_depth[&2], Slice { files: 1, bytes: 5000, time: ms(4) });
+
+    let (top, rest) = breakdown.top_extensions(2);
+    assert_eq!(top, vec![("png", breakdown.by_extension["png"]), ("rs", breakdown.by_extension["rs"])]);
+    assert_eq!(rest, breakdown.by_extension["md"]);
+    let (all, none) = breakdown.top_extensions(10);
+    assert_eq!((all.len(), none), (3, Slice::default()));
+}
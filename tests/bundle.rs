@@ -0,0 +1,66 @@
+use io::bundle::Bundle;
+
+/// The payload of a zstd frame made of raw blocks, as `Bundle` writes them.
+fn unframe(frame: &[u8]) -> Vec<u8> {
+    assert_eq!(frame[..4], 0xfd2f_b528u32.to_le_bytes());
+    let size = u64::from_le_bytes(frame[5..13].try_into().unwrap()) as usize;
+    let (mut at, mut data) = (13, Vec::new());
+    loop {
+        let header = u32::from_le_bytes([frame[at], frame[at + 1], frame[at + 2], 0]);
+        assert_eq!(header >> 1 & 3, 0, "not a raw block");
+        let len = (header >> 3) as usize;
+        data.extend_from_slice(&frame[at + 3..at + 3 + len]);
+        at += 3 + len;
+        if header & 1 == 1 {
+            break;
+        }
+    }
+    assert_eq!(at, frame.len());
+    assert_eq!(data.len(), size);
+    data
+}
+
+fn octal(field: &[u8]) -> u64 {
+    let digits = std::str::from_utf8(field).unwrap().trim_end_matches(['\0', ' ']);
+    u64::from_str_radix(digits, 8).unwrap()
+}
+
+#[test]
+fn bundles_are_tar_in_zstd() {
+    let big = vec![b'x'; 300 * 1024];
+    let mut bundle = Bundle::new("run");
+    bundle.add("command.txt", "io bench\n").add("big.bin", big.clone());
+    let tar = unframe(&bundle.to_bytes().unwrap());
+    assert_eq!(tar, bundle.to_tar().unwrap()[..tar.len()]);
+
+    let header = &tar[..512];
+    assert!(header.starts_with(b"run/command.txt\0"));
+    assert_eq!(&header[257..263], b"ustar\0");
+    assert_eq!(octal(&header[124..136]), 9);
+    let mut unsummed = header.to_vec();
+    unsummed[148..156].copy_from_slice(b"        ");
+    assert_eq!(octal(&header[148..156]), unsummed.iter().map(|&b| b as u64).sum::<u64>());
+    assert_eq!(&tar[512..521], b"io bench\n");
+
+    let second = &tar[1024..1536];
+    assert!(second.starts_with(b"run/big.bin\0"));
+    assert_eq!(octal(&second[124..136]), big.len() as u64);
+    assert_eq!(tar.len(), 1024 + 512 + big.len() + 1024);
+    assert!(tar[tar.len() - 1024..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn long_names_get_pax_headers() {
+    let mut bundle = Bundle::new("run");
+    let name = "x".repeat(200);
+    bundle.add(name.clone(), "data");
+    let tar = bundle.to_tar().unwrap();
+    // A pax header, its path record, then the member itself.
+    assert_eq!(tar[156], b'x');
+    let record = format!("path=run/{}\n", name);
+    assert!(tar.windows(record.len()).any(|window| window == record.as_bytes()));
+    assert_eq!(tar[1024 + 156], b'0');
+    assert_eq!(tar.len() % 512, 0);
+    let empty = Bundle::new("run").to_bytes().unwrap();
+    assert_eq!(unframe(&empty).len(), 1024);
+}
@@ -0,0 +1,38 @@
+#![cfg(all(feature = "libc", feature = "mmap", feature = "rayon"))]
+
+use std::fs;
+
+use io::cache;
+use io::filesystem::Filesystem;
+
+#[test]
+fn evicted_files_leave_the_page_cache() {
+    let dir = std::env::temp_dir().join(format!("io-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let page = cache::page_size();
+    let paths: Vec<_> = (0..3).map(|i| dir.join(format!("f{}", i))).collect();
+    for path in &paths {
+        fs::write(path, vec![7u8; 64 * page]).unwrap();
+        fs::read(path).unwrap();
+    }
+    let empty = dir.join("empty");
+    fs::write(&empty, b"").unwrap();
+
+    let warm = cache::residency(&paths[0]).unwrap();
+    assert_eq!((warm.files, warm.total_pages), (1, 64));
+    assert!(warm.resident_pages > 0);
+
+    let all: Vec<_> = paths.iter().chain([&empty]).collect();
+    let after = cache::evict_from_cache(&all).unwrap();
+    assert_eq!((after.files, after.total_pages), (4, 3 * 64));
+    // tmpfs pages are the files themselves and can't be dropped.
+    if cfg!(target_os = "linux") && Filesystem::of(&dir).is_some_and(|filesystem| filesystem.fs_type != "tmpfs") {
+        assert_eq!(after.resident_pages, 0, "{:?}", after);
+        assert_eq!(cache::residency(&paths[1]).unwrap().resident_pages, 0);
+        assert_eq!(cache::sample_residency(&paths, 3).unwrap().percent(), 0.0);
+    }
+    assert_eq!(fs::read(&paths[2]).unwrap(), vec![7u8; 64 * page]);
+    assert!(cache::evict_from_cache(&[dir.join("missing")]).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,42 @@
+use std::fs;
+
+use io::calibration::{self, Calibration, SizeChoice};
+use io::filesystem::Filesystem;
+
+fn choice(size: usize, read: &str) -> SizeChoice {
+    SizeChoice { size, files: 100, strategies: ["Pack".to_string(), read.to_string(), "Smart".to_string(), "Traditional".to_string()] }
+}
+
+#[test]
+fn calibrations_are_cached_per_storage_and_sized_by_ratio() {
+    let dir = std::env::temp_dir().join(format!("io-calibration-{}", std::process::id()));
+    let cache = dir.join("io/calibration.json");
+    assert!(calibration::read(&cache).unwrap().is_empty());
+
+    let mut other = Calibration::new("sdb:xfs".to_string(), 4);
+    other.add(choice(4096, "Traditional"));
+    other.store_in(&cache).unwrap();
+    let mut here = Calibration::new("sda:ext4".to_string(), 8);
+    here.add(choice(64 * 1024, "Adaptive"));
+    here.add(choice(100, "Preallocated"));
+    here.store_in(&cache).unwrap();
+    here.add(choice(100, "Vectored"));
+    here.store_in(&cache).unwrap();
+
+    let cached = calibration::read(&cache).unwrap();
+    assert_eq!(cached, [other, here.clone()]);
+    assert_eq!(here.sizes.iter().map(|choice| choice.size).collect::<Vec<_>>(), [100, 64 * 1024]);
+    assert_eq!(here.strategy("read", 1), Some("Vectored"));
+    assert_eq!(here.strategy("read", 2048), Some("Vectored"));
+    assert_eq!(here.strategy("read", 4096), Some("Adaptive"));
+    assert_eq!(here.strategy("create", 1 << 30), Some("Pack"));
+    assert_eq!(here.strategy("rename", 100), None);
+
+    if let Some(filesystem) = Filesystem::of(&dir) {
+        let mut mine = Calibration::new(calibration::storage_key(&filesystem), 1);
+        mine.add(choice(100, "Traditional"));
+        mine.store_in(&cache).unwrap();
+        assert_eq!(calibration::load_from(&cache, &dir), Some(mine));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
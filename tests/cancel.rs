@@ -0,0 +1,42 @@
+//! Checks that cancelling one run's token stops that run's phases and leaves a run with a
+//! token of its own alone.
+
+#![cfg(feature = "bench")]
+
+use std::env;
+use std::fs;
+
+use io::bench::{self, Options, STRATEGIES, Workload};
+use io::cancel::{self, Token};
+
+#[test]
+fn tokens_are_per_run() {
+    let token = Token::new();
+    let clone = token.clone();
+    let other = Token::new();
+    assert!(!token.is_cancelled());
+    clone.cancel();
+    assert!(token.is_cancelled());
+    assert!(!other.is_cancelled());
+    assert!(!cancel::is_interrupted());
+}
+
+#[test]
+fn a_cancelled_run_stops_and_others_finish() {
+    let dir = env::temp_dir().join(format!("io-cancel-{}", std::process::id()));
+    let strategy = &STRATEGIES[0];
+    let cancelled = Options { workload: Workload::new(16, 64), ..Options::default() };
+    cancelled.cancel.cancel();
+    let running = cancelled.with_workload(Workload::new(16, 64));
+    assert!(running.cancel.is_cancelled(), "options for the same run share its token");
+    let separate = Options { workload: Workload::new(16, 64), ..Options::default() };
+
+    fs::create_dir_all(&dir).unwrap();
+    let paths = bench::file_paths(&dir, 16);
+    let result = bench::run_strategy_on(strategy, &paths, &cancelled).unwrap();
+    assert_eq!(result.interrupted, Some("create"));
+    let result = bench::run_strategy_on(strategy, &paths, &separate).unwrap();
+    assert_eq!(result.interrupted, None);
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    fs::remove_dir_all(&dir).unwrap();
+}
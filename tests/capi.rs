@@ -0,0 +1,46 @@
+#![cfg(feature = "capi")]
+
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::ptr;
+
+use io::capi::{self, IoBuffer};
+
+#[test]
+fn files_round_trip_through_the_c_interface() {
+    let dir = std::env::temp_dir().join(format!("io-capi-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let names: Vec<CString> = (0..5).map(|i| CString::new(dir.join(format!("{}.txt", i)).to_str().unwrap()).unwrap()).collect();
+    let paths: Vec<*const _> = names.iter().map(|name| name.as_ptr()).collect();
+    let contents: Vec<Vec<u8>> = (0..5).map(|i| vec![b'a' + i as u8; i * 3]).collect();
+    let data: Vec<*const u8> = contents.iter().map(|bytes| if bytes.is_empty() { ptr::null() } else { bytes.as_ptr() }).collect();
+    let lens: Vec<usize> = contents.iter().map(Vec::len).collect();
+
+    assert_eq!(unsafe { capi::io_write_many(paths.as_ptr(), data.as_ptr(), lens.as_ptr(), 5) }, 0);
+    let mut out: Vec<IoBuffer> = (0..5).map(|_| IoBuffer { data: ptr::null_mut(), len: 0 }).collect();
+    assert_eq!(unsafe { capi::io_read_many(paths.as_ptr(), 5, out.as_mut_ptr()) }, 0);
+    for (buffer, bytes) in out.iter().zip(&contents) {
+        assert_eq!(buffer.len, bytes.len());
+        assert_eq!(unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) }, &bytes[..]);
+    }
+    unsafe { capi::io_free_buffers(out.as_mut_ptr(), 5) };
+    assert!(out.iter().all(|buffer| buffer.data.is_null() && buffer.len == 0));
+
+    assert_eq!(unsafe { capi::io_delete_many(paths.as_ptr(), 5) }, 0);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failures_are_described_on_the_calling_thread() {
+    let missing = CString::new("/nonexistent/io-capi-missing").unwrap();
+    let mut out = IoBuffer { data: ptr::null_mut(), len: 0 };
+    assert_eq!(unsafe { capi::io_read_many(&missing.as_ptr(), 1, &mut out) }, -1);
+    assert!(out.data.is_null());
+    let message = unsafe { CStr::from_ptr(capi::io_last_error()) }.to_str().unwrap().to_string();
+    assert!(message.contains("io-capi-missing"), "{}", message);
+
+    assert_eq!(unsafe { capi::io_delete_many(ptr::null(), 2) }, -1);
+    assert!(unsafe { CStr::from_ptr(capi::io_last_error()) }.to_str().unwrap().contains("null"));
+    assert_eq!(unsafe { capi::io_delete_many(ptr::null(), 0) }, 0);
+}
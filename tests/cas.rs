@@ -0,0 +1,35 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use io::cas::{self, Checkout, Store};
+
+#[test]
+fn identical_contents_are_stored_once_and_checked_out_anywhere() {
+    let dir = std::env::temp_dir().join(format!("io-cas-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let store = Store::open(dir.join("store")).unwrap();
+
+    let first = store.insert(b"left-pad").unwrap();
+    let again = store.insert(b"left-pad").unwrap();
+    let other = store.insert(b"is-odd").unwrap();
+    assert!(first.new && !again.new && other.new);
+    assert_eq!(first.hash, again.hash);
+    assert!(store.contains(first.hash));
+    let stored = store.path_of(first.hash);
+    assert_eq!(stored.parent().unwrap().parent().unwrap(), store.root());
+    assert_eq!(fs::read(&stored).unwrap(), b"left-pad");
+
+    let manifest = vec![(PathBuf::from("a/index.js"), first.hash), (PathBuf::from("b/c/index.js"), first.hash), (PathBuf::from("odd.js"), other.hash)];
+    for method in [Checkout::HardLink, Checkout::Copy] {
+        let tree = dir.join(method.name());
+        store.checkout_tree(&manifest, &tree, method).unwrap();
+        assert_eq!(fs::read(tree.join("b/c/index.js")).unwrap(), b"left-pad");
+        assert_eq!(fs::read(tree.join("odd.js")).unwrap(), b"is-odd");
+    }
+    match cas::reflink(&stored, &dir.join("reflinked")) {
+        Ok(()) => assert_eq!(fs::read(dir.join("reflinked")).unwrap(), b"left-pad"),
+        Err(e) => assert_eq!(e.kind(), ErrorKind::Unsupported, "{}", e),
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,52 @@
+use std::fs;
+use std::path::Path;
+
+use io::cgroup::{IoLimit, Limits, Version};
+
+fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+#[test]
+fn v2_limits_are_the_tightest_up_the_tree() {
+    let root = std::env::temp_dir().join(format!("io-cgroup-v2-{}", std::process::id()));
+    write(&root.join("cgroup.controllers"), "cpu io memory\n");
+    write(&root.join("pods/cpu.max"), "200000 100000\n");
+    write(&root.join("pods/io.max"), "8:0 rbps=1048576 wbps=max riops=max wiops=500\n");
+    write(&root.join("pods/web/cpu.max"), "max 100000\n");
+    write(&root.join("pods/web/io.max"), "8:0 rbps=4194304 wbps=2097152 riops=max wiops=max\n259:0 rbps=max wbps=max riops=max wiops=max\n");
+    let limits = Limits::read(&root, "0::/pods/web\n").unwrap();
+    assert_eq!(limits.version, Version::V2);
+    assert_eq!(limits.cpu_quota, Some((200_000, 100_000)));
+    assert_eq!(limits.io, vec![IoLimit { device: "8:0".to_string(), read_bps: Some(1_048_576), write_bps: Some(2_097_152), read_iops: None, write_iops: Some(500) }]);
+    assert_eq!((limits.threads(8), limits.threads(1)), (2, 1));
+    assert_eq!(Limits::from_json(&limits.to_json()), Some(limits));
+
+    // A path from outside the cgroup namespace isn't under the mount, which is then the
+    // process's own cgroup.
+    let limits = Limits::read(&root, "0::/system.slice/docker-abc.scope\n").unwrap();
+    assert!(!limits.is_limited());
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn v1_limits_come_from_the_cpu_and_blkio_hierarchies() {
+    let root = std::env::temp_dir().join(format!("io-cgroup-v1-{}", std::process::id()));
+    write(&root.join("cpu,cpuacct/docker/abc/cpu.cfs_quota_us"), "50000\n");
+    write(&root.join("cpu,cpuacct/docker/abc/cpu.cfs_period_us"), "100000\n");
+    write(&root.join("blkio/blkio.throttle.read_iops_device"), "");
+    write(&root.join("blkio/blkio.throttle.write_bps_device"), "8:16 10485760\n");
+    let membership = "12:blkio:/docker/abc\n4:cpu,cpuacct:/docker/abc\n0::/\n";
+    let limits = Limits::read(&root, membership).unwrap();
+    assert_eq!(limits.version, Version::V1);
+    assert_eq!(limits.cpus(), Some(0.5));
+    assert_eq!(limits.threads(4), 1);
+    assert_eq!(limits.io.len(), 1);
+    assert_eq!((limits.io[0].device.as_str(), limits.io[0].write_bps), ("8:16", Some(10_485_760)));
+    assert_eq!(limits.notes(), ["cgroup v1 CPU quota of 0.50 CPUs", "cgroup v1 I/O limit on 8:16 write 10.0 MiB/s"]);
+
+    let unlimited = Limits::read(&root, "3:cpuset:/\n");
+    assert!(unlimited.is_none());
+    fs::remove_dir_all(&root).unwrap();
+}
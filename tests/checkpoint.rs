@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::PathBuf;
+
+use io::checkpoint::{self, Checkpoint};
+use io::json::Value;
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-checkpoint-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn cells_survive_a_reload() {
+    let dir = dir("reload");
+    let args = vec!["--threads".to_string(), "1,2".to_string()];
+    let mut saved = Checkpoint::create(&dir, "123-abc", "sweep", args.clone()).unwrap();
+    saved.record(Value::object().with("strategy", "Smart").with("threads", 1u64)).unwrap();
+    saved.record(Value::object().with("strategy", "Traditional").with("threads", 1u64)).unwrap();
+
+    let loaded = Checkpoint::load(&dir, "123-abc").unwrap();
+    assert_eq!(loaded.run_id(), "123-abc");
+    assert_eq!(loaded.command(), "sweep");
+    assert_eq!(loaded.args(), &args[..]);
+    assert_eq!(loaded.started_at(), saved.started_at());
+    let strategies: Vec<&str> = loaded.cells().iter().filter_map(|cell| cell.get("strategy")?.as_str()).collect();
+    assert_eq!(strategies, ["Smart", "Traditional"]);
+    assert_eq!(checkpoint::ids(&dir).unwrap(), ["123-abc"]);
+
+    loaded.finish().unwrap();
+    assert!(checkpoint::ids(&dir).unwrap().is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_missing_checkpoint_names_the_ones_there_are() {
+    let dir = dir("missing");
+    Checkpoint::create(&dir, "1-a", "soak", Vec::new()).unwrap();
+    Checkpoint::create(&dir, "2-b", "sweep", Vec::new()).unwrap();
+    let e = Checkpoint::load(&dir, "3-c").unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+    assert!(e.to_string().contains("1-a, 2-b"), "{}", e);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bad_ids_and_files_are_refused() {
+    let dir = dir("bad");
+    assert!(Checkpoint::create(&dir, "../escape", "sweep", Vec::new()).is_err());
+    assert!(Checkpoint::create(&dir, "", "sweep", Vec::new()).is_err());
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("old.json"), "{\"version\": 0}").unwrap();
+    assert_eq!(Checkpoint::load(&dir, "old").unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn checkpoints_live_in_the_state_directory() {
+    let dir = checkpoint::state_dir().unwrap();
+    assert!(dir.ends_with("io/checkpoints"), "{}", dir.display());
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "bench")]
+
+use io::cipher::{self, Aes256Gcm, Cipher, Sealer, XChaCha20Poly1305};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn key() -> [u8; 32] {
+    std::array::from_fn(|i| i as u8)
+}
+
+fn content(len: usize) -> Vec<u8> {
+    (0..len).map(|i| ((i * 13 + 7) % 256) as u8).collect()
+}
+
+#[test]
+fn ciphers_match_reference_vectors() {
+    // draft-irtf-cfrg-xchacha, section 2.2.1.
+    let nonce = [0, 0, 0, 0x09, 0, 0, 0, 0x4a, 0, 0, 0, 0, 0x31, 0x41, 0x59, 0x27];
+    assert_eq!(hex(&cipher::hchacha20(&key(), &nonce)), "82413b4227b27bfed30e42508a877d73a0f9e4d58a74a853c12ec41326d3ecdc");
+
+    // The rest from the Python `cryptography` package, XChaCha20 through its HChaCha20.
+    let gcm_nonce: [u8; 12] = std::array::from_fn(|i| 100 + i as u8);
+    let xchacha_nonce: [u8; 24] = std::array::from_fn(|i| 50 + i as u8);
+    for (len, gcm, xchacha) in [
+        (0, "609bd50f6ad76df665f048ba8c5fd706", "38394bffcf01d29b499477d641ef2277"),
+        (17, "4f0fff4842a103fc511ed67e79d5d73795c3ab6f3a1bedb360aae8b062b78378a7", "0c6e7ef255a91e542c0bfbfb63a6ed763fe4a0e73bf0e18ceac6e3e46cc50b300f"),
+        (1000, "cc09bd46e05a3bea306cb574938046a7", "ce384a9dc97267090e6f68d1e528f0d0"),
+    ] {
+        // The portable tables as well as AES-NI, wherever the CPU has it.
+        for aes in [Aes256Gcm::new(&key()), Aes256Gcm::portable(&key())] {
+            let mut sealed = content(len);
+            let tag = aes.seal(&gcm_nonce, &mut sealed).unwrap();
+            let shown = if len < 100 { [&sealed[..], &tag].concat() } else { tag.to_vec() };
+            assert_eq!(hex(&shown), gcm, "aes-gcm of {} bytes, hardware {}", len, aes.is_hardware());
+            aes.open(&gcm_nonce, &mut sealed, &tag).unwrap();
+            assert_eq!(sealed, content(len));
+        }
+        let mut sealed = content(len);
+        let tag = XChaCha20Poly1305::new(&key()).seal(&xchacha_nonce, &mut sealed).unwrap();
+        let shown = if len < 100 { [&sealed[..], &tag].concat() } else { tag.to_vec() };
+        assert_eq!(hex(&shown), xchacha, "xchacha20 of {} bytes", len);
+    }
+}
+
+#[test]
+fn sealed_files_open_only_intact_and_under_their_key() {
+    let data = content(5000);
+    for cipher in Cipher::ALL {
+        let sealer = Sealer::new(cipher, &key()).unwrap();
+        let sealed = sealer.seal(&data).unwrap();
+        assert_eq!(sealed.len(), data.len() + cipher.overhead());
+        assert_eq!(sealer.open(&sealed).unwrap(), data, "{}", cipher);
+        assert_eq!(cipher.name().parse::<Cipher>().unwrap(), cipher);
+        if cipher == Cipher::Plaintext {
+            continue;
+        }
+        // Every file gets its own random nonce, whichever sealer seals it.
+        let again = Sealer::new(cipher, &key()).unwrap();
+        let nonces: std::collections::HashSet<Vec<u8>> =
+            (0..100).flat_map(|_| [sealer.seal(&data).unwrap(), again.seal(&data).unwrap()]).map(|sealed| sealed[..cipher.nonce_len()].to_vec()).collect();
+        assert_eq!(nonces.len(), 200);
+        assert!(!nonces.contains(&sealed[..cipher.nonce_len()]));
+        let mut tampered = sealed.clone();
+        tampered[cipher.nonce_len() + 10] ^= 1;
+        assert_eq!(sealer.open(&tampered).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        let other = Sealer::new(cipher, &[9; 32]).unwrap();
+        assert!(other.open(&sealed).is_err());
+        assert!(sealer.open(&sealed[..20]).is_err());
+    }
+    assert!("rot13".parse::<Cipher>().is_err());
+}
+
+#[test]
+fn portable_aes_matches_hardware_on_every_length() {
+    let (hardware, portable) = (Aes256Gcm::new(&key()), Aes256Gcm::portable(&key()));
+    assert!(!portable.is_hardware());
+    let nonce = [7; 12];
+    for len in [1, 15, 16, 17, 63, 64, 65, 255, 4096, 4097] {
+        let (mut a, mut b) = (content(len), content(len));
+        assert_eq!(hardware.seal(&nonce, &mut a).unwrap(), portable.seal(&nonce, &mut b).unwrap(), "{} bytes", len);
+        assert_eq!(a, b);
+        let tag = portable.seal(&nonce, &mut content(len)).unwrap();
+        a[0] ^= 1;
+        assert!(portable.open(&nonce, &mut a, &tag).is_err());
+    }
+}
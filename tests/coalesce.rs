@@ -0,0 +1,62 @@
+#![cfg(all(unix, feature = "libc"))]
+
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+
+use io::coalesce::{self, Coalescer};
+use io::dirs::Dirs;
+use io::retry::RetryPolicy;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-coalesce-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("a")).unwrap();
+    fs::create_dir_all(dir.join("b")).unwrap();
+    dir
+}
+
+#[test]
+fn files_are_arranged_by_directory_then_inode_then_name() {
+    let dir = scratch("arrange");
+    fs::write(dir.join("b/old"), "").unwrap();
+    fs::write(dir.join("a/old2"), "").unwrap();
+    fs::write(dir.join("a/old1"), "").unwrap();
+    let paths: Vec<PathBuf> = ["b/new", "a/zeta", "b/old", "a/old1", "a/alpha", "a/old2"].iter().map(|name| dir.join(name)).collect();
+    let arranged = coalesce::arrange(&Dirs::open(&paths).unwrap(), &paths).unwrap();
+    let inode = |name: &str| fs::metadata(dir.join(name)).unwrap().ino();
+    let olds = if inode("a/old1") < inode("a/old2") { [3, 5] } else { [5, 3] };
+    assert_eq!(arranged, [2, 0, olds[0], olds[1], 4, 1]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn grouped_writes_land_like_any_others() {
+    let dir = scratch("write");
+    let files: Vec<(PathBuf, String)> = (0..300).map(|i| (dir.join(if i % 3 == 0 { "a" } else { "b" }).join(format!("f{}", i)), format!("contents {}", i))).collect();
+    coalesce::write_grouped(&files, &RetryPolicy::default()).unwrap();
+    coalesce::write_grouped(&files[..10], &RetryPolicy::default()).unwrap();
+    for (path, contents) in &files {
+        assert_eq!(&fs::read_to_string(path).unwrap(), contents);
+    }
+    let missing = [(dir.join("c/f"), "x")];
+    assert!(coalesce::write_grouped(&missing, &RetryPolicy::default()).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn the_coalescer_holds_writes_until_the_limit() {
+    let dir = scratch("coalescer");
+    let mut coalescer = Coalescer::new(10, RetryPolicy::default());
+    coalescer.write(dir.join("a/one"), "1234").unwrap();
+    coalescer.write(dir.join("a/one"), "5678").unwrap();
+    assert_eq!(coalescer.pending(), 1, "the second write replaces the first");
+    assert!(!dir.join("a/one").exists());
+    coalescer.write(dir.join("b/two"), "123456").unwrap();
+    assert_eq!(coalescer.pending(), 0);
+    assert_eq!(fs::read_to_string(dir.join("a/one")).unwrap(), "5678");
+    coalescer.write(dir.join("b/three"), "3").unwrap();
+    coalescer.flush().unwrap();
+    assert_eq!(fs::read_to_string(dir.join("b/three")).unwrap(), "3");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,18 @@
+use io::codec::Codec;
+
+#[test]
+fn every_codec_round_trips_and_parses_its_name() {
+    let data = b"compressible, compressible, compressible content\n".repeat(100);
+    for &codec in Codec::ALL {
+        let compressed = codec.compress(&data).unwrap();
+        assert_eq!(codec.decompress(&compressed).unwrap(), data, "{}", codec);
+        if codec != Codec::Uncompressed {
+            assert!(compressed.len() < data.len() / 4, "{} left {} bytes", codec, compressed.len());
+        }
+        assert_eq!(codec.name().parse::<Codec>().unwrap(), codec);
+    }
+    assert!("brotli".parse::<Codec>().is_err());
+    assert_eq!("zstd".parse::<Codec>().is_ok(), cfg!(feature = "zstd"));
+    // Each codec's output is its own format.
+    assert!(Codec::Gzip.decompress(&Codec::Lz4.compress(&data).unwrap()).is_err());
+}
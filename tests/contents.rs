@@ -0,0 +1,97 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use io::contents::{self, Order};
+use io::retry::RetryPolicy;
+
+fn files(name: &str, count: usize) -> (PathBuf, Vec<PathBuf>) {
+    let dir = std::env::temp_dir().join(format!("io-contents-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<PathBuf> = (0..count).map(|i| dir.join(format!("file_{}", i))).collect();
+    for (i, path) in paths.iter().enumerate() {
+        fs::write(path, vec![b'x'; i]).unwrap();
+    }
+    (dir, paths)
+}
+
+#[test]
+fn streamed_reads_come_back_in_input_order() {
+    let (dir, mut paths) = files("ordered", 200);
+    paths.insert(50, dir.join("missing"));
+    let stream = contents::read_many_stream(&paths, 8, Order::Input, &RetryPolicy::never());
+    assert_eq!(stream.len(), 201);
+    let read: Vec<(PathBuf, _)> = stream.collect();
+    assert_eq!(read.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), paths);
+    // A failed file is handed back in its place without stopping the rest.
+    assert_eq!(read[50].1.as_ref().unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(read[51].1.as_ref().unwrap().len(), 50);
+    assert_eq!(read[200].1.as_ref().unwrap().len(), 199);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn streamed_reads_in_completion_order_cover_every_file() {
+    let (dir, paths) = files("unordered", 100);
+    let mut read: Vec<(PathBuf, usize)> = contents::read_many_stream(&paths, 4, Order::Completion, &RetryPolicy::default()).map(|(path, read)| (path, read.unwrap().len())).collect();
+    read.sort();
+    let mut expected: Vec<(PathBuf, usize)> = paths.iter().cloned().zip(0..).collect();
+    expected.sort();
+    assert_eq!(read, expected);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_stream_dropped_early_stops_reading() {
+    let (dir, paths) = files("dropped", 100);
+    let mut stream = contents::read_many_stream(&paths, 2, Order::Input, &RetryPolicy::default());
+    assert_eq!(stream.next().unwrap().0, paths[0]);
+    assert_eq!(stream.remaining(), 99);
+    drop(stream);
+    assert_eq!(contents::read_many_stream(&paths[..0], 2, Order::Input, &RetryPolicy::default()).count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// A path that panics the first time it's asked for.
+struct Fuse(PathBuf, AtomicBool);
+
+impl AsRef<Path> for Fuse {
+    fn as_ref(&self) -> &Path {
+        if !self.1.swap(true, Ordering::Relaxed) {
+            panic!("blown");
+        }
+        &self.0
+    }
+}
+
+#[test]
+fn a_panic_is_the_error_of_its_file() {
+    let (dir, paths) = files("panic", 20);
+    let mut fused: Vec<Fuse> = paths.iter().map(|path| Fuse(path.clone(), AtomicBool::new(true))).collect();
+    fused[7].1 = AtomicBool::new(false);
+    let error = contents::read_many(&fused, &RetryPolicy::default()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Other);
+    assert_eq!(error.to_string(), format!("{}: panicked: blown", paths[7].display()));
+    // The pool is unharmed.
+    assert_eq!(contents::read_many(&paths, &RetryPolicy::default()).unwrap().len(), 20);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn mapped_contents_match_read_contents() {
+    // File 0 is empty, and empty files can't be mapped.
+    let (dir, paths) = files("mapped", 12);
+    let read = contents::read_many(&paths, &RetryPolicy::never()).unwrap();
+    let mapped = contents::read_many_mmap(&paths).unwrap();
+    assert_eq!(mapped.len(), read.len());
+    for (i, (mapped, read)) in mapped.iter().zip(&read).enumerate() {
+        assert_eq!(&mapped[..], &read[..], "file {}", i);
+        assert_eq!(mapped.is_mapped(), cfg!(all(feature = "mmap", not(target_os = "wasi"))) && i > 0, "file {}", i);
+    }
+    assert_eq!(contents::read_many_mmap(&paths[..0]).unwrap().len(), 0);
+    let missing = [paths[3].clone(), dir.join("missing")];
+    assert_eq!(contents::read_many_mmap(&missing).unwrap_err().kind(), ErrorKind::NotFound);
+    fs::remove_dir_all(&dir).unwrap();
+}
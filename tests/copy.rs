@@ -0,0 +1,93 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use io::copy::{self, Preserve};
+
+fn write_tree(root: &Path) {
+    fs::create_dir_all(root.join("a/b/c")).unwrap();
+    fs::create_dir_all(root.join("empty")).unwrap();
+    for (name, contents) in [("top", "top"), ("a/one", "one"), ("a/b/two", "two"), ("a/b/c/three", "three")] {
+        fs::write(root.join(name), contents).unwrap();
+    }
+    #[cfg(unix)]
+    io::links::symlink(Path::new("a/one"), &root.join("link")).unwrap();
+}
+
+#[test]
+fn trees_are_copied_whole() {
+    let dir = std::env::temp_dir().join(format!("io-copy-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let (src, dst) = (dir.join("src"), dir.join("dst"));
+    write_tree(&src);
+    let report = copy::copy_tree(&src, &dst, Preserve::NONE).unwrap();
+    assert_eq!((report.files, report.dirs, report.bytes), (4, 4, 14));
+    assert_eq!(fs::read_to_string(dst.join("a/b/c/three")).unwrap(), "three");
+    assert!(dst.join("empty").is_dir());
+    #[cfg(unix)]
+    assert_eq!(fs::read_link(dst.join("link")).unwrap(), Path::new("a/one"));
+
+    let error = copy::copy_tree(&src, &dst, Preserve::NONE).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn metadata_is_carried_over_when_asked() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("io-copy-preserve-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let src = dir.join("src");
+    write_tree(&src);
+    let old = SystemTime::now() - Duration::from_secs(86_400);
+    for path in [src.join("a/one"), src.join("a/b")] {
+        fs::File::open(&path).unwrap().set_modified(old).unwrap();
+    }
+    fs::set_permissions(src.join("a/one"), fs::Permissions::from_mode(0o640)).unwrap();
+    let xattrs = cfg!(feature = "libc") && io_xattr_set(&src.join("a/b/two"));
+
+    let preserve = Preserve { xattrs, ..Preserve::ALL };
+    copy::copy_tree(&src, &dir.join("kept"), preserve).unwrap();
+    let kept = dir.join("kept");
+    assert_eq!(fs::metadata(kept.join("a/one")).unwrap().modified().unwrap(), old);
+    assert_eq!(fs::metadata(kept.join("a/b")).unwrap().modified().unwrap(), old);
+    assert_eq!(fs::metadata(kept.join("a/one")).unwrap().permissions().mode() & 0o777, 0o640);
+    #[cfg(feature = "libc")]
+    if xattrs {
+        assert_eq!(io::xattr::get_xattr(&kept.join("a/b/two"), "user.io.test").unwrap().as_deref(), Some(&b"kept"[..]));
+    }
+
+    copy::copy_tree(&src, &dir.join("plain"), Preserve::NONE).unwrap();
+    assert_ne!(fs::metadata(dir.join("plain/a/one")).unwrap().modified().unwrap(), old);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Sets a test attribute, where the filesystem takes them.
+#[cfg(unix)]
+fn io_xattr_set(path: &Path) -> bool {
+    #[cfg(feature = "libc")]
+    {
+        io::xattr::set_xattr(path, "user.io.test", b"kept").is_ok()
+    }
+    #[cfg(not(feature = "libc"))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn every_method_copies_every_file() {
+    let dir = std::env::temp_dir().join(format!("io-copy-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = io::bench::Options { workload: io::bench::Workload::new(40, 100), ..io::bench::Options::default() };
+    let result = copy::run(&dir, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.methods.iter().map(|method| method.method).collect::<Vec<_>>(), copy::Method::ALL);
+    assert!(result.methods.iter().filter(|method| matches!(method.method, copy::Method::Parallel | copy::Method::Naive)).all(|method| method.elapsed.is_some()));
+    fs::remove_dir_all(&dir).unwrap();
+}
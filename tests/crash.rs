@@ -0,0 +1,51 @@
+#![cfg(all(unix, feature = "bench"))]
+
+use std::fs;
+use std::process::Command;
+use std::time::Duration;
+
+use io::bench::Options;
+use io::crash::{self, CrashConfig, CrashMode, CrashStrategy, Survival};
+
+#[test]
+fn files_are_told_apart_as_current_stale_or_torn() {
+    let dir = std::env::temp_dir().join(format!("io-crash-check-{}", std::process::id()));
+    let size = 100;
+    assert_eq!(crash::generation_of(&crash::version(3, 7, size), 3, size), Some(7));
+    assert_eq!(crash::generation_of(&crash::version(3, 7, size), 4, size), None);
+
+    for strategy in CrashStrategy::ALL {
+        crash::write_generations(&dir, strategy, 5, size, Some(2), |_, _| Ok(())).unwrap();
+        assert_eq!(crash::check(&dir, size, &[2; 5]).unwrap(), Survival { current: 5, ..Survival::default() }, "{}", strategy.name());
+        assert_eq!(strategy.name().parse::<CrashStrategy>().unwrap(), strategy);
+    }
+
+    let version = |index, generation| crash::version(index, generation, size);
+    fs::write(crash::file_path(&dir, 1), [&version(1, 1)[..50], &version(1, 2)[50..]].concat()).unwrap();
+    fs::write(crash::file_path(&dir, 2), b"").unwrap();
+    fs::remove_file(crash::file_path(&dir, 4)).unwrap();
+    // File 0 was acknowledged at generation 3 but holds 2; file 3 may hold the one in flight.
+    fs::write(crash::file_path(&dir, 3), version(3, 3)).unwrap();
+    let survival = crash::check(&dir, size, &[3, 2, 2, 2, 2]).unwrap();
+    assert_eq!(survival, Survival { current: 1, stale: 1, torn: 2, missing: 1 });
+    assert!(survival.breaks(CrashStrategy::RenameFsync, CrashMode::Kill));
+    assert!(survival.breaks(CrashStrategy::TruncateFsync, CrashMode::PowerCut));
+    assert!(!survival.breaks(CrashStrategy::TruncateFsync, CrashMode::Kill));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn killed_workers_leave_renamed_files_whole() {
+    let dir = std::env::temp_dir().join(format!("io-crash-kill-{}", std::process::id()));
+    let config = CrashConfig { mode: CrashMode::Kill, files: 4, size: 4096, trials: 2, max_delay: Duration::from_millis(20), seed: 7 };
+    let worker = |dir: &std::path::Path, strategy: CrashStrategy| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_io"));
+        command.args(["bench", "crash-worker", "--strategy", strategy.name(), "--files", "4", "--size", "4096", "--dir"]).arg(dir);
+        command
+    };
+    let result = crash::run(&dir, &[CrashStrategy::Rename], config, worker, &Options::default()).unwrap();
+    let crashes = &result.strategies[0];
+    assert_eq!(crashes.survival.current, 8, "{:?}", crashes);
+    assert_eq!(crashes.broken, 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::PathBuf;
+
+use io::dedupe::{self, PARTIAL_BYTES};
+use io::digest::blake3;
+
+#[test]
+fn groups_hold_files_with_the_same_contents() {
+    let dir = std::env::temp_dir().join(format!("io-dedupe-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("a/b")).unwrap();
+    let write = |name: &str, contents: &[u8]| fs::write(dir.join(name), contents).unwrap();
+    write("one", b"small");
+    write("a/two", b"small");
+    write("a/b/three", b"small");
+    write("different", b"other");
+    write("empty", b"");
+    write("also_empty", b"");
+    // Large files that share their first bytes, so only the full hash tells two apart.
+    let large = vec![7u8; PARTIAL_BYTES as usize * 2];
+    let mut changed_tail = large.clone();
+    *changed_tail.last_mut().unwrap() = 8;
+    write("large", &large);
+    write("a/large_copy", &large);
+    write("a/b/large_tail", &changed_tail);
+
+    let groups = dedupe::find_duplicates(&dir).unwrap();
+    let paths = |names: &[&str]| names.iter().map(|name| vec![dir.join(name)]).collect::<Vec<Vec<PathBuf>>>();
+    let copies = |group: &dedupe::Group| group.copies.iter().map(|copy| copy.paths.clone()).collect::<Vec<_>>();
+    assert_eq!(groups.len(), 2, "{:?}", groups);
+    assert_eq!(copies(&groups[0]), paths(&["a/large_copy", "large"]));
+    assert_eq!(groups[0].hash, blake3(&large));
+    assert_eq!(groups[0].wasted(), PARTIAL_BYTES * 2);
+    assert_eq!(copies(&groups[1]), paths(&["a/b/three", "a/two", "one"]));
+    assert_eq!(groups[1].hash, blake3(b"small"));
+    assert_eq!((groups[1].size, groups[1].wasted()), (5, 10));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_tree_without_copies_has_no_groups() {
+    let dir = std::env::temp_dir().join(format!("io-dedupe-none-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for i in 0..10 {
+        fs::write(dir.join(i.to_string()), format!("file {}", i)).unwrap();
+    }
+    assert!(dedupe::find_duplicates(&dir).unwrap().is_empty());
+    assert!(dedupe::find_duplicates(&dir.join("missing")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn hard_links_are_one_copy() {
+    let dir = std::env::temp_dir().join(format!("io-dedupe-links-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("original"), b"linked").unwrap();
+    fs::hard_link(dir.join("original"), dir.join("link")).unwrap();
+    // Only links of one file: nothing to reclaim, so no group.
+    assert!(dedupe::find_duplicates(&dir).unwrap().is_empty());
+
+    fs::write(dir.join("copy"), b"linked").unwrap();
+    let groups = dedupe::find_duplicates(&dir).unwrap();
+    assert_eq!(groups.len(), 1, "{:?}", groups);
+    let copies: Vec<Vec<PathBuf>> = groups[0].copies.iter().map(|copy| copy.paths.clone()).collect();
+    assert_eq!(copies, vec![vec![dir.join("copy")], vec![dir.join("link"), dir.join("original")]]);
+    assert_eq!(groups[0].wasted(), 6);
+    fs::remove_dir_all(&dir).unwrap();
+}
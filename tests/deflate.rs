@@ -0,0 +1,79 @@
+use io::deflate::{self, GzipWriter};
+use std::io::Write;
+
+fn hex(text: &str) -> Vec<u8> {
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+}
+
+/// Text that compresses, with some noise so not every block is one long match.
+fn sample(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491u32;
+    (0..len)
+        .map(|i| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            if state.is_multiple_of(7) { state as u8 } else { b"fn main() { let x = 1; }\n"[i % 25] }
+        })
+        .collect()
+}
+
+#[test]
+fn crc32_matches_the_standard_check_value() {
+    assert_eq!(deflate::crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(deflate::crc32(b""), 0);
+    assert_eq!(deflate::crc32_update(deflate::crc32(b"1234"), b"56789"), 0xcbf4_3926);
+}
+
+#[test]
+fn round_trips_and_shrinks_what_repeats() {
+    for len in [0, 1, 2, 3, 100, 70_000, deflate::CHUNK + 12_345] {
+        let data = sample(len);
+        let compressed = deflate::deflate(&data);
+        assert_eq!(deflate::inflate(&compressed).unwrap(), data, "length {}", len);
+        if len >= 70_000 {
+            assert!(compressed.len() < data.len() / 2, "{} bytes compressed to {}", len, compressed.len());
+        }
+    }
+    // Noise goes out in stored blocks, barely larger than it came in.
+    let noise: Vec<u8> = (0..200_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+    let compressed = deflate::deflate(&noise);
+    assert_eq!(deflate::inflate(&compressed).unwrap(), noise);
+    assert!(compressed.len() <= noise.len() + 64);
+}
+
+#[test]
+fn inflates_zlib_dynamic_blocks() {
+    // zlib level 9's raw deflate of the text below, a single dynamic-Huffman block.
+    let compressed = hex("c58cc715802010055bf93660035683ba0405170906aa779f372bf0f8c34cb184bdba69c598f8dca0f9c25243cce083128acc5eb51b339be193903d51ccfd5bfe6d884ae0706314f274c542bb83e4d16883777be5244293bb07");
+    let mut text = b"the quick brown fox jumps over the lazy dog; the lazy dog sleeps. ".repeat(3);
+    text.extend_from_slice(b"pack my box with five dozen liquor jugs!");
+    assert_eq!(deflate::inflate(&compressed).unwrap(), text);
+    assert!(deflate::inflate(&compressed[..40]).is_err());
+}
+
+#[test]
+fn gzip_streams_and_checks_its_trailer() {
+    // Python's gzip.compress(b"hello, hello, hello gzip\n", mtime=0).
+    let python = hex("1f8b0800000000000203cb48cdc9c9d751c840a214d2ab320bb8002a0844a619000000");
+    assert_eq!(deflate::gunzip(&python).unwrap(), b"hello, hello, hello gzip\n");
+
+    let data = sample(3 * deflate::CHUNK / 2);
+    let mut writer = GzipWriter::new(Vec::new()).unwrap();
+    for piece in data.chunks(10_000) {
+        writer.write_all(piece).unwrap();
+    }
+    let streamed = writer.finish().unwrap();
+    assert_eq!(deflate::gunzip(&streamed).unwrap(), data);
+    assert_eq!(deflate::gunzip(&deflate::gzip(&data)).unwrap(), data);
+
+    // Concatenated members decompress as one.
+    let mut two = deflate::gzip(b"first ");
+    two.extend_from_slice(&deflate::gzip(b"second"));
+    assert_eq!(deflate::gunzip(&two).unwrap(), b"first second");
+
+    let mut corrupt = deflate::gzip(b"some bytes to check");
+    let crc = corrupt.len() - 8;
+    corrupt[crc] ^= 1;
+    assert_eq!(deflate::gunzip(&corrupt).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,76 @@
+use io::digest::{self, Algorithm, Blake3, Sha256, Xxh64};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The input of the BLAKE3 test vectors.
+fn counting(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+#[test]
+fn hashes_match_their_reference_vectors() {
+    assert_eq!(hex(&digest::sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(hex(&digest::blake3(b"")), "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+    for (len, expected) in [
+        (1, "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213"),
+        (1023, "10108970eeda3eb932baac1428c7a2163b0e924c9a9e25b35bba72b28f70bd11"),
+        (1024, "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7"),
+        (1025, "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444"),
+        (2048, "e776b6028c7cd22a4d0ba182a8bf62205d2ef576467e838ed6f2529b85fba24a"),
+    ] {
+        assert_eq!(hex(&digest::blake3(&counting(len))), expected, "blake3 of {} bytes", len);
+    }
+    // From the reference libxxhash.
+    assert_eq!(digest::xxh64(b"", 0), 0xef46_db37_51d8_e999);
+    assert_eq!(digest::xxh64(b"abc", 0), 0x44bc_2cf5_ad77_0999);
+    assert_eq!(digest::xxh64(b"abc", 1), 0xbea9_ca81_9932_8908);
+    assert_eq!(digest::xxh64(b"Nobody inspects the spammish repetition", 0), 0xfbce_a83c_8a37_8bf1);
+    let pattern: Vec<u8> = (0..1000).map(|i| ((i * 7 + 3) % 251) as u8).collect();
+    assert_eq!(digest::xxh64(&pattern, 0), 0x021f_7a74_2408_5ea4);
+    assert_eq!(digest::xxh32(&pattern, 0), 0x65bd_14ed);
+}
+
+#[test]
+fn streaming_matches_one_shot_however_the_input_is_split() {
+    let data = counting(9000);
+    for split in [1, 7, 63, 64, 65, 1000, 1024, 4096] {
+        let (mut sha, mut xxh, mut blake) = (Sha256::new(), Xxh64::new(0), Blake3::new());
+        for piece in data.chunks(split) {
+            sha.update(piece);
+            xxh.update(piece);
+            blake.update(piece);
+        }
+        assert_eq!(sha.finish(), digest::sha256(&data), "sha256 in {}-byte pieces", split);
+        assert_eq!(xxh.finish(), digest::xxh64(&data, 0), "xxh64 in {}-byte pieces", split);
+        assert_eq!(blake.finish(), digest::blake3(&data), "blake3 in {}-byte pieces", split);
+    }
+    for algorithm in Algorithm::ALL {
+        let mut hasher = algorithm.hasher();
+        data.chunks(100).for_each(|piece| hasher.update(piece));
+        assert_eq!(hasher.finish(), algorithm.digest(&data), "{}", algorithm);
+        assert_eq!(algorithm.name().parse::<Algorithm>().unwrap(), algorithm);
+    }
+    assert!("md5".parse::<Algorithm>().is_err());
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn every_method_hashes_a_file_alike() {
+    use io::digest::Method;
+
+    let dir = std::env::temp_dir().join(format!("io-digest-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for len in [0, 100, digest::STREAM_CHUNK * 2 + 5] {
+        let path = dir.join(format!("{}.bin", len));
+        let data = counting(len);
+        std::fs::write(&path, &data).unwrap();
+        for algorithm in Algorithm::ALL {
+            for method in Method::ALL {
+                assert_eq!(method.hash_file(algorithm, &path).unwrap(), algorithm.digest(&data), "{} {} of {} bytes", algorithm, method.name(), len);
+            }
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
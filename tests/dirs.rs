@@ -0,0 +1,73 @@
+#![cfg(all(unix, feature = "libc"))]
+
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+use io::dirs::{self, Access, Dirs, Resolve};
+use io::remove;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-dirs-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn files_are_opened_and_unlinked_by_name() {
+    let root = scratch("open");
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    let paths = vec![root.join("one"), root.join("a/two"), root.join("a/b/three")];
+    for resolve in [Resolve::Openat, Resolve::Beneath] {
+        let dirs = Dirs::open(&paths).unwrap().resolve(resolve);
+        for (i, path) in paths.iter().enumerate() {
+            dirs.open_file(path, Access::Create).unwrap().write_all(i.to_string().as_bytes()).unwrap();
+            let mut contents = String::new();
+            dirs.open_file(path, Access::Read).unwrap().read_to_string(&mut contents).unwrap();
+            assert_eq!(contents, i.to_string(), "{:?}", resolve);
+            assert_eq!(fs::read_to_string(path).unwrap(), i.to_string());
+        }
+        dirs.open_file(&paths[0], Access::Truncate).unwrap();
+        assert_eq!(fs::metadata(&paths[0]).unwrap().len(), 0);
+        for path in &paths {
+            dirs.unlink(path).unwrap();
+            assert!(!path.exists());
+        }
+        assert_eq!(dirs.open_file(&paths[1], Access::Read).unwrap_err().kind(), ErrorKind::NotFound);
+        // A directory that wasn't opened is an error, not a panic.
+        assert_eq!(dirs.entry(&root.join("elsewhere/x")).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+    assert_eq!(dirs::open_file(None, &paths[0], Access::Truncate).unwrap_err().kind(), ErrorKind::NotFound);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn beneath_refuses_links_out_of_the_directory() {
+    let root = scratch("beneath");
+    fs::write(root.join("outside"), "secret").unwrap();
+    fs::create_dir(root.join("inner")).unwrap();
+    let link = root.join("inner/link");
+    std::os::unix::fs::symlink(root.join("outside"), &link).unwrap();
+    let paths = [link.clone()];
+    assert!(Dirs::open(&paths).unwrap().open_file(&link, Access::Read).is_ok());
+    if cfg!(target_os = "linux") {
+        assert!(Dirs::open(&paths).unwrap().resolve(Resolve::Beneath).open_file(&link, Access::Read).is_err());
+    }
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn bare_names_are_in_the_current_directory() {
+    let root = scratch("relative");
+    std::env::set_current_dir(&root).unwrap();
+    let paths = [PathBuf::from("x"), PathBuf::from("y")];
+    let dirs = Dirs::open(&paths).unwrap();
+    for path in &paths {
+        dirs.open_file(path, Access::Create).unwrap();
+    }
+    assert!(root.join("x").exists());
+    remove::remove_files(&paths).unwrap();
+    assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+    fs::remove_dir_all(&root).unwrap();
+}
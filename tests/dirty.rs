@@ -0,0 +1,35 @@
+use io::dirty::{self, Backpressure, Relief, Thresholds};
+
+#[test]
+fn reads_the_kernel_thresholds_from_vmstat() {
+    let vmstat = "nr_dirty 120\nnr_writeback 8\nnr_dirty_threshold 40000\nnr_dirty_background_threshold 20000\n";
+    let thresholds = Thresholds::parse(vmstat, 4096).unwrap();
+    assert_eq!(thresholds, Thresholds { background: 20000 * 4096, limit: 40000 * 4096 });
+    assert_eq!(thresholds.free_run(), 30000 * 4096);
+    assert_eq!(thresholds.default_ceiling(), 25000 * 4096);
+    assert_eq!(Thresholds::parse("nr_dirty_threshold 40000\n", 4096), None);
+}
+
+#[test]
+fn counts_dirty_and_writeback_pages() {
+    let meminfo = "MemTotal:       16384000 kB\nDirty:              2048 kB\nWriteback:           512 kB\n";
+    assert_eq!(dirty::dirty_bytes(meminfo), Some(2560 * 1024));
+    assert_eq!(dirty::dirty_bytes("Dirty: 10 kB\n"), None);
+}
+
+#[test]
+fn reliefs_parse_by_name() {
+    for relief in [Relief::Watch, Relief::Pace, Relief::Flush] {
+        assert_eq!(relief.name().parse::<Relief>().unwrap(), relief);
+    }
+    assert!("sync".parse::<Relief>().is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn watches_this_machine() {
+    let backpressure = Backpressure::new(Relief::Watch, Some(1 << 30)).unwrap();
+    assert_eq!(backpressure.ceiling(), 1 << 30);
+    assert!(backpressure.throttled().is_empty());
+    assert_eq!(backpressure.waited(), std::time::Duration::ZERO);
+}
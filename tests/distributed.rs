@@ -0,0 +1,123 @@
+#![cfg(all(unix, feature = "bench"))]
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-distributed-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// An agent on a free loopback port, with the address it listens on.
+struct Agent {
+    child: Child,
+    address: String,
+}
+
+impl Agent {
+    fn start(dir: &Path, token: &str, idle_timeout: u64) -> Agent {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_io"))
+            .args(["agent", "--listen", "127.0.0.1:0", "--token", token, "--idle-timeout", &idle_timeout.to_string(), "--dir"])
+            .arg(dir)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        let line = lines.next().unwrap().unwrap();
+        // Kept draining, or the agent's next line would find the pipe closed.
+        std::thread::spawn(move || lines.for_each(drop));
+        let address = line.strip_prefix("Agent listening on ").and_then(|rest| rest.split(',').next()).unwrap().to_string();
+        Agent { child, address }
+    }
+}
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn orchestrate(dir: &Path, hosts: &str) -> Output {
+    let path = dir.join("hosts.toml");
+    fs::write(&path, hosts).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_io")).arg("orchestrate").arg(&path).env_remove("IO_AGENT_TOKEN").output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn hosts_run_every_phase_with_the_right_token() {
+    let dir = dir("run");
+    let agent = Agent::start(&dir, "s3cret", 600);
+    let hosts = format!("[global]\nfiles = 20\nsize = \"1K\"\nthreads = 1\nstrategies = [\"Smart\"]\ntoken = \"s3cret\"\n\n[a]\naddress = \"{}\"\n", agent.address);
+    let output = orchestrate(&dir, &hosts);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}{}", stdout, stderr(&output));
+    assert!(stdout.contains("a at 127.0.0.1"), "{}", stdout);
+    assert!(stdout.contains("Smart, all hosts"), "{}", stdout);
+
+    // The agent turns away a wrong token and serves the next orchestrator.
+    let output = orchestrate(&dir, &hosts.replace("s3cret", "guess"));
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("wrong token"), "{}", stderr(&output));
+    assert!(orchestrate(&dir, &hosts).status.success());
+
+    // Ctrl-C stops an idle agent.
+    let mut agent = agent;
+    assert!(Command::new("kill").arg("-INT").arg(agent.child.id().to_string()).status().unwrap().success());
+    let started = Instant::now();
+    while agent.child.try_wait().unwrap().is_none() {
+        assert!(started.elapsed() < Duration::from_secs(10), "the agent ignored Ctrl-C");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    drop(agent);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_stalled_orchestrator_is_dropped() {
+    let dir = dir("stalled");
+    let agent = Agent::start(&dir, "s3cret", 1);
+    let mut stalled = TcpStream::connect(&agent.address).unwrap();
+    stalled.write_all(b"run\tstalled\ts3cret\t--files\t5\t--size\t1K\t--threads\t1\t--slice\t0/1\t--strategy\tSmart\n").unwrap();
+    let hosts = format!("[global]\nfiles = 5\nsize = \"1K\"\nthreads = 1\nstrategies = [\"Smart\"]\ntoken = \"s3cret\"\n\n[a]\naddress = \"{}\"\n", agent.address);
+    let output = orchestrate(&dir, &hosts);
+    assert!(output.status.success(), "{}", stderr(&output));
+    drop((stalled, agent));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn agents_need_a_token_beyond_loopback() {
+    let output = Command::new(env!("CARGO_BIN_EXE_io")).args(["agent", "--listen", "0.0.0.0:0"]).env_remove("IO_AGENT_TOKEN").output().unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("without a token"), "{}", stderr(&output));
+}
+
+#[test]
+fn hosts_files_are_checked_before_connecting() {
+    let dir = dir("hosts");
+    for (hosts, error) in [
+        ("[a]\naddress = \"127.0.0.1:1\"\nbogus = 1\n", "[a] unknown key 'bogus'"),
+        ("[a]\nfiles = 10\n", "[a] has no address"),
+        ("[a]\naddress = \"127.0.0.1:1\"\nfiles = 0\n", "files must be a positive integer"),
+        ("[a]\naddress = \"127.0.0.1:1\"\ntoken = \"two\\tfields\"\n", "can't hold tabs"),
+        ("[global]\nstrategies = [\"Nope\"]\n[a]\naddress = \"127.0.0.1:1\"\n", "Nope"),
+        ("[global]\nfiles = 10\n", "no hosts"),
+    ] {
+        let output = orchestrate(&dir, hosts);
+        assert!(!output.status.success(), "{}", hosts);
+        assert!(stderr(&output).contains(error), "{}: {}", hosts, stderr(&output));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
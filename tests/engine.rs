@@ -0,0 +1,40 @@
+#![cfg(feature = "rayon")]
+
+use std::sync::Arc;
+
+use io::engine::{Engine, Workers};
+use io::priority::Priority;
+use rayon::prelude::*;
+
+#[test]
+fn a_shared_pool_runs_the_work_without_new_threads() {
+    let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(3).build().unwrap());
+    let mut engine = Engine::new(8).workers(Workers::Shared(Arc::clone(&pool)));
+    assert_eq!(engine.threads(), 3);
+    let warm_up = engine.warm_up().unwrap();
+    assert_eq!(warm_up.pinned_threads, 0);
+    let index = engine.install(|| Ok(rayon::current_thread_index())).unwrap();
+    assert!(index.is_some_and(|index| index < 3), "{:?}", index);
+    assert!(std::ptr::eq(engine.pool().unwrap().unwrap(), &*pool));
+}
+
+#[test]
+fn the_global_pool_needs_no_handle() {
+    let mut engine = Engine::new(8).workers(Workers::Global);
+    assert_eq!(engine.threads(), rayon::current_num_threads());
+    assert!(engine.pool().unwrap().is_none());
+    let sum = engine.install(|| Ok((1..=100u64).into_par_iter().sum::<u64>())).unwrap();
+    assert_eq!(sum, 5050);
+}
+
+#[test]
+fn a_dedicated_pool_is_built_to_size() {
+    let mut engine = Engine::new(2).pinned(false);
+    assert_eq!(engine.pool().unwrap().unwrap().current_num_threads(), 2);
+}
+
+#[test]
+fn pools_the_engine_does_not_own_keep_their_priority() {
+    let mut engine = Engine::new(2).workers(Workers::Global).priority(Priority { nice: Some(5), io: None });
+    assert_eq!(engine.warm_up().unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+
+use io::bench::Options;
+use io::dirs::Resolve;
+use io::environment::{Capability, Environment};
+
+#[test]
+fn detection_leaves_nothing_behind() {
+    let dir = std::env::temp_dir().join(format!("io-environment-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let environment = Environment::detect(&dir);
+    for (capability, why) in &environment.unavailable {
+        assert!(!environment.has(*capability));
+        assert!(!why.is_empty(), "{}", capability);
+    }
+    assert_eq!(environment.notes().len(), environment.unavailable.len() + usize::from(environment.container.is_some() || environment.seccomp));
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_capabilities_turn_options_off() {
+    let environment = Environment {
+        unavailable: vec![(Capability::Eviction, "pages stayed cached".to_string()), (Capability::Openat2, "ENOSYS".to_string())],
+        ..Environment::default()
+    };
+    let mut options = Options { cold_read: true, open_at: Some(Resolve::Beneath), ..Options::default() };
+    let changes = environment.degrade(&mut options);
+    assert!(!options.cold_read);
+    assert_eq!(options.open_at, Some(Resolve::Openat));
+    assert_eq!(changes.len(), 2);
+    assert!(Environment::default().degrade(&mut Options { cold_read: true, ..Options::default() }).is_empty());
+}
@@ -0,0 +1,44 @@
+use std::io::{ErrorKind, Read, Write};
+
+use io::fault::{FaultInjector, Faults};
+
+#[test]
+fn no_space_after_budget() {
+    let injector = FaultInjector::new(Faults { no_space_after: Some(10), ..Faults::default() });
+    let mut first = injector.wrap(Vec::new());
+    first.write_all(b"123456").unwrap();
+    let mut second = injector.wrap(Vec::new());
+    let e = second.write_all(b"abcdef").unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::StorageFull);
+    // The write that crossed the budget was cut short.
+    assert_eq!(second.get_ref(), b"abcd");
+    assert_eq!(injector.written(), 10);
+    assert_eq!(injector.injected().no_space, 1);
+}
+
+#[test]
+fn interrupts_are_retried_and_repeatable() {
+    let faults = Faults { interrupt: 0.5, ..Faults::default() };
+    let injector = FaultInjector::new(faults);
+    let mut reader = injector.wrap(&b"hello, faults"[..]);
+    let mut read = String::new();
+    // read_to_string retries EINTR, as callers of raw reads should.
+    reader.read_to_string(&mut read).unwrap();
+    assert_eq!(read, "hello, faults");
+    let interrupts = injector.injected().interrupts;
+    assert!(interrupts > 0);
+
+    let again = FaultInjector::new(faults);
+    again.wrap(&b"hello, faults"[..]).read_to_string(&mut String::new()).unwrap();
+    assert_eq!(again.injected().interrupts, interrupts);
+}
+
+#[test]
+fn io_errors_fail_operations() {
+    let injector = FaultInjector::new(Faults { io_error: 1.0, ..Faults::default() });
+    let e = injector.wrap(Vec::new()).write_all(b"data").unwrap_err();
+    assert_ne!(e.kind(), ErrorKind::Interrupted);
+    assert!(injector.before_op().is_err());
+    assert_eq!(injector.injected().io_errors, 2);
+    assert_eq!(injector.written(), 0);
+}
@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use io::fdlimit::{self, NoFileLimit, OpenFileLimiter};
+
+#[test]
+fn permits_never_exceed_the_budget() {
+    let limiter = OpenFileLimiter::new(3);
+    let (open, most) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    thread::scope(|scope| {
+        for _ in 0..12 {
+            scope.spawn(|| {
+                for _ in 0..20 {
+                    let _permit = limiter.acquire();
+                    let now = open.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_micros(200));
+                    open.fetch_sub(1, Ordering::SeqCst);
+                }
+            });
+        }
+    });
+    assert!((1..=3).contains(&most.load(Ordering::SeqCst)), "{}", most.load(Ordering::SeqCst));
+    assert_eq!(OpenFileLimiter::new(0).max(), 1);
+    assert_eq!(OpenFileLimiter::default().max(), usize::MAX);
+}
+
+#[test]
+fn the_budget_keeps_descriptors_back() {
+    assert_eq!(fdlimit::open_file_budget(NoFileLimit { soft: 1024, hard: 4096 }), 1024 - fdlimit::RESERVED_FDS as usize);
+    assert_eq!(fdlimit::open_file_budget(NoFileLimit { soft: 10, hard: 10 }), 1);
+}
+
+#[cfg(all(unix, feature = "libc"))]
+#[test]
+fn too_many_open_files_says_what_to_raise() {
+    let limiter = OpenFileLimiter::new(100);
+    let explained = fdlimit::explain(std::io::Error::from_raw_os_error(libc::EMFILE), &limiter);
+    let message = explained.to_string();
+    assert!(message.contains("RLIMIT_NOFILE soft limit") && message.contains("at most 100 files open at once"), "{}", message);
+    assert!(message.contains("--raise-nofile"), "{}", message);
+    let limits = fdlimit::nofile_limit().unwrap();
+    assert!(message.contains(&limits.soft.to_string()) && limits.soft <= limits.hard);
+
+    // Anything else passes through untouched.
+    let other = fdlimit::explain(std::io::Error::new(std::io::ErrorKind::NotFound, "gone"), &limiter);
+    assert_eq!((other.kind(), other.to_string()), (std::io::ErrorKind::NotFound, "gone".to_string()));
+}
@@ -0,0 +1,41 @@
+//! Checks that the crate builds with every combination of its optional features.
+//!
+//! This shells out to `cargo check` once per combination, so it is ignored by default:
+//! run it with `cargo test --test feature_matrix -- --ignored`.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+const FEATURES: &[&str] = &["rayon", "mmap", "libc", "tokio", "bench", "sqlite", "lmdb", "s3", "zstd", "capi"];
+
+#[test]
+#[ignore]
+fn every_feature_combination_compiles() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut failures = Vec::new();
+
+    for mask in 0..1u32 << FEATURES.len() {
+        let features: Vec<&str> = FEATURES
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| mask & (1 << i) != 0)
+            .map(|(_, feature)| *feature)
+            .collect();
+        let status = Command::new(&cargo)
+            .current_dir(manifest_dir)
+            .args(["check", "--quiet", "--all-targets", "--no-default-features", "--features"])
+            .arg(features.join(","))
+            .arg("--target-dir")
+            .arg(manifest_dir.join("target/feature-matrix"))
+            .env("RUSTFLAGS", "-D warnings")
+            .status()
+            .expect("failed to run cargo");
+        if !status.success() {
+            failures.push(features.join(","));
+        }
+    }
+
+    assert!(failures.is_empty(), "feature combinations failed to compile: {:?}", failures);
+}
@@ -0,0 +1,31 @@
+#![cfg(all(target_os = "linux", feature = "libc"))]
+
+use std::os::unix::fs::MetadataExt;
+
+use io::filesystem::Filesystem;
+
+#[test]
+fn the_temp_directory_reports_its_type_and_block_size() {
+    let dir = std::env::temp_dir().canonicalize().unwrap();
+    let filesystem = Filesystem::of(&dir).unwrap();
+    assert!(!filesystem.fs_type.is_empty() && !filesystem.fs_type.contains(' '), "{:?}", filesystem);
+    assert!(dir.starts_with(&filesystem.mount_point), "{:?}", filesystem);
+    // The mount that holds it is the one listed for its mount point.
+    let mounts = std::fs::read_to_string("/proc/self/mounts").unwrap();
+    let mount_point = filesystem.mount_point.to_str().unwrap().replace(' ', "\\040");
+    assert!(mounts.lines().any(|line| line.split(' ').nth(1) == Some(&mount_point) && line.split(' ').nth(2) == Some(&filesystem.fs_type)), "{:?}", filesystem);
+
+    let block_size = filesystem.block_size.unwrap();
+    assert!(block_size >= 512 && block_size.is_power_of_two(), "{}", block_size);
+    assert_eq!(block_size, std::fs::metadata(&dir).unwrap().blksize());
+    assert!(filesystem.available.unwrap() <= filesystem.total.unwrap());
+
+    let read = Filesystem::from_json(&filesystem.to_json()).unwrap();
+    assert_eq!((read.fs_type, read.mount_point, read.block_size, read.total), (filesystem.fs_type, filesystem.mount_point, filesystem.block_size, filesystem.total));
+}
+
+#[test]
+fn virtual_filesystems_are_named_by_type() {
+    assert_eq!(Filesystem::of("/proc/self".as_ref()).unwrap().fs_type, "proc");
+    assert!(Filesystem::of("/no/such/path".as_ref()).is_none());
+}
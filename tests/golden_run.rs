@@ -0,0 +1,57 @@
+//! Golden-run checks of the adaptive strategy: which backend it picks for each file at
+//! fixed thresholds, and that every file succeeds, compared against `tests/golden` without
+//! asserting on any timing.
+//!
+//! After an intended change to the decisions, regenerate the files with
+//! `UPDATE_GOLDEN=1 cargo test --test golden_run`.
+
+#![cfg(feature = "bench")]
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use io::bench::{self, Options, STRATEGIES, Scheduling, Workload};
+use io::crossover::Thresholds;
+use io::golden::{Recorder, Recording};
+
+fn check(name: &str, actual: &Recording) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.write(&path).unwrap();
+        return;
+    }
+    let expected = Recording::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    let differences = expected.diff(actual);
+    assert!(differences.is_empty(), "{} differs from the run; if the change is intended, rerun with UPDATE_GOLDEN=1\n{:#?}", path.display(), &differences[..differences.len().min(10)]);
+}
+
+fn run_adaptive(name: &str, scheduling: Scheduling, chunk: Option<usize>) {
+    let dir = env::temp_dir().join(format!("io-golden-run-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let recorder = Arc::new(Recorder::default());
+    let options = Options {
+        workload: Workload::new(64, 8192),
+        thresholds: Thresholds { mmap_read: Some(8192), mmap_update: None },
+        golden: Some(recorder.clone()),
+        scheduling,
+        chunk,
+        ..Options::default()
+    };
+    let adaptive = STRATEGIES.iter().find(|strategy| strategy.label == "Adaptive").unwrap();
+    bench::run_strategy(adaptive, &dir, &options).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    check("adaptive.golden", &recorder.recording(&options));
+}
+
+#[test]
+fn adaptive_decisions() {
+    run_adaptive("stealing", Scheduling::Stealing, None);
+}
+
+/// Dividing the files up front changes which worker handles each, never what happens to it.
+#[test]
+fn static_scheduling_decides_the_same() {
+    run_adaptive("static", Scheduling::Static, Some(5));
+}
@@ -0,0 +1,53 @@
+use std::fs;
+use std::io::Read;
+use std::sync::Arc;
+
+use io::handles::HandleCache;
+
+#[test]
+fn handles_are_reused_evicted_and_forgotten() {
+    let dir = std::env::temp_dir().join(format!("io-handles-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (a, b, c) = (dir.join("a"), dir.join("b"), dir.join("c"));
+    for path in [&a, &b, &c] {
+        fs::write(path, path.file_name().unwrap().as_encoded_bytes()).unwrap();
+    }
+    let cache = HandleCache::new(2);
+    let first = cache.open(&a).unwrap();
+    assert!(Arc::ptr_eq(&first, &cache.open(&a).unwrap()));
+    cache.open(&b).unwrap();
+    // `a` was used last, so `b` makes way for `c`.
+    cache.open(&a).unwrap();
+    cache.open(&c).unwrap();
+    assert_eq!(cache.len(), 2);
+    assert!(Arc::ptr_eq(&first, &cache.open(&a).unwrap()));
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.evictions), (3, 3, 1));
+
+    // A renamed handle follows its file, and the one it replaced is dropped.
+    cache.rename(&a, &c).unwrap();
+    assert!(Arc::ptr_eq(&first, &cache.open(&c).unwrap()));
+    assert_eq!(cache.len(), 1);
+
+    // A file created again after a delete is opened anew.
+    cache.remove_file(&c).unwrap();
+    fs::write(&c, "new").unwrap();
+    let mut content = String::new();
+    (&*cache.open(&c).unwrap()).read_to_string(&mut content).unwrap();
+    assert_eq!(content, "new");
+    assert!(cache.open(&a).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn the_update_finds_the_read_handles_open() {
+    let dir = std::env::temp_dir().join(format!("io-handles-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = io::bench::Options { workload: io::bench::Workload::new(10, 100), ..io::bench::Options::default() };
+    let result = io::handles::run(&dir, 100, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!((result.stats.hits, result.stats.misses, result.stats.evictions), (10, 10, 0));
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,65 @@
+use std::fs;
+
+use io::health::{Change, Device, Snapshot};
+
+#[test]
+fn smartctl_output_gives_nvme_and_ata_attributes() {
+    let nvme = Snapshot::from_smartctl(
+        r#"{"smartctl": {"exit_status": 0}, "nvme_smart_health_information_log": {"temperature": 41, "percentage_used": 3, "warning_temp_time": 12, "critical_comp_time": 1, "note": "text"}}"#,
+    );
+    assert_eq!(nvme.get("temperature"), Some(41.0));
+    assert_eq!(nvme.get("percentage_used"), Some(3.0));
+    assert_eq!(nvme.get("note"), None);
+    assert_eq!(nvme.throttled_minutes(), Some(13.0));
+
+    let ata = Snapshot::from_smartctl(
+        r#"{"ata_smart_attributes": {"table": [
+            {"id": 194, "name": "Temperature_Celsius", "raw": {"value": 35, "string": "35 (Min/Max 20/50)"}},
+            {"id": 5, "name": "Reallocated_Sector_Ct", "raw": {"value": 0}},
+            {"id": 9, "name": "Power_On_Hours"}
+        ]}}"#,
+    );
+    assert_eq!(ata.readings, vec![("Temperature_Celsius".to_string(), 35.0), ("Reallocated_Sector_Ct".to_string(), 0.0)]);
+    assert_eq!(ata.throttled_minutes(), None);
+
+    // What smartctl prints when it can't open the device isn't JSON.
+    assert!(Snapshot::from_smartctl("smartctl: Permission denied").is_empty());
+}
+
+#[test]
+fn changes_cover_readings_of_either_snapshot() {
+    let before = Snapshot { readings: vec![("temp1 °C".to_string(), 40.0), ("gone".to_string(), 1.0)] };
+    let after = Snapshot { readings: vec![("temp1 °C".to_string(), 52.5), ("new".to_string(), 2.0)] };
+    let changes = before.changes(&after);
+    assert_eq!(
+        changes,
+        vec![
+            Change { name: "temp1 °C".to_string(), before: Some(40.0), after: Some(52.5) },
+            Change { name: "gone".to_string(), before: Some(1.0), after: None },
+            Change { name: "new".to_string(), before: None, after: Some(2.0) },
+        ]
+    );
+    assert_eq!(changes.iter().map(Change::delta).collect::<Vec<_>>(), vec![Some(12.5), None, None]);
+}
+
+#[test]
+fn temperatures_come_from_every_hwmon_sensor_of_the_device() {
+    // A stand-in for the device's sysfs directory: an NVMe-style `device/hwmon0` and a
+    // drivetemp-style `device/hwmon/hwmon1`.
+    let sysfs = std::env::temp_dir().join(format!("io-health-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&sysfs);
+    let (nvme, drivetemp) = (sysfs.join("device/hwmon0"), sysfs.join("device/hwmon/hwmon1"));
+    fs::create_dir_all(&nvme).unwrap();
+    fs::create_dir_all(&drivetemp).unwrap();
+    fs::write(nvme.join("temp1_input"), "38850\n").unwrap();
+    fs::write(nvme.join("temp1_label"), "Composite\n").unwrap();
+    fs::write(nvme.join("temp2_input"), "not a number").unwrap();
+    fs::write(drivetemp.join("temp1_input"), "30000\n").unwrap();
+
+    let device = Device { name: "nvme9n1".to_string(), sysfs: sysfs.clone() };
+    assert_eq!(device.node(), std::path::Path::new("/dev/nvme9n1"));
+    let temperatures = device.temperatures();
+    // Sensors are read in path order; an unreadable input is skipped.
+    assert_eq!(temperatures.readings, vec![("temp1 °C".to_string(), 30.0), ("Composite °C".to_string(), 38.85)]);
+    fs::remove_dir_all(&sysfs).unwrap();
+}
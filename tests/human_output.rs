@@ -0,0 +1,72 @@
+//! Golden-file checks of the human output layout: what the formatters and tables print
+//! must not change without bumping `human::LAYOUT_VERSION`.
+//!
+//! After an intended layout change, regenerate the files with
+//! `UPDATE_GOLDEN=1 cargo test --test human_output` and bump the version.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use io::human::{self, Align, Table};
+
+fn check(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    assert!(
+        expected == actual,
+        "{} differs from the output; if the change is intended, bump LAYOUT_VERSION and rerun with UPDATE_GOLDEN=1\n--- expected\n{}--- actual\n{}",
+        path.display(),
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn numbers() {
+    let mut out = format!("{}\n", human::header());
+    for n in [0, 7, 999, 1_000, 65_536, 1_234_567, u64::MAX] {
+        writeln!(out, "thousands {} = {}", n, human::thousands(n)).unwrap();
+    }
+    for (value, decimals) in [(0.0, 2), (-0.001, 2), (2.71, 0), (1234.5, 1), (-98765.4321, 3), (1e9, 2)] {
+        writeln!(out, "decimal {} {} = {}", value, decimals, human::decimal(value, decimals)).unwrap();
+    }
+    for percent in [0.0, 0.04, 12.5, -3.0, 1500.0] {
+        writeln!(out, "change {} = {}", percent, human::change(percent, 1)).unwrap();
+    }
+    for n in [0, 512, 1023, 1024, 1536, 4 << 20, 3 << 30, u64::MAX] {
+        writeln!(out, "bytes {} = {}", n, human::bytes(n)).unwrap();
+    }
+    writeln!(out, "rate = {}", human::rate(123_456.0, Duration::from_millis(1500))).unwrap();
+    writeln!(out, "rate zero = {}", human::rate(10.0, Duration::ZERO)).unwrap();
+    check("numbers.txt", &out);
+}
+
+#[test]
+fn durations() {
+    let mut out = format!("{}\n", human::header());
+    for nanos in [0, 850, 12_500, 3_204_000, 1_234_000_000, 125_000_000_000, 7_384_500_000_000] {
+        let d = Duration::from_nanos(nanos);
+        writeln!(out, "{} ns: duration {}, millis {}", nanos, human::duration(d), human::millis(d)).unwrap();
+    }
+    check("durations.txt", &out);
+}
+
+#[test]
+fn tables() {
+    let mut table = Table::new(["Strategy", "Threads", "Size", "Create ms", "Note"]).align(4, Align::Left);
+    table.row(["Smart", "4", &human::bytes(4096), &human::millis(Duration::from_micros(12_345)), "mmap"]);
+    table.row(["Traditional", "16", &human::bytes(1 << 20), &human::millis(Duration::from_secs(2)), ""]);
+    table.row(["Short row"]);
+    table.row(["Long row", "1", "2", "3", "4", "dropped"]);
+    let mut out = format!("{}\n", human::header());
+    out.push_str(&table.render());
+    out.push_str(&Table::new(["Empty", "Table"]).render());
+    check("tables.txt", &out);
+}
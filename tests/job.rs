@@ -0,0 +1,64 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-job-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run_job(path: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_io")).args(["bench", "--job"]).arg(path).output().unwrap()
+}
+
+/// The summary rows of one job: its strategies and sync mode.
+fn rows<'a>(stdout: &'a str, job: &str) -> Vec<Vec<&'a str>> {
+    stdout.lines().map(|line| line.split_whitespace().collect::<Vec<_>>()).filter(|fields| fields.first() == Some(&job)).collect()
+}
+
+#[test]
+fn toml_and_yaml_jobs_run_each_workload_in_order() {
+    let dir = scratch("run");
+    let toml = dir.join("job.toml");
+    fs::write(
+        &toml,
+        "[global]\nthreads = 2\nfiles = 20\n\n[small-files]\nsize = \"1K\"\nstrategies = [\"traditional_io\", \"Smart\"]\n\n[durable]\nfiles = 4\nsize = \"16K\"\nsync = \"data\"\nstrategies = [\"vectored\"]\n",
+    )
+    .unwrap();
+    let yaml = dir.join("job.yml");
+    fs::write(&yaml, "global:\n  threads: 2\n  files: 20\n\nsmall-files:\n  size: 1K\n  strategies: [traditional_io, Smart]\n\ndurable:\n  files: 4\n  size: 16K\n  sync: data\n  strategies:\n  - vectored   # writev\n").unwrap();
+
+    for path in [&toml, &yaml] {
+        let output = run_job(path);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+        assert!(stdout.find("Job small-files:").unwrap() < stdout.find("Job durable:").unwrap(), "{}", stdout);
+        let small = rows(&stdout, "small-files");
+        assert_eq!(small.iter().map(|row| row[1]).collect::<Vec<_>>(), ["Traditional", "Smart"], "{}", stdout);
+        assert!(small.iter().all(|row| row[2] == "2" && row[3] == "20"), "{:?}", small);
+        let durable = rows(&stdout, "durable");
+        assert_eq!(durable.len(), 1, "{}", stdout);
+        assert_eq!((durable[0][1], durable[0][3], durable[0][6]), ("Vectored", "4", "data"));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn mistakes_in_a_job_file_are_named() {
+    let dir = scratch("errors");
+    let error = |name: &str, text: &str| {
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        let output = run_job(&path);
+        assert!(!output.status.success(), "{} ran", name);
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+    assert!(error("strategy.toml", "[a]\nstrategies = [\"fastest\"]\n").contains("unknown strategy 'fastest'"));
+    assert!(error("key.toml", "[a]\ncolour = \"blue\"\n").contains("unknown key 'colour'"));
+    assert!(error("job.ini", "[a]\n").contains(".toml, .yaml or .yml"));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,44 @@
+#![cfg(all(target_os = "linux", feature = "bench"))]
+
+use std::fs;
+use std::path::PathBuf;
+
+use io::bench::{Options, SyncMode, Workload};
+use io::linked;
+
+#[test]
+fn linked_chains_create_every_file() {
+    let dir = std::env::temp_dir().join(format!("io-linked-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<PathBuf> = (0..100).map(|i| dir.join(format!("file_{}", i))).collect();
+    let options = Options { workload: Workload::new(100, 1000), sync: SyncMode::Data, ..Options::default() };
+    match linked::create_files_linked(&paths, &options, 16) {
+        // Kernels before 5.15 and sandboxes without io_uring can't run the chains.
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {}
+        result => {
+            let enters = result.unwrap();
+            assert!(enters >= 7, "{} enters for 7 batches", enters);
+            for path in &paths {
+                assert_eq!(fs::read(path).unwrap(), options.workload.content());
+            }
+            // A missing directory breaks every chain at the open.
+            let missing = dir.join("missing").join("file");
+            assert_eq!(linked::create_files_linked(&[missing], &options, 16).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn every_batch_size_is_timed() {
+    let dir = std::env::temp_dir().join(format!("io-linked-run-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(20, 100), ..Options::default() };
+    let result = linked::run(&dir, &[1, 8], &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    if result.skipped.is_none() {
+        assert_eq!(result.batches.iter().map(|batch| batch.batch).collect::<Vec<_>>(), [1, 8]);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 20);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::PathBuf;
+
+use io::links;
+
+#[test]
+fn many_links_reach_their_targets() {
+    let dir = std::env::temp_dir().join(format!("io-links-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("store")).unwrap();
+    fs::create_dir_all(dir.join("hard")).unwrap();
+    fs::create_dir_all(dir.join("soft")).unwrap();
+    let targets: Vec<PathBuf> = (0..20).map(|i| dir.join("store").join(i.to_string())).collect();
+    for (i, target) in targets.iter().enumerate() {
+        fs::write(target, format!("file {}", i)).unwrap();
+    }
+
+    let hard: Vec<(PathBuf, PathBuf)> = targets.iter().map(|target| (target.clone(), dir.join("hard").join(target.file_name().unwrap()))).collect();
+    links::link_many(&hard).unwrap();
+    // Relative targets resolve from the link's directory.
+    let soft: Vec<(PathBuf, PathBuf)> = targets.iter().map(|target| (PathBuf::from("../store").join(target.file_name().unwrap()), dir.join("soft").join(target.file_name().unwrap()))).collect();
+    links::symlink_many(&soft).unwrap();
+
+    for (i, ((_, hard), (_, soft))) in hard.iter().zip(&soft).enumerate() {
+        assert_eq!(fs::read_to_string(hard).unwrap(), format!("file {}", i));
+        assert_eq!(fs::read_to_string(soft).unwrap(), format!("file {}", i));
+        assert!(fs::symlink_metadata(soft).unwrap().file_type().is_symlink());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        assert_eq!(fs::metadata(&targets[0]).unwrap().nlink(), 2);
+    }
+
+    // An existing link is an error that names it.
+    let e = links::link_many(&hard[..1]).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists);
+    assert!(e.to_string().contains(&hard[0].1.display().to_string()), "{}", e);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn every_method_is_timed() {
+    use io::bench::{Options, Workload};
+    use io::links::Materialize;
+
+    let dir = std::env::temp_dir().join(format!("io-links-run-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(20, 100), ..Options::default() };
+    let result = links::run(&dir, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.times.iter().map(|(method, _)| *method).collect::<Vec<_>>(), Materialize::ALL);
+    // Each tree is removed once timed.
+    for method in Materialize::ALL {
+        assert!(!dir.join(format!("tree-{}", method.name())).exists());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
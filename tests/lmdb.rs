@@ -0,0 +1,45 @@
+#![cfg(all(unix, feature = "lmdb"))]
+
+use std::fs;
+use std::thread;
+
+use io::lmdb::{self, Lmdb};
+
+#[test]
+fn keys_replace_read_back_and_delete() {
+    let dir = std::env::temp_dir().join(format!("io-lmdb-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("files.lmdb");
+    let store = Lmdb::open(&path, 1 << 26).unwrap();
+    store.set_durable(false).unwrap();
+    thread::scope(|scope| {
+        for worker in 0..4 {
+            let store = &store;
+            scope.spawn(move || {
+                for i in 0..25 {
+                    store.put(&format!("file_{}", worker * 25 + i), &[i as u8; 300]).unwrap();
+                }
+            });
+        }
+    });
+    let mut txn = store.write().unwrap();
+    txn.put("file_0", b"").unwrap();
+    assert!(txn.delete("file_1").unwrap());
+    drop(txn);
+    assert_eq!(store.get("file_1").unwrap().unwrap(), [1; 300]);
+
+    store.put("file_0", b"").unwrap();
+    assert_eq!(store.len().unwrap(), 100);
+    assert_eq!(store.get("file_0").unwrap().unwrap(), b"");
+    assert!(store.delete("file_1").unwrap());
+    assert!(!store.delete("file_1").unwrap());
+    assert_eq!(store.get("file_1").unwrap(), None);
+    store.sync().unwrap();
+    drop(store);
+
+    assert_eq!(Lmdb::open(&path, 1 << 26).unwrap().get("file_99").unwrap().unwrap(), [24; 300]);
+    lmdb::remove(&path).unwrap();
+    assert!(!path.exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,80 @@
+#![cfg(all(unix, feature = "libc"))]
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use io::lock::{self, FileLock, LockKind, LockMode};
+
+fn lock_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-lock-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn locks_exclude_each_other_within_a_process() {
+    let dir = lock_dir("exclude");
+    let path = dir.join("lock");
+    for kind in LockKind::ALL {
+        let held = FileLock::acquire(&path, kind, LockMode::Exclusive, None).unwrap();
+        assert!(FileLock::try_acquire(&path, kind, LockMode::Shared).unwrap().is_none(), "{}", kind);
+        let error = FileLock::acquire(&path, kind, LockMode::Exclusive, Some(Duration::from_millis(20))).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+        held.unlock().unwrap();
+
+        let shared = FileLock::acquire(&path, kind, LockMode::Shared, None).unwrap();
+        assert!(FileLock::try_acquire(&path, kind, LockMode::Shared).unwrap().is_some(), "{}", kind);
+        drop(shared);
+        assert!(FileLock::try_acquire(&path, kind, LockMode::Exclusive).unwrap().is_some(), "{}", kind);
+        assert_eq!(kind.name().parse::<LockKind>().unwrap(), kind);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn lock_many_takes_all_or_none() {
+    let dir = lock_dir("many");
+    let paths: Vec<PathBuf> = ["c", "a", "b", "a"].iter().map(|name| dir.join(name)).collect();
+    let locks = lock::lock_many(&paths, LockKind::Flock, LockMode::Exclusive, None).unwrap();
+    assert_eq!(locks.iter().map(|lock| lock.path().file_name().unwrap().to_str().unwrap()).collect::<Vec<_>>(), ["a", "b", "c"]);
+    drop(locks);
+
+    let held = FileLock::acquire(&dir.join("b"), LockKind::Flock, LockMode::Exclusive, None).unwrap();
+    let error = lock::lock_many(&paths, LockKind::Flock, LockMode::Exclusive, Some(Duration::from_millis(20))).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::TimedOut);
+    // The lock on `a` was released with the failure.
+    assert!(FileLock::try_acquire(&dir.join("a"), LockKind::Flock, LockMode::Exclusive).unwrap().is_some());
+    drop(held);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn contending_threads_lose_no_updates() {
+    let dir = lock_dir("bench");
+    let config = lock::LockConfig { lock_files: 3, per_acquisition: 2, acquisitions: 400, timeout: Duration::from_secs(10) };
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let result = pool.install(|| lock::run(&dir, config, &LockKind::ALL, &io::bench::Options::default())).unwrap();
+    for kind in &result.kinds {
+        assert_eq!((kind.acquired, kind.timed_out, kind.lost_updates), (400, 0, 0), "{}", kind.kind);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn lock_files_never_taken_count_nothing() {
+    let dir = lock_dir("untaken");
+    let config = lock::LockConfig { lock_files: 50, per_acquisition: 1, acquisitions: 1, timeout: Duration::from_secs(10) };
+    let result = lock::run(&dir, config, &LockKind::ALL, &io::bench::Options::default()).unwrap();
+    for kind in &result.kinds {
+        assert_eq!((kind.acquired, kind.lost_updates), (1, 0), "{}", kind.kind);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_io")).args(["bench", "lock", "--acquisitions", "0"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--acquisitions expects a positive value"));
+}
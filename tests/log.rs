@@ -0,0 +1,50 @@
+use std::sync::{Arc, Mutex};
+
+use io::log::{self, Event, Format, Level};
+
+// The level and sink are process-wide, so everything that sets them is one test.
+#[test]
+fn events_reach_the_sink_with_their_spans() {
+    let seen: Arc<Mutex<Vec<String>>> = Arc::default();
+    let sink = seen.clone();
+    log::set_sink(Some(Box::new(move |event: &Event| sink.lock().unwrap().push(event.to_json().to_string()))));
+    log::set_level(Level::Info);
+
+    {
+        let _strategy = log::span("strategy", "Smart");
+        let _phase = log::span("phase", "update");
+        log::event(Level::Warn, "test", "slow file", &[("path", "/tmp/a b".to_string())]);
+        log::event(Level::Debug, "test", "dropped", &[]);
+    }
+    log::info("test", "outside");
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [
+            r#"{"level":"warn","target":"test","message":"slow file","spans":{"strategy":"Smart","phase":"update"},"fields":{"path":"/tmp/a b"}}"#,
+            r#"{"level":"info","target":"test","message":"outside","spans":{},"fields":{}}"#,
+        ]
+    );
+
+    // At debug, closing a span is an event too.
+    log::set_level(Level::Debug);
+    seen.lock().unwrap().clear();
+    drop(log::span("phase", "read"));
+    assert!(seen.lock().unwrap()[0].contains(r#""message":"span closed""#));
+    assert!(!log::enabled(Level::Trace));
+
+    log::set_level(Level::Info);
+    log::set_sink(None);
+}
+
+#[test]
+fn text_events_read_as_one_line() {
+    let spans = [("strategy", "Smart".to_string()), ("phase", "read".to_string())];
+    let fields = [("path", "/tmp/x".to_string()), ("error", "no such file".to_string())];
+    let event = Event { level: Level::Error, target: "bench", message: "failed", fields: &fields, spans: &spans };
+    assert_eq!(event.to_string(), r#"error [Smart/read] bench: failed path=/tmp/x error="no such file""#);
+
+    assert_eq!("trace".parse::<Level>().unwrap(), Level::Trace);
+    assert!("loud".parse::<Level>().is_err());
+    assert_eq!("json".parse::<Format>().unwrap(), Format::Json);
+    assert!(Level::Error < Level::Trace);
+}
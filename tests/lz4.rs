@@ -0,0 +1,55 @@
+use io::lz4;
+
+fn hex(text: &str) -> Vec<u8> {
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+}
+
+fn text() -> Vec<u8> {
+    let mut text = b"the quick brown fox jumps over the lazy dog; the lazy dog sleeps. ".repeat(3);
+    text.extend_from_slice(b"pack my box with five dozen liquor jugs!");
+    text
+}
+
+#[test]
+fn reads_frames_the_lz4_tool_writes() {
+    // `lz4 -BD`: linked blocks and a content checksum.
+    let linked = hex("04224d186440a768000000f01074686520717569636b2062726f776e20666f78206a756d7073206f766572201f00996c617a7920646f673b0e008120736c656570732e15000f42006df0197061636b206d7920626f782077697468206669766520646f7a656e206c6971756f72206a75677321000000002474e057");
+    assert_eq!(lz4::decompress(&linked).unwrap(), text());
+    // `lz4 -BX --content-size`: block checksums and the content size too.
+    let checked = hex("04224d187c40ee00000000000000d568000000f01074686520717569636b2062726f776e20666f78206a756d7073206f766572201f00996c617a7920646f673b0e008120736c656570732e15000f42006df0197061636b206d7920626f782077697468206669766520646f7a656e206c6971756f72206a7567732102c026b8000000002474e057");
+    assert_eq!(lz4::decompress(&checked).unwrap(), text());
+
+    let mut corrupt = linked.clone();
+    let last = corrupt.len() - 1;
+    corrupt[last] ^= 1;
+    assert_eq!(lz4::decompress(&corrupt).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    assert!(lz4::decompress(&linked[..linked.len() - 6]).is_err());
+}
+
+#[test]
+fn round_trips_every_size() {
+    let mut state = 1u32;
+    let noisy: Vec<u8> = (0..5 << 20)
+        .map(|i: usize| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            if state >> 28 == 0 { (state >> 16) as u8 } else { b"0123456789abcdef"[i / 7 % 16] }
+        })
+        .collect();
+    for len in [0, 1, 12, 13, 100, 65_536, 70_000, noisy.len()] {
+        let data = &noisy[..len];
+        let frame = lz4::compress(data);
+        assert_eq!(lz4::decompress(&frame).unwrap(), data, "length {}", len);
+    }
+    assert!(lz4::compress(&noisy).len() < noisy.len() / 2);
+
+    // Incompressible blocks are stored, so the frame barely grows.
+    let random: Vec<u8> = (0..100_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 11) as u8).collect();
+    let frame = lz4::compress(&random);
+    assert!(frame.len() <= random.len() + 32);
+    assert_eq!(lz4::decompress(&frame).unwrap(), random);
+
+    // Frames concatenate.
+    let mut two = lz4::compress(b"first ");
+    two.extend_from_slice(&lz4::compress(b"second"));
+    assert_eq!(lz4::decompress(&two).unwrap(), b"first second");
+}
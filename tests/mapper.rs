@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use io::mapper::{self, EncryptedNames, Identity, PathMapper, Prefix, Sharded};
+
+#[test]
+fn simple_mappers_round_trip() {
+    assert_eq!(Identity.map("a/b"), PathBuf::from("a/b"));
+    assert_eq!(Identity.unmap(Path::new("a/b")).as_deref(), Some("a/b"));
+
+    let prefix = Prefix::new("tenant");
+    assert_eq!(prefix.map("a/b"), PathBuf::from("tenant/a/b"));
+    assert_eq!(prefix.unmap(Path::new("tenant/a/b")).as_deref(), Some("a/b"));
+    assert_eq!(prefix.unmap(Path::new("other/a/b")), None);
+}
+
+#[test]
+fn sharding_spreads_names_over_hash_directories() {
+    let sharded = Sharded::default();
+    let path = sharded.map("file.txt");
+    let components: Vec<String> = path.iter().map(|c| c.to_string_lossy().into_owned()).collect();
+    assert_eq!(components.len(), 3);
+    assert!(components[..2].iter().all(|shard| shard.len() == 2 && shard.chars().all(|c| c.is_ascii_hexdigit())));
+    assert_eq!(components[2], "file.txt");
+    assert_eq!(sharded.unmap(&path).as_deref(), Some("file.txt"));
+    // Only the shards the name hashes to are accepted.
+    assert_eq!(sharded.unmap(Path::new("zz/zz/file.txt")), None);
+    assert_eq!(sharded.unmap(Path::new(&components[0])), None);
+
+    let wide = Sharded { levels: 3, width: 4 };
+    assert_eq!(wide.map("x").iter().count(), 4);
+    let spread: std::collections::HashSet<PathBuf> = (0..256).map(|i| sharded.map(&i.to_string()).parent().unwrap().to_path_buf()).collect();
+    assert!(spread.len() > 200, "{}", spread.len());
+}
+
+#[test]
+fn encrypted_names_hide_the_name_and_need_the_key() {
+    let names = EncryptedNames::new(0x5eed);
+    let path = names.map("invoices/2024.csv");
+    let hex = path.to_str().unwrap();
+    assert!(!hex.contains("invoices"));
+    assert_eq!(hex.len(), 16 + 2 * "invoices/2024.csv".len());
+    assert_eq!(names.map("invoices/2024.csv"), path);
+    assert_eq!(names.unmap(&path).as_deref(), Some("invoices/2024.csv"));
+    assert_eq!(EncryptedNames::new(0x5eee).unmap(&path), None);
+    assert_eq!(names.unmap(Path::new("not hex at all")), None);
+    assert_eq!(names.unmap(Path::new("0123")), None);
+}
+
+#[test]
+fn a_layout_maps_a_name_to_the_same_path_every_time() {
+    let layout = EncryptedNames::new(0x5eed).then(Sharded::default());
+    let path = layout.map("invoices/2024.csv-long-name-here");
+    assert_eq!(path, PathBuf::from("79/2c/6f8b40814f0b0b1bc1eb3d208bd63df52265d6131312b30eac5ec1e84e1518f33fe074ed1bb590d1"));
+    assert_eq!(layout.unmap(&path).as_deref(), Some("invoices/2024.csv-long-name-here"));
+}
+
+#[test]
+fn batches_get_their_directories_made() {
+    let root = std::env::temp_dir().join(format!("io-mapper-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let layout = Sharded::default().then(Prefix::new("tenant"));
+    let names: Vec<String> = (0..50).map(|i| format!("file_{}", i)).collect();
+    let paths = mapper::paths(&root, &layout, names.iter().map(String::as_str));
+    assert_eq!(paths.len(), 50);
+    assert!(paths.iter().all(|path| path.starts_with(root.join("tenant"))));
+    mapper::create_dirs(&paths).unwrap();
+    for (name, path) in names.iter().zip(&paths) {
+        fs::write(path, name).unwrap();
+        assert_eq!(layout.unmap(path.strip_prefix(&root).unwrap()).as_ref(), Some(name));
+    }
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn names_that_would_escape_the_root_are_rejected() {
+    assert!(mapper::check_name("a/b.txt").is_ok());
+    for name in ["", "/etc/passwd", "../up", "a/../../b", "./a", "dir/"] {
+        let e = mapper::check_name(name).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput, "{:?}", name);
+    }
+}
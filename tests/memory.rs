@@ -0,0 +1,36 @@
+#![cfg(feature = "bench")]
+
+use std::env;
+use std::fs;
+use std::time::Duration;
+
+use io::bench::{self, Options, STRATEGIES, Workload};
+use io::memory::{Memory, PeakSampler};
+
+#[test]
+fn the_peak_covers_the_whole_sampling() {
+    let sampler = PeakSampler::start(Duration::from_millis(1));
+    let held = vec![1u8; 32 << 20];
+    std::thread::sleep(Duration::from_millis(20));
+    let peak = sampler.finish();
+    assert_eq!(peak.is_some(), Memory::current().is_some());
+    if let Some(peak) = peak {
+        assert!(peak.rss >= held.len() as u64, "{:?}", peak);
+        assert!(peak.regions > 0);
+    }
+    drop(held);
+}
+
+#[test]
+fn phases_are_only_sampled_when_asked() {
+    let dir = env::temp_dir().join(format!("io-memory-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(16, 1024), ..Options::default() };
+    let quiet = bench::run_strategy(&STRATEGIES[0], &dir, &options).unwrap();
+    assert!(quiet.memory.peak().is_none());
+
+    let options = Options { sample_memory: true, ..options };
+    let sampled = bench::run_strategy(&STRATEGIES[0], &dir, &options).unwrap();
+    assert_eq!(sampled.memory.peak().is_some(), Memory::current().is_some());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,76 @@
+#![cfg(all(unix, feature = "bench"))]
+
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use io::bench::{Options, Workload};
+use io::metadata::{META_STRATEGIES, MetaPhase};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-metadata-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn each_phase_changes_what_it_says() {
+    let dir = scratch("phases");
+    let options = Options { workload: Workload::new(12, 100), ..Options::default() };
+    for strategy in META_STRATEGIES {
+        let paths = options.workload.paths(&dir);
+        for path in &paths {
+            fs::write(path, b"x").unwrap();
+            fs::set_permissions(path, fs::Permissions::from_mode(0o644)).unwrap();
+        }
+        match strategy.run_phase(MetaPhase::Stat, &paths, &options) {
+            Err(e) if e.kind() == ErrorKind::Unsupported => {}
+            result => result.unwrap(),
+        }
+
+        if strategy.supports(MetaPhase::Chmod) {
+            strategy.run_phase(MetaPhase::Chmod, &paths, &options).unwrap();
+            assert!(paths.iter().all(|path| fs::metadata(path).unwrap().permissions().mode() & 0o777 == 0o600), "{}", strategy.label);
+        } else {
+            assert_eq!(strategy.run_phase(MetaPhase::Chmod, &paths, &options).unwrap_err().kind(), ErrorKind::Unsupported);
+        }
+        if strategy.supports(MetaPhase::Utimes) {
+            let atime = fs::metadata(&paths[0]).unwrap().atime();
+            strategy.run_phase(MetaPhase::Utimes, &paths, &options).unwrap();
+            assert!(paths.iter().all(|path| fs::metadata(path).unwrap().mtime() == 1_000_000_000), "{}", strategy.label);
+            assert_eq!(fs::metadata(&paths[0]).unwrap().atime(), atime);
+        }
+        if strategy.supports(MetaPhase::Touch) {
+            let before = SystemTime::now() - Duration::from_secs(60);
+            strategy.run_phase(MetaPhase::Touch, &paths, &options).unwrap();
+            assert!(paths.iter().all(|path| fs::metadata(path).unwrap().modified().unwrap() > before), "{}", strategy.label);
+        }
+        // Only metadata changed.
+        assert!(paths.iter().all(|path| fs::read(path).unwrap() == b"x"));
+        for path in &paths {
+            fs::remove_file(path).unwrap();
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_run_times_the_phases_its_strategy_has_and_cleans_up() {
+    let dir = scratch("run");
+    let options = Options { workload: Workload::new(20, 64), ..Options::default() };
+    for strategy in META_STRATEGIES {
+        let result = match io::metadata::run_strategy(strategy, &dir, &options) {
+            Err(e) if e.kind() == ErrorKind::Unsupported => continue,
+            result => result.unwrap(),
+        };
+        assert!(result.failures.is_empty(), "{:?}", result.failures);
+        for phase in MetaPhase::ALL {
+            assert_eq!(result.times.get(phase).is_some(), strategy.supports(phase), "{} {}", strategy.label, phase.name());
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0, "{}", strategy.label);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::Path;
+
+use io::mirror::{self, Compare, SyncReport};
+
+fn scratch(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-mirror-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn write(path: &Path, contents: &[u8]) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+#[test]
+fn only_differences_are_applied() {
+    let dir = scratch("diff");
+    let (src, dst) = (dir.join("src"), dir.join("dst"));
+    write(&src.join("same"), b"same");
+    write(&src.join("a/grown"), b"longer now");
+    write(&src.join("a/b/new"), b"new");
+    write(&dst.join("same"), b"same");
+    write(&dst.join("a/grown"), b"short");
+    write(&dst.join("gone"), b"gone");
+    write(&dst.join("old/deep/file"), b"old");
+
+    let report = mirror::sync_trees(&src, &dst, Compare::Contents).unwrap();
+    assert_eq!(report, SyncReport { created: 1, updated: 1, deleted: 2, unchanged: 1, bytes: 13 });
+    assert_eq!(fs::read(dst.join("a/grown")).unwrap(), b"longer now");
+    assert_eq!(fs::read(dst.join("a/b/new")).unwrap(), b"new");
+    assert!(!dst.join("gone").exists() && !dst.join("old").exists());
+
+    // A second sync by either comparison has nothing to do.
+    for compare in Compare::ALL {
+        let report = mirror::sync_trees(&src, &dst, compare).unwrap();
+        assert_eq!(report, SyncReport { unchanged: 3, ..SyncReport::default() }, "{}", compare.name());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn same_size_rewrites_are_found_by_contents() {
+    let dir = scratch("rewrite");
+    let (src, dst) = (dir.join("src"), dir.join("dst"));
+    write(&src.join("file"), b"aaaa");
+    mirror::sync_trees(&src, &dst, Compare::SizeTime).unwrap();
+    assert_eq!(fs::metadata(src.join("file")).unwrap().modified().unwrap(), fs::metadata(dst.join("file")).unwrap().modified().unwrap());
+
+    // Same size, and the time put back: only the contents tell.
+    let modified = fs::metadata(src.join("file")).unwrap().modified().unwrap();
+    fs::write(src.join("file"), b"bbbb").unwrap();
+    fs::File::options().write(true).open(src.join("file")).unwrap().set_modified(modified).unwrap();
+    assert_eq!(mirror::sync_trees(&src, &dst, Compare::SizeTime).unwrap().unchanged, 1);
+    assert_eq!(mirror::sync_trees(&src, &dst, Compare::Contents).unwrap().updated, 1);
+    assert_eq!(fs::read(dst.join("file")).unwrap(), b"bbbb");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn entries_that_changed_kind_are_replaced() {
+    let dir = scratch("kinds");
+    let (src, dst) = (dir.join("src"), dir.join("dst"));
+    write(&src.join("was_dir"), b"file now");
+    write(&src.join("was_file/inner"), b"inside");
+    write(&src.join("target"), b"t");
+    io::links::symlink(Path::new("target"), &src.join("link")).unwrap();
+    write(&dst.join("was_dir/inner"), b"x");
+    write(&dst.join("was_file"), b"x");
+    write(&dst.join("target"), b"t");
+    io::links::symlink(Path::new("elsewhere"), &dst.join("link")).unwrap();
+
+    let report = mirror::sync_trees(&src, &dst, Compare::SizeTime).unwrap();
+    assert_eq!((report.created, report.deleted), (2, 2));
+    assert_eq!(fs::read(dst.join("was_dir")).unwrap(), b"file now");
+    assert_eq!(fs::read(dst.join("was_file/inner")).unwrap(), b"inside");
+    assert_eq!(fs::read_link(dst.join("link")).unwrap(), Path::new("target"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn incremental_syncs_find_exactly_the_changes() {
+    let dir = scratch("bench");
+    fs::create_dir_all(&dir).unwrap();
+    let options = io::bench::Options { workload: io::bench::Workload::new(50, 100), verify: true, ..io::bench::Options::default() };
+    let result = mirror::run(&dir, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!((result.changes.updated, result.changes.deleted, result.changes.created), (5, 5, 5));
+    assert_eq!(result.compares.iter().map(|compare| compare.compare).collect::<Vec<_>>(), Compare::ALL);
+    assert!(result.compares.iter().all(|compare| compare.report.bytes == 1000));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn equal_contents_take_the_source_time() {
+    let dir = scratch("touch");
+    let (src, dst) = (dir.join("src"), dir.join("dst"));
+    let mut contents = vec![1u8; 200 * 1024];
+    write(&src.join("file"), &contents);
+    write(&dst.join("file"), &contents);
+    let earlier = fs::metadata(src.join("file")).unwrap().modified().unwrap() - std::time::Duration::from_secs(60);
+    fs::File::options().write(true).open(dst.join("file")).unwrap().set_modified(earlier).unwrap();
+
+    assert_eq!(mirror::sync_trees(&src, &dst, Compare::Contents).unwrap(), SyncReport { unchanged: 1, ..SyncReport::default() });
+    assert_eq!(mirror::sync_trees(&src, &dst, Compare::SizeTime).unwrap(), SyncReport { unchanged: 1, ..SyncReport::default() });
+
+    // Only the last byte differs, past the comparison's first block.
+    *contents.last_mut().unwrap() = 2;
+    write(&src.join("file"), &contents);
+    assert_eq!(mirror::sync_trees(&src, &dst, Compare::Contents).unwrap().updated, 1);
+    assert_eq!(fs::read(dst.join("file")).unwrap(), contents);
+    fs::remove_dir_all(&dir).unwrap();
+}
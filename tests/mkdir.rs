@@ -0,0 +1,54 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use io::mkdir;
+use io::retry::RetryPolicy;
+
+#[test]
+fn shared_ancestors_are_made_once() {
+    let dir = std::env::temp_dir().join(format!("io-mkdir-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("a/existing")).unwrap();
+    let leaves: Vec<PathBuf> = ["a/b/c/d", "a/b/c/e", "a/b/f", "a/existing/g", "a/existing"].iter().map(|leaf| dir.join(leaf)).collect();
+    // dir/a exists; b, c, d, e, f and g are new.
+    assert_eq!(mkdir::create_dirs_many(&leaves, &RetryPolicy::default()).unwrap(), 6);
+    assert!(leaves.iter().all(|leaf| leaf.is_dir()));
+    assert_eq!(mkdir::create_dirs_many(&leaves, &RetryPolicy::default()).unwrap(), 0);
+
+    fs::write(dir.join("a/file"), b"").unwrap();
+    let error = mkdir::create_dirs_many(&[dir.join("a/file/below")], &RetryPolicy::never()).unwrap_err();
+    assert!(error.to_string().contains("file"), "{}", error);
+    let error = mkdir::create_dirs_many(&[dir.join("a/file")], &RetryPolicy::never()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn concurrent_calls_over_one_tree_both_succeed() {
+    let dir = std::env::temp_dir().join(format!("io-mkdir-race-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let leaves: Vec<PathBuf> = (0..200).map(|i| dir.join(format!("{}/{}/{}", i % 3, i % 7, i))).collect();
+    let created: usize = std::thread::scope(|scope| {
+        let calls: Vec<_> = (0..4).map(|_| scope.spawn(|| mkdir::create_dirs_many(&leaves, &RetryPolicy::default()).unwrap())).collect();
+        calls.into_iter().map(|call| call.join().unwrap()).sum()
+    });
+    // Every directory was made by exactly one of the calls.
+    assert_eq!(created, 1 + 3 + 21 + 200);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn both_methods_make_the_whole_tree() {
+    let dir = std::env::temp_dir().join(format!("io-mkdir-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = io::bench::Options { workload: io::bench::Workload::new(64, 0), ..io::bench::Options::default() };
+    let result = mkdir::run(&dir, 4, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.times.iter().map(|(method, _)| *method).collect::<Vec<_>>(), mkdir::Method::ALL);
+    // The root, two shared directories, then 8 and 64.
+    assert_eq!(result.dirs, 1 + 2 + 8 + 64);
+    assert_eq!(mkdir::leaf_path(&dir, 3, 9), dir.join("d0/d1/d1"));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,45 @@
+#![cfg(all(feature = "libc", feature = "mmap"))]
+
+use std::fs;
+
+use io::mmap::MmapFile;
+
+#[test]
+fn mapped_files_grow_shrink_and_empty_with_their_mapping() {
+    let dir = std::env::temp_dir().join(format!("io-mmap-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("mapped");
+
+    let mut mapped = MmapFile::open(&path).unwrap();
+    assert!(mapped.is_empty());
+    assert_eq!(mapped.as_slice(), b"");
+    mapped.write_at(0, b"").unwrap();
+    assert!(mapped.is_empty());
+
+    // Past the end of a page, then across several.
+    mapped.write_at(4090, b"0123456789").unwrap();
+    assert_eq!(mapped.len(), 4100);
+    assert_eq!(&mapped.as_slice()[4090..], b"0123456789");
+    assert!(mapped.as_slice()[..4090].iter().all(|&byte| byte == 0));
+    let big: Vec<u8> = (0..50_000).map(|i| (i % 251) as u8).collect();
+    mapped.write_at(100, &big).unwrap();
+    assert_eq!(mapped.len(), 50_100);
+    assert_eq!(&mapped.as_slice()[100..], &big[..]);
+
+    mapped.replace(b"short").unwrap();
+    mapped.flush().unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"short");
+    mapped.replace(b"").unwrap();
+    assert!(mapped.is_empty());
+    mapped.flush().unwrap();
+    drop(mapped);
+    assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+
+    fs::write(&path, b"existing").unwrap();
+    let mut mapped = MmapFile::open(&path).unwrap();
+    assert_eq!(mapped.as_slice(), b"existing");
+    mapped.write_at(0, b"E").unwrap();
+    drop(mapped);
+    assert_eq!(fs::read(&path).unwrap(), b"Existing");
+    fs::remove_dir_all(&dir).unwrap();
+}
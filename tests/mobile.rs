@@ -0,0 +1,34 @@
+use std::env;
+
+use io::mobile;
+
+#[test]
+fn android_packages_come_from_the_process_name() {
+    assert_eq!(mobile::android_package(b"com.example.app\0").as_deref(), Some("com.example.app"));
+    assert_eq!(mobile::android_package(b"com.example.app:sync\0").as_deref(), Some("com.example.app"));
+    assert_eq!(mobile::android_package(b"org.app_2.io").as_deref(), Some("org.app_2.io"));
+    assert_eq!(mobile::android_package(b"/data/local/tmp/mobile\0/data/local/tmp\0"), None);
+    assert_eq!(mobile::android_package(b"sh\0-c\0"), None);
+    assert_eq!(mobile::android_package(b"com..app\0"), None);
+    assert_eq!(mobile::android_package(b""), None);
+}
+
+#[test]
+fn desktops_keep_the_temp_directory() {
+    if mobile::MOBILE {
+        return;
+    }
+    assert_eq!(mobile::cache_dir(), None);
+    assert_eq!(mobile::temp_dir(), env::temp_dir());
+}
+
+#[test]
+fn mobile_refuses_pinning() {
+    let cpu = io::platform::allowed_cpus()[0];
+    let pinned = std::thread::spawn(move || io::pinning::pin_thread(cpu)).join().unwrap();
+    if mobile::MOBILE {
+        assert_eq!(pinned.unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+    } else {
+        assert!(pinned.is_ok());
+    }
+}
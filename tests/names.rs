@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use io::names::{self, MAX_NAME, Names};
+
+#[test]
+fn every_pattern_makes_distinct_names_that_fit() {
+    for names in Names::ALL {
+        let all: HashSet<String> = (0..500).map(|i| names.name(i)).collect();
+        assert_eq!(all.len(), 500, "{}", names);
+        for name in &all {
+            assert!(!name.is_empty() && name.len() <= MAX_NAME, "{:?}", name);
+            assert!(!name.contains('/') && !name.contains('\0'), "{:?}", name);
+        }
+        assert_eq!(names.label().parse::<Names>().unwrap(), names);
+    }
+    assert!((0..10).all(|i| Names::Long.name(i).len() == MAX_NAME));
+    assert_eq!(Names::Ascii.name(7), "file_7.txt");
+    assert!("emoji".parse::<Names>().is_err());
+}
+
+#[test]
+fn paths_stripe_and_can_be_created() {
+    let dir = std::env::temp_dir().join(format!("io-names-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let dirs: Vec<PathBuf> = (0..2).map(|i| dir.join(i.to_string())).collect();
+    for d in &dirs {
+        std::fs::create_dir_all(d).unwrap();
+    }
+    let paths = names::paths(&dirs, 12, Names::Mixed);
+    assert_eq!(paths.len(), 12);
+    for (i, path) in paths.iter().enumerate() {
+        assert!(path.starts_with(&dirs[i % 2]));
+        std::fs::write(path, i.to_string()).unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), i.to_string());
+    }
+    assert_eq!(names::paths(&dirs[..1], 1, Names::Ascii), vec![dirs[0].join("file_0.txt")]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn verbatim_paths() {
+    assert_eq!(names::verbatim(r"C:\data\CON.txt").as_deref(), Some(r"\\?\C:\data\CON.txt"));
+    assert_eq!(names::verbatim("d:/a/b").as_deref(), Some(r"\\?\d:\a\b"));
+    assert_eq!(names::verbatim(r"\\server\share\x").as_deref(), Some(r"\\?\UNC\server\share\x"));
+    assert_eq!(names::verbatim(r"\\?\C:\x"), None);
+    assert_eq!(names::verbatim(r"relative\x"), None);
+    if !cfg!(windows) {
+        assert_eq!(names::long_path("/tmp/x".as_ref()), PathBuf::from("/tmp/x"));
+    }
+}
+
+#[test]
+fn derived_names_stay_within_the_limit() {
+    let short = std::path::Path::new("/d/file_1.txt");
+    assert_eq!(names::sibling(short, ".", ".tmp"), PathBuf::from("/d/.file_1.txt.tmp"));
+    let long = PathBuf::from("/d").join(Names::Long.name(1));
+    let other = PathBuf::from("/d").join(Names::Long.name(2));
+    let (a, b) = (names::sibling(&long, ".", ".1234.0.tmp"), names::sibling(&other, ".", ".1234.0.tmp"));
+    for sibling in [&a, &b] {
+        let name = sibling.file_name().unwrap().to_str().unwrap();
+        assert_eq!(name.len(), MAX_NAME, "{}", name);
+        assert!(name.starts_with(".file_") && name.ends_with(".1234.0.tmp"), "{}", name);
+        assert_eq!(sibling.parent(), long.parent());
+    }
+    assert_ne!(a, b);
+    // Cut on a character boundary, however wide the characters.
+    let wide = PathBuf::from("🚀".repeat(MAX_NAME / 4));
+    assert!(names::sibling(&wide, "added-", "").to_str().unwrap().len() <= MAX_NAME);
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn long_names_run_through_every_phase() {
+    let dir = std::env::temp_dir().join(format!("io-names-long-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for command in [&[][..], &["xattr"], &["mirror"]] {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_io"))
+            .arg("bench")
+            .args(command)
+            .args(["--names", "long", "--files", "20", "--size", "1K"])
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}: {}", command, String::from_utf8_lossy(&output.stderr));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
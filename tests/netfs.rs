@@ -0,0 +1,63 @@
+use std::path::Path;
+use std::time::Duration;
+
+use io::netfs::{self, Latency, Rpc, RpcSample, SimulatedNetwork};
+
+const MOUNTSTATS: &str = "\
+device proc mounted on /proc with fstype proc
+device server:/export mounted on /mnt/nfs with fstype nfs4 statvers=1.1
+\topts:\trw,vers=4.2
+\tper-op statistics
+\t        NULL: 1 1 0 44 24 0 0 0 0
+\t        READ: 10 10 0 1640 41200 0 20 25 0
+\t        OPEN: 40 40 0 9120 14400 1 80 95 0
+\t      LOOKUP: 25 25 0 4100 6000 0 30 35 25
+device server:/other mounted on /mnt/nfs/deeper with fstype nfs statvers=1.1
+\tper-op statistics
+\t     GETATTR: 7 7 0 700 800 0 7 8 0
+";
+
+#[test]
+fn finds_the_deepest_nfs_mount() {
+    assert_eq!(netfs::nfs_mount(MOUNTSTATS, Path::new("/mnt/nfs/a/b")), Some("/mnt/nfs".into()));
+    assert_eq!(netfs::nfs_mount(MOUNTSTATS, Path::new("/mnt/nfs/deeper/c")), Some("/mnt/nfs/deeper".into()));
+    assert_eq!(netfs::nfs_mount(MOUNTSTATS, Path::new("/proc/self")), None);
+    assert_eq!(netfs::nfs_mount(MOUNTSTATS, Path::new("/mnt/nfsish")), None);
+}
+
+#[test]
+fn parses_per_op_counts_of_one_mount() {
+    let sample = netfs::parse_rpcs(MOUNTSTATS, Path::new("/mnt/nfs")).unwrap();
+    assert_eq!(sample.0.len(), 4);
+    assert_eq!(sample.0[2], Rpc { op: "OPEN".to_string(), count: 40, rtt_ms: 80 });
+    assert_eq!(sample.total(), 76);
+    let deeper = netfs::parse_rpcs(MOUNTSTATS, Path::new("/mnt/nfs/deeper")).unwrap();
+    assert_eq!(deeper.0, vec![Rpc { op: "GETATTR".to_string(), count: 7, rtt_ms: 7 }]);
+    assert_eq!(netfs::parse_rpcs(MOUNTSTATS, Path::new("/proc")), None);
+}
+
+#[test]
+fn samples_subtract_to_a_phase() {
+    let rpc = |op: &str, count, rtt_ms| Rpc { op: op.to_string(), count, rtt_ms };
+    let before = RpcSample(vec![rpc("OPEN", 10, 20), rpc("LOOKUP", 5, 5)]);
+    let after = RpcSample(vec![rpc("OPEN", 30, 60), rpc("LOOKUP", 15, 25), rpc("REMOVE", 4, 8)]);
+    let phase = after.since(&before);
+    assert_eq!(phase.total(), 34);
+    assert_eq!(phase.mean_rtt(), Some(Duration::from_secs_f64(0.068 / 34.0)));
+    let top: Vec<&str> = phase.top(2).iter().map(|rpc| rpc.op.as_str()).collect();
+    assert_eq!(top, ["OPEN", "LOOKUP"]);
+    assert_eq!(RpcSample::default().mean_rtt(), None);
+}
+
+#[test]
+fn round_trips_stay_within_the_jitter() {
+    let latency = Latency { mean: Duration::from_micros(200), jitter: Duration::from_micros(100) };
+    let network = SimulatedNetwork::new(latency);
+    for _ in 0..20 {
+        let wait = network.round_trip();
+        assert!(wait >= Duration::from_micros(100) && wait <= Duration::from_micros(300), "{:?}", wait);
+    }
+    assert_eq!(network.round_trips(), 20);
+    assert!(network.waited() >= Duration::from_micros(2000));
+    assert_eq!(network.latency(), latency);
+}
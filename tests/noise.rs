@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use io::noise::{self, Concern, Quiet, Readings};
+
+fn readings() -> Readings {
+    Readings {
+        governors: vec!["performance".to_string()],
+        frequency_mhz: Some(2400.0),
+        max_frequency_mhz: Some(3600.0),
+        load: Some(0.5),
+        cpus: 4,
+        available_memory: Some(8 << 30),
+        total_memory: Some(16 << 30),
+        temperature: Some(45.0),
+    }
+}
+
+#[test]
+fn a_quiet_machine_raises_no_concern() {
+    assert_eq!(readings().concerns(&Quiet::default()), vec![]);
+    assert_eq!(Readings::default().concerns(&Quiet::default()), vec![], "what can't be read raises nothing");
+}
+
+#[test]
+fn each_reading_is_held_against_its_limit() {
+    let noisy = Readings {
+        governors: vec!["performance".to_string(), "powersave".to_string()],
+        load: Some(3.0),
+        available_memory: Some(1 << 30),
+        temperature: Some(71.0),
+        ..readings()
+    };
+    let concerns = noisy.concerns(&Quiet::default());
+    assert_eq!(concerns, vec![Concern::Governor("powersave".to_string()), Concern::Load { load: 3.0, cpus: 4 }, Concern::Memory { available: 1 << 30, total: 16 << 30 }, Concern::Temperature(71.0)]);
+    assert_eq!(concerns.iter().filter(|concern| concern.passes_with_time()).count(), 3);
+    assert_eq!(concerns[1].to_string(), "load average 3.00 on 4 CPUs");
+}
+
+#[test]
+fn readings_round_trip_through_json() {
+    for readings in [readings(), Readings::default()] {
+        assert_eq!(Readings::from_json(&io::json::parse(&readings.to_json().to_string()).unwrap()), Some(readings));
+    }
+}
+
+#[test]
+fn quiesce_returns_at_once_when_nothing_can_wait_out() {
+    let lenient = Quiet { max_load_per_cpu: f64::INFINITY, min_available_memory: 0.0, max_temperature: f64::INFINITY };
+    let start = Instant::now();
+    let (readings, waited) = noise::quiesce(&std::env::temp_dir(), &lenient, Duration::from_secs(60), Duration::from_secs(1));
+    assert!(waited < Duration::from_secs(1) && start.elapsed() < Duration::from_secs(5));
+    assert!(readings.concerns(&lenient).iter().all(|concern| !concern.passes_with_time()));
+    assert!(readings.cpus > 0);
+}
+
+#[test]
+fn quiesce_gives_up_after_its_timeout() {
+    let strict = Quiet { max_load_per_cpu: -1.0, ..Quiet::default() };
+    if Readings::sample(&std::env::temp_dir()).load.is_none() {
+        return;
+    }
+    let (readings, waited) = noise::quiesce(&std::env::temp_dir(), &strict, Duration::from_millis(300), Duration::from_millis(100));
+    assert!(waited >= Duration::from_millis(300), "{:?}", waited);
+    assert!(readings.concerns(&strict).iter().any(|concern| matches!(concern, Concern::Load { .. })));
+}
@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use io::oplog::{self, Kind, Mode, Op, Timing, Trace};
+
+const STRACE: &str = r#"
+1200  1697040000.100000 openat(AT_FDCWD, "/proj/src", O_RDONLY|O_CLOEXEC|O_DIRECTORY) = 4
+1200  1697040000.100100 openat(4, "main.rs", O_RDONLY|O_CLOEXEC) = 5
+1200  1697040000.100200 read(5, "fn main() {}\n", 8192) = 13
+1201  1697040000.100300 mkdir("/proj/target", 0777 <unfinished ...>
+1200  1697040000.100400 openat(AT_FDCWD, "/proj/missing", O_RDONLY) = -1 ENOENT (No such file or directory)
+1201  1697040000.100500 <... mkdir resumed>) = 0
+1200  1697040000.100600 close(5)                = 0
+1201  1697040000.101000 openat(AT_FDCWD, "/proj/target/a\"b.tmp", O_WRONLY|O_CREAT|O_TRUNC, 0666) = 6
+1201  1697040000.101100 write(6, "\0\0\0"..., 4096) = 4096
+1201  1697040000.101200 renameat2(AT_FDCWD, "/proj/target/a\"b.tmp", AT_FDCWD, "/proj/target/out", 0) = 0
+1201  1697040000.101300 +++ exited with 0 +++
+"#;
+
+#[test]
+fn strace_calls_become_operations_on_paths() {
+    let trace = Trace::parse_strace(STRACE).unwrap();
+    let ops: Vec<&Op> = trace.events.iter().map(|event| &event.op).collect();
+    let main = PathBuf::from("/proj/src/main.rs");
+    let temporary = PathBuf::from("/proj/target/a\"b.tmp");
+    assert_eq!(
+        ops,
+        [
+            &Op::Open { path: main.clone(), mode: Mode::Read },
+            &Op::Read { path: main.clone(), bytes: 13 },
+            &Op::Mkdir { path: PathBuf::from("/proj/target") },
+            &Op::Close { path: main },
+            &Op::Open { path: temporary.clone(), mode: Mode::Write },
+            &Op::Write { path: temporary.clone(), bytes: 4096 },
+            &Op::Rename { from: temporary, to: PathBuf::from("/proj/target/out") },
+        ]
+    );
+    // The directory open, the failed open and the exit line.
+    assert_eq!(trace.skipped, 3);
+    assert_eq!(trace.events[0].at, Duration::from_micros(100));
+    assert_eq!(trace.duration(), Duration::from_micros(1200));
+
+    let written = Trace::parse_jsonl(&trace.to_jsonl()).unwrap();
+    assert_eq!(written.events.iter().map(|event| &event.op).collect::<Vec<_>>(), ops);
+}
+
+#[test]
+fn redirected_descriptors_follow_their_file() {
+    let strace = r#"
+1300  1697040000.100000 openat(AT_FDCWD, "/proj/f", O_WRONLY|O_CREAT|O_TRUNC, 0666) = 3
+1300  1697040000.100100 dup2(3, 1) = 1
+1300  1697040000.100200 close(3) = 0
+1300  1697040000.100300 write(1, "x\n", 2) = 2
+1300  1697040000.100400 dup2(10, 1) = 1
+1300  1697040000.100500 write(1, "y\n", 2) = 2
+"#;
+    let trace = Trace::parse_strace(strace).unwrap();
+    let file = PathBuf::from("/proj/f");
+    assert_eq!(
+        trace.events.iter().map(|event| &event.op).collect::<Vec<_>>(),
+        [&Op::Open { path: file.clone(), mode: Mode::Write }, &Op::Close { path: file.clone() }, &Op::Write { path: file, bytes: 2 }]
+    );
+    // Only the write to the terminal restored on descriptor 1.
+    assert_eq!(trace.skipped, 1);
+}
+
+#[test]
+fn op_logs_replay_against_prepared_files() {
+    let log = r#"
+{"t": 0.0, "op": "open", "path": "src/lib.rs"}
+{"t": 0.001, "op": "read", "path": "src/lib.rs", "bytes": 3000}
+{"op": "close", "path": "src/lib.rs"}
+{"t": 0.002, "op": "mkdir", "path": "target"}
+{"t": 0.003, "op": "open", "path": "target/out.tmp", "mode": "w"}
+{"t": 0.003, "op": "write", "path": "target/out.tmp", "bytes": 5000}
+{"t": 0.004, "op": "rename", "path": "target/out.tmp", "to": "target/out"}
+{"t": 0.005, "op": "unlink", "path": "gone"}
+"#;
+    let trace = Trace::parse_jsonl(log).unwrap();
+    assert_eq!(trace.events[2].at, Duration::from_millis(1));
+
+    let root = std::env::temp_dir().join(format!("io-oplog-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    // The source file, and the file the trace removes without having made it.
+    assert_eq!(oplog::prepare(&trace, &root).unwrap(), 2);
+    assert_eq!(fs::metadata(root.join("src/lib.rs")).unwrap().len(), 3000);
+
+    let report = oplog::replay(&trace, &root, Timing::Compressed).unwrap();
+    assert!(report.failures.is_empty(), "{:?}", report.failures);
+    let stats = |kind: Kind| report.stats.iter().find(|stats| stats.kind == kind).unwrap();
+    assert_eq!(stats(Kind::Read).bytes, 3000);
+    assert_eq!(stats(Kind::Write).bytes, 5000);
+    assert_eq!(stats(Kind::Open).count, 2);
+    assert_eq!(fs::metadata(root.join("target/out")).unwrap().len(), 5000);
+    assert!(!Path::new(&root.join("gone")).exists());
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn timings_parse() {
+    assert_eq!("original".parse::<Timing>().unwrap(), Timing::Original);
+    assert_eq!("2x".parse::<Timing>().unwrap(), Timing::Scaled(2.0));
+    assert!("0x".parse::<Timing>().is_err());
+    assert_eq!("0.001x".parse::<Timing>().unwrap(), Timing::Scaled(0.001));
+    assert!("1e-300x".parse::<Timing>().is_err());
+    assert!("infx".parse::<Timing>().is_err());
+    assert!(Trace::parse_jsonl(r#"{"op": "read", "path": "a"}"#).is_err());
+}
+
+#[test]
+fn files_read_through_several_opens_are_as_large_as_the_furthest_read() {
+    let log = r#"
+{"op": "open", "path": "a"}
+{"op": "read", "path": "a", "bytes": 1000}
+{"op": "read", "path": "a", "bytes": 500}
+{"op": "close", "path": "a"}
+{"op": "open", "path": "a"}
+{"op": "read", "path": "a", "bytes": 1200}
+{"op": "close", "path": "a"}
+{"op": "stat", "path": "b"}
+"#;
+    let trace = Trace::parse_jsonl(log).unwrap();
+    let root = std::env::temp_dir().join(format!("io-oplog-reopen-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    assert_eq!(oplog::prepare(&trace, &root).unwrap(), 2);
+    assert_eq!(fs::metadata(root.join("a")).unwrap().len(), 1500);
+    assert_eq!(fs::metadata(root.join("b")).unwrap().len(), 0);
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,55 @@
+//! Checks that every read order visits each file exactly once, the orders that depend on
+//! file metadata follow it, and files that vanished go last instead of failing the run.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use io::order::{self, ReadOrder};
+
+#[test]
+fn orders_are_permutations() {
+    let dir = env::temp_dir().join(format!("io-order-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<PathBuf> = (0..20).map(|i| dir.join(format!("file_{}.txt", i))).collect();
+    for (i, path) in paths.iter().enumerate() {
+        fs::write(path, vec![b'x'; (i * 7919) % 20 * 100]).unwrap();
+    }
+
+    for read_order in ReadOrder::ALL {
+        let mut indices = order::arrange(read_order, &paths);
+        if read_order == ReadOrder::Size {
+            let sizes: Vec<u64> = indices.iter().map(|&i| fs::metadata(&paths[i]).unwrap().len()).collect();
+            assert!(sizes.is_sorted(), "{:?}", sizes);
+        }
+        indices.sort_unstable();
+        assert_eq!(indices, (0..paths.len()).collect::<Vec<_>>(), "{}", read_order.name());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn vanished_files_go_last() {
+    let dir = env::temp_dir().join(format!("io-order-vanished-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<PathBuf> = (0..6).map(|i| dir.join(format!("file_{}.txt", i))).collect();
+    for (i, path) in paths.iter().enumerate() {
+        fs::write(path, vec![b'x'; (6 - i) * 10]).unwrap();
+    }
+    fs::remove_file(&paths[2]).unwrap();
+    let mut gone = paths.clone();
+    gone.push(env::temp_dir().join(format!("io-order-missing-{}", std::process::id())).join("file"));
+
+    for read_order in [ReadOrder::Directory, ReadOrder::Inode, ReadOrder::Size] {
+        let indices = order::arrange(read_order, &gone);
+        assert_eq!(indices.len(), gone.len());
+        let mut last = indices[5..].to_vec();
+        last.sort_unstable();
+        assert_eq!(last, [2, 6], "{}: {:?}", read_order.name(), indices);
+    }
+
+    // Listed sizes are used as given, without examining the files.
+    let sizes = [5, 4, 3, 2, 1, 0];
+    assert_eq!(order::arrange_sized(ReadOrder::Size, &paths, &sizes), [5, 4, 3, 2, 1, 0]);
+    fs::remove_dir_all(&dir).unwrap();
+}
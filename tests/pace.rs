@@ -0,0 +1,39 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use io::pace::{Pacer, Rate};
+
+#[test]
+fn parses_rates() {
+    assert_eq!("5000".parse::<Rate>().unwrap(), Rate(5000.0));
+    assert_eq!("5k/s".parse::<Rate>().unwrap(), Rate(5000.0));
+    assert_eq!("2.5M".parse::<Rate>().unwrap(), Rate(2.5e6));
+    assert!("0".parse::<Rate>().is_err());
+    assert!("fast".parse::<Rate>().is_err());
+    assert!("1e-300".parse::<Rate>().is_err());
+    assert_eq!("0.001".parse::<Rate>().unwrap().interval(), Duration::from_secs(1000));
+    // Built directly, a rate too slow to schedule is held to the slowest.
+    assert_eq!(Rate(1e-300).interval(), Duration::from_secs(1000));
+    assert_eq!(Rate(0.0).interval(), Duration::from_secs(1000));
+}
+
+#[test]
+fn intended_starts_follow_the_schedule() {
+    let pacer = Pacer::new(Rate(1000.0));
+    let start = Instant::now();
+    let intended: Vec<Instant> = (0..10).map(|_| pacer.next()).collect();
+    assert!(start.elapsed() >= Duration::from_millis(8));
+    for pair in intended.windows(2) {
+        assert_eq!(pair[1] - pair[0], Duration::from_millis(1));
+    }
+    // Slots are a millisecond apart; a loaded machine may miss a few by more than that.
+    let late = pacer.late();
+    assert!(late < 10, "{} of 10 slots late", late);
+
+    // A stall puts the following slots behind; they start at once and count as late.
+    thread::sleep(Duration::from_millis(20));
+    let behind = pacer.next();
+    assert!(behind.elapsed() >= Duration::from_millis(15));
+    assert_eq!(pacer.late(), late + 1);
+    assert_eq!(pacer.issued(), 11);
+}
@@ -0,0 +1,58 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::thread;
+
+use io::pack::Pack;
+
+#[test]
+fn newest_records_win_and_survive_reopening_and_compaction() {
+    let dir = std::env::temp_dir().join(format!("io-pack-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("files.pack");
+    let pack = Pack::create(&path).unwrap();
+    thread::scope(|scope| {
+        for worker in 0..4 {
+            let pack = &pack;
+            scope.spawn(move || {
+                for i in 0..50 {
+                    pack.put(&format!("{}/{}", worker, i), format!("v1 {} {}", worker, i).as_bytes()).unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(pack.len(), 200);
+    assert_eq!(pack.garbage(), 0);
+    pack.put("0/0", b"v2").unwrap();
+    assert!(pack.delete("0/1").unwrap());
+    assert!(!pack.delete("0/1").unwrap());
+    assert_eq!(pack.get("0/0").unwrap().unwrap(), b"v2");
+    assert_eq!(pack.get("0/1").unwrap(), None);
+    assert!(pack.garbage() > 0);
+    drop(pack);
+
+    // A put cut short by a crash leaves a partial record at the end, which opening drops.
+    let complete = fs::metadata(&path).unwrap().len();
+    OpenOptions::new().append(true).open(&path).unwrap().write_all(&[5, 0, 0, 0, 0, 0, 0, 0, 99]).unwrap();
+    // Read only, it is left alone.
+    let read_only = Pack::open_read_only(&path).unwrap();
+    assert_eq!(read_only.len(), 199);
+    assert_eq!(read_only.get("0/0").unwrap().unwrap(), b"v2");
+    assert!(read_only.put("0/0", b"v3").is_err());
+    assert_eq!(fs::metadata(&path).unwrap().len(), complete + 9);
+    drop(read_only);
+    let mut pack = Pack::open(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), complete);
+    assert_eq!(pack.len(), 199);
+    assert_eq!(pack.get("3/49").unwrap().unwrap(), b"v1 3 49");
+    assert_eq!(pack.get("0/0").unwrap().unwrap(), b"v2");
+    assert!(!pack.contains("0/1"));
+
+    let reclaimed = pack.compact().unwrap();
+    assert!(reclaimed > 0);
+    assert_eq!(pack.garbage(), 0);
+    assert_eq!(fs::metadata(&path).unwrap().len(), complete - reclaimed);
+    assert_eq!(Pack::open(&path).unwrap().get("0/0").unwrap().unwrap(), b"v2");
+    assert_eq!(pack.names().len(), 199);
+    fs::remove_dir_all(&dir).unwrap();
+}
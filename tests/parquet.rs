@@ -0,0 +1,188 @@
+//! Reads files back with a small Thrift compact-protocol decoder, following the Parquet
+//! format's field ids, so a change to the writer that a real reader would choke on shows
+//! up here.
+
+use io::parquet::{self, Column, Values};
+
+/// A decoded Thrift value.
+#[derive(Debug, Clone, PartialEq)]
+enum Thrift {
+    Bool(bool),
+    Int(i64),
+    Binary(Vec<u8>),
+    List(Vec<Thrift>),
+    Struct(Vec<(i16, Thrift)>),
+}
+
+impl Thrift {
+    fn get(&self, id: i16) -> Option<&Thrift> {
+        let Thrift::Struct(fields) = self else { panic!("not a struct: {:?}", self) };
+        fields.iter().find(|(field, _)| *field == id).map(|(_, value)| value)
+    }
+
+    fn field(&self, id: i16) -> &Thrift {
+        self.get(id).unwrap_or_else(|| panic!("no field {} in {:?}", id, self))
+    }
+
+    fn int(&self, id: i16) -> i64 {
+        match self.field(id) {
+            Thrift::Int(n) => *n,
+            other => panic!("field {} isn't an integer: {:?}", id, other),
+        }
+    }
+
+    fn string(&self, id: i16) -> String {
+        match self.field(id) {
+            Thrift::Binary(bytes) => String::from_utf8(bytes.clone()).unwrap(),
+            other => panic!("field {} isn't binary: {:?}", id, other),
+        }
+    }
+
+    fn list(&self, id: i16) -> &[Thrift] {
+        match self.field(id) {
+            Thrift::List(items) => items,
+            other => panic!("field {} isn't a list: {:?}", id, other),
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> u8 {
+        self.at += 1;
+        self.bytes[self.at - 1]
+    }
+
+    fn varint(&mut self) -> u64 {
+        let (mut n, mut shift) = (0, 0);
+        loop {
+            let byte = self.byte();
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return n;
+            }
+            shift += 7;
+        }
+    }
+
+    fn zigzag(&mut self) -> i64 {
+        let n = self.varint();
+        (n >> 1) as i64 ^ -((n & 1) as i64)
+    }
+
+    fn value(&mut self, kind: u8) -> Thrift {
+        match kind {
+            1 => Thrift::Bool(true),
+            2 => Thrift::Bool(false),
+            3 => Thrift::Int(self.byte() as i8 as i64),
+            4..=6 => Thrift::Int(self.zigzag()),
+            8 => {
+                let len = self.varint() as usize;
+                self.at += len;
+                Thrift::Binary(self.bytes[self.at - len..self.at].to_vec())
+            }
+            9 => {
+                let header = self.byte();
+                let len = if header >> 4 == 15 { self.varint() as usize } else { (header >> 4) as usize };
+                // Booleans in lists are whole bytes.
+                let element = header & 15;
+                Thrift::List((0..len).map(|_| if element <= 2 { Thrift::Bool(self.byte() == 1) } else { self.value(element) }).collect())
+            }
+            12 => self.structure(),
+            _ => panic!("unexpected Thrift type {} at {}", kind, self.at),
+        }
+    }
+
+    fn structure(&mut self) -> Thrift {
+        let (mut fields, mut last) = (Vec::new(), 0i16);
+        loop {
+            let header = self.byte();
+            if header == 0 {
+                return Thrift::Struct(fields);
+            }
+            let id = match header >> 4 {
+                0 => self.zigzag() as i16,
+                delta => last + delta as i16,
+            };
+            last = id;
+            fields.push((id, self.value(header & 15)));
+        }
+    }
+}
+
+/// Column `index`'s name and its values as the file stores them.
+fn read_column(file: &[u8], metadata: &Thrift, index: usize) -> (String, Values) {
+    let element = &metadata.list(2)[index + 1];
+    let chunk = &metadata.list(4)[0].list(1)[index];
+    let column = chunk.field(3);
+    assert_eq!(column.int(4), 0, "uncompressed");
+    let rows = metadata.int(3) as usize;
+    assert_eq!(column.int(5) as usize, rows);
+
+    let mut reader = Reader { bytes: file, at: column.int(9) as usize };
+    let page = reader.structure();
+    assert_eq!(page.int(1), 0, "a data page");
+    assert_eq!(page.field(5).int(1) as usize, rows);
+    let body = &file[reader.at..reader.at + page.int(3) as usize];
+    assert_eq!(reader.at - column.int(9) as usize + body.len(), column.int(7) as usize, "chunk size");
+
+    let number = |width: usize| body.chunks_exact(width);
+    let values = match (element.int(1), element.get(6)) {
+        (1, None) => Values::Int32(number(4).map(|b| i32::from_le_bytes(b.try_into().unwrap())).collect()),
+        (2, Some(Thrift::Int(14))) => Values::UInt64(number(8).map(|b| u64::from_le_bytes(b.try_into().unwrap())).collect()),
+        (2, None) => Values::Int64(number(8).map(|b| i64::from_le_bytes(b.try_into().unwrap())).collect()),
+        (6, Some(Thrift::Int(0))) => {
+            let (mut strings, mut at) = (Vec::new(), 0);
+            while at < body.len() {
+                let len = u32::from_le_bytes(body[at..at + 4].try_into().unwrap()) as usize;
+                strings.push(String::from_utf8(body[at + 4..at + 4 + len].to_vec()).unwrap());
+                at += 4 + len;
+            }
+            Values::Utf8(strings)
+        }
+        other => panic!("unexpected column type {:?}", other),
+    };
+    (element.string(4), values)
+}
+
+#[test]
+fn files_read_back_as_written() {
+    let columns = vec![
+        Column::new("int32", Values::Int32(vec![1, -2, i32::MAX])),
+        Column::new("int64", Values::Int64(vec![i64::MIN, 0, 42])),
+        Column::new("uint64", Values::UInt64(vec![u64::MAX, 0, 7])),
+        Column::new("path", Values::Utf8(vec!["a".to_string(), String::new(), "héllo/wörld".to_string()])),
+    ];
+    let file = parquet::encode(&columns).unwrap();
+    assert_eq!(&file[..4], b"PAR1");
+    assert_eq!(&file[file.len() - 4..], b"PAR1");
+    let footer = u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap()) as usize;
+    let mut reader = Reader { bytes: &file, at: file.len() - 8 - footer };
+    let metadata = reader.structure();
+    assert_eq!(reader.at, file.len() - 8, "the footer is exactly the metadata");
+
+    assert_eq!(metadata.int(1), 1);
+    assert_eq!(metadata.int(3), 3);
+    let schema = metadata.list(2);
+    assert_eq!(schema.len(), columns.len() + 1);
+    assert_eq!(schema[0].int(5), columns.len() as i64);
+    for (index, column) in columns.iter().enumerate() {
+        assert_eq!(read_column(&file, &metadata, index), (column.name.clone(), column.values.clone()));
+    }
+}
+
+#[test]
+fn empty_and_mismatched_columns() {
+    let empty = parquet::encode(&[Column::new("none", Values::Int64(Vec::new()))]).unwrap();
+    let footer = u32::from_le_bytes(empty[empty.len() - 8..empty.len() - 4].try_into().unwrap()) as usize;
+    let metadata = Reader { bytes: &empty, at: empty.len() - 8 - footer }.structure();
+    assert_eq!(metadata.int(3), 0);
+    assert_eq!(read_column(&empty, &metadata, 0).1, Values::Int64(Vec::new()));
+
+    let uneven = [Column::new("a", Values::Int32(vec![1, 2])), Column::new("b", Values::Int32(vec![1]))];
+    assert_eq!(parquet::encode(&uneven).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+}
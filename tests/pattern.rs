@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+
+use io::pattern::{self, DEFAULT_BLOCK, Generator, Pattern};
+
+#[test]
+fn patterns_parse_and_print_back() {
+    for text in ["zero", "random", "compressible:4", "compressible:2.5"] {
+        let pattern: Pattern = text.parse().unwrap();
+        assert_eq!(pattern.to_string(), text);
+    }
+    assert_eq!("compressible:4:1".parse::<Pattern>().unwrap(), Pattern::Compressible(4.0));
+    for text in ["ones", "compressible", "compressible:0.5", "compressible:x", "zero:1"] {
+        assert_eq!(text.parse::<Pattern>().unwrap_err().kind(), ErrorKind::InvalidInput, "{}", text);
+    }
+    assert_eq!(pattern::parse_ratio("3:1"), Some(3.0));
+    assert_eq!(pattern::parse_ratio("1"), Some(1.0));
+    assert_eq!(pattern::parse_ratio("0.9"), None);
+}
+
+#[test]
+fn contents_depend_only_on_seed_index_and_offset() {
+    let generator = Generator::new(7, Pattern::Random);
+    let whole = generator.contents(3, 3 * DEFAULT_BLOCK + 100);
+    assert_eq!(generator.contents(3, 3 * DEFAULT_BLOCK + 100), whole);
+    // A shorter file is a prefix of a longer one.
+    assert_eq!(generator.contents(3, 5000), whole[..5000]);
+    assert!(generator.matches(3, &whole));
+    assert!(!generator.matches(4, &whole));
+    assert_ne!(Generator::new(8, Pattern::Random).contents(3, 100), whole[..100]);
+
+    let mut changed = whole.clone();
+    changed[2 * DEFAULT_BLOCK + 1] ^= 1;
+    assert!(!generator.matches(3, &changed));
+    assert_ne!(generator.next_version().contents(3, 100), whole[..100]);
+
+    assert!(Generator::new(7, Pattern::Zero).contents(3, 10_000).iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn compressible_blocks_are_mostly_zeros() {
+    let generator = Generator::new(1, Pattern::Compressible(4.0));
+    let contents = generator.contents(0, 16 * DEFAULT_BLOCK);
+    for block in contents.chunks(DEFAULT_BLOCK) {
+        // A random head of a quarter of the block, then zeros.
+        assert!(block[DEFAULT_BLOCK / 4..].iter().all(|&byte| byte == 0));
+        assert!(block[..DEFAULT_BLOCK / 4].iter().filter(|&&byte| byte == 0).count() < DEFAULT_BLOCK / 64);
+    }
+}
+
+#[test]
+fn dedupe_repeats_about_the_asked_share_of_blocks() {
+    // How many of 1024 blocks appear once, and how many distinct blocks repeat.
+    let count = |generator: Generator| {
+        let mut seen: HashMap<Vec<u8>, usize> = HashMap::new();
+        for index in 0..64 {
+            for block in generator.contents(index, 16 * generator.block).chunks(generator.block) {
+                *seen.entry(block.to_vec()).or_default() += 1;
+            }
+        }
+        let once = seen.values().filter(|&&n| n == 1).count();
+        (once, seen.len() - once)
+    };
+    let plain = Generator::new(2, Pattern::Random);
+    assert_eq!(count(plain), (1024, 0));
+    // About a quarter stay unique; the rest are copies of a small shared set.
+    let (once, repeated) = count(Generator { dedupe: 4.0, block: 512, ..plain });
+    assert!((200..310).contains(&once), "{}", once);
+    assert!(repeated <= 64, "{}", repeated);
+    assert_eq!(Generator { dedupe: 4.0, ..plain }.to_string(), "random contents from seed 2, dedupe 4:1 in 4096-byte blocks");
+}
@@ -0,0 +1,48 @@
+#![cfg(unix)]
+
+use std::fs::{self, File};
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+
+use io::permissions;
+use io::retry::RetryPolicy;
+
+#[test]
+fn modes_and_owners_change_in_bulk() {
+    let dir = std::env::temp_dir().join(format!("io-permissions-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<PathBuf> = (0..20).map(|i| dir.join(format!("file_{}", i))).collect();
+    for path in &paths {
+        fs::write(path, b"data").unwrap();
+    }
+    let policy = RetryPolicy::default();
+    permissions::chmod_many(&paths, 0o600, &policy).unwrap();
+    assert!(paths.iter().all(|path| permissions::mode(path).unwrap() == 0o600));
+
+    // The descriptors were opened read-only, which fchmod doesn't mind.
+    let files: Vec<(PathBuf, File)> = paths.iter().map(|path| (path.clone(), File::open(path).unwrap())).collect();
+    permissions::fchmod_many(&files, 0o640, &policy).unwrap();
+    assert!(paths.iter().all(|path| permissions::mode(path).unwrap() == 0o640));
+
+    // Owners set to themselves, which anyone may do; `None` leaves the owner alone.
+    let metadata = fs::metadata(&paths[0]).unwrap();
+    permissions::chown_many(&paths, Some(metadata.uid()), None, &policy).unwrap();
+    permissions::fchown_many(&files, None, Some(metadata.gid()), &policy).unwrap();
+    assert!(paths.iter().all(|path| fs::metadata(path).unwrap().gid() == metadata.gid()));
+
+    let error = permissions::chmod_many(&[dir.join("missing")], 0o600, &policy).unwrap_err();
+    assert!(error.to_string().contains("missing"), "{}", error);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn both_methods_set_every_mode() {
+    let dir = std::env::temp_dir().join(format!("io-permissions-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = io::bench::Options { workload: io::bench::Workload::new(10, 100), ..io::bench::Options::default() };
+    let result = permissions::run(&dir, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.methods.iter().map(|method| method.method).collect::<Vec<_>>(), permissions::Method::ALL);
+    fs::remove_dir_all(&dir).unwrap();
+}
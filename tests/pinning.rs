@@ -0,0 +1,71 @@
+use io::pinning::{self, NumaNode, NumaPlacement};
+
+fn nodes() -> Vec<NumaNode> {
+    vec![
+        NumaNode { id: 0, cpus: vec![0, 1, 2, 3], memory: Some(1 << 30) },
+        NumaNode { id: 1, cpus: vec![4, 5, 6, 7], memory: Some(1 << 30) },
+    ]
+}
+
+#[test]
+fn parses_kernel_cpu_lists() {
+    assert_eq!(pinning::parse_cpu_list("0-3,8,10-11\n"), Some(vec![0, 1, 2, 3, 8, 10, 11]));
+    assert_eq!(pinning::parse_cpu_list(""), Some(vec![]));
+    assert_eq!(pinning::parse_cpu_list("0-x"), None);
+}
+
+#[test]
+fn places_workers_by_node() {
+    let allowed: Vec<usize> = (0..8).filter(|&cpu| cpu != 5).collect();
+    assert_eq!(pinning::worker_cpus(None, &nodes(), &allowed).unwrap(), allowed);
+    assert_eq!(pinning::worker_cpus(Some(NumaPlacement::Node(1)), &nodes(), &allowed).unwrap(), [4, 6, 7]);
+    assert_eq!(pinning::worker_cpus(Some(NumaPlacement::Interleave), &nodes(), &allowed).unwrap(), [0, 4, 1, 6, 2, 7, 3]);
+    assert!(pinning::worker_cpus(Some(NumaPlacement::Node(2)), &nodes(), &allowed).is_err());
+    assert!(pinning::worker_cpus(Some(NumaPlacement::Node(1)), &nodes(), &[0, 1]).is_err());
+    // Without any nodes described, interleaving has nothing to spread over.
+    assert_eq!(pinning::worker_cpus(Some(NumaPlacement::Interleave), &[], &allowed).unwrap(), allowed);
+}
+
+#[test]
+fn parses_placements() {
+    assert_eq!("interleave".parse::<NumaPlacement>().unwrap(), NumaPlacement::Interleave);
+    assert_eq!("1".parse::<NumaPlacement>().unwrap(), NumaPlacement::Node(1));
+    assert_eq!("node0".parse::<NumaPlacement>().unwrap(), NumaPlacement::Node(0));
+    assert!("nearest".parse::<NumaPlacement>().is_err());
+}
+
+#[cfg(all(target_os = "linux", feature = "libc"))]
+#[test]
+fn a_pinned_thread_runs_where_it_was_pinned() {
+    let cpu = io::platform::allowed_cpus()[0];
+    std::thread::spawn(move || {
+        pinning::pin_thread(cpu).unwrap();
+        assert_eq!(pinning::current_cpu(), Some(cpu));
+    })
+    .join()
+    .unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn worker_times_account_for_every_file() {
+    use io::bench::{self, Options, STRATEGIES, WorkerTimes, Workload};
+    use std::sync::Arc;
+
+    let dir = std::env::temp_dir().join(format!("io-worker-times-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let workers = Arc::new(WorkerTimes::default());
+    let options = Options { workload: Workload::new(60, 512), worker_times: Some(workers.clone()), ..Options::default() };
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+    pool.install(|| bench::run_strategy(&STRATEGIES[0], &dir, &options)).unwrap();
+    let shares = workers.take();
+    let label = STRATEGIES[0].label;
+    for phase in ["create", "read", "update", "delete"] {
+        let scope = format!("{}/{}", label, phase);
+        let phase_shares: Vec<_> = shares.iter().filter(|share| *share.scope == *scope).collect();
+        assert_eq!(phase_shares.iter().map(|share| share.files).sum::<u64>(), 60, "{}", scope);
+        assert!(phase_shares.iter().all(|share| share.worker < 3));
+    }
+    assert!(workers.take().is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
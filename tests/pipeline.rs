@@ -0,0 +1,37 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use io::bench::{Options, Workload};
+use io::fdlimit::OpenFileLimiter;
+use io::pipeline;
+
+#[test]
+fn pipelined_creates_write_every_file() {
+    let dir = std::env::temp_dir().join(format!("io-pipeline-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<PathBuf> = (0..200).map(|i| dir.join(format!("file_{}", i))).collect();
+    // A queue and budget smaller than the open threads still get every file through.
+    let options = Options { workload: Workload::new(200, 100), queue_depth: Some(1), open_files: Arc::new(OpenFileLimiter::new(2)), ..Options::default() };
+    pipeline::create_files_pipelined(&paths, &options, 4).unwrap();
+    for path in &paths {
+        assert_eq!(fs::read(path).unwrap(), options.workload.content());
+    }
+
+    let missing: Vec<PathBuf> = paths.iter().map(|path| dir.join("missing").join(path.file_name().unwrap())).collect();
+    assert!(pipeline::create_files_pipelined(&missing, &options, 4).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn both_models_create_the_workload() {
+    let dir = std::env::temp_dir().join(format!("io-pipeline-run-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(50, 100), ..Options::default() };
+    let result = pipeline::run(&dir, 2, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 50);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,45 @@
+use std::fs;
+
+use io::contents;
+use io::plan::{self, Action, Problem};
+use io::retry::RetryPolicy;
+
+#[test]
+fn plans_touch_nothing_and_find_every_problem() {
+    let dir = std::env::temp_dir().join(format!("io-plan-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("old"), b"12345").unwrap();
+
+    let files = vec![
+        (dir.join("new"), b"abc".to_vec()),
+        (dir.join("old"), b"abcdefgh".to_vec()),
+        (dir.join("missing/new"), b"x".to_vec()),
+        (dir.join("sub/../new"), b"y".to_vec()),
+        (dir.join("sub"), b"z".to_vec()),
+    ];
+    let plan = plan::plan_write(&files);
+    assert!(!dir.join("new").exists());
+    assert_eq!(fs::read(dir.join("old")).unwrap(), b"12345");
+    assert_eq!(plan.steps.iter().map(|step| step.action).collect::<Vec<_>>(), [Action::Create, Action::Overwrite]);
+    assert_eq!((plan.bytes_written(), plan.bytes_removed()), (11, 5));
+    assert_eq!(
+        plan.problems,
+        [Problem::MissingParent(dir.join("missing/new")), Problem::Duplicate(dir.join("sub/../new")), Problem::NotAFile(dir.join("sub"))]
+    );
+    assert!(plan.to_string().contains("overwrite 1 files"));
+    assert_eq!(plan.check().unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+    let clean = plan::plan_write(&files[..2]).check().unwrap();
+    contents::write_many(&files[..2], &RetryPolicy::default()).unwrap();
+    let paths: Vec<_> = clean.steps.iter().map(|step| step.path.clone()).collect();
+    let deletes = plan::plan_delete(&[paths[0].clone(), paths[1].clone(), dir.join("gone"), paths[0].clone()]);
+    assert_eq!(deletes.count(Action::Delete), 2);
+    assert_eq!(deletes.bytes_removed(), 11);
+    assert_eq!(deletes.problems, [Problem::Missing(dir.join("gone")), Problem::Duplicate(paths[0].clone())]);
+    assert!(paths.iter().all(|path| path.exists()));
+
+    contents::delete_many(&paths, &RetryPolicy::default()).unwrap();
+    assert!(paths.iter().all(|path| !path.exists()));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,22 @@
+use io::platform::{self, Host};
+
+#[test]
+fn cpu_models_come_from_whichever_field_the_architecture_has() {
+    let x86 = "processor\t: 0\nvendor_id\t: GenuineIntel\nmodel\t\t: 85\nmodel name\t: Intel(R) Xeon(R) Gold 6148 CPU @ 2.40GHz\n";
+    assert_eq!(platform::cpu_model(x86).as_deref(), Some("Intel(R) Xeon(R) Gold 6148 CPU @ 2.40GHz"));
+    let pi = "processor\t: 0\nBogoMIPS\t: 108.00\nCPU part\t: 0xd08\n\nRevision\t: c03111\nModel\t\t: Raspberry Pi 4 Model B Rev 1.1\n";
+    assert_eq!(platform::cpu_model(pi).as_deref(), Some("Raspberry Pi 4 Model B Rev 1.1"));
+    let riscv = "processor\t: 0\nhart\t\t: 1\nisa\t\t: rv64imafdc\nmmu\t\t: sv39\nuarch\t\t: sifive,u74-mc\n";
+    assert_eq!(platform::cpu_model(riscv).as_deref(), Some("sifive,u74-mc"));
+    let graviton = "processor\t: 0\nCPU implementer\t: 0x41\nCPU part\t: 0xd0c\nmodel name\t: \n";
+    assert_eq!(platform::cpu_model(graviton), None);
+}
+
+#[test]
+fn the_current_host_names_its_platform() {
+    let host = Host::current();
+    assert_eq!((host.os.as_str(), host.arch.as_str()), (std::env::consts::OS, std::env::consts::ARCH));
+    if cfg!(target_os = "linux") {
+        assert!(host.kernel.is_some());
+    }
+}
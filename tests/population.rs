@@ -0,0 +1,63 @@
+use std::fs;
+use std::io::ErrorKind;
+
+use io::population::{self, Manifest, Store};
+
+#[test]
+fn names_are_single_plain_components() {
+    for name in ["small", "repo-2", "with.dot"] {
+        assert!(population::valid_name(name), "{}", name);
+    }
+    for name in ["", ".", "..", "../escape", "a/b", "/abs", "trailing/"] {
+        assert!(!population::valid_name(name), "{:?}", name);
+    }
+}
+
+#[test]
+fn the_store_refuses_names_that_leave_it() {
+    let dir = std::env::temp_dir().join(format!("io-population-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let store = Store::new(dir.join("store"));
+    for name in ["../outside", "/etc/passwd", "a/b"] {
+        assert_eq!(store.population_dir(name).unwrap_err().kind(), ErrorKind::InvalidInput, "{}", name);
+        assert_eq!(store.load(name).unwrap_err().kind(), ErrorKind::InvalidInput, "{}", name);
+        assert_eq!(store.remove(name).unwrap_err().kind(), ErrorKind::InvalidInput, "{}", name);
+    }
+
+    let tree = dir.join("tree");
+    fs::create_dir_all(tree.join("sub")).unwrap();
+    fs::write(tree.join("sub/file"), b"x").unwrap();
+    let manifest = Manifest::discover("tree", &tree).unwrap();
+    store.save(&manifest).unwrap();
+    assert_eq!(store.load("tree").unwrap().unwrap().paths(), [tree.join("sub/file")]);
+
+    // A manifest edited to point outside its population is not one.
+    let path = dir.join("store/tree.json");
+    let text = fs::read_to_string(&path).unwrap();
+    fs::write(&path, text.replace("sub/file", "../../outside")).unwrap();
+    assert_eq!(store.load("tree").unwrap_err().kind(), ErrorKind::InvalidData);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn removing_leaves_files_the_manifest_places_elsewhere() {
+    let dir = std::env::temp_dir().join(format!("io-population-remove-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let store = Store::new(dir.join("store"));
+    let files = store.population_dir("gen").unwrap();
+    fs::create_dir_all(&files).unwrap();
+    let paths = vec![files.join("file_0.txt")];
+    fs::write(&paths[0], b"data").unwrap();
+    store.save(&Manifest::generated("gen", &files, &paths, 1, 4).unwrap()).unwrap();
+
+    // Pointed at a directory that only starts with the store's path.
+    let outside = dir.join("store/../kept");
+    fs::create_dir_all(&outside).unwrap();
+    let path = dir.join("store/gen.json");
+    let text = fs::read_to_string(&path).unwrap();
+    fs::write(&path, text.replace(&*files.to_string_lossy(), &outside.to_string_lossy())).unwrap();
+    store.remove("gen").unwrap();
+    assert!(outside.is_dir());
+    assert!(!path.exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,36 @@
+use std::fs;
+
+use io::precondition::{self, Precondition, MAX_UTILIZATION};
+
+#[test]
+fn filler_reaches_the_utilization_after_deletion() {
+    let mut target = Precondition::new(0.8);
+    target.fragmentation = 0.5;
+    // 100 bytes, 30 used: 50 more must stay, so 100 are written and half deleted, but only
+    // up to what leaves the filesystem MAX_UTILIZATION full.
+    let allowed = (70.0 - (1.0 - MAX_UTILIZATION) * 100.0) as u64;
+    assert_eq!(target.filler_bytes(100, 70), allowed);
+    target.fragmentation = 0.0;
+    assert_eq!(target.filler_bytes(1000, 700), 500);
+    assert_eq!(target.filler_bytes(1000, 100), 0);
+}
+
+#[test]
+fn fill_writes_the_budget_and_deletes_a_share() {
+    let dir = std::env::temp_dir().join(format!("io-precondition-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let report = precondition::fill(&dir, 1 << 20, 16 * 1024, 0.25, 7).unwrap();
+    assert_eq!(report.bytes_written, 1 << 20);
+    assert_eq!(report.files_deleted, (report.files_written as f64 * 0.25).round() as usize);
+
+    let mut remaining = Vec::new();
+    for subdir in fs::read_dir(&dir).unwrap() {
+        for file in fs::read_dir(subdir.unwrap().path()).unwrap() {
+            remaining.push(file.unwrap().metadata().unwrap().len());
+        }
+    }
+    assert_eq!(remaining.len(), report.files_written - report.files_deleted);
+    assert_eq!(remaining.iter().sum::<u64>(), report.bytes_written - report.bytes_deleted);
+    assert!(remaining.iter().all(|&size| size <= 24 * 1024));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,39 @@
+use io::priority::{IoPriority, Priority};
+
+#[test]
+fn io_priorities_parse_like_ionice_classes() {
+    assert_eq!("idle".parse::<IoPriority>().unwrap(), IoPriority::Idle);
+    assert_eq!("best-effort".parse::<IoPriority>().unwrap(), IoPriority::BestEffort(4));
+    assert_eq!("realtime:0".parse::<IoPriority>().unwrap(), IoPriority::Realtime(0));
+    for priority in [IoPriority::Idle, IoPriority::BestEffort(7), IoPriority::Realtime(2)] {
+        assert_eq!(priority.to_string().parse::<IoPriority>().unwrap(), priority);
+    }
+    for invalid in ["idle:3", "best-effort:8", "background", "realtime:"] {
+        assert!(invalid.parse::<IoPriority>().is_err(), "{}", invalid);
+    }
+    assert!(!Priority::default().is_set());
+}
+
+#[cfg(all(target_os = "linux", feature = "libc"))]
+#[test]
+fn lowered_priorities_apply_to_the_thread_and_its_children() {
+    let background = Priority { nice: Some(10), io: Some(IoPriority::Idle) };
+    let (applied, inherited) = std::thread::spawn(move || {
+        background.apply().unwrap();
+        let inherited = std::thread::spawn(|| Priority::current().unwrap()).join().unwrap();
+        (Priority::current().unwrap(), inherited)
+    })
+    .join()
+    .unwrap();
+    assert_eq!(applied, background);
+    assert_eq!(inherited, background);
+}
+
+#[cfg(all(target_os = "linux", feature = "libc", feature = "rayon"))]
+#[test]
+fn engine_workers_run_at_the_priority() {
+    let priority = Priority { nice: Some(5), io: Some(IoPriority::BestEffort(6)) };
+    let mut engine = io::engine::Engine::new(2).pinned(false).priority(priority);
+    let seen = engine.pool().unwrap().unwrap().broadcast(|_| Priority::current().unwrap());
+    assert!(seen.iter().all(|&current| current == priority), "{:?}", seen);
+}
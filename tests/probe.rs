@@ -0,0 +1,31 @@
+#![cfg(all(unix, feature = "libc", feature = "mmap", feature = "rayon"))]
+
+use std::fs;
+
+use io::crossover::Thresholds;
+use io::probe::{Probe, Verdict};
+
+#[test]
+fn probing_a_local_filesystem_finds_what_the_fast_paths_rely_on() {
+    let dir = std::env::temp_dir().join(format!("io-probe-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let probe = Probe::run(&dir).unwrap();
+    assert_eq!(probe.atomic_rename, Verdict::Yes);
+    assert_eq!(probe.mmap_coherent, Verdict::Yes);
+    assert!(probe.max_name_len.is_some_and(|len| len >= 14), "{:?}", probe.max_name_len);
+    assert!(probe.direct_io_alignment.is_none_or(|align| align.is_power_of_two()));
+    // The scratch directory is gone.
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    assert_eq!(probe.adjust(Thresholds::default()), Thresholds::default());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn incoherent_mappings_keep_adaptive_off_mmap() {
+    let dir = std::env::temp_dir().join(format!("io-probe-adjust-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let probe = Probe { mmap_coherent: Verdict::No("stale pages".to_string()), ..Probe::run(&dir).unwrap() };
+    assert_eq!(probe.adjust(Thresholds { mmap_read: Some(1 << 20), mmap_update: Some(0) }), Thresholds { mmap_read: None, mmap_update: None });
+    assert_eq!(probe.mmap_coherent.to_string(), "no (stale pages)");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+use std::process::Command;
+
+use io::bench;
+
+#[test]
+fn slices_cover_every_file_once() {
+    let files: Vec<usize> = (0..10).collect();
+    for count in 1..=12 {
+        let slices: Vec<&[usize]> = (0..count).map(|index| bench::slice(&files, index, count)).collect();
+        assert_eq!(slices.concat(), files, "{} slices", count);
+        let (shortest, longest) = (slices.iter().map(|slice| slice.len()).min().unwrap(), slices.iter().map(|slice| slice.len()).max().unwrap());
+        assert!(longest - shortest <= 1, "{} slices: {:?}", count, slices);
+    }
+    assert!(bench::slice(&files[..0], 0, 3).is_empty());
+}
+
+fn io_bench(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_io")).arg("bench").args(args).output().unwrap()
+}
+
+#[test]
+fn processes_refuse_flags_they_cannot_honour() {
+    for flag in [&["--csv", "times.csv"][..], &["--target", "/tmp"], &["--record", "ops.json"], &["--schedule", "both"], &["--anomalies"]] {
+        let output = io_bench(&[&["--processes", "2", "--files", "4"][..], flag].concat());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success(), "{:?}", flag);
+        assert!(stderr.contains(&format!("--processes doesn't support {}", flag[0])), "{:?}: {}", flag, stderr);
+    }
+}
+
+#[test]
+fn workers_get_the_workload_and_the_coordinator_keeps_the_baseline() {
+    let dir = std::env::temp_dir().join(format!("io-processes-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let baseline = dir.join("baseline.json");
+    let output = io_bench(&["--processes", "2", "--threads", "2", "--files", "30", "--size", "1K", "--save-baseline", baseline.to_str().unwrap()]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    // Passed on to the workers, each of which got half the files and one thread.
+    assert!(stdout.contains("Running 2 processes of 1 threads, up to 15 files of 1.0 KiB each"), "{}", stdout);
+    assert!(stdout.contains("(2 processes)"), "{}", stdout);
+    let saved = io::json::parse(&fs::read_to_string(&baseline).unwrap()).unwrap();
+    assert_eq!(saved.get("files").and_then(io::json::Value::as_u64), Some(30));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,43 @@
+#![cfg(all(unix, feature = "libc"))]
+
+use io::profile::{self, ProfileKind};
+
+const SCRIPT: &str = "\
+io
+\t    55d0c0a1b2c3 std::fs::write+0x23
+\t    55d0c0a1b000 io::bench::create+0x1f0
+\t    7f0000001234 start_thread+0x90
+
+io
+\t    55d0c0a1b2c3 std::fs::write+0x40
+\t    55d0c0a1b000 io::bench::create+0x1f0
+\t    7f0000001234 start_thread+0x90
+
+io
+\t    55d0c0a1c000 std::fs::read+0x10
+\t    ffffffff81000000 [unknown]
+";
+
+#[test]
+fn folds_perf_script_stacks_root_first() {
+    let folded = profile::fold(SCRIPT);
+    assert_eq!(
+        folded,
+        vec![
+            ("io;[unknown];std::fs::read".to_string(), 1),
+            ("io;start_thread;io::bench::create;std::fs::write".to_string(), 2),
+        ]
+    );
+}
+
+#[test]
+fn flamegraphs_size_frames_by_samples() {
+    let svg = profile::flamegraph(&profile::fold(SCRIPT), "Traditional-create");
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("Traditional-create (3 samples)"));
+    assert!(svg.contains("<title>io (3 samples, 100.00%)</title><rect x=\"0.0\""));
+    assert!(svg.contains("<title>std::fs::write (2 samples, 66.67%)</title><rect x=\"400.0\""));
+    assert_eq!(svg.matches("<rect").count(), 6);
+    assert!("flamegraph".parse::<ProfileKind>().is_ok());
+    assert!("pprof".parse::<ProfileKind>().is_err());
+}
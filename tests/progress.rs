@@ -0,0 +1,46 @@
+//! Checks that progress counts batches, and that targets run side by side each keep a
+//! count and stage of their own while adding to the shared one.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use io::progress::Progress;
+
+#[test]
+fn batches_restart_the_count() {
+    let progress = Progress::default();
+    progress.begin(2);
+    progress.advance();
+    progress.advance();
+    assert_eq!((progress.done(), progress.total()), (2, 2));
+    progress.begin(3);
+    progress.skip(1);
+    assert_eq!((progress.done(), progress.total()), (1, 3));
+}
+
+#[test]
+fn targets_count_toward_the_whole() {
+    let reported = Arc::new(AtomicUsize::new(0));
+    let counter = reported.clone();
+    let all = Arc::new(Progress::on_progress(move |done, _| {
+        counter.store(done, Ordering::Relaxed);
+    }));
+    all.set_stage("setup");
+    let (ssd, disk) = (Progress::target(&all, "/ssd"), Progress::target(&all, "/disk"));
+    ssd.set_stage("Smart create");
+    disk.set_stage("Smart read");
+    assert_eq!(all.stage(), "/ssd: Smart create, /disk: Smart read");
+
+    ssd.begin(2);
+    disk.begin(3);
+    ssd.advance();
+    disk.advance();
+    disk.advance();
+    assert_eq!((ssd.done(), ssd.total()), (1, 2));
+    assert_eq!((disk.done(), disk.total()), (2, 3));
+    assert_eq!((all.done(), all.total()), (3, 5));
+    assert_eq!(reported.load(Ordering::Relaxed), 3);
+
+    drop((ssd, disk));
+    assert_eq!(all.stage(), "setup");
+}
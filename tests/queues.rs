@@ -0,0 +1,49 @@
+use std::fs;
+
+use io::health::Device;
+use io::queues::{self, Queue, QueueCounter, QueueSample};
+
+#[test]
+fn hardware_queues_are_read_from_the_mq_directory() {
+    // A stand-in for the device's sysfs directory.
+    let sysfs = std::env::temp_dir().join(format!("io-queues-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&sysfs);
+    for (queue, cpus) in [("10", "6"), ("0", "0-1,4\n"), ("1", "2, 3")] {
+        fs::create_dir_all(sysfs.join("mq").join(queue)).unwrap();
+        fs::write(sysfs.join("mq").join(queue).join("cpu_list"), cpus).unwrap();
+    }
+    fs::create_dir_all(sysfs.join("mq/not-a-queue")).unwrap();
+    fs::create_dir_all(sysfs.join("mq/2")).unwrap();
+
+    let device = Device { name: "nvme9n1".to_string(), sysfs: sysfs.clone() };
+    assert_eq!(
+        queues::hardware_queues(&device),
+        vec![
+            Queue { index: 0, cpus: vec![0, 1, 4] },
+            Queue { index: 1, cpus: vec![2, 3] },
+            Queue { index: 2, cpus: vec![] },
+            Queue { index: 10, cpus: vec![6] },
+        ]
+    );
+    // Without a controller to name the interrupts there is nothing to count from.
+    assert!(QueueCounter::new(&device).is_none());
+
+    let plain = Device { name: "sda".to_string(), sysfs: sysfs.join("missing") };
+    assert!(queues::hardware_queues(&plain).is_empty());
+    assert!(QueueCounter::new(&plain).is_none());
+    fs::remove_dir_all(&sysfs).unwrap();
+}
+
+#[test]
+fn samples_give_each_queue_its_share() {
+    let before = QueueSample(vec![10, 5, 0]);
+    let after = QueueSample(vec![40, 5, 30, 7]);
+    let phase = after.since(&before);
+    assert_eq!(phase, QueueSample(vec![30, 0, 30, 7]));
+    assert_eq!((phase.total(), phase.active()), (67, 3));
+    assert_eq!(QueueSample(vec![5, 5, 5, 5]).imbalance(), 1.0);
+    assert_eq!(QueueSample(vec![0, 8, 0, 0]).imbalance(), 4.0);
+    assert_eq!(QueueSample(vec![0, 0]).imbalance(), 0.0);
+    // A counter that went backwards, as after a device reset, counts as nothing.
+    assert_eq!(QueueSample(vec![3]).since(&QueueSample(vec![9])), QueueSample(vec![0]));
+}
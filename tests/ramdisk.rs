@@ -0,0 +1,49 @@
+//! Checks that a ramdisk is a tmpfs with the room asked for, and that it goes away both
+//! when dropped and when the process removes its live directories to exit at once.
+
+#![cfg(all(target_os = "linux", feature = "libc"))]
+
+use std::fs;
+
+use io::filesystem::Filesystem;
+use io::ramdisk::Ramdisk;
+use io::rundir;
+
+const SIZE: u64 = 1 << 20;
+
+fn setup() -> Option<Ramdisk> {
+    match Ramdisk::setup(SIZE) {
+        Ok(ramdisk) => Some(ramdisk),
+        // Neither allowed to mount nor given a tmpfs to borrow.
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => None,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[test]
+fn ramdisks_are_removed_however_the_run_ends() {
+    let Some(ramdisk) = setup() else {
+        return;
+    };
+    let dir = ramdisk.dir().to_path_buf();
+    assert_eq!(Filesystem::of(&dir).unwrap().fs_type, "tmpfs");
+    fs::write(dir.join("file"), b"data").unwrap();
+    drop(ramdisk);
+    assert!(!dir.exists());
+
+    // As on a second Ctrl-C: the process exits without dropping the ramdisk.
+    let Some(ramdisk) = setup() else {
+        return;
+    };
+    let dir = ramdisk.dir().to_path_buf();
+    fs::write(dir.join("file"), b"data").unwrap();
+    let mounted = ramdisk.mounted();
+    rundir::remove_live();
+    assert!(!dir.exists(), "{} outlived remove_live (mounted: {})", dir.display(), mounted);
+    if mounted {
+        let mounts = fs::read_to_string("/proc/self/mounts").unwrap();
+        assert!(!mounts.contains(&*dir.to_string_lossy()), "still mounted");
+    }
+    // Dropping it afterwards finds nothing left to do.
+    drop(ramdisk);
+}
@@ -0,0 +1,41 @@
+#![cfg(unix)]
+
+use std::fs;
+
+use io::ranged::{self, Method, SEGMENT};
+
+#[test]
+fn ranges_cover_the_file_in_whole_segments() {
+    let len = 5 * SEGMENT as u64 + 100;
+    for writers in 1..=8 {
+        let ranges: Vec<_> = (0..writers).map(|writer| ranged::range(len, writers, writer)).collect();
+        assert_eq!(ranges[0].start, 0);
+        assert_eq!(ranges[writers - 1].end, len);
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            assert!(pair[1].start % SEGMENT as u64 == 0 || pair[1].start == len);
+        }
+        let sizes: Vec<u64> = ranges.iter().filter(|range| !range.is_empty()).map(|range| range.end.div_ceil(SEGMENT as u64) - range.start / SEGMENT as u64).collect();
+        assert!(sizes.iter().max().unwrap() - sizes.iter().min().unwrap() <= 1, "{:?}", ranges);
+    }
+    assert!(ranged::range(len, 8, 7).is_empty());
+    assert_eq!(ranged::range(0, 4, 0), 0..0);
+}
+
+#[test]
+fn every_method_writes_the_same_file() {
+    let dir = std::env::temp_dir().join(format!("io-ranged-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let bytes: Vec<u8> = (0..2 * SEGMENT + 12_345).map(|i| (i * 31 % 251) as u8).collect();
+    for &method in Method::ALL {
+        for writers in [1, 2, 3, 8] {
+            let path = dir.join(format!("{}-{}", method.name(), writers));
+            ranged::write_parallel(&path, &bytes, writers, method, writers == 3).unwrap();
+            assert!(fs::read(&path).unwrap() == bytes, "{} with {} writers", method.name(), writers);
+        }
+        let empty = dir.join(format!("{}-empty", method.name()));
+        ranged::write_parallel(&empty, &[], 4, method, false).unwrap();
+        assert_eq!(fs::metadata(&empty).unwrap().len(), 0);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
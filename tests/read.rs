@@ -0,0 +1,46 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+
+use io::bench::{Options, ReadMethod, Workload};
+use io::cache::Hint;
+use io::read;
+
+#[test]
+fn every_read_method_reads_what_was_written() {
+    let dir = std::env::temp_dir().join(format!("io-read-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(8, 20_000), verify: true, madvise: Some(Hint::WillNeed), ..Options::default() };
+    let result = read::run(&dir, &ReadMethod::ALL, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.methods.iter().map(|method| method.method).collect::<Vec<_>>(), ReadMethod::ALL);
+    assert_eq!(result.read_bytes, 8 * 20_000);
+    for method in ReadMethod::ALL {
+        assert_eq!(method.name().parse::<ReadMethod>().unwrap(), method);
+    }
+
+    // A file that no longer holds what the create phase wrote fails verification.
+    fs::write(dir.join("file_3.txt"), b"changed").unwrap();
+    let paths = io::bench::file_paths(&dir, 8);
+    for method in ReadMethod::ALL {
+        let error = io::bench::read_files_by(&paths, &options, method).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData, "{}", method);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn prefetching_reads_the_same_files_again() {
+    let dir = std::env::temp_dir().join(format!("io-read-prefetch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for prefetch in ["readahead", "willneed"] {
+        let options = Options { workload: Workload::new(50, 10_000), verify: true, prefetch: Some(prefetch.parse().unwrap()), prefetch_window: Some(4), ..Options::default() };
+        let result = read::run(&dir, &ReadMethod::ALL, &options).unwrap();
+        assert!(result.failures.is_empty(), "{:?}", result.failures);
+        assert!(result.methods.iter().all(|method| method.prefetched.is_some()), "{}", prefetch);
+        fs::remove_dir_all(&dir).unwrap();
+        fs::create_dir_all(&dir).unwrap();
+    }
+    assert!("eagerly".parse::<io::cache::Prefetch>().is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
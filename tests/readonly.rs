@@ -0,0 +1,65 @@
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use io::readonly::DataSet;
+
+fn tree(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-readonly-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("a/b")).unwrap();
+    fs::write(dir.join("one"), b"first").unwrap();
+    fs::write(dir.join("a/two"), b"second!").unwrap();
+    fs::write(dir.join("a/b/three"), vec![3u8; 1000]).unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(dir.join("one"), dir.join("a/link")).unwrap();
+    dir
+}
+
+#[test]
+fn a_data_set_lists_regular_files_and_opens_them_for_reading() {
+    let dir = tree("discover");
+    let set = DataSet::discover(&dir).unwrap();
+    assert_eq!(set.root(), dir);
+    assert_eq!(set.len(), 3);
+    assert_eq!(set.bytes(), 5 + 7 + 1000);
+    let mut listed: Vec<(PathBuf, u64)> = set.paths().iter().cloned().zip(set.sizes().iter().copied()).collect();
+    listed.sort();
+    assert_eq!(listed, vec![(dir.join("a/b/three"), 1000), (dir.join("a/two"), 7), (dir.join("one"), 5)]);
+
+    let mut file = set.open(&dir.join("a/two")).unwrap();
+    assert_eq!(file.metadata().unwrap().len(), 7);
+    file.seek(SeekFrom::Start(3)).unwrap();
+    let mut rest = String::new();
+    file.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "ond!");
+    assert!(set.open(&dir.join("missing")).is_err());
+
+    assert!(DataSet::discover(&dir.join("missing")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn phases_read_everything_change_nothing_and_record_vanished_files() {
+    use io::bench::Options;
+    use io::readonly::{self, ReadPhase};
+
+    let dir = tree("run");
+    let set = DataSet::discover(&dir).unwrap();
+    let modified = fs::metadata(dir.join("a/b/three")).unwrap().modified().unwrap();
+    // Gone between listing and reading, as in a live data set.
+    fs::remove_file(dir.join("one")).unwrap();
+
+    let result = readonly::run(&set, &Options::default()).unwrap();
+    assert_eq!(result.times.iter().map(|&(phase, _, _)| phase).collect::<Vec<_>>(), ReadPhase::ALL);
+    for &(phase, _, bytes) in &result.times {
+        assert_eq!(bytes, if phase == ReadPhase::Stat { 0 } else { 1007 }, "{}", phase.name());
+    }
+    assert_eq!(result.failures.len(), ReadPhase::ALL.len(), "{:?}", result.failures);
+    assert!(result.failures.iter().all(|failure| failure.path == dir.join("one")));
+
+    assert_eq!(fs::read(dir.join("a/b/three")).unwrap(), vec![3u8; 1000]);
+    assert_eq!(fs::metadata(dir.join("a/b/three")).unwrap().modified().unwrap(), modified);
+    fs::remove_dir_all(&dir).unwrap();
+}
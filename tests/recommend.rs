@@ -0,0 +1,39 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+use std::process::Command;
+
+use io::json::{self, Value};
+
+#[test]
+fn every_phase_gets_the_fastest_strategy() {
+    let dir = std::env::temp_dir().join(format!("io-recommend-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let (saved, baseline) = (dir.join("recommendation.json"), dir.join("baseline.json"));
+    let output = Command::new(env!("CARGO_BIN_EXE_io"))
+        .args(["bench", "--files", "20", "--size", "1K", "--threads", "2", "--recommend", saved.to_str().unwrap(), "--save-baseline", baseline.to_str().unwrap()])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("Saved recommendation to"), "{}", stdout);
+
+    let recommendation = json::parse(&fs::read_to_string(&saved).unwrap()).unwrap();
+    let baseline = json::parse(&fs::read_to_string(&baseline).unwrap()).unwrap();
+    assert_eq!(recommendation.get("files").and_then(Value::as_u64), Some(20));
+    let phases = recommendation.get("phases").and_then(Value::as_array).unwrap();
+    let names: Vec<&str> = phases.iter().filter_map(|phase| phase.get("phase")?.as_str()).collect();
+    assert_eq!(names, ["create", "read", "update", "delete"]);
+    let results = baseline.get("results").and_then(Value::as_array).unwrap();
+    for (phase, name) in phases.iter().zip(names) {
+        let ms = phase.get("ms").and_then(Value::as_f64).unwrap();
+        let times: Vec<f64> = results.iter().filter_map(|result| result.get(&format!("{}_ms", name))?.as_f64()).collect();
+        assert_eq!(ms, times.iter().copied().fold(f64::INFINITY, f64::min), "{:?}", phase);
+        let strategy = phase.get("strategy").and_then(Value::as_str).unwrap();
+        assert!(results.iter().any(|result| result.get("strategy").and_then(Value::as_str) == Some(strategy)), "{}", strategy);
+        assert!(phase.get("runner_up_ms").and_then(Value::as_f64).is_some_and(|runner_up| runner_up >= ms), "{:?}", phase);
+        assert!(phase.get("slowest_slower_percent").and_then(Value::as_f64).is_some_and(|percent| percent >= 0.0), "{:?}", phase);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,81 @@
+//! Builds the `io-record` shim and records a small shell command end to end, checking the
+//! op log it writes and that the log of calls doesn't outlive the run.
+
+#![cfg(all(target_os = "linux", feature = "bench"))]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use io::oplog::{Kind, Mode, Op, Trace};
+
+/// Builds the shim into a target directory of its own, so the build doesn't wait on the
+/// lock of the one running the tests.
+fn build_shim() -> PathBuf {
+    let target = Path::new(env!("CARGO_TARGET_TMPDIR")).join("record-shim");
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--quiet", "-p", "io-record", "--target-dir"])
+        .arg(&target)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()
+        .unwrap();
+    assert!(status.success(), "building io-record: {}", status);
+    target.join("debug/libio_record.so")
+}
+
+fn record_logs(before: &[PathBuf]) -> Vec<PathBuf> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(env::temp_dir())
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("io-record-")))
+        .filter(|path| !before.contains(path))
+        .collect();
+    logs.sort();
+    logs
+}
+
+#[test]
+fn records_a_shell_command() {
+    let shim = build_shim();
+    let dir = env::temp_dir().join(format!("io-record-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("trace.jsonl");
+    let before = record_logs(&[]);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_io"))
+        .args(["record", "--shim"])
+        .arg(&shim)
+        .arg("--output")
+        .arg(&output)
+        .args(["--", "sh", "-c", "echo x > f; cat f > /dev/null"])
+        .stdout(std::process::Stdio::null())
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "{}", status);
+    assert!(record_logs(&before).is_empty(), "the log of calls was left behind");
+
+    let trace = Trace::parse_jsonl(&fs::read_to_string(&output).unwrap()).unwrap();
+    let file = dir.join("f");
+    let on_file: Vec<&Op> = trace.events.iter().map(|event| &event.op).filter(|op| op.path() == file || op.path() == Path::new("f")).collect();
+    assert!(on_file.iter().any(|op| matches!(op, Op::Open { mode: Mode::Write, .. })), "{:#?}", on_file);
+    assert!(on_file.iter().any(|op| matches!(op, Op::Write { bytes: 2, .. })), "{:#?}", on_file);
+    assert!(on_file.iter().any(|op| matches!(op, Op::Open { mode: Mode::Read, .. })), "{:#?}", on_file);
+    assert!(on_file.iter().any(|op| matches!(op, Op::Read { bytes: 2, .. })), "{:#?}", on_file);
+    let first = |kind: Kind| on_file.iter().position(|op| op.kind() == kind).unwrap();
+    assert!(first(Kind::Write) < first(Kind::Read), "{:#?}", on_file);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_missing_shim_is_an_error() {
+    let status = Command::new(env!("CARGO_BIN_EXE_io"))
+        .args(["record", "--shim", "/nonexistent/libio_record.so", "--", "true"])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
@@ -0,0 +1,86 @@
+#![cfg(all(target_os = "linux", feature = "bench"))]
+
+use std::fs;
+
+use io::bench::{Options, Workload};
+use io::registered::{self, Tier};
+
+#[test]
+fn fallback_tiers_read_everything() {
+    let dir = std::env::temp_dir().join(format!("io-registered-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(50, 1000), queue_depth: Some(8), ..Options::default() };
+    let result = registered::run(&dir, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    for tier in [Tier::PosixAio, Tier::NoWait] {
+        if result.skipped.iter().any(|(skipped, _)| *skipped == tier) {
+            continue;
+        }
+        let (_, _, bytes) = result.times.iter().find(|(timed, _, _)| *timed == tier).unwrap();
+        assert_eq!(*bytes, 50_000, "{}", tier.name());
+    }
+    if let Some((served, punted)) = result.nowait {
+        assert_eq!(served + punted, 50);
+    }
+    assert_eq!(result.uring_unavailable.is_some(), result.skipped.iter().any(|(tier, _)| *tier == Tier::Uring));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn oversized_buffers_are_refused_before_any_file_is_created() {
+    let dir = std::env::temp_dir().join(format!("io-registered-cap-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(1, 1 << 20), queue_depth: Some(2048), ..Options::default() };
+    let e = registered::run(&dir, &options).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(e.to_string().contains("--queue-depth"), "{}", e);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn every_tier_that_runs_reads_every_byte() {
+    let dir = std::env::temp_dir().join(format!("io-registered-all-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // A partial last batch and a depth that isn't a power of two.
+    let options = Options { workload: Workload::new(37, 3000), queue_depth: Some(5), ..Options::default() };
+    let result = registered::run(&dir, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.times.len() + result.skipped.len(), Tier::ALL.len());
+    assert!(result.times.iter().any(|(tier, _, _)| *tier == Tier::Read));
+    for (tier, _, bytes) in &result.times {
+        assert_eq!(*bytes, 37 * 3000, "{}", tier.name());
+    }
+    // Every timed async tier reports its concurrency, never more than the depth asked for.
+    for (tier, depth, mean) in &result.concurrency {
+        assert!(result.times.iter().any(|(timed, _, _)| timed == tier), "{}", tier.name());
+        assert_eq!(*depth, 5);
+        assert!(*mean <= 5.0 * rayon::current_num_threads() as f64, "{}: {}", tier.name(), mean);
+    }
+    if result.uring_unavailable.is_none() {
+        assert!(result.times.iter().any(|(tier, _, _)| *tier == Tier::Registered), "{:?}", result.skipped);
+    }
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn per_core_shards_each_report_when_they_finished() {
+    let dir = std::env::temp_dir().join(format!("io-registered-per-core-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(3, 100), queue_depth: Some(4), ..Options::default() };
+    let result = registered::run(&dir, &options).unwrap();
+    match result.times.iter().find(|(tier, _, _)| *tier == Tier::PerCore) {
+        Some((_, elapsed, bytes)) => {
+            assert_eq!(*bytes, 300);
+            // One shard per worker, but never more shards than files.
+            assert_eq!(result.shards.len(), rayon::current_num_threads().min(3));
+            assert!(result.shards.iter().all(|finished| finished <= elapsed), "{:?} after {:?}", result.shards, elapsed);
+        }
+        None => {
+            assert!(result.skipped.iter().any(|(tier, _)| *tier == Tier::PerCore));
+            assert!(result.shards.is_empty());
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,75 @@
+#![cfg(all(unix, feature = "libc"))]
+
+use std::fs;
+use std::path::PathBuf;
+
+use io::remove;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-remove-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn only_the_listed_files_are_removed() {
+    let dir = scratch("files");
+    fs::create_dir_all(dir.join("a/b")).unwrap();
+    let listed: Vec<PathBuf> = ["one", "a/two", "a/b/three", "a/b/four"].iter().map(|name| dir.join(name)).collect();
+    for path in &listed {
+        fs::write(path, b"x").unwrap();
+    }
+    fs::write(dir.join("a/kept"), b"x").unwrap();
+    remove::remove_files(&listed).unwrap();
+    assert!(listed.iter().all(|path| !path.exists()));
+    assert!(dir.join("a/kept").exists() && dir.join("a/b").is_dir());
+
+    let e = remove::remove_files(&[dir.join("a/kept"), dir.join("a/missing")]).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+    assert!(e.to_string().contains("missing"), "{}", e);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_tree_is_removed_without_following_symlinks() {
+    let dir = scratch("tree");
+    let root = dir.join("tree");
+    for i in 0..8 {
+        let sub = root.join(format!("d{}/e{}", i, i % 3));
+        fs::create_dir_all(&sub).unwrap();
+        for j in 0..5 {
+            fs::write(sub.join(format!("f{}", j)), b"x").unwrap();
+        }
+    }
+    fs::create_dir_all(root.join("empty")).unwrap();
+    // More entries than one read of the directory returns.
+    fs::create_dir_all(root.join("wide")).unwrap();
+    for j in 0..2000 {
+        fs::write(root.join(format!("wide/file_with_a_longer_name_{}", j)), b"").unwrap();
+    }
+    fs::create_dir_all(dir.join("outside")).unwrap();
+    fs::write(dir.join("outside/kept"), b"x").unwrap();
+    std::os::unix::fs::symlink(dir.join("outside"), root.join("d0/link")).unwrap();
+
+    remove::remove_tree_fast(&root).unwrap();
+    assert!(!root.exists());
+    assert!(dir.join("outside/kept").exists());
+    assert_eq!(remove::remove_tree_fast(&root).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn every_removal_is_timed_and_leaves_nothing() {
+    use io::bench::{Options, Workload};
+    use io::remove::Removal;
+
+    let dir = scratch("run");
+    let options = Options { workload: Workload::new(200, 10), ..Options::default() };
+    let result = remove::run(&dir, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.times.iter().map(|(removal, _)| *removal).collect::<Vec<_>>(), Removal::ALL);
+    assert!(!dir.join("tree").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
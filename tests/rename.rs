@@ -0,0 +1,48 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+
+use io::bench::{Options, Workload};
+use io::rename::{self, Moved};
+
+#[test]
+fn a_rename_within_a_filesystem_is_not_a_copy() {
+    let dir = std::env::temp_dir().join(format!("io-rename-file-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("published")).unwrap();
+    fs::write(dir.join("staged"), b"contents").unwrap();
+    let to = dir.join("published/file");
+    assert_eq!(rename::move_file(&dir.join("staged"), &to, &Options::default()).unwrap(), Moved::Renamed);
+    assert_eq!(fs::read(&to).unwrap(), b"contents");
+    assert!(!dir.join("staged").exists());
+    assert!(rename::same_filesystem(&dir, &to).unwrap());
+    assert!(rename::move_file(&dir.join("missing"), &to, &Options::default()).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn every_file_is_moved_and_cleaned_up() {
+    let dir = std::env::temp_dir().join(format!("io-rename-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(30, 500), ..Options::default() };
+    let cross_dir = rename::default_cross_dir(&dir);
+    let result = rename::run(&dir, cross_dir.as_deref(), &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+
+    let rename = &result.phases[0];
+    assert_eq!(rename.name, "rename");
+    assert_eq!((rename.counts.renamed, rename.counts.copied), (30, 0));
+    assert_eq!(rename.dir, dir.join("published"));
+    // A directory on another filesystem can only be reached by copying.
+    if let Some(cross_dir) = cross_dir {
+        let cross = &result.phases[1];
+        assert_eq!((cross.name, cross.counts.renamed, cross.counts.copied), ("cross-fs", 0, 30));
+        assert!(!cross.dir.exists() && cross.dir.starts_with(&cross_dir));
+    } else {
+        assert_eq!(result.phases.len(), 1);
+    }
+    assert_eq!(fs::read_dir(dir.join("published")).unwrap().count(), 0);
+    assert_eq!(fs::read_dir(dir.join("staged")).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn io_bench(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_io")).arg("bench").args(args).output().unwrap()
+}
+
+fn save_baseline(path: &Path) {
+    let output = io_bench(&["--files", "20", "--size", "1K", "--threads", "2", "--save-baseline", path.to_str().unwrap()]);
+    assert!(output.status.success(), "{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn runs_are_compared_side_by_side_and_their_names_escaped() {
+    let dir = std::env::temp_dir().join(format!("io-report-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let (plain, odd) = (dir.join("before.json"), dir.join("<b>&after.json"));
+    save_baseline(&plain);
+    fs::copy(&plain, &odd).unwrap();
+
+    let markdown = io_bench(&["report", plain.to_str().unwrap(), odd.to_str().unwrap()]);
+    let stdout = String::from_utf8_lossy(&markdown.stdout);
+    assert!(markdown.status.success(), "{}{}", stdout, String::from_utf8_lossy(&markdown.stderr));
+    assert!(stdout.contains("before") && stdout.contains("<b>&after"), "{}", stdout);
+
+    let page = dir.join("report.html");
+    let html = io_bench(&["report", plain.to_str().unwrap(), odd.to_str().unwrap(), "--report", "html", "--report-file", page.to_str().unwrap()]);
+    assert!(html.status.success(), "{}", String::from_utf8_lossy(&html.stderr));
+    let page = fs::read_to_string(&page).unwrap();
+    assert!(page.contains("&lt;b&gt;&amp;after"), "{}", page);
+    assert!(!page.contains("<b>&after"), "{}", page);
+    assert!(page.contains("<svg"), "{}", page);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,57 @@
+use std::cell::Cell;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+
+use io::contents;
+use io::retry::RetryPolicy;
+
+#[test]
+fn retries_transient_errors_only() {
+    let policy = RetryPolicy { initial_backoff: Duration::ZERO, ..RetryPolicy::default() };
+    let tries = Cell::new(0);
+    let value = policy.run(|| {
+        tries.set(tries.get() + 1);
+        if tries.get() < 3 { Err(Error::from(ErrorKind::Interrupted)) } else { Ok(7) }
+    });
+    assert_eq!(value.unwrap(), 7);
+    assert_eq!(tries.get(), 3);
+
+    tries.set(0);
+    let e = policy.run(|| -> std::io::Result<()> {
+        tries.set(tries.get() + 1);
+        Err(Error::from(ErrorKind::WouldBlock))
+    });
+    assert_eq!(e.unwrap_err().kind(), ErrorKind::WouldBlock);
+    assert_eq!(tries.get(), policy.max_attempts);
+
+    tries.set(0);
+    let e = policy.run(|| -> std::io::Result<()> {
+        tries.set(tries.get() + 1);
+        Err(Error::from(ErrorKind::NotFound))
+    });
+    assert_eq!(e.unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(tries.get(), 1);
+
+    let custom = RetryPolicy { codes: vec![23], ..RetryPolicy::never() };
+    assert!(custom.is_retryable(&Error::from_raw_os_error(23)));
+    assert_eq!(policy.backoff(0), Duration::ZERO);
+    assert_eq!(RetryPolicy::default().backoff(3), Duration::from_millis(8));
+    assert_eq!(RetryPolicy::default().backoff(30), Duration::from_millis(100));
+}
+
+#[test]
+fn bulk_reads_and_writes() {
+    let dir = std::env::temp_dir().join(format!("io-retry-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let files: Vec<_> = (0..16).map(|i| (dir.join(format!("{}.txt", i)), format!("file {}", i))).collect();
+    contents::write_many(&files, &RetryPolicy::default()).unwrap();
+    let paths: Vec<_> = files.iter().map(|(path, _)| path.clone()).collect();
+    let read = contents::read_many(&paths, &RetryPolicy::default()).unwrap();
+    assert_eq!(read[9], b"file 9");
+
+    let e = contents::read_many(&[dir.join("missing")], &RetryPolicy::default()).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::NotFound);
+    assert!(e.to_string().contains("missing"));
+    fs::remove_dir_all(&dir).unwrap();
+}
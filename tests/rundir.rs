@@ -0,0 +1,29 @@
+//! Checks that run directories go away with their guard and that only those of processes
+//! that are gone count as stale.
+
+use std::env;
+use std::fs;
+
+use io::rundir::{self, PREFIX, RunDir};
+
+#[test]
+fn removed_on_drop_and_stale_only_when_orphaned() {
+    let parent = env::temp_dir().join(format!("io-rundir-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&parent);
+    let dir = RunDir::create(rundir::run_dir(&parent)).unwrap();
+    fs::write(dir.join("file"), b"x").unwrap();
+    // Above the largest pid Linux hands out, 2^22.
+    let orphan = parent.join(format!("{}{}-0", PREFIX, (1 << 22) + 1));
+    fs::create_dir_all(&orphan).unwrap();
+    // Old fixed names may be the user's own, so they are only ever listed apart.
+    let legacy = parent.join("temp_files");
+    fs::create_dir_all(&legacy).unwrap();
+    fs::create_dir_all(parent.join("bench_files_mine")).unwrap();
+
+    assert_eq!(rundir::stale(&parent).unwrap(), vec![orphan]);
+    assert_eq!(rundir::legacy(&parent).unwrap(), vec![legacy]);
+    let path = dir.to_path_buf();
+    drop(dir);
+    assert!(!path.exists());
+    fs::remove_dir_all(&parent).unwrap();
+}
@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use io::sample::{Estimate, Sampler};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-sample-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn files(dir: &Path, count: usize, size: usize) {
+    fs::create_dir_all(dir).unwrap();
+    for i in 0..count {
+        fs::write(dir.join(format!("f{}", i)), vec![0u8; size]).unwrap();
+    }
+}
+
+#[test]
+fn estimates_come_with_an_interval() {
+    assert_eq!(Estimate::of([]), Estimate::default());
+    let one = Estimate::of([4.0]);
+    assert_eq!((one.mean, one.margin), (4.0, f64::INFINITY));
+    let same = Estimate::of([7.0; 10]);
+    assert_eq!((same.mean, same.margin, same.relative()), (7.0, 0.0, 0.0));
+    let spread = Estimate::of([0.0, 10.0, 0.0, 10.0]);
+    assert_eq!(spread.mean, 5.0);
+    assert!((spread.margin - 1.96 * (100.0f64 / 3.0 / 4.0).sqrt()).abs() < 1e-9);
+    // A count can't be negative, so the interval stops at zero.
+    assert_eq!(Estimate { mean: 1.0, margin: 3.0 }.interval(), (0.0, 4.0));
+}
+
+#[test]
+fn a_balanced_tree_is_estimated_exactly() {
+    let root = scratch("balanced");
+    files(&root, 1, 10);
+    for a in 0..3 {
+        for b in 0..2 {
+            files(&root.join(format!("a{}/b{}", a, b)), 4, 10);
+        }
+    }
+    let sample = Sampler { probes: 20, seed: 7 }.run_measuring(&root, |_| vec![1.0, 2.0]).unwrap();
+    assert_eq!(sample.probes.len(), 20);
+    assert_eq!(sample.dirs(), Estimate { mean: 10.0, margin: 0.0 });
+    assert_eq!(sample.files(), Estimate { mean: 25.0, margin: 0.0 });
+    assert_eq!(sample.bytes(), Estimate { mean: 250.0, margin: 0.0 });
+    // A measurement of 1 per file totals the file count.
+    assert_eq!(sample.measured(0).mean, 25.0);
+    assert_eq!(sample.measured(1).mean, 50.0);
+    assert_eq!(sample.measured(2).mean, 0.0);
+    assert_eq!(sample.unreadable, 0);
+    assert!(sample.listed <= 10);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn a_lopsided_tree_is_estimated_within_its_interval() {
+    let root = scratch("lopsided");
+    files(&root.join("big/deep/deeper"), 200, 1);
+    for i in 0..9 {
+        files(&root.join(format!("small{}", i)), 2, 1);
+    }
+    let sampler = Sampler { probes: 2000, seed: 42 };
+    let sample = sampler.run(&root).unwrap();
+    let (low, high) = sample.files().interval();
+    assert!(low <= 218.0 && 218.0 <= high, "{:?}", sample.files());
+    assert!(sample.files().margin > 0.0);
+    // The same seed takes the same walks.
+    assert_eq!(sampler.run(&root).unwrap().probes, sample.probes);
+
+    assert!(Sampler::new(10).run(&root.join("missing")).is_err());
+    assert_eq!(Sampler::new(0).probes, 1);
+    fs::remove_dir_all(&root).unwrap();
+}
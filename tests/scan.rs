@@ -0,0 +1,54 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+
+use io::bench::{Options, Workload};
+use io::scan::{self, FANOUT, FILES_PER_DIR, Walker};
+
+#[test]
+fn the_tree_nests_files_under_two_levels() {
+    let root = std::path::Path::new("root");
+    let paths = scan::tree_paths(root, FILES_PER_DIR * FANOUT + 1);
+    assert_eq!(paths[0], root.join("d0/d0/file_0.txt"));
+    assert_eq!(paths[FILES_PER_DIR], root.join(format!("d0/d1/file_{}.txt", FILES_PER_DIR)));
+    assert_eq!(paths[FILES_PER_DIR * FANOUT], root.join(format!("d1/d0/file_{}.txt", FILES_PER_DIR * FANOUT)));
+}
+
+#[test]
+fn every_walker_counts_the_same_entries_without_following_symlinks() {
+    let dir = std::env::temp_dir().join(format!("io-scan-walk-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("a/b/c")).unwrap();
+    fs::create_dir_all(dir.join("empty")).unwrap();
+    for file in ["top", "a/one", "a/b/two", "a/b/c/three"] {
+        fs::write(dir.join(file), b"x").unwrap();
+    }
+    // A symlink back to the root is one entry, not a loop.
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&dir, dir.join("a/loop")).unwrap();
+    let expected = if cfg!(unix) { 9 } else { 8 };
+    for walker in Walker::ALL {
+        match walker.walk(&dir, &Options::default()) {
+            Ok(entries) => assert_eq!(entries, expected, "{}", walker.name()),
+            Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::Unsupported, "{}: {}", walker.name(), e),
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn run_times_each_walker_over_the_workload() {
+    let dir = std::env::temp_dir().join(format!("io-scan-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(200, 10), ..Options::default() };
+    let result = scan::run(&dir, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.walks.len() + result.skipped.len(), Walker::ALL.len());
+    // 200 files in four leaf directories under one top-level directory.
+    for walk in &result.walks {
+        assert_eq!(walk.entries, 205, "{}", walk.walker.name());
+    }
+    assert!(!dir.join("tree").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
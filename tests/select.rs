@@ -0,0 +1,50 @@
+use std::fs;
+use std::path::Path;
+
+use io::readonly::DataSet;
+use io::select::{Glob, Selector};
+
+#[test]
+fn globs_match_like_shells_do() {
+    let matches = |pattern: &str, path: &str| Glob::new(pattern).unwrap().matches(Path::new(path));
+    assert!(matches("**/*.rs", "src/lib.rs") && matches("**/*.rs", "lib.rs") && matches("*.rs", "a/b/c.rs"));
+    assert!(!matches("src/*.rs", "src/a/b.rs") && matches("src/**/*.rs", "src/a/b.rs") && matches("src/**/*.rs", "src/b.rs"));
+    assert!(matches("file_?.txt", "file_7.txt") && !matches("file_?.txt", "file_10.txt"));
+    assert!(matches("[a-c]*.{rs,toml}", "build.toml") && !matches("[!a-c]*", "cat") && matches("[!a-c]*", "dog"));
+    assert!(matches("./docs/**", "docs/a/b.md") && !matches("docs/**/x", "other/x"));
+    for invalid in ["", "{a,b", "a}", "[ab", "{a,{b}}"] {
+        assert!(Glob::new(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn selected_data_sets_hold_only_matching_files() {
+    let dir = std::env::temp_dir().join(format!("io-select-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src/nested")).unwrap();
+    fs::create_dir_all(dir.join("target")).unwrap();
+    for (path, size) in [("src/lib.rs", 10), ("src/nested/mod.rs", 2000), ("src/notes.md", 5), ("target/out.rs", 7)] {
+        fs::write(dir.join(path), vec![b'x'; size]).unwrap();
+    }
+    let selector = Selector::new().include(Glob::new("**/*.rs").unwrap()).exclude(Glob::new("target/**").unwrap());
+    let set = DataSet::select(&dir, &selector).unwrap();
+    let mut paths: Vec<_> = set.paths().iter().map(|path| path.strip_prefix(&dir).unwrap().to_path_buf()).collect();
+    paths.sort();
+    assert_eq!(paths, [Path::new("src/lib.rs"), Path::new("src/nested/mod.rs")]);
+    assert_eq!(set.bytes(), 2010);
+
+    let small = DataSet::select(&dir, &selector.clone().filter(|_, size| size < 100)).unwrap();
+    assert_eq!(small.len(), 1);
+    assert_eq!(DataSet::select(&dir, &Selector::new()).unwrap().len(), 4);
+
+    #[cfg(feature = "bench")]
+    {
+        let options = io::bench::Options::default();
+        let result = io::read::run_set(&set, &io::bench::ReadMethod::ALL, &options).unwrap();
+        assert!(result.failures.is_empty(), "{:?}", result.failures);
+        assert_eq!(result.read_bytes, 2010);
+        let verify = io::bench::Options { verify: true, ..io::bench::Options::default() };
+        assert!(io::read::run_set(&set, &io::bench::ReadMethod::ALL, &verify).is_err());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,37 @@
+#![cfg(all(unix, feature = "bench"))]
+
+use std::collections::HashSet;
+use std::fs;
+
+use io::selftest::{self, Backend, Check, Outcome};
+
+#[test]
+fn every_backend_conforms() {
+    let dir = std::env::temp_dir().join(format!("io-selftest-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let backends = Backend::all();
+    let results = selftest::run(&dir, &backends).unwrap();
+    assert_eq!(results.len(), backends.len());
+    for conformance in &results {
+        for check in Check::ALL {
+            if let Outcome::Fail(why) = conformance.outcome(check) {
+                panic!("{} fails {}: {}", conformance.backend.name(), check.name(), why);
+            }
+        }
+        assert!(!conformance.failed());
+        assert!(!matches!(conformance.outcome(Check::Content), Outcome::NotApplicable), "{} had no content checked", conformance.backend.name());
+    }
+    // Each backend cleans up after itself.
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir(&dir).unwrap();
+}
+
+#[test]
+fn backends_have_distinct_names() {
+    let backends = Backend::all();
+    let names: HashSet<String> = backends.iter().map(Backend::name).collect();
+    assert_eq!(names.len(), backends.len());
+    let checks: HashSet<&str> = Check::ALL.iter().map(|check| check.name()).collect();
+    assert_eq!(checks.len(), Check::ALL.len());
+}
@@ -0,0 +1,72 @@
+use io::sketch::{Running, TDigest};
+
+#[test]
+fn running_matches_the_direct_summary() {
+    let values: Vec<f64> = (0..1000).map(|i| ((i * 37) % 101) as f64).collect();
+    let mut running = Running::default();
+    for &value in &values {
+        running.add(value);
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    assert_eq!(running.count(), 1000);
+    assert!((running.mean() - mean).abs() < 1e-9, "{} vs {}", running.mean(), mean);
+    assert!((running.stddev() - variance.sqrt()).abs() < 1e-9);
+    assert_eq!((running.min(), running.max()), (0.0, 100.0));
+
+    let mut one = Running::default();
+    one.add(5.0);
+    assert_eq!((one.stddev(), one.min(), one.max()), (0.0, 5.0, 5.0));
+}
+
+#[test]
+fn quantiles_stay_close_on_many_values() {
+    let mut digest = TDigest::default();
+    // A shuffled 0..100_000, so values don't arrive sorted.
+    for i in 0..100_000u64 {
+        digest.add(((i * 7919) % 100_000) as f64);
+    }
+    assert_eq!(digest.count(), 100_000);
+    assert_eq!((digest.min(), digest.max()), (0.0, 99_999.0));
+    for (q, tolerance) in [(0.5, 0.01), (0.9, 0.01), (0.99, 0.002), (0.999, 0.001)] {
+        let estimate = digest.quantile(q) / 100_000.0;
+        assert!((estimate - q).abs() < tolerance, "p{}: {}", q * 100.0, estimate);
+    }
+    assert_eq!(digest.quantile(0.0), 0.0);
+    assert_eq!(digest.quantile(1.0), 99_999.0);
+}
+
+#[test]
+fn merged_digests_answer_for_everything_they_saw() {
+    let (mut low, mut high) = (TDigest::default(), TDigest::default());
+    for i in 0..10_000 {
+        low.add(i as f64);
+        high.add((10_000 + i) as f64);
+    }
+    low.merge(&high);
+    low.merge(&TDigest::default());
+    assert_eq!(low.count(), 20_000);
+    assert_eq!((low.min(), low.max()), (0.0, 19_999.0));
+    assert!((low.quantile(0.5) / 20_000.0 - 0.5).abs() < 0.01, "{}", low.quantile(0.5));
+
+    let mut empty = TDigest::default();
+    assert_eq!(empty.quantile(0.5), 0.0);
+    empty.merge(&high);
+    assert_eq!((empty.count(), empty.min()), (10_000, 10_000.0));
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn embedded_runs_report_latency_from_sketches() {
+    use std::process::Command;
+
+    let bench = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_io")).arg("bench").args(args).output().unwrap();
+    let output = bench(&["--embedded", "--files", "20", "--size", "1K"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("Per-file latency:") && stdout.contains("p99"), "{}", stdout);
+
+    let refused = bench(&["--embedded", "--html", "report.html"]);
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("--embedded"));
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "rayon")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use io::population::hash_bytes;
+use io::snapshot::{self, Config, Instability, OP_SCOPE};
+use io::trace::OpId;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-snapshot-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn a_quiet_tree_is_read_whole_in_one_pass() {
+    let root = scratch("quiet");
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    fs::write(root.join("one"), b"first").unwrap();
+    fs::write(root.join("a/b/two"), vec![2u8; 5000]).unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(root.join("one"), root.join("a/link")).unwrap();
+
+    for config in [Config::default(), Config { hash: true, ..Config::default() }] {
+        let snapshot = snapshot::read(&root, &config).unwrap();
+        assert!(snapshot.unstable.is_empty(), "{:?}", snapshot.unstable);
+        let paths: Vec<&Path> = snapshot.files.iter().map(|file| file.path.as_path()).collect();
+        assert_eq!(paths, [Path::new("a/b/two"), Path::new("one")]);
+        assert_eq!(snapshot.bytes(), 5005);
+        assert_eq!(snapshot.retried(), 0);
+        for file in &snapshot.files {
+            assert_eq!(file.op, OpId::new(OP_SCOPE, &file.path));
+            assert_eq!(file.generation.hash, Some(hash_bytes(&file.contents)));
+            assert_eq!(file.generation.size, file.contents.len() as u64);
+            assert_eq!(file.attempts, 1);
+        }
+    }
+    assert!(snapshot::read(&root.join("missing"), &Config::default()).is_err());
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn files_written_during_the_read_are_whole_versions_or_reported() {
+    let root = scratch("busy");
+    let paths: Vec<PathBuf> = (0..8).map(|i| root.join(format!("f{}", i))).collect();
+    for path in &paths {
+        fs::write(path, vec![0u8; 1000]).unwrap();
+    }
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        // Rewrites every file with a new version of a different size, and replaces one file
+        // at a time with a fresh inode, until the snapshots are done.
+        scope.spawn(|| {
+            let mut version = 1u8;
+            while !stop.load(Ordering::Relaxed) {
+                for path in &paths {
+                    fs::write(path, vec![version; 1000 + version as usize]).unwrap();
+                }
+                let temp = root.join("temp");
+                fs::write(&temp, vec![version; 10]).unwrap();
+                fs::rename(&temp, &paths[version as usize % paths.len()]).unwrap();
+                version = version.wrapping_add(1).max(1);
+            }
+        });
+        for _ in 0..20 {
+            let snapshot = snapshot::read(&root, &Config { retries: 1, hash: false }).unwrap();
+            for file in &snapshot.files {
+                // Never two versions in one copy: every byte the same, and as many as the
+                // version had.
+                assert_eq!(file.generation.size, file.contents.len() as u64, "{}", file.path.display());
+                assert!(file.contents.iter().all(|&byte| byte == file.contents[0]), "{} is torn", file.path.display());
+                assert!(file.attempts <= 2);
+            }
+            for unstable in &snapshot.unstable {
+                assert!(
+                    matches!(unstable.reason, Instability::Changed { attempts: 2 } | Instability::Vanished | Instability::Appeared),
+                    "{:?}",
+                    unstable
+                );
+            }
+            // Every file is either in the snapshot or reported, once.
+            for i in 0..paths.len() {
+                let name = PathBuf::from(format!("f{}", i));
+                let read = snapshot.files.iter().filter(|file| file.path == name).count();
+                let reported = snapshot.unstable.iter().filter(|unstable| unstable.path == name).count();
+                assert_eq!(read + reported, 1, "{}", name.display());
+            }
+        }
+        stop.store(true, Ordering::Relaxed);
+    });
+    fs::remove_dir_all(&root).unwrap();
+}
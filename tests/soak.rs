@@ -0,0 +1,57 @@
+use std::fs;
+use std::time::{Duration, UNIX_EPOCH};
+
+use io::soak::{self, Format, Iteration, Log, State};
+
+fn iteration(n: usize) -> Iteration {
+    Iteration {
+        iteration: n,
+        timestamp: UNIX_EPOCH + Duration::from_millis(1_700_000_000_500),
+        elapsed: Duration::from_millis(2500),
+        strategy: "Smart".to_string(),
+        ms: [1.0, 2.0, 3.0, 4.5],
+        state: State {
+            temperatures: vec![("Composite °C".to_string(), 41.85), ("Sensor 2 °C".to_string(), 48.0)],
+            cached: Some(4096),
+            dirty: Some(0),
+            writeback: None,
+            available: Some(1 << 30),
+        },
+    }
+}
+
+#[test]
+fn reads_meminfo_fields_in_bytes() {
+    let meminfo = "MemTotal:       16318480 kB\nCached:          2048 kB\nSwapCached:        12 kB\nDirty:             8 kB\n";
+    assert_eq!(soak::meminfo_bytes(meminfo, "Cached"), Some(2048 * 1024));
+    assert_eq!(soak::meminfo_bytes(meminfo, "Dirty"), Some(8 * 1024));
+    assert_eq!(soak::meminfo_bytes(meminfo, "Writeback"), None);
+}
+
+#[test]
+fn csv_rows_follow_the_header() {
+    let row = iteration(3).csv_row();
+    assert_eq!(row, "3,1700000000.500,2.500,Smart,1.000,2.000,3.000,4.500,10.500,48.0,4096,0,,1073741824");
+    assert_eq!(row.split(',').count(), soak::CSV_HEADER.split(',').count());
+}
+
+#[test]
+fn logs_append_with_one_csv_header() {
+    let dir = std::env::temp_dir().join(format!("io-soak-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let csv = dir.join("soak.csv");
+    for n in 1..=2 {
+        Log::open(&csv).unwrap().append(&iteration(n)).unwrap();
+    }
+    let text = fs::read_to_string(&csv).unwrap();
+    assert_eq!(text.lines().count(), 3);
+    assert_eq!(text.lines().next(), Some(soak::CSV_HEADER));
+
+    let jsonl = dir.join("soak.jsonl");
+    assert_eq!(Format::of(&jsonl), Format::Jsonl);
+    Log::open(&jsonl).unwrap().append(&iteration(1)).unwrap();
+    let line = io::json::parse(fs::read_to_string(&jsonl).unwrap().trim()).unwrap();
+    assert_eq!(line.get("total_ms").and_then(|total| total.as_f64()), Some(10.5));
+    assert_eq!(line.get("temperatures_c").and_then(|temperatures| temperatures.get("Sensor 2 °C")).and_then(|celsius| celsius.as_f64()), Some(48.0));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,49 @@
+use std::io::{Error, ErrorKind};
+
+use io::filesystem::Filesystem;
+use io::space;
+
+#[test]
+fn projects_whole_blocks_twice_over() {
+    assert_eq!(space::projected(10, 100), 2 * 10 * 4096);
+    assert_eq!(space::projected(3, 8192), 2 * 3 * 8192);
+    assert_eq!(space::projected(0, 4096), 0);
+}
+
+#[test]
+fn checks_fail_when_the_workload_cannot_fit() {
+    let dir = std::env::temp_dir();
+    space::check(&dir, 0).unwrap();
+    if Filesystem::of(&dir).and_then(|filesystem| filesystem.available).is_some() {
+        let e = space::check(&dir, u64::MAX).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::StorageFull);
+        assert!(space::is_no_space(&e));
+    }
+    assert!(space::is_no_space(&Error::from(ErrorKind::QuotaExceeded)));
+    assert!(!space::is_no_space(&Error::from(ErrorKind::NotFound)));
+}
+
+#[cfg(all(target_os = "linux", feature = "libc"))]
+#[test]
+fn reservations_hold_space_until_released() {
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+
+    use io::space::Reservation;
+
+    let dir = std::env::temp_dir().join(format!("io-space-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let reservation = match Reservation::new(&dir, 1 << 20) {
+        Ok(reservation) => reservation,
+        // tmpfs and ext4 allocate; some test machines' temp directories don't.
+        Err(e) if e.kind() == ErrorKind::Unsupported => return,
+        Err(e) => panic!("{}", e),
+    };
+    let placeholder = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().metadata().unwrap();
+    assert!(placeholder.blocks() * 512 >= 1 << 20);
+    assert!(reservation.is_held());
+    reservation.release();
+    assert!(!reservation.is_held());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
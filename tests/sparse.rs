@@ -0,0 +1,46 @@
+#![cfg(all(unix, feature = "libc"))]
+
+use std::fs::{self, File, OpenOptions};
+
+use io::sparse;
+
+#[test]
+fn sparse_files_keep_their_extents_and_read_zeros_elsewhere() {
+    let dir = std::env::temp_dir().join(format!("io-sparse-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("image");
+    let mib = 1 << 20;
+    sparse::write_extents(&path, 8 * mib, &[(0, b"head"), (4 * mib, b"middle")]).unwrap();
+    let file = File::open(&path).unwrap();
+    assert_eq!(file.metadata().unwrap().len(), 8 * mib);
+    let bytes = fs::read(&path).unwrap();
+    assert_eq!(&bytes[..4], b"head");
+    assert_eq!(&bytes[4 * mib as usize..4 * mib as usize + 6], b"middle");
+    assert_eq!(bytes.iter().filter(|&&b| b != 0).count(), 10);
+
+    // Wherever the filesystem puts the holes, the data is inside the ranges it reports.
+    let ranges = sparse::data_ranges(&file).unwrap();
+    assert!(ranges.windows(2).all(|pair| pair[0].end <= pair[1].start));
+    for offset in [0, 4 * mib] {
+        assert!(ranges.iter().any(|range| range.contains(&offset)), "{} not in {:?}", offset, ranges);
+    }
+    let data = sparse::read_data(&file).unwrap();
+    assert_eq!(data.iter().map(|(offset, bytes)| *offset..*offset + bytes.len() as u64).collect::<Vec<_>>(), ranges);
+    if ranges.len() > 1 {
+        assert!(sparse::allocated_bytes(&file).unwrap() < 8 * mib);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let whole = dir.join("whole");
+        fs::write(&whole, vec![1u8; 4 * mib as usize]).unwrap();
+        let file = OpenOptions::new().write(true).open(&whole).unwrap();
+        if sparse::punch_hole(&file, mib, 2 * mib).is_ok() {
+            let bytes = fs::read(&whole).unwrap();
+            assert_eq!(bytes.len(), 4 * mib as usize);
+            assert!(bytes[mib as usize..3 * mib as usize].iter().all(|&b| b == 0));
+            assert!(bytes[..mib as usize].iter().chain(&bytes[3 * mib as usize..]).all(|&b| b == 1));
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "sqlite")]
+
+use std::fs;
+
+use io::sqlite::{self, BlobStore};
+
+#[test]
+fn rows_replace_read_back_and_delete() {
+    let dir = std::env::temp_dir().join(format!("io-sqlite-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("files.sqlite");
+    let mut store = BlobStore::open(&path).unwrap();
+    store.set_durable(false).unwrap();
+    store.begin().unwrap();
+    for i in 0..100 {
+        store.put(&format!("file_{}", i), &[i as u8; 300]).unwrap();
+    }
+    store.commit().unwrap();
+    store.put("file_0", b"").unwrap();
+    assert_eq!(store.len().unwrap(), 100);
+    assert_eq!(store.get("file_0").unwrap().unwrap(), b"");
+    assert!(store.delete("file_1").unwrap());
+    assert!(!store.delete("file_1").unwrap());
+    assert_eq!(store.get("file_1").unwrap(), None);
+
+    // A second connection sees what the first committed.
+    let mut other = BlobStore::open(&path).unwrap();
+    assert_eq!(other.get("file_99").unwrap().unwrap(), [99; 300]);
+    assert_eq!(other.len().unwrap(), 99);
+    drop((store, other));
+    sqlite::remove(&path).unwrap();
+    assert!(!path.exists());
+
+    let missing = BlobStore::open(dir.join("missing").join("files.sqlite")).err().unwrap();
+    assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use io::cas::Checkout;
+use io::stash;
+
+fn write_tree(root: &Path) {
+    fs::create_dir_all(root.join("deps/nested")).unwrap();
+    fs::create_dir_all(root.join("empty")).unwrap();
+    fs::write(root.join("top"), b"top").unwrap();
+    fs::write(root.join("deps/a"), b"aaaa").unwrap();
+    fs::write(root.join("deps/nested/b"), b"bb").unwrap();
+    #[cfg(unix)]
+    io::links::symlink(Path::new("deps/a"), &root.join("link")).unwrap();
+}
+
+fn assert_tree(root: &Path) {
+    assert_eq!(fs::read(root.join("top")).unwrap(), b"top");
+    assert_eq!(fs::read(root.join("deps/a")).unwrap(), b"aaaa");
+    assert_eq!(fs::read(root.join("deps/nested/b")).unwrap(), b"bb");
+    assert!(root.join("empty").is_dir());
+    #[cfg(unix)]
+    assert_eq!(fs::read_link(root.join("link")).unwrap(), Path::new("deps/a"));
+}
+
+#[test]
+fn trees_round_trip_by_every_method() {
+    let dir = std::env::temp_dir().join(format!("io-stash-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let (cache, saved) = (dir.join("cache"), dir.join("saved"));
+    write_tree(&cache);
+    for method in [None].into_iter().chain(Checkout::ALL.map(Some)) {
+        let report = match stash::snapshot(&cache, &saved, method) {
+            Err(e) if e.kind() == ErrorKind::Unsupported => {
+                assert_eq!(method, Some(Checkout::Reflink));
+                fs::remove_dir_all(&saved).unwrap();
+                continue;
+            }
+            report => report.unwrap(),
+        };
+        assert_eq!((report.files, report.bytes, report.dirs), (3, 9, 3));
+        match method {
+            Some(method) => assert_eq!(report.method, method),
+            None => assert_ne!(report.method, Checkout::HardLink, "hard links are only used when asked for"),
+        }
+        assert_tree(&saved);
+
+        fs::remove_dir_all(&cache).unwrap();
+        stash::restore(&saved, &cache, method).unwrap();
+        assert_tree(&cache);
+        fs::remove_dir_all(&saved).unwrap();
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn copies_are_independent_of_the_tree() {
+    let dir = std::env::temp_dir().join(format!("io-stash-independent-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let (cache, saved) = (dir.join("cache"), dir.join("saved"));
+    write_tree(&cache);
+    stash::snapshot(&cache, &saved, None).unwrap();
+    fs::write(cache.join("top"), b"changed").unwrap();
+    assert_eq!(fs::read(saved.join("top")).unwrap(), b"top");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_destination_with_entries_is_refused() {
+    let dir = std::env::temp_dir().join(format!("io-stash-occupied-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let (cache, saved) = (dir.join("cache"), dir.join("saved"));
+    write_tree(&cache);
+    fs::create_dir_all(&saved).unwrap();
+    fs::write(saved.join("stale"), b"old").unwrap();
+    let error = stash::snapshot(&cache, &saved, None).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+    assert_eq!(fs::read_dir(&saved).unwrap().count(), 1);
+
+    // An empty directory is fine.
+    fs::remove_file(saved.join("stale")).unwrap();
+    stash::snapshot(&cache, &saved, None).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn every_method_restores_the_workload() {
+    let dir = std::env::temp_dir().join(format!("io-stash-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = io::bench::Options { workload: io::bench::Workload::new(40, 100), verify: true, ..io::bench::Options::default() };
+    let result = stash::run(&dir, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.methods.iter().map(|method| method.method).collect::<Vec<_>>(), Checkout::ALL);
+    for method in &result.methods {
+        assert_eq!(method.snapshot.is_some(), method.restore.is_some());
+        assert!(method.method == Checkout::Reflink || method.snapshot.is_some());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "tokio")]
+
+use std::fs;
+
+use futures::StreamExt;
+use io::contents::Order;
+use io::retry::RetryPolicy;
+use io::stream::{self, Backend, Completions, Op, Output, StreamOptions};
+
+#[test]
+fn completions_stay_within_capacity() {
+    let dir = std::env::temp_dir().join(format!("io-stream-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(2).build().unwrap();
+
+    let writes = (0..20).map(|i| Op::Write(dir.join(format!("{}.txt", i)), vec![b'x'; i]));
+    let mut completions = Completions::new(writes, 3);
+    let mut seen = [false; 20];
+    runtime.block_on(async {
+        while let Some(completion) = completions.next().await {
+            assert!(completions.in_flight() < 3);
+            match completion.result.unwrap() {
+                Output::Written(bytes) => assert_eq!(bytes, completion.index as u64),
+                other => panic!("unexpected {:?}", other),
+            }
+            seen[completion.index] = true;
+        }
+    });
+    assert!(seen.iter().all(|&seen| seen));
+
+    let ops = [Op::Read(dir.join("5.txt")), Op::Remove(dir.join("missing"))];
+    let mut results: Vec<_> = runtime.block_on(Completions::new(ops, 0).collect());
+    results.sort_by_key(|completion| completion.index);
+    assert!(matches!(&results[0].result, Ok(Output::Read(bytes)) if bytes.len() == 5));
+    assert_eq!(results[1].path, dir.join("missing"));
+    assert_eq!(results[1].result.as_ref().unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn read_streams_keep_input_order() {
+    let dir = std::env::temp_dir().join(format!("io-stream-reads-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<_> = (0..30).map(|i| dir.join(format!("{}.txt", i))).collect();
+    for (i, path) in paths.iter().enumerate() {
+        fs::write(path, vec![b'x'; i]).unwrap();
+    }
+    let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(2).build().unwrap();
+    let options = StreamOptions { in_flight: 4, order: Order::Input, backend: Backend::Blocking };
+    let reads: Vec<_> = runtime.block_on(stream::read_many_stream(paths.clone(), &options, &RetryPolicy::default()).collect());
+    assert_eq!(reads.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), paths);
+    assert!(reads.iter().enumerate().all(|(i, (_, read))| read.as_ref().unwrap().len() == i));
+    let options = StreamOptions { order: Order::Completion, ..options };
+    let unordered = runtime.block_on(stream::read_many_stream(paths, &options, &RetryPolicy::default()).count());
+    assert_eq!(unordered, 30);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn every_backend_writes_reads_and_deletes() {
+    let dir = std::env::temp_dir().join(format!("io-stream-backends-{}", std::process::id()));
+    let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(2).build().unwrap();
+    let policy = RetryPolicy::never();
+    for backend in Backend::ALL {
+        fs::create_dir_all(&dir).unwrap();
+        let files: Vec<_> = (0..150).map(|i| (dir.join(format!("{}.txt", i)), vec![b'0' + (i % 10) as u8; i * 7])).collect();
+        let paths: Vec<_> = files.iter().map(|(path, _)| path.clone()).collect();
+        let options = StreamOptions { in_flight: 100, order: Order::Input, backend };
+
+        let written: Vec<_> = runtime.block_on(stream::write_many_stream(files.clone(), &options, &policy).collect());
+        assert_eq!(written.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), paths, "{}", backend.name());
+        assert!(written.iter().enumerate().all(|(i, (_, written))| *written.as_ref().unwrap() == i as u64 * 7));
+        let read: Vec<_> = runtime.block_on(stream::read_many_stream(paths.clone(), &options, &policy).collect());
+        assert!(read.into_iter().zip(&files).all(|((_, read), (_, bytes))| read.unwrap() == *bytes), "{}", backend.name());
+
+        let mut missing = paths.clone();
+        missing.push(dir.join("missing"));
+        let mut deleted: Vec<_> = runtime.block_on(stream::delete_many_stream(missing, &StreamOptions { order: Order::Completion, ..options }, &policy).collect());
+        assert_eq!(deleted.len(), 151);
+        let (_, error) = deleted.remove(deleted.iter().position(|(path, _)| path.ends_with("missing")).unwrap());
+        assert_eq!(error.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert!(deleted.iter().all(|(_, deleted)| deleted.is_ok()));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn bulk_futures_fail_on_the_first_error() {
+    let dir = std::env::temp_dir().join(format!("io-stream-futures-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(2).build().unwrap();
+    let policy = RetryPolicy::default();
+    let files: Vec<_> = (0..10).map(|i| (dir.join(format!("{}.txt", i)), vec![b'x'; i])).collect();
+    let paths: Vec<_> = files.iter().map(|(path, _)| path.clone()).collect();
+    runtime.block_on(async {
+        stream::write_many(files.clone(), &policy).await.unwrap();
+        let read = stream::read_many(paths.clone(), &policy).await.unwrap();
+        assert_eq!(read, files.iter().map(|(_, bytes)| bytes.clone()).collect::<Vec<_>>());
+
+        let error = stream::read_many(vec![paths[0].clone(), dir.join("missing")], &policy).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("missing"));
+        stream::delete_many(paths, &policy).await.unwrap();
+    });
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
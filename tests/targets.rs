@@ -0,0 +1,80 @@
+//! Checks that the crate builds for the ARM and RISC-V Linux targets it supports and for
+//! Android and iOS phones, and that the library's WASI subset builds for `wasm32-wasip1`.
+//!
+//! This shells out to `cargo check` once per target whose standard library is installed
+//! (`rustup target add aarch64-unknown-linux-gnu riscv64gc-unknown-linux-gnu
+//! aarch64-linux-android aarch64-apple-ios wasm32-wasip1`) and skips the others, so it is
+//! ignored by default: run it with `cargo test --test targets -- --ignored`.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+const TARGETS: &[&str] = &["aarch64-unknown-linux-gnu", "riscv64gc-unknown-linux-gnu", "aarch64-linux-android", "aarch64-apple-ios"];
+/// The sandbox has no mappings, threads or libc beyond WASI's, so only the bulk API.
+const WASI: &str = "wasm32-wasip1";
+const WASI_FEATURES: &str = "rayon,mmap,capi";
+
+fn installed_targets(rustc: &str, targets: &[&str]) -> Vec<String> {
+    let Ok(output) = Command::new(rustc).args(["--print", "sysroot"]).output() else {
+        return Vec::new();
+    };
+    let sysroot = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    targets
+        .iter()
+        .filter(|target| Path::new(&sysroot).join("lib/rustlib").join(target).join("lib").is_dir())
+        .map(|target| target.to_string())
+        .collect()
+}
+
+#[test]
+#[ignore]
+fn arm_riscv_and_mobile_targets_compile() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let targets = installed_targets(&rustc, TARGETS);
+    for target in TARGETS.iter().filter(|target| !targets.iter().any(|installed| installed == *target)) {
+        eprintln!("skipping {}: its standard library isn't installed", target);
+    }
+    let mut failures = Vec::new();
+
+    for target in &targets {
+        let status = Command::new(&cargo)
+            .current_dir(manifest_dir)
+            .args(["check", "--quiet", "--all-targets", "--target", target])
+            .arg("--target-dir")
+            .arg(manifest_dir.join("target/cross-check"))
+            .env("RUSTFLAGS", "-D warnings")
+            .status()
+            .expect("failed to run cargo");
+        if !status.success() {
+            failures.push(target.clone());
+        }
+    }
+
+    assert!(failures.is_empty(), "targets failed to compile: {:?}", failures);
+}
+
+#[test]
+#[ignore]
+fn wasi_subset_compiles() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    if installed_targets(&rustc, &[WASI]).is_empty() {
+        eprintln!("skipping {}: its standard library isn't installed", WASI);
+        return;
+    }
+    for features in ["", WASI_FEATURES] {
+        let status = Command::new(&cargo)
+            .current_dir(manifest_dir)
+            .args(["check", "--quiet", "--lib", "--target", WASI, "--no-default-features", "--features", features])
+            .arg("--target-dir")
+            .arg(manifest_dir.join("target/cross-check"))
+            .env("RUSTFLAGS", "-D warnings")
+            .status()
+            .expect("failed to run cargo");
+        assert!(status.success(), "{} failed to compile with features '{}'", WASI, features);
+    }
+}
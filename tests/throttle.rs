@@ -0,0 +1,31 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use io::throttle::{Throttle, TokenBucket};
+
+#[test]
+fn bucket_refills_at_its_rate() {
+    let bucket = TokenBucket::new(1000.0);
+    // A full bucket holds a tenth of a second's worth.
+    assert_eq!(bucket.take(100.0), Duration::ZERO);
+    let start = Instant::now();
+    // Going into debt sleeps it off.
+    let waited = bucket.take(50.0);
+    assert!(waited >= Duration::from_millis(45), "{:?}", waited);
+    assert!(start.elapsed() >= Duration::from_millis(45));
+}
+
+#[test]
+fn limits_are_shared_by_workers() {
+    let throttle = Throttle::new(None, Some(2000.0));
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| (0..150).for_each(|_| throttle.op()));
+        }
+    });
+    // 600 operations at 2000/s with a 200-operation burst take at least 0.2 s.
+    assert!(start.elapsed() >= Duration::from_millis(190));
+    assert!(throttle.waited() > Duration::ZERO);
+    assert_eq!(throttle.bytes_per_second(), None);
+}
@@ -0,0 +1,15 @@
+use std::path::Path;
+
+use io::rundir;
+use io::trace::OpId;
+
+#[test]
+fn ids_ignore_where_the_run_directory_is() {
+    let relative = OpId::new("Smart/read", Path::new("Smart/file_1.txt"));
+    let here = rundir::run_dir(Path::new("/tmp")).join("Smart/file_1.txt");
+    let elsewhere = Path::new("/mnt/scratch").join(format!("{}1-ff", rundir::PREFIX)).join("Smart/file_1.txt");
+    assert_eq!(OpId::new("Smart/read", &here), relative);
+    assert_eq!(OpId::new("Smart/read", &elsewhere), relative);
+    assert_ne!(OpId::new("Smart/update", &here), relative);
+    assert_ne!(OpId::new("Smart/read", Path::new("/tmp/Smart/file_1.txt")), relative);
+}
@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::Path;
+
+use io::transform::{self, EachChunk, Identity, LineEndings, Replace, CHUNK};
+
+#[test]
+fn transforms_carry_across_chunks() {
+    let dir = std::env::temp_dir().join(format!("io-transform-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("source");
+
+    // A `\r\n` and a placeholder straddle the first chunk boundary.
+    let mut text = vec![b'a'; CHUNK - 1];
+    text.extend_from_slice(b"\r\n{{name}} and {{name}}\r");
+    fs::write(&source, &text).unwrap();
+
+    let lf = dir.join("lf");
+    transform::copy_with(&source, &lf, &LineEndings::Lf).unwrap();
+    let expected = [&text[..CHUNK - 1], b"\n{{name}} and {{name}}\r"].concat();
+    assert_eq!(fs::read(&lf).unwrap(), expected);
+
+    let crlf = dir.join("crlf");
+    transform::copy_with(&lf, &crlf, &LineEndings::Crlf).unwrap();
+    assert_eq!(fs::read(&crlf).unwrap(), text);
+
+    let mut shifted = vec![b'a'; CHUNK - 4];
+    shifted.extend_from_slice(b"{{name}}{{na");
+    fs::write(&source, &shifted).unwrap();
+    let filled = dir.join("filled");
+    let written = transform::copy_with(&source, &filled, &Replace::new([("{{name}}", "io")])).unwrap();
+    let expected = [&shifted[..CHUNK - 4], b"io{{na"].concat();
+    assert_eq!(fs::read(&filled).unwrap(), expected);
+    assert_eq!(written, expected.len() as u64);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn copies_many_in_parallel() {
+    let dir = std::env::temp_dir().join(format!("io-transform-many-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let pairs: Vec<_> = (0..32)
+        .map(|i| {
+            let from = dir.join(format!("{}.txt", i));
+            fs::write(&from, format!("file {}\n", i)).unwrap();
+            (from, dir.join(format!("{}.out", i)))
+        })
+        .collect();
+
+    let upper = EachChunk(|_: &Path, input: &[u8], output: &mut Vec<u8>| output.extend(input.to_ascii_uppercase()));
+    let written = transform::copy_many_with(&pairs, &upper).unwrap();
+    assert_eq!(written, pairs.iter().map(|(from, _)| fs::metadata(from).unwrap().len()).sum::<u64>());
+    assert_eq!(fs::read_to_string(&pairs[7].1).unwrap(), "FILE 7\n");
+
+    transform::copy_many_with(&pairs, &Identity).unwrap();
+    assert_eq!(fs::read_to_string(&pairs[7].1).unwrap(), "file 7\n");
+
+    let missing = [(dir.join("missing"), dir.join("missing.out"))];
+    let e = transform::copy_many_with(&missing, &Identity).unwrap_err();
+    assert!(e.to_string().contains("missing"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
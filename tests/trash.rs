@@ -0,0 +1,50 @@
+use std::fs;
+use std::path::PathBuf;
+
+use io::retry::RetryPolicy;
+use io::trash::Trash;
+
+#[test]
+fn staged_deletes_commit_or_roll_back() {
+    let dir = std::env::temp_dir().join(format!("io-trash-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("a")).unwrap();
+    fs::create_dir_all(dir.join("b")).unwrap();
+    // The same name in two directories, which staging must keep apart.
+    let paths: Vec<PathBuf> = (0..10).map(|i| dir.join(if i % 2 == 0 { "a" } else { "b" }).join(format!("file_{}", i / 2))).collect();
+    for (i, path) in paths.iter().enumerate() {
+        fs::write(path, i.to_string()).unwrap();
+    }
+    let trash = Trash::new(dir.join(".trash")).unwrap();
+    let policy = RetryPolicy::default();
+
+    let staged = trash.delete_many(&paths, &policy).unwrap();
+    assert_eq!(staged.len(), 10);
+    assert!(paths.iter().all(|path| !path.exists()));
+    staged.rollback().unwrap();
+    for (i, path) in paths.iter().enumerate() {
+        assert_eq!(fs::read_to_string(path).unwrap(), i.to_string());
+    }
+    assert_eq!(fs::read_dir(trash.dir()).unwrap().count(), 0);
+
+    // One missing file stages nothing.
+    let mut with_missing = paths.clone();
+    with_missing.push(dir.join("missing"));
+    assert!(trash.delete_many(&with_missing, &policy).is_err());
+    assert!(paths.iter().all(|path| path.exists()));
+
+    // A path taken again keeps its new file; the old one stays in the trash.
+    let staged = trash.delete_many(&paths[..2], &policy).unwrap();
+    let batch = staged.dir().to_path_buf();
+    fs::write(&paths[0], "new").unwrap();
+    assert_eq!(staged.rollback().unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(fs::read_to_string(&paths[0]).unwrap(), "new");
+    assert_eq!(fs::read_to_string(&paths[1]).unwrap(), "1");
+    assert_eq!(fs::read_dir(&batch).unwrap().count(), 1);
+
+    let staged = trash.delete_many(&paths, &policy).unwrap();
+    let batch = staged.dir().to_path_buf();
+    staged.commit().unwrap();
+    assert!(!batch.exists() && paths.iter().all(|path| !path.exists()));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,42 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+
+use io::bench::{self, MmapFlush, Options, UpdateMethod, Workload};
+use io::update;
+
+#[test]
+fn every_update_method_leaves_the_updated_contents() {
+    let dir = std::env::temp_dir().join(format!("io-update-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(8, 10_000), verify: true, ..Options::default() };
+    let result = update::run(&dir, &UpdateMethod::ALL, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.methods.iter().map(|method| method.method).collect::<Vec<_>>(), UpdateMethod::ALL);
+
+    // A file longer than its update loses the old tail.
+    let paths = bench::file_paths(&dir, 8);
+    for path in &paths {
+        fs::write(path, vec![b'x'; 20_000]).unwrap();
+    }
+    for method in UpdateMethod::ALL {
+        bench::update_files(&paths, &options, method).unwrap();
+        assert!(paths.iter().all(|path| fs::read(path).unwrap() == options.workload.update_content()), "{}", method);
+        assert_eq!(method.name().parse::<UpdateMethod>().unwrap(), method);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn mmap_flush_policies_count_writeback() {
+    let dir = std::env::temp_dir().join(format!("io-update-flush-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for flush in ["none", "async", "sync", "end"] {
+        let options = Options { workload: Workload::new(4, 64 << 10), mmap_flush: flush.parse().unwrap(), ..Options::default() };
+        let result = update::run(&dir, &[UpdateMethod::Mmap], &options).unwrap();
+        assert!(result.failures.is_empty(), "{:?}", result.failures);
+        assert!(result.methods[0].writeback <= result.methods[0].update, "{}", flush);
+    }
+    assert!("eventually".parse::<MmapFlush>().is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
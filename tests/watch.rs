@@ -0,0 +1,33 @@
+#![cfg(all(target_os = "linux", feature = "bench"))]
+
+use std::fs;
+use std::time::Duration;
+
+use io::bench::{Options, Workload};
+use io::watch::{self, WatchBackend, WatchConfig};
+
+#[test]
+fn every_updated_file_is_reported() {
+    let dir = std::env::temp_dir().join(format!("io-watch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = Options { workload: Workload::new(200, 1_000), ..Options::default() };
+    let config = WatchConfig { backend: WatchBackend::Inotify, dirs: 20, settle: Duration::from_secs(5) };
+    let result = watch::run(&dir, config, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    // The root, two parents and twenty leaves.
+    assert_eq!(result.watches, 23);
+    assert_eq!(result.delivered, 200);
+    assert_eq!(result.drop_rate(), 0.0);
+    assert_eq!(result.latency.running.count(), 200);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn backends_without_bindings_are_unsupported() {
+    for backend in WatchBackend::ALL {
+        assert_eq!(backend.name().parse::<WatchBackend>().unwrap(), backend);
+    }
+    assert_eq!(WatchBackend::native(), WatchBackend::Inotify);
+    let error = watch::Watcher::new(WatchBackend::FsEvents).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+}
@@ -0,0 +1,52 @@
+//! Checks that the watchdog counts and reports operations over their timeout without
+//! stopping the run when told to skip them, and that an abort cancels only its own run and
+//! gives up on operations still stuck.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use io::cancel::Token;
+use io::watchdog::{OnTimeout, Timeouts, Watchdog};
+
+#[test]
+fn slow_operations_time_out() {
+    let timeouts = Timeouts { op: Some(Duration::from_millis(10)), phase: None, on_timeout: OnTimeout::Skip };
+    let token = Token::new();
+    let watchdog = Watchdog::start(timeouts, token.clone(), || panic!("gave up")).unwrap();
+    let scope: Arc<str> = "test/read".into();
+
+    let fast = watchdog.op(&scope, Path::new("fast"));
+    assert_eq!(fast.finish(), None);
+    let slow = watchdog.op(&scope, Path::new("slow"));
+    thread::sleep(Duration::from_millis(50));
+    assert!(slow.finish().is_some_and(|elapsed| elapsed >= Duration::from_millis(50)));
+
+    assert_eq!(watchdog.timed_out(), 1);
+    assert!(!watchdog.gave_up());
+    assert!(!token.is_cancelled());
+}
+
+#[test]
+fn abort_cancels_the_run_then_gives_up() {
+    let timeouts = Timeouts { op: Some(Duration::from_millis(10)), phase: None, on_timeout: OnTimeout::Abort };
+    let (token, other) = (Token::new(), Token::new());
+    let gave_up = Arc::new(AtomicUsize::new(0));
+    let counter = gave_up.clone();
+    let watchdog = Watchdog::start(timeouts, token.clone(), move || {
+        counter.fetch_add(1, Ordering::Relaxed);
+    })
+    .unwrap();
+    let scope: Arc<str> = "test/read".into();
+
+    let stuck = watchdog.op(&scope, Path::new("stuck"));
+    thread::sleep(Duration::from_millis(200));
+    assert!(token.is_cancelled());
+    assert!(!other.is_cancelled());
+    assert!(watchdog.gave_up());
+    drop(stuck);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(gave_up.load(Ordering::Relaxed), 1, "gives up once");
+}
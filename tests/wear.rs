@@ -0,0 +1,41 @@
+use io::wear::{DEFAULT_ERASE_BLOCK, Flash};
+
+#[test]
+fn writes_cost_whole_erase_blocks() {
+    let flash = Flash { erase_block: 4096 };
+    assert_eq!(flash.programmed(0), 0);
+    assert_eq!(flash.programmed(1), 4096);
+    assert_eq!(flash.programmed(4096), 4096);
+    assert_eq!(flash.programmed(4097), 8192);
+    assert_eq!(flash.amplification(0), 1.0);
+    assert_eq!(flash.amplification(1024), 4.0);
+    assert_eq!(flash.amplification(8192), 1.0);
+    // A zero block is treated as one byte rather than dividing by zero.
+    assert_eq!(Flash { erase_block: 0 }.programmed(10), 10);
+}
+
+#[test]
+fn wear_is_bytes_written_times_amplification() {
+    let flash = Flash::default();
+    assert_eq!(flash.erase_block, DEFAULT_ERASE_BLOCK);
+    let small = flash.estimate(1 << 20, 1024);
+    assert_eq!(small.written, 1 << 20);
+    assert_eq!(small.amplification, DEFAULT_ERASE_BLOCK as f64 / 1024.0);
+    assert_eq!(small.programmed(), (1u64 << 20) as f64 * 4096.0);
+    let aligned = flash.estimate(1 << 30, DEFAULT_ERASE_BLOCK as u64);
+    assert_eq!(aligned.programmed(), (1u64 << 30) as f64);
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn bench_reports_wear_for_the_erase_block_given() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_io"))
+        .args(["bench", "--files", "20", "--size", "1K", "--erase-block", "64K"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("Estimated flash wear (64K erase blocks"), "{}", stdout);
+    // 1 KiB files each start a 64 KiB block.
+    assert!(stdout.contains("64.00x"), "{}", stdout);
+}
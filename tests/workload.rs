@@ -0,0 +1,135 @@
+#![cfg(feature = "bench")]
+
+use std::fs;
+use std::path::PathBuf;
+
+use io::bench::{Options, Phase};
+use io::json;
+use io::population::Store;
+use io::workload::{self, Config, Op, PopulationKind};
+
+fn config(text: &str) -> Result<Config, String> {
+    Config::from_json(&json::parse(text).unwrap())
+}
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("io-workload-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn a_workload_file_gives_populations_and_phases() {
+    let config = config(
+        r#"{
+            "populations": {
+                "small": { "files": 10, "size": 100 },
+                "kept": { "files": 2, "size": 4096, "persist": true },
+                "repo": { "discover": "/srv/repo" }
+            },
+            "phases": [
+                { "name": "make", "op": "create", "population": "small", "strategy": "smart" },
+                { "name": "scan", "op": "stat", "population": "small", "after": ["make"] },
+                { "name": "read-repo", "op": "read", "population": "repo" }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let kinds: Vec<(&str, &PopulationKind)> = config.populations.iter().map(|p| (p.name.as_str(), &p.kind)).collect();
+    assert!(kinds.contains(&("small", &PopulationKind::Generated { files: 10, size: 100, persist: false })));
+    assert!(kinds.contains(&("kept", &PopulationKind::Generated { files: 2, size: 4096, persist: true })));
+    assert!(kinds.contains(&("repo", &PopulationKind::Discovered(PathBuf::from("/srv/repo")))));
+    assert_eq!(config.nodes[0].op, Op::Phase(Phase::Create));
+    assert_eq!(config.nodes[0].strategy.label, workload::strategy("smart").unwrap().label);
+    assert_eq!((config.nodes[1].op, config.nodes[1].after.clone()), (Op::Stat, vec![0]));
+    assert_eq!(config.populations[config.nodes[2].population].name, "repo");
+}
+
+#[test]
+fn strategies_are_found_by_label_or_name() {
+    assert!(workload::strategy("SMART").is_some());
+    assert_eq!(workload::strategy("smart_io").map(|s| s.label), workload::strategy("smart").map(|s| s.label));
+    assert!(workload::strategy("fastest").is_none());
+}
+
+#[test]
+fn mistakes_in_the_file_are_named() {
+    let populations = r#""populations": { "small": { "files": 1, "size": 1 }, "repo": { "discover": "/srv" } }"#;
+    let error = |phases: &str| config(&format!("{{ {}, \"phases\": [{}] }}", populations, phases)).unwrap_err();
+    assert!(error(r#"{ "name": "a", "op": "create", "population": "small", "after": ["b"] }, { "name": "b", "op": "read", "population": "small", "after": ["a"] }"#).contains("cycle"));
+    assert!(error(r#"{ "name": "a", "op": "create", "population": "small" }, { "name": "a", "op": "read", "population": "small" }"#).contains("unique"));
+    assert!(error(r#"{ "name": "a", "op": "shred", "population": "small" }"#).contains("\"op\""));
+    assert!(error(r#"{ "name": "a", "op": "read", "population": "large" }"#).contains("population"));
+    assert!(error(r#"{ "name": "a", "op": "delete", "population": "repo" }"#).contains("would modify"));
+    assert!(error(r#"{ "name": "a", "op": "read", "population": "small", "strategy": "fastest" }"#).contains("unknown strategy"));
+    assert!(error(r#"{ "name": "a", "op": "read", "population": "small", "after": ["z"] }"#).contains("unknown phase z"));
+    assert!(config(r#"{ "populations": { "small": { "files": 1 } }, "phases": [] }"#).unwrap_err().contains("\"size\""));
+    assert!(config(r#"{ "phases": [] }"#).unwrap_err().contains("populations"));
+}
+
+#[test]
+fn phases_run_in_dependency_order() {
+    let root = scratch("run");
+    let store = Store::new(root.join("store"));
+    let config = config(
+        r#"{
+            "populations": { "small": { "files": 20, "size": 100 }, "other": { "files": 5, "size": 10 } },
+            "phases": [
+                { "name": "make", "op": "create", "population": "small" },
+                { "name": "read", "op": "read", "population": "small", "after": ["make"] },
+                { "name": "scan", "op": "stat", "population": "small", "after": ["make"] },
+                { "name": "cleanup", "op": "delete", "population": "small", "after": ["read", "scan"] },
+                { "name": "make-other", "op": "create", "population": "other" }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let report = workload::execute(&config, &root, &store, &Options::default(), None).unwrap();
+    let timings = &report.timings;
+    assert!(timings.iter().all(|timing| timing.failures.is_empty() && !timing.reused));
+    for (node, timing) in config.nodes.iter().zip(timings) {
+        for &dep in &node.after {
+            assert!(timings[dep].start + timings[dep].elapsed <= timing.start, "{} started before {} finished", node.name, config.nodes[dep].name);
+        }
+    }
+    // The critical path ends with cleanup and runs back to make through one of its readers.
+    assert_eq!(report.critical_path.first(), Some(&0));
+    assert_eq!(report.critical_path.last(), Some(&3));
+    assert_eq!(report.critical_path.len(), 3);
+
+    let small = &report.population_dirs[config.populations.iter().position(|p| p.name == "small").unwrap()];
+    assert_eq!(fs::read_dir(small).unwrap().count(), 0);
+    let other = &report.population_dirs[config.populations.iter().position(|p| p.name == "other").unwrap()];
+    assert_eq!(fs::read_dir(other).unwrap().count(), 5);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn persisted_populations_are_created_once() {
+    let root = scratch("persist");
+    let store = Store::new(root.join("store"));
+    let config = config(
+        r#"{
+            "populations": { "kept": { "files": 8, "size": 64, "persist": true } },
+            "phases": [
+                { "name": "make", "op": "create", "population": "kept" },
+                { "name": "read", "op": "read", "population": "kept", "after": ["make"] }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let first = workload::execute(&config, &root.join("run1"), &store, &Options::default(), None).unwrap();
+    assert!(!first.timings[0].reused);
+    assert!(first.population_dirs[0].starts_with(&store.dir));
+    let second = workload::execute(&config, &root.join("run2"), &store, &Options::default(), None).unwrap();
+    assert!(second.timings[0].reused);
+    assert_eq!(second.population_dirs, first.population_dirs);
+
+    // A stored file that changed is caught before anything runs.
+    let file = fs::read_dir(&first.population_dirs[0]).unwrap().next().unwrap().unwrap().path();
+    fs::write(&file, b"changed").unwrap();
+    let e = workload::execute(&config, &root.join("run3"), &store, &Options::default(), None).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,41 @@
+#![cfg(all(target_os = "linux", feature = "libc"))]
+
+use std::fs;
+use std::path::PathBuf;
+
+use io::retry::RetryPolicy;
+use io::xattr;
+
+#[test]
+fn attributes_round_trip_in_bulk() {
+    let dir = std::env::temp_dir().join(format!("io-xattr-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<PathBuf> = (0..20).map(|i| dir.join(format!("file_{}", i))).collect();
+    for path in &paths {
+        fs::write(path, b"data").unwrap();
+    }
+    assert_eq!(xattr::get_xattr(&paths[0], "user.io.test").unwrap(), None);
+
+    // Values past the first buffer are read at their full size.
+    let files: Vec<(PathBuf, Vec<u8>)> = paths.iter().enumerate().map(|(i, path)| (path.clone(), vec![i as u8; 100 * i])).collect();
+    xattr::set_xattr_many(&files, "user.io.test", &RetryPolicy::default()).unwrap();
+    let values = xattr::get_xattr_many(&paths, "user.io.test", &RetryPolicy::default()).unwrap();
+    assert_eq!(values, files.iter().map(|(_, value)| Some(value.clone())).collect::<Vec<_>>());
+    assert!(xattr::list_xattrs(&paths[1]).unwrap().contains(&"user.io.test".to_string()));
+
+    let error = xattr::set_xattr(&dir.join("missing"), "user.io.test", b"x").unwrap_err();
+    assert!(error.to_string().contains("No such file"), "{}", error);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "bench")]
+#[test]
+fn both_stores_read_back_what_they_wrote() {
+    let dir = std::env::temp_dir().join(format!("io-xattr-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let options = io::bench::Options { workload: io::bench::Workload::new(10, 100), ..io::bench::Options::default() };
+    let result = xattr::run(&dir, 300, &options).unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    assert_eq!(result.stores.iter().map(|store| store.store).collect::<Vec<_>>(), xattr::MetaStore::ALL);
+    fs::remove_dir_all(&dir).unwrap();
+}
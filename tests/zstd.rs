@@ -0,0 +1,27 @@
+#![cfg(feature = "zstd")]
+
+use io::bundle::Bundle;
+use io::zstd;
+
+#[test]
+fn round_trips_and_reads_other_writers_frames() {
+    let data: Vec<u8> = (0..1_000_000u32).map(|i| b"zstd compresses this "[(i % 21) as usize] ^ (i % 251 == 0) as u8).collect();
+    let frame = zstd::compress(&data, zstd::DEFAULT_LEVEL).unwrap();
+    assert!(frame.len() < data.len() / 10);
+    assert_eq!(zstd::decompress(&frame).unwrap(), data);
+    assert_eq!(zstd::decompress(&zstd::compress(&[], 1).unwrap()).unwrap(), b"");
+
+    // Bundles are zstd frames of raw blocks.
+    let mut bundle = Bundle::new("run");
+    bundle.add("command.txt", "io bench\n");
+    assert_eq!(zstd::decompress(&bundle.to_bytes().unwrap()).unwrap(), bundle.to_tar().unwrap());
+
+    let mut frames = zstd::compress(b"first ", 1).unwrap();
+    frames.extend_from_slice(&zstd::compress(b"second", 19).unwrap());
+    assert_eq!(zstd::decompress(&frames).unwrap(), b"first second");
+
+    let mut corrupt = frame.clone();
+    corrupt.truncate(frame.len() / 2);
+    assert!(zstd::decompress(&corrupt).is_err());
+    assert_eq!(zstd::decompress(b"not zstd at all").unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}